@@ -0,0 +1,180 @@
+# User facing messages of the command line.
+# Placeholders use the fluent syntax `{ $name }`.
+
+error-label = error
+hint-label = hint
+
+no-source-matching = No source matching '{ $url }' found
+no-supported-source = No supported source found for '{ $url }'
+no-curated-list = No curated list named '{ $name }' found
+popular-not-supported = '{ $source }' does not support popular browse
+no-novels-found = No novels found
+novel-not-found = The novel does not exist
+novel-data-not-found = The novel data could not be found
+extension-file-missing = The wasm extension file '{ $path }' could not be found
+lock-file-unreadable = Failed to read lock file '{ $path }': { $reason }
+bundle-failed = Failed to bundle { $format }: { $reason }
+bundle-split = Split the { $format } output into { $count } parts
+bundle-unchanged = '{ $path }' is up to date
+bundle-parts-unchanged = Kept { $count } unchanged parts
+site-written = Written { $count } novels to '{ $path }'
+template-exported = Wrote the default PDF template to '{ $path }'
+template-exists = '{ $path }' already exists
+template-not-found = No template found at '{ $path }'
+template-set = PDF bundles are now laid out with '{ $path }'
+template-reset = PDF bundles are now laid out with the default template
+template-default = The default template
+calibre-added = Added '{ $path }' to Calibre as book { $id }
+calibre-updated = Updated book { $id } of Calibre with '{ $path }'
+calibre-failed = Could not add '{ $path }' to Calibre: { $reason }
+calibre-set = Bundles are now added to the Calibre library '{ $library }'
+calibre-reset = Bundles are now added to the last library used by Calibre
+calibre-default = The last library used by Calibre
+metadata-mapped = The { $name } metadata is now written to { $field }
+sanitize-enabled = Chapters are now cleaned up with the { $step } step
+sanitize-disabled = Chapters are no longer cleaned up with the { $step } step
+sanitize-watermark-added = Sentences matching '{ $pattern }' are now removed from chapters
+sanitize-watermark-removed = Sentences matching '{ $pattern }' are no longer removed from chapters
+bundle-cancelled = Cancelled { $format }, removed the incomplete { $path }
+profile-saved = Saved the profile { $name }
+profile-removed = Removed the profile { $name }
+profile-not-found = There is no profile named { $name }
+host-suspended = '{ $host }' is suspended until { $until } after repeated request failures
+no-notes = No notes for '{ $title }'
+novel-info = { $status }, { $downloaded } of { $total } chapters downloaded, updated { $date }
+provenance-field = { $field }: { $source } v{ $version }, fetched { $date }
+provenance-chapters = { $count } chapters: { $source } v{ $version }, last fetched { $date }
+provenance-chapters-unknown = { $count } chapters were saved before their provenance was recorded
+provenance-unknown = No provenance was recorded, the novel was saved before it was tracked
+no-rights = No license or attribution for '{ $title }'
+no-title-rules = '{ $title }' uses the title options given when bundling
+novel-edited = Saved the edits of '{ $title }'
+no-overrides = No field was edited, the novel uses the metadata of the source
+no-chapters-in-dates = None of the chapters were updated within the given dates
+no-chapters-selected = None of the chapters are selected
+status-novels = Novels in library: { $count }
+status-chapters = Chapters downloaded: { $downloaded } of { $total }
+word-count = Words: { $words }, about { $time } of reading
+migration-none = The library is up to date (version { $version })
+migration-pending = Version { $version }: { $description } (pending)
+migration-applied = Version { $version }: { $description }
+compression-applied = Chapters are now stored with { $method } compression ({ $count } rewritten)
+backup-created = Backed up { $count } novels to '{ $path }'
+backup-restored = Restored { $count } files and added { $novels } novels
+backup-skipped = Kept { $count } existing files of the library
+executor-set = Extensions now run with the { $executor } executor
+mature-enabled = Mature content of { $source } will be downloaded
+mature-disabled = Mature content of { $source } is hidden again
+library-added = Added the library '{ $name }'
+library-exists = A library named '{ $name }' already exists
+library-not-found = No library is named '{ $name }'
+library-default = The default library '{ $name }' cannot be removed
+library-removed = Removed the library '{ $name }', its files are still in '{ $path }'
+library-switched = Now using the library '{ $name }'
+storage-same-library = The other library must be in a different directory
+storage-reindexed = Summarized { $count } novels into the library index
+storage-novel-copied = [{ $number }/{ $total }] Copied '{ $title }' with { $chapters } chapters
+storage-migrated = Copied { $novels } novels and { $chapters } chapters, { $skipped } novels were copied before
+novel-imported = Imported '{ $title }' with { $chapters } chapters in { $volumes } volumes
+import-failed = Failed to import '{ $path }': { $reason }
+imports-failed = { $count } files could not be imported
+verify-intact = Checked { $chapters } chapters, { $checksums } against their checksum, nothing is corrupted
+verify-corrupted = Found { $count } corrupted entries
+cleanup-orphaned-chapter = Orphaned chapter file: { $path }
+cleanup-missing-chapter = Missing chapter file: { $url } in '{ $dir }'
+cleanup-dangling-cover = Dangling cover: { $path }
+cleanup-clean = The library has no orphaned or missing files
+cleanup-found = Found { $orphaned } orphaned chapters, { $missing } missing chapters and { $covers } dangling covers, run with --fix to repair them
+cleanup-fixed = Removed { $orphaned } orphaned chapters and { $covers } dangling covers, { $missing } missing chapters will be downloaded again
+storage-deduplicated = { $shared } of { $chapters } chapters share their content with another, saving { $saved } bytes ({ $deduplicated } linked now)
+storage-dumped = Wrote { $count } novels to { $path }
+storage-loaded = Added { $novels } novels with { $chapters } chapters, { $skipped } already in the library were skipped
+maintenance-done = { $task }: done
+maintenance-cache-pruned = { $task }: removed { $removed } cached chapters
+maintenance-compacted = { $task }: purged { $purged } novels from the trash, recompressed { $recompressed } chapters, { $saved } bytes saved by shared chapters
+maintenance-verified = { $task }: checked { $chapters } chapters, { $corrupted } corrupted entries
+maintenance-index-rebuilt = { $task }: summarized { $novels } novels
+maintenance-backed-up = { $task }: backed up { $novels } novels to '{ $path }', removed { $removed } older backups
+maintenance-failed = { $task }: failed: { $reason }
+maintenance-tasks-failed = { $count } maintenance tasks failed
+remote-not-configured = No bucket or share is configured, set one with `quelle storage remote` or `quelle storage webdav`
+remote-key-missing = The { $name } environment variable is not set
+remote-pushed = Uploaded { $uploaded } files, { $unchanged } were unchanged
+remote-pulled = Downloaded { $downloaded } files, { $unchanged } were unchanged
+url-redirected = { $url } moved to { $target }
+url-updated = Saved the new url { $url }
+url-login-required = { $url } requires an account or a subscription
+url-dead = { $url } returned { $status }
+url-unreachable = { $url } could not be reached: { $reason }
+urls-checked = Checked { $count } novels, { $broken } need attention
+collection-exists = The collection '{ $name }' already exists
+collection-not-found = The collection '{ $name }' does not exist or does not contain the novel
+collection-added = Added { $url } to '{ $name }'
+novel-trashed = Moved '{ $title }' to the trash, restore it with `quelle trash restore { $url }`
+novels-merged = Merged '{ $title }' into the novel with { $chapters } chapters and { $assets } files, the duplicate was moved to the trash
+novel-restored = Restored '{ $title }' from the trash
+trash-not-found = No novel from { $url } is in the trash
+trash-purged = Permanently deleted { $count } novels from the trash
+mirror-added = Recorded { $mirror } as a mirror of { $url }
+mirror-removed = Forgot the mirror { $mirror }
+mirror-not-found = { $mirror } is not a mirror of a saved novel
+mirror-switched = Now downloading from { $mirror }, kept { $count } downloaded chapters
+chapter-not-found = The novel has no chapter { $number }
+no-versions = No previous content was kept for '{ $title }'
+version-not-found = The chapter has no version { $number }
+extension-not-found = No extension with the id '{ $id }' is installed
+fixtures-missing = The extension '{ $id }' does not bundle any fixtures
+fixture-passed = PASS { $url }
+fixture-failed = FAIL { $url }: { $reason }
+extension-unchanged = No changes between the two versions
+extension-test-failed = { $count } checks of '{ $id }' failed
+cover-updated = Novel cover updated, previous cover kept at '{ $path }'.
+chapter-downloaded = Downloaded chapter { $number } of { $total }: { $title }
+chapter-skipped = Skipped chapter { $number } of { $total }, already downloaded: { $title }
+chapter-bundled = Bundled chapter { $number } of { $total }: { $title }
+chapter-failed = Failed to download '{ $title }': { $reason }
+chapter-refused = The source refused '{ $title }', it may be locked or paywalled
+chapter-pending = { $number }. { $title }: not downloaded yet
+chapter-queued = { $number }. { $title }: queued by an interrupted download
+chapter-status-failed = { $number }. { $title }: failed { $attempts } times, { $error }
+chapter-status-skipped = { $number }. { $title }: refused { $attempts } times, { $reason }
+url-not-novel = '{ $url }' does not look like a novel page of { $source }, trying to correct it
+url-corrected = Using the novel url '{ $to }' instead of '{ $from }'
+download-resuming = Resuming '{ $title }' with { $count } of { $total } chapters already downloaded
+chapters-failed = { $count } chapters could not be downloaded after retrying
+offline-mode = This command needs network access, but quelle is running offline
+offline-detected = '{ $host }' could not be reached, you appear to be offline
+offline-saved-copy = A saved copy of '{ $title }' is available and can still be bundled
+credential-saved = Saved the credential profile '{ $profile }' for { $source }
+credential-not-found = No credential profile '{ $profile }' stored for { $source }
+passphrase-missing = Set the passphrase to encrypt the library with in QUELLE_PASSPHRASE
+library-encrypted = Encrypted { $count } novels, keep the passphrase safe as the library cannot be read without it
+
+hint-unknown = Run the command again with -vvv for details and report the issue if it persists.
+hint-store-io = Check that the data directory exists and is writable.
+hint-store-corrupt = The saved data could not be read. Restore it from a backup or remove the file to start over.
+hint-schema-unsupported = The library was written by a newer release. Update quelle to open it.
+hint-backup-invalid = The file is not a quelle backup or was written by a newer release.
+hint-backup-conflict = Restore with --on-conflict skip to keep the existing files or overwrite to replace them.
+hint-novel-exists = Remove the novel saved from the url with `quelle remove <url>` before restoring it.
+hint-library-locked = Another quelle process is using the library. Wait for it to finish, or set QUELLE_WAIT_FOR_LOCK to wait for it automatically.
+hint-epub-invalid = The file is not an EPUB or its package document could not be read.
+hint-remote-failed = Check the endpoint and bucket set with `quelle storage remote` and the keys in AWS_ACCESS_KEY_ID and AWS_SECRET_ACCESS_KEY.
+hint-novel-not-found = Download the novel first with `quelle download <url>`.
+hint-lock-unreadable = Generate the lock file with `quelle lock` or pass its location with --lock-file.
+hint-source-not-supported = Run `quelle extensions` to list the supported sources.
+hint-extension-missing = Build the extensions and regenerate the lock file with `quelle lock`.
+hint-extension-failed = The extension crashed. Update the extension or retry with --isolate.
+hint-extension-parse-failed = The website layout may have changed. Update the extension to a newer version.
+hint-extension-unsupported = The source does not support this operation.
+hint-fixtures-missing = Record fixtures for the extension and regenerate the lock file with `quelle lock`.
+hint-extension-test-failed = The installed extension may be corrupted or incompatible. Reinstall or update it.
+hint-budget-exceeded = The extension made too many requests or took too long. It may be stuck paginating, update the extension or report the issue.
+hint-request-failed = Check your connection and that the website is reachable, then try again.
+hint-host-suspended = The website failed repeatedly. Wait until the suspension ends before retrying.
+hint-chapters-failed = Run the download again later to fetch the remaining chapters.
+hint-offline = Saved novels can still be bundled. Connect to the internet and run the command without --offline to fetch updates.
+hint-credential-missing = Add the profile with `quelle credentials add <url> <profile> --cookie NAME=VALUE`.
+hint-passphrase-required = Set the passphrase of the library in the QUELLE_PASSPHRASE environment variable.
+hint-passphrase-wrong = Check the passphrase in the QUELLE_PASSPHRASE environment variable.
+hint-bundle-failed = Check that the output path is writable and the downloaded chapters are intact.
@@ -0,0 +1,180 @@
+# Mensajes de la línea de comandos para el usuario.
+# Los marcadores usan la sintaxis de fluent `{ $nombre }`.
+
+error-label = error
+hint-label = sugerencia
+
+no-source-matching = No se encontró ninguna fuente para '{ $url }'
+no-supported-source = No se encontró una fuente compatible para '{ $url }'
+no-curated-list = No existe una lista seleccionada llamada '{ $name }'
+popular-not-supported = '{ $source }' no permite explorar las novelas populares
+no-novels-found = No se encontraron novelas
+novel-not-found = La novela no existe
+novel-data-not-found = No se encontraron los datos de la novela
+extension-file-missing = No se encontró el archivo de extensión wasm '{ $path }'
+lock-file-unreadable = No se pudo leer el archivo de bloqueo '{ $path }': { $reason }
+bundle-failed = No se pudo generar { $format }: { $reason }
+bundle-split = La salida { $format } se dividió en { $count } partes
+bundle-unchanged = '{ $path }' está actualizado
+bundle-parts-unchanged = Se conservaron { $count } partes sin cambios
+site-written = Se escribieron { $count } novelas en '{ $path }'
+template-exported = Se escribió la plantilla PDF predeterminada en '{ $path }'
+template-exists = '{ $path }' ya existe
+template-not-found = No se encontró ninguna plantilla en '{ $path }'
+template-set = Los PDF ahora se componen con '{ $path }'
+template-reset = Los PDF ahora se componen con la plantilla predeterminada
+template-default = La plantilla predeterminada
+calibre-added = Se añadió '{ $path }' a Calibre como el libro { $id }
+calibre-updated = Se actualizó el libro { $id } de Calibre con '{ $path }'
+calibre-failed = No se pudo añadir '{ $path }' a Calibre: { $reason }
+calibre-set = Los libros ahora se añaden a la biblioteca de Calibre '{ $library }'
+calibre-reset = Los libros ahora se añaden a la última biblioteca usada por Calibre
+calibre-default = La última biblioteca usada por Calibre
+metadata-mapped = Los metadatos { $name } ahora se escriben en { $field }
+sanitize-enabled = Los capítulos ahora se limpian con el paso { $step }
+sanitize-disabled = Los capítulos ya no se limpian con el paso { $step }
+sanitize-watermark-added = Las frases que coinciden con '{ $pattern }' ahora se eliminan de los capítulos
+sanitize-watermark-removed = Las frases que coinciden con '{ $pattern }' ya no se eliminan de los capítulos
+bundle-cancelled = Se canceló { $format }, se eliminó el archivo incompleto { $path }
+profile-saved = Se guardó el perfil { $name }
+profile-removed = Se eliminó el perfil { $name }
+profile-not-found = No hay ningún perfil llamado { $name }
+host-suspended = '{ $host }' está suspendido hasta { $until } tras fallos repetidos en las solicitudes
+no-notes = No hay notas para '{ $title }'
+novel-info = { $status }, { $downloaded } de { $total } capítulos descargados, actualizada el { $date }
+provenance-field = { $field }: { $source } v{ $version }, obtenido el { $date }
+provenance-chapters = { $count } capítulos: { $source } v{ $version }, última descarga el { $date }
+provenance-chapters-unknown = { $count } capítulos se guardaron antes de registrar su procedencia
+provenance-unknown = No se registró la procedencia, la novela se guardó antes de registrarla
+no-rights = No hay licencia ni atribución para '{ $title }'
+no-title-rules = '{ $title }' usa las opciones de títulos indicadas al empaquetar
+novel-edited = Se guardaron los cambios de '{ $title }'
+no-overrides = No se editó ningún campo, la novela usa los metadatos de la fuente
+no-chapters-in-dates = Ninguno de los capítulos se actualizó entre las fechas indicadas
+no-chapters-selected = Ninguno de los capítulos está seleccionado
+status-novels = Novelas en la biblioteca: { $count }
+status-chapters = Capítulos descargados: { $downloaded } de { $total }
+word-count = Palabras: { $words }, unas { $time } de lectura
+migration-none = La biblioteca está actualizada (versión { $version })
+migration-pending = Versión { $version }: { $description } (pendiente)
+migration-applied = Versión { $version }: { $description }
+compression-applied = Los capítulos ahora se guardan con compresión { $method } ({ $count } reescritos)
+backup-created = Se respaldaron { $count } novelas en '{ $path }'
+backup-restored = Se restauraron { $count } archivos y se añadieron { $novels } novelas
+backup-skipped = Se conservaron { $count } archivos existentes de la biblioteca
+executor-set = Las extensiones ahora se ejecutan con el ejecutor { $executor }
+mature-enabled = Se descargará el contenido para adultos de { $source }
+mature-disabled = El contenido para adultos de { $source } vuelve a estar oculto
+library-added = Se añadió la biblioteca '{ $name }'
+library-exists = Ya existe una biblioteca llamada '{ $name }'
+library-not-found = No hay ninguna biblioteca llamada '{ $name }'
+library-default = La biblioteca predeterminada '{ $name }' no se puede eliminar
+library-removed = Se eliminó la biblioteca '{ $name }', sus archivos siguen en '{ $path }'
+library-switched = Ahora se usa la biblioteca '{ $name }'
+storage-same-library = La otra biblioteca debe estar en un directorio diferente
+storage-reindexed = Se resumieron { $count } novelas en el índice de la biblioteca
+storage-novel-copied = [{ $number }/{ $total }] Se copió '{ $title }' con { $chapters } capítulos
+storage-migrated = Se copiaron { $novels } novelas y { $chapters } capítulos, { $skipped } novelas se copiaron antes
+novel-imported = Se importó '{ $title }' con { $chapters } capítulos en { $volumes } volúmenes
+import-failed = No se pudo importar '{ $path }': { $reason }
+imports-failed = { $count } archivos no se pudieron importar
+verify-intact = Se comprobaron { $chapters } capítulos, { $checksums } con su suma de verificación, nada está dañado
+verify-corrupted = Se encontraron { $count } entradas dañadas
+cleanup-orphaned-chapter = Archivo de capítulo huérfano: { $path }
+cleanup-missing-chapter = Falta el archivo del capítulo: { $url } en '{ $dir }'
+cleanup-dangling-cover = Portada colgante: { $path }
+cleanup-clean = La biblioteca no tiene archivos huérfanos ni faltantes
+cleanup-found = Se encontraron { $orphaned } capítulos huérfanos, { $missing } capítulos faltantes y { $covers } portadas colgantes, ejecute con --fix para repararlos
+cleanup-fixed = Se eliminaron { $orphaned } capítulos huérfanos y { $covers } portadas colgantes, { $missing } capítulos faltantes se descargarán de nuevo
+storage-deduplicated = { $shared } de { $chapters } capítulos comparten su contenido con otro, ahorrando { $saved } bytes ({ $deduplicated } enlazados ahora)
+storage-dumped = Se escribieron { $count } novelas en { $path }
+storage-loaded = Se agregaron { $novels } novelas con { $chapters } capítulos, se omitieron { $skipped } que ya estaban en la biblioteca
+maintenance-done = { $task }: hecho
+maintenance-cache-pruned = { $task }: se eliminaron { $removed } capítulos en caché
+maintenance-compacted = { $task }: se purgaron { $purged } novelas de la papelera, se recomprimieron { $recompressed } capítulos, { $saved } bytes ahorrados por capítulos compartidos
+maintenance-verified = { $task }: se comprobaron { $chapters } capítulos, { $corrupted } entradas dañadas
+maintenance-index-rebuilt = { $task }: se resumieron { $novels } novelas
+maintenance-backed-up = { $task }: se respaldaron { $novels } novelas en '{ $path }', se eliminaron { $removed } copias anteriores
+maintenance-failed = { $task }: falló: { $reason }
+maintenance-tasks-failed = { $count } tareas de mantenimiento fallaron
+remote-not-configured = No hay ningún bucket ni recurso compartido configurado, configure uno con `quelle storage remote` o `quelle storage webdav`
+remote-key-missing = La variable de entorno { $name } no está definida
+remote-pushed = Se subieron { $uploaded } archivos, { $unchanged } no cambiaron
+remote-pulled = Se descargaron { $downloaded } archivos, { $unchanged } no cambiaron
+url-redirected = { $url } se movió a { $target }
+url-updated = Se guardó la nueva url { $url }
+url-login-required = { $url } requiere una cuenta o una suscripción
+url-dead = { $url } devolvió { $status }
+url-unreachable = No se pudo acceder a { $url }: { $reason }
+urls-checked = Se comprobaron { $count } novelas, { $broken } requieren atención
+collection-exists = La colección '{ $name }' ya existe
+collection-not-found = La colección '{ $name }' no existe o no contiene la novela
+collection-added = Se agregó { $url } a '{ $name }'
+novel-trashed = Se movió '{ $title }' a la papelera, restáurela con `quelle trash restore { $url }`
+novels-merged = Se combinó '{ $title }' con la novela con { $chapters } capítulos y { $assets } archivos, el duplicado se movió a la papelera
+novel-restored = Se restauró '{ $title }' desde la papelera
+trash-not-found = Ninguna novela de { $url } está en la papelera
+trash-purged = Se eliminaron definitivamente { $count } novelas de la papelera
+mirror-added = Se registró { $mirror } como espejo de { $url }
+mirror-removed = Se olvidó el espejo { $mirror }
+mirror-not-found = { $mirror } no es un espejo de una novela guardada
+mirror-switched = Ahora se descarga desde { $mirror }, se conservaron { $count } capítulos descargados
+chapter-not-found = La novela no tiene el capítulo { $number }
+no-versions = No se guardó contenido anterior de '{ $title }'
+version-not-found = El capítulo no tiene la versión { $number }
+extension-not-found = No hay ninguna extensión instalada con el id '{ $id }'
+fixtures-missing = La extensión '{ $id }' no incluye datos de prueba
+fixture-passed = CORRECTO { $url }
+fixture-failed = FALLO { $url }: { $reason }
+extension-unchanged = No hay cambios entre las dos versiones
+extension-test-failed = Fallaron { $count } comprobaciones de '{ $id }'
+cover-updated = Portada de la novela actualizada, la portada anterior se guardó en '{ $path }'.
+chapter-downloaded = Capítulo { $number } de { $total } descargado: { $title }
+chapter-skipped = Capítulo { $number } de { $total } omitido, ya descargado: { $title }
+chapter-bundled = Capítulo { $number } de { $total } empaquetado: { $title }
+chapter-failed = No se pudo descargar '{ $title }': { $reason }
+chapter-refused = La fuente rechazó '{ $title }', puede estar bloqueado o ser de pago
+chapter-pending = { $number }. { $title }: aún no se ha descargado
+chapter-queued = { $number }. { $title }: en cola de una descarga interrumpida
+chapter-status-failed = { $number }. { $title }: falló { $attempts } veces, { $error }
+chapter-status-skipped = { $number }. { $title }: rechazado { $attempts } veces, { $reason }
+url-not-novel = '{ $url }' no parece una página de novela de { $source }, intentando corregirla
+url-corrected = Usando la url de la novela '{ $to }' en lugar de '{ $from }'
+download-resuming = Reanudando '{ $title }' con { $count } de { $total } capítulos ya descargados
+chapters-failed = No se pudieron descargar { $count } capítulos tras reintentarlo
+offline-mode = Este comando necesita acceso a la red, pero quelle se está ejecutando sin conexión
+offline-detected = No se pudo conectar con '{ $host }', parece que no hay conexión
+offline-saved-copy = Hay una copia guardada de '{ $title }' que todavía se puede empaquetar
+credential-saved = Se guardó el perfil de credenciales '{ $profile }' para { $source }
+credential-not-found = No hay ningún perfil de credenciales '{ $profile }' guardado para { $source }
+passphrase-missing = Indique en QUELLE_PASSPHRASE la contraseña con la que cifrar la biblioteca
+library-encrypted = Se cifraron { $count } novelas, guarde la contraseña ya que la biblioteca no se puede leer sin ella
+
+hint-unknown = Vuelva a ejecutar el comando con -vvv para ver más detalles e informe del problema si persiste.
+hint-store-io = Compruebe que el directorio de datos existe y tiene permisos de escritura.
+hint-store-corrupt = No se pudieron leer los datos guardados. Restáurelos desde una copia de seguridad o elimine el archivo para empezar de nuevo.
+hint-schema-unsupported = La biblioteca fue escrita por una versión más reciente. Actualice quelle para abrirla.
+hint-backup-invalid = El archivo no es una copia de seguridad de quelle o fue escrito por una versión más reciente.
+hint-backup-conflict = Restaure con --on-conflict skip para conservar los archivos existentes u overwrite para reemplazarlos.
+hint-novel-exists = Elimine la novela guardada desde la url con `quelle remove <url>` antes de restaurarla.
+hint-library-locked = Otro proceso de quelle está usando la biblioteca. Espere a que termine o defina QUELLE_WAIT_FOR_LOCK para esperarlo automáticamente.
+hint-epub-invalid = El archivo no es un EPUB o no se pudo leer su documento de paquete.
+hint-remote-failed = Compruebe el endpoint y el bucket configurados con `quelle storage remote` y las claves en AWS_ACCESS_KEY_ID y AWS_SECRET_ACCESS_KEY.
+hint-novel-not-found = Descargue primero la novela con `quelle download <url>`.
+hint-lock-unreadable = Genere el archivo de bloqueo con `quelle lock` o indique su ubicación con --lock-file.
+hint-source-not-supported = Ejecute `quelle extensions` para ver las fuentes compatibles.
+hint-extension-missing = Compile las extensiones y regenere el archivo de bloqueo con `quelle lock`.
+hint-extension-failed = La extensión falló. Actualice la extensión o vuelva a intentarlo con --isolate.
+hint-extension-parse-failed = Es posible que el sitio web haya cambiado. Actualice la extensión a una versión más reciente.
+hint-extension-unsupported = La fuente no admite esta operación.
+hint-fixtures-missing = Grabe datos de prueba para la extensión y regenere el archivo de bloqueo con `quelle lock`.
+hint-extension-test-failed = Es posible que la extensión instalada esté dañada o sea incompatible. Reinstálela o actualícela.
+hint-budget-exceeded = La extensión hizo demasiadas solicitudes o tardó demasiado. Puede estar atascada paginando, actualice la extensión o informe del problema.
+hint-request-failed = Compruebe su conexión y que el sitio web esté disponible, y vuelva a intentarlo.
+hint-host-suspended = El sitio web falló repetidamente. Espere a que termine la suspensión antes de volver a intentarlo.
+hint-chapters-failed = Vuelva a ejecutar la descarga más tarde para obtener los capítulos restantes.
+hint-offline = Las novelas guardadas todavía se pueden empaquetar. Conéctese a internet y ejecute el comando sin --offline para obtener actualizaciones.
+hint-credential-missing = Añada el perfil con `quelle credentials add <url> <perfil> --cookie NOMBRE=VALOR`.
+hint-passphrase-required = Indique la contraseña de la biblioteca en la variable de entorno QUELLE_PASSPHRASE.
+hint-passphrase-wrong = Compruebe la contraseña de la variable de entorno QUELLE_PASSPHRASE.
+hint-bundle-failed = Compruebe que la ruta de salida tiene permisos de escritura y que los capítulos descargados están intactos.
@@ -1,5 +1,9 @@
 mod cover_action;
 mod download_range;
+mod novel_sort;
+mod output_format;
 
 pub use cover_action::CoverAction;
 pub use download_range::DownloadRange;
+pub use novel_sort::NovelSort;
+pub use output_format::OutputFormat;
@@ -0,0 +1,32 @@
+use std::str::FromStr;
+
+/// Defines the order saved novels are listed in
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum NovelSort {
+    /// By the url of the novel
+    #[default]
+    Url,
+
+    /// By title, ignoring case
+    Title,
+
+    /// The most recently updated first
+    Updated,
+
+    /// The most chapters left to download first
+    Pending,
+}
+
+impl FromStr for NovelSort {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "url" => Ok(NovelSort::Url),
+            "title" => Ok(NovelSort::Title),
+            "updated" => Ok(NovelSort::Updated),
+            "pending" => Ok(NovelSort::Pending),
+            _ => Err("unable to parse unknown sort order"),
+        }
+    }
+}
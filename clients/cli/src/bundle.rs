@@ -1,22 +1,132 @@
-use std::{fs::File, io::BufWriter, path::PathBuf};
+use std::{collections::HashMap, path::PathBuf};
 
-use quelle_bundle::PersistBundle;
+use log::{info, warn};
+use quelle_bundle::{image_sources, Bundle, CachedBundle, PersistBundle};
+use quelle_common::TitleRules;
 use quelle_core::prelude::*;
-use quelle_persist::SavedNovel;
+use quelle_engine::images::ImageProxy;
+use quelle_persist::{AssetStore, Cipher, PersistNovel, SavedNovel};
+use sha2::{Digest, Sha256};
 
-pub fn compile_epub(
+/// Create a bundle from the saved novel that can be shared between formats
+///
+/// The title rules of the novel take precedence over the given ones.
+pub fn persist_bundle(
     meta: Option<Meta>,
     data: SavedNovel,
     base_path: PathBuf,
-    out: &mut BufWriter<File>,
-) -> Result<(), Box<dyn std::error::Error>> {
+    include_notes: bool,
+    title_rules: TitleRules,
+    cipher: Option<Cipher>,
+    assets: Option<AssetStore>,
+) -> CachedBundle<PersistBundle> {
+    let fetched_at = data.updated_at;
+    let data = data.merged();
     let bundle = PersistBundle {
         meta,
         novel: data.novel,
         cover: data.cover.map(Into::into),
         base_path,
         chapter_content: data.downloaded,
+        notes: data.notes.filter(|_| include_notes),
+        rights: data.rights,
+        title_rules: data.title_rules.unwrap_or(title_rules),
+        cipher,
+        assets,
+        fetched_at: Some(fetched_at),
     };
 
-    quelle_bundle::epub::bundle_epub(bundle, out)
+    CachedBundle::new(bundle)
+}
+
+/// Download the images of the downloaded chapters that are not stored yet into
+/// the assets of the novel, returning the number of images stored
+///
+/// Images that fail to download are left out of the bundle.
+pub async fn store_images(
+    novel: &PersistNovel<'_>,
+    data: &SavedNovel,
+    cache_dir: PathBuf,
+) -> anyhow::Result<usize> {
+    let mut assets = novel.read_assets()?;
+    let proxy = ImageProxy::new(cache_dir);
+
+    let mut stored = 0;
+    for path in data.downloaded.values() {
+        let content = novel.read_chapter(path)?;
+        for source in image_sources(&content) {
+            if assets.get_asset(&source)?.is_some() {
+                continue;
+            }
+
+            match proxy.fetch(&source).await {
+                Ok(image) => {
+                    assets.store_asset(&source, &image.content_type, &image.bytes)?;
+                    stored += 1;
+                }
+                Err(e) => warn!("failed to download the image '{source}': {e}"),
+            }
+        }
+    }
+
+    assets.save()?;
+    info!("Stored {stored} chapter images.");
+    Ok(stored)
+}
+
+/// Identifies what the bundle would contain, so that it is only written again
+/// when its chapters, metadata or settings change
+///
+/// Chapters are identified by the checksums of their downloaded content. The
+/// list of parts is left out so that earlier parts of a split novel are kept
+/// when chapters are added to the last one.
+pub fn export_fingerprint<B: Bundle>(
+    bundle: &B,
+    hashes: &HashMap<String, String>,
+    settings: &str,
+) -> String {
+    let novel = bundle.novel();
+    let mut hasher = Sha256::new();
+    let mut field = |value: &str| {
+        hasher.update(value.as_bytes());
+        hasher.update([0]);
+    };
+
+    field(settings);
+    field(&novel.title);
+    field(&novel.authors.join(","));
+    field(&novel.description.join("\n"));
+    field(novel.cover.as_deref().unwrap_or_default());
+    field(bundle.notes().unwrap_or_default());
+    field(bundle.rights().unwrap_or_default());
+    if let Some(part) = bundle.part() {
+        field(&part.number().to_string());
+    }
+
+    for (position, chapter) in bundle.chapters() {
+        let title = bundle
+            .chapter_title(chapter, position)
+            .unwrap_or_else(|| chapter.title.clone());
+        field(&position.to_string());
+        field(&chapter.url);
+        field(&title);
+        field(
+            hashes
+                .get(&chapter.url)
+                .map(String::as_str)
+                .unwrap_or_default(),
+        );
+    }
+
+    format!("{:x}", hasher.finalize())
+}
+
+/// Tells the novel, and the part of a split novel, apart from the other books
+/// of the Calibre library
+pub fn calibre_identifier<B: Bundle>(bundle: &B) -> String {
+    let hash = format!("{:x}", Sha256::digest(bundle.novel().url.as_bytes()));
+    match bundle.part() {
+        Some(part) => format!("{}-{}", &hash[..16], part.number()),
+        None => hash[..16].to_string(),
+    }
 }
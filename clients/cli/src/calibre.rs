@@ -0,0 +1,50 @@
+use clap::Subcommand;
+
+use crate::{open_persist, open_persist_shared, t};
+
+#[derive(Subcommand)]
+pub enum CalibreAction {
+    /// Add bundles to the library at the path, or the content server at the url
+    /// (ex: http://localhost:8080/#library) with the password in
+    /// QUELLE_CALIBRE_PASSWORD
+    Use {
+        library: String,
+
+        /// The username of the content server
+        #[arg(long)]
+        username: Option<String>,
+    },
+    /// Go back to the last library used by Calibre
+    Reset,
+    /// Show the library bundles are added to
+    Show,
+}
+
+pub fn handle(action: CalibreAction) -> anyhow::Result<()> {
+    match action {
+        CalibreAction::Use { library, username } => {
+            let persist = open_persist()?;
+            let mut config = persist.read_config()?;
+            config.calibre.library = Some(library.clone());
+            config.calibre.username = username;
+            persist.save_config(&config)?;
+            println!("{}", t!("calibre-set", library));
+        }
+        CalibreAction::Reset => {
+            let persist = open_persist()?;
+            let mut config = persist.read_config()?;
+            config.calibre = Default::default();
+            persist.save_config(&config)?;
+            println!("{}", t!("calibre-reset"));
+        }
+        CalibreAction::Show => {
+            let persist = open_persist_shared()?;
+            match persist.read_config()?.calibre.library {
+                Some(library) => println!("{library}"),
+                None => println!("{}", t!("calibre-default")),
+            }
+        }
+    }
+
+    Ok(())
+}
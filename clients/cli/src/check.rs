@@ -0,0 +1,124 @@
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+
+use reqwest::{header::LOCATION, redirect::Policy, Client, Method, StatusCode};
+use url::Url;
+
+/// Words in the path of a redirect target that show the page requires an account
+const LOGIN_PATHS: [&str; 4] = ["login", "signin", "sign-in", "account"];
+
+/// What was found at the url of a novel
+#[derive(Debug)]
+pub enum UrlStatus {
+    Ok,
+    /// The novel moved to another url
+    Redirected(Url),
+    /// The page requires an account or a subscription
+    LoginRequired,
+    /// The page no longer exists or the website returned an error
+    Dead(StatusCode),
+    /// The website could not be reached
+    Unreachable(String),
+}
+
+/// Checks the urls of saved novels without fetching the novels with an extension
+///
+/// Requests to the same host wait for the delay since the previous request.
+pub struct UrlChecker {
+    client: Client,
+    delay: Duration,
+    last_request: HashMap<String, Instant>,
+}
+
+impl UrlChecker {
+    pub fn new(delay: Duration) -> anyhow::Result<Self> {
+        let client = Client::builder()
+            .user_agent(
+                "Mozilla/5.0 (X11; Fedora; Linux x86_64; rv:107.0) Gecko/20100101 Firefox/107.0",
+            )
+            .redirect(Policy::none())
+            .timeout(Duration::from_secs(30))
+            .build()?;
+
+        Ok(Self {
+            client,
+            delay,
+            last_request: HashMap::new(),
+        })
+    }
+
+    pub async fn check(&mut self, url: &Url) -> UrlStatus {
+        // Some websites do not answer HEAD requests, the page is requested instead
+        let status = match self.request(Method::HEAD, url).await {
+            Ok((status, _))
+                if status == StatusCode::METHOD_NOT_ALLOWED
+                    || status == StatusCode::NOT_IMPLEMENTED =>
+            {
+                self.request(Method::GET, url).await
+            }
+            result => result,
+        };
+
+        match status {
+            Ok((status, location)) if status.is_redirection() => {
+                match location.and_then(|location| url.join(&location).ok()) {
+                    Some(target) if is_login(&target) => UrlStatus::LoginRequired,
+                    Some(target) if same_page(url, &target) => UrlStatus::Ok,
+                    Some(target) => UrlStatus::Redirected(target),
+                    None => UrlStatus::Dead(status),
+                }
+            }
+            Ok((status, _)) if status.is_success() => UrlStatus::Ok,
+            Ok((
+                StatusCode::UNAUTHORIZED | StatusCode::PAYMENT_REQUIRED | StatusCode::FORBIDDEN,
+                _,
+            )) => UrlStatus::LoginRequired,
+            Ok((status, _)) => UrlStatus::Dead(status),
+            Err(e) => UrlStatus::Unreachable(e.to_string()),
+        }
+    }
+
+    async fn request(
+        &mut self,
+        method: Method,
+        url: &Url,
+    ) -> reqwest::Result<(StatusCode, Option<String>)> {
+        self.wait(url).await;
+
+        let response = self.client.request(method, url.clone()).send().await?;
+        let location = response
+            .headers()
+            .get(LOCATION)
+            .and_then(|value| value.to_str().ok())
+            .map(String::from);
+
+        Ok((response.status(), location))
+    }
+
+    /// Wait until the delay passed since the previous request to the host
+    async fn wait(&mut self, url: &Url) {
+        let host = url.host_str().unwrap_or_default().to_string();
+        if let Some(last) = self.last_request.get(&host) {
+            let elapsed = last.elapsed();
+            if elapsed < self.delay {
+                tokio::time::sleep(self.delay - elapsed).await;
+            }
+        }
+
+        self.last_request.insert(host, Instant::now());
+    }
+}
+
+fn is_login(url: &Url) -> bool {
+    let path = url.path().to_lowercase();
+    LOGIN_PATHS.iter().any(|word| path.contains(word))
+}
+
+/// Whether the redirect only changed the scheme or a trailing slash
+fn same_page(url: &Url, target: &Url) -> bool {
+    url.host_str() == target.host_str()
+        && url.path().trim_end_matches('/') == target.path().trim_end_matches('/')
+        && url.query() == target.query()
+}
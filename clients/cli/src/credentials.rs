@@ -0,0 +1,105 @@
+use clap::Subcommand;
+use quelle_persist::Credential;
+use url::Url;
+
+use crate::{
+    error::{coded, ErrorCode},
+    open_lock, open_persist, t, GlobalArgs,
+};
+
+#[derive(Subcommand)]
+pub enum CredentialsAction {
+    /// Store a credential profile for the source of the url
+    Add {
+        /// A url of the source website
+        url: Url,
+
+        /// The name of the profile (ex: main, premium)
+        #[arg(default_value = "default")]
+        profile: String,
+
+        /// A cookie to send, as NAME=VALUE
+        #[arg(long = "cookie", value_parser = parse_pair)]
+        cookies: Vec<(String, String)>,
+
+        /// A header to send, as NAME=VALUE
+        #[arg(long = "header", value_parser = parse_pair)]
+        headers: Vec<(String, String)>,
+    },
+
+    /// List the stored profiles of every source
+    List,
+
+    /// Remove a stored credential profile
+    Remove {
+        /// A url of the source website
+        url: Url,
+
+        /// The name of the profile
+        #[arg(default_value = "default")]
+        profile: String,
+    },
+}
+
+pub fn handle(cli: &GlobalArgs, action: CredentialsAction) -> anyhow::Result<()> {
+    let persist = open_persist()?;
+    let mut credentials = persist.read_credentials()?;
+
+    match action {
+        CredentialsAction::Add {
+            url,
+            profile,
+            cookies,
+            headers,
+        } => {
+            let lock = open_lock(&cli.lock_file)?;
+            let (source, _) = lock.find(url.as_str()).ok_or_else(|| {
+                coded(
+                    ErrorCode::SourceNotSupported,
+                    t!("no-supported-source", url = url),
+                )
+            })?;
+
+            let credential = Credential {
+                cookies: cookies.into_iter().collect(),
+                headers: headers.into_iter().collect(),
+            };
+
+            credentials.insert(source, &profile, credential);
+            persist.save_credentials(&credentials)?;
+            println!("{}", t!("credential-saved", profile, source));
+        }
+        CredentialsAction::List => {
+            for source in credentials.sources() {
+                println!("{source}: {}", credentials.profiles(source).join(", "));
+            }
+        }
+        CredentialsAction::Remove { url, profile } => {
+            let lock = open_lock(&cli.lock_file)?;
+            let (source, _) = lock.find(url.as_str()).ok_or_else(|| {
+                coded(
+                    ErrorCode::SourceNotSupported,
+                    t!("no-supported-source", url = url),
+                )
+            })?;
+
+            if credentials.remove(source, &profile).is_none() {
+                return Err(coded(
+                    ErrorCode::CredentialMissing,
+                    t!("credential-not-found", profile, source),
+                ));
+            }
+
+            persist.save_credentials(&credentials)?;
+        }
+    }
+
+    Ok(())
+}
+
+fn parse_pair(value: &str) -> Result<(String, String), String> {
+    value
+        .split_once('=')
+        .map(|(name, value)| (name.trim().to_string(), value.trim().to_string()))
+        .ok_or_else(|| format!("expected NAME=VALUE, found '{value}'"))
+}
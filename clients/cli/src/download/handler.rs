@@ -1,27 +1,92 @@
 use std::{
-    fs::{self, File},
-    io::BufWriter,
+    fs,
     path::{Path, PathBuf},
     thread,
+    time::Duration,
 };
 
 use anyhow::bail;
-use log::info;
-use quelle_core::prelude::{Chapter, ExtensionConfig, Meta};
-use quelle_engine::{data::DefaultImpl, Runtime};
-use quelle_persist::{CoverLoc, EventKind, EventLog, Persist, PersistNovel, SavedNovel};
-use reqwest::{blocking::Client, header::CONTENT_TYPE};
+use log::{info, warn};
+use quelle_common::ProgressEvent;
+use quelle_core::prelude::{Chapter, ExtensionConfig, Meta, Novel, QuelleError, RequestErrorKind};
+use quelle_engine::module::{
+    http::{SendOptions, Session},
+    resume::{read_body, RESUME_ATTEMPTS},
+};
+use quelle_persist::{
+    content_hash, count_words, ChapterCache, Compression, CoverLoc, Credential, EventKind,
+    EventLog, Persist, PersistNovel, Provenance, SavedNovel, NOVEL_FIELDS,
+};
+use reqwest::{header::CONTENT_TYPE, Client, Method};
+use sha2::{Digest, Sha256};
 use url::Url;
 
-use super::DownloadOptions;
+use crate::{
+    error::{coded, ErrorCode},
+    t,
+};
+
+/// How long a chapter may take to download when retrying
+const RETRY_TIMEOUT: Duration = Duration::from_secs(300);
+
+/// The minimum delay between chapters when retrying
+const RETRY_DELAY: Duration = Duration::from_secs(2);
+
+use super::{
+    lang::{detect_lang, is_expected_lang},
+    print_progress,
+    runner::Runner,
+    DownloadOptions,
+};
+
+/// A chapter that could not be downloaded after being retried
+pub struct FailedChapter {
+    pub title: String,
+    pub url: String,
+    pub error: quelle_engine::error::Error,
+    /// The source refused the chapter, such as a locked chapter, so it was not retried
+    pub skipped: bool,
+}
+
+impl FailedChapter {
+    fn new(chapter: &Chapter, error: quelle_engine::error::Error) -> Self {
+        Self {
+            title: chapter.title.clone(),
+            url: chapter.url.clone(),
+            skipped: is_refused(&error),
+            error,
+        }
+    }
+}
+
+/// Whether the source refused the chapter, as it is locked or paywalled
+fn is_refused(error: &quelle_engine::error::Error) -> bool {
+    match error {
+        quelle_engine::error::Error::ReturnedError(QuelleError::RequestFailed(error)) => {
+            matches!(error.kind(), RequestErrorKind::Status(401 | 402 | 403))
+        }
+        _ => false,
+    }
+}
 
 pub struct DownloadHandler<'a> {
-    pub runner: Runtime<DefaultImpl>,
+    /// The url of the novel, after being corrected by the extension
+    pub url: Url,
+    pub runner: Runner,
+    pub wasm_path: PathBuf,
+    /// Authenticates the requests with the credential profile of the novel
+    pub session: Option<Session>,
     pub meta: Meta,
     pub persist_novel: PersistNovel<'a>,
     pub data: SavedNovel,
     pub options: DownloadOptions,
     pub log: EventLog,
+    /// Chapter content shared with other novels, unless disabled
+    pub cache: Option<ChapterCache>,
+    /// How the chapters are compressed, according to the library config
+    pub compression: Compression,
+    /// Whether the cover url changed since the novel was last saved
+    pub cover_changed: bool,
 }
 
 impl<'a> DownloadHandler<'a> {
@@ -31,34 +96,110 @@ impl<'a> DownloadHandler<'a> {
         wasm_path: PathBuf,
         options: DownloadOptions,
     ) -> anyhow::Result<DownloadHandler<'a>> {
-        let mut runner = Runtime::new(&wasm_path).await?;
-        runner
-            .setup(&ExtensionConfig {
-                level_filter: log::LevelFilter::Info,
-            })
-            .await?;
+        let mut runner = Runner::new(&wasm_path, options.executor).await?;
+        runner.setup(&Self::extension_config()).await?;
+
+        let meta = runner.meta().await?;
+        let url = Self::canonicalize_url(&mut runner, &meta, url).await?;
+
+        // Updates keep using the profile the novel was bound to when it was added
+        let profile = options
+            .profile
+            .clone()
+            .or_else(|| Self::bound_profile(persist, &url));
+
+        let mature = persist.read_config()?.source(&meta.id).mature;
+        let session = Self::session(persist, &meta, profile.as_deref(), mature)?;
+
+        if let Some(session) = &session {
+            runner.authenticate(session.clone()).await?;
+        }
 
         let novel = runner.fetch_novel(url.as_str()).await?;
         if novel.title.is_empty() {
             bail!("The novel title cannot be empty");
         }
 
-        let meta = runner.meta().await?;
+        // A novel saved from the url is continued even when its title changed since
+        let dir = match persist.read_global()?.novel_path_from_url(url.as_str()) {
+            Some(dir) => dir.to_path_buf(),
+            None => persist.novel_path(&meta, &novel.title),
+        };
 
-        let persist_novel = persist.persist_novel(persist.novel_path(&meta, &novel.title));
-        let data = persist_novel
-            .read_data()?
-            .unwrap_or_else(|| SavedNovel::new(novel));
+        let persist_novel = persist.persist_novel(dir);
+        let (mut data, cover_changed) = match persist_novel.read_data()? {
+            Some(mut data) => {
+                Self::verify_saved(&data, &novel);
 
-        let log = persist_novel.event_log()?;
+                let cover_changed = data.novel.cover != novel.cover;
+                if cover_changed {
+                    info!("The novel cover has changed to {:?}.", novel.cover);
+                    data.novel.cover = novel.cover;
+                    data.set_provenance(["cover"], &Provenance::now(&meta));
+                }
+                (data, cover_changed)
+            }
+            None => {
+                let mut data = SavedNovel::new(novel);
+                data.set_provenance(NOVEL_FIELDS, &Provenance::now(&meta));
+                (data, false)
+            }
+        };
+
+        data.credential = profile;
+
+        // Chapters saved by an interrupted download are still in the journal
+        let mut log = persist_novel.event_log()?;
+        log.read_events()?;
+        if let Some(events) = log.take_events() {
+            info!(
+                "Recovered {} chapters from an interrupted download.",
+                events.len()
+            );
+            data.commit_events(events);
+        }
+
+        let total = chapter_count(&data.novel);
+        let downloaded = data
+            .downloaded
+            .values()
+            .filter(|path| persist_novel.dir().join(path).exists())
+            .count();
+
+        if downloaded > 0 && downloaded < total {
+            println!(
+                "{}",
+                t!(
+                    "download-resuming",
+                    title = data.novel.title,
+                    count = downloaded,
+                    total = total
+                )
+            );
+        }
+        // The shared cache is not encrypted, so encrypted libraries never use it
+        // Refetched chapters must come from the source to find the changes
+        let refetch = options.refetch || options.refetch_version.is_some();
+        let cache = if options.shared_cache && !refetch && persist.cipher().is_none() {
+            Some(persist.read_chapter_cache()?)
+        } else {
+            None
+        };
+        let compression = persist.read_config()?.compression;
 
         Ok(Self {
+            url,
             runner,
+            wasm_path,
+            session,
             meta,
             persist_novel,
             data,
             log,
+            cache,
+            compression,
             options,
+            cover_changed,
         })
     }
 
@@ -73,7 +214,100 @@ impl<'a> DownloadHandler<'a> {
         Ok(())
     }
 
-    pub async fn download(&mut self) -> anyhow::Result<()> {
+    /// Ask the extension for the url of the novel page, in case a chapter url was given
+    async fn canonicalize_url(runner: &mut Runner, meta: &Meta, url: Url) -> anyhow::Result<Url> {
+        let canonical = runner.canonicalize_url(url.as_str()).await?;
+
+        let url = match Url::parse(&canonical) {
+            Ok(canonical) if canonical != url => {
+                println!("{}", t!("url-corrected", from = url, to = canonical));
+                canonical
+            }
+            Ok(_) => url,
+            Err(e) => {
+                warn!("The extension returned an invalid url '{canonical}': {e}");
+                url
+            }
+        };
+
+        if meta.is_novel_url(url.as_str()) == Some(false) {
+            warn!("'{url}' does not look like a novel url of {}.", meta.name);
+        }
+
+        Ok(url)
+    }
+
+    /// Warn when the novel fetched from the source no longer matches the saved one
+    fn verify_saved(data: &SavedNovel, novel: &Novel) {
+        if data.novel.title != novel.title {
+            warn!(
+                "The novel title changed from '{}' to '{}'.",
+                data.novel.title, novel.title
+            );
+        }
+
+        let (saved, fetched) = (chapter_count(&data.novel), chapter_count(novel));
+        if saved != fetched {
+            warn!("The source now lists {fetched} chapters, {saved} were saved before.");
+        }
+    }
+
+    /// The credential profile bound to the novel saved from the url
+    fn bound_profile(persist: &Persist, url: &Url) -> Option<String> {
+        let global = persist.read_global().ok()?;
+        let path = global.novel_path_from_url(url.as_str())?;
+        let data = persist.persist_novel(path.into()).read_data().ok()??;
+        data.credential
+    }
+
+    /// The headers sent to the source, from the credential profile and the age gate
+    fn session(
+        persist: &Persist,
+        meta: &Meta,
+        profile: Option<&str>,
+        mature: bool,
+    ) -> anyhow::Result<Option<Session>> {
+        let mut credential = match profile {
+            Some(profile) => {
+                let credentials = persist.read_credentials()?;
+                credentials.get(&meta.id, profile).cloned().ok_or_else(|| {
+                    coded(
+                        ErrorCode::CredentialMissing,
+                        t!("credential-not-found", profile = profile, source = meta.id),
+                    )
+                })?
+            }
+            None => Credential::default(),
+        };
+
+        if mature {
+            match &meta.age_gate {
+                Some(gate) => credential.add_age_gate(gate),
+                None => warn!("{} does not declare how to show mature content.", meta.name),
+            }
+        }
+
+        if profile.is_none() && credential == Credential::default() {
+            return Ok(None);
+        }
+
+        Ok(Some(Session::for_base_urls(
+            &meta.base_urls,
+            credential.header_pairs(),
+        )))
+    }
+
+    fn extension_config() -> ExtensionConfig {
+        ExtensionConfig {
+            level_filter: log::LevelFilter::Info,
+        }
+    }
+
+    /// Download the chapters, retrying failed chapters once all others are done
+    ///
+    /// Retries run in a separate worker process with a longer timeout and delay.
+    /// The chapters that failed both attempts are returned.
+    pub async fn download(&mut self) -> anyhow::Result<Vec<FailedChapter>> {
         let chapter_dir = self.persist_novel.chapters_dir();
         if !chapter_dir.exists() {
             fs::create_dir_all(&chapter_dir)?;
@@ -92,56 +326,205 @@ impl<'a> DownloadHandler<'a> {
             None => &chapters,
         };
 
-        Self::download_chapters(
+        // Queued first so that an interrupted download shows what is left
+        let urls = chapters
+            .iter()
+            .filter(|chapter| !self.data.downloaded.contains_key(&chapter.url))
+            .map(|chapter| chapter.url.clone())
+            .collect::<Vec<_>>();
+        if !urls.is_empty() {
+            self.log.push_event(EventKind::Queued { urls })?;
+        }
+
+        let failed = Self::download_chapters(
             &mut self.runner,
+            &self.meta,
             &self.persist_novel,
             &self.data,
             &mut self.log,
+            &mut self.cache,
+            self.compression,
+            chapters,
+            self.persist_novel.dir(),
+            &self.options,
+            self.options.delay,
+        )
+        .await?;
+
+        // Chapters refused by the source would be refused again
+        let (skipped, failed) = failed
+            .into_iter()
+            .partition::<Vec<_>, _>(|(_, error)| is_refused(error));
+        let mut skipped = skipped
+            .into_iter()
+            .map(|(chapter, error)| FailedChapter::new(chapter, error))
+            .collect::<Vec<_>>();
+
+        if failed.is_empty() {
+            return Ok(skipped);
+        }
+
+        info!("Retrying {} failed chapters.", failed.len());
+
+        let mut runner = Runner::isolated(&self.wasm_path, RETRY_TIMEOUT)?;
+        runner.setup(&Self::extension_config()).await?;
+        if let Some(session) = &self.session {
+            runner.authenticate(session.clone()).await?;
+        }
+
+        let delay = self.options.delay.unwrap_or_default() * 2;
+        let chapters = failed
+            .into_iter()
+            .map(|(chapter, _)| chapter)
+            .collect::<Vec<_>>();
+
+        let failed = Self::download_chapters(
+            &mut runner,
+            &self.meta,
+            &self.persist_novel,
+            &self.data,
+            &mut self.log,
+            &mut self.cache,
+            self.compression,
             &chapters,
             self.persist_novel.dir(),
             &self.options,
+            Some(delay.max(RETRY_DELAY)),
         )
         .await?;
 
-        Ok(())
+        skipped.extend(
+            failed
+                .into_iter()
+                .map(|(chapter, error)| FailedChapter::new(chapter, error)),
+        );
+        Ok(skipped)
     }
 
-    async fn download_chapters(
-        runner: &mut Runtime<DefaultImpl>,
+    /// Download the chapters, returning the ones that could not be fetched
+    #[allow(clippy::too_many_arguments)]
+    async fn download_chapters<'c>(
+        runner: &mut Runner,
+        meta: &Meta,
         persist_novel: &PersistNovel<'a>,
         data: &SavedNovel,
         log: &mut EventLog,
-        chapters: &[&Chapter],
+        cache: &mut Option<ChapterCache>,
+        compression: Compression,
+        chapters: &[&'c Chapter],
         save_dir: &Path,
         options: &DownloadOptions,
-    ) -> anyhow::Result<()> {
-        for chapter in chapters {
+        delay: Option<Duration>,
+    ) -> anyhow::Result<Vec<(&'c Chapter, quelle_engine::error::Error)>> {
+        let mut failed = vec![];
+
+        let total = chapters.len();
+        for (index, chapter) in chapters.iter().enumerate() {
             if let Some(path) = data.downloaded.get(&chapter.url) {
-                if save_dir.join(path).exists() {
+                let refetch = options.refetch
+                    || options.refetch_version.as_ref().is_some_and(|version| {
+                        data.chapter_provenance
+                            .get(&chapter.url)
+                            .is_some_and(|provenance| provenance.version == *version)
+                    });
+
+                if !refetch && save_dir.join(path).exists() {
+                    if options.accessible {
+                        print_progress(&ProgressEvent::ChapterSkipped {
+                            number: index + 1,
+                            total,
+                            title: chapter.title.clone(),
+                        });
+                    }
                     continue;
                 }
             }
 
-            if let Some(delay) = &options.delay {
-                thread::sleep(*delay);
+            let cached = match cache {
+                Some(cache) => cache.get(&chapter.url)?,
+                None => None,
+            };
+
+            let content = match cached {
+                Some(content) => {
+                    info!("Reusing the saved content of '{}'.", &chapter.title);
+                    content
+                }
+                None => {
+                    if let Some(delay) = &delay {
+                        thread::sleep(*delay);
+                    }
+
+                    let content = match runner.fetch_chapter_content(&chapter.url).await {
+                        Ok(content) => content.data,
+                        Err(error) => {
+                            warn!("Failed to download '{}': {error}", &chapter.title);
+                            log.push_event(EventKind::Failed {
+                                url: chapter.url.clone(),
+                                error: error.to_string(),
+                                skipped: is_refused(&error),
+                            })?;
+                            failed.push((*chapter, error));
+                            continue;
+                        }
+                    };
+
+                    if let Some(cache) = cache {
+                        cache.insert(&chapter.url, &content)?;
+                    }
+                    content
+                }
+            };
+
+            let lang = if options.detect_lang {
+                detect_lang(&content)
+            } else {
+                None
+            };
+
+            if let Some(lang) = &lang {
+                if !is_expected_lang(lang, &data.novel.langs) {
+                    warn!(
+                        "'{}' appears to be in '{lang}', expected one of [{}].",
+                        &chapter.title,
+                        data.novel.langs.join(", ")
+                    );
+                }
             }
 
-            let content = runner.fetch_chapter_content(&chapter.url).await?;
-            let path = persist_novel.save_chapter(chapter, content.data)?;
+            let words = count_words(&content);
+            let hash = content_hash(&content);
+            let path = persist_novel.save_chapter(chapter, content, compression)?;
 
             info!("Downloaded '{}' to '{}'.", &chapter.title, path.display());
+            if options.accessible {
+                print_progress(&ProgressEvent::ChapterDownloaded {
+                    number: index + 1,
+                    total,
+                    title: chapter.title.clone(),
+                });
+            }
 
             let path = persist_novel.relative_path(path);
             log.push_event(EventKind::Downloaded {
                 url: chapter.url.clone(),
                 path,
+                lang,
+                words: Some(words),
+                provenance: Some(Provenance::now(meta)),
+                hash: Some(hash),
             })?;
         }
 
-        Ok(())
+        if let Some(cache) = cache {
+            cache.save()?;
+        }
+
+        Ok(failed)
     }
 
-    pub fn download_cover(&mut self) -> anyhow::Result<()> {
+    /// Download the cover, resuming it if the connection drops
+    pub async fn download_cover(&mut self) -> anyhow::Result<()> {
         let data = &mut self.data;
         let Some(url) = data.novel.cover.as_ref() else { return Ok(()) };
 
@@ -151,7 +534,7 @@ impl<'a> DownloadHandler<'a> {
             )
             .build()?;
 
-        let mut response = client.get(url).send()?;
+        let response = client.get(url).send().await?;
         if !response.status().is_success() {
             let status = response.status();
             bail!("Cover download failed with {}", status.as_str());
@@ -169,15 +552,59 @@ impl<'a> DownloadHandler<'a> {
 
         info!("Content type from headers: {content_type}");
 
+        let bytes = read_body(
+            &client,
+            &Method::GET,
+            response,
+            SendOptions::default(),
+            RESUME_ATTEMPTS,
+        )
+        .await?;
+        let hash = format!("{:x}", Sha256::digest(&bytes));
+
+        if let Some(cover) = data.cover.as_mut() {
+            // Covers saved before hashes were recorded are hashed from their file
+            if cover.hash.is_none() {
+                cover.hash = fs::read(&cover.path)
+                    .ok()
+                    .map(|bytes| format!("{:x}", Sha256::digest(bytes)));
+            }
+
+            if cover.hash.as_ref() == Some(&hash) && cover.path.exists() {
+                info!("The novel cover is unchanged.");
+                cover.url = Some(url.clone());
+                return Ok(());
+            }
+        }
+
+        // The url and hash of a previous cover are kept even when its file is gone
+        if let Some(mut previous) = data.cover.take() {
+            if previous.path.exists() {
+                previous = self.persist_novel.archive_cover(previous)?;
+                println!("{}", t!("cover-updated", path = previous.path.display()));
+            }
+            data.cover_history.push(previous);
+        }
+
         let suffix = mime_guess::get_mime_extensions_str(&content_type).map(|exts| exts[0]);
-        let path = self.persist_novel.cover_path(suffix);
+        let path = self.persist_novel.write_cover(suffix, &bytes)?;
 
-        let mut file = BufWriter::new(File::create(&path)?);
-        response.copy_to(&mut file)?;
+        let mut assets = self.persist_novel.read_assets()?;
+        assets.store_asset(url, &content_type, &bytes)?;
+        assets.save()?;
 
         info!("Saved novel cover to '{}'.", path.display());
-        data.cover = Some(CoverLoc { path, content_type });
+        data.cover = Some(CoverLoc {
+            path,
+            content_type,
+            url: Some(url.clone()),
+            hash: Some(hash),
+        });
 
         Ok(())
     }
 }
+
+fn chapter_count(novel: &Novel) -> usize {
+    novel.volumes.iter().map(|v| v.chapters.len()).sum()
+}
@@ -0,0 +1,94 @@
+use whatlang::{Detector, Lang};
+
+/// The minimum number of characters needed for a reliable detection
+const MIN_TEXT_LEN: usize = 200;
+
+/// Detect the language of the html content as an ISO 639-1 code where available
+pub fn detect_lang(html: &str) -> Option<String> {
+    let text = strip_tags(html);
+    if text.chars().count() < MIN_TEXT_LEN {
+        return None;
+    }
+
+    let info = Detector::new().detect(&text)?;
+    if !info.is_reliable() {
+        return None;
+    }
+
+    Some(lang_code(info.lang()).to_string())
+}
+
+/// Whether the detected language matches any of the expected languages
+pub fn is_expected_lang(detected: &str, expected: &[String]) -> bool {
+    expected.is_empty()
+        || expected.iter().any(|lang| {
+            let lang = lang.split(['-', '_']).next().unwrap_or(lang);
+            lang.eq_ignore_ascii_case(detected)
+        })
+}
+
+fn lang_code(lang: Lang) -> &'static str {
+    match lang {
+        Lang::Eng => "en",
+        Lang::Spa => "es",
+        Lang::Fra => "fr",
+        Lang::Deu => "de",
+        Lang::Ita => "it",
+        Lang::Por => "pt",
+        Lang::Nld => "nl",
+        Lang::Pol => "pl",
+        Lang::Rus => "ru",
+        Lang::Ukr => "uk",
+        Lang::Tur => "tr",
+        Lang::Ara => "ar",
+        Lang::Hin => "hi",
+        Lang::Ind => "id",
+        Lang::Vie => "vi",
+        Lang::Tha => "th",
+        Lang::Cmn => "zh",
+        Lang::Jpn => "ja",
+        Lang::Kor => "ko",
+        _ => lang.code(),
+    }
+}
+
+fn strip_tags(html: &str) -> String {
+    let mut text = String::with_capacity(html.len());
+    let mut in_tag = false;
+
+    for c in html.chars() {
+        match c {
+            '<' => in_tag = true,
+            '>' if in_tag => {
+                in_tag = false;
+                text.push(' ');
+            }
+            _ if !in_tag => text.push(c),
+            _ => (),
+        }
+    }
+
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_detect_lang_of_html() {
+        let html = "<p>She walked slowly through the quiet village, wondering whether \
+            anyone would remember her name after all these years. The houses were smaller \
+            than she remembered, and the old bakery on the corner had closed long ago.</p>";
+        assert_eq!(detect_lang(html).as_deref(), Some("en"));
+        assert_eq!(detect_lang("<p>Too short</p>"), None);
+    }
+
+    #[test]
+    fn should_match_expected_lang() {
+        assert!(is_expected_lang("en", &[String::from("en")]));
+        assert!(is_expected_lang("en", &[String::from("en-US")]));
+        assert!(is_expected_lang("en", &[]));
+        assert!(!is_expected_lang("zh", &[String::from("en")]));
+    }
+}
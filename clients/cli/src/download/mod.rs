@@ -1,50 +1,163 @@
 mod handler;
+mod lang;
 mod options;
+mod runner;
 
 use std::path::PathBuf;
 
+use chrono::Utc;
 use log::warn;
 pub use options::DownloadOptions;
+use quelle_common::ProgressEvent;
+use quelle_core::prelude::QuelleError;
+use quelle_engine::error::Error;
 use quelle_persist::{Persist, SavedNovel};
 use url::Url;
 
-use crate::args::CoverAction;
+use crate::{
+    args::CoverAction,
+    error::{coded, ErrorCode},
+    t,
+};
 
 use self::handler::DownloadHandler;
 
 pub async fn download(
     persist: Persist,
+    source: &str,
+    url: Url,
+    wasm_path: PathBuf,
+    options: DownloadOptions,
+) -> anyhow::Result<SavedNovel> {
+    let host = url.host_str().unwrap_or_default().to_string();
+    let mut hosts = persist.read_hosts()?;
+
+    if let Some(until) = hosts.suspended_until(&host, Utc::now()) {
+        return Err(coded(
+            ErrorCode::HostSuspended,
+            t!("host-suspended", host = host, until = until),
+        ));
+    }
+
+    let executor = options.executor;
+    let result = download_novel(&persist, url, wasm_path, options).await;
+
+    // Failing to update the registries must not replace the result of the download
+    let mut record = || -> anyhow::Result<()> {
+        let mut sources = persist.read_sources()?;
+        match &result {
+            Ok(_) => sources.record_success(source, executor, Utc::now()),
+            Err(error) if is_executor_failure(error) => {
+                sources.record_failure(source, executor, Utc::now())
+            }
+            Err(_) => (),
+        }
+        persist.save_sources(&sources)?;
+
+        match &result {
+            Ok(_) => hosts.record_success(&host),
+            Err(error) if is_request_failure(error) => {
+                let status = hosts.record_failure(&host, Utc::now());
+                if let Some(until) = status.suspended_until {
+                    warn!("'{host}' appears to be down, suspended until {until}");
+                }
+            }
+            Err(_) => (),
+        }
+        persist.save_hosts(&hosts)?;
+        Ok(())
+    };
+    if let Err(error) = record() {
+        warn!("failed to record the download of '{host}': {error}");
+    }
+
+    result
+}
+
+async fn download_novel(
+    persist: &Persist,
     url: Url,
     wasm_path: PathBuf,
     options: DownloadOptions,
 ) -> anyhow::Result<SavedNovel> {
     let mut global = persist.read_global()?;
 
-    let url_string = url.to_string();
-    let mut handler = DownloadHandler::new(&persist, url, wasm_path, options).await?;
+    let mut handler = DownloadHandler::new(persist, url, wasm_path, options).await?;
     handler.save()?;
 
     match &handler.options.cover {
         CoverAction::Dynamic => {
-            if !handler.data.is_cover_downloaded() {
-                download_cover_and_warn(&mut handler)?;
+            if !handler.data.is_cover_downloaded() || handler.cover_changed {
+                download_cover_and_warn(&mut handler).await?;
             }
         }
-        CoverAction::Force => download_cover_and_warn(&mut handler)?,
+        CoverAction::Force => download_cover_and_warn(&mut handler).await?,
         CoverAction::Ignore => (),
     }
 
-    global.insert_novel(url_string, handler.persist_novel.dir().to_path_buf());
+    global.insert_novel(
+        handler.url.to_string(),
+        handler.persist_novel.dir().to_path_buf(),
+    );
     persist.save_global(&global)?;
 
-    handler.download().await?;
+    let (skipped, failed) = handler
+        .download()
+        .await?
+        .into_iter()
+        .partition::<Vec<_>, _>(|chapter| chapter.skipped);
     handler.save()?;
 
+    for chapter in &skipped {
+        warn!("{}", t!("chapter-refused", title = chapter.title));
+    }
+
+    if !failed.is_empty() {
+        for chapter in &failed {
+            print_progress(&ProgressEvent::ChapterFailed {
+                title: chapter.title.clone(),
+                url: chapter.url.clone(),
+                reason: chapter.error.to_string(),
+            });
+        }
+
+        return Err(coded(
+            ErrorCode::ChaptersFailed,
+            t!("chapters-failed", count = failed.len()),
+        ));
+    }
+
     Ok(handler.data)
 }
 
-fn download_cover_and_warn(handler: &mut DownloadHandler) -> Result<(), anyhow::Error> {
-    match handler.download_cover() {
+/// Print a line describing the progress event
+pub(crate) fn print_progress(event: &ProgressEvent) {
+    let line = match event {
+        ProgressEvent::ChapterDownloaded {
+            number,
+            total,
+            title,
+        } => t!("chapter-downloaded", number, total, title),
+        ProgressEvent::ChapterSkipped {
+            number,
+            total,
+            title,
+        } => t!("chapter-skipped", number, total, title),
+        ProgressEvent::ChapterFailed { title, reason, .. } => {
+            t!("chapter-failed", title, reason)
+        }
+        ProgressEvent::ChapterBundled {
+            number,
+            total,
+            title,
+        } => t!("chapter-bundled", number, total, title),
+    };
+
+    println!("{line}");
+}
+
+async fn download_cover_and_warn(handler: &mut DownloadHandler<'_>) -> Result<(), anyhow::Error> {
+    match handler.download_cover().await {
         Ok(_) => handler.save(),
         Err(error) => {
             warn!("{error}");
@@ -52,3 +165,19 @@ fn download_cover_and_warn(handler: &mut DownloadHandler) -> Result<(), anyhow::
         }
     }
 }
+
+/// Whether the error was caused by the way the extension was executed
+fn is_executor_failure(error: &anyhow::Error) -> bool {
+    matches!(
+        error.downcast_ref::<Error>(),
+        Some(Error::Trap(_) | Error::ProcessError(_) | Error::MemoryAccessError | Error::Other(_))
+    )
+}
+
+/// Whether the error was caused by a failed request to the source
+fn is_request_failure(error: &anyhow::Error) -> bool {
+    matches!(
+        error.downcast_ref::<Error>(),
+        Some(Error::ReturnedError(QuelleError::RequestFailed(_)))
+    )
+}
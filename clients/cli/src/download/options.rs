@@ -1,5 +1,7 @@
 use std::{ops::RangeInclusive, path::PathBuf, time::Duration};
 
+use quelle_persist::Executor;
+
 use crate::args::CoverAction;
 
 #[derive(Debug)]
@@ -8,6 +10,21 @@ pub struct DownloadOptions {
     pub range: Option<RangeInclusive<usize>>,
     pub delay: Option<Duration>,
     pub cover: CoverAction,
+    /// Detect the language of downloaded chapters
+    pub detect_lang: bool,
+    /// How the extension is executed
+    pub executor: Executor,
+    /// Report the progress of each chapter on its own line
+    pub accessible: bool,
+    /// The stored credential profile to fetch the novel with, replacing the one bound to the novel
+    pub profile: Option<String>,
+    /// Reuse chapter content already downloaded for another novel with the same url
+    pub shared_cache: bool,
+    /// Download the chapters that were already saved again, keeping the
+    /// previous content as a version when it changed
+    pub refetch: bool,
+    /// Download again the chapters fetched with this version of the extension
+    pub refetch_version: Option<String>,
 }
 
 impl Default for DownloadOptions {
@@ -17,6 +34,13 @@ impl Default for DownloadOptions {
             range: Default::default(),
             delay: Default::default(),
             cover: Default::default(),
+            detect_lang: true,
+            executor: Executor::InProcess,
+            accessible: false,
+            profile: None,
+            shared_cache: true,
+            refetch: false,
+            refetch_version: None,
         }
     }
 }
@@ -0,0 +1,158 @@
+use std::{
+    collections::HashMap,
+    env,
+    ffi::OsStr,
+    fs,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex, OnceLock},
+    time::{Duration, SystemTime},
+};
+
+use quelle_core::prelude::*;
+use quelle_engine::{
+    budget::Budget, data::DefaultImpl, error, module::http::Session, process::ProcessRuntime,
+    PooledRuntime, RuntimePool,
+};
+use quelle_persist::Executor;
+
+/// The runtimes of an extension alive at the same time, which are kept for
+/// the next download from its source
+const POOL_SIZE: u32 = 2;
+
+/// The downloads a runtime is reused for before it is instantiated again
+const POOL_MAX_USES: usize = 32;
+
+/// The runtime pools by extension path, with the modification time of the
+/// extension they were compiled from
+type Pools = HashMap<PathBuf, (Option<SystemTime>, Arc<RuntimePool<DefaultImpl>>)>;
+
+static POOLS: OnceLock<Mutex<Pools>> = OnceLock::new();
+
+/// The runtime pool of the extension, compiling it on first use or when the
+/// extension was updated since
+fn pool(wasm_path: &Path) -> anyhow::Result<Arc<RuntimePool<DefaultImpl>>> {
+    let modified = fs::metadata(wasm_path)
+        .and_then(|metadata| metadata.modified())
+        .ok();
+    let mut pools = POOLS
+        .get_or_init(Default::default)
+        .lock()
+        .unwrap_or_else(|e| e.into_inner());
+
+    if let Some((compiled, pool)) = pools.get(wasm_path) {
+        if *compiled == modified {
+            return Ok(pool.clone());
+        }
+    }
+
+    let pool = Arc::new(RuntimePool::open(wasm_path, POOL_SIZE)?.max_uses(POOL_MAX_USES));
+    pools.insert(wasm_path.to_path_buf(), (modified, pool.clone()));
+    Ok(pool)
+}
+
+/// The limits of fetching a novel, which may request many pages of chapters
+fn novel_budget() -> Budget {
+    Budget::default()
+        .max_requests(1000)
+        .deadline(Duration::from_secs(600))
+}
+
+/// The limits of fetching the content of a single chapter
+fn chapter_budget() -> Budget {
+    Budget::default()
+        .max_requests(50)
+        .max_bytes(50 * 1024 * 1024)
+        .deadline(Duration::from_secs(120))
+}
+
+/// The extension runtime used for a download
+pub enum Runner {
+    /// The extension runs inside this process, in a runtime recycled by the
+    /// pool of the extension once the download is done
+    Local(PooledRuntime<DefaultImpl>),
+    /// The extension runs in a worker child process
+    Process(ProcessRuntime),
+}
+
+impl Runner {
+    pub async fn new(wasm_path: &Path, executor: Executor) -> anyhow::Result<Self> {
+        match executor {
+            Executor::InProcess => {
+                let mut runtime = pool(wasm_path)?.acquire().await?;
+                // The session of a recycled runtime belongs to an earlier download
+                runtime.data_mut().session = None;
+                Ok(Runner::Local(runtime))
+            }
+            Executor::Isolated => Ok(Runner::Process(Self::worker(wasm_path)?)),
+        }
+    }
+
+    /// Run the extension in a worker process with the response timeout
+    pub fn isolated(wasm_path: &Path, timeout: Duration) -> anyhow::Result<Self> {
+        Ok(Runner::Process(Self::worker(wasm_path)?.timeout(timeout)))
+    }
+
+    fn worker(wasm_path: &Path) -> anyhow::Result<ProcessRuntime> {
+        let program = env::current_exe()?;
+        let args = [OsStr::new("worker"), wasm_path.as_os_str()];
+        Ok(ProcessRuntime::new(program, args))
+    }
+
+    pub async fn setup(&mut self, config: &ExtensionConfig) -> error::Result<()> {
+        match self {
+            Runner::Local(runtime) => runtime.setup(config).await,
+            Runner::Process(runtime) => runtime.setup(config).await,
+        }
+    }
+
+    /// Authenticate the requests to the source with the session
+    pub async fn authenticate(&mut self, session: Session) -> error::Result<()> {
+        match self {
+            Runner::Local(runtime) => {
+                runtime.data_mut().session = Some(session);
+                Ok(())
+            }
+            Runner::Process(runtime) => runtime.authenticate(session).await,
+        }
+    }
+
+    pub async fn meta(&mut self) -> error::Result<Meta> {
+        match self {
+            Runner::Local(runtime) => runtime.meta().await,
+            Runner::Process(runtime) => runtime.meta().await,
+        }
+    }
+
+    pub async fn canonicalize_url(&mut self, url: &str) -> error::Result<String> {
+        match self {
+            Runner::Local(runtime) => runtime.canonicalize_url(url).await,
+            Runner::Process(runtime) => runtime.canonicalize_url(url).await,
+        }
+    }
+
+    pub async fn fetch_novel(&mut self, url: &str) -> error::Result<Novel> {
+        match self {
+            Runner::Local(runtime) => {
+                runtime
+                    .within_budget(novel_budget(), async |runtime| {
+                        runtime.fetch_novel(url).await
+                    })
+                    .await
+            }
+            Runner::Process(runtime) => runtime.fetch_novel(url, novel_budget()).await,
+        }
+    }
+
+    pub async fn fetch_chapter_content(&mut self, url: &str) -> error::Result<Content> {
+        match self {
+            Runner::Local(runtime) => {
+                runtime
+                    .within_budget(chapter_budget(), async |runtime| {
+                        runtime.fetch_chapter_content(url).await
+                    })
+                    .await
+            }
+            Runner::Process(runtime) => runtime.fetch_chapter_content(url, chapter_budget()).await,
+        }
+    }
+}
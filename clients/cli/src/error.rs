@@ -0,0 +1,187 @@
+use std::{fmt::Display, io};
+
+use quelle_core::prelude::{QuelleError, RequestErrorKind};
+use quelle_engine::error::Error as EngineError;
+use quelle_persist::PersistError;
+use serde::Serialize;
+
+use crate::{args::OutputFormat, t};
+
+pub use quelle_common::ErrorCode;
+
+/// What the user can do to resolve the error
+pub fn hint(code: ErrorCode) -> String {
+    match code {
+        ErrorCode::Unknown => t!("hint-unknown"),
+        ErrorCode::StoreIo => t!("hint-store-io"),
+        ErrorCode::StoreCorrupt => t!("hint-store-corrupt"),
+        ErrorCode::NovelNotFound => t!("hint-novel-not-found"),
+        ErrorCode::SchemaUnsupported => t!("hint-schema-unsupported"),
+        ErrorCode::BackupInvalid => t!("hint-backup-invalid"),
+        ErrorCode::BackupConflict => t!("hint-backup-conflict"),
+        ErrorCode::RemoteFailed => t!("hint-remote-failed"),
+        ErrorCode::NovelExists => t!("hint-novel-exists"),
+        ErrorCode::LibraryLocked => t!("hint-library-locked"),
+        ErrorCode::EpubInvalid => t!("hint-epub-invalid"),
+        ErrorCode::LockUnreadable => t!("hint-lock-unreadable"),
+        ErrorCode::SourceNotSupported => t!("hint-source-not-supported"),
+        ErrorCode::ExtensionMissing => t!("hint-extension-missing"),
+        ErrorCode::ExtensionFailed => t!("hint-extension-failed"),
+        ErrorCode::ExtensionParseFailed => t!("hint-extension-parse-failed"),
+        ErrorCode::ExtensionUnsupported => t!("hint-extension-unsupported"),
+        ErrorCode::FixturesMissing => t!("hint-fixtures-missing"),
+        ErrorCode::ExtensionTestFailed => t!("hint-extension-test-failed"),
+        ErrorCode::BudgetExceeded => t!("hint-budget-exceeded"),
+        ErrorCode::RequestFailed => t!("hint-request-failed"),
+        ErrorCode::HostSuspended => t!("hint-host-suspended"),
+        ErrorCode::ChaptersFailed => t!("hint-chapters-failed"),
+        ErrorCode::Offline => t!("hint-offline"),
+        ErrorCode::CredentialMissing => t!("hint-credential-missing"),
+        ErrorCode::PassphraseRequired => t!("hint-passphrase-required"),
+        ErrorCode::PassphraseWrong => t!("hint-passphrase-wrong"),
+        ErrorCode::BundleFailed => t!("hint-bundle-failed"),
+    }
+}
+
+/// An error raised by the command line with a known code
+#[derive(Debug)]
+pub struct CodedError {
+    pub code: ErrorCode,
+    pub message: String,
+}
+
+impl Display for CodedError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl std::error::Error for CodedError {}
+
+/// Create an error with the code and message
+pub fn coded<S: Into<String>>(code: ErrorCode, message: S) -> anyhow::Error {
+    anyhow::Error::new(CodedError {
+        code,
+        message: message.into(),
+    })
+}
+
+/// Find the code that best describes the error
+pub fn classify(error: &anyhow::Error) -> ErrorCode {
+    for cause in error.chain() {
+        if let Some(error) = cause.downcast_ref::<CodedError>() {
+            return error.code;
+        }
+
+        if let Some(error) = cause.downcast_ref::<PersistError>() {
+            return match error {
+                PersistError::SerializationError => ErrorCode::StoreCorrupt,
+                PersistError::IO(_) => ErrorCode::StoreIo,
+                PersistError::UnsupportedSchema { .. } => ErrorCode::SchemaUnsupported,
+                PersistError::Encryption(_) | PersistError::TransferMismatch(_) => {
+                    ErrorCode::StoreCorrupt
+                }
+                PersistError::PassphraseRequired => ErrorCode::PassphraseRequired,
+                PersistError::WrongPassphrase => ErrorCode::PassphraseWrong,
+                PersistError::InvalidBackup(_) => ErrorCode::BackupInvalid,
+                PersistError::InvalidEpub(_) => ErrorCode::EpubInvalid,
+                PersistError::BackupConflict(_) => ErrorCode::BackupConflict,
+                PersistError::Remote(_) => ErrorCode::RemoteFailed,
+                PersistError::NovelExists(_) | PersistError::SameNovel(_) => ErrorCode::NovelExists,
+                PersistError::Locked(_) => ErrorCode::LibraryLocked,
+            };
+        }
+
+        if let Some(error) = cause.downcast_ref::<EngineError>() {
+            return match error {
+                EngineError::ReturnedError(QuelleError::RequestFailed(error))
+                    if matches!(error.kind(), RequestErrorKind::Connect) =>
+                {
+                    ErrorCode::Offline
+                }
+                EngineError::ReturnedError(QuelleError::RequestFailed(_)) => {
+                    ErrorCode::RequestFailed
+                }
+                EngineError::ReturnedError(QuelleError::ParseFailed(_)) => {
+                    ErrorCode::ExtensionParseFailed
+                }
+                EngineError::NotSupported(_) => ErrorCode::ExtensionUnsupported,
+                EngineError::BudgetExceeded(_) => ErrorCode::BudgetExceeded,
+                _ => ErrorCode::ExtensionFailed,
+            };
+        }
+
+        if cause
+            .downcast_ref::<reqwest::Error>()
+            .is_some_and(reqwest::Error::is_connect)
+        {
+            return ErrorCode::Offline;
+        }
+
+        if cause.downcast_ref::<io::Error>().is_some() {
+            return ErrorCode::StoreIo;
+        }
+    }
+
+    ErrorCode::Unknown
+}
+
+#[derive(Serialize)]
+struct ErrorReport {
+    code: ErrorCode,
+    message: String,
+    hint: String,
+}
+
+/// Write the error with its code and remediation hint
+pub fn report(error: &anyhow::Error, output: OutputFormat) {
+    let code = classify(error);
+
+    match output {
+        OutputFormat::Text => {
+            eprintln!("{}[{}]: {error:#}", t!("error-label"), code.code());
+            eprintln!("  {}: {}", t!("hint-label"), hint(code));
+        }
+        OutputFormat::Json => {
+            let report = ErrorReport {
+                code,
+                message: format!("{error:#}"),
+                hint: hint(code),
+            };
+
+            match serde_json::to_string(&serde_json::json!({ "error": report })) {
+                Ok(json) => println!("{json}"),
+                Err(_) => eprintln!("error[{}]: {error:#}", code.code()),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use quelle_core::prelude::RequestError;
+
+    use super::*;
+
+    #[test]
+    fn should_classify_errors() {
+        let error = coded(ErrorCode::NovelNotFound, "The novel does not exist");
+        assert_eq!(classify(&error), ErrorCode::NovelNotFound);
+
+        let error = anyhow::Error::new(PersistError::SerializationError).context("reading data");
+        assert_eq!(classify(&error), ErrorCode::StoreCorrupt);
+
+        let error = anyhow::anyhow!("something else");
+        assert_eq!(classify(&error), ErrorCode::Unknown);
+
+        let error = anyhow::Error::new(EngineError::ReturnedError(QuelleError::RequestFailed(
+            RequestError {
+                kind: RequestErrorKind::Connect,
+                url: Some(String::from("https://example.com")),
+                message: String::from("connection refused"),
+            }
+            .into(),
+        )));
+        assert_eq!(classify(&error), ErrorCode::Offline);
+    }
+}
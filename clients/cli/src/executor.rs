@@ -0,0 +1,82 @@
+use clap::Subcommand;
+use quelle_persist::{Executor, Task};
+
+use crate::{open_persist, t};
+
+#[derive(Subcommand)]
+pub enum ExecutorAction {
+    /// Use the executor for a command or a source, or by default
+    Set {
+        /// The executor: in_process or isolated
+        executor: Executor,
+
+        /// The command it is used for: add or update
+        #[arg(long, conflicts_with = "source")]
+        command: Option<Task>,
+
+        /// The id of the source it is used for
+        #[arg(long)]
+        source: Option<String>,
+    },
+    /// Remove the executor of a command or a source, or the default
+    Unset {
+        #[arg(long, conflicts_with = "source")]
+        command: Option<Task>,
+
+        #[arg(long)]
+        source: Option<String>,
+    },
+    /// Show the configured executors
+    List,
+}
+
+pub fn handle(action: ExecutorAction) -> anyhow::Result<()> {
+    let persist = open_persist()?;
+    let mut config = persist.read_config()?;
+    let executors = &mut config.executors;
+
+    match action {
+        ExecutorAction::Set {
+            executor,
+            command,
+            source,
+        } => {
+            match (command, source) {
+                (Some(command), _) => {
+                    executors.commands.insert(command, executor);
+                }
+                (_, Some(source)) => {
+                    executors.sources.insert(source, executor);
+                }
+                _ => executors.default = Some(executor),
+            }
+            persist.save_config(&config)?;
+            println!("{}", t!("executor-set", executor));
+        }
+        ExecutorAction::Unset { command, source } => {
+            match (command, source) {
+                (Some(command), _) => {
+                    executors.commands.remove(&command);
+                }
+                (_, Some(source)) => {
+                    executors.sources.remove(&source);
+                }
+                _ => executors.default = None,
+            }
+            persist.save_config(&config)?;
+        }
+        ExecutorAction::List => {
+            if let Some(executor) = executors.default {
+                println!("default: {executor}");
+            }
+            for (command, executor) in &executors.commands {
+                println!("{command}: {executor}");
+            }
+            for (source, executor) in &executors.sources {
+                println!("{source}: {executor}");
+            }
+        }
+    }
+
+    Ok(())
+}
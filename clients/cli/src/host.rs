@@ -0,0 +1,99 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    path::{Path, PathBuf},
+};
+
+use quelle_engine::{data::DefaultImpl, Runtime, RuntimePre};
+use quelle_lock::Lock;
+
+use crate::{
+    error::{coded, ErrorCode},
+    open_lock,
+};
+
+/// The number of extension runtimes kept instantiated
+const CAPACITY: usize = 4;
+
+/// Loads the lock and the extensions it lists only when a command needs them
+///
+/// Only the extension matching a url is compiled, and the most recently used
+/// runtimes are kept so that a later request to the same source is not
+/// instantiated again. Extensions are compiled once, so a runtime dropped to
+/// make room is instantiated again without compiling its extension.
+pub struct ExtensionHost {
+    lock_path: PathBuf,
+    lock: Option<Lock>,
+    /// Instantiated runtimes by extension id, the most recently used last
+    runtimes: VecDeque<(String, Runtime<DefaultImpl>)>,
+    capacity: usize,
+    /// Compiled extensions by extension id
+    prepared: HashMap<String, RuntimePre<DefaultImpl>>,
+}
+
+impl ExtensionHost {
+    pub fn new(lock_path: PathBuf) -> Self {
+        Self {
+            lock_path,
+            lock: None,
+            runtimes: VecDeque::new(),
+            capacity: CAPACITY,
+            prepared: HashMap::new(),
+        }
+    }
+
+    /// The lock file, read the first time it is needed
+    pub fn lock(&mut self) -> anyhow::Result<&Lock> {
+        if self.lock.is_none() {
+            self.lock = Some(open_lock(&self.lock_path)?);
+        }
+
+        Ok(self.lock.as_ref().expect("the lock was just opened"))
+    }
+
+    /// The runtime of the extension the url belongs to, if the lock has one
+    pub async fn runtime(
+        &mut self,
+        url: &str,
+    ) -> anyhow::Result<Option<&mut Runtime<DefaultImpl>>> {
+        let Some((id, extension)) = self.lock()?.find(url) else {
+            return Ok(None);
+        };
+
+        let id = id.clone();
+        let path = PathBuf::from(&extension.path);
+
+        match self.runtimes.iter().position(|(cached, _)| *cached == id) {
+            Some(position) => {
+                let entry = self.runtimes.remove(position).expect("the position exists");
+                self.runtimes.push_back(entry);
+            }
+            None => {
+                if !self.prepared.contains_key(&id) {
+                    let pre = Self::prepare(&path)?;
+                    self.prepared.insert(id.clone(), pre);
+                }
+                let runtime = self.prepared[&id]
+                    .instantiate_default(Default::default())
+                    .await?;
+                if self.runtimes.len() >= self.capacity {
+                    self.runtimes.pop_front();
+                }
+                self.runtimes.push_back((id, runtime));
+            }
+        }
+
+        Ok(self.runtimes.back_mut().map(|(_, runtime)| runtime))
+    }
+
+    fn prepare(path: &Path) -> anyhow::Result<RuntimePre<DefaultImpl>> {
+        if !path.exists() {
+            return Err(coded(
+                ErrorCode::ExtensionMissing,
+                t!("extension-file-missing", path = path.display()),
+            ));
+        }
+
+        log::info!("Loading the extension at '{}'", path.display());
+        Ok(RuntimePre::new(path)?)
+    }
+}
@@ -0,0 +1,141 @@
+//! Translations of user facing messages.
+//!
+//! Messages are stored in `locales/<lang>.ftl` using a subset of the fluent
+//! syntax: one `key = value` per line with `{ $name }` placeholders.
+
+use std::{collections::HashMap, env, fmt::Display, sync::OnceLock};
+
+const FALLBACK: &str = "en";
+
+const LOCALES: [(&str, &str); 2] = [
+    ("en", include_str!("../locales/en.ftl")),
+    ("es", include_str!("../locales/es.ftl")),
+];
+
+static CATALOG: OnceLock<Catalog> = OnceLock::new();
+
+struct Catalog {
+    messages: HashMap<String, String>,
+    fallback: HashMap<String, String>,
+}
+
+/// Load the messages of the language, detecting it from the environment when not given
+pub fn init(lang: Option<&str>) {
+    let lang = lang.map(normalize).unwrap_or_else(detect_lang);
+    let _ = CATALOG.set(Catalog::new(&lang));
+}
+
+/// Translate the message with the key, substituting the arguments
+pub fn tr(key: &str, args: &[(&str, &dyn Display)]) -> String {
+    let catalog = CATALOG.get_or_init(|| Catalog::new(&detect_lang()));
+
+    let Some(message) = catalog
+        .messages
+        .get(key)
+        .or_else(|| catalog.fallback.get(key))
+    else {
+        return key.to_string();
+    };
+
+    format_message(message, args)
+}
+
+/// Translate a message: `t!("no-notes", title = data.novel.title)`
+///
+/// Variables can be passed by name alone when they share the placeholder name.
+#[macro_export]
+macro_rules! t {
+    ($key:literal) => {
+        $crate::i18n::tr($key, &[])
+    };
+    ($key:literal, $($name:ident = $value:expr),+ $(,)?) => {
+        $crate::i18n::tr($key, &[$((stringify!($name), &$value as &dyn std::fmt::Display)),+])
+    };
+    ($key:literal, $($name:ident),+ $(,)?) => {
+        $crate::i18n::tr($key, &[$((stringify!($name), &$name as &dyn std::fmt::Display)),+])
+    };
+}
+
+impl Catalog {
+    fn new(lang: &str) -> Self {
+        Self {
+            messages: load(lang).unwrap_or_default(),
+            fallback: load(FALLBACK).unwrap_or_default(),
+        }
+    }
+}
+
+fn load(lang: &str) -> Option<HashMap<String, String>> {
+    LOCALES
+        .iter()
+        .find(|(name, _)| *name == lang)
+        .map(|(_, source)| parse(source))
+}
+
+fn parse(source: &str) -> HashMap<String, String> {
+    source
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .filter_map(|line| line.split_once('='))
+        .map(|(key, value)| (key.trim().to_string(), value.trim().to_string()))
+        .collect()
+}
+
+fn format_message(message: &str, args: &[(&str, &dyn Display)]) -> String {
+    let mut formatted = message.to_string();
+    for (name, value) in args {
+        formatted = formatted.replace(&format!("{{ ${name} }}"), &value.to_string());
+    }
+    formatted
+}
+
+/// The language of the user from the standard locale environment variables
+fn detect_lang() -> String {
+    ["LC_ALL", "LC_MESSAGES", "LANG"]
+        .into_iter()
+        .filter_map(|key| env::var(key).ok())
+        .find(|value| !value.is_empty())
+        .map(|value| normalize(&value))
+        .unwrap_or_else(|| FALLBACK.to_string())
+}
+
+/// Reduce a locale such as `es_MX.UTF-8` to its language code
+fn normalize(locale: &str) -> String {
+    locale
+        .split(['_', '-', '.', '@'])
+        .next()
+        .unwrap_or(locale)
+        .to_lowercase()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_translate_with_fallback() {
+        let es = Catalog::new("es");
+        let message = es.messages.get("no-notes").unwrap();
+        assert_eq!(
+            format_message(message, &[("title", &"Solo Leveling")]),
+            "No hay notas para 'Solo Leveling'"
+        );
+
+        let unknown = Catalog::new("xx");
+        assert!(unknown.messages.is_empty());
+        assert!(unknown.fallback.contains_key("no-notes"));
+        assert_eq!(normalize("es_MX.UTF-8"), "es");
+    }
+
+    #[test]
+    fn should_define_every_message_in_all_locales() {
+        let fallback = load(FALLBACK).unwrap();
+        for (lang, source) in LOCALES {
+            let messages = parse(source);
+            for key in fallback.keys() {
+                assert!(messages.contains_key(key), "'{key}' missing in '{lang}'");
+            }
+        }
+    }
+}
@@ -1,29 +1,78 @@
 mod args;
 mod bundle;
+mod calibre;
+mod check;
+mod credentials;
 mod download;
+mod error;
+mod executor;
+mod host;
+mod i18n;
+mod maintenance;
+mod mirror;
+mod network;
+mod sanitize;
+mod storage;
+mod trash;
 
 use std::{
-    fs::File,
+    collections::BTreeMap,
+    fs::{self, File},
     io::BufWriter,
     path::{Path, PathBuf},
     process::exit,
+    sync::OnceLock,
     time::Duration,
 };
 
-use anyhow::{anyhow, bail};
-use args::{CoverAction, DownloadRange};
-use clap::{Parser, Subcommand};
-use download::DownloadOptions;
+use anyhow::anyhow;
+use args::{CoverAction, DownloadRange, NovelSort, OutputFormat};
+use check::{UrlChecker, UrlStatus};
+use chrono::{DateTime, NaiveDate, Utc};
+use clap::{Args, Parser, Subcommand};
+use download::{print_progress, DownloadOptions};
+use error::{classify, coded, ErrorCode};
+use host::ExtensionHost;
+use itertools::Itertools;
 use log::{info, warn};
-use quelle_engine::Runtime;
-use quelle_lock::Lock;
-use quelle_persist::{create_parent_all, Persist, PersistOptions};
+use quelle_bundle::{
+    add_to_calibre, bundle_site, part_path, split_chapters, write_library_index, AudioOptions,
+    Bundle, CalibreOptions, CancellationToken, Cancelled, ChapterSelection, EpubOptions,
+    FontOptions, Format, FormatOptions, FrontMatter, ImageOptions, MetadataField, MetadataMapping,
+    OutputTemplate, Part, PartBundle, PartSpan, PdfOptions, SanitizeOptions, SanitizeStep,
+    SanitizedBundle, SiteEntry, SpeechEngine, SplitOptions, Theme, TxtOptions,
+    DEFAULT_PDF_TEMPLATE, PDF_TEMPLATE_FILE,
+};
+use quelle_common::{Field, Query, TitleRules, ValueRange};
+use quelle_core::prelude::{Chapter, TaggedDateTime};
+use quelle_engine::fixtures::{self, Fixtures};
+use quelle_lock::{
+    diff::{diff_extensions, ExtensionSummary},
+    Extension, Lock,
+};
+use quelle_persist::{
+    create_parent_all, diff_lines, text_lines, BundleProfile, ChapterStatus, Compression,
+    ConflictStrategy, DiffLine, Executor, LibraryManager, LockMode, NovelOverrides, Persist,
+    PersistNovel, PersistOptions, SanitizeConfig, SavedNovel, SourceSettings, Task,
+    DEFAULT_LIBRARY,
+};
 use simplelog::{Config, LevelFilter, TermLogger};
 use url::Url;
 
 #[derive(Parser)]
 #[command(author, version, about, long_about = None)]
 struct Cli {
+    #[command(flatten)]
+    global: GlobalArgs,
+
+    #[command(subcommand)]
+    command: Commands,
+}
+
+// The options shared by every command, kept apart so that handlers can borrow
+// them after the command was taken
+#[derive(Args)]
+struct GlobalArgs {
     /// Provide additional information (default only shows errors).
     #[clap(short, long, action = clap::ArgAction::Count)]
     verbose: u8,
@@ -34,8 +83,28 @@ struct Cli {
     #[clap(short, long, default_value = "data")]
     data_dir: PathBuf,
 
-    #[command(subcommand)]
-    command: Commands,
+    /// The named library to use instead of the active one.
+    /// Can also be set with QUELLE_LIBRARY.
+    #[clap(long)]
+    library: Option<String>,
+
+    /// How errors are reported (text, json)
+    #[clap(long, default_value = "text")]
+    output: OutputFormat,
+
+    /// The language of messages (ex: en, es), detected from the environment by default
+    #[clap(long)]
+    lang: Option<String>,
+
+    /// Screen reader friendly output without colors, reporting progress in plain lines.
+    /// Can also be enabled by setting QUELLE_ACCESSIBLE.
+    #[clap(long)]
+    accessible: bool,
+
+    /// Only use the saved library and installed extensions, without network access.
+    /// Can also be enabled by setting QUELLE_OFFLINE.
+    #[clap(long)]
+    offline: bool,
 }
 
 #[derive(Subcommand)]
@@ -50,6 +119,24 @@ enum Commands {
         url: Url,
     },
 
+    /// List the extensions available in the lock file
+    Extensions {
+        /// Only list extensions tagged with the category (ex: lang:en, fanfiction)
+        #[arg(short, long)]
+        category: Option<String>,
+
+        /// Only list extensions in the curated list with this name
+        #[arg(short, long)]
+        list: Option<String>,
+
+        /// Also print the files and branding of each extension
+        #[arg(long)]
+        detailed: bool,
+
+        #[command(subcommand)]
+        action: Option<ExtensionsAction>,
+    },
+
     Download {
         /// The url to the novel
         url: Url,
@@ -65,6 +152,33 @@ enum Commands {
         /// How the novel cover download should be handled
         #[arg(short, long, default_value = "dynamic")]
         cover: CoverAction,
+
+        /// Skip detecting the language of downloaded chapters
+        #[arg(long)]
+        no_detect_lang: bool,
+
+        /// Run the extension in a separate process so that crashes and hangs are contained.
+        /// By default the executor set with the executor command is used, or else the
+        /// cheapest executor known to work for the source.
+        #[arg(long)]
+        isolate: bool,
+
+        /// The stored credential profile to use, remembered for later updates of the novel
+        #[arg(long)]
+        profile: Option<String>,
+
+        /// Download every chapter, even when the same chapter was saved for another novel
+        #[arg(long)]
+        no_cache: bool,
+
+        /// Download the saved chapters again, keeping their previous content when it changed
+        #[arg(long)]
+        refetch: bool,
+
+        /// Download again the chapters fetched with this version of the extension,
+        /// such as one that was released with a bug
+        #[arg(long, value_name = "VERSION")]
+        refetch_version: Option<String>,
     },
 
     Popular {
@@ -78,144 +192,2398 @@ enum Commands {
 
     Bundle {
         url: Url,
+
+        /// Apply the settings of the saved profile, see `quelle profile`
+        #[arg(long)]
+        profile: Option<String>,
+
+        #[command(flatten)]
+        settings: BundleSettings,
+
+        /// Include the novel notes as a front matter page
+        #[arg(long)]
+        notes: bool,
+
+        #[command(flatten)]
+        titles: TitleArgs,
+
+        #[command(flatten)]
+        dates: DateArgs,
+
+        #[command(flatten)]
+        selection: SelectionArgs,
+
+        #[command(flatten)]
+        front_matter: FrontMatterArgs,
+
+        /// Bundle the chapter content as saved, without the cleanup steps of
+        /// `quelle sanitize`
+        #[arg(long)]
+        no_sanitize: bool,
+
+        /// Turn the reading notation of Japanese web novels, such as
+        /// ｜漢字《かんじ》, into ruby shown above the text
+        #[arg(long)]
+        ruby_notation: bool,
+
+        #[command(flatten)]
+        txt: TxtArgs,
+
+        #[command(flatten)]
+        audio: AudioArgs,
+
+        /// Only write the files whose chapters, metadata or options changed since
+        /// they were last bundled, which with --split-chapters rewrites only the
+        /// parts new chapters were added to
+        #[arg(long)]
+        incremental: bool,
+
+        /// Add the written files to the Calibre library, see `quelle calibre`,
+        /// updating the book of the novel when it was added before
+        #[arg(long)]
+        calibre: bool,
+    },
+
+    /// Render saved novels as a static website to read in the browser or self-host
+    Site {
+        /// The novels to render, each in its own directory with a library index
+        /// when there are several
+        #[arg(required_unless_present = "all")]
+        urls: Vec<Url>,
+
+        /// Render every novel of the library
+        #[arg(long, conflicts_with = "urls")]
+        all: bool,
+
+        /// The directory to write the site into
+        #[arg(short, long)]
+        output: PathBuf,
+
+        /// Include the novel notes on the index page
+        #[arg(long)]
+        notes: bool,
+
+        #[command(flatten)]
+        titles: TitleArgs,
+    },
+
+    /// Show information about a saved novel
+    Info {
+        url: Url,
+
+        /// Show which extension version fetched each field and chapter, and when
+        #[arg(long)]
+        provenance: bool,
+
+        /// List the chapters that are not downloaded and why
+        #[arg(long)]
+        chapters: bool,
+    },
+
+    /// Show or change the personal notes of a saved novel
+    Note {
+        url: Url,
+
+        /// The notes in plain text with paragraphs separated by blank lines,
+        /// replacing any existing notes
+        text: Option<String>,
+
+        /// Remove the notes of the novel
+        #[arg(long, conflicts_with = "text")]
+        clear: bool,
+    },
+
+    /// List the previous contents of a chapter kept when it was downloaded again
+    Versions {
+        url: Url,
+
+        /// The position of the chapter in the novel, starting at 1
+        chapter: usize,
+
+        /// Show what changed after the version, 1 being the oldest
+        #[arg(long)]
+        diff: Option<usize>,
+
+        /// Forget the previous contents of the chapter
+        #[arg(long, conflicts_with = "diff")]
+        clear: bool,
+    },
+
+    /// Show or change the license and attribution of a saved novel
+    Rights {
+        url: Url,
+
+        /// The license or attribution, replacing the one reported by the source
+        text: Option<String>,
+
+        /// Remove the license set by the user
+        #[arg(long, conflicts_with = "text")]
+        clear: bool,
+    },
+
+    /// Show or change how the chapter titles of a saved novel are exported,
+    /// replacing the options given to bundle
+    Titles {
+        url: Url,
+
+        #[command(flatten)]
+        titles: TitleArgs,
+
+        /// Remove the title rules of the novel
+        #[arg(long)]
+        clear: bool,
+    },
+
+    /// Show or edit the title, authors, tags and cover of a saved novel,
+    /// kept when the novel is updated from the source
+    Edit {
+        url: Url,
+
+        #[arg(long)]
+        title: Option<String>,
+
+        /// An author of the novel, replacing all the fetched authors
+        #[arg(long = "author")]
+        authors: Vec<String>,
+
+        /// A tag of the novel, replacing all the fetched subjects and tags
+        #[arg(long = "tag")]
+        tags: Vec<String>,
+
+        /// An image to use as the cover
+        #[arg(long)]
+        cover: Option<PathBuf>,
+
+        /// Go back to the fetched value of a field: title, authors, tags or cover
+        #[arg(long, value_parser = ["title", "authors", "tags", "cover"])]
+        reset: Vec<String>,
+    },
+
+    /// List the saved novels matching the query
+    List {
+        /// The query to filter novels with (ex: 'author:"Tappei Nagatsuki" lang:en|ja')
+        ///
+        /// Also accepts status:, tag:, updated:2023-01-01..2023-06-30 and pending:>0
+        query: Option<String>,
+
+        /// The order to list novels in: url, title, updated or pending
+        #[arg(short, long, default_value = "url")]
+        sort: NovelSort,
+
+        /// List novels in the opposite order
+        #[arg(short, long)]
+        reverse: bool,
+    },
+
+    /// Move a saved novel to the trash, from which it can be restored
+    Remove {
+        /// The url of the novel
+        url: Url,
+    },
+
+    /// Combine two saved novels of the same work, moving the duplicate to the trash
+    Merge {
+        /// The url of the novel to keep
+        primary: Url,
+
+        /// The url of the novel to merge into it
+        duplicate: Url,
+    },
+
+    /// Restore or permanently delete the novels in the trash
+    Trash {
+        #[command(subcommand)]
+        action: trash::TrashAction,
+    },
+
+    /// Group novels into collections, such as "Reading" or "Finished"
+    Collection {
+        #[command(subcommand)]
+        action: CollectionAction,
+    },
+
+    /// Record other urls of a saved novel, and switch to one when its source dies
+    Mirror {
+        #[command(subcommand)]
+        action: mirror::MirrorAction,
+    },
+
+    /// Check that the urls of the saved novels still work, without updating the novels
+    CheckUrls {
+        /// Delay between requests to the same website in milliseconds
+        #[arg(long, default_value = "1000")]
+        delay: u64,
+
+        /// Save the new url of novels that moved
+        #[arg(long)]
+        update: bool,
+    },
+
+    /// Manage the accounts used to access sources
+    Credentials {
+        #[command(subcommand)]
+        action: credentials::CredentialsAction,
+    },
+
+    /// Bring the library up to date with this release
+    Migrate {
+        /// Only list the pending migrations without running them
+        #[arg(long)]
+        dry_run: bool,
+    },
+
+    /// Set how chapter content is compressed and rewrite the downloaded chapters
+    Compress {
+        /// The compression of the chapters: none, gzip or zstd
+        method: Compression,
+    },
+
+    /// Encrypt the novels of the library with the passphrase in QUELLE_PASSPHRASE
+    Encrypt,
+
+    /// Choose how extensions are executed for each command or source
+    Executor {
+        #[command(subcommand)]
+        action: executor::ExecutorAction,
+    },
+
+    /// Manage the named libraries, such as one for fan fiction or an archive
+    Library {
+        #[command(subcommand)]
+        action: LibraryAction,
+    },
+
+    /// Manage the named sets of bundle settings
+    Profile {
+        #[command(subcommand)]
+        action: ProfileAction,
+    },
+
+    /// Manage the Typst template PDF bundles are laid out with
+    Template {
+        #[command(subcommand)]
+        action: TemplateAction,
+    },
+
+    /// Manage the Calibre library bundles are added to with `bundle --calibre`
+    Calibre {
+        #[command(subcommand)]
+        action: calibre::CalibreAction,
+    },
+
+    /// Choose where bundles write the metadata collected by extensions
+    Metadata {
+        #[command(subcommand)]
+        action: MetadataAction,
+    },
+
+    /// Choose how chapter content is cleaned up before it is bundled
+    Sanitize {
+        #[command(subcommand)]
+        action: sanitize::SanitizeAction,
+    },
+
+    /// Change the settings of a source
+    Source {
+        #[command(subcommand)]
+        action: SourceAction,
+    },
+
+    /// Manage where and how the library is stored
+    Storage {
+        #[command(subcommand)]
+        action: storage::StorageAction,
+    },
+
+    /// Pack the whole library into a single archive
+    Backup {
+        /// The archive to write (ex: library.tar.zst)
+        path: PathBuf,
+    },
+
+    /// Add the novels of existing EPUB files to the library
+    Import {
+        /// The EPUB files to import
+        #[arg(required = true)]
+        paths: Vec<PathBuf>,
+    },
+
+    /// Restore the novels of a backup into the library
+    Restore {
+        /// The archive written by the backup command
+        path: PathBuf,
+
+        /// What to do with files that already exist: skip, overwrite or fail
+        #[arg(long, default_value = "skip")]
+        on_conflict: ConflictStrategy,
+    },
+
+    /// Run housekeeping tasks, such as cache pruning and backups, at night
+    Maintenance {
+        #[command(subcommand)]
+        action: maintenance::MaintenanceAction,
+    },
+
+    /// Show information about the library
+    Status {
+        /// Show how often each executor succeeded for every source
+        #[arg(long)]
+        sources: bool,
+    },
+
+    /// Serve extension requests over stdin and stdout, used by --isolate
+    #[command(hide = true)]
+    Worker {
+        /// The wasm extension to run
+        path: PathBuf,
     },
 }
 
-#[tokio::main]
-async fn main() -> anyhow::Result<()> {
-    let cli = Cli::parse();
+#[derive(Args)]
+struct TitleArgs {
+    /// Remove chapter numbers repeated at the start of titles (ex: "Chapter 12 - ")
+    #[arg(long)]
+    strip_title_prefix: bool,
 
-    let level = match cli.verbose {
-        0 => LevelFilter::Error,
-        1 => LevelFilter::Warn,
-        2 => LevelFilter::Info,
-        3 => LevelFilter::Debug,
-        _ => LevelFilter::Trace,
-    };
+    /// The template of chapter titles using {number}, {title} and {position}
+    /// (ex: "Chapter {number}: {title}")
+    #[arg(long)]
+    title_template: Option<String>,
 
-    TermLogger::init(
-        level,
-        Config::default(),
-        simplelog::TerminalMode::Mixed,
-        simplelog::ColorChoice::Auto,
-    )
-    .unwrap();
+    /// Number chapters sequentially across volumes
+    #[arg(long)]
+    renumber: bool,
+}
 
-    run(cli).await
+impl From<TitleArgs> for TitleRules {
+    fn from(value: TitleArgs) -> Self {
+        TitleRules {
+            strip_prefix: value.strip_title_prefix,
+            template: value.title_template,
+            renumber: value.renumber,
+        }
+    }
 }
 
-async fn run(cli: Cli) -> anyhow::Result<()> {
-    match cli.command {
-        Commands::Detect { url } => {
-            let lock = Lock::open(&cli.lock_file)?;
+#[derive(Args)]
+struct TxtArgs {
+    /// The line written between chapters of txt bundles
+    #[arg(long, default_value = "* * *")]
+    txt_separator: String,
 
-            let extension = lock
-                .extensions
-                .into_iter()
-                .map(|(_, e)| e)
-                .find(|e| e.base_urls.iter().any(|bu| url.as_str().starts_with(bu)));
+    /// Wrap the lines of txt bundles at this many characters
+    #[arg(long)]
+    txt_width: Option<usize>,
+}
 
-            match extension {
-                Some(extension) => println!("{extension:#?}"),
-                None => println!("No source matching '{url}' found"),
-            }
+impl From<TxtArgs> for TxtOptions {
+    fn from(value: TxtArgs) -> Self {
+        TxtOptions {
+            separator: value.txt_separator,
+            width: value.txt_width,
         }
-        Commands::Lock { dir } => {
-            let lock = Lock::generate(&dir).await?;
-            lock.save(&cli.lock_file)?;
-            info!("Saved lock file to '{}'", cli.lock_file.display());
+    }
+}
+
+#[derive(Args)]
+struct FrontMatterArgs {
+    /// Leave out the title page of epub and txt bundles
+    #[arg(long)]
+    no_title_page: bool,
+
+    /// Leave out the synopsis page of epub and txt bundles
+    #[arg(long)]
+    no_synopsis: bool,
+
+    /// Leave out the colophon with the source and dates of epub and txt bundles
+    #[arg(long)]
+    no_colophon: bool,
+}
+
+impl From<FrontMatterArgs> for FrontMatter {
+    fn from(value: FrontMatterArgs) -> Self {
+        FrontMatter {
+            title_page: !value.no_title_page,
+            synopsis: !value.no_synopsis,
+            colophon: !value.no_colophon,
         }
-        Commands::Download {
-            url,
-            range,
-            delay,
-            cover,
-        } => {
-            let persist = Persist::new(PersistOptions::default());
+    }
+}
 
-            let lock = Lock::open(&cli.lock_file)?;
-            let Some(extension) = lock.detect(url.as_str())? else {
-                println!("supported source not found.");
-                exit(1);
-            };
+/// The bundle settings that can be saved in a profile
+#[derive(Args)]
+struct BundleSettings {
+    /// The formats to bundle into, separated by commas: epub, kepub, fb2, cbz,
+    /// pdf, m4b, mp3 or txt (ex: epub,txt), epub by default
+    #[arg(short, long, value_delimiter = ',')]
+    format: Vec<Format>,
 
-            let options = DownloadOptions {
-                dir: cli.data_dir,
-                range: range.map(|r| r.0),
-                delay: delay.map(|v| Duration::from_millis(v as u64)),
-                cover,
-            };
+    /// The output path template (ex: "~/Books/{author}/{title} - {chapters} ch.{ext}")
+    #[arg(short, long)]
+    output: Option<String>,
+
+    /// The theme of epub bundles: serif, sans, sepia, high-contrast, or the path
+    /// of a stylesheet or of a directory with stylesheets and their fonts
+    #[arg(long)]
+    epub_theme: Option<Theme>,
+
+    /// The Typst template of pdf bundles, instead of the template of the library
+    #[arg(long)]
+    pdf_template: Option<PathBuf>,
+
+    /// Set Japanese and Chinese novels in vertical columns in pdf bundles, read
+    /// from right to left
+    #[arg(long)]
+    pdf_vertical: bool,
 
-            download::download(persist, url, PathBuf::from(&extension.path), options).await?;
+    /// A font to embed into epub and pdf bundles, for scripts e-readers lack
+    /// the glyphs of, repeated in the order the fonts are preferred
+    #[arg(long = "font")]
+    fonts: Vec<PathBuf>,
+
+    /// Embed whole fonts into epub bundles instead of only the glyphs the novel uses
+    #[arg(long)]
+    no_font_subset: bool,
+
+    #[command(flatten)]
+    images: ImageArgs,
+
+    /// Split the output into a part per volume, within the other split limits
+    #[arg(long)]
+    split_volumes: bool,
+
+    /// Split the output into parts of at most this many chapters
+    #[arg(long)]
+    split_chapters: Option<usize>,
+
+    /// Split the output into parts of at most this many megabytes of chapter content
+    #[arg(long)]
+    split_size: Option<u64>,
+}
+
+impl BundleSettings {
+    /// Fill in the settings that were not given from the profile
+    fn with_profile(self, profile: BundleProfile) -> anyhow::Result<Self> {
+        let format = if self.format.is_empty() {
+            profile
+                .formats
+                .iter()
+                .map(|format| format.parse().map_err(|e: String| anyhow!(e)))
+                .collect::<anyhow::Result<_>>()?
+        } else {
+            self.format
+        };
+        let epub_theme = match (self.epub_theme, profile.epub_theme) {
+            (None, Some(theme)) => Some(theme.parse().map_err(|e: String| anyhow!(e))?),
+            (theme, _) => theme,
+        };
+
+        Ok(Self {
+            format,
+            output: self.output.or(profile.output),
+            epub_theme,
+            pdf_template: self.pdf_template.or(profile.pdf_template),
+            pdf_vertical: self.pdf_vertical || profile.pdf_vertical,
+            fonts: if self.fonts.is_empty() {
+                profile.fonts
+            } else {
+                self.fonts
+            },
+            no_font_subset: self.no_font_subset || profile.no_font_subset,
+            images: ImageArgs {
+                include_images: self.images.include_images || profile.include_images,
+                image_max_width: self.images.image_max_width.or(profile.image_max_width),
+            },
+            split_volumes: self.split_volumes || profile.split_volumes,
+            split_chapters: self.split_chapters.or(profile.split_chapters),
+            split_size: self.split_size.or(profile.split_size),
+        })
+    }
+}
+
+impl From<BundleSettings> for BundleProfile {
+    fn from(value: BundleSettings) -> Self {
+        BundleProfile {
+            formats: value.format.iter().map(ToString::to_string).collect(),
+            output: value.output,
+            epub_theme: value.epub_theme.as_ref().map(ToString::to_string),
+            pdf_template: value.pdf_template,
+            pdf_vertical: value.pdf_vertical,
+            fonts: value.fonts,
+            no_font_subset: value.no_font_subset,
+            include_images: value.images.include_images,
+            image_max_width: value.images.image_max_width,
+            split_volumes: value.split_volumes,
+            split_chapters: value.split_chapters,
+            split_size: value.split_size,
         }
-        Commands::Popular { url, page } => {
-            let lock = Lock::open(&cli.lock_file)?;
-            let Some(extension) = lock.detect(url.as_str())? else {
-                println!("supported source not found.");
-                exit(1);
-            };
+    }
+}
 
-            let mut runner = Runtime::new(Path::new(&extension.path)).await?;
-            let meta = runner.meta().await?;
+#[derive(Args)]
+struct ImageArgs {
+    /// Embed the images of chapters into epub and pdf bundles, downloading the
+    /// ones that are not stored yet
+    #[arg(long)]
+    include_images: bool,
 
-            if !runner.popular_supported() {
-                log::error!("'{}' does not support popular browse", meta.name);
-                exit(1);
-            }
+    /// Scale embedded images wider than this many pixels down, using ImageMagick
+    #[arg(long, requires = "include_images")]
+    image_max_width: Option<u32>,
+}
 
-            log::info!("fetching popular from '{}'", meta.name);
-            let novels = runner.popular(page).await?;
-            if novels.is_empty() {
-                log::error!("No novels found");
-            }
+impl From<ImageArgs> for ImageOptions {
+    fn from(value: ImageArgs) -> Self {
+        ImageOptions {
+            include_images: value.include_images,
+            max_width: value.image_max_width,
+            ..Default::default()
+        }
+    }
+}
 
-            for novel in novels {
-                println!("{} <{}>", novel.title, novel.url);
-            }
+#[derive(Args)]
+struct AudioArgs {
+    /// The speech engine reading m4b and mp3 bundles: espeak or piper
+    #[arg(long, default_value = "espeak", value_parser = ["espeak", "piper"])]
+    tts_engine: String,
+
+    /// The voice of espeak, or the path of the voice model of piper
+    #[arg(long, required_if_eq("tts_engine", "piper"))]
+    tts_voice: Option<String>,
+
+    /// A speech program to use instead of the engine, reading text on stdin and
+    /// writing a WAV file to {output} given in its arguments
+    #[arg(long)]
+    tts_command: Option<String>,
+
+    /// An argument of the speech program, once per argument
+    /// (ex: --tts-arg=--out --tts-arg={output})
+    #[arg(long = "tts-arg", requires = "tts_command", allow_hyphen_values = true)]
+    tts_args: Vec<String>,
+
+    /// The ffmpeg program used to encode audiobooks
+    #[arg(long, default_value = "ffmpeg")]
+    ffmpeg: String,
+}
+
+impl From<AudioArgs> for AudioOptions {
+    fn from(value: AudioArgs) -> Self {
+        let engine = match (value.tts_command, value.tts_engine.as_str()) {
+            (Some(program), _) => SpeechEngine::Command {
+                program,
+                args: value.tts_args,
+            },
+            (None, "piper") => SpeechEngine::Piper {
+                model: value.tts_voice.unwrap_or_default(),
+            },
+            (None, _) => SpeechEngine::Espeak {
+                voice: value.tts_voice,
+            },
+        };
+
+        AudioOptions {
+            engine,
+            ffmpeg: value.ffmpeg,
         }
-        Commands::Bundle { url } => {
-            let persist = Persist::new(PersistOptions::default());
-            let global = persist.read_global()?;
-            info!("Loaded global data");
+    }
+}
 
-            let path = global
-                .novel_path_from_url(&url.to_string())
-                .ok_or(anyhow!("The novel does not exist"))?;
+#[derive(Args)]
+struct DateArgs {
+    /// Only include chapters updated on or after the date (ex: 2023-01-01)
+    #[arg(long)]
+    since: Option<NaiveDate>,
 
-            info!("Found novel data at '{}'.", path.display());
+    /// Only include chapters updated on or before the date (ex: 2023-12-31)
+    #[arg(long)]
+    until: Option<NaiveDate>,
+}
 
-            let lock = Lock::open(&cli.lock_file)?;
-            let meta = if let Some(ext) = lock.detect(url.as_str())? {
-                let path = Path::new(&ext.path);
-                if !path.exists() {
-                    bail!("The wasm extension file could not be found");
-                }
+impl DateArgs {
+    fn is_empty(&self) -> bool {
+        self.since.is_none() && self.until.is_none()
+    }
 
-                let mut runner = Runtime::new(path).await?;
-                let meta = runner.meta().await?;
-                info!("Acquired source meta information from wasm file.");
+    /// Whether the chapter was updated within the dates, chapters without a date never are
+    fn contains(&self, chapter: &Chapter) -> bool {
+        let Some(date) = chapter.updated_at.as_ref().map(TaggedDateTime::date) else {
+            return false;
+        };
 
-                Some(meta)
-            } else {
-                warn!("failed to retrieve meta information for the url");
-                None
-            };
+        self.since.is_none_or(|since| date >= since) && self.until.is_none_or(|until| date <= until)
+    }
+}
 
-            let novel = persist.persist_novel(path.into());
-            let data = novel.read_data()?.ok_or(anyhow!("novel data not found"))?;
+#[derive(Args)]
+struct SelectionArgs {
+    /// Only include the chapters at these positions, separated by commas (ex: 100-250,300)
+    #[arg(long, value_delimiter = ',', value_parser = parse_position_range)]
+    chapters: Vec<ValueRange<usize>>,
 
-            info!("Loaded novel information from disk");
+    /// Only include the volumes with these numbers, separated by commas (ex: 2,4-5)
+    #[arg(long, value_delimiter = ',', value_parser = parse_position_range)]
+    volumes: Vec<ValueRange<usize>>,
 
-            let output_path =
-                path.join(format!("output/{}.epub", slug::slugify(&data.novel.title)));
-            create_parent_all(&output_path)?;
+    /// Only include the chapters added since the novel was last bundled, the ones
+    /// not read yet on the e-reader
+    #[arg(long)]
+    unread: bool,
+}
 
-            let mut file = BufWriter::new(File::create(&output_path)?);
+/// A range of positions written as `a-b` or as a query range such as `a..b` or `>a`
+fn parse_position_range(value: &str) -> Result<ValueRange<usize>, String> {
+    match value.split_once('-') {
+        Some((start, end)) if !start.is_empty() => format!("{start}..{end}").parse(),
+        _ => value.parse(),
+    }
+}
 
-            info!("Writing to '{}'", &output_path.display());
+#[derive(Subcommand)]
+enum ExtensionsAction {
+    /// Verify that an installed extension works using its bundled fixtures
+    Test {
+        /// The id of the extension
+        id: String,
+    },
+    /// Show what changed in the meta information, capabilities, filters and size
+    /// between two versions of an extension
+    Diff {
+        /// The id of an installed extension or the path to a wasm or package
+        old: String,
 
-            bundle::compile_epub(meta, data, path.to_path_buf(), &mut file)
-                .map_err(|e| anyhow!("failed to bundle epub: {}", e.to_string()))?;
+        /// The id of an installed extension or the path to a wasm or package
+        new: String,
+    },
+}
+
+#[derive(Subcommand)]
+enum LibraryAction {
+    /// Add a library stored in the directory
+    Add { name: String, dir: PathBuf },
+    /// Forget a library, leaving its files in place
+    Remove { name: String },
+    /// Use the library when none is given with --library
+    Use { name: String },
+    /// List the libraries, marking the active one
+    List,
+}
+
+#[derive(Subcommand)]
+enum ProfileAction {
+    /// Save the bundle settings under the name, replacing the profile of that name
+    Save {
+        name: String,
+
+        #[command(flatten)]
+        settings: BundleSettings,
+    },
+    /// Show the saved profiles and their settings
+    List,
+    /// Remove the profile
+    Remove { name: String },
+}
+
+#[derive(Subcommand)]
+enum TemplateAction {
+    /// Write the default template into a directory to change it there
+    Export { dir: PathBuf },
+    /// Lay out PDF bundles of the library with the template file or directory
+    Use { path: PathBuf },
+    /// Go back to the default template
+    Reset,
+    /// Show the template used by the library
+    Show,
+}
+
+#[derive(Subcommand)]
+enum MetadataAction {
+    /// Write the metadata of the name to a Dublin Core element (ex: dc:subject),
+    /// a Calibre column (ex: calibre:#translator), or leave it out with skip
+    Map { name: String, field: MetadataField },
+    /// Go back to the default field of the metadata
+    Unmap { name: String },
+    /// Show the fields metadata is mapped to
+    List,
+}
+
+#[derive(Subcommand)]
+enum SourceAction {
+    /// Send the age gate of the source so that mature content is downloaded
+    Mature {
+        /// The id of the source
+        source: String,
+
+        /// Hide mature content of the source again
+        #[arg(long)]
+        disable: bool,
+    },
+    /// Show the settings of every configured source
+    List,
+}
+
+#[derive(Subcommand)]
+enum CollectionAction {
+    /// Create an empty collection
+    Create { name: String },
+
+    /// Delete a collection, keeping its novels in the library
+    Delete { name: String },
+
+    /// Add a saved novel to a collection, creating the collection if needed
+    Add {
+        name: String,
+
+        /// The url of the novel
+        url: Url,
+    },
+
+    /// Remove a novel from a collection
+    Remove {
+        name: String,
+
+        /// The url of the novel
+        url: Url,
+    },
+
+    /// List the collections and the number of novels in each
+    List,
+}
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    let Cli {
+        global: mut cli,
+        command,
+    } = Cli::parse();
+    cli.accessible |= std::env::var_os("QUELLE_ACCESSIBLE").is_some();
+    cli.offline |= std::env::var_os("QUELLE_OFFLINE").is_some();
+    let library = cli.library.clone().or_else(|| {
+        std::env::var(LIBRARY_VAR)
+            .ok()
+            .filter(|value| !value.is_empty())
+    });
+    LIBRARY.get_or_init(|| library);
+
+    let level = match cli.verbose {
+        0 => LevelFilter::Error,
+        1 => LevelFilter::Warn,
+        2 => LevelFilter::Info,
+        3 => LevelFilter::Debug,
+        _ => LevelFilter::Trace,
+    };
+
+    TermLogger::init(
+        level,
+        Config::default(),
+        simplelog::TerminalMode::Mixed,
+        if cli.accessible {
+            simplelog::ColorChoice::Never
+        } else {
+            simplelog::ColorChoice::Auto
+        },
+    )
+    .unwrap();
+
+    i18n::init(cli.lang.as_deref());
+
+    let output = cli.output;
+    if let Err(error) = run(cli, command).await {
+        error::report(&error, output);
+        exit(1);
+    }
+
+    Ok(())
+}
+
+fn read_saved_novel<'a>(
+    persist: &'a Persist,
+    url: &Url,
+) -> anyhow::Result<(PersistNovel<'a>, SavedNovel)> {
+    let global = persist.read_global()?;
+
+    let path = global
+        .novel_path_from_url(url.as_str())
+        .ok_or_else(|| coded(ErrorCode::NovelNotFound, t!("novel-not-found")))?;
+
+    let novel = persist.persist_novel(path.into());
+    let data = novel
+        .read_data()?
+        .ok_or_else(|| coded(ErrorCode::NovelNotFound, t!("novel-data-not-found")))?;
+
+    Ok((novel, data))
+}
+
+/// The environment variable holding the passphrase of an encrypted library
+const PASSPHRASE_VAR: &str = "QUELLE_PASSPHRASE";
+
+/// The environment variable holding the password of the Calibre content server
+const CALIBRE_PASSWORD_VAR: &str = "QUELLE_CALIBRE_PASSWORD";
+
+fn passphrase() -> Option<String> {
+    std::env::var(PASSPHRASE_VAR)
+        .ok()
+        .filter(|value| !value.is_empty())
+}
+
+/// Set to wait for other processes to release the library instead of failing
+const WAIT_FOR_LOCK_VAR: &str = "QUELLE_WAIT_FOR_LOCK";
+
+fn wait_for_lock() -> bool {
+    std::env::var_os(WAIT_FOR_LOCK_VAR).is_some()
+}
+
+/// The file listing the named libraries
+const LIBRARIES_FILE: &str = "libraries.json";
+
+/// The environment variable selecting the library, like --library
+const LIBRARY_VAR: &str = "QUELLE_LIBRARY";
+
+/// The library selected for this run, the active one if none
+static LIBRARY: OnceLock<Option<String>> = OnceLock::new();
+
+/// The layout of the selected library
+fn library_options() -> anyhow::Result<PersistOptions> {
+    let manager = LibraryManager::open(Path::new(LIBRARIES_FILE))?;
+    let name = LIBRARY.get().cloned().flatten();
+    manager
+        .options(name.as_deref())
+        .ok_or_else(|| anyhow!(t!("library-not-found", name = name.unwrap_or_default())))
+}
+
+/// Open the library to write to it, locking out other processes
+fn open_persist() -> anyhow::Result<Persist> {
+    open_persist_with(LockMode::Exclusive)
+}
+
+/// Open the library to only read from it, allowing other readers
+fn open_persist_shared() -> anyhow::Result<Persist> {
+    open_persist_with(LockMode::Shared)
+}
+
+/// Open the library, migrating it to the layout of this release
+fn open_persist_with(mode: LockMode) -> anyhow::Result<Persist> {
+    let mut persist = Persist::new(library_options()?);
+    persist.lock(mode, wait_for_lock())?;
+    persist.unlock(passphrase().as_deref())?;
+
+    // Migrations write to the library, which readers may not do
+    if mode == LockMode::Shared && !persist.pending_migrations()?.is_empty() {
+        persist.lock(LockMode::Exclusive, wait_for_lock())?;
+    }
+
+    let report = persist.initialize()?;
+    for (version, description) in &report.migrations {
+        info!("Migrated the library to version {version}: {description}");
+    }
+
+    Ok(persist)
+}
+
+fn contains_ignore_case(value: &str, pattern: &str) -> bool {
+    value.to_lowercase().contains(&pattern.to_lowercase())
+}
+
+/// Write the lock file of the extensions found in the directory
+async fn generate_lock(lock_file: &Path, dir: &Path) -> anyhow::Result<()> {
+    let mut lock = Lock::generate(dir).await?;
+
+    // Curated lists are maintained by hand and must survive regeneration
+    if lock_file.exists() {
+        lock.lists = open_lock(lock_file)?.lists;
+    }
+
+    lock.save(lock_file)?;
+    info!("Saved lock file to '{}'", lock_file.display());
+    Ok(())
+}
+
+fn open_lock(path: &Path) -> anyhow::Result<Lock> {
+    Lock::open(path).map_err(|e| {
+        coded(
+            ErrorCode::LockUnreadable,
+            t!("lock-file-unreadable", path = path.display(), reason = e),
+        )
+    })
+}
+
+/// The estimated reading time in hours and minutes, ex: `12h 05m`
+fn format_reading_time(time: Duration) -> String {
+    let minutes = time.as_secs() / 60;
+    format!("{}h {:02}m", minutes / 60, minutes % 60)
+}
+
+/// Print the chapters that are not downloaded with the reason
+fn print_chapter_states(data: &SavedNovel) {
+    let chapters = data
+        .novel
+        .volumes
+        .iter()
+        .flat_map(|volume| &volume.chapters);
+    for (number, chapter) in chapters.enumerate() {
+        let (number, title) = (number + 1, chapter.title.as_str());
+        let line = match data.chapter_status(&chapter.url) {
+            ChapterStatus::Downloaded => continue,
+            ChapterStatus::Pending => t!("chapter-pending", number, title),
+            ChapterStatus::Queued => t!("chapter-queued", number, title),
+            ChapterStatus::Failed { error, attempts } => {
+                t!("chapter-status-failed", number, title, attempts, error)
+            }
+            ChapterStatus::Skipped { reason, attempts } => {
+                t!("chapter-status-skipped", number, title, attempts, reason)
+            }
+        };
+        println!("{line}");
+    }
+}
+
+/// Print the fields edited by the user
+fn print_overrides(overrides: &NovelOverrides) {
+    if overrides.is_empty() {
+        println!("{}", t!("no-overrides"));
+        return;
+    }
+
+    if let Some(title) = &overrides.title {
+        println!("title: {title}");
+    }
+    if let Some(authors) = &overrides.authors {
+        println!("authors: {}", authors.join(", "));
+    }
+    if let Some(tags) = &overrides.tags {
+        println!("tags: {}", tags.join(", "));
+    }
+    if let Some(cover) = &overrides.cover {
+        println!("cover: {}", cover.path.display());
+    }
+}
+
+/// Print the extension versions that produced the fields and chapters of the novel
+fn print_provenance(data: &SavedNovel) {
+    if data.provenance.is_empty() && data.chapter_provenance.is_empty() {
+        println!("{}", t!("provenance-unknown"));
+        return;
+    }
+
+    for (field, provenance) in &data.provenance {
+        println!(
+            "{}",
+            t!(
+                "provenance-field",
+                field = field,
+                source = provenance.source,
+                version = provenance.version,
+                date = provenance.fetched_at.format("%Y-%m-%d %H:%M")
+            )
+        );
+    }
+
+    // Chapters are summarized by the extension version that fetched them
+    let mut versions = BTreeMap::<(&str, &str), (usize, DateTime<Utc>)>::new();
+    for provenance in data.chapter_provenance.values() {
+        let entry = versions
+            .entry((&provenance.source, &provenance.version))
+            .or_insert((0, provenance.fetched_at));
+        entry.0 += 1;
+        entry.1 = entry.1.max(provenance.fetched_at);
+    }
+
+    for ((source, version), (count, last)) in versions {
+        println!(
+            "{}",
+            t!(
+                "provenance-chapters",
+                count = count,
+                source = source,
+                version = version,
+                date = last.format("%Y-%m-%d %H:%M")
+            )
+        );
+    }
+
+    let unknown = data
+        .downloaded
+        .len()
+        .saturating_sub(data.chapter_provenance.len());
+    if unknown > 0 {
+        println!("{}", t!("provenance-chapters-unknown", count = unknown));
+    }
+}
+
+/// The wasm of the installed extension with the id, or else the value as a path
+fn extension_path(lock: &Lock, value: &str) -> PathBuf {
+    match lock.extensions.get(value) {
+        Some(extension) => extension.path.clone(),
+        None => PathBuf::from(value),
+    }
+}
+
+/// Print the files of the extension and its branding, one `key: value` per line
+/// so that other programs can read them
+fn print_extension_details(extension: &Extension) {
+    println!("  wasm: {}", extension.path.display());
+    let files = [
+        ("icon", &extension.icon),
+        ("changelog", &extension.changelog),
+        ("fixtures", &extension.fixtures),
+    ];
+    for (key, path) in files {
+        if let Some(path) = path {
+            println!("  {key}: {}", path.display());
+        }
+    }
+    if let Some(accent) = &extension.accent {
+        println!("  accent: {accent}");
+    }
+    if !extension.base_urls.is_empty() {
+        println!("  urls: {}", extension.base_urls.join(", "));
+    }
+}
+
+fn write_bundle<B: Bundle>(
+    format: Format,
+    bundle: &B,
+    path: &Path,
+    options: &FormatOptions,
+    cancel: &CancellationToken,
+) -> anyhow::Result<()> {
+    create_parent_all(path)?;
+    let mut file = BufWriter::new(File::create(path)?);
+
+    info!("Writing to '{}'", path.display());
+
+    let progress = |event| print_progress(&event);
+    match format.bundle(bundle, &mut file, options, progress, cancel) {
+        Ok(()) => Ok(()),
+        Err(e) if e.is::<Cancelled>() => {
+            drop(file);
+            let _ = fs::remove_file(path);
+            Err(coded(
+                ErrorCode::BundleFailed,
+                t!("bundle-cancelled", format = format, path = path.display()),
+            ))
+        }
+        Err(e) => Err(coded(
+            ErrorCode::BundleFailed,
+            t!("bundle-failed", format = format, reason = e),
+        )),
+    }
+}
+
+/// The cleanup steps and watermarks of the library
+fn sanitize_options(config: &SanitizeConfig) -> anyhow::Result<SanitizeOptions> {
+    SanitizeOptions::new(&config.disabled, &config.watermarks).map_err(|e| anyhow!(e))
+}
+
+/// Add the written bundle to the Calibre library
+fn push_to_calibre<B: Bundle>(
+    options: &CalibreOptions,
+    bundle: &B,
+    path: &Path,
+) -> anyhow::Result<()> {
+    let identifier = bundle::calibre_identifier(bundle);
+    let book = add_to_calibre(options, bundle.novel(), &identifier, path).map_err(|e| {
+        coded(
+            ErrorCode::BundleFailed,
+            t!("calibre-failed", path = path.display(), reason = e),
+        )
+    })?;
+
+    if book.added {
+        println!(
+            "{}",
+            t!("calibre-added", path = path.display(), id = book.id)
+        );
+    } else {
+        println!(
+            "{}",
+            t!("calibre-updated", path = path.display(), id = book.id)
+        );
+    }
+    Ok(())
+}
+
+async fn run(cli: GlobalArgs, command: Commands) -> anyhow::Result<()> {
+    match command {
+        Commands::Detect { url } => {
+            let lock = open_lock(&cli.lock_file)?;
+
+            let extension = lock
+                .extensions
+                .into_iter()
+                .map(|(_, e)| e)
+                .find(|e| e.base_urls.iter().any(|bu| url.as_str().starts_with(bu)));
+
+            match extension {
+                Some(extension) => println!("{extension:#?}"),
+                None => println!("{}", t!("no-source-matching", url = url)),
+            }
+        }
+        Commands::Extensions {
+            action: Some(ExtensionsAction::Test { id }),
+            ..
+        } => {
+            let lock = open_lock(&cli.lock_file)?;
+            let extension = lock.extensions.get(&id).ok_or_else(|| {
+                coded(
+                    ErrorCode::SourceNotSupported,
+                    t!("extension-not-found", id = id),
+                )
+            })?;
+
+            let fixtures_path = extension.fixtures.as_ref().ok_or_else(|| {
+                coded(ErrorCode::FixturesMissing, t!("fixtures-missing", id = id))
+            })?;
+
+            let recorded = Fixtures::open(fixtures_path)?;
+            let checks = fixtures::verify(&extension.path, recorded).await?;
+
+            let mut failures = 0;
+            for check in checks {
+                let url = check.url;
+                match check.result {
+                    Ok(_) => println!("{}", t!("fixture-passed", url)),
+                    Err(reason) => {
+                        failures += 1;
+                        println!("{}", t!("fixture-failed", url, reason));
+                    }
+                }
+            }
+
+            if failures > 0 {
+                return Err(coded(
+                    ErrorCode::ExtensionTestFailed,
+                    t!("extension-test-failed", id = id, count = failures),
+                ));
+            }
+        }
+        Commands::Extensions {
+            action: Some(ExtensionsAction::Diff { old, new }),
+            ..
+        } => {
+            let lock = open_lock(&cli.lock_file)?;
+            let old = ExtensionSummary::load(&extension_path(&lock, &old)).await?;
+            let new = ExtensionSummary::load(&extension_path(&lock, &new)).await?;
+
+            let changes = diff_extensions(&old, &new);
+            if changes.is_empty() {
+                println!("{}", t!("extension-unchanged"));
+            }
+            for change in changes {
+                println!("{change}");
+            }
+        }
+        Commands::Extensions {
+            category,
+            list,
+            detailed,
+            ..
+        } => {
+            let lock = open_lock(&cli.lock_file)?;
+
+            let mut extensions = match &list {
+                Some(name) => lock
+                    .curated(name)
+                    .ok_or_else(|| anyhow!(t!("no-curated-list", name = name)))?,
+                None => lock.extensions.iter().collect(),
+            };
+
+            if let Some(category) = &category {
+                extensions.retain(|(_, extension)| extension.has_category(category));
+            }
+
+            extensions.sort_by_key(|(id, _)| *id);
+            for (id, extension) in extensions {
+                println!(
+                    "{id} {} v{} [{}]",
+                    extension.name,
+                    extension.version,
+                    extension.categories.join(", ")
+                );
+
+                if detailed {
+                    print_extension_details(extension);
+                }
+            }
+        }
+        Commands::Lock { dir } => generate_lock(&cli.lock_file, &dir).await?,
+        Commands::Download {
+            url,
+            range,
+            delay,
+            cover,
+            no_detect_lang,
+            isolate,
+            profile,
+            no_cache,
+            refetch,
+            refetch_version,
+        } => {
+            let persist = open_persist()?;
+
+            // Read up front, as the library is handed to the download
+            let saved = read_saved_novel(&persist, &url)
+                .ok()
+                .map(|(_, data)| data.novel.title);
+            let print_saved_copy = || {
+                if let Some(title) = &saved {
+                    println!("{}", t!("offline-saved-copy", title = title));
+                }
+            };
+
+            if let Err(error) = network::require_online(cli.offline) {
+                print_saved_copy();
+                return Err(error);
+            }
+
+            let lock = open_lock(&cli.lock_file)?;
+            let Some((id, extension)) = lock.find(url.as_str()) else {
+                return Err(coded(
+                    ErrorCode::SourceNotSupported,
+                    t!("no-supported-source", url = url),
+                ));
+            };
+
+            if extension.is_novel_url(url.as_str()) == Some(false) {
+                println!(
+                    "{}",
+                    t!("url-not-novel", url = url, source = extension.name)
+                );
+            }
+
+            let options = DownloadOptions {
+                dir: cli.data_dir,
+                range: range.map(|r| r.0),
+                delay: delay.map(|v| Duration::from_millis(v as u64)),
+                cover,
+                detect_lang: !no_detect_lang,
+                executor: if isolate {
+                    Executor::Isolated
+                } else {
+                    let global = persist.read_global()?;
+                    let task = match global.novel_path_from_url(url.as_str()) {
+                        Some(_) => Task::Update,
+                        None => Task::Add,
+                    };
+                    match persist.read_config()?.executors.resolve(id, task) {
+                        Some(executor) => executor,
+                        None => persist.read_sources()?.preferred(id),
+                    }
+                },
+                accessible: cli.accessible,
+                profile,
+                shared_cache: !no_cache,
+                refetch,
+                refetch_version,
+            };
+
+            info!("Using the {:?} executor", options.executor);
+
+            let path = PathBuf::from(&extension.path);
+            if let Err(error) = download::download(persist, id, url.clone(), path, options).await {
+                let error = network::explain_offline(error, &url);
+                if classify(&error) == ErrorCode::Offline {
+                    print_saved_copy();
+                }
+                return Err(error);
+            }
+        }
+        Commands::Popular { url, page } => {
+            let mut host = ExtensionHost::new(cli.lock_file.clone());
+            let Some(runner) = host.runtime(url.as_str()).await? else {
+                return Err(coded(
+                    ErrorCode::SourceNotSupported,
+                    t!("no-supported-source", url = url),
+                ));
+            };
+
+            let meta = runner.meta().await?;
+
+            if !runner.popular_supported() {
+                return Err(coded(
+                    ErrorCode::ExtensionUnsupported,
+                    t!("popular-not-supported", source = meta.name),
+                ));
+            }
+
+            network::require_online(cli.offline)?;
+
+            log::info!("fetching popular from '{}'", meta.name);
+            let novels = runner
+                .popular(page)
+                .await
+                .map_err(|error| network::explain_offline(error.into(), &url))?;
+            if novels.is_empty() {
+                log::error!("{}", t!("no-novels-found"));
+            }
+
+            for novel in novels {
+                println!("{} <{}>", novel.title, novel.url);
+            }
+        }
+        Commands::Bundle {
+            url,
+            profile,
+            settings,
+            notes,
+            titles,
+            dates,
+            selection,
+            front_matter,
+            no_sanitize,
+            ruby_notation,
+            txt,
+            audio,
+            incremental,
+            calibre,
+        } => {
+            let persist = open_persist()?;
+            let config = persist.read_config()?;
+            let metadata = MetadataMapping::new(&config.metadata).map_err(|e| anyhow!(e))?;
+            let mut sanitize = if no_sanitize {
+                SanitizeOptions {
+                    disabled: SanitizeStep::ALL.into(),
+                    watermarks: vec![],
+                    ruby_notation: false,
+                }
+            } else {
+                sanitize_options(&config.sanitize)?
+            };
+            sanitize.ruby_notation = ruby_notation;
+            let calibre = calibre.then(|| CalibreOptions {
+                library: config.calibre.library.clone(),
+                username: config.calibre.username.clone(),
+                password: std::env::var(CALIBRE_PASSWORD_VAR)
+                    .ok()
+                    .filter(|value| !value.is_empty()),
+                metadata: metadata.clone(),
+                ..Default::default()
+            });
+            let settings = match profile {
+                Some(name) => {
+                    let profile = config
+                        .profiles
+                        .get(&name)
+                        .cloned()
+                        .ok_or_else(|| anyhow!(t!("profile-not-found", name)))?;
+                    settings.with_profile(profile)?
+                }
+                None => settings,
+            };
+            let BundleSettings {
+                mut format,
+                output,
+                epub_theme,
+                pdf_template,
+                pdf_vertical,
+                fonts,
+                no_font_subset,
+                images,
+                split_volumes,
+                split_chapters: max_chapters,
+                split_size: max_size,
+            } = settings;
+            if format.is_empty() {
+                format.push(Format::Epub);
+            }
+
+            let global = persist.read_global()?;
+            info!("Loaded global data");
+
+            let path = global
+                .novel_path_from_url(&url.to_string())
+                .ok_or_else(|| coded(ErrorCode::NovelNotFound, t!("novel-not-found")))?;
+
+            info!("Found novel data at '{}'.", path.display());
+
+            // Chapters of a pulled library stay in the bucket until they are bundled
+            let dir = path.to_path_buf();
+            let (persist, fetched) = storage::with_storage(persist, move |storage, persist| {
+                storage.fetch_chapters(persist, &dir)
+            })
+            .await;
+            match fetched {
+                Ok(Some(count)) => info!("Downloaded {count} chapters from the bucket."),
+                Ok(None) => {}
+                Err(e) => warn!("failed to download the chapters from the bucket: {e}"),
+            }
+
+            let mut host = ExtensionHost::new(cli.lock_file.clone());
+            let meta = if let Some(runner) = host.runtime(url.as_str()).await? {
+                let meta = runner.meta().await?;
+                info!("Acquired source meta information from wasm file.");
+
+                Some(meta)
+            } else {
+                warn!("failed to retrieve meta information for the url");
+                None
+            };
+
+            let novel = persist.persist_novel(path.into());
+            let mut data = novel
+                .read_data()?
+                .ok_or_else(|| coded(ErrorCode::NovelNotFound, t!("novel-data-not-found")))?;
+
+            info!("Loaded novel information from disk");
+
+            if !dates.is_empty() {
+                let removed = data
+                    .novel
+                    .retain_chapters(|chapter| dates.contains(chapter));
+                info!("Excluded {removed} chapters outside of the dates.");
+
+                if data.novel.volumes.is_empty() {
+                    return Err(coded(ErrorCode::BundleFailed, t!("no-chapters-in-dates")));
+                }
+            }
+
+            let mut exports = novel.read_exports()?;
+            let selection = ChapterSelection {
+                chapters: selection.chapters,
+                volumes: selection.volumes,
+                after: exports.last_chapter.clone().filter(|_| selection.unread),
+            };
+            if !selection.is_empty() {
+                let removed = selection.apply(&mut data.novel);
+                info!("Excluded {removed} chapters outside of the selection.");
+
+                if data.novel.volumes.is_empty() {
+                    return Err(coded(ErrorCode::BundleFailed, t!("no-chapters-selected")));
+                }
+            }
+
+            if images.include_images {
+                let cache_dir = persist.options.base_dir.join("cache").join("images");
+                let stored = bundle::store_images(&novel, &data, cache_dir).await?;
+                info!("Downloaded {stored} chapter images.");
+            }
+
+            let name = slug::slugify(data.title());
+            let template = output.map(OutputTemplate::new);
+            let hashes = data.chapter_hashes.clone();
+            let bundle = bundle::persist_bundle(
+                meta,
+                data,
+                path.to_path_buf(),
+                notes,
+                titles.into(),
+                persist.cipher().cloned(),
+                Some(novel.read_assets()?),
+            );
+
+            let options = FormatOptions {
+                epub: EpubOptions {
+                    theme: epub_theme.unwrap_or_default(),
+                },
+                txt: txt.into(),
+                pdf: PdfOptions {
+                    template: pdf_template.or(config.pdf_template),
+                    vertical: pdf_vertical,
+                    ..Default::default()
+                },
+                audio: audio.into(),
+                images: images.into(),
+                fonts: FontOptions {
+                    fonts,
+                    subset: !no_font_subset,
+                    ..Default::default()
+                },
+                metadata,
+                front_matter: front_matter.into(),
+                sanitize,
+            };
+            let settings = format!("{options:?}");
+            let split = SplitOptions {
+                per_volume: split_volumes,
+                max_chapters,
+                max_bytes: max_size.map(|size| size * 1024 * 1024),
+            };
+            let ranges = if split.is_none() {
+                vec![]
+            } else {
+                split_chapters(&bundle, &split).map_err(|e| anyhow!("{e}"))?
+            };
+
+            // The first ctrl-c stops the bundle being written, and its
+            // incomplete file is removed. The second exits at once, as the
+            // default handler would have.
+            let cancel = CancellationToken::new();
+            let interrupted = cancel.clone();
+            tokio::spawn(async move {
+                while tokio::signal::ctrl_c().await.is_ok() {
+                    if interrupted.is_cancelled() {
+                        std::process::exit(130);
+                    }
+                    interrupted.cancel();
+                }
+            });
+
+            for format in format.into_iter().unique() {
+                let output_path = match &template {
+                    Some(template) => template
+                        .render(bundle.novel(), bundle.meta(), format)
+                        .map_err(|e| anyhow!(e))?,
+                    None => path.join(format!("output/{name}.{}", format.extension())),
+                };
+
+                // Bundles of the same content in another format are told apart
+                let settings = format!("{format} {settings}");
+
+                if ranges.len() <= 1 {
+                    let fingerprint = bundle::export_fingerprint(&bundle, &hashes, &settings);
+                    if incremental && exports.is_current(&output_path, &fingerprint) {
+                        println!("{}", t!("bundle-unchanged", path = output_path.display()));
+                        continue;
+                    }
+
+                    write_bundle(format, &bundle, &output_path, &options, &cancel)?;
+                    if let Some(calibre) = &calibre {
+                        push_to_calibre(calibre, &bundle, &output_path)?;
+                    }
+                    let chapters = bundle.chapters().len();
+                    exports.record(output_path, format.extension(), chapters, fingerprint);
+                    continue;
+                }
+
+                let paths = (1..=ranges.len())
+                    .map(|number| part_path(&output_path, number, ranges.len()))
+                    .collect::<Vec<_>>();
+
+                let spans = ranges
+                    .iter()
+                    .zip(&paths)
+                    .map(|(chapters, path)| PartSpan {
+                        chapters: chapters.clone(),
+                        file_name: path
+                            .file_name()
+                            .map(|name| name.to_string_lossy().to_string()),
+                    })
+                    .collect::<Vec<_>>();
+
+                let mut unchanged = 0;
+                for (index, path) in paths.iter().enumerate() {
+                    let part = Part {
+                        index,
+                        spans: spans.clone(),
+                    };
+                    let part = PartBundle::new(&bundle, part);
+
+                    let fingerprint = bundle::export_fingerprint(&part, &hashes, &settings);
+                    if incremental && exports.is_current(path, &fingerprint) {
+                        unchanged += 1;
+                        continue;
+                    }
+
+                    write_bundle(format, &part, path, &options, &cancel)?;
+                    if let Some(calibre) = &calibre {
+                        push_to_calibre(calibre, &part, path)?;
+                    }
+                    let chapters = part.chapters().len();
+                    exports.record(path.clone(), format.extension(), chapters, fingerprint);
+                }
+                if unchanged > 0 {
+                    println!("{}", t!("bundle-parts-unchanged", count = unchanged));
+                }
+
+                println!(
+                    "{}",
+                    t!("bundle-split", format = format, count = ranges.len())
+                );
+            }
+
+            exports.last_chapter = bundle
+                .chapters()
+                .last()
+                .map(|(_, chapter)| chapter.url.clone());
+            novel.write_exports(&exports)?;
+        }
+        Commands::Site {
+            urls,
+            all,
+            output,
+            notes,
+            titles,
+        } => {
+            let persist = open_persist_shared()?;
+            let global = persist.read_global()?;
+
+            let dirs = if all {
+                global.novels().map(|(_, dir)| dir.clone()).collect()
+            } else {
+                urls.iter()
+                    .map(|url| {
+                        global
+                            .novel_path_from_url(url.as_str())
+                            .map(Path::to_path_buf)
+                            .ok_or_else(|| coded(ErrorCode::NovelNotFound, t!("novel-not-found")))
+                    })
+                    .collect::<anyhow::Result<Vec<_>>>()?
+            };
+
+            let titles = TitleRules::from(titles);
+            let sanitize = sanitize_options(&persist.read_config()?.sanitize)?;
+            let library = all || dirs.len() > 1;
+            let mut entries = vec![];
+            for dir in dirs {
+                let novel = persist.persist_novel(dir.clone());
+                let Some(data) = novel.read_data()? else {
+                    warn!("no novel data found at '{}'", dir.display());
+                    continue;
+                };
+
+                let entry = SiteEntry {
+                    title: data.title().to_string(),
+                    authors: data.authors().to_vec(),
+                    path: slug::slugify(data.title()),
+                };
+                let bundle = bundle::persist_bundle(
+                    None,
+                    data,
+                    dir,
+                    notes,
+                    titles.clone(),
+                    persist.cipher().cloned(),
+                    Some(novel.read_assets()?),
+                );
+
+                let path = if library {
+                    output.join(&entry.path)
+                } else {
+                    output.clone()
+                };
+                let bundle = SanitizedBundle::new(&bundle, &sanitize);
+                bundle_site(&bundle, &path).map_err(|e| {
+                    coded(
+                        ErrorCode::BundleFailed,
+                        t!("bundle-failed", format = "site", reason = e),
+                    )
+                })?;
+                entries.push(entry);
+            }
+
+            if library {
+                entries.sort_by(|a, b| a.title.cmp(&b.title));
+                write_library_index(&output, &entries)?;
+            }
+            println!(
+                "{}",
+                t!(
+                    "site-written",
+                    count = entries.len(),
+                    path = output.display()
+                )
+            );
+        }
+        Commands::Info {
+            url,
+            provenance,
+            chapters,
+        } => {
+            let persist = open_persist_shared()?;
+            let (_, data) = read_saved_novel(&persist, &url)?;
+            let novel = &data.novel;
+
+            println!("{}", data.title());
+            if !data.authors().is_empty() {
+                println!("{}", data.authors().join(", "));
+            }
+            let stats = data.stats();
+            println!(
+                "{}",
+                t!(
+                    "novel-info",
+                    status = format!("{:?}", novel.status),
+                    downloaded = stats.downloaded,
+                    total = stats.chapters,
+                    date = data.updated_at.format("%Y-%m-%d %H:%M")
+                )
+            );
+            println!(
+                "{}",
+                t!(
+                    "word-count",
+                    words = stats.words,
+                    time = format_reading_time(stats.reading_time())
+                )
+            );
+
+            if provenance {
+                print_provenance(&data);
+            }
+            if chapters {
+                print_chapter_states(&data);
+            }
+        }
+        Commands::Note { url, text, clear } => {
+            let persist = open_persist()?;
+            let (novel, mut data) = read_saved_novel(&persist, &url)?;
+
+            if clear {
+                data.notes = None;
+            } else if let Some(text) = text {
+                data.notes = Some(text);
+            } else {
+                match &data.notes {
+                    Some(notes) => println!("{notes}"),
+                    None => println!("{}", t!("no-notes", title = data.novel.title)),
+                }
+                return Ok(());
+            }
+
+            novel.write_data(&data)?;
+            info!("Saved notes for '{}'", data.novel.title);
+        }
+        Commands::Versions {
+            url,
+            chapter,
+            diff,
+            clear,
+        } => {
+            let persist = open_persist()?;
+            let (novel, data) = read_saved_novel(&persist, &url)?;
+            let chapter = data
+                .novel
+                .volumes
+                .iter()
+                .flat_map(|volume| &volume.chapters)
+                .nth(chapter.wrapping_sub(1))
+                .ok_or_else(|| anyhow!(t!("chapter-not-found", number = chapter)))?;
+
+            let versions = novel.chapter_versions(chapter)?;
+            if clear {
+                novel.clear_chapter_versions(chapter)?;
+                return Ok(());
+            }
+
+            let Some(number) = diff else {
+                for (number, version) in versions.iter().enumerate() {
+                    println!(
+                        "{}  {}",
+                        number + 1,
+                        version.replaced_at.format("%Y-%m-%d %H:%M")
+                    );
+                }
+                if versions.is_empty() {
+                    println!("{}", t!("no-versions", title = chapter.title));
+                }
+                return Ok(());
+            };
+
+            let Some(version) = number.checked_sub(1).and_then(|index| versions.get(index)) else {
+                return Err(anyhow!(t!("version-not-found", number)));
+            };
+
+            // A version is compared with the one that replaced it
+            let newer = match versions.get(number) {
+                Some(newer) => newer.path.clone(),
+                None => data
+                    .downloaded
+                    .get(&chapter.url)
+                    .map(|path| novel.dir().join(path))
+                    .ok_or_else(|| anyhow!(t!("version-not-found", number)))?,
+            };
+
+            let old = text_lines(&novel.read_chapter(&version.path)?);
+            let new = text_lines(&novel.read_chapter(&newer)?);
+            for line in diff_lines(&old, &new) {
+                match line {
+                    DiffLine::Same(line) => println!("  {line}"),
+                    DiffLine::Removed(line) => println!("- {line}"),
+                    DiffLine::Added(line) => println!("+ {line}"),
+                }
+            }
+        }
+        Commands::Rights { url, text, clear } => {
+            let persist = open_persist()?;
+            let (novel, mut data) = read_saved_novel(&persist, &url)?;
+
+            if clear {
+                data.rights = None;
+            } else if let Some(text) = text {
+                data.rights = Some(text);
+            } else {
+                match data.rights() {
+                    Some(rights) => println!("{rights}"),
+                    None => println!("{}", t!("no-rights", title = data.novel.title)),
+                }
+                return Ok(());
+            }
+
+            novel.write_data(&data)?;
+            info!("Saved rights for '{}'", data.novel.title);
+        }
+        Commands::Titles { url, titles, clear } => {
+            let persist = open_persist()?;
+            let (novel, mut data) = read_saved_novel(&persist, &url)?;
+
+            let rules = TitleRules::from(titles);
+            if clear {
+                data.title_rules = None;
+            } else if !rules.is_default() {
+                data.title_rules = Some(rules);
+            } else {
+                match &data.title_rules {
+                    Some(rules) => println!("{rules:#?}"),
+                    None => println!("{}", t!("no-title-rules", title = data.novel.title)),
+                }
+                return Ok(());
+            }
+
+            novel.write_data(&data)?;
+            info!("Saved title rules for '{}'", data.novel.title);
+        }
+        Commands::Edit {
+            url,
+            title,
+            authors,
+            tags,
+            cover,
+            reset,
+        } => {
+            let persist = open_persist()?;
+            let (novel, mut data) = read_saved_novel(&persist, &url)?;
+
+            let unchanged = title.is_none()
+                && authors.is_empty()
+                && tags.is_empty()
+                && cover.is_none()
+                && reset.is_empty();
+            if unchanged {
+                print_overrides(&data.overrides);
+                return Ok(());
+            }
+
+            for field in &reset {
+                match field.as_str() {
+                    "title" => data.overrides.title = None,
+                    "authors" => data.overrides.authors = None,
+                    "tags" => data.overrides.tags = None,
+                    _ => novel.remove_cover_override(&mut data)?,
+                }
+            }
+
+            if title.is_some() {
+                data.overrides.title = title;
+            }
+            if !authors.is_empty() {
+                data.overrides.authors = Some(authors);
+            }
+            if !tags.is_empty() {
+                data.overrides.tags = Some(tags);
+            }
+            if let Some(cover) = cover {
+                let content_type = mime_guess::from_path(&cover)
+                    .first_or_octet_stream()
+                    .to_string();
+                novel.set_cover_override(&mut data, &cover, content_type)?;
+            }
+
+            novel.write_data(&data)?;
+            println!("{}", t!("novel-edited", title = data.title()));
+        }
+        Commands::List {
+            query,
+            sort,
+            reverse,
+        } => {
+            let query = query
+                .unwrap_or_default()
+                .parse::<Query>()
+                .map_err(|e| anyhow!(e))?;
+
+            let persist = open_persist_shared()?;
+            let collections = persist.read_collections()?;
+
+            // The summaries are read from the library index where it is up to date
+            let mut matches = vec![];
+            for (url, summary) in persist.novel_summaries()? {
+                let id = persist.novel_id(&summary.dir);
+                let source = id.as_ref().map(|id| id.source.as_str()).unwrap_or_default();
+
+                let matched = query.matches(&|field, value| match field {
+                    Field::Title => contains_ignore_case(&summary.title, value),
+                    Field::Author => summary
+                        .authors
+                        .iter()
+                        .any(|author| contains_ignore_case(author, value)),
+                    Field::Source => contains_ignore_case(source, value),
+                    Field::Lang => summary
+                        .langs
+                        .iter()
+                        .any(|lang| lang.eq_ignore_ascii_case(value)),
+                    Field::Collection => collections
+                        .collections_of(&url)
+                        .any(|name| name.eq_ignore_ascii_case(value)),
+                    Field::Status => summary.status.eq_ignore_ascii_case(value),
+                    Field::Tag => summary
+                        .tags
+                        .iter()
+                        .any(|tag| tag.eq_ignore_ascii_case(value)),
+                    Field::Updated => value.parse::<ValueRange<String>>().is_ok_and(|range| {
+                        range.contains(&summary.updated_at.format("%Y-%m-%d").to_string())
+                    }),
+                    Field::Pending => value
+                        .parse::<ValueRange<usize>>()
+                        .is_ok_and(|range| range.contains(&summary.pending)),
+                });
+
+                if matched {
+                    matches.push((url, id, summary));
+                }
+            }
+
+            match sort {
+                NovelSort::Url => {}
+                NovelSort::Title => {
+                    matches.sort_by_cached_key(|(_, _, summary)| summary.title.to_lowercase())
+                }
+                NovelSort::Updated => {
+                    matches.sort_by_key(|(_, _, summary)| std::cmp::Reverse(summary.updated_at))
+                }
+                NovelSort::Pending => {
+                    matches.sort_by_key(|(_, _, summary)| std::cmp::Reverse(summary.pending))
+                }
+            }
+            if reverse {
+                matches.reverse();
+            }
+
+            for (url, id, summary) in matches {
+                match id {
+                    Some(id) => println!("{id} {} <{url}>", summary.title),
+                    None => println!("{} <{url}>", summary.title),
+                }
+            }
+        }
+        Commands::Collection { action } => {
+            let persist = open_persist()?;
+            let mut collections = persist.read_collections()?;
+
+            match action {
+                CollectionAction::Create { name } => {
+                    if !collections.create_collection(&name) {
+                        println!("{}", t!("collection-exists", name));
+                        return Ok(());
+                    }
+                }
+                CollectionAction::Delete { name } => {
+                    if !collections.delete_collection(&name) {
+                        return Err(anyhow!(t!("collection-not-found", name)));
+                    }
+                }
+                CollectionAction::Add { name, url } => {
+                    if persist
+                        .read_global()?
+                        .novel_path_from_url(url.as_str())
+                        .is_none()
+                    {
+                        return Err(coded(ErrorCode::NovelNotFound, t!("novel-not-found")));
+                    }
+
+                    collections.add_to_collection(&name, url.as_str());
+                    println!("{}", t!("collection-added", name, url));
+                }
+                CollectionAction::Remove { name, url } => {
+                    if !collections.remove_from_collection(&name, url.as_str()) {
+                        return Err(anyhow!(t!("collection-not-found", name)));
+                    }
+                }
+                CollectionAction::List => {
+                    for (name, count) in collections.list_collections() {
+                        println!("{name} ({count})");
+                    }
+                    return Ok(());
+                }
+            }
+
+            persist.save_collections(&collections)?;
+        }
+        Commands::Remove { url } => {
+            let persist = open_persist()?;
+            let novel = persist
+                .delete_novel(url.as_str())?
+                .ok_or_else(|| coded(ErrorCode::NovelNotFound, t!("novel-not-found")))?;
+
+            println!(
+                "{}",
+                t!("novel-trashed", title = novel.title, url = novel.url)
+            );
+        }
+        Commands::Merge { primary, duplicate } => {
+            let persist = open_persist()?;
+            let report = persist
+                .merge_novels(primary.as_str(), duplicate.as_str())?
+                .ok_or_else(|| coded(ErrorCode::NovelNotFound, t!("novel-not-found")))?;
+
+            println!(
+                "{}",
+                t!(
+                    "novels-merged",
+                    title = report.duplicate.title,
+                    chapters = report.chapters,
+                    assets = report.assets
+                )
+            );
+        }
+        Commands::Trash { action } => trash::handle(action)?,
+        Commands::Mirror { action } => mirror::handle(&cli, action).await?,
+        Commands::CheckUrls { delay, update } => {
+            let persist = open_persist()?;
+            let mut global = persist.read_global()?;
+            let mut checker = UrlChecker::new(Duration::from_millis(delay))?;
+
+            let novels = global
+                .novels()
+                .map(|(url, dir)| (url.clone(), dir.clone()))
+                .sorted()
+                .collect::<Vec<_>>();
+
+            let mut broken = 0;
+            for (url, dir) in novels {
+                let Ok(parsed) = Url::parse(&url) else {
+                    continue;
+                };
+
+                match checker.check(&parsed).await {
+                    UrlStatus::Ok => continue,
+                    UrlStatus::Redirected(target) => {
+                        println!("{}", t!("url-redirected", url = url, target = target));
+                        if update {
+                            let novel = persist.persist_novel(dir.clone());
+                            if let Some(mut data) = novel.read_data()? {
+                                data.novel.url = target.to_string();
+                                novel.write_data(&data)?;
+                            }
+
+                            global.remove_novel(&url);
+                            global.insert_novel(target.to_string(), dir);
+                            persist.save_global(&global)?;
+
+                            let mut collections = persist.read_collections()?;
+                            collections.rename_novel(&url, target.as_str());
+                            persist.save_collections(&collections)?;
+                            println!("{}", t!("url-updated", url = target));
+                        }
+                    }
+                    UrlStatus::LoginRequired => println!("{}", t!("url-login-required", url)),
+                    UrlStatus::Dead(status) => {
+                        println!("{}", t!("url-dead", url = url, status = status))
+                    }
+                    UrlStatus::Unreachable(reason) => {
+                        println!("{}", t!("url-unreachable", url = url, reason = reason))
+                    }
+                }
+                broken += 1;
+            }
+
+            println!(
+                "{}",
+                t!(
+                    "urls-checked",
+                    count = global.novels().count(),
+                    broken = broken
+                )
+            );
+        }
+        Commands::Credentials { action } => credentials::handle(&cli, action)?,
+        Commands::Migrate { dry_run } => {
+            let mut persist = Persist::new(library_options()?);
+            persist.lock(LockMode::Exclusive, wait_for_lock())?;
+            persist.unlock(passphrase().as_deref())?;
+            let report = if dry_run {
+                persist.pending_migrations()?
+            } else {
+                persist.initialize()?
+            };
+
+            if report.is_empty() {
+                println!("{}", t!("migration-none", version = report.to));
+            }
+
+            for (version, description) in report.migrations {
+                if report.dry_run {
+                    println!("{}", t!("migration-pending", version, description));
+                } else {
+                    println!("{}", t!("migration-applied", version, description));
+                }
+            }
+        }
+        Commands::Compress { method } => {
+            let persist = open_persist()?;
+            let mut config = persist.read_config()?;
+            config.compression = method;
+            persist.save_config(&config)?;
+
+            let count = persist.recompress(method)?;
+            println!("{}", t!("compression-applied", method, count));
+        }
+        Commands::Encrypt => {
+            let passphrase = passphrase()
+                .ok_or_else(|| coded(ErrorCode::PassphraseRequired, t!("passphrase-missing")))?;
+
+            let mut persist = Persist::new(library_options()?);
+            persist.lock(LockMode::Exclusive, wait_for_lock())?;
+            persist.initialize()?;
+            let count = persist.encrypt(&passphrase)?;
+            println!("{}", t!("library-encrypted", count));
+        }
+        Commands::Executor { action } => executor::handle(action)?,
+        Commands::Library { action } => {
+            let path = Path::new(LIBRARIES_FILE);
+            let mut manager = LibraryManager::open(path)?;
+
+            match action {
+                LibraryAction::Add { name, dir } => {
+                    if !manager.add(&name, dir) {
+                        return Err(anyhow!(t!("library-exists", name)));
+                    }
+                    println!("{}", t!("library-added", name));
+                }
+                LibraryAction::Remove { name } => {
+                    if name == DEFAULT_LIBRARY {
+                        return Err(anyhow!(t!("library-default", name)));
+                    }
+                    let dir = manager
+                        .remove(&name)
+                        .ok_or_else(|| anyhow!(t!("library-not-found", name = name)))?;
+                    println!(
+                        "{}",
+                        t!("library-removed", name = name, path = dir.display())
+                    );
+                }
+                LibraryAction::Use { name } => {
+                    if !manager.switch(&name) {
+                        return Err(anyhow!(t!("library-not-found", name)));
+                    }
+                    println!("{}", t!("library-switched", name));
+                }
+                LibraryAction::List => {
+                    for (name, dir) in manager.libraries() {
+                        let marker = if name == manager.active() { "*" } else { " " };
+                        println!("{marker} {name} ({})", dir.display());
+                    }
+                    return Ok(());
+                }
+            }
+
+            manager.save(path)?;
+        }
+        Commands::Profile { action } => {
+            let persist = open_persist()?;
+            let mut config = persist.read_config()?;
+
+            match action {
+                ProfileAction::Save { name, settings } => {
+                    config.profiles.insert(name.clone(), settings.into());
+                    persist.save_config(&config)?;
+                    println!("{}", t!("profile-saved", name));
+                }
+                ProfileAction::List => {
+                    for (name, profile) in &config.profiles {
+                        println!("{name}: {}", serde_json::to_string(profile)?);
+                    }
+                }
+                ProfileAction::Remove { name } => {
+                    if config.profiles.remove(&name).is_none() {
+                        return Err(anyhow!(t!("profile-not-found", name)));
+                    }
+                    persist.save_config(&config)?;
+                    println!("{}", t!("profile-removed", name));
+                }
+            }
+        }
+        Commands::Template { action } => match action {
+            TemplateAction::Export { dir } => {
+                let path = dir.join(PDF_TEMPLATE_FILE);
+                if path.exists() {
+                    return Err(anyhow!(t!("template-exists", path = path.display())));
+                }
+                fs::create_dir_all(&dir)?;
+                fs::write(&path, DEFAULT_PDF_TEMPLATE)?;
+                println!("{}", t!("template-exported", path = path.display()));
+            }
+            TemplateAction::Use { path } => {
+                if !path.exists() {
+                    return Err(anyhow!(t!("template-not-found", path = path.display())));
+                }
+                let path = fs::canonicalize(path)?;
+
+                let persist = open_persist()?;
+                let mut config = persist.read_config()?;
+                config.pdf_template = Some(path.clone());
+                persist.save_config(&config)?;
+                println!("{}", t!("template-set", path = path.display()));
+            }
+            TemplateAction::Reset => {
+                let persist = open_persist()?;
+                let mut config = persist.read_config()?;
+                config.pdf_template = None;
+                persist.save_config(&config)?;
+                println!("{}", t!("template-reset"));
+            }
+            TemplateAction::Show => {
+                let persist = open_persist_shared()?;
+                match persist.read_config()?.pdf_template {
+                    Some(path) => println!("{}", path.display()),
+                    None => println!("{}", t!("template-default")),
+                }
+            }
+        },
+        Commands::Calibre { action } => calibre::handle(action)?,
+        Commands::Metadata { action } => match action {
+            MetadataAction::Map { name, field } => {
+                let persist = open_persist()?;
+                let mut config = persist.read_config()?;
+                config.metadata.insert(name.clone(), field.to_string());
+                persist.save_config(&config)?;
+                println!("{}", t!("metadata-mapped", name, field = field.to_string()));
+            }
+            MetadataAction::Unmap { name } => {
+                let persist = open_persist()?;
+                let mut config = persist.read_config()?;
+                config.metadata.remove(&name);
+                persist.save_config(&config)?;
+                let field = MetadataMapping::default().field(&name);
+                println!("{}", t!("metadata-mapped", name, field = field.to_string()));
+            }
+            MetadataAction::List => {
+                let persist = open_persist_shared()?;
+                let config = persist.read_config()?;
+                for (name, field) in &config.metadata {
+                    println!("{name}: {field}");
+                }
+            }
+        },
+        Commands::Sanitize { action } => sanitize::handle(action)?,
+        Commands::Source { action } => {
+            let persist = open_persist()?;
+            let mut config = persist.read_config()?;
+
+            match action {
+                SourceAction::Mature { source, disable } => {
+                    let settings = config.sources.entry(source.clone()).or_default();
+                    settings.mature = !disable;
+                    if *settings == SourceSettings::default() {
+                        config.sources.remove(&source);
+                    }
+                    persist.save_config(&config)?;
+
+                    if disable {
+                        println!("{}", t!("mature-disabled", source));
+                    } else {
+                        println!("{}", t!("mature-enabled", source));
+                    }
+                }
+                SourceAction::List => {
+                    for (source, settings) in &config.sources {
+                        println!("{source}: mature={}", settings.mature);
+                    }
+                }
+            }
+        }
+        Commands::Storage { action } => storage::handle(action).await?,
+        Commands::Maintenance { action } => maintenance::handle(&cli, action).await?,
+        Commands::Import { paths } => {
+            let persist = open_persist()?;
+
+            let mut failures = 0;
+            for path in paths {
+                match persist.import_epub(&path) {
+                    Ok(imported) => println!(
+                        "{}",
+                        t!(
+                            "novel-imported",
+                            title = imported.title,
+                            chapters = imported.chapters,
+                            volumes = imported.volumes
+                        )
+                    ),
+                    Err(e) => {
+                        failures += 1;
+                        let (path, reason) = (path.display(), e.to_string());
+                        println!("{}", t!("import-failed", path, reason));
+                    }
+                }
+            }
+
+            if failures > 0 {
+                return Err(anyhow!(t!("imports-failed", count = failures)));
+            }
+        }
+        Commands::Backup { path } => {
+            let persist = open_persist()?;
+            let manifest = persist.export_backup(&path)?;
+            println!(
+                "{}",
+                t!(
+                    "backup-created",
+                    count = manifest.novels,
+                    path = path.display()
+                )
+            );
+        }
+        Commands::Restore { path, on_conflict } => {
+            let mut persist = Persist::new(library_options()?);
+            persist.lock(LockMode::Exclusive, wait_for_lock())?;
+            let report = persist.import_backup(&path, on_conflict)?;
+            persist.unlock(passphrase().as_deref())?;
+            persist.initialize()?;
+
+            println!(
+                "{}",
+                t!(
+                    "backup-restored",
+                    count = report.restored,
+                    novels = report.novels
+                )
+            );
+            if !report.skipped.is_empty() {
+                println!("{}", t!("backup-skipped", count = report.skipped.len()));
+            }
+        }
+        Commands::Status { sources } => {
+            let persist = open_persist_shared()?;
+            let stats = persist.library_stats()?;
+            println!("{}", t!("status-novels", count = stats.novels));
+            println!(
+                "{}",
+                t!(
+                    "status-chapters",
+                    downloaded = stats.downloaded,
+                    total = stats.chapters
+                )
+            );
+            println!(
+                "{}",
+                t!(
+                    "word-count",
+                    words = stats.words,
+                    time = format_reading_time(stats.reading_time())
+                )
+            );
+
+            if sources {
+                let stats = persist.read_sources()?;
+                for source in stats.sources().sorted() {
+                    println!("{source} ({:?})", stats.preferred(source));
+
+                    for executor in Executor::ALL {
+                        let Some(executor_stats) = stats.stats(source, executor) else {
+                            continue;
+                        };
+
+                        let rate = executor_stats.success_rate().unwrap_or_default() * 100.0;
+                        println!(
+                            "  {executor:?}: {rate:.0}% ({}/{})",
+                            executor_stats.successes,
+                            executor_stats.attempts()
+                        );
+                    }
+                }
+            }
+        }
+        Commands::Worker { path } => {
+            quelle_engine::process::serve(&path).await?;
         }
     }
 
@@ -0,0 +1,163 @@
+use std::path::PathBuf;
+
+use anyhow::anyhow;
+use chrono::{Local, Utc};
+use clap::Subcommand;
+use log::info;
+use quelle_persist::{MaintenanceTask, TaskSummary};
+
+use crate::{generate_lock, open_persist, t, GlobalArgs};
+
+#[derive(Subcommand)]
+pub enum MaintenanceAction {
+    /// Run the enabled tasks when within the maintenance hours, meant to be started
+    /// every hour by a scheduler such as cron. Runs at most once a night.
+    Run {
+        /// Run now, outside of the maintenance hours or if it already ran
+        #[arg(long)]
+        force: bool,
+
+        /// The directory to find wasm extensions, used to refresh the lock file
+        #[arg(long, default_value = "extensions")]
+        extensions_dir: PathBuf,
+    },
+    /// Run the task during maintenance: prune-cache, compact, verify,
+    /// refresh-manifest or backup
+    Enable { task: MaintenanceTask },
+    /// Stop running the task during maintenance
+    Disable { task: MaintenanceTask },
+    /// Set the local hours the maintenance may start in (ex: 23 4)
+    Hours {
+        #[arg(value_parser = clap::value_parser!(u32).range(0..24))]
+        start: u32,
+
+        #[arg(value_parser = clap::value_parser!(u32).range(0..24))]
+        end: u32,
+    },
+    /// Show the maintenance hours and tasks
+    List,
+}
+
+pub async fn handle(cli: &GlobalArgs, action: MaintenanceAction) -> anyhow::Result<()> {
+    let persist = open_persist()?;
+    let mut config = persist.read_config()?;
+
+    match action {
+        MaintenanceAction::Run {
+            force,
+            extensions_dir,
+        } => {
+            let maintenance = &config.maintenance;
+            if !force && !maintenance.is_due(Local::now()) {
+                info!(
+                    "Maintenance is not due, it runs once between {}:00 and {}:00.",
+                    maintenance.start_hour, maintenance.end_hour
+                );
+                return Ok(());
+            }
+
+            let tasks = MaintenanceTask::ALL
+                .into_iter()
+                .filter(|task| maintenance.is_enabled(*task));
+
+            let mut failures = 0;
+            for task in tasks {
+                let result = match task {
+                    MaintenanceTask::RefreshManifest => {
+                        generate_lock(&cli.lock_file, &extensions_dir)
+                            .await
+                            .map(|_| None)
+                    }
+                    _ => persist.maintain(task).map_err(anyhow::Error::from),
+                };
+
+                match result {
+                    Ok(summary) => print_task_summary(task, summary),
+                    Err(e) => {
+                        failures += 1;
+                        let (task, reason) = (task.to_string(), e.to_string());
+                        println!("{}", t!("maintenance-failed", task, reason));
+                    }
+                }
+            }
+
+            config.maintenance.last_run = Some(Utc::now());
+            persist.save_config(&config)?;
+
+            if failures > 0 {
+                return Err(anyhow!(t!("maintenance-tasks-failed", count = failures)));
+            }
+        }
+        MaintenanceAction::Enable { task } => {
+            config.maintenance.disabled.remove(&task);
+            persist.save_config(&config)?;
+        }
+        MaintenanceAction::Disable { task } => {
+            config.maintenance.disabled.insert(task);
+            persist.save_config(&config)?;
+        }
+        MaintenanceAction::Hours { start, end } => {
+            config.maintenance.start_hour = start;
+            config.maintenance.end_hour = end;
+            persist.save_config(&config)?;
+        }
+        MaintenanceAction::List => {
+            let maintenance = &config.maintenance;
+            println!(
+                "hours: {}:00-{}:00",
+                maintenance.start_hour, maintenance.end_hour
+            );
+            for task in MaintenanceTask::ALL {
+                println!("{task}: enabled={}", maintenance.is_enabled(task));
+            }
+            if let Some(last_run) = maintenance.last_run {
+                println!("last run: {last_run}");
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn print_task_summary(task: MaintenanceTask, summary: Option<TaskSummary>) {
+    let task = task.to_string();
+    let message = match summary {
+        None => t!("maintenance-done", task),
+        Some(TaskSummary::CachePruned { removed }) => {
+            t!("maintenance-cache-pruned", task, removed)
+        }
+        Some(TaskSummary::Compacted {
+            purged,
+            recompressed,
+            dedup,
+        }) => t!(
+            "maintenance-compacted",
+            task = task,
+            purged = purged,
+            recompressed = recompressed,
+            saved = dedup.saved_bytes
+        ),
+        Some(TaskSummary::Verified(report)) => t!(
+            "maintenance-verified",
+            task = task,
+            chapters = report.chapters,
+            corrupted = report.corrupted.len()
+        ),
+        Some(TaskSummary::IndexRebuilt { novels }) => {
+            t!("maintenance-index-rebuilt", task, novels)
+        }
+        Some(TaskSummary::BackedUp {
+            path,
+            novels,
+            removed,
+        }) => t!(
+            "maintenance-backed-up",
+            task = task,
+            path = path.display(),
+            novels = novels,
+            removed = removed
+        ),
+    };
+
+    println!("{message}");
+}
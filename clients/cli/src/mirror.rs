@@ -0,0 +1,98 @@
+use anyhow::anyhow;
+use clap::Subcommand;
+use url::Url;
+
+use crate::{
+    error::{coded, ErrorCode},
+    host::ExtensionHost,
+    network, open_persist, open_persist_shared, read_saved_novel, t, GlobalArgs,
+};
+
+#[derive(Subcommand)]
+pub enum MirrorAction {
+    /// Record another url the novel can be downloaded from
+    Add {
+        /// The url of the novel
+        url: Url,
+
+        /// The url of the same novel on another source
+        mirror: Url,
+    },
+
+    /// Forget a mirror of a novel
+    Remove { mirror: Url },
+
+    /// Download the novel from the mirror from now on, keeping the downloaded chapters
+    Switch {
+        /// The url of the novel
+        url: Url,
+
+        /// The url of the same novel on another source
+        mirror: Url,
+    },
+
+    /// List the urls the novel can be downloaded from, the active one first
+    List {
+        /// The url of the novel
+        url: Url,
+    },
+}
+
+pub async fn handle(cli: &GlobalArgs, action: MirrorAction) -> anyhow::Result<()> {
+    match action {
+        MirrorAction::Add { url, mirror } => {
+            let persist = open_persist()?;
+            if !persist.add_mirror(url.as_str(), mirror.as_str())? {
+                return Err(coded(ErrorCode::NovelNotFound, t!("novel-not-found")));
+            }
+
+            println!("{}", t!("mirror-added", url = url, mirror = mirror));
+        }
+        MirrorAction::Remove { mirror } => {
+            let persist = open_persist()?;
+            if !persist.remove_mirror(mirror.as_str())? {
+                return Err(anyhow!(t!("mirror-not-found", mirror)));
+            }
+
+            println!("{}", t!("mirror-removed", mirror));
+        }
+        MirrorAction::Switch { url, mirror } => {
+            let persist = open_persist()?;
+            // Fails early when the novel is not saved, before fetching the mirror
+            read_saved_novel(&persist, &url)?;
+
+            network::require_online(cli.offline)?;
+            let mut host = ExtensionHost::new(cli.lock_file.clone());
+            let Some(runtime) = host.runtime(mirror.as_str()).await? else {
+                return Err(coded(
+                    ErrorCode::SourceNotSupported,
+                    t!("no-supported-source", url = mirror),
+                ));
+            };
+
+            let novel = runtime
+                .fetch_novel(mirror.as_str())
+                .await
+                .map_err(|error| network::explain_offline(error.into(), &mirror))?;
+            let carried = persist
+                .switch_source(url.as_str(), novel)?
+                .ok_or_else(|| coded(ErrorCode::NovelNotFound, t!("novel-not-found")))?;
+
+            println!(
+                "{}",
+                t!("mirror-switched", mirror = mirror, count = carried)
+            );
+        }
+        MirrorAction::List { url } => {
+            let persist = open_persist_shared()?;
+            let (_, data) = read_saved_novel(&persist, &url)?;
+
+            println!("{}", data.novel.url);
+            for mirror in &data.mirrors {
+                println!("{mirror}");
+            }
+        }
+    }
+
+    Ok(())
+}
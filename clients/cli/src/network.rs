@@ -0,0 +1,27 @@
+use url::Url;
+
+use crate::{
+    error::{classify, coded, ErrorCode},
+    t,
+};
+
+/// Fail with a clear message when the operation needs the network but quelle
+/// is running offline
+pub fn require_online(offline: bool) -> anyhow::Result<()> {
+    if offline {
+        return Err(coded(ErrorCode::Offline, t!("offline-mode")));
+    }
+
+    Ok(())
+}
+
+/// Explain the error of an operation on the url when it failed because no
+/// connection to the website could be opened
+pub fn explain_offline(error: anyhow::Error, url: &Url) -> anyhow::Error {
+    if classify(&error) != ErrorCode::Offline {
+        return error;
+    }
+
+    let host = url.host_str().unwrap_or_default();
+    error.context(t!("offline-detected", host))
+}
@@ -0,0 +1,72 @@
+use clap::Subcommand;
+use quelle_bundle::SanitizeStep;
+
+use crate::{open_persist, open_persist_shared, sanitize_options, t};
+
+#[derive(Subcommand)]
+pub enum SanitizeAction {
+    /// Run the cleanup step before bundling: scripts, tracking-pixels,
+    /// watermarks, empty-paragraphs or inline-styles
+    Enable { step: SanitizeStep },
+    /// Stop running the cleanup step
+    Disable { step: SanitizeStep },
+    /// Remove the sentences matching the regular expression from chapters
+    /// (ex: "(?i)support us on patreon[.!]?")
+    Watermark { pattern: String },
+    /// Stop removing the sentences matching the regular expression
+    Unwatermark { pattern: String },
+    /// Show the cleanup steps and watermark patterns
+    List,
+}
+
+pub fn handle(action: SanitizeAction) -> anyhow::Result<()> {
+    match action {
+        SanitizeAction::Enable { step } => {
+            let persist = open_persist()?;
+            let mut config = persist.read_config()?;
+            config.sanitize.disabled.remove(&step.to_string());
+            persist.save_config(&config)?;
+            println!("{}", t!("sanitize-enabled", step = step.to_string()));
+        }
+        SanitizeAction::Disable { step } => {
+            let persist = open_persist()?;
+            let mut config = persist.read_config()?;
+            config.sanitize.disabled.insert(step.to_string());
+            persist.save_config(&config)?;
+            println!("{}", t!("sanitize-disabled", step = step.to_string()));
+        }
+        SanitizeAction::Watermark { pattern } => {
+            let persist = open_persist()?;
+            let mut config = persist.read_config()?;
+            if !config.sanitize.watermarks.contains(&pattern) {
+                config.sanitize.watermarks.push(pattern.clone());
+            }
+            // Refuse patterns that would fail every bundle
+            sanitize_options(&config.sanitize)?;
+            persist.save_config(&config)?;
+            println!("{}", t!("sanitize-watermark-added", pattern));
+        }
+        SanitizeAction::Unwatermark { pattern } => {
+            let persist = open_persist()?;
+            let mut config = persist.read_config()?;
+            config
+                .sanitize
+                .watermarks
+                .retain(|watermark| *watermark != pattern);
+            persist.save_config(&config)?;
+            println!("{}", t!("sanitize-watermark-removed", pattern));
+        }
+        SanitizeAction::List => {
+            let persist = open_persist_shared()?;
+            let options = sanitize_options(&persist.read_config()?.sanitize)?;
+            for step in SanitizeStep::ALL {
+                println!("{step}: enabled={}", options.is_enabled(step));
+            }
+            for watermark in &options.watermarks {
+                println!("watermark: {watermark}");
+            }
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,405 @@
+use std::path::PathBuf;
+
+use anyhow::anyhow;
+use clap::Subcommand;
+use log::info;
+use quelle_persist::{
+    ConflictStrategy, IndexProgress, LockMode, ObjectStoreStorage, Persist, PersistError,
+    PersistOptions, RemoteConfig, S3Store, TransferEvent, WebDavConfig, WebDavStore,
+};
+
+use crate::{
+    error::{coded, ErrorCode},
+    open_persist, open_persist_shared, passphrase, t, wait_for_lock,
+};
+
+/// The environment variable holding the password of the WebDAV share
+pub const WEBDAV_PASSWORD_VAR: &str = "QUELLE_WEBDAV_PASSWORD";
+
+/// The bucket or WebDAV share the library is stored in, if one is configured
+///
+/// The stores use blocking http clients, which may neither be created nor
+/// dropped on the runtime, so this is only called through [`with_storage`].
+fn remote_storage(persist: &Persist) -> anyhow::Result<Option<ObjectStoreStorage>> {
+    let config = persist.read_config()?;
+    if let Some(config) = config.webdav {
+        let password = std::env::var(WEBDAV_PASSWORD_VAR)
+            .ok()
+            .filter(|value| !value.is_empty());
+        let store = WebDavStore::new(config, password)?;
+        return Ok(Some(ObjectStoreStorage::new(Box::new(store))));
+    }
+
+    let Some(config) = config.remote else {
+        return Ok(None);
+    };
+
+    let key = |name: &str| {
+        std::env::var(name)
+            .ok()
+            .filter(|value| !value.is_empty())
+            .ok_or_else(|| coded(ErrorCode::RemoteFailed, t!("remote-key-missing", name)))
+    };
+
+    let store = S3Store::new(
+        config,
+        key("AWS_ACCESS_KEY_ID")?,
+        key("AWS_SECRET_ACCESS_KEY")?,
+    );
+    Ok(Some(ObjectStoreStorage::new(Box::new(store))))
+}
+
+/// Run the call with the storage of the library on a blocking thread,
+/// handing the library back along with the result of the call, or `None`
+/// when no storage is configured
+pub async fn with_storage<T: Send + 'static>(
+    persist: Persist,
+    call: impl FnOnce(&ObjectStoreStorage, &Persist) -> Result<T, PersistError> + Send + 'static,
+) -> (Persist, anyhow::Result<Option<T>>) {
+    let task = tokio::task::spawn_blocking(move || {
+        let result = remote_storage(&persist).and_then(|storage| match storage {
+            Some(storage) => Ok(Some(call(&storage, &persist)?)),
+            None => Ok(None),
+        });
+        (persist, result)
+    });
+
+    match task.await {
+        Ok(done) => done,
+        // The library is only lost when the call panicked, which is raised again
+        Err(e) => std::panic::resume_unwind(e.into_panic()),
+    }
+}
+
+/// Like [`with_storage`], failing when no storage is configured
+pub async fn with_required_storage<T: Send + 'static>(
+    persist: Persist,
+    call: impl FnOnce(&ObjectStoreStorage, &Persist) -> Result<T, PersistError> + Send + 'static,
+) -> anyhow::Result<T> {
+    with_storage(persist, call)
+        .await
+        .1?
+        .ok_or_else(|| coded(ErrorCode::RemoteFailed, t!("remote-not-configured")))
+}
+
+#[derive(Subcommand)]
+pub enum StorageAction {
+    /// Copy every novel into another library, such as one on a synced or mounted drive,
+    /// using its compression and encryption settings
+    Migrate {
+        /// The directory of the other library
+        #[arg(long)]
+        to: PathBuf,
+    },
+    /// Store the library in a bucket of an S3 compatible service, with the keys in
+    /// AWS_ACCESS_KEY_ID and AWS_SECRET_ACCESS_KEY
+    Remote {
+        /// The url of the service (ex: https://s3.us-east-1.amazonaws.com)
+        #[arg(long)]
+        endpoint: String,
+
+        #[arg(long)]
+        bucket: String,
+
+        #[arg(long, default_value = "us-east-1")]
+        region: String,
+
+        /// Prepended to the keys of the library files in the bucket
+        #[arg(long, default_value = "")]
+        prefix: String,
+    },
+    /// Store the library in a WebDAV share, such as Nextcloud, with the password
+    /// in QUELLE_WEBDAV_PASSWORD
+    Webdav {
+        /// The url of the directory holding the library
+        /// (ex: https://cloud.example.com/remote.php/dav/files/user/quelle)
+        #[arg(long)]
+        url: String,
+
+        #[arg(long)]
+        username: Option<String>,
+    },
+    /// Upload the changes of the library to the bucket or share
+    Push,
+    /// Download the library from the bucket or share, chapters are downloaded when bundled
+    Pull,
+    /// Check the chapters against their recorded checksums and that the
+    /// metadata files can be read
+    Verify,
+
+    /// Summarize every novel into the index read when listing the library
+    Reindex,
+
+    /// Find chapter files without novel data, chapters whose file is missing and
+    /// covers out of sync with the novel data
+    Cleanup {
+        /// Remove the orphaned files and forget the missing chapters and covers,
+        /// so that they are downloaded again
+        #[arg(long)]
+        fix: bool,
+    },
+    /// Store the chapters with identical content once, such as those of a novel
+    /// saved from two sources, and report the space saved
+    Dedup {
+        /// Only report the space that would be saved
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Write the whole library to a JSON file, for other tools or debugging
+    Dump {
+        /// The file to write (ex: library.json)
+        path: PathBuf,
+
+        /// Include the content of the downloaded chapters
+        #[arg(long)]
+        chapters: bool,
+    },
+    /// Add the novels of a JSON file written by the dump command
+    Load {
+        path: PathBuf,
+
+        /// What to do with novels that already exist: skip, overwrite or fail
+        #[arg(long, default_value = "skip")]
+        on_conflict: ConflictStrategy,
+    },
+}
+
+pub async fn handle(action: StorageAction) -> anyhow::Result<()> {
+    match action {
+        StorageAction::Migrate { to } => {
+            let persist = open_persist()?;
+            if to == persist.options.base_dir {
+                return Err(anyhow!(t!("storage-same-library")));
+            }
+
+            let mut target = Persist::new(PersistOptions::with_base_dir(to));
+            target.lock(LockMode::Exclusive, wait_for_lock())?;
+            target.unlock(passphrase().as_deref())?;
+            target.initialize()?;
+
+            let report = persist.transfer_to(&target, |event| match event {
+                TransferEvent::Copied {
+                    title,
+                    number,
+                    total,
+                    chapters,
+                } => println!(
+                    "{}",
+                    t!(
+                        "storage-novel-copied",
+                        title = title,
+                        number = number,
+                        total = total,
+                        chapters = chapters
+                    )
+                ),
+                TransferEvent::Skipped { url, .. } => {
+                    info!("'{url}' was copied by a previous run.")
+                }
+            })?;
+
+            println!(
+                "{}",
+                t!(
+                    "storage-migrated",
+                    novels = report.novels,
+                    chapters = report.chapters,
+                    skipped = report.skipped
+                )
+            );
+        }
+        StorageAction::Remote {
+            endpoint,
+            bucket,
+            region,
+            prefix,
+        } => {
+            let persist = open_persist()?;
+            let mut config = persist.read_config()?;
+            config.remote = Some(RemoteConfig {
+                endpoint,
+                bucket,
+                region,
+                prefix,
+            });
+            config.webdav = None;
+            persist.save_config(&config)?;
+        }
+        StorageAction::Webdav { url, username } => {
+            let persist = open_persist()?;
+            let mut config = persist.read_config()?;
+            config.webdav = Some(WebDavConfig { url, username });
+            config.remote = None;
+            persist.save_config(&config)?;
+        }
+        StorageAction::Push => {
+            let persist = open_persist()?;
+            let report =
+                with_required_storage(persist, |storage, persist| storage.push(persist)).await?;
+            println!(
+                "{}",
+                t!(
+                    "remote-pushed",
+                    uploaded = report.uploaded,
+                    unchanged = report.unchanged
+                )
+            );
+        }
+        StorageAction::Pull => {
+            let persist = open_persist()?;
+            let report =
+                with_required_storage(persist, |storage, persist| storage.pull(persist)).await?;
+            println!(
+                "{}",
+                t!(
+                    "remote-pulled",
+                    downloaded = report.downloaded,
+                    unchanged = report.unchanged
+                )
+            );
+        }
+        StorageAction::Reindex => {
+            let persist = open_persist()?;
+            let index = persist.rebuild_index(|progress| {
+                let IndexProgress { url, number, total } = progress;
+                info!("[{number}/{total}] Indexed '{url}'.");
+            })?;
+            println!("{}", t!("storage-reindexed", count = index.novels.len()));
+        }
+        StorageAction::Verify => {
+            let persist = open_persist()?;
+            let report = persist.verify()?;
+
+            for entry in &report.corrupted {
+                println!("{}: {}", entry.path.display(), entry.kind);
+            }
+
+            let (chapters, checksums) = (report.chapters, report.checksums);
+            if !report.is_empty() {
+                return Err(coded(
+                    ErrorCode::StoreCorrupt,
+                    t!("verify-corrupted", count = report.corrupted.len()),
+                ));
+            }
+            println!("{}", t!("verify-intact", chapters, checksums));
+        }
+        StorageAction::Cleanup { fix } => {
+            let persist = open_persist()?;
+            let report = persist.cleanup(fix)?;
+
+            for path in &report.orphaned_chapters {
+                println!("{}", t!("cleanup-orphaned-chapter", path = path.display()));
+            }
+            for (dir, url) in &report.missing_chapters {
+                println!(
+                    "{}",
+                    t!("cleanup-missing-chapter", url = url, dir = dir.display())
+                );
+            }
+            for path in &report.dangling_covers {
+                println!("{}", t!("cleanup-dangling-cover", path = path.display()));
+            }
+
+            let (orphaned, missing, covers) = (
+                report.orphaned_chapters.len(),
+                report.missing_chapters.len(),
+                report.dangling_covers.len(),
+            );
+            if report.is_empty() {
+                println!("{}", t!("cleanup-clean"));
+            } else if report.fixed {
+                println!("{}", t!("cleanup-fixed", orphaned, missing, covers));
+            } else {
+                println!("{}", t!("cleanup-found", orphaned, missing, covers));
+            }
+        }
+        StorageAction::Dedup { dry_run } => {
+            let persist = open_persist()?;
+            let report = persist.deduplicate(dry_run)?;
+            println!(
+                "{}",
+                t!(
+                    "storage-deduplicated",
+                    chapters = report.chapters,
+                    shared = report.shared,
+                    deduplicated = report.deduplicated,
+                    saved = report.saved_bytes
+                )
+            );
+        }
+        StorageAction::Dump { path, chapters } => {
+            let persist = open_persist_shared()?;
+            let dump = persist.dump_json(&path, chapters)?;
+            println!(
+                "{}",
+                t!(
+                    "storage-dumped",
+                    count = dump.novels.len(),
+                    path = path.display()
+                )
+            );
+        }
+        StorageAction::Load { path, on_conflict } => {
+            let persist = open_persist()?;
+            let report = persist.load_json(&path, on_conflict)?;
+            println!(
+                "{}",
+                t!(
+                    "storage-loaded",
+                    novels = report.novels,
+                    chapters = report.chapters,
+                    skipped = report.skipped.len()
+                )
+            );
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn should_use_bucket_from_the_runtime() {
+        let dir = tempfile::tempdir().unwrap();
+        let persist = Persist::new(PersistOptions::with_base_dir(dir.path().to_path_buf()));
+
+        let (persist, pushed) =
+            with_storage(persist, |storage, persist| storage.push(persist)).await;
+        assert!(pushed.unwrap().is_none());
+
+        // Nothing listens on the port, so the push fails instead of panicking
+        // when the client is dropped
+        let mut config = persist.read_config().unwrap();
+        config.remote = Some(RemoteConfig {
+            endpoint: String::from("http://127.0.0.1:1"),
+            bucket: String::from("quelle"),
+            region: String::from("us-east-1"),
+            prefix: String::new(),
+        });
+        persist.save_config(&config).unwrap();
+        std::env::set_var("AWS_ACCESS_KEY_ID", "key");
+        std::env::set_var("AWS_SECRET_ACCESS_KEY", "secret");
+
+        let pushed = with_required_storage(persist, |storage, persist| storage.push(persist)).await;
+        assert!(pushed.is_err());
+    }
+
+    #[tokio::test]
+    async fn should_use_webdav_share_from_the_runtime() {
+        let dir = tempfile::tempdir().unwrap();
+        let persist = Persist::new(PersistOptions::with_base_dir(dir.path().to_path_buf()));
+
+        let mut config = persist.read_config().unwrap();
+        config.webdav = Some(WebDavConfig {
+            url: String::from("http://127.0.0.1:1/quelle"),
+            username: None,
+        });
+        persist.save_config(&config).unwrap();
+
+        let (_, pulled) = with_storage(persist, |storage, persist| storage.pull(persist)).await;
+        assert!(pulled.is_err());
+    }
+}
@@ -0,0 +1,60 @@
+use anyhow::anyhow;
+use clap::Subcommand;
+use url::Url;
+
+use crate::{open_persist, t};
+
+#[derive(Subcommand)]
+pub enum TrashAction {
+    /// List the novels in the trash, the most recently deleted last
+    List,
+
+    /// Move a deleted novel back into the library
+    Restore {
+        /// The url of the novel
+        url: Url,
+    },
+
+    /// Permanently delete the novels kept longer than the retention of the library
+    Purge {
+        /// Delete every novel in the trash
+        #[arg(long)]
+        all: bool,
+    },
+}
+
+pub fn handle(action: TrashAction) -> anyhow::Result<()> {
+    let persist = open_persist()?;
+
+    match action {
+        TrashAction::List => {
+            for novel in persist.read_trash()?.novels() {
+                println!(
+                    "{}  {}  {}",
+                    novel.deleted_at.format("%Y-%m-%d %H:%M"),
+                    novel.title,
+                    novel.url
+                );
+            }
+        }
+        TrashAction::Restore { url } => {
+            let novel = persist
+                .restore_novel(url.as_str())?
+                .ok_or_else(|| anyhow!(t!("trash-not-found", url)))?;
+
+            println!("{}", t!("novel-restored", title = novel.title));
+        }
+        TrashAction::Purge { all } => {
+            let retention = if all {
+                chrono::Duration::zero()
+            } else {
+                persist.read_config()?.trash_retention()
+            };
+
+            let count = persist.purge_trash(retention)?.len();
+            println!("{}", t!("trash-purged", count));
+        }
+    }
+
+    Ok(())
+}
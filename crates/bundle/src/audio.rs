@@ -0,0 +1,446 @@
+use std::{
+    fs::{self, File},
+    io::{self, Write},
+    path::{Path, PathBuf},
+    process::{Child, Command, ExitStatus, Stdio},
+    thread,
+    time::Duration,
+};
+
+use log::{info, warn};
+
+use crate::{
+    data::{cover_image, image_extension, Bundle},
+    progress::{CancellationToken, Cancelled},
+    text::{strip_title_heading, text_paragraphs},
+    work::work_dir,
+};
+
+/// The placeholder replaced with the path of the audio file in the
+/// arguments of a speech command
+pub const OUTPUT_PLACEHOLDER: &str = "{output}";
+
+/// How often the speech command and ffmpeg are checked on while they run
+const COMMAND_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Turns text into speech, written as a WAV file
+///
+/// Implement this to use a speech engine other than the local commands of
+/// [`SpeechEngine`], such as a cloud service. Backends should stop with
+/// [`Cancelled`] once `cancel` is cancelled.
+pub trait SpeechBackend {
+    fn synthesize(
+        &self,
+        text: &str,
+        output: &Path,
+        cancel: &CancellationToken,
+    ) -> Result<(), Box<dyn std::error::Error>>;
+}
+
+/// The local speech engines, run as commands given the text on stdin
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum SpeechEngine {
+    /// eSpeak NG with an optional voice
+    Espeak { voice: Option<String> },
+    /// Piper with the path of a voice model
+    Piper { model: String },
+    /// Any command reading text on stdin, with [`OUTPUT_PLACEHOLDER`] in its
+    /// arguments replaced by the WAV file to write
+    Command { program: String, args: Vec<String> },
+}
+
+impl Default for SpeechEngine {
+    fn default() -> Self {
+        Self::Espeak { voice: None }
+    }
+}
+
+impl SpeechEngine {
+    fn command(&self) -> (String, Vec<String>) {
+        let owned = |args: &[&str]| args.iter().map(ToString::to_string).collect::<Vec<_>>();
+        match self {
+            Self::Espeak { voice } => {
+                let mut args = owned(&["--stdin", "-w", OUTPUT_PLACEHOLDER]);
+                if let Some(voice) = voice {
+                    args.extend([String::from("-v"), voice.clone()]);
+                }
+                (String::from("espeak-ng"), args)
+            }
+            Self::Piper { model } => (
+                String::from("piper"),
+                owned(&["--model", model, "--output_file", OUTPUT_PLACEHOLDER]),
+            ),
+            Self::Command { program, args } => (program.clone(), args.clone()),
+        }
+    }
+}
+
+impl SpeechBackend for SpeechEngine {
+    fn synthesize(
+        &self,
+        text: &str,
+        output: &Path,
+        cancel: &CancellationToken,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let (program, args) = self.command();
+        let args = args
+            .iter()
+            .map(|arg| arg.replace(OUTPUT_PLACEHOLDER, &output.to_string_lossy()));
+
+        let mut child = Command::new(&program)
+            .args(args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .stderr(Stdio::inherit())
+            .spawn()
+            .map_err(|e| format!("failed to start '{program}': {e}"))?;
+
+        // Written from another thread so that the command is always waited
+        // for, even when it exits without reading the whole text
+        let mut stdin = child.stdin.take().expect("stdin is piped");
+        let input = text.to_string();
+        let writer = thread::spawn(move || stdin.write_all(input.as_bytes()));
+
+        let status = wait_or_kill(&mut child, cancel);
+        let written = writer.join().map_err(|_| "failed to write the text")?;
+        let status = status?;
+        if !status.success() {
+            return Err(format!("'{program}' exited with {status}").into());
+        }
+        Ok(written?)
+    }
+}
+
+/// How chapters are spoken and encoded into an audiobook
+#[derive(Clone, Debug)]
+pub struct AudioOptions {
+    pub engine: SpeechEngine,
+    /// The ffmpeg program used to join and encode the chapters
+    pub ffmpeg: String,
+}
+
+impl Default for AudioOptions {
+    fn default() -> Self {
+        Self {
+            engine: SpeechEngine::default(),
+            ffmpeg: String::from("ffmpeg"),
+        }
+    }
+}
+
+/// The audio files an audiobook can be encoded into
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum AudioContainer {
+    M4b,
+    Mp3,
+}
+
+/// Speak the chapters of the novel and encode them into a single audiobook
+/// with a chapter marker and the metadata of the novel
+pub fn bundle_audio<B: Bundle, W: Write>(
+    bundle: &B,
+    out: &mut W,
+    options: &AudioOptions,
+    container: AudioContainer,
+    cancel: &CancellationToken,
+) -> Result<(), Box<dyn std::error::Error>> {
+    bundle_audio_with(
+        bundle,
+        out,
+        &options.engine,
+        &options.ffmpeg,
+        container,
+        cancel,
+    )
+}
+
+/// Like [`bundle_audio`] with any speech backend
+pub fn bundle_audio_with<B: Bundle, W: Write>(
+    bundle: &B,
+    out: &mut W,
+    backend: &dyn SpeechBackend,
+    ffmpeg: &str,
+    container: AudioContainer,
+    cancel: &CancellationToken,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let dir = work_dir("audio")?;
+    let mut chapters = vec![];
+
+    for (position, chapter) in bundle.chapters() {
+        let title = bundle
+            .chapter_title(chapter, position)
+            .unwrap_or_else(|| chapter.title.clone());
+
+        let Some(content) = bundle.chapter_content(&chapter.url)? else {
+            warn!("Skipping '{}' as it is not downloaded.", chapter.title);
+            continue;
+        };
+
+        // The title is read once, whether or not the content repeats it
+        let mut paragraphs = text_paragraphs(&content);
+        strip_title_heading(&mut paragraphs, &[&title, &chapter.title]);
+        paragraphs.insert(0, title.clone());
+
+        let path = dir.path().join(format!("{position:05}.wav"));
+        backend.synthesize(&paragraphs.join("\n\n"), &path, cancel)?;
+        let duration = wav_duration(&fs::read(&path)?)
+            .ok_or_else(|| format!("the speech of '{}' is not a valid WAV file", title))?;
+        chapters.push(AudioChapter {
+            title,
+            path,
+            duration,
+        });
+
+        info!("Spoken '{}'.", chapter.title);
+    }
+
+    if chapters.is_empty() {
+        return Err("none of the chapters are downloaded".into());
+    }
+
+    let list = dir.path().join("chapters.txt");
+    fs::write(&list, concat_list(&chapters))?;
+    let metadata = dir.path().join("metadata.txt");
+    fs::write(&metadata, ffmetadata(bundle, &chapters))?;
+
+    let cover = cover(bundle, dir.path())?;
+    let output = dir.path().join(match container {
+        AudioContainer::M4b => "book.m4b",
+        AudioContainer::Mp3 => "book.mp3",
+    });
+
+    let mut command = Command::new(ffmpeg);
+    command
+        .args([
+            "-loglevel",
+            "error",
+            "-y",
+            "-f",
+            "concat",
+            "-safe",
+            "0",
+            "-i",
+        ])
+        .arg(&list)
+        .arg("-i")
+        .arg(&metadata);
+    if let Some(cover) = &cover {
+        command.arg("-i").arg(cover);
+    }
+    command.args(["-map_metadata", "1", "-map_chapters", "1", "-map", "0:a"]);
+    if cover.is_some() {
+        command.args([
+            "-map",
+            "2:v",
+            "-c:v",
+            "copy",
+            "-disposition:v",
+            "attached_pic",
+        ]);
+    }
+    match container {
+        AudioContainer::M4b => command.args(["-c:a", "aac", "-b:a", "64k", "-f", "ipod"]),
+        AudioContainer::Mp3 => {
+            command.args(["-c:a", "libmp3lame", "-q:a", "6", "-id3v2_version", "3"])
+        }
+    };
+    let mut child = command
+        .arg(&output)
+        .spawn()
+        .map_err(|e| format!("failed to start '{ffmpeg}': {e}"))?;
+    let status = wait_or_kill(&mut child, cancel)?;
+    if !status.success() {
+        return Err(format!("'{ffmpeg}' exited with {status}").into());
+    }
+
+    io::copy(&mut File::open(&output)?, out)?;
+    out.flush()?;
+
+    info!("Audiobook writing complete.");
+    Ok(())
+}
+
+/// Wait for the command to exit, killing it once the bundle is cancelled as
+/// speaking a chapter or encoding a long novel takes a while
+fn wait_or_kill(
+    child: &mut Child,
+    cancel: &CancellationToken,
+) -> Result<ExitStatus, Box<dyn std::error::Error>> {
+    loop {
+        if let Some(status) = child.try_wait()? {
+            return Ok(status);
+        }
+        if cancel.is_cancelled() {
+            let _ = child.kill();
+            let _ = child.wait();
+            return Err(Cancelled.into());
+        }
+        thread::sleep(COMMAND_POLL_INTERVAL);
+    }
+}
+
+struct AudioChapter {
+    title: String,
+    path: PathBuf,
+    /// The length of the speech in milliseconds
+    duration: u64,
+}
+
+/// The length of the WAV audio in milliseconds
+fn wav_duration(wav: &[u8]) -> Option<u64> {
+    if wav.get(..4)? != b"RIFF" || wav.get(8..12)? != b"WAVE" {
+        return None;
+    }
+
+    let mut byte_rate = None;
+    let mut offset = 12;
+    while offset + 8 <= wav.len() {
+        let id = &wav[offset..offset + 4];
+        let size = u32::from_le_bytes(wav[offset + 4..offset + 8].try_into().ok()?) as u64;
+        let data = offset + 8;
+
+        match id {
+            b"fmt " => {
+                let rate = wav.get(data + 8..data + 12)?;
+                byte_rate = Some(u32::from_le_bytes(rate.try_into().ok()?) as u64);
+            }
+            b"data" => {
+                // Streamed files may not know their size, so the rest is the data
+                let size = size.min((wav.len() - data) as u64);
+                return Some(size * 1000 / byte_rate.filter(|rate| *rate > 0)?);
+            }
+            _ => {}
+        }
+        // Chunks are padded to an even size
+        offset = data + size as usize + (size as usize % 2);
+    }
+
+    None
+}
+
+/// The list of files joined by the concat demuxer of ffmpeg
+fn concat_list(chapters: &[AudioChapter]) -> String {
+    chapters
+        .iter()
+        .map(|chapter| {
+            let path = chapter.path.to_string_lossy().replace('\'', r"'\''");
+            format!("file '{path}'\n")
+        })
+        .collect()
+}
+
+/// The metadata and chapter markers of the audiobook in the ffmetadata format
+fn ffmetadata<B: Bundle>(bundle: &B, chapters: &[AudioChapter]) -> String {
+    let novel = bundle.novel();
+    let title = match bundle.part() {
+        Some(part) => format!(
+            "{} (Part {} of {})",
+            novel.title,
+            part.number(),
+            part.total()
+        ),
+        None => novel.title.clone(),
+    };
+
+    let mut metadata = String::from(";FFMETADATA1\n");
+    metadata += &format!("title={}\n", escape_metadata(&title));
+    metadata += &format!("album={}\n", escape_metadata(&novel.title));
+    if !novel.authors.is_empty() {
+        metadata += &format!("artist={}\n", escape_metadata(&novel.authors.join(", ")));
+    }
+    if !novel.description.is_empty() {
+        metadata += &format!(
+            "comment={}\n",
+            escape_metadata(&novel.description.join("\n"))
+        );
+    }
+    if let Some(rights) = bundle.rights() {
+        metadata += &format!("copyright={}\n", escape_metadata(rights));
+    }
+    metadata += "genre=Audiobook\n";
+
+    let mut start = 0;
+    for chapter in chapters {
+        let end = start + chapter.duration;
+        metadata += &format!(
+            "\n[CHAPTER]\nTIMEBASE=1/1000\nSTART={start}\nEND={end}\ntitle={}\n",
+            escape_metadata(&chapter.title)
+        );
+        start = end;
+    }
+
+    metadata
+}
+
+fn escape_metadata(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        if matches!(c, '=' | ';' | '#' | '\\' | '\n') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+/// Write the cover into the directory to attach it to the audiobook
+fn cover<B: Bundle>(bundle: &B, dir: &Path) -> Result<Option<PathBuf>, Box<dyn std::error::Error>> {
+    let Some((content_type, content)) = cover_image(bundle)? else {
+        return Ok(None);
+    };
+    let path = dir.join(format!("cover.{}", image_extension(&content_type)));
+    fs::write(&path, content)?;
+    Ok(Some(path))
+}
+
+#[cfg(test)]
+mod tests {
+    use quelle_core::prelude::*;
+
+    use super::*;
+    use crate::test_support::TestBundle;
+
+    fn wav(seconds: u32) -> Vec<u8> {
+        let byte_rate: u32 = 16000 * 2;
+        let size = byte_rate * seconds;
+
+        let mut wav = b"RIFF".to_vec();
+        wav.extend((36 + size).to_le_bytes());
+        wav.extend(b"WAVEfmt ");
+        wav.extend(16u32.to_le_bytes());
+        wav.extend(1u16.to_le_bytes());
+        wav.extend(1u16.to_le_bytes());
+        wav.extend(16000u32.to_le_bytes());
+        wav.extend(byte_rate.to_le_bytes());
+        wav.extend(2u16.to_le_bytes());
+        wav.extend(16u16.to_le_bytes());
+        wav.extend(b"data");
+        wav.extend(size.to_le_bytes());
+        wav.extend(vec![0; size as usize]);
+        wav
+    }
+
+    #[test]
+    fn should_mark_chapters_by_speech_duration() {
+        assert_eq!(wav_duration(&wav(2)), Some(2000));
+        assert_eq!(wav_duration(b"not a wav file"), None);
+
+        let bundle = TestBundle::new(Novel {
+            title: String::from("Novel"),
+            authors: vec![String::from("A=B")],
+            ..Default::default()
+        });
+        let chapters = ["One", "Two"]
+            .into_iter()
+            .map(|title| AudioChapter {
+                title: title.to_string(),
+                path: PathBuf::from(format!("{title}.wav")),
+                duration: 1500,
+            })
+            .collect::<Vec<_>>();
+
+        let metadata = ffmetadata(&bundle, &chapters);
+        assert!(metadata.starts_with(";FFMETADATA1\ntitle=Novel\nalbum=Novel\nartist=A\\=B\n"));
+        assert!(metadata.ends_with("START=1500\nEND=3000\ntitle=Two\n"));
+    }
+}
@@ -0,0 +1,192 @@
+use std::{
+    io::Write,
+    path::Path,
+    process::{Command, Stdio},
+};
+
+use log::{info, warn};
+use quelle_core::prelude::Novel;
+
+use crate::metadata::MetadataMapping;
+
+/// The identifier type books are tagged with in the Calibre library, so that
+/// bundling a novel again updates its book
+const IDENTIFIER: &str = "quelle";
+
+/// The Calibre library bundles are added to with `calibredb`
+#[derive(Clone, Debug)]
+pub struct CalibreOptions {
+    /// The calibredb program
+    pub program: String,
+    /// The path of the library or the url of a content server, such as
+    /// `http://localhost:8080/#library`, the last used library by default
+    pub library: Option<String>,
+    /// The username of the content server
+    pub username: Option<String>,
+    /// The password of the content server
+    pub password: Option<String>,
+    /// The columns the metadata of novels is written to
+    pub metadata: MetadataMapping,
+}
+
+impl Default for CalibreOptions {
+    fn default() -> Self {
+        Self {
+            program: String::from("calibredb"),
+            library: None,
+            username: None,
+            password: None,
+            metadata: Default::default(),
+        }
+    }
+}
+
+/// The book of the Calibre library a bundle was added to
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CalibreBook {
+    pub id: u64,
+    /// Whether the book was added, or already there and updated
+    pub added: bool,
+}
+
+/// Add the bundle to the Calibre library, replacing the file of its format and
+/// the title, authors and tags when the library has a book with the identifier,
+/// and set the columns the metadata of the novel is mapped to
+///
+/// The identifier tells the novel, and the part of a split novel, apart from
+/// the other books of the library.
+pub fn add_to_calibre(
+    options: &CalibreOptions,
+    novel: &Novel,
+    identifier: &str,
+    path: &Path,
+) -> Result<CalibreBook, Box<dyn std::error::Error>> {
+    let search = format!("identifiers:{IDENTIFIER}:{identifier}");
+    let found = calibredb(options, &["search", "--limit", "1", &search]).ok();
+    let path = path.to_string_lossy();
+
+    let book = match found
+        .as_deref()
+        .and_then(|ids| ids.trim().parse::<u64>().ok())
+    {
+        Some(id) => {
+            calibredb(options, &["add_format", &id.to_string(), &path])?;
+            info!("Updated calibre book {id}");
+            CalibreBook { id, added: false }
+        }
+        None => {
+            let identifier = format!("{IDENTIFIER}:{identifier}");
+            let output = calibredb(options, &["add", "--identifier", &identifier, &path])?;
+            let id = added_id(&output).ok_or_else(|| {
+                format!(
+                    "calibre did not add '{path}', it may already have a book with the same title"
+                )
+            })?;
+            info!("Added calibre book {id}");
+            CalibreBook { id, added: true }
+        }
+    };
+
+    // Calibre reads the metadata of the file only when the book is added
+    let mut fields = vec![];
+    if !book.added {
+        fields.push(format!("title:{}", novel.title));
+        if !novel.authors.is_empty() {
+            fields.push(format!("authors:{}", novel.authors.join(" & ")));
+        }
+        let tags = options.metadata.dublin_core(novel, "subject");
+        if !tags.is_empty() {
+            fields.push(format!("tags:{}", tags.join(",")));
+        }
+    }
+    for (column, values) in options.metadata.calibre(novel) {
+        fields.push(format!("{column}:{}", values.join(",")));
+    }
+
+    if !fields.is_empty() {
+        let id = book.id.to_string();
+        let mut args = vec!["set_metadata", &id];
+        for field in &fields {
+            args.extend(["--field", field]);
+        }
+        // Columns the library does not have fail the whole command
+        if let Err(e) = calibredb(options, &args) {
+            warn!("failed to set the metadata of calibre book {id}: {e}");
+        }
+    }
+
+    Ok(book)
+}
+
+/// Run calibredb on the library, returning its output
+fn calibredb(
+    options: &CalibreOptions,
+    args: &[&str],
+) -> Result<String, Box<dyn std::error::Error>> {
+    let mut command = Command::new(&options.program);
+    command.args(args);
+    if let Some(library) = &options.library {
+        command.arg("--with-library").arg(library);
+    }
+    if let Some(username) = &options.username {
+        command.arg("--username").arg(username);
+    }
+    // The password is read from stdin so that it does not show in the process list
+    if options.password.is_some() {
+        command.arg("--password").arg("<stdin>");
+    }
+
+    let mut child = command
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("failed to start '{}': {e}", options.program))?;
+    let mut stdin = child.stdin.take().expect("stdin is piped");
+    if let Some(password) = &options.password {
+        writeln!(stdin, "{password}")?;
+    }
+    drop(stdin);
+
+    let output = child.wait_with_output()?;
+    if !output.status.success() {
+        let error = String::from_utf8_lossy(&output.stderr);
+        return Err(format!(
+            "'{} {}' exited with {}: {}",
+            options.program,
+            args[0],
+            output.status,
+            error.trim()
+        )
+        .into());
+    }
+
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// The id of the book added by `calibredb add` from its output
+fn added_id(output: &str) -> Option<u64> {
+    output
+        .lines()
+        .find_map(|line| line.trim().strip_prefix("Added book ids:"))
+        .and_then(|ids| ids.split(',').next())
+        .and_then(|id| id.trim().parse().ok())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_read_added_book_id() {
+        assert_eq!(added_id("Added book ids: 42\n"), Some(42));
+        assert_eq!(
+            added_id("Backing up metadata\nAdded book ids: 7, 8"),
+            Some(7)
+        );
+        assert_eq!(
+            added_id("The following books were not added as they already exist"),
+            None
+        );
+    }
+}
@@ -0,0 +1,206 @@
+use std::io::{Seek, Write};
+
+use log::{info, warn};
+use zip::{write::FileOptions, CompressionMethod, ZipWriter};
+
+use crate::{
+    data::{image_extension, Bundle},
+    images::image_sources,
+    text::escape,
+};
+
+/// Write the images of the chapters as a comic book archive, for novels whose
+/// chapters are pages of images such as manga and manhwa
+///
+/// Images are read from the stored assets of the novel, so the chapters
+/// need to have been downloaded with their images. Pages are named after
+/// their chapter and position so that readers show them in order.
+pub fn bundle_cbz<B: Bundle, W: Write + Seek>(
+    bundle: &B,
+    out: W,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut zip = ZipWriter::new(out);
+    // Images are already compressed
+    let options = FileOptions::default().compression_method(CompressionMethod::Stored);
+
+    let chapters = bundle.chapters();
+    let width = chapters.len().to_string().len().max(3);
+    let mut pages = 0;
+
+    for (position, chapter) in &chapters {
+        let Some(content) = bundle.chapter_content(&chapter.url)? else {
+            warn!("Skipping '{}' as it is not downloaded.", chapter.title);
+            continue;
+        };
+
+        let mut count = 0;
+        for source in image_sources(&content) {
+            let Some((content_type, image)) = bundle.asset(&source)? else {
+                warn!(
+                    "Skipping the image '{source}' of '{}' as it is not stored.",
+                    chapter.title
+                );
+                continue;
+            };
+
+            count += 1;
+            let name = format!(
+                "{position:0width$}-{count:03}.{}",
+                image_extension(&content_type)
+            );
+            zip.start_file(name, options)?;
+            zip.write_all(&image)?;
+        }
+
+        if count == 0 {
+            warn!("No stored images found for '{}'.", chapter.title);
+        }
+        pages += count;
+        info!("Written {count} pages of '{}'.", chapter.title);
+    }
+
+    if pages == 0 {
+        return Err("none of the chapters have stored images".into());
+    }
+
+    zip.start_file("ComicInfo.xml", FileOptions::default())?;
+    zip.write_all(comic_info(bundle, pages).as_bytes())?;
+    zip.finish()?;
+
+    info!("Cbz writing complete.");
+    Ok(())
+}
+
+/// The ComicInfo.xml metadata read by comic readers and library managers
+fn comic_info<B: Bundle>(bundle: &B, pages: usize) -> String {
+    let novel = bundle.novel();
+    let mut fields = vec![];
+
+    let title = match bundle.part() {
+        Some(part) => format!(
+            "{} (Part {} of {})",
+            novel.title,
+            part.number(),
+            part.total()
+        ),
+        None => novel.title.clone(),
+    };
+    fields.push(("Title", title));
+    fields.push(("Series", novel.title.clone()));
+    if let Some(part) = bundle.part() {
+        fields.push(("Number", part.number().to_string()));
+        fields.push(("Count", part.total().to_string()));
+    }
+    if !novel.description.is_empty() {
+        fields.push(("Summary", novel.description.join("\n\n")));
+    }
+    if !novel.authors.is_empty() {
+        fields.push(("Writer", novel.authors.join(", ")));
+    }
+
+    let genres = novel
+        .metadata
+        .iter()
+        .filter(|metadata| ["subject", "tag"].contains(&metadata.name.as_str()))
+        .map(|metadata| metadata.value.as_str())
+        .collect::<Vec<_>>();
+    if !genres.is_empty() {
+        fields.push(("Genre", genres.join(", ")));
+    }
+    fields.push(("Web", novel.url.clone()));
+    fields.push(("PageCount", pages.to_string()));
+    if let Some(lang) = novel.langs.first() {
+        fields.push(("LanguageISO", lang.clone()));
+    }
+    if bundle.rights().is_some() || bundle.notes().is_some() {
+        let notes = [bundle.rights(), bundle.notes()];
+        fields.push((
+            "Notes",
+            notes.into_iter().flatten().collect::<Vec<_>>().join("\n\n"),
+        ));
+    }
+
+    let mut xml = String::from(concat!(
+        r#"<?xml version="1.0" encoding="utf-8"?>"#,
+        "\n",
+        r#"<ComicInfo xmlns:xsi="http://www.w3.org/2001/XMLSchema-instance" xmlns:xsd="http://www.w3.org/2001/XMLSchema">"#,
+        "\n"
+    ));
+    for (name, value) in fields {
+        xml += &format!("  <{name}>{}</{name}>\n", escape(&value));
+    }
+    xml += "</ComicInfo>\n";
+    xml
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{Cursor, Read};
+
+    use quelle_core::prelude::*;
+    use zip::ZipArchive;
+
+    use super::*;
+    use crate::test_support::TestBundle;
+
+    #[test]
+    fn should_package_stored_chapter_images() {
+        let chapter = |url: &str| Chapter {
+            index: 0,
+            title: url.to_string(),
+            url: url.to_string(),
+            updated_at: None,
+            number: None,
+            part: None,
+            label: None,
+        };
+        let bundle = TestBundle::new(Novel {
+            title: String::from("Comic"),
+            authors: vec![String::from("Artist")],
+            volumes: vec![Volume {
+                chapters: vec![chapter("one"), chapter("two")],
+                ..Default::default()
+            }],
+            ..Default::default()
+        })
+        .content(|url| {
+            Some(format!(
+                r#"<img src="{url}/a.jpg"><img src="{url}/missing.jpg"><img src="{url}/b.png">"#
+            ))
+        })
+        .asset(|url| {
+            let content_type = match url.rsplit('.').next() {
+                _ if url.contains("missing") => return None,
+                Some("png") => "image/png",
+                _ => "image/jpeg",
+            };
+            Some((content_type.to_string(), url.as_bytes().to_vec()))
+        });
+
+        let mut out = Cursor::new(vec![]);
+        bundle_cbz(&bundle, &mut out).unwrap();
+
+        let mut archive = ZipArchive::new(out).unwrap();
+        let mut names = archive.file_names().map(String::from).collect::<Vec<_>>();
+        names.sort();
+        assert_eq!(
+            names,
+            vec![
+                "001-001.jpg",
+                "001-002.png",
+                "002-001.jpg",
+                "002-002.png",
+                "ComicInfo.xml"
+            ]
+        );
+
+        let mut info = String::new();
+        archive
+            .by_name("ComicInfo.xml")
+            .unwrap()
+            .read_to_string(&mut info)
+            .unwrap();
+        assert!(info.contains("<Writer>Artist</Writer>"));
+        assert!(info.contains("<PageCount>4</PageCount>"));
+    }
+}
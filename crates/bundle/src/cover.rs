@@ -0,0 +1,178 @@
+use log::info;
+
+use crate::{
+    data::{cover_image, Bundle},
+    text::{escape, wrap},
+};
+
+#[cfg(any(feature = "epub", feature = "fb2"))]
+use crate::magick::convert;
+
+pub(crate) const SVG: &str = "image/svg+xml";
+
+/// The size of generated covers, the 2:3 ratio of most e-book covers
+const WIDTH: usize = 1600;
+const HEIGHT: usize = 2400;
+
+/// The background and text colours of generated covers, picked by the title so
+/// that a novel keeps its colours
+const PALETTE: [(&str, &str); 8] = [
+    ("#1d3557", "#f1faee"),
+    ("#2a4d3a", "#f4f1de"),
+    ("#6b2737", "#fbeee0"),
+    ("#3d2c5e", "#f3e9ff"),
+    ("#264653", "#e9c46a"),
+    ("#7a4419", "#fdf0d5"),
+    ("#22223b", "#f2e9e4"),
+    ("#5c3d2e", "#f6e7cb"),
+];
+
+const FONT: &str = "Georgia, 'Times New Roman', serif";
+
+/// The cover of the novel, or a typographic cover when the source has none so
+/// that the bundle does not show blank in reader libraries
+pub(crate) fn cover_or_generated<B: Bundle>(
+    bundle: &B,
+) -> Result<(String, Vec<u8>), Box<dyn std::error::Error>> {
+    match cover_image(bundle)? {
+        Some(cover) => Ok(cover),
+        None => Ok(generated_cover(bundle)),
+    }
+}
+
+/// A cover with the title and authors of the novel on a coloured background,
+/// as an SVG image
+pub(crate) fn generated_cover<B: Bundle>(bundle: &B) -> (String, Vec<u8>) {
+    let novel = bundle.novel();
+    let subtitle = bundle
+        .part()
+        .map(|part| format!("Part {} of {}", part.number(), part.total()));
+
+    info!("Generated a cover for '{}'", novel.title);
+    let svg = cover_svg(&novel.title, subtitle.as_deref(), &novel.authors);
+    (SVG.to_string(), svg.into_bytes())
+}
+
+/// Convert an SVG cover into a png for readers that only show raster covers,
+/// keeping it as is when ImageMagick fails
+#[cfg(any(feature = "epub", feature = "fb2"))]
+pub(crate) fn rasterize(
+    program: &str,
+    content_type: String,
+    content: Vec<u8>,
+) -> (String, Vec<u8>) {
+    if content_type != SVG {
+        return (content_type, content);
+    }
+
+    let args = [String::from("svg:-"), String::from("png:-")];
+    match convert(program, &args, &content) {
+        Ok(png) => (String::from("image/png"), png),
+        Err(e) => {
+            log::warn!("Keeping the cover as an SVG image: {e}");
+            (content_type, content)
+        }
+    }
+}
+
+fn cover_svg(title: &str, subtitle: Option<&str>, authors: &[String]) -> String {
+    let (background, foreground) = PALETTE[fnv(title) as usize % PALETTE.len()];
+
+    // Long titles are set smaller so that they fit in fewer lines
+    let (width, size) = match title.chars().count() {
+        0..=40 => (14, 140),
+        41..=90 => (20, 100),
+        _ => (26, 76),
+    };
+    let mut lines = wrap(title, width);
+    lines.truncate(9);
+
+    let center = WIDTH / 2;
+    let line_height = size * 5 / 4;
+    let mut y = HEIGHT * 2 / 5 - lines.len().saturating_sub(1) * line_height / 2;
+
+    let mut svg = format!(
+        r#"<svg xmlns="http://www.w3.org/2000/svg" width="{WIDTH}" height="{HEIGHT}" viewBox="0 0 {WIDTH} {HEIGHT}">"#
+    );
+    svg += &format!(r#"<rect width="{WIDTH}" height="{HEIGHT}" fill="{background}"/>"#);
+    svg += &format!(
+        r#"<rect x="60" y="60" width="{}" height="{}" fill="none" stroke="{foreground}" stroke-width="6" stroke-opacity="0.6"/>"#,
+        WIDTH - 120,
+        HEIGHT - 120
+    );
+    svg += &format!(r#"<g font-family="{FONT}" fill="{foreground}" text-anchor="middle">"#);
+
+    for line in &lines {
+        svg += &format!(
+            r#"<text x="{center}" y="{y}" font-size="{size}" font-weight="bold">{}</text>"#,
+            escape(line)
+        );
+        y += line_height;
+    }
+
+    y += 40;
+    svg += &format!(
+        r#"<rect x="{}" y="{y}" width="400" height="6" fill="{foreground}"/>"#,
+        center - 200
+    );
+
+    if let Some(subtitle) = subtitle {
+        y += 140;
+        svg += &format!(
+            r#"<text x="{center}" y="{y}" font-size="72" font-style="italic">{}</text>"#,
+            escape(subtitle)
+        );
+    }
+
+    let mut authors = wrap(&authors.join(", "), 32);
+    authors.truncate(4);
+    let mut y = HEIGHT - 240 - authors.len().saturating_sub(1) * 96;
+    for line in &authors {
+        svg += &format!(
+            r#"<text x="{center}" y="{y}" font-size="76">{}</text>"#,
+            escape(line)
+        );
+        y += 96;
+    }
+
+    svg += "</g></svg>";
+    svg
+}
+
+/// The 32 bit FNV-1a hash, stable across builds unlike the hasher of the
+/// standard library
+fn fnv(value: &str) -> u32 {
+    value.bytes().fold(0x811c9dc5, |hash, byte| {
+        (hash ^ u32::from(byte)).wrapping_mul(0x01000193)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_set_title_and_authors_on_cover() {
+        let svg = cover_svg(
+            "The Tale of Tom & Jerry",
+            Some("Part 1 of 2"),
+            &[String::from("Author")],
+        );
+        assert!(svg.starts_with("<svg "));
+        assert!(svg.ends_with("</g></svg>"));
+        assert!(svg.contains(">The Tale of</text>"));
+        assert!(svg.contains(">Tom &amp; Jerry</text>"));
+        assert!(svg.contains(">Part 1 of 2</text>"));
+        assert!(svg.contains(">Author</text>"));
+
+        // The same title always has the same colours
+        assert_eq!(
+            svg,
+            cover_svg(
+                "The Tale of Tom & Jerry",
+                Some("Part 1 of 2"),
+                &[String::from("Author")]
+            )
+        );
+    }
+}
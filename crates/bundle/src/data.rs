@@ -1,15 +1,34 @@
 use std::{
     collections::HashMap,
-    fs,
     path::{Path, PathBuf},
+    sync::Mutex,
 };
 
+use chrono::{DateTime, Utc};
 use log::info;
+use quelle_common::TitleRules;
 use quelle_core::prelude::*;
-use quelle_persist::CoverLoc;
+use quelle_persist::{AssetStore, Cipher, CoverLoc};
+
+use crate::split::Part;
+
+/// The content type and content of a stored image or other file
+pub type Asset = (String, Vec<u8>);
+
+/// A volume with chapters to bundle
+#[derive(Debug)]
+pub struct BundleVolume<'a> {
+    /// The position of the volume in the novel, starting at 1
+    pub number: usize,
+    pub volume: &'a Volume,
+    /// The chapters of the volume to bundle, as returned by [`Bundle::chapters`]
+    pub chapters: Vec<(usize, &'a Chapter)>,
+}
 
 /// A trait that provides necessary information for bundlers
-pub trait Bundle {
+///
+/// Bundlers read chapters from several threads, so bundles need to be shareable.
+pub trait Bundle: Sync {
     /// The source meta information
     fn meta(&self) -> Option<&Meta>;
 
@@ -22,8 +41,219 @@ pub trait Bundle {
     /// The content type of the cover or thumbnail
     fn cover_content_type(&self) -> Option<&str>;
 
+    /// The content of the cover, read from [`Bundle::cover_path`] by default
+    fn cover_content(&self) -> Result<Option<Vec<u8>>, Box<dyn std::error::Error>> {
+        match self.cover_path() {
+            Some(path) if path.exists() => Ok(Some(std::fs::read(path)?)),
+            _ => Ok(None),
+        }
+    }
+
     /// Return chapter content when the url of the chapter is provided
     fn chapter_content(&self, url: &str) -> Result<Option<String>, Box<dyn std::error::Error>>;
+
+    /// Return the content type and content of a stored image or other file
+    /// when its url is provided
+    fn asset(&self, _url: &str) -> Result<Option<Asset>, Box<dyn std::error::Error>> {
+        Ok(None)
+    }
+
+    /// Notes about the novel in plain text to be included as front matter,
+    /// with paragraphs separated by blank lines
+    fn notes(&self) -> Option<&str> {
+        None
+    }
+
+    /// The license or attribution of the novel
+    fn rights(&self) -> Option<&str> {
+        self.novel().rights()
+    }
+
+    /// When the novel was last fetched from the source
+    fn fetched_at(&self) -> Option<DateTime<Utc>> {
+        None
+    }
+
+    /// How chapter titles are normalized, leaving them unchanged by default
+    fn title_rules(&self) -> Option<&TitleRules> {
+        None
+    }
+
+    /// The title of the chapter at the position in the novel, starting at 1
+    fn chapter_title(&self, chapter: &Chapter, position: usize) -> Option<String> {
+        let rules = self.title_rules().filter(|rules| !rules.is_default())?;
+        let number = chapter.label.clone().or_else(|| chapter.display_number());
+        Some(rules.apply(&chapter.title, number.as_deref(), position))
+    }
+
+    /// The part of the novel being bundled when the output is split
+    fn part(&self) -> Option<&Part> {
+        None
+    }
+
+    /// The chapters to bundle with their position in the novel, starting at 1
+    fn chapters(&self) -> Vec<(usize, &Chapter)> {
+        let chapters = self
+            .novel()
+            .volumes
+            .iter()
+            .flat_map(|volume| &volume.chapters)
+            .enumerate()
+            .map(|(index, chapter)| (index + 1, chapter));
+
+        match self.part() {
+            Some(part) => chapters
+                .skip(part.chapters().start)
+                .take(part.chapters().len())
+                .collect(),
+            None => chapters.collect(),
+        }
+    }
+
+    /// The volumes with chapters to bundle
+    fn volumes(&self) -> Vec<BundleVolume<'_>> {
+        let chapters = self.chapters();
+        let mut volumes = vec![];
+        let mut start = 0;
+
+        for (index, volume) in self.novel().volumes.iter().enumerate() {
+            let range = start + 1..=start + volume.chapters.len();
+            start += volume.chapters.len();
+
+            let selected = chapters
+                .iter()
+                .filter(|(position, _)| range.contains(position))
+                .copied()
+                .collect::<Vec<_>>();
+
+            if !selected.is_empty() {
+                volumes.push(BundleVolume {
+                    number: index + 1,
+                    volume,
+                    chapters: selected,
+                });
+            }
+        }
+
+        volumes
+    }
+}
+
+/// The name of the volume, numbered when the source did not name it
+pub fn volume_title(volume: &Volume, number: usize) -> String {
+    match volume.name.trim() {
+        "" | "_default" => format!("Volume {number}"),
+        name => name.to_string(),
+    }
+}
+
+/// The content type and content of the cover, read from its file or the stored copy
+pub(crate) fn cover_image<B: Bundle>(
+    bundle: &B,
+) -> Result<Option<Asset>, Box<dyn std::error::Error>> {
+    if let Some(content_type) = bundle.cover_content_type() {
+        if let Some(content) = bundle.cover_content()? {
+            return Ok(Some((content_type.to_string(), content)));
+        }
+    }
+
+    // Use the stored copy of the cover instead of downloading it again
+    match &bundle.novel().cover {
+        Some(url) => bundle.asset(url),
+        None => Ok(None),
+    }
+}
+
+/// The file extension of an image of the content type
+pub(crate) fn image_extension(content_type: &str) -> &str {
+    match content_type.rsplit('/').next() {
+        Some("jpeg") => "jpg",
+        Some("svg+xml") => "svg",
+        Some(extension) if !extension.is_empty() => extension,
+        _ => "img",
+    }
+}
+
+/// A bundle that remembers chapter content after it is first read
+///
+/// This allows the same content to be shared when bundling multiple formats.
+pub struct CachedBundle<B> {
+    inner: B,
+    contents: Mutex<HashMap<String, Option<String>>>,
+}
+
+impl<B: Bundle> CachedBundle<B> {
+    pub fn new(inner: B) -> Self {
+        Self {
+            inner,
+            contents: Default::default(),
+        }
+    }
+
+    pub fn into_inner(self) -> B {
+        self.inner
+    }
+}
+
+impl<B: Bundle> Bundle for CachedBundle<B> {
+    fn meta(&self) -> Option<&Meta> {
+        self.inner.meta()
+    }
+
+    fn novel(&self) -> &Novel {
+        self.inner.novel()
+    }
+
+    fn cover_path(&self) -> Option<&Path> {
+        self.inner.cover_path()
+    }
+
+    fn cover_content_type(&self) -> Option<&str> {
+        self.inner.cover_content_type()
+    }
+
+    fn cover_content(&self) -> Result<Option<Vec<u8>>, Box<dyn std::error::Error>> {
+        self.inner.cover_content()
+    }
+
+    fn asset(&self, url: &str) -> Result<Option<Asset>, Box<dyn std::error::Error>> {
+        self.inner.asset(url)
+    }
+
+    fn notes(&self) -> Option<&str> {
+        self.inner.notes()
+    }
+
+    fn rights(&self) -> Option<&str> {
+        self.inner.rights()
+    }
+
+    fn fetched_at(&self) -> Option<DateTime<Utc>> {
+        self.inner.fetched_at()
+    }
+
+    fn title_rules(&self) -> Option<&TitleRules> {
+        self.inner.title_rules()
+    }
+
+    fn part(&self) -> Option<&Part> {
+        self.inner.part()
+    }
+
+    fn chapter_content(&self, url: &str) -> Result<Option<String>, Box<dyn std::error::Error>> {
+        if let Some(content) = self.contents.lock().unwrap().get(url) {
+            return Ok(content.clone());
+        }
+
+        // Read without holding the lock so that other chapters are read meanwhile
+        let content = self.inner.chapter_content(url)?;
+        self.contents
+            .lock()
+            .unwrap()
+            .insert(url.to_string(), content.clone());
+
+        Ok(content)
+    }
 }
 
 ///
@@ -34,6 +264,15 @@ pub struct PersistBundle {
     pub cover: Option<CoverLoc>,
     pub base_path: PathBuf,
     pub chapter_content: HashMap<String, PathBuf>,
+    pub notes: Option<String>,
+    /// License or attribution overriding the one reported by the source
+    pub rights: Option<String>,
+    pub title_rules: TitleRules,
+    /// Decrypts the chapters and cover of an encrypted library
+    pub cipher: Option<Cipher>,
+    /// Covers and images stored for the novel
+    pub assets: Option<AssetStore>,
+    pub fetched_at: Option<DateTime<Utc>>,
 }
 
 #[cfg(feature = "persist")]
@@ -54,11 +293,50 @@ impl Bundle for PersistBundle {
         self.cover.as_ref().map(|cover| cover.content_type.as_str())
     }
 
+    fn cover_content(&self) -> Result<Option<Vec<u8>>, Box<dyn std::error::Error>> {
+        match &self.cover {
+            Some(cover) if cover.path.exists() => {
+                let content = quelle_persist::read_file(&cover.path, self.cipher.as_ref())?;
+                Ok(Some(content))
+            }
+            _ => Ok(None),
+        }
+    }
+
     fn chapter_content(&self, url: &str) -> Result<Option<String>, Box<dyn std::error::Error>> {
-        let Some(file_path) = self.chapter_content.get(url) else { return Ok(None) };
+        let Some(file_path) = self.chapter_content.get(url) else {
+            return Ok(None);
+        };
         let file_path = self.base_path.join(file_path);
-        let content = fs::read_to_string(&file_path)?;
+        let content = quelle_persist::read_content(&file_path, self.cipher.as_ref())?;
         info!("Read chapter content from '{}'.", file_path.display());
         Ok(Some(content))
     }
+
+    fn asset(&self, url: &str) -> Result<Option<Asset>, Box<dyn std::error::Error>> {
+        let Some(assets) = &self.assets else {
+            return Ok(None);
+        };
+
+        let asset = assets
+            .get_asset(url)?
+            .map(|(asset, content)| (asset.content_type.clone(), content));
+        Ok(asset)
+    }
+
+    fn notes(&self) -> Option<&str> {
+        self.notes.as_deref()
+    }
+
+    fn rights(&self) -> Option<&str> {
+        self.rights.as_deref().or_else(|| self.novel.rights())
+    }
+
+    fn fetched_at(&self) -> Option<DateTime<Utc>> {
+        self.fetched_at
+    }
+
+    fn title_rules(&self) -> Option<&TitleRules> {
+        Some(&self.title_rules)
+    }
 }
@@ -0,0 +1,210 @@
+use std::collections::HashMap;
+
+use log::{info, warn};
+
+use crate::{
+    data::{image_extension, Bundle},
+    images::{attribute, is_image, tag_end},
+    magick::convert,
+    text::decode_entities,
+};
+
+#[cfg(any(feature = "epub", test))]
+use crate::text::escape;
+
+/// How the images of chapters are included in bundles
+#[derive(Clone, Debug)]
+pub struct ImageOptions {
+    /// Embed the stored images of chapters instead of linking to the source,
+    /// leaving out the ones that are not stored
+    pub include_images: bool,
+    /// Scale images wider than this down to it
+    pub max_width: Option<u32>,
+    /// The ImageMagick program used to resize images and re-encode the ones
+    /// e-readers do not support
+    pub convert: String,
+}
+
+impl Default for ImageOptions {
+    fn default() -> Self {
+        Self {
+            include_images: false,
+            max_width: None,
+            convert: String::from("magick"),
+        }
+    }
+}
+
+/// An image embedded into a bundle
+pub(crate) struct EmbeddedImage {
+    /// The path of the image in the bundle
+    pub name: String,
+    // Only epub lists the content type of its files
+    #[cfg_attr(not(feature = "epub"), allow(dead_code))]
+    pub content_type: String,
+    pub content: Vec<u8>,
+}
+
+/// The images embedded into a bundle, each stored image only once
+pub(crate) struct EmbeddedImages<'a> {
+    options: &'a ImageOptions,
+    names: HashMap<String, Option<String>>,
+    pub images: Vec<EmbeddedImage>,
+}
+
+impl<'a> EmbeddedImages<'a> {
+    pub fn new(options: &'a ImageOptions) -> Self {
+        Self {
+            options,
+            names: HashMap::new(),
+            images: vec![],
+        }
+    }
+
+    pub fn include_images(&self) -> bool {
+        self.options.include_images
+    }
+
+    /// Embed the stored image of the source, returning its path in the bundle
+    pub fn embed<B: Bundle>(
+        &mut self,
+        bundle: &B,
+        source: &str,
+    ) -> Result<Option<String>, Box<dyn std::error::Error>> {
+        if let Some(name) = self.names.get(source) {
+            return Ok(name.clone());
+        }
+
+        let name = match bundle.asset(source)? {
+            Some((content_type, content)) => {
+                let (content_type, content) = prepare_image(self.options, content_type, content);
+                let name = format!(
+                    "images/{}.{}",
+                    self.images.len() + 1,
+                    image_extension(&content_type)
+                );
+                self.images.push(EmbeddedImage {
+                    name: name.clone(),
+                    content_type,
+                    content,
+                });
+                Some(name)
+            }
+            None => {
+                warn!("Leaving out the image '{source}' as it is not stored.");
+                None
+            }
+        };
+
+        self.names.insert(source.to_string(), name.clone());
+        Ok(name)
+    }
+}
+
+/// Replace every image of the html content with the result of the function,
+/// given its source and alternative text, or remove it when there is none
+pub(crate) fn replace_images<E>(
+    html: &str,
+    mut replace: impl FnMut(&str, &str) -> Result<Option<String>, E>,
+) -> Result<String, E> {
+    let mut replaced = String::with_capacity(html.len());
+    let mut rest = html;
+
+    while let Some(start) = rest.find('<') {
+        replaced.push_str(&rest[..start]);
+        let inner = &rest[start + 1..];
+        let end = tag_end(inner);
+        let tag = &inner[..end];
+        rest = inner.get(end + 1..).unwrap_or_default();
+
+        let source = is_image(tag).then(|| attribute(tag, "src")).flatten();
+        match source {
+            Some(source) => {
+                let alt = attribute(tag, "alt").map(|alt| decode_entities(&alt));
+                let replacement = replace(
+                    &decode_entities(&source),
+                    alt.as_deref().unwrap_or_default(),
+                )?;
+                replaced.push_str(replacement.as_deref().unwrap_or_default());
+            }
+            None => {
+                replaced.push('<');
+                replaced.push_str(tag);
+                if end < inner.len() {
+                    replaced.push('>');
+                }
+            }
+        }
+    }
+
+    replaced.push_str(rest);
+    Ok(replaced)
+}
+
+/// An xhtml image element of the image embedded at the path
+#[cfg(any(feature = "epub", test))]
+pub(crate) fn image_element(path: &str, alt: &str) -> String {
+    format!("<img src=\"{}\" alt=\"{}\"/>", escape(path), escape(alt))
+}
+
+/// Resize the image and re-encode it when e-readers do not support its format,
+/// keeping it as is when ImageMagick fails
+fn prepare_image(
+    options: &ImageOptions,
+    content_type: String,
+    content: Vec<u8>,
+) -> (String, Vec<u8>) {
+    // Vector images need no resizing
+    if content_type == "image/svg+xml" {
+        return (content_type, content);
+    }
+
+    let supported = matches!(
+        content_type.as_str(),
+        "image/jpeg" | "image/png" | "image/gif"
+    );
+    if supported && options.max_width.is_none() {
+        return (content_type, content);
+    }
+
+    // Transparency and animations are kept, everything else becomes a jpeg
+    let (format, target) = match content_type.as_str() {
+        "image/png" => ("png", "image/png"),
+        "image/gif" => ("gif", "image/gif"),
+        _ => ("jpeg", "image/jpeg"),
+    };
+    let mut args = vec![String::from("-"), String::from("-auto-orient")];
+    if let Some(width) = options.max_width {
+        args.push(String::from("-resize"));
+        args.push(format!("{width}x>"));
+    }
+    args.push(format!("{format}:-"));
+
+    match convert(&options.convert, &args, &content) {
+        Ok(converted) => {
+            info!("Re-encoded a {content_type} image as {target}");
+            (target.to_string(), converted)
+        }
+        Err(e) => {
+            warn!("Keeping the {content_type} image as is: {e}");
+            (content_type, content)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_replace_images() {
+        let html = r#"<p>a<img src="1.png" alt="A &amp; B"></p><p><img src=2.png>b</p>"#;
+        let replaced = replace_images(html, |source, alt| {
+            Ok::<_, ()>((source == "1.png").then(|| image_element("images/1.png", alt)))
+        });
+        assert_eq!(
+            replaced.unwrap(),
+            r#"<p>a<img src="images/1.png" alt="A &amp; B"/></p><p>b</p>"#
+        );
+    }
+}
@@ -1,32 +1,115 @@
-use std::{collections::HashMap, fs::File, io::BufWriter, path::Path};
+use std::{collections::BTreeSet, fs::File, io::BufWriter, path::Path};
 
-use epub_builder::{EpubBuilder, EpubContent, ReferenceType, ZipLibrary};
+use epub_builder::{EpubBuilder, EpubContent, EpubVersion, ReferenceType, ZipLibrary};
 use indoc::formatdoc;
 use itertools::Itertools;
 use log::{info, warn};
 use quelle_core::prelude::*;
 
-use crate::data::Bundle;
+pub use crate::data::volume_title;
+pub use crate::xhtml::{page, PageKind};
+use crate::{
+    cover::{generated_cover, rasterize},
+    data::{image_extension, Bundle, BundleVolume},
+    embed::{image_element, replace_images, EmbeddedImages, ImageOptions},
+    fonts::{font_faces, subset_font, FontOptions},
+    footnote::xhtml_footnotes,
+    front::{bundle_title, colophon, FrontMatter},
+    kobo::kobo_content,
+    metadata::MetadataMapping,
+    render::render_in_order,
+    split::Part,
+    text::escape,
+    theme::Theme,
+};
+
+/// How the novel is laid out as an epub
+#[derive(Clone, Debug, Default)]
+pub struct EpubOptions {
+    pub theme: Theme,
+}
 
 pub fn bundle_epub<B: Bundle>(
-    bundle: B,
+    bundle: &B,
+    out: &mut BufWriter<File>,
+    options: &EpubOptions,
+    images: &ImageOptions,
+    fonts: &FontOptions,
+    metadata: &MetadataMapping,
+    front_matter: &FrontMatter,
+) -> Result<(), Box<dyn std::error::Error>> {
+    write_epub(
+        bundle,
+        out,
+        options,
+        images,
+        fonts,
+        metadata,
+        front_matter,
+        false,
+    )
+}
+
+/// Bundle the novel as an epub with the sentence spans that Kobo readers
+/// use for page statistics and reading progress
+pub fn bundle_kepub<B: Bundle>(
+    bundle: &B,
     out: &mut BufWriter<File>,
+    options: &EpubOptions,
+    images: &ImageOptions,
+    fonts: &FontOptions,
+    metadata: &MetadataMapping,
+    front_matter: &FrontMatter,
+) -> Result<(), Box<dyn std::error::Error>> {
+    write_epub(
+        bundle,
+        out,
+        options,
+        images,
+        fonts,
+        metadata,
+        front_matter,
+        true,
+    )
+}
+
+#[allow(clippy::too_many_arguments)]
+fn write_epub<B: Bundle>(
+    bundle: &B,
+    out: &mut BufWriter<File>,
+    options: &EpubOptions,
+    image_options: &ImageOptions,
+    font_options: &FontOptions,
+    metadata: &MetadataMapping,
+    front_matter: &FrontMatter,
+    kobo: bool,
 ) -> Result<(), Box<dyn std::error::Error>> {
-    let meta = bundle.meta();
     let novel = bundle.novel();
 
     let mut builder = EpubBuilder::new(ZipLibrary::new()?)?;
+    // Epub 3 for the navigation document with its landmarks
+    builder.epub_version(EpubVersion::V30);
+
+    // Pages are in the language of the novel, which the reading system
+    // otherwise has to guess for text to speech and hyphenation
+    let lang = novel.langs.first().map(String::as_str);
+
+    // The fonts are set after the theme, and are only written once the
+    // characters of the pages they are subset to are known
+    let fonts = font_options.read()?;
+    let mut chars = BTreeSet::new();
+    let stylesheet = options.theme.stylesheet()? + &font_faces(&fonts);
+    builder.stylesheet(stylesheet.as_bytes())?;
+    for resource in options.theme.resources()? {
+        let file = File::open(&resource.path)?;
+        builder.add_resource(&resource.name, file, resource.content_type)?;
+    }
 
-    let preface_content = preface_content(meta, novel);
-    let preface = EpubContent::new("preface.xhtml", preface_content.as_bytes())
-        .title("Preface")
-        .reftype(ReferenceType::Preface);
+    info!("Written '{}' theme", options.theme);
 
-    if let (Some(path), Some(content_type)) = (bundle.cover_path(), bundle.cover_content_type()) {
-        set_cover_image(&mut builder, path, content_type)?;
-    }
+    set_cover_image(&mut builder, bundle, image_options)?;
 
-    builder.set_title(&novel.title);
+    builder.set_title(bundle_title(bundle));
     for author in &novel.authors {
         builder.add_author(author);
     }
@@ -37,52 +120,250 @@ pub fn bundle_epub<B: Bundle>(
 
     info!("Written title, authors, and description");
 
-    for metadata in &novel.metadata {
-        if ["title", "author", "subject", "language"].contains(&metadata.name.as_str()) {
-            builder.metadata(&metadata.name, &metadata.value)?;
-        }
+    // Only the elements the builder knows are written, the title, authors and
+    // language being set from the novel
+    for subject in metadata.dublin_core(novel, "subject") {
+        builder.metadata("subject", subject)?;
+    }
+    for description in metadata.dublin_core(novel, "description") {
+        builder.add_description(description);
+    }
+
+    if let Some(rights) = bundle.rights() {
+        builder.metadata("license", rights)?;
     }
 
     builder.set_generator("quelle");
-    builder.set_lang(novel.langs.iter().join(","));
+    if let Some(lang) = lang {
+        builder.set_lang(lang);
+    }
 
     info!("Written metadata");
 
-    builder.add_content(preface)?;
+    if front_matter.title_page {
+        let content = page(
+            "title.xhtml",
+            &novel.title,
+            lang,
+            PageKind::TitlePage,
+            &title_page_content(bundle),
+        );
+        chars.extend(content.chars());
+        let content = EpubContent::new("title.xhtml", content.as_bytes())
+            .title("Title Page")
+            .reftype(ReferenceType::TitlePage);
+        builder.add_content(content)?;
+
+        info!("Written title page");
+    }
 
-    info!("Written novel preface");
+    if front_matter.synopsis {
+        let content = page(
+            "synopsis.xhtml",
+            "Synopsis",
+            lang,
+            PageKind::Synopsis,
+            &synopsis_content(novel),
+        );
+        chars.extend(content.chars());
+        let content = EpubContent::new("synopsis.xhtml", content.as_bytes())
+            .title("Synopsis")
+            .reftype(ReferenceType::Preface);
+        builder.add_content(content)?;
+
+        info!("Written synopsis");
+    }
+
+    if front_matter.colophon {
+        let content = page(
+            "colophon.xhtml",
+            "Colophon",
+            lang,
+            PageKind::Colophon,
+            &colophon_content(&colophon(bundle)),
+        );
+        chars.extend(content.chars());
+        let content = EpubContent::new("colophon.xhtml", content.as_bytes())
+            .title("Colophon")
+            .reftype(ReferenceType::Colophon);
+        builder.add_content(content)?;
+
+        info!("Written colophon");
+    }
+
+    if let Some(part) = bundle.part() {
+        let parts_content = page(
+            "parts.xhtml",
+            "Parts",
+            lang,
+            PageKind::Parts,
+            &parts_content(part),
+        );
+        chars.extend(parts_content.chars());
+        let parts = EpubContent::new("parts.xhtml", parts_content.as_bytes()).title("Parts");
+        builder.add_content(parts)?;
+
+        info!("Written list of parts");
+    }
 
-    for volume in &novel.volumes {
-        for chapter in &volume.chapters {
+    if let Some(rights) = bundle.rights() {
+        let rights_content = page(
+            "rights.xhtml",
+            "Rights",
+            lang,
+            PageKind::Copyright,
+            &rights_content(novel, rights),
+        );
+        chars.extend(rights_content.chars());
+        let rights = EpubContent::new("rights.xhtml", rights_content.as_bytes())
+            .title("Rights")
+            .reftype(ReferenceType::Copyright);
+        builder.add_content(rights)?;
+
+        info!("Written rights page");
+    }
+
+    if let Some(notes) = bundle.notes() {
+        let notes_content = page(
+            "notes.xhtml",
+            "Notes",
+            lang,
+            PageKind::Notes,
+            &notes_content(notes),
+        );
+        chars.extend(notes_content.chars());
+        let notes = EpubContent::new("notes.xhtml", notes_content.as_bytes())
+            .title("Notes")
+            .reftype(ReferenceType::Notes);
+        builder.add_content(notes)?;
+
+        info!("Written novel notes");
+    }
+
+    // Chapters are nested under a title page of their volume when the novel has several
+    let structured = novel.volumes.len() > 1;
+    let mut images = EmbeddedImages::new(image_options);
+    // The first page of the body is the start of reading landmark
+    let mut body_started = false;
+
+    for BundleVolume {
+        number,
+        volume,
+        chapters,
+    } in bundle.volumes()
+    {
+        if structured {
+            let file_name = format!("volumes/{number}.xhtml");
+            let title = volume_title(volume, number);
+            let content = page(
+                &file_name,
+                &title,
+                lang,
+                PageKind::Volume,
+                &volume_content(&title),
+            );
+            chars.extend(content.chars());
+            let mut content = EpubContent::new(&file_name, content.as_bytes())
+                .title(title)
+                .level(1);
+            if !body_started {
+                content = content.reftype(ReferenceType::Text);
+                body_started = true;
+            }
+            builder.add_content(content)?;
+
+            info!("Written volume '{}' as '{}'.", volume.name, file_name);
+        }
+
+        // Chapters are read and marked up on several threads, then added in order
+        let render = |&(position, chapter): &(usize, &Chapter)| {
             let file_name = format!("chapters/{}.xhtml", &chapter.index);
 
-            let content = if let Some(content) = bundle.chapter_content(&chapter.url)? {
-                prepare_content(&chapter, content)
+            // Normalized titles are used both as the heading and in the table of contents
+            let (title, toc_title) = match bundle.chapter_title(chapter, position) {
+                Some(title) => (title.clone(), title),
+                None => (chapter.title.clone(), chapter.toc_title()),
+            };
+
+            let content = bundle
+                .chapter_content(&chapter.url)
+                .map_err(|e| e.to_string())?;
+            let mut content = if let Some(content) = content {
+                prepare_content(&title, xhtml_footnotes(&content))
             } else {
                 warn!("Using placeholder content for '{}'.", file_name);
-                empty_content(&chapter)
+                empty_content(&title)
             };
-
-            let content = EpubContent::new(&file_name, content.as_bytes()).title(&chapter.title);
+            if kobo {
+                content = kobo_content(&content);
+            }
+
+            Ok((file_name, title, toc_title, content))
+        };
+
+        render_in_order(&chapters, render, |(_, chapter), rendered| {
+            let (file_name, title, toc_title, mut content) = rendered;
+            if image_options.include_images {
+                content = replace_images(&content, |source, alt| {
+                    let name = images.embed(bundle, source)?;
+                    Ok::<_, Box<dyn std::error::Error>>(
+                        name.map(|name| image_element(&format!("../{name}"), alt)),
+                    )
+                })?;
+            }
+            let content = page(&file_name, &title, lang, PageKind::Chapter, &content);
+            chars.extend(content.chars());
+
+            let level = if structured { 2 } else { 1 };
+            let mut content = EpubContent::new(&file_name, content.as_bytes())
+                .title(toc_title)
+                .level(level);
+            if !body_started {
+                content = content.reftype(ReferenceType::Text);
+                body_started = true;
+            }
             builder.add_content(content)?;
 
             info!("Written '{}' as '{}'.", chapter.title, file_name);
-        }
+            Ok(())
+        })?;
+    }
+
+    for image in &images.images {
+        builder.add_resource(&image.name, image.content.as_slice(), &image.content_type)?;
+    }
+    if !images.images.is_empty() {
+        info!("Written {} images", images.images.len());
+    }
+
+    for font in &fonts {
+        let content = if font_options.subset {
+            subset_font(font_options, font, &chars)?
+        } else {
+            std::fs::read(&font.path)?
+        };
+        builder.add_resource(&font.name, content.as_slice(), font.content_type)?;
+    }
+    if !fonts.is_empty() {
+        info!("Written {} fonts", fonts.len());
     }
 
     builder.generate(out)?;
 
-    info!("Epub writing complete.");
+    if kobo {
+        info!("Kepub writing complete.");
+    } else {
+        info!("Epub writing complete.");
+    }
     Ok(())
 }
 
-pub fn prepare_content(chapter: &Chapter, content: String) -> String {
-    let title = &chapter.title;
-    format!("<h1>{title}</h1>{content}")
+pub fn prepare_content(title: &str, content: String) -> String {
+    format!("<h1>{}</h1>{content}", escape(title))
 }
 
-pub fn empty_content(chapter: &Chapter) -> String {
-    let title = &chapter.title;
+pub fn empty_content(title: &str) -> String {
+    let title = escape(title);
 
     formatdoc! {r#"
         <h1>{title}</h1>
@@ -90,86 +371,175 @@ pub fn empty_content(chapter: &Chapter) -> String {
     "#}
 }
 
-fn set_cover_image(
+pub fn volume_content(title: &str) -> String {
+    let title = escape(title);
+
+    formatdoc! {r#"
+        <h1 class="volume">{title}</h1>
+    "#}
+}
+
+fn set_cover_image<B: Bundle>(
     builder: &mut EpubBuilder<ZipLibrary>,
-    cover_path: &Path,
-    content_type: &str,
+    bundle: &B,
+    image_options: &ImageOptions,
 ) -> Result<(), Box<dyn std::error::Error>> {
-    if cover_path.exists() {
-        let file = File::open(cover_path)?;
-        let name = cover_path
-            .file_name()
-            .map(|name| name.to_string_lossy().to_string())
-            .unwrap_or_else(|| String::from("cover.unknwon"));
-
-        builder.add_cover_image(name, file, content_type)?;
-        info!("Written cover file '{}'", cover_path.display());
-    } else {
-        warn!("The cover file could not be found.");
+    if let Some(content_type) = bundle.cover_content_type() {
+        if let Some(content) = bundle.cover_content()? {
+            let name = bundle
+                .cover_path()
+                .and_then(Path::file_name)
+                .map(|name| name.to_string_lossy().to_string())
+                .unwrap_or_else(|| String::from("cover.unknwon"));
+
+            builder.add_cover_image(&name, content.as_slice(), content_type)?;
+            info!("Written cover file '{name}'");
+            return Ok(());
+        }
     }
 
-    Ok(())
-}
+    // Use the stored copy of the cover instead of downloading it again
+    let stored = match &bundle.novel().cover {
+        Some(url) => bundle.asset(url)?,
+        None => None,
+    };
 
-fn capitalize(s: &str) -> String {
-    let mut c = s.chars();
-    match c.next() {
-        None => String::new(),
-        Some(f) => f.to_uppercase().collect::<String>() + c.as_str(),
+    match stored {
+        Some((content_type, content)) => {
+            builder.add_cover_image("cover", content.as_slice(), &content_type)?;
+            info!("Written stored cover");
+        }
+        None => {
+            // Reader libraries show books without a cover as blank
+            let (content_type, content) = generated_cover(bundle);
+            let (content_type, content) = rasterize(&image_options.convert, content_type, content);
+            let name = format!("cover.{}", image_extension(&content_type));
+            builder.add_cover_image(name, content.as_slice(), &content_type)?;
+            info!("Written generated cover");
+        }
     }
-}
 
-pub fn preface_content(_meta: Option<&Meta>, novel: &Novel) -> String {
-    let title = &novel.title;
-    let url = &novel.url;
+    Ok(())
+}
 
+/// The title page with the title, the part of a split novel and the authors
+pub fn title_page_content<B: Bundle>(bundle: &B) -> String {
+    let novel = bundle.novel();
+    let title = escape(&novel.title);
+    let part = bundle
+        .part()
+        .map(|part| {
+            format!(
+                "<p class=\"part\">Part {} of {}</p>",
+                part.number(),
+                part.total()
+            )
+        })
+        .unwrap_or_default();
     let authors = if novel.authors.is_empty() {
-        String::from("<p>Unknown author</p>")
+        String::from("Unknown author")
     } else {
-        format!("<p>{}</p>", novel.authors.join(", "))
+        escape(&novel.authors.join(", "))
     };
 
+    formatdoc! {r#"
+        <h1 class="title">{title}</h1>
+        {part}
+        <p class="authors">{authors}</p>
+    "#}
+}
+
+pub fn synopsis_content(novel: &Novel) -> String {
     let description = if novel.description.is_empty() {
         String::from("<p>No description provided</p>")
     } else {
-        format!("<p>{}</p>", novel.description.join("</p><p>"))
+        let paragraphs = novel.description.iter().map(|paragraph| escape(paragraph));
+        format!("<p>{}</p>", paragraphs.join("</p><p>"))
     };
 
-    let metadata = {
-        let mut metadata_by_tag = HashMap::<&str, Vec<&Metadata>>::new();
-        for metadata in &novel.metadata {
-            metadata_by_tag
-                .entry(&metadata.name)
-                .or_insert(vec![])
-                .push(metadata);
-        }
+    formatdoc! {r#"
+        <h1>Synopsis</h1>
+        {description}
+    "#}
+}
 
-        let metadata = metadata_by_tag
-            .into_iter()
-            .map(|(name, values)| {
-                format!(
-                    "<div><h2>{}</h2><p>{}</p></div>",
-                    capitalize(&name),
-                    values.into_iter().map(|v| &v.value).join(", ")
-                )
-            })
-            .join("");
-
-        metadata
-    };
+/// The facts of the colophon as a definition list, linking to the source
+pub fn colophon_content(entries: &[(String, String)]) -> String {
+    let entries = entries
+        .iter()
+        .map(|(label, value)| {
+            let value = if label == "Source" {
+                format!("<a href=\"{0}\">{0}</a>", escape(value))
+            } else {
+                escape(value)
+            };
+            format!("<dt>{}</dt><dd>{value}</dd>", escape(label))
+        })
+        .join("");
+
+    formatdoc! {r#"
+        <h1>Colophon</h1>
+        <dl>{entries}</dl>
+    "#}
+}
+
+pub fn rights_content(novel: &Novel, rights: &str) -> String {
+    let title = escape(&novel.title);
+    let url = escape(&novel.url);
+    let rights = escape(rights);
+
+    formatdoc! {r#"
+        <h1>Rights</h1>
+        <p>{rights}</p>
+        <p>Original work: <a href="{url}">{title}</a></p>
+    "#}
+}
+
+/// List every part of the novel with its chapters and file, marking the current one
+pub fn parts_content(part: &Part) -> String {
+    let items = part
+        .spans
+        .iter()
+        .enumerate()
+        .map(|(index, span)| {
+            let mut item = format!(
+                "Part {}: chapters {} to {}",
+                index + 1,
+                span.chapters.start + 1,
+                span.chapters.end
+            );
+            if let Some(file_name) = &span.file_name {
+                item.push_str(&format!(" ({})", escape(file_name)));
+            }
+
+            if index == part.index {
+                format!("<li><strong>{item}</strong></li>")
+            } else {
+                format!("<li>{item}</li>")
+            }
+        })
+        .join("");
+
+    formatdoc! {r#"
+        <h1>Parts</h1>
+        <ul>{items}</ul>
+    "#}
+}
+
+/// Render plain text notes as paragraphs, keeping line breaks within a paragraph
+pub fn notes_content(notes: &str) -> String {
+    let paragraphs = notes
+        .split("\n\n")
+        .map(str::trim)
+        .filter(|paragraph| !paragraph.is_empty())
+        .map(|paragraph| {
+            let lines = paragraph.lines().map(escape).join("<br/>");
+            format!("<p>{lines}</p>")
+        })
+        .join("");
 
     formatdoc! {r#"
-        <h1>
-            <a href="{url}">{title}</a>
-        </h1>
-        <div>
-            <h2>Authors</h2>
-            {authors}
-        </div>
-        <div>
-            <h2>Description</h2>
-            {description}
-        </div>
-        {metadata}
+        <h1>Notes</h1>
+        {paragraphs}
     "#}
 }
@@ -0,0 +1,293 @@
+use std::io::Write;
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use chrono::Utc;
+use itertools::Itertools;
+use log::{info, warn};
+
+use crate::{
+    cover::{cover_or_generated, rasterize, SVG},
+    data::{volume_title, Bundle, BundleVolume},
+    metadata::MetadataMapping,
+    text::{escape, strip_title_heading, text_paragraphs},
+};
+
+/// The id of the cover image among the binaries of the book
+const COVER_ID: &str = "cover";
+
+/// The ImageMagick program generated covers are converted with
+const MAGICK: &str = "magick";
+
+/// FB2 genres of common tags, the genres of FB2 being a fixed list
+const GENRES: [(&str, &str); 12] = [
+    ("fantasy", "sf_fantasy"),
+    ("science fiction", "sf"),
+    ("sci-fi", "sf"),
+    ("horror", "sf_horror"),
+    ("romance", "love"),
+    ("mystery", "detective"),
+    ("thriller", "thriller"),
+    ("adventure", "adventure"),
+    ("action", "sf_action"),
+    ("comedy", "humor"),
+    ("historical", "prose_history"),
+    ("drama", "dramaturgy"),
+];
+
+/// Write the novel as a FictionBook 2 document, with the tags of the novel as
+/// keywords and genres, and the cover as an embedded image
+pub fn bundle_fb2<B: Bundle, W: Write>(
+    bundle: &B,
+    out: &mut W,
+    metadata: &MetadataMapping,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let novel = bundle.novel();
+    // FB2 readers only show raster covers
+    let (content_type, content) = cover_or_generated(bundle)?;
+    let cover = match rasterize(MAGICK, content_type, content) {
+        (content_type, _) if content_type == SVG => {
+            warn!("Leaving out the cover, which could not be converted to png.");
+            None
+        }
+        cover => Some(cover),
+    };
+
+    writeln!(out, r#"<?xml version="1.0" encoding="UTF-8"?>"#)?;
+    writeln!(
+        out,
+        r#"<FictionBook xmlns="http://www.gribuser.ru/xml/fictionbook/2.0" xmlns:l="http://www.w3.org/1999/xlink">"#
+    )?;
+
+    write_description(bundle, out, metadata, cover.is_some())?;
+    info!("Written title, authors, and description");
+
+    writeln!(out, "<body>")?;
+    writeln!(out, "<title><p>{}</p></title>", escape(&title(bundle)))?;
+
+    if let Some(rights) = bundle.rights() {
+        write_section(out, "Rights", &[rights.to_string()])?;
+    }
+    if let Some(notes) = bundle.notes() {
+        let paragraphs = notes
+            .split("\n\n")
+            .map(str::trim)
+            .filter(|paragraph| !paragraph.is_empty())
+            .map(ToString::to_string)
+            .collect::<Vec<_>>();
+        write_section(out, "Notes", &paragraphs)?;
+    }
+
+    // Chapters are nested in a section of their volume when the novel has several
+    let structured = novel.volumes.len() > 1;
+    for BundleVolume {
+        number,
+        volume,
+        chapters,
+    } in bundle.volumes()
+    {
+        if structured {
+            let title = volume_title(volume, number);
+            writeln!(out, "<section><title><p>{}</p></title>", escape(&title))?;
+        }
+
+        for (position, chapter) in chapters {
+            let title = bundle
+                .chapter_title(chapter, position)
+                .unwrap_or_else(|| chapter.title.clone());
+
+            let paragraphs = match bundle.chapter_content(&chapter.url)? {
+                Some(content) => {
+                    let mut paragraphs = text_paragraphs(&content);
+                    strip_title_heading(&mut paragraphs, &[&title, &chapter.title]);
+                    paragraphs
+                }
+                None => {
+                    warn!("Using placeholder content for '{}'.", chapter.title);
+                    vec![String::from("No downloaded content")]
+                }
+            };
+            write_section(out, &title, &paragraphs)?;
+
+            info!("Written '{}'.", chapter.title);
+        }
+
+        if structured {
+            writeln!(out, "</section>")?;
+        }
+    }
+    writeln!(out, "</body>")?;
+
+    if let Some((content_type, content)) = cover {
+        writeln!(
+            out,
+            r#"<binary id="{COVER_ID}" content-type="{}">{}</binary>"#,
+            escape(&content_type),
+            STANDARD.encode(content)
+        )?;
+        info!("Written cover");
+    }
+
+    writeln!(out, "</FictionBook>")?;
+    out.flush()?;
+
+    info!("FB2 writing complete.");
+    Ok(())
+}
+
+fn title<B: Bundle>(bundle: &B) -> String {
+    let title = &bundle.novel().title;
+    match bundle.part() {
+        Some(part) => format!("{title} (Part {} of {})", part.number(), part.total()),
+        None => title.clone(),
+    }
+}
+
+fn write_description<B: Bundle, W: Write>(
+    bundle: &B,
+    out: &mut W,
+    metadata: &MetadataMapping,
+    has_cover: bool,
+) -> std::io::Result<()> {
+    let novel = bundle.novel();
+    let tags = metadata.dublin_core(novel, "subject");
+
+    writeln!(out, "<description>")?;
+    writeln!(out, "<title-info>")?;
+
+    let genres = tags
+        .iter()
+        .filter_map(|tag| {
+            let tag = tag.to_lowercase();
+            GENRES
+                .iter()
+                .find(|(name, _)| *name == tag)
+                .map(|(_, genre)| *genre)
+        })
+        .unique()
+        .collect::<Vec<_>>();
+    if genres.is_empty() {
+        writeln!(out, "<genre>prose_contemporary</genre>")?;
+    }
+    for genre in genres {
+        writeln!(out, "<genre>{genre}</genre>")?;
+    }
+
+    write_authors(out, &novel.authors)?;
+    writeln!(out, "<book-title>{}</book-title>", escape(&title(bundle)))?;
+
+    if !novel.description.is_empty() {
+        writeln!(out, "<annotation>")?;
+        for paragraph in &novel.description {
+            writeln!(out, "<p>{}</p>", escape(paragraph))?;
+        }
+        writeln!(out, "</annotation>")?;
+    }
+    if !tags.is_empty() {
+        writeln!(out, "<keywords>{}</keywords>", escape(&tags.join(", ")))?;
+    }
+    if has_cover {
+        writeln!(
+            out,
+            r##"<coverpage><image l:href="#{COVER_ID}"/></coverpage>"##
+        )?;
+    }
+    if let Some(lang) = novel.langs.first() {
+        writeln!(out, "<lang>{}</lang>", escape(lang))?;
+    }
+    // Readers group the parts of a split novel as a series
+    if let Some(part) = bundle.part() {
+        writeln!(
+            out,
+            r#"<sequence name="{}" number="{}"/>"#,
+            escape(&novel.title),
+            part.number()
+        )?;
+    }
+
+    writeln!(out, "</title-info>")?;
+    writeln!(out, "<document-info>")?;
+    write_authors(out, &novel.authors)?;
+    writeln!(out, "<program-used>quelle</program-used>")?;
+
+    let date = Utc::now().date_naive();
+    writeln!(out, r#"<date value="{date}">{date}</date>"#)?;
+    writeln!(out, "<src-url>{}</src-url>", escape(&novel.url))?;
+    writeln!(out, "<id>{}</id>", escape(&novel.url))?;
+    writeln!(out, "<version>1.0</version>")?;
+    writeln!(out, "</document-info>")?;
+    writeln!(out, "</description>")
+}
+
+/// Authors of web novels mostly go by a pen name, which FB2 calls a nickname
+fn write_authors<W: Write>(out: &mut W, authors: &[String]) -> std::io::Result<()> {
+    if authors.is_empty() {
+        return writeln!(out, "<author><nickname>Unknown</nickname></author>");
+    }
+
+    for author in authors {
+        writeln!(
+            out,
+            "<author><nickname>{}</nickname></author>",
+            escape(author)
+        )?;
+    }
+    Ok(())
+}
+
+fn write_section<W: Write>(out: &mut W, title: &str, paragraphs: &[String]) -> std::io::Result<()> {
+    writeln!(out, "<section>")?;
+    writeln!(out, "<title><p>{}</p></title>", escape(title))?;
+    if paragraphs.is_empty() {
+        writeln!(out, "<empty-line/>")?;
+    }
+    for paragraph in paragraphs {
+        writeln!(out, "<p>{}</p>", escape(paragraph))?;
+    }
+    writeln!(out, "</section>")
+}
+
+#[cfg(test)]
+mod tests {
+    use quelle_core::prelude::*;
+
+    use super::*;
+    use crate::test_support::TestBundle;
+
+    #[test]
+    fn should_write_metadata_chapters_and_cover() {
+        let bundle = TestBundle::new(Novel {
+            title: String::from("Novel <1>"),
+            cover: Some(String::from("https://example.com/cover.png")),
+            metadata: vec![
+                Metadata::new(String::from("subject"), String::from("Fantasy"), None),
+                Metadata::new(String::from("tag"), String::from("Isekai"), None),
+            ],
+            volumes: vec![Volume {
+                chapters: vec![Chapter {
+                    index: 0,
+                    title: String::from("One"),
+                    url: String::from("1"),
+                    updated_at: None,
+                    number: None,
+                    part: None,
+                    label: None,
+                }],
+                ..Default::default()
+            }],
+            ..Default::default()
+        })
+        .content(|_| Some(String::from("<h1>One</h1><p>Tom &amp; Jerry</p>")))
+        .asset(|_| Some((String::from("image/png"), b"png".to_vec())));
+
+        let mut out = vec![];
+        bundle_fb2(&bundle, &mut out, &Default::default()).unwrap();
+        let document = String::from_utf8(out).unwrap();
+
+        assert!(document.contains("<genre>sf_fantasy</genre>"));
+        assert!(document.contains("<keywords>Fantasy, Isekai</keywords>"));
+        assert!(document.contains("<book-title>Novel &lt;1&gt;</book-title>"));
+        assert!(document
+            .contains("<section>\n<title><p>One</p></title>\n<p>Tom &amp; Jerry</p>\n</section>"));
+        assert!(document.contains(r#"<binary id="cover" content-type="image/png">cG5n</binary>"#));
+    }
+}
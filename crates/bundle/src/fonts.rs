@@ -0,0 +1,292 @@
+#[cfg(feature = "pdf")]
+use std::path::Path;
+#[cfg(feature = "epub")]
+use std::{collections::BTreeSet, process::Command};
+use std::{fs, path::PathBuf};
+
+#[cfg(feature = "epub")]
+use log::info;
+use log::warn;
+
+#[cfg(feature = "epub")]
+use crate::work::work_dir;
+
+/// Fonts embedded into epub and pdf bundles, for novels in scripts that the
+/// fonts of e-readers lack the glyphs of, such as Chinese or Japanese
+#[derive(Clone, Debug)]
+pub struct FontOptions {
+    /// TrueType or OpenType fonts, in the order they are preferred
+    pub fonts: Vec<PathBuf>,
+    /// Keep only the glyphs the novel uses in the fonts of epub bundles, which
+    /// can be several megabytes otherwise. Typst always subsets the fonts of
+    /// pdf bundles.
+    pub subset: bool,
+    /// The pyftsubset program of fonttools used to subset fonts
+    pub pyftsubset: String,
+}
+
+impl Default for FontOptions {
+    fn default() -> Self {
+        Self {
+            fonts: vec![],
+            subset: true,
+            pyftsubset: String::from("pyftsubset"),
+        }
+    }
+}
+
+/// A font to embed
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct Font {
+    pub path: PathBuf,
+    /// The path of the font in the bundle
+    pub name: String,
+    /// The family the font is known by, from its name table
+    pub family: String,
+    #[cfg_attr(not(feature = "epub"), allow(dead_code))]
+    pub content_type: &'static str,
+}
+
+impl FontOptions {
+    /// Read the family names of the fonts
+    pub(crate) fn read(&self) -> Result<Vec<Font>, Box<dyn std::error::Error>> {
+        let mut fonts = vec![];
+        for path in &self.fonts {
+            let extension = path
+                .extension()
+                .map(|extension| extension.to_string_lossy().to_ascii_lowercase())
+                .unwrap_or_default();
+            let content_type = match extension.as_str() {
+                "ttf" => "font/ttf",
+                "otf" => "font/otf",
+                _ => {
+                    return Err(
+                        format!("'{}' is not a TrueType or OpenType font", path.display()).into(),
+                    )
+                }
+            };
+
+            let data =
+                fs::read(path).map_err(|e| format!("failed to read '{}': {e}", path.display()))?;
+            let stem = path
+                .file_stem()
+                .map(|stem| stem.to_string_lossy().into_owned())
+                .unwrap_or_default();
+            let family = font_family(&data).unwrap_or_else(|| {
+                warn!("'{}' has no family name, using '{stem}'", path.display());
+                stem.clone()
+            });
+
+            fonts.push(Font {
+                path: path.clone(),
+                name: format!("fonts/{stem}.{extension}"),
+                family,
+                content_type,
+            });
+        }
+        Ok(fonts)
+    }
+}
+
+/// The stylesheet rules declaring the fonts and setting the text in them,
+/// in a stylesheet at the root of the bundle
+#[cfg(feature = "epub")]
+pub(crate) fn font_faces(fonts: &[Font]) -> String {
+    if fonts.is_empty() {
+        return String::new();
+    }
+
+    let mut css = String::new();
+    for font in fonts {
+        css += &format!(
+            "@font-face {{ font-family: \"{}\"; src: url(\"{}\"); }}\n",
+            font.family, font.name
+        );
+    }
+    let families = fonts
+        .iter()
+        .map(|font| format!("\"{}\"", font.family))
+        .collect::<Vec<_>>()
+        .join(", ");
+    css += &format!("body {{ font-family: {families}, serif; }}\n");
+    css
+}
+
+/// The font with only the glyphs of the characters and of printable ascii,
+/// or the whole font when pyftsubset fails
+#[cfg(feature = "epub")]
+pub(crate) fn subset_font(
+    options: &FontOptions,
+    font: &Font,
+    chars: &BTreeSet<char>,
+) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+    let dir = work_dir("font")?;
+    let text = dir.path().join("text.txt");
+    let output = dir.path().join("font");
+    fs::write(
+        &text,
+        (' '..='~').chain(chars.iter().copied()).collect::<String>(),
+    )?;
+
+    // Layout features are kept for the vertical forms and ligatures of the text
+    let status = Command::new(&options.pyftsubset)
+        .arg(&font.path)
+        .arg(format!("--text-file={}", text.display()))
+        .arg(format!("--output-file={}", output.display()))
+        .arg("--layout-features=*")
+        .status();
+    match status {
+        Ok(status) if status.success() => {
+            info!("Subset '{}'", font.family);
+            Ok(fs::read(output)?)
+        }
+        Ok(status) => {
+            warn!(
+                "'{}' exited with {status}, embedding the whole font",
+                options.pyftsubset
+            );
+            Ok(fs::read(&font.path)?)
+        }
+        Err(e) => {
+            warn!(
+                "failed to start '{}', embedding the whole font: {e}",
+                options.pyftsubset
+            );
+            Ok(fs::read(&font.path)?)
+        }
+    }
+}
+
+/// Copy the fonts into the directory at their path in the bundle
+#[cfg(feature = "pdf")]
+pub(crate) fn copy_fonts(fonts: &[Font], dir: &Path) -> std::io::Result<()> {
+    for font in fonts {
+        let path = dir.join(&font.name);
+        fs::create_dir_all(path.parent().unwrap_or(dir))?;
+        fs::copy(&font.path, path)?;
+    }
+    Ok(())
+}
+
+/// The family name of a TrueType or OpenType font, or of the first font of a
+/// collection, preferring the typographic family and the English name
+pub(crate) fn font_family(data: &[u8]) -> Option<String> {
+    let u16_at = |at: usize| {
+        data.get(at..at + 2)
+            .map(|b| u16::from_be_bytes([b[0], b[1]]))
+    };
+    let u32_at = |at: usize| {
+        data.get(at..at + 4)
+            .map(|b| u32::from_be_bytes([b[0], b[1], b[2], b[3]]))
+    };
+
+    let start = if data.starts_with(b"ttcf") {
+        u32_at(12)? as usize
+    } else {
+        0
+    };
+    let tables = u16_at(start + 4)? as usize;
+    let name = (0..tables)
+        .map(|i| start + 12 + i * 16)
+        .find(|&record| data.get(record..record + 4) == Some(b"name"))
+        .and_then(|record| u32_at(record + 8))? as usize;
+
+    let count = u16_at(name + 2)? as usize;
+    let strings = name + u16_at(name + 4)? as usize;
+    let mut family: Option<(u16, String)> = None;
+    for i in 0..count {
+        let record = name + 6 + i * 12;
+        let (platform, language, id) = (u16_at(record)?, u16_at(record + 4)?, u16_at(record + 6)?);
+        // The typographic family groups more styles than the legacy family
+        let rank = match id {
+            16 => 0,
+            1 => 3,
+            _ => continue,
+        } + match platform {
+            3 if language == 0x409 => 0,
+            0 | 3 => 1,
+            1 => 2,
+            _ => continue,
+        };
+        if family.as_ref().is_some_and(|(best, _)| *best <= rank) {
+            continue;
+        }
+
+        let offset = strings + u16_at(record + 10)? as usize;
+        let Some(bytes) = data.get(offset..offset + u16_at(record + 8)? as usize) else {
+            continue;
+        };
+        // Macintosh names are in Mac Roman, which is ascii for most names
+        let value = if platform == 1 {
+            bytes.iter().map(|&b| b as char).collect()
+        } else {
+            let units = bytes
+                .chunks_exact(2)
+                .map(|b| u16::from_be_bytes([b[0], b[1]]))
+                .collect::<Vec<_>>();
+            String::from_utf16_lossy(&units)
+        };
+        if !value.trim().is_empty() {
+            family = Some((rank, value.trim().to_string()));
+        }
+    }
+
+    family.map(|(_, family)| family)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A font with only a name table of the records
+    fn font(records: &[(u16, u16, u16, &[u8])]) -> Vec<u8> {
+        let mut data = vec![0, 1, 0, 0, 0, 1, 0, 0, 0, 0, 0, 0];
+        data.extend(b"name");
+        data.extend([0; 4]);
+        data.extend(28u32.to_be_bytes());
+        data.extend([0; 4]);
+
+        let strings = 6 + records.len() * 12;
+        data.extend([0, 0]);
+        data.extend((records.len() as u16).to_be_bytes());
+        data.extend((strings as u16).to_be_bytes());
+        let mut offset = 0;
+        for (platform, language, id, value) in records {
+            for field in [*platform, 1, *language, *id, value.len() as u16, offset] {
+                data.extend(field.to_be_bytes());
+            }
+            offset += value.len() as u16;
+        }
+        for (_, _, _, value) in records {
+            data.extend(*value);
+        }
+        data
+    }
+
+    fn utf16(value: &str) -> Vec<u8> {
+        value.encode_utf16().flat_map(u16::to_be_bytes).collect()
+    }
+
+    #[test]
+    fn should_read_font_family() {
+        let japanese = utf16("源ノ明朝");
+        let english = utf16("Source Han Serif");
+        let data = font(&[
+            (1, 0, 1, b"Mac Name"),
+            (3, 0x411, 1, &japanese),
+            (3, 0x409, 1, &english),
+            (3, 0x409, 2, &utf16("Regular")),
+        ]);
+        assert_eq!(font_family(&data).as_deref(), Some("Source Han Serif"));
+
+        let typographic = utf16("Noto Serif JP");
+        let data = font(&[(3, 0x409, 1, &english), (3, 0x409, 16, &typographic)]);
+        assert_eq!(font_family(&data).as_deref(), Some("Noto Serif JP"));
+
+        assert_eq!(
+            font_family(&font(&[(1, 0, 1, b"Mac Name")])).as_deref(),
+            Some("Mac Name")
+        );
+        assert_eq!(font_family(b"not a font"), None);
+    }
+}
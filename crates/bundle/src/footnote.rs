@@ -0,0 +1,501 @@
+use std::ops::Range;
+
+use crate::{
+    images::{attribute, tag_end},
+    text::{decode_entities, text_paragraphs},
+};
+
+/// Marks the place of a footnote in the chapter content, followed by its
+/// number and [`FOOTNOTE_END`], private use characters that sources do not
+/// write
+pub(crate) const FOOTNOTE_MARKER: char = '\u{E000}';
+pub(crate) const FOOTNOTE_END: char = '\u{E001}';
+
+/// Elements a footnote or author's note can be
+const BLOCK_TAGS: [&str; 8] = [
+    "p",
+    "li",
+    "div",
+    "aside",
+    "section",
+    "blockquote",
+    "dd",
+    "span",
+];
+
+/// Elements without an end tag
+const VOID_TAGS: [&str; 8] = ["br", "hr", "img", "input", "meta", "link", "wbr", "source"];
+
+/// Elements left without text once their notes are taken out
+const CONTAINER_TAGS: [&str; 6] = ["ol", "ul", "div", "section", "aside", "dl"];
+
+/// Classes sources give author's notes
+const AUTHOR_NOTE_CLASSES: [&str; 4] = ["author-note", "authors-note", "author_note", "authornote"];
+
+/// How author's and translator's notes written in the text start
+const AUTHOR_NOTE_PREFIXES: [&str; 10] = [
+    "a/n",
+    "an:",
+    "author's note",
+    "author’s note",
+    "authors note",
+    "t/n",
+    "tn:",
+    "tl note",
+    "translator's note",
+    "translator’s note",
+];
+
+/// The longest text of a link that refers to a footnote, such as `[12]`
+const MAX_LABEL: usize = 6;
+
+/// A note taken out of the chapter content
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct Footnote {
+    pub number: usize,
+    /// The content of the note as html
+    pub content: String,
+}
+
+enum Kind {
+    Text,
+    Start { name: String, void: bool },
+    End { name: String },
+    Other,
+}
+
+struct Token<'a> {
+    range: Range<usize>,
+    /// The inside of the tag
+    tag: &'a str,
+    kind: Kind,
+    /// The start tag of the element the token is in
+    parent: Option<usize>,
+    /// The end tag of a start tag
+    end: Option<usize>,
+}
+
+/// Take the footnotes and author's notes out of the chapter content
+///
+/// Footnotes are links such as `<sup><a href="#fn1">1</a></sup>` to an
+/// element later in the chapter, and author's notes are blocks with an author
+/// note class or starting with "A/N" or "T/N". Their place is marked by a
+/// [`FOOTNOTE_MARKER`], author's notes at the end of the paragraph before them.
+pub(crate) fn extract_footnotes(html: &str) -> (String, Vec<Footnote>) {
+    let tokens = tokenize(html);
+
+    // The part of the content each reference is replaced in and the note it refers to
+    let mut references: Vec<(Range<usize>, Range<usize>)> = vec![];
+    let mut author_notes: Vec<Range<usize>> = vec![];
+    let taken = |references: &[(Range<usize>, Range<usize>)],
+                 author_notes: &[Range<usize>],
+                 index: usize| {
+        references
+            .iter()
+            .any(|(replaced, note)| replaced.contains(&index) || note.contains(&index))
+            || author_notes.iter().any(|note| note.contains(&index))
+    };
+
+    for (index, token) in tokens.iter().enumerate() {
+        let Kind::Start { name, .. } = &token.kind else {
+            continue;
+        };
+        if taken(&references, &author_notes, index) {
+            continue;
+        }
+
+        if let Some(note) = author_note(&tokens, html, index) {
+            author_notes.push(note);
+            continue;
+        }
+
+        if name != "a" {
+            continue;
+        }
+        let Some(id) =
+            attribute(token.tag, "href").and_then(|href| href.strip_prefix('#').map(String::from))
+        else {
+            continue;
+        };
+        let Some(end) = token.end else {
+            continue;
+        };
+        if !is_label(&text(html, &tokens, index..end + 1)) {
+            continue;
+        }
+        let Some(note) = note_element(&tokens, &id, end) else {
+            continue;
+        };
+        if references.iter().any(|(_, taken)| *taken == note) {
+            continue;
+        }
+
+        // A superscript around the link alone is replaced with it
+        let replaced = match token.parent {
+            Some(parent)
+                if matches!(&tokens[parent].kind, Kind::Start { name, .. } if name == "sup")
+                    && tokens[parent].end == Some(end + 1)
+                    && parent + 1 == index =>
+            {
+                parent..end + 2
+            }
+            _ => index..end + 1,
+        };
+        references.push((replaced, note));
+    }
+
+    let mut content = String::with_capacity(html.len());
+    let mut footnotes = vec![];
+    // Where the markers of author's notes go, before the end of the last paragraph
+    let mut paragraph_end: Option<usize> = None;
+    let mut pending = String::new();
+    let mut index = 0;
+
+    while index < tokens.len() {
+        let token = &tokens[index];
+
+        if let Some((replaced, note)) = references
+            .iter()
+            .find(|(replaced, _)| replaced.start == index)
+        {
+            let number = footnotes.len() + 1;
+            footnotes.push(Footnote {
+                number,
+                content: note_content(html, &tokens, note.start + 1..note.end - 1),
+            });
+            content.push_str(&marker(number));
+            index = replaced.end;
+            continue;
+        }
+        if let Some((_, note)) = references.iter().find(|(_, note)| note.start == index) {
+            index = note.end;
+            continue;
+        }
+        if let Some(note) = author_notes.iter().find(|note| note.start == index) {
+            let number = footnotes.len() + 1;
+            footnotes.push(Footnote {
+                number,
+                content: note_content(html, &tokens, note.start + 1..note.end - 1),
+            });
+            match &mut paragraph_end {
+                Some(position) => {
+                    let marker = marker(number);
+                    content.insert_str(*position, &marker);
+                    *position += marker.len();
+                }
+                None => pending.push_str(&marker(number)),
+            }
+            index = note.end;
+            continue;
+        }
+
+        if matches!(&token.kind, Kind::End { name } if name == "p") {
+            content.push_str(&std::mem::take(&mut pending));
+            paragraph_end = Some(content.len());
+        }
+        content.push_str(&html[token.range.clone()]);
+        index += 1;
+    }
+    content.push_str(&pending);
+
+    if footnotes.is_empty() {
+        return (content, footnotes);
+    }
+    (remove_empty_containers(&content), footnotes)
+}
+
+/// Replace the footnote markers of the text with the result of the function,
+/// given the number of the footnote
+pub(crate) fn replace_markers(text: &str, mut replace: impl FnMut(usize) -> String) -> String {
+    let mut replaced = String::with_capacity(text.len());
+    let mut rest = text;
+
+    while let Some(start) = rest.find(FOOTNOTE_MARKER) {
+        replaced.push_str(&rest[..start]);
+        let marked = &rest[start + FOOTNOTE_MARKER.len_utf8()..];
+        let Some(end) = marked.find(FOOTNOTE_END) else {
+            rest = marked;
+            continue;
+        };
+        if let Ok(number) = marked[..end].parse() {
+            replaced.push_str(&replace(number));
+        }
+        rest = &marked[end + FOOTNOTE_END.len_utf8()..];
+    }
+
+    replaced.push_str(rest);
+    replaced
+}
+
+/// The text of the note without markup, as a single paragraph
+#[cfg_attr(not(feature = "pdf"), allow(dead_code))]
+pub(crate) fn note_text(footnote: &Footnote) -> String {
+    text_paragraphs(&footnote.content).join(" ")
+}
+
+/// The chapter content with its notes as EPUB 3 footnotes, which reading
+/// systems show in a popup when the reference is followed
+#[cfg(any(feature = "epub", test))]
+pub(crate) fn xhtml_footnotes(html: &str) -> String {
+    let (content, footnotes) = extract_footnotes(html);
+    if footnotes.is_empty() {
+        return content;
+    }
+
+    let mut content = replace_markers(&content, |number| {
+        format!(
+            r##"<sup><a id="noteref-{number}" href="#note-{number}" class="noteref" epub:type="noteref" role="doc-noteref">{number}</a></sup>"##
+        )
+    });
+    for footnote in footnotes {
+        let number = footnote.number;
+        content += &format!(
+            r##"<aside id="note-{number}" class="footnote" epub:type="footnote" role="doc-footnote"><a href="#noteref-{number}" role="doc-backlink">{number}.</a> {}</aside>"##,
+            footnote.content.trim()
+        );
+    }
+    content
+}
+
+fn marker(number: usize) -> String {
+    format!("{FOOTNOTE_MARKER}{number}{FOOTNOTE_END}")
+}
+
+fn tokenize(html: &str) -> Vec<Token<'_>> {
+    let mut tokens: Vec<Token> = vec![];
+    let mut open: Vec<usize> = vec![];
+    let mut position = 0;
+
+    while position < html.len() {
+        let rest = &html[position..];
+        let (length, tag, kind) = match rest.strip_prefix('<') {
+            Some(inner) => {
+                let end = tag_end(inner);
+                let tag = &inner[..end];
+                let length = (end + 2).min(rest.len());
+                let name = tag
+                    .trim_start_matches('/')
+                    .split(|c: char| c.is_whitespace() || c == '/')
+                    .next()
+                    .unwrap_or_default()
+                    .to_ascii_lowercase();
+
+                let kind = if tag.starts_with(['!', '?']) || name.is_empty() {
+                    Kind::Other
+                } else if tag.starts_with('/') {
+                    Kind::End { name }
+                } else {
+                    let void = VOID_TAGS.contains(&name.as_str()) || tag.trim_end().ends_with('/');
+                    Kind::Start { name, void }
+                };
+                (length, tag, kind)
+            }
+            None => (rest.find('<').unwrap_or(rest.len()), "", Kind::Text),
+        };
+
+        let index = tokens.len();
+        let mut parent = open.last().copied();
+        match &kind {
+            Kind::Start { void: false, .. } => open.push(index),
+            Kind::End { name } => {
+                let matching = open.iter().rposition(|&start| {
+                    matches!(&tokens[start].kind, Kind::Start { name: open, .. } if open == name)
+                });
+                if let Some(matching) = matching {
+                    let start = open[matching];
+                    open.truncate(matching);
+                    tokens[start].end = Some(index);
+                    parent = tokens[start].parent;
+                }
+            }
+            _ => {}
+        }
+
+        tokens.push(Token {
+            range: position..position + length,
+            tag,
+            kind,
+            parent,
+            end: None,
+        });
+        position += length;
+    }
+
+    tokens
+}
+
+/// The text of the tokens without markup
+fn text(html: &str, tokens: &[Token], range: Range<usize>) -> String {
+    let text = tokens[range]
+        .iter()
+        .filter(|token| matches!(token.kind, Kind::Text))
+        .map(|token| &html[token.range.clone()])
+        .collect::<String>();
+    decode_entities(&text).trim().to_string()
+}
+
+fn is_label(text: &str) -> bool {
+    !text.is_empty() && text.chars().count() <= MAX_LABEL
+}
+
+/// The tokens of the element with the id after the reference, the block it is
+/// in when it is an anchor within a paragraph
+fn note_element(tokens: &[Token], id: &str, after: usize) -> Option<Range<usize>> {
+    let (index, token) = tokens
+        .iter()
+        .enumerate()
+        .skip(after + 1)
+        .find(|(_, token)| {
+            matches!(token.kind, Kind::Start { .. })
+                && (attribute(token.tag, "id").as_deref() == Some(id)
+                    || attribute(token.tag, "name").as_deref() == Some(id))
+        })?;
+
+    let mut start = index;
+    let mut current = token;
+    while !matches!(&current.kind, Kind::Start { name, .. } if BLOCK_TAGS.contains(&name.as_str()) && name != "span")
+    {
+        start = current.parent?;
+        current = &tokens[start];
+    }
+    Some(start..current.end? + 1)
+}
+
+/// The tokens of the author's note starting at the token
+fn author_note(tokens: &[Token], html: &str, index: usize) -> Option<Range<usize>> {
+    let token = &tokens[index];
+    let Kind::Start { name, .. } = &token.kind else {
+        return None;
+    };
+    if !BLOCK_TAGS.contains(&name.as_str()) || name == "li" {
+        return None;
+    }
+    let end = token.end?;
+
+    let class = attribute(token.tag, "class")
+        .unwrap_or_default()
+        .to_ascii_lowercase();
+    let classed = AUTHOR_NOTE_CLASSES
+        .iter()
+        .any(|author_note| class.contains(author_note));
+    let prefixed = name == "p" && {
+        let text = text(html, tokens, index..end + 1).to_lowercase();
+        AUTHOR_NOTE_PREFIXES
+            .iter()
+            .any(|prefix| text.starts_with(prefix))
+    };
+
+    (classed || prefixed).then_some(index..end + 1)
+}
+
+/// The html of the tokens without the links back to the reference
+fn note_content(html: &str, tokens: &[Token], range: Range<usize>) -> String {
+    let mut content = String::new();
+    let mut index = range.start;
+
+    while index < range.end {
+        let token = &tokens[index];
+        if let (Kind::Start { name, .. }, Some(end)) = (&token.kind, token.end) {
+            let backlink = name == "a"
+                && attribute(token.tag, "href").is_some_and(|href| href.starts_with('#'))
+                && end < range.end
+                && is_label(&text(html, tokens, index..end + 1));
+            if backlink {
+                index = end + 1;
+                continue;
+            }
+        }
+        content.push_str(&html[token.range.clone()]);
+        index += 1;
+    }
+
+    content.trim().to_string()
+}
+
+/// The html without the lists and sections emptied by taking out their notes,
+/// and the rule that separated them from the chapter
+fn remove_empty_containers(html: &str) -> String {
+    let tokens = tokenize(html);
+    let mut content = String::with_capacity(html.len());
+    let mut index = 0;
+
+    while index < tokens.len() {
+        let token = &tokens[index];
+        if let (Kind::Start { name, .. }, Some(end)) = (&token.kind, token.end) {
+            let empty = CONTAINER_TAGS.contains(&name.as_str())
+                && text(html, &tokens, index..end + 1).is_empty()
+                && !tokens[index..end]
+                    .iter()
+                    .any(|token| matches!(&token.kind, Kind::Start { name, .. } if name == "img"));
+            if empty {
+                index = end + 1;
+                continue;
+            }
+        }
+        content.push_str(&html[token.range.clone()]);
+        index += 1;
+    }
+
+    let trimmed = content.trim_end();
+    match trimmed.rfind('<') {
+        Some(start)
+            if trimmed[start + 1..]
+                .trim_end_matches(['>', '/', ' '])
+                .eq_ignore_ascii_case("hr") =>
+        {
+            content.truncate(start);
+            content
+        }
+        _ => content,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_turn_notes_into_footnotes() {
+        let html = concat!(
+            r##"<p>A cultivator<sup><a href="#fn1" id="ref1">[1]</a></sup> walked in.</p>"##,
+            r#"<p>A/N: Thanks for reading!</p>"#,
+            r##"<p>The end, see <a href="#top">the start of the chapter</a>.</p>"##,
+            r##"<hr/><ol class="footnotes"><li id="fn1">Someone who trains their qi. <a href="#ref1">↩</a></li></ol>"##,
+        );
+
+        let (content, footnotes) = extract_footnotes(html);
+        assert_eq!(
+            content,
+            concat!(
+                "<p>A cultivator\u{E000}1\u{E001} walked in.\u{E000}2\u{E001}</p>",
+                r##"<p>The end, see <a href="#top">the start of the chapter</a>.</p>"##,
+            )
+        );
+        assert_eq!(
+            footnotes,
+            [
+                Footnote {
+                    number: 1,
+                    content: String::from("Someone who trains their qi."),
+                },
+                Footnote {
+                    number: 2,
+                    content: String::from("A/N: Thanks for reading!"),
+                },
+            ]
+        );
+
+        let xhtml = xhtml_footnotes(html);
+        assert!(xhtml.contains(
+            r##"cultivator<sup><a id="noteref-1" href="#note-1" class="noteref" epub:type="noteref" role="doc-noteref">1</a></sup> walked"##
+        ));
+        assert!(xhtml.ends_with(
+            r##"<aside id="note-2" class="footnote" epub:type="footnote" role="doc-footnote"><a href="#noteref-2" role="doc-backlink">2.</a> A/N: Thanks for reading!</aside>"##
+        ));
+
+        let html = r#"<div class="author-note"><p>Before the chapter</p></div><p>Text</p>"#;
+        let (content, footnotes) = extract_footnotes(html);
+        assert_eq!(content, "<p>Text\u{E000}1\u{E001}</p>");
+        assert_eq!(footnotes[0].content, "<p>Before the chapter</p>");
+    }
+}
@@ -0,0 +1,170 @@
+use std::{fmt::Display, fs::File, io::BufWriter, str::FromStr};
+
+use quelle_common::ProgressEvent;
+
+#[cfg(feature = "audio")]
+use crate::audio::{AudioContainer, AudioOptions};
+#[cfg(feature = "epub")]
+use crate::epub::EpubOptions;
+#[cfg(feature = "pdf")]
+use crate::pdf::PdfOptions;
+use crate::{
+    data::Bundle,
+    front::FrontMatter,
+    metadata::MetadataMapping,
+    progress::{CancellationToken, ProgressBundle},
+    sanitize::{SanitizeOptions, SanitizedBundle},
+    txt::TxtOptions,
+};
+#[cfg(any(feature = "epub", feature = "pdf"))]
+use crate::{embed::ImageOptions, fonts::FontOptions};
+
+/// The output formats a novel can be bundled into
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum Format {
+    Epub,
+    /// An epub marked up for the page statistics and reading progress of Kobo readers
+    Kepub,
+    /// A FictionBook 2 document, read by most e-reader apps for phones
+    Fb2,
+    /// A PDF laid out with a Typst template
+    Pdf,
+    /// An audiobook of the chapters read by a speech engine, with chapter markers
+    M4b,
+    /// Like [`Format::M4b`] for players without support for m4b
+    Mp3,
+    /// A comic book archive of the chapter images, for manga and manhwa
+    Cbz,
+    /// A single plain text file, for simple e-readers and text to speech tools
+    Txt,
+}
+
+/// The settings of the formats that have any
+#[derive(Clone, Debug, Default)]
+pub struct FormatOptions {
+    #[cfg(feature = "epub")]
+    pub epub: EpubOptions,
+    pub txt: TxtOptions,
+    #[cfg(feature = "pdf")]
+    pub pdf: PdfOptions,
+    #[cfg(feature = "audio")]
+    pub audio: AudioOptions,
+    /// Whether and how the images of chapters are embedded into epub and pdf bundles
+    #[cfg(any(feature = "epub", feature = "pdf"))]
+    pub images: ImageOptions,
+    /// The fonts embedded into epub and pdf bundles
+    #[cfg(any(feature = "epub", feature = "pdf"))]
+    pub fonts: FontOptions,
+    /// Where the metadata collected by extensions is written in epub and fb2 bundles
+    pub metadata: MetadataMapping,
+    /// The pages written before the chapters of epub and txt bundles
+    pub front_matter: FrontMatter,
+    /// How the chapter content is cleaned up before it is bundled
+    pub sanitize: SanitizeOptions,
+}
+
+impl Format {
+    /// The file extension used for the format
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Format::Epub => "epub",
+            Format::Kepub => "kepub.epub",
+            Format::Fb2 => "fb2",
+            Format::Pdf => "pdf",
+            Format::M4b => "m4b",
+            Format::Mp3 => "mp3",
+            Format::Cbz => "cbz",
+            Format::Txt => "txt",
+        }
+    }
+
+    /// Bundle the novel into this format, reporting every chapter read to
+    /// `progress`
+    ///
+    /// The chapter content is sanitized first. Fails with
+    /// [`Cancelled`](crate::Cancelled) once `cancel` is cancelled, and when the
+    /// feature required by the format is not enabled.
+    #[allow(unused_variables)]
+    pub fn bundle<B: Bundle>(
+        &self,
+        bundle: &B,
+        out: &mut BufWriter<File>,
+        options: &FormatOptions,
+        progress: impl Fn(ProgressEvent) + Sync,
+        cancel: &CancellationToken,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        cancel.check()?;
+        let bundle = &SanitizedBundle::new(bundle, &options.sanitize);
+        let bundle = &ProgressBundle::new(bundle, progress, cancel);
+        match self {
+            #[cfg(feature = "epub")]
+            Format::Epub => crate::epub::bundle_epub(
+                bundle,
+                out,
+                &options.epub,
+                &options.images,
+                &options.fonts,
+                &options.metadata,
+                &options.front_matter,
+            ),
+            #[cfg(feature = "epub")]
+            Format::Kepub => crate::epub::bundle_kepub(
+                bundle,
+                out,
+                &options.epub,
+                &options.images,
+                &options.fonts,
+                &options.metadata,
+                &options.front_matter,
+            ),
+            #[cfg(feature = "fb2")]
+            Format::Fb2 => crate::fb2::bundle_fb2(bundle, out, &options.metadata),
+            #[cfg(feature = "pdf")]
+            Format::Pdf => crate::pdf::bundle_pdf(
+                bundle,
+                out,
+                &options.pdf,
+                &options.images,
+                &options.fonts,
+                cancel,
+            ),
+            #[cfg(feature = "audio")]
+            Format::M4b => {
+                crate::audio::bundle_audio(bundle, out, &options.audio, AudioContainer::M4b, cancel)
+            }
+            #[cfg(feature = "audio")]
+            Format::Mp3 => {
+                crate::audio::bundle_audio(bundle, out, &options.audio, AudioContainer::Mp3, cancel)
+            }
+            #[cfg(feature = "cbz")]
+            Format::Cbz => crate::cbz::bundle_cbz(bundle, out),
+            Format::Txt => crate::txt::bundle_txt(bundle, out, &options.txt, &options.front_matter),
+            #[allow(unreachable_patterns)]
+            format => Err(format!("'{format}' support is not enabled").into()),
+        }
+    }
+}
+
+impl FromStr for Format {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "epub" => Ok(Format::Epub),
+            "kepub" | "kepub.epub" => Ok(Format::Kepub),
+            "fb2" => Ok(Format::Fb2),
+            "pdf" => Ok(Format::Pdf),
+            "m4b" => Ok(Format::M4b),
+            "mp3" => Ok(Format::Mp3),
+            "cbz" => Ok(Format::Cbz),
+            "txt" => Ok(Format::Txt),
+            _ => Err(format!("unsupported bundle format '{s}'")),
+        }
+    }
+}
+
+impl Display for Format {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.extension())
+    }
+}
@@ -0,0 +1,116 @@
+use chrono::Utc;
+
+use crate::data::Bundle;
+
+/// The pages written before the chapters, each of which can be left out
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FrontMatter {
+    /// A page with the title and authors of the novel
+    pub title_page: bool,
+    /// A page with the description of the novel
+    pub synopsis: bool,
+    /// A page with the source, the dates the novel was fetched and bundled,
+    /// and the metadata of the source
+    pub colophon: bool,
+}
+
+impl Default for FrontMatter {
+    fn default() -> Self {
+        Self {
+            title_page: true,
+            synopsis: true,
+            colophon: true,
+        }
+    }
+}
+
+/// The title of the bundle, with the part of a split novel
+pub(crate) fn bundle_title<B: Bundle>(bundle: &B) -> String {
+    let novel = bundle.novel();
+    match bundle.part() {
+        Some(part) => format!(
+            "{} (Part {} of {})",
+            novel.title,
+            part.number(),
+            part.total()
+        ),
+        None => novel.title.clone(),
+    }
+}
+
+/// The facts of the colophon as labels and values, the metadata of the source
+/// in the order it was first given
+pub(crate) fn colophon<B: Bundle>(bundle: &B) -> Vec<(String, String)> {
+    let novel = bundle.novel();
+    let mut entries = vec![(String::from("Source"), novel.url.clone())];
+    if let Some(fetched_at) = bundle.fetched_at() {
+        entries.push((
+            String::from("Fetched"),
+            fetched_at.format("%Y-%m-%d").to_string(),
+        ));
+    }
+
+    let mut metadata: Vec<(String, Vec<&str>)> = vec![];
+    for entry in &novel.metadata {
+        let label = capitalize(&entry.name);
+        match metadata.iter_mut().find(|(name, _)| *name == label) {
+            Some((_, values)) => values.push(&entry.value),
+            None => metadata.push((label, vec![&entry.value])),
+        }
+    }
+    entries.extend(
+        metadata
+            .into_iter()
+            .map(|(label, values)| (label, values.join(", "))),
+    );
+
+    entries.push((
+        String::from("Bundled"),
+        format!("{} with quelle", Utc::now().format("%Y-%m-%d")),
+    ));
+    entries
+}
+
+fn capitalize(s: &str) -> String {
+    let mut c = s.chars();
+    match c.next() {
+        None => String::new(),
+        Some(f) => f.to_uppercase().collect::<String>() + c.as_str(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use quelle_core::prelude::*;
+
+    use super::*;
+    use crate::test_support::TestBundle;
+
+    #[test]
+    fn should_group_metadata_in_colophon() {
+        let entry = |name: &str, value: &str| Metadata::new(name.into(), value.into(), None);
+        let bundle = TestBundle::new(Novel {
+            url: String::from("https://example.com/novel"),
+            metadata: vec![
+                entry("subject", "Fantasy"),
+                entry("translator", "Someone"),
+                entry("subject", "Isekai"),
+            ],
+            ..Default::default()
+        });
+
+        let colophon = colophon(&bundle);
+        assert_eq!(
+            &colophon[..3],
+            [
+                (
+                    String::from("Source"),
+                    String::from("https://example.com/novel")
+                ),
+                (String::from("Subject"), String::from("Fantasy, Isekai")),
+                (String::from("Translator"), String::from("Someone")),
+            ]
+        );
+        assert_eq!(colophon[3].0, "Bundled");
+    }
+}
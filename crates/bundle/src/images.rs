@@ -0,0 +1,81 @@
+use crate::text::decode_entities;
+
+/// The sources of the images of the html content, in order of appearance
+pub fn image_sources(html: &str) -> Vec<String> {
+    let mut sources = vec![];
+    let mut rest = html;
+
+    while let Some(start) = rest.find('<') {
+        rest = &rest[start + 1..];
+        let end = tag_end(rest);
+        let tag = &rest[..end];
+        rest = &rest[end..];
+
+        if let Some(source) = is_image(tag).then(|| attribute(tag, "src")).flatten() {
+            sources.push(decode_entities(&source));
+        }
+    }
+
+    sources
+}
+
+pub(crate) fn is_image(tag: &str) -> bool {
+    tag.get(..4)
+        .filter(|name| name.eq_ignore_ascii_case("img "))
+        .is_some()
+}
+
+/// The position of the end of the tag, skipping quoted attribute values
+pub(crate) fn tag_end(tag: &str) -> usize {
+    let mut quote = None;
+    for (index, c) in tag.char_indices() {
+        match (quote, c) {
+            (None, '"' | '\'') => quote = Some(c),
+            (Some(open), _) if open == c => quote = None,
+            (None, '>') => return index,
+            _ => {}
+        }
+    }
+    tag.len()
+}
+
+/// The value of the attribute in the inside of an element start tag
+pub(crate) fn attribute(tag: &str, name: &str) -> Option<String> {
+    let mut rest = tag;
+    while let Some(index) = rest.find('=') {
+        let key = rest[..index]
+            .trim_end()
+            .rsplit(|c: char| c.is_whitespace())
+            .next()
+            .unwrap_or_default();
+        let value = rest[index + 1..].trim_start();
+
+        let (found, remaining) = match value.chars().next() {
+            Some(quote @ ('"' | '\'')) => {
+                let end = value[1..].find(quote).map(|end| end + 1)?;
+                (&value[1..end], &value[end + 1..])
+            }
+            _ => {
+                let end = value.find(char::is_whitespace).unwrap_or(value.len());
+                (value[..end].trim_end_matches('/'), &value[end..])
+            }
+        };
+
+        if key.eq_ignore_ascii_case(name) {
+            return Some(found.to_string());
+        }
+        rest = remaining;
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_find_image_sources() {
+        let html = r#"<p><img alt="a > b" src="1.png"><IMG class=x src='2.png'/><image src=no></p><img src=3.png />"#;
+        assert_eq!(image_sources(html), vec!["1.png", "2.png", "3.png"]);
+    }
+}
@@ -0,0 +1,153 @@
+/// Elements that start a new paragraph in the numbering of Kobo spans
+const PARAGRAPH_TAGS: [&str; 11] = [
+    "p",
+    "div",
+    "h1",
+    "h2",
+    "h3",
+    "h4",
+    "h5",
+    "h6",
+    "li",
+    "blockquote",
+    "pre",
+];
+
+/// Elements whose text is not read, or only annotates the text of ruby, and so
+/// is not split into spans
+const SKIPPED_TAGS: [&str; 5] = ["script", "style", "svg", "rt", "rp"];
+
+/// Mark up the chapter content the way Kobo readers expect of a kepub
+///
+/// Every sentence is wrapped in a `koboSpan` numbered by its paragraph and
+/// its position in the paragraph, which is what Kobo readers use for page
+/// statistics and reading progress. The content is wrapped in the book
+/// columns used for the layout of the pages.
+pub fn kobo_content(html: &str) -> String {
+    let mut content = String::with_capacity(html.len() * 2);
+    content.push_str(r#"<div id="book-columns"><div id="book-inner">"#);
+
+    let mut paragraph = 0;
+    let mut sentence = 0;
+    let mut skipped: Option<String> = None;
+    let mut rest = html;
+
+    while !rest.is_empty() {
+        let text = &rest[..rest.find('<').unwrap_or(rest.len())];
+        if skipped.is_some() {
+            content.push_str(text);
+        } else {
+            for part in sentences(text) {
+                if part.trim().is_empty() {
+                    content.push_str(part);
+                    continue;
+                }
+
+                // Text before the first paragraph element has a paragraph of its own
+                if paragraph == 0 {
+                    paragraph = 1;
+                }
+                sentence += 1;
+                content.push_str(&format!(
+                    r#"<span class="koboSpan" id="kobo.{paragraph}.{sentence}">{part}</span>"#
+                ));
+            }
+        }
+        rest = &rest[text.len()..];
+
+        if rest.is_empty() {
+            break;
+        }
+        let Some(end) = rest.find('>') else {
+            content.push_str(rest);
+            break;
+        };
+
+        let tag = &rest[..=end];
+        let closing = tag.starts_with("</");
+        let name = tag
+            .trim_start_matches(['<', '/'])
+            .split(|c: char| c.is_whitespace() || c == '/' || c == '>')
+            .next()
+            .unwrap_or_default()
+            .to_ascii_lowercase();
+
+        match &skipped {
+            Some(skipped_name) if closing && *skipped_name == name => skipped = None,
+            Some(_) => {}
+            None if !closing && SKIPPED_TAGS.contains(&name.as_str()) && !tag.ends_with("/>") => {
+                skipped = Some(name.clone());
+            }
+            None if !closing && PARAGRAPH_TAGS.contains(&name.as_str()) => {
+                paragraph += 1;
+                sentence = 0;
+            }
+            None => {}
+        }
+
+        content.push_str(tag);
+        rest = &rest[end + 1..];
+    }
+
+    content.push_str("</div></div>");
+    content
+}
+
+/// Split the text after the punctuation ending each sentence, keeping the
+/// whitespace between sentences as parts of their own
+fn sentences(text: &str) -> Vec<&str> {
+    let mut parts = vec![];
+    let mut start = 0;
+    let mut chars = text.char_indices().peekable();
+
+    while let Some((_, c)) = chars.next() {
+        if !matches!(c, '.' | '!' | '?' | '…') {
+            continue;
+        }
+        // Closing quotes belong to the sentence they end
+        while let Some((_, '"' | '\'' | '”' | '’' | ')')) = chars.peek() {
+            chars.next();
+        }
+        let end = chars.peek().map(|(next, _)| *next).unwrap_or(text.len());
+        if end < text.len() && !text[end..].starts_with(char::is_whitespace) {
+            continue;
+        }
+
+        parts.push(&text[start..end]);
+        let spaces = text[end..]
+            .find(|c: char| !c.is_whitespace())
+            .unwrap_or(text.len() - end);
+        if spaces > 0 {
+            parts.push(&text[end..end + spaces]);
+        }
+        start = end + spaces;
+    }
+
+    if start < text.len() {
+        parts.push(&text[start..]);
+    }
+    parts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_wrap_sentences_in_numbered_spans() {
+        let html = "<h1>One</h1>\n<p>He left. \"Why?\" she asked</p><style>p { x: 1. }</style>";
+        assert_eq!(
+            kobo_content(html),
+            concat!(
+                r#"<div id="book-columns"><div id="book-inner">"#,
+                r#"<h1><span class="koboSpan" id="kobo.1.1">One</span></h1>"#,
+                "\n",
+                r#"<p><span class="koboSpan" id="kobo.2.1">He left.</span> "#,
+                r#"<span class="koboSpan" id="kobo.2.2">"Why?"</span> "#,
+                r#"<span class="koboSpan" id="kobo.2.3">she asked</span></p>"#,
+                "<style>p { x: 1. }</style>",
+                "</div></div>"
+            )
+        );
+    }
+}
@@ -1,8 +1,78 @@
 #![forbid(unsafe_code)]
 
+mod calibre;
+#[cfg(any(feature = "epub", feature = "fb2", feature = "pdf"))]
+mod cover;
 mod data;
+#[cfg(any(feature = "epub", feature = "pdf"))]
+mod embed;
+#[cfg(any(feature = "epub", feature = "pdf"))]
+mod fonts;
+#[cfg(any(feature = "epub", feature = "pdf", test))]
+mod footnote;
+mod format;
+mod front;
+mod images;
+#[cfg(any(feature = "epub", test))]
+mod kobo;
+#[cfg(any(feature = "epub", feature = "fb2", feature = "pdf"))]
+mod magick;
+mod metadata;
+mod progress;
+#[cfg(any(feature = "epub", feature = "pdf", test))]
+mod render;
+mod sanitize;
+mod selection;
+mod site;
+mod split;
+mod template;
+#[cfg(test)]
+mod test_support;
+mod text;
+#[cfg(any(feature = "epub", test))]
+mod theme;
+mod txt;
+#[cfg(any(feature = "epub", test))]
+mod xhtml;
 
+#[cfg(feature = "audio")]
+mod audio;
+#[cfg(feature = "cbz")]
+mod cbz;
 #[cfg(feature = "epub")]
 pub mod epub;
+#[cfg(feature = "fb2")]
+mod fb2;
+#[cfg(feature = "pdf")]
+mod pdf;
+#[cfg(any(feature = "audio", feature = "epub", feature = "pdf"))]
+mod work;
 
-pub use data::{Bundle, PersistBundle};
+#[cfg(feature = "audio")]
+pub use audio::{
+    bundle_audio, bundle_audio_with, AudioContainer, AudioOptions, SpeechBackend, SpeechEngine,
+    OUTPUT_PLACEHOLDER,
+};
+pub use calibre::{add_to_calibre, CalibreBook, CalibreOptions};
+pub use data::{Asset, Bundle, BundleVolume, CachedBundle, PersistBundle};
+#[cfg(any(feature = "epub", feature = "pdf"))]
+pub use embed::ImageOptions;
+#[cfg(feature = "epub")]
+pub use epub::EpubOptions;
+#[cfg(any(feature = "epub", feature = "pdf"))]
+pub use fonts::FontOptions;
+pub use format::{Format, FormatOptions};
+pub use front::FrontMatter;
+pub use images::image_sources;
+pub use metadata::{MetadataField, MetadataMapping};
+#[cfg(feature = "pdf")]
+pub use pdf::{bundle_pdf, PdfOptions, DEFAULT_PDF_TEMPLATE, PDF_TEMPLATE_FILE};
+pub use progress::{CancellationToken, Cancelled};
+pub use sanitize::{sanitize_html, SanitizeOptions, SanitizeStep, SanitizedBundle};
+pub use selection::ChapterSelection;
+pub use site::{bundle_site, write_library_index, SiteEntry};
+pub use split::{part_path, split_chapters, Part, PartBundle, PartSpan, SplitOptions};
+pub use template::OutputTemplate;
+#[cfg(feature = "epub")]
+pub use theme::{Theme, ThemeResource};
+pub use txt::TxtOptions;
@@ -0,0 +1,33 @@
+use std::{
+    io::Write,
+    process::{Command, Stdio},
+    thread,
+};
+
+/// Run the ImageMagick program with the image on stdin, returning its stdout
+pub(crate) fn convert(
+    program: &str,
+    args: &[String],
+    content: &[u8],
+) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+    let mut child = Command::new(program)
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::inherit())
+        .spawn()
+        .map_err(|e| format!("failed to start '{program}': {e}"))?;
+
+    // Written from another thread so that a full stdout does not block the input
+    let mut stdin = child.stdin.take().expect("stdin is piped");
+    let input = content.to_vec();
+    let writer = thread::spawn(move || stdin.write_all(&input));
+
+    let output = child.wait_with_output()?;
+    writer.join().map_err(|_| "failed to write the image")??;
+    if !output.status.success() || output.stdout.is_empty() {
+        return Err(format!("'{program}' exited with {}", output.status).into());
+    }
+
+    Ok(output.stdout)
+}
@@ -0,0 +1,180 @@
+use std::{collections::BTreeMap, fmt::Display, str::FromStr};
+
+use quelle_core::prelude::{Novel, DUBLIN_CORE};
+
+/// Metadata names of extensions that are not Dublin Core elements and where
+/// they are written unless the library maps them elsewhere
+const DEFAULT_FIELDS: [(&str, &str); 5] = [
+    ("tag", "dc:subject"),
+    ("genre", "dc:subject"),
+    ("warning", "calibre:#warnings"),
+    ("translator", "calibre:#translator"),
+    ("rating", "calibre:#rating"),
+];
+
+/// Where a metadata entry of the novel is written in bundles
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum MetadataField {
+    /// A Dublin Core element of the package metadata, written as `dc:subject`
+    DublinCore(String),
+    /// A column of the Calibre library, written as `calibre:#translator`, set
+    /// when the bundle is added to Calibre
+    Calibre(String),
+    /// Left out of bundles, written as `skip`
+    Skip,
+}
+
+impl FromStr for MetadataField {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        if s == "skip" {
+            return Ok(MetadataField::Skip);
+        }
+
+        match s.split_once(':') {
+            Some(("dc", name)) if DUBLIN_CORE.contains(&name) => {
+                Ok(MetadataField::DublinCore(name.to_string()))
+            }
+            Some(("dc", name)) => Err(format!("'{name}' is not a Dublin Core element")),
+            Some(("calibre", column)) if !column.is_empty() => {
+                Ok(MetadataField::Calibre(column.to_string()))
+            }
+            _ => Err(format!(
+                "unsupported metadata field '{s}', expected dc:<element>, calibre:<column> or skip"
+            )),
+        }
+    }
+}
+
+impl Display for MetadataField {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MetadataField::DublinCore(name) => write!(f, "dc:{name}"),
+            MetadataField::Calibre(column) => write!(f, "calibre:{column}"),
+            MetadataField::Skip => write!(f, "skip"),
+        }
+    }
+}
+
+/// Maps the metadata names collected by extensions to the fields bundles
+/// write them to
+///
+/// Dublin Core names are written to their element, tags and genres as
+/// subjects, warnings, translators and ratings to Calibre columns, and other
+/// names are left out unless mapped.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct MetadataMapping {
+    fields: BTreeMap<String, MetadataField>,
+}
+
+impl MetadataMapping {
+    /// The default mapping with the fields of the library taking precedence
+    pub fn new<I, K, V>(fields: I) -> Result<Self, String>
+    where
+        I: IntoIterator<Item = (K, V)>,
+        K: Into<String>,
+        V: AsRef<str>,
+    {
+        let fields = fields
+            .into_iter()
+            .map(|(name, field)| Ok((name.into(), field.as_ref().parse()?)))
+            .collect::<Result<_, String>>()?;
+
+        Ok(Self { fields })
+    }
+
+    /// The field the metadata entry of the name is written to
+    pub fn field(&self, name: &str) -> MetadataField {
+        if let Some(field) = self.fields.get(name) {
+            return field.clone();
+        }
+
+        if let Some((_, field)) = DEFAULT_FIELDS.iter().find(|(default, _)| *default == name) {
+            return field.parse().expect("default fields are valid");
+        }
+
+        if DUBLIN_CORE.contains(&name) {
+            MetadataField::DublinCore(name.to_string())
+        } else {
+            MetadataField::Skip
+        }
+    }
+
+    /// The values of the novel metadata written to the Dublin Core element,
+    /// without duplicates
+    pub fn dublin_core<'a>(&self, novel: &'a Novel, element: &str) -> Vec<&'a str> {
+        let mut values = vec![];
+        for metadata in &novel.metadata {
+            let field = self.field(&metadata.name);
+            if matches!(&field, MetadataField::DublinCore(name) if name == element)
+                && !values.contains(&metadata.value.as_str())
+            {
+                values.push(metadata.value.as_str());
+            }
+        }
+        values
+    }
+
+    /// The values of the novel metadata by Calibre column
+    pub fn calibre<'a>(&self, novel: &'a Novel) -> BTreeMap<String, Vec<&'a str>> {
+        let mut columns = BTreeMap::<String, Vec<&str>>::new();
+        for metadata in &novel.metadata {
+            if let MetadataField::Calibre(column) = self.field(&metadata.name) {
+                columns.entry(column).or_default().push(&metadata.value);
+            }
+        }
+        columns
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use quelle_core::prelude::Metadata;
+
+    use super::*;
+
+    #[test]
+    fn should_map_metadata_to_fields() {
+        let entry = |name: &str, value: &str| Metadata::new(name.into(), value.into(), None);
+        let novel = Novel {
+            metadata: vec![
+                entry("subject", "Fantasy"),
+                entry("tag", "Isekai"),
+                entry("tag", "Fantasy"),
+                entry("translator", "Someone"),
+                entry("warning", "Violence"),
+                entry("views", "1000"),
+            ],
+            ..Default::default()
+        };
+
+        let mapping = MetadataMapping::default();
+        assert_eq!(
+            mapping.dublin_core(&novel, "subject"),
+            ["Fantasy", "Isekai"]
+        );
+        assert_eq!(
+            mapping.calibre(&novel).get("#translator"),
+            Some(&vec!["Someone"])
+        );
+        assert_eq!(mapping.field("views"), MetadataField::Skip);
+
+        let mapping = MetadataMapping::new([
+            ("tag", "skip"),
+            ("views", "calibre:#views"),
+            ("warning", "dc:description"),
+        ])
+        .unwrap();
+        assert_eq!(mapping.dublin_core(&novel, "subject"), ["Fantasy"]);
+        assert_eq!(mapping.dublin_core(&novel, "description"), ["Violence"]);
+        assert_eq!(
+            mapping.calibre(&novel).keys().collect::<Vec<_>>(),
+            ["#translator", "#views"]
+        );
+
+        assert!(MetadataMapping::new([("tag", "dc:tag")]).is_err());
+        assert!(MetadataMapping::new([("tag", "calibre:")]).is_err());
+    }
+}
@@ -0,0 +1,579 @@
+use std::{
+    fs::{self, File},
+    io::{self, Write},
+    path::{Path, PathBuf},
+    process::Command,
+    thread,
+    time::Duration,
+};
+
+use chrono::Utc;
+use log::{info, warn};
+use quelle_core::prelude::{Chapter, ReadingDirection};
+
+use crate::{
+    cover::cover_or_generated,
+    data::{image_extension, volume_title, Bundle, BundleVolume},
+    embed::{replace_images, EmbeddedImages, ImageOptions},
+    fonts::{copy_fonts, FontOptions},
+    footnote::{extract_footnotes, note_text, replace_markers, Footnote},
+    front::bundle_title,
+    progress::{CancellationToken, Cancelled},
+    render::render_in_order,
+    text::{
+        annotate_ruby, strip_title_heading, text_paragraphs, RUBY_ANCHOR, RUBY_SEPARATOR,
+        RUBY_TERMINATOR,
+    },
+    work::work_dir,
+};
+
+/// The Typst template PDF bundles are laid out with unless another is given,
+/// documenting the variables templates receive
+pub const DEFAULT_PDF_TEMPLATE: &str = include_str!("pdf.typ");
+
+/// Marks a paragraph standing for an embedded image, followed by its path
+const IMAGE_MARKER: char = '\u{FFFC}';
+
+/// Sets the annotation of ruby in a smaller size centred above its base text
+const RUBY_FUNCTION: &str = "#let ruby(base, annotation) = box(base + place(top + center, dy: -0.6em, text(size: 0.5em, annotation)))\n";
+
+/// Languages written from right to left, by ISO 639 code
+const RTL_LANGS: [&str; 10] = ["ar", "he", "fa", "ur", "yi", "ps", "sd", "ug", "ckb", "dv"];
+
+/// Languages that can be set in vertical columns
+const VERTICAL_LANGS: [&str; 2] = ["ja", "zh"];
+
+/// How often typst is checked on while it compiles the document
+const TYPST_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// The name of the template file, in the template directory and as imported
+/// by the document
+pub const PDF_TEMPLATE_FILE: &str = "template.typ";
+
+/// How the novel is laid out as a PDF
+#[derive(Clone, Debug)]
+pub struct PdfOptions {
+    /// A Typst template, or a directory with a `template.typ` and the fonts and
+    /// images it uses, replacing [`DEFAULT_PDF_TEMPLATE`]
+    pub template: Option<PathBuf>,
+    /// The typst program used to compile the document
+    pub typst: String,
+    /// Set Japanese and Chinese novels in vertical columns read from right to
+    /// left, the way they are printed
+    pub vertical: bool,
+}
+
+impl Default for PdfOptions {
+    fn default() -> Self {
+        Self {
+            template: None,
+            typst: String::from("typst"),
+            vertical: false,
+        }
+    }
+}
+
+/// Lay out the novel with the Typst template and compile it into a PDF
+pub fn bundle_pdf<B: Bundle, W: Write>(
+    bundle: &B,
+    out: &mut W,
+    options: &PdfOptions,
+    image_options: &ImageOptions,
+    font_options: &FontOptions,
+    cancel: &CancellationToken,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let dir = work_dir("pdf")?;
+
+    match &options.template {
+        Some(template) if template.is_dir() => {
+            copy_dir(template, dir.path())?;
+            if !dir.path().join(PDF_TEMPLATE_FILE).exists() {
+                return Err(format!(
+                    "the template directory '{}' has no {PDF_TEMPLATE_FILE}",
+                    template.display()
+                )
+                .into());
+            }
+        }
+        Some(template) => {
+            fs::copy(template, dir.path().join(PDF_TEMPLATE_FILE))?;
+        }
+        None => fs::write(dir.path().join(PDF_TEMPLATE_FILE), DEFAULT_PDF_TEMPLATE)?,
+    }
+    info!("Written template");
+
+    let (content_type, content) = cover_or_generated(bundle)?;
+    let cover = format!("quelle-cover.{}", image_extension(&content_type));
+    fs::write(dir.path().join(&cover), content)?;
+
+    // Typst finds the fonts in the directory and embeds the glyphs it uses
+    let fonts = font_options.read()?;
+    copy_fonts(&fonts, dir.path())?;
+    if !fonts.is_empty() {
+        info!("Written {} fonts", fonts.len());
+    }
+    let families = fonts
+        .into_iter()
+        .map(|font| font.family)
+        .collect::<Vec<_>>();
+
+    let mut images = EmbeddedImages::new(image_options);
+    let source = document_source(
+        bundle,
+        Some(&cover),
+        &mut images,
+        options.vertical,
+        &families,
+    )?;
+    let document = dir.path().join("main.typ");
+    fs::write(&document, source)?;
+    info!("Written document");
+
+    for image in &images.images {
+        let path = dir.path().join(&image.name);
+        fs::create_dir_all(path.parent().unwrap_or(dir.path()))?;
+        fs::write(path, &image.content)?;
+    }
+    if !images.images.is_empty() {
+        info!("Written {} images", images.images.len());
+    }
+
+    let output = dir.path().join("book.pdf");
+    let mut child = Command::new(&options.typst)
+        .arg("compile")
+        .arg("--root")
+        .arg(dir.path())
+        .arg("--font-path")
+        .arg(dir.path())
+        .arg(&document)
+        .arg(&output)
+        .spawn()
+        .map_err(|e| format!("failed to start '{}': {e}", options.typst))?;
+    // Compiling a long novel takes a while, so typst is stopped when the
+    // bundle is cancelled
+    let status = loop {
+        if let Some(status) = child.try_wait()? {
+            break status;
+        }
+        if cancel.is_cancelled() {
+            let _ = child.kill();
+            let _ = child.wait();
+            return Err(Cancelled.into());
+        }
+        thread::sleep(TYPST_POLL_INTERVAL);
+    };
+    if !status.success() {
+        return Err(format!("'{}' exited with {status}", options.typst).into());
+    }
+
+    io::copy(&mut File::open(&output)?, out)?;
+    out.flush()?;
+
+    info!("PDF writing complete.");
+    Ok(())
+}
+
+/// How the text of the novel runs on the page
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Layout {
+    Ltr,
+    Rtl,
+    Vertical,
+}
+
+impl Layout {
+    /// The layout of the novel in the language, right to left when the
+    /// language or the source is and vertical when asked for and supported
+    fn of<B: Bundle>(bundle: &B, lang: Option<&str>, vertical: bool) -> Self {
+        if vertical {
+            if lang.is_some_and(|lang| VERTICAL_LANGS.contains(&lang)) {
+                return Layout::Vertical;
+            }
+            warn!(
+                "Only {} novels can be set vertically",
+                VERTICAL_LANGS.join(", ")
+            );
+        }
+
+        let rtl_source = bundle.meta().is_some_and(|meta| {
+            !meta.rds.is_empty()
+                && meta
+                    .rds
+                    .iter()
+                    .all(|rd| matches!(rd, ReadingDirection::Rtl))
+        });
+        if rtl_source || lang.is_some_and(|lang| RTL_LANGS.contains(&lang)) {
+            Layout::Rtl
+        } else {
+            Layout::Ltr
+        }
+    }
+}
+
+/// The Typst document of the novel, passing the novel to the `book` function
+/// of the template and the chapters as its body
+fn document_source<B: Bundle>(
+    bundle: &B,
+    cover: Option<&str>,
+    images: &mut EmbeddedImages,
+    vertical: bool,
+    fonts: &[String],
+) -> Result<String, Box<dyn std::error::Error>> {
+    let novel = bundle.novel();
+    let title = bundle_title(bundle);
+    let notes = bundle
+        .notes()
+        .map(|notes| {
+            notes
+                .split("\n\n")
+                .map(str::trim)
+                .filter(|paragraph| !paragraph.is_empty())
+                .collect::<Vec<_>>()
+        })
+        .unwrap_or_default();
+    // Typst only knows languages by their ISO 639 code
+    let lang = novel
+        .langs
+        .first()
+        .and_then(|lang| lang.split(['-', '_']).next())
+        .map(str::to_ascii_lowercase);
+    let layout = Layout::of(bundle, lang.as_deref(), vertical);
+    let lang = lang.map(|lang| string(&lang));
+
+    // Templates are only given the layout arguments when they apply, so that
+    // templates written without them keep working
+    let mut source = match layout {
+        Layout::Vertical => format!("#import \"{PDF_TEMPLATE_FILE}\": book, vertical\n"),
+        _ => format!("#import \"{PDF_TEMPLATE_FILE}\": book\n"),
+    };
+    source += "#show: book.with(\n";
+    source += &format!("  title: {},\n", string(&title));
+    source += &format!("  authors: {},\n", array(&novel.authors));
+    source += &format!("  description: {},\n", array(&novel.description));
+    source += &format!("  lang: {},\n", lang.as_deref().unwrap_or("none"));
+    source += &format!(
+        "  cover: {},\n",
+        cover.map(string).as_deref().unwrap_or("none")
+    );
+    source += &format!(
+        "  rights: {},\n",
+        bundle.rights().map(string).as_deref().unwrap_or("none")
+    );
+    source += &format!("  notes: {},\n", array(&notes));
+    source += &format!("  url: {},\n", string(&novel.url));
+    source += &format!(
+        "  date: {},\n",
+        string(&Utc::now().format("%Y-%m-%d").to_string())
+    );
+    match layout {
+        Layout::Ltr => {}
+        Layout::Rtl => source += "  dir: rtl,\n",
+        Layout::Vertical => source += "  vertical: true,\n",
+    }
+    if !fonts.is_empty() {
+        source += &format!("  fonts: {},\n", array(fonts));
+    }
+    source += ")\n";
+    source += RUBY_FUNCTION;
+
+    // Chapters are nested under a heading of their volume when the novel has several
+    let structured = novel.volumes.len() > 1;
+    let include_images = images.include_images();
+    for BundleVolume {
+        number,
+        volume,
+        chapters,
+    } in bundle.volumes()
+    {
+        if structured {
+            source += &format!("\n= {}\n", markup(&volume_title(volume, number)));
+        }
+
+        // Chapters are read and marked up on several threads, then added in order
+        let render = |&(position, chapter): &(usize, &Chapter)| {
+            let title = bundle
+                .chapter_title(chapter, position)
+                .unwrap_or_else(|| chapter.title.clone());
+            let content = bundle
+                .chapter_content(&chapter.url)
+                .map_err(|e| e.to_string())?;
+            Ok((
+                title.clone(),
+                chapter_blocks(&title, chapter, content, include_images, layout),
+            ))
+        };
+
+        render_in_order(&chapters, render, |(_, chapter), (title, blocks)| {
+            let level = if structured { "==" } else { "=" };
+            source += &format!("\n{level} {}\n", markup(&title));
+
+            // Vertical text is set a run of paragraphs at a time, between images
+            let mut run = vec![];
+            for block in blocks {
+                match block {
+                    Block::Text(text) if layout == Layout::Vertical => run.push(text),
+                    Block::Text(text) => source += &format!("\n{text}\n"),
+                    Block::Image(image) => {
+                        if !run.is_empty() {
+                            source += &format!("\n#vertical({})\n", array(&run));
+                            run.clear();
+                        }
+                        if let Some(name) = images.embed(bundle, &image)? {
+                            source += &format!("\n#align(center, image({}))\n", string(&name));
+                        }
+                    }
+                }
+            }
+            if !run.is_empty() {
+                source += &format!("\n#vertical({})\n", array(&run));
+            }
+
+            info!("Written '{}'.", chapter.title);
+            Ok(())
+        })?;
+    }
+
+    Ok(source)
+}
+
+/// A paragraph of a chapter in Typst markup, or as text when it is set
+/// vertically, or the source of an image
+enum Block {
+    Text(String),
+    Image(String),
+}
+
+/// The paragraphs and images of the chapter content, in order
+fn chapter_blocks(
+    title: &str,
+    chapter: &Chapter,
+    content: Option<String>,
+    include_images: bool,
+    layout: Layout,
+) -> Vec<Block> {
+    let Some(content) = content else {
+        warn!("Using placeholder content for '{title}'.");
+        return vec![Block::Text(String::from("No downloaded content"))];
+    };
+
+    // Notes are set as footnotes at their reference instead of in the text
+    let (mut content, footnotes) = extract_footnotes(&annotate_ruby(&content));
+
+    if include_images {
+        // Images become paragraphs of their own to keep their place in the text
+        content = replace_images(&content, |source, _| {
+            Ok::<_, ()>(Some(format!("<p>{IMAGE_MARKER}{source}</p>")))
+        })
+        .unwrap_or_default();
+    }
+
+    let mut paragraphs = text_paragraphs(&content);
+    strip_title_heading(&mut paragraphs, &[title, &chapter.title]);
+
+    if layout == Layout::Vertical {
+        // Footnotes follow the chapter, marked by their number in the text
+        let notes = footnotes
+            .iter()
+            .map(|footnote| format!("※{} {}", footnote.number, note_text(footnote)));
+        return paragraphs
+            .into_iter()
+            .map(|paragraph| match paragraph.strip_prefix(IMAGE_MARKER) {
+                Some(source) => Block::Image(source.to_string()),
+                None => Block::Text(vertical_text(&paragraph)),
+            })
+            .chain(notes.map(|note| Block::Text(vertical_text(&note))))
+            .collect();
+    }
+
+    paragraphs
+        .into_iter()
+        .map(|paragraph| match paragraph.strip_prefix(IMAGE_MARKER) {
+            Some(source) => Block::Image(source.to_string()),
+            None => Block::Text(ruby_markup(&footnote_markup(
+                &markup(&paragraph),
+                &footnotes,
+            ))),
+        })
+        .collect()
+}
+
+/// The paragraph as text to set vertically, character by character, with the
+/// readings of ruby in brackets after their base and footnotes by number
+fn vertical_text(paragraph: &str) -> String {
+    replace_markers(paragraph, |number| format!("※{number}"))
+        .replace(RUBY_ANCHOR, "")
+        .replace(RUBY_SEPARATOR, "（")
+        .replace(RUBY_TERMINATOR, "）")
+}
+
+/// The paragraph of Typst markup with its footnote markers replaced by the
+/// footnotes
+fn footnote_markup(paragraph: &str, footnotes: &[Footnote]) -> String {
+    replace_markers(paragraph, |number| match footnotes.get(number - 1) {
+        // The semicolon ends the call so that the text after it stays text
+        Some(footnote) => format!("#footnote[{}];", markup(&note_text(footnote))),
+        None => String::new(),
+    })
+}
+
+/// The paragraph of Typst markup with its ruby annotated by the ruby function
+/// of the document
+fn ruby_markup(paragraph: &str) -> String {
+    // Brackets of the text are escaped, so the base and annotation are set apart
+    paragraph
+        .replace(RUBY_ANCHOR, "#ruby[")
+        .replace(RUBY_SEPARATOR, "][")
+        .replace(RUBY_TERMINATOR, "];")
+}
+
+/// A Typst string literal
+fn string(value: &str) -> String {
+    let escaped = value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n");
+    format!("\"{escaped}\"")
+}
+
+/// A Typst array of strings
+fn array<S: AsRef<str>>(values: &[S]) -> String {
+    match values {
+        [] => String::from("()"),
+        // A single value in parentheses is not an array without the comma
+        [value] => format!("({},)", string(value.as_ref())),
+        values => {
+            let values = values
+                .iter()
+                .map(|value| string(value.as_ref()))
+                .collect::<Vec<_>>();
+            format!("({})", values.join(", "))
+        }
+    }
+}
+
+/// Text shown as is in Typst markup
+fn markup(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        if matches!(
+            c,
+            '\\' | '#' | '*' | '_' | '`' | '$' | '@' | '<' | '>' | '[' | ']' | '~' | '/'
+        ) {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+
+    // Lists, headings, and numbered lists are only marked at the start of a line
+    if escaped.starts_with(['=', '-', '+']) {
+        escaped.insert(0, '\\');
+    }
+    let digits = escaped.chars().take_while(char::is_ascii_digit).count();
+    if digits > 0 && escaped[digits..].starts_with('.') {
+        escaped.insert(digits, '\\');
+    }
+
+    escaped
+}
+
+fn copy_dir(from: &Path, to: &Path) -> io::Result<()> {
+    fs::create_dir_all(to)?;
+    for entry in fs::read_dir(from)? {
+        let entry = entry?;
+        let target = to.join(entry.file_name());
+        if entry.file_type()?.is_dir() {
+            copy_dir(&entry.path(), &target)?;
+        } else {
+            fs::copy(entry.path(), target)?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use quelle_core::prelude::*;
+
+    use super::*;
+    use crate::test_support::TestBundle;
+
+    fn bundle(novel: Novel) -> TestBundle {
+        TestBundle::new(novel)
+            .content(|_| {
+                Some(String::from(
+                    "<h1>One</h1><p>#1 costs $5 [sic]<sup><a href=\"#n1\">1</a></sup></p><p>- a dash<img src=\"1.png\"></p><p>2. Two <ruby>漢<rt>かん</rt></ruby></p><p id=\"n1\">= a [note]</p>",
+                ))
+            })
+            .asset(|_| Some((String::from("image/png"), vec![0])))
+    }
+
+    #[test]
+    fn should_escape_novel_into_document() {
+        let bundle = bundle(Novel {
+            title: String::from("The \"Novel\""),
+            authors: vec![String::from("Author")],
+            langs: vec![String::from("en-US")],
+            volumes: vec![Volume {
+                chapters: vec![Chapter {
+                    index: 0,
+                    title: String::from("One"),
+                    url: String::from("1"),
+                    updated_at: None,
+                    number: None,
+                    part: None,
+                    label: None,
+                }],
+                ..Default::default()
+            }],
+            ..Default::default()
+        });
+
+        let options = ImageOptions {
+            include_images: true,
+            ..Default::default()
+        };
+        let mut images = EmbeddedImages::new(&options);
+        let source = document_source(&bundle, None, &mut images, false, &[]).unwrap();
+        assert!(source.contains("  title: \"The \\\"Novel\\\"\",\n  authors: (\"Author\",),\n"));
+        assert!(source.contains("  lang: \"en\",\n  cover: none,\n"));
+        assert!(
+            source.ends_with("\n= One\n\n\\#1 costs \\$5 \\[sic\\]#footnote[\\= a \\[note\\]];\n\n\\- a dash\n\n#align(center, image(\"images/1.png\"))\n\n2\\. Two #ruby[漢][かん];\n")
+        );
+        assert_eq!(images.images.len(), 1);
+    }
+
+    #[test]
+    fn should_lay_out_by_language() {
+        let novel = |lang: &str| Novel {
+            langs: vec![String::from(lang)],
+            volumes: vec![Volume {
+                chapters: vec![Chapter {
+                    index: 0,
+                    title: String::from("One"),
+                    url: String::from("1"),
+                    updated_at: None,
+                    number: None,
+                    part: None,
+                    label: None,
+                }],
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+        let options = ImageOptions::default();
+
+        let mut images = EmbeddedImages::new(&options);
+        let source = document_source(&bundle(novel("ar")), None, &mut images, true, &[]).unwrap();
+        assert!(source.starts_with("#import \"template.typ\": book\n"));
+        assert!(source.contains("  dir: rtl,\n)\n"));
+
+        let fonts = [String::from("Noto Serif JP")];
+        let source =
+            document_source(&bundle(novel("ja")), None, &mut images, true, &fonts).unwrap();
+        assert!(source.starts_with("#import \"template.typ\": book, vertical\n"));
+        assert!(source.contains("  vertical: true,\n  fonts: (\"Noto Serif JP\",),\n)\n"));
+        assert!(source.ends_with(
+            "\n= One\n\n#vertical((\"#1 costs $5 [sic]※1\", \"- a dash\", \"2. Two 漢（かん）\", \"※1 = a [note]\"))\n"
+        ));
+    }
+}
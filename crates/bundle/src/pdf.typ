@@ -0,0 +1,166 @@
+// The layout of the PDF bundles of quelle.
+//
+// Write this file to a directory with `quelle template export <dir>`, change
+// it there, then lay out bundles with it using `quelle template use <dir>`.
+// Fonts and images in the directory can be used by their relative paths.
+//
+// The novel is passed to `book` as named arguments:
+//
+//   title        the title of the novel, with the part when the bundle is split
+//   authors      the authors, an array of strings
+//   description  the paragraphs of the description, an array of strings
+//   lang         the language code of the novel, or none
+//   cover        the path of the cover image, or none
+//   rights       the rights statement of the novel, or none
+//   notes        the notes included in the bundle, an array of strings
+//   url          the url the novel was downloaded from
+//   date         the day the bundle was made, as YYYY-MM-DD
+//   dir          rtl for novels written from right to left, only passed to
+//                such novels
+//   vertical     true when the chapters are set in vertical columns, only
+//                passed to such novels
+//   fonts        the families of the fonts given with `--font`, in the order
+//                they are preferred, only passed when there are any
+//
+// The body follows with a level 1 heading per chapter. When the novel has
+// several volumes, volumes are level 1 headings and their chapters level 2.
+// Footnotes and author's notes of chapters are set as footnotes, and ruby with
+// the `ruby` function of the document.
+//
+// Vertical novels are written with `quelle bundle --pdf-vertical`, which sets
+// the paragraphs of chapters with the `vertical` function of this file
+// instead. It is given the paragraphs of a run of text as an array of strings.
+
+#let margin = (x: 1.8cm, y: 2cm)
+
+// Characters replaced by their forms for vertical text
+#let vertical-forms = (
+  "、": "︑", "。": "︒", "，": "︐", "：": "︓", "；": "︔", "！": "︕", "？": "︖",
+  "「": "﹁", "」": "﹂", "『": "﹃", "』": "﹄", "（": "︵", "）": "︶",
+  "【": "︻", "】": "︼", "《": "︽", "》": "︾", "〈": "︿", "〉": "﹀",
+  "…": "︙", "‥": "︰",
+)
+
+// Characters turned sideways in vertical text, along with latin letters and digits
+#let sideways = ("ー", "—", "―", "〜", "～", "-", "=", "→", "←")
+
+#let vertical-glyph(c) = {
+  if c in vertical-forms {
+    vertical-forms.at(c)
+  } else if c in sideways or c.match(regex("^[A-Za-z0-9]$")) != none {
+    rotate(90deg, c)
+  } else {
+    c
+  }
+}
+
+// Sets the paragraphs in columns read from top to bottom and from right to
+// left, each paragraph starting in a new column indented by a space
+#let vertical(paragraphs) = context {
+  let cell = 1em.to-absolute()
+  let rows = calc.floor((page.height - 2 * margin.y) / cell) - 1
+  let columns = ()
+  for paragraph in paragraphs {
+    columns += (("　",) + paragraph.clusters()).chunks(rows)
+  }
+
+  // The columns are laid out as a line of boxes running from right to left
+  // that fills the page before wrapping onto the next one
+  set par(justify: false, first-line-indent: 0pt, leading: 0pt)
+  set text(dir: rtl)
+  block(width: 100%, for column in columns {
+    box(width: 1.75 * cell, height: rows * cell, align(center, stack(
+      dir: ttb,
+      ..column.map(c => box(width: cell, height: cell, align(center + horizon, vertical-glyph(c)))),
+    )))
+  })
+}
+
+#let book(
+  title: "",
+  authors: (),
+  description: (),
+  lang: none,
+  cover: none,
+  rights: none,
+  notes: (),
+  url: "",
+  date: "",
+  dir: auto,
+  vertical: false,
+  fonts: (),
+  body,
+) = {
+  set document(title: title, author: authors)
+  set text(
+    font: fonts + ("Libertinus Serif", "Linux Libertine", "Georgia"),
+    size: 11pt,
+    lang: if lang == none { "en" } else { lang },
+    dir: dir,
+  )
+  set par(justify: true, leading: 0.7em, first-line-indent: 1.2em)
+  set page(
+    paper: "a5",
+    margin: margin,
+    // Books read from right to left are bound on the right
+    binding: if vertical { right } else { auto },
+    header: context {
+      if counter(page).get().first() > 2 {
+        align(end, text(size: 8pt, fill: luma(100), title))
+      }
+    },
+    footer: context {
+      if counter(page).get().first() > 2 {
+        align(center, text(size: 9pt, counter(page).display()))
+      }
+    },
+  )
+
+  show heading: set text(weight: "regular")
+  show heading.where(level: 1): it => {
+    pagebreak(weak: true)
+    v(15%)
+    align(center, text(size: 18pt, it.body))
+    v(2em)
+  }
+  show heading.where(level: 2): it => {
+    pagebreak(weak: true)
+    align(center, text(size: 14pt, it.body))
+    v(1.5em)
+  }
+
+  if cover != none {
+    page(margin: 0pt, header: none, footer: none, image(cover, width: 100%, height: 100%, fit: "contain"))
+  }
+
+  page(header: none, footer: none, align(center + horizon, {
+    text(size: 24pt, title)
+    if authors.len() > 0 {
+      v(1.5em)
+      text(size: 13pt, authors.join(", "))
+    }
+    v(3em)
+    text(size: 9pt, fill: luma(100), link(url))
+  }))
+
+  if description.len() > 0 or rights != none or notes.len() > 0 {
+    for paragraph in description {
+      par(paragraph)
+    }
+    if rights != none {
+      v(1em)
+      text(size: 9pt, rights)
+    }
+    if notes.len() > 0 {
+      v(1em)
+      for paragraph in notes {
+        par(emph(paragraph))
+      }
+    }
+    pagebreak()
+  }
+
+  outline(title: "Contents", depth: 2)
+
+  body
+}
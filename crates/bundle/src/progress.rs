@@ -0,0 +1,200 @@
+use std::{
+    collections::HashMap,
+    fmt::Display,
+    path::Path,
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc,
+    },
+};
+
+use quelle_common::{ProgressEvent, TitleRules};
+use quelle_core::prelude::*;
+
+use crate::{
+    data::{Asset, Bundle},
+    split::Part,
+};
+
+/// Stops the bundles being written, from another thread such as the handler
+/// of ctrl-c or the cancel button of an interface
+///
+/// Clones cancel the same bundles.
+#[derive(Clone, Debug, Default)]
+pub struct CancellationToken(Arc<AtomicBool>);
+
+impl CancellationToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+
+    /// Fail with [`Cancelled`] once the bundle is cancelled
+    pub(crate) fn check(&self) -> Result<(), Cancelled> {
+        match self.is_cancelled() {
+            true => Err(Cancelled),
+            false => Ok(()),
+        }
+    }
+}
+
+/// The error of a bundle that was cancelled before it was written, which
+/// leaves the output incomplete
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Cancelled;
+
+impl Display for Cancelled {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "the bundle was cancelled")
+    }
+}
+
+impl std::error::Error for Cancelled {}
+
+/// A bundle that reports every chapter read by the format being written, and
+/// stops reading chapters once it is cancelled
+///
+/// Formats read chapters on several threads, so chapters are numbered in the
+/// order they are read rather than their order in the novel.
+pub(crate) struct ProgressBundle<'a, B, P> {
+    inner: &'a B,
+    progress: P,
+    cancel: &'a CancellationToken,
+    titles: HashMap<&'a str, &'a str>,
+    read: AtomicUsize,
+}
+
+impl<'a, B: Bundle, P: Fn(ProgressEvent) + Sync> ProgressBundle<'a, B, P> {
+    pub fn new(inner: &'a B, progress: P, cancel: &'a CancellationToken) -> Self {
+        let titles = inner
+            .chapters()
+            .into_iter()
+            .map(|(_, chapter)| (chapter.url.as_str(), chapter.title.as_str()))
+            .collect();
+
+        Self {
+            inner,
+            progress,
+            cancel,
+            titles,
+            read: AtomicUsize::new(0),
+        }
+    }
+}
+
+impl<'a, B: Bundle, P: Fn(ProgressEvent) + Sync> Bundle for ProgressBundle<'a, B, P> {
+    fn meta(&self) -> Option<&Meta> {
+        self.inner.meta()
+    }
+
+    fn novel(&self) -> &Novel {
+        self.inner.novel()
+    }
+
+    fn cover_path(&self) -> Option<&Path> {
+        self.inner.cover_path()
+    }
+
+    fn cover_content_type(&self) -> Option<&str> {
+        self.inner.cover_content_type()
+    }
+
+    fn cover_content(&self) -> Result<Option<Vec<u8>>, Box<dyn std::error::Error>> {
+        self.inner.cover_content()
+    }
+
+    fn chapter_content(&self, url: &str) -> Result<Option<String>, Box<dyn std::error::Error>> {
+        self.cancel.check()?;
+        let content = self.inner.chapter_content(url)?;
+
+        let number = self.read.fetch_add(1, Ordering::Relaxed) + 1;
+        (self.progress)(ProgressEvent::ChapterBundled {
+            number,
+            total: self.titles.len(),
+            title: self.titles.get(url).copied().unwrap_or(url).to_string(),
+        });
+        Ok(content)
+    }
+
+    fn asset(&self, url: &str) -> Result<Option<Asset>, Box<dyn std::error::Error>> {
+        self.inner.asset(url)
+    }
+
+    fn notes(&self) -> Option<&str> {
+        self.inner.notes()
+    }
+
+    fn rights(&self) -> Option<&str> {
+        self.inner.rights()
+    }
+
+    fn fetched_at(&self) -> Option<chrono::DateTime<chrono::Utc>> {
+        self.inner.fetched_at()
+    }
+
+    fn title_rules(&self) -> Option<&TitleRules> {
+        self.inner.title_rules()
+    }
+
+    fn part(&self) -> Option<&Part> {
+        self.inner.part()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use super::*;
+    use crate::test_support::TestBundle;
+
+    #[test]
+    fn should_report_chapters_until_cancelled() {
+        let chapter = |url: &str, title: &str| Chapter {
+            index: 0,
+            title: title.to_string(),
+            url: url.to_string(),
+            updated_at: None,
+            number: None,
+            part: None,
+            label: None,
+        };
+        let inner = TestBundle::new(Novel {
+            volumes: vec![Volume {
+                chapters: vec![chapter("1", "One"), chapter("2", "Two")],
+                ..Default::default()
+            }],
+            ..Default::default()
+        })
+        .content(|url| Some(format!("<p>{url}</p>")));
+
+        let events = Mutex::new(vec![]);
+        let cancel = CancellationToken::new();
+        let bundle =
+            ProgressBundle::new(&inner, |event| events.lock().unwrap().push(event), &cancel);
+
+        assert_eq!(
+            bundle.chapter_content("2").unwrap().as_deref(),
+            Some("<p>2</p>")
+        );
+        cancel.clone().cancel();
+        let error = bundle.chapter_content("1").unwrap_err();
+        assert!(error.is::<Cancelled>());
+
+        assert_eq!(
+            events.into_inner().unwrap(),
+            [ProgressEvent::ChapterBundled {
+                number: 1,
+                total: 2,
+                title: String::from("Two"),
+            }]
+        );
+    }
+}
@@ -0,0 +1,107 @@
+use std::{
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Mutex,
+    },
+    thread,
+};
+
+/// The number of items rendered ahead per thread
+const WINDOW_PER_THREAD: usize = 4;
+
+/// Render the items on as many threads as there are cores, passing the results
+/// to `consume` in the order of the items
+///
+/// Items are rendered a window at a time so that only the results of the
+/// window are held in memory.
+pub(crate) fn render_in_order<I, T, R, C>(
+    items: &[I],
+    render: R,
+    mut consume: C,
+) -> Result<(), Box<dyn std::error::Error>>
+where
+    I: Sync,
+    T: Send,
+    R: Fn(&I) -> Result<T, String> + Sync,
+    C: FnMut(&I, T) -> Result<(), Box<dyn std::error::Error>>,
+{
+    let threads = thread::available_parallelism()
+        .map(usize::from)
+        .unwrap_or(1);
+
+    for window in items.chunks(threads * WINDOW_PER_THREAD) {
+        let results = render_window(window, threads, &render);
+        for (item, result) in window.iter().zip(results) {
+            consume(item, result?)?;
+        }
+    }
+
+    Ok(())
+}
+
+fn render_window<I, T, R>(window: &[I], threads: usize, render: &R) -> Vec<Result<T, String>>
+where
+    I: Sync,
+    T: Send,
+    R: Fn(&I) -> Result<T, String> + Sync,
+{
+    if threads <= 1 || window.len() <= 1 {
+        return window.iter().map(render).collect();
+    }
+
+    let next = AtomicUsize::new(0);
+    let results = Mutex::new(window.iter().map(|_| None).collect::<Vec<_>>());
+
+    thread::scope(|scope| {
+        for _ in 0..threads.min(window.len()) {
+            scope.spawn(|| loop {
+                let index = next.fetch_add(1, Ordering::Relaxed);
+                let Some(item) = window.get(index) else {
+                    break;
+                };
+
+                let result = render(item);
+                results.lock().unwrap()[index] = Some(result);
+            });
+        }
+    });
+
+    results
+        .into_inner()
+        .unwrap()
+        .into_iter()
+        .map(|result| result.expect("every item is rendered"))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_consume_results_in_order() {
+        let items = (0..100).collect::<Vec<usize>>();
+        let mut consumed = vec![];
+        render_in_order(
+            &items,
+            |item| Ok(item * 2),
+            |item, result| {
+                assert_eq!(result, item * 2);
+                consumed.push(result);
+                Ok(())
+            },
+        )
+        .unwrap();
+        assert_eq!(consumed, (0..100).map(|item| item * 2).collect::<Vec<_>>());
+
+        let failed = render_in_order(
+            &items,
+            |item| match item {
+                50 => Err(String::from("failed")),
+                item => Ok(*item),
+            },
+            |_, _| Ok(()),
+        );
+        assert_eq!(failed.unwrap_err().to_string(), "failed");
+    }
+}
@@ -0,0 +1,542 @@
+use std::{collections::BTreeSet, fmt::Display, path::Path, str::FromStr};
+
+use quelle_common::TitleRules;
+use quelle_core::prelude::*;
+use regex::Regex;
+
+use crate::{
+    data::{Asset, Bundle},
+    images::{attribute, is_image, tag_end},
+    split::Part,
+    text::decode_entities,
+};
+
+/// Sentences sources add to chapters to point readers to their site, removed
+/// along with the patterns of the library
+const WATERMARKS: [&str; 1] = [
+    r"(?i)[^.!?]*\b(?:read|find|visit|hosted|published|stolen|available)\b[^.!?]*?\b[a-z0-9-]+(?:\.|\s+dot\s+)(?:com|net|org|co)\b[^.!?]*[.!?]?",
+];
+
+/// The longest base text and reading of the ruby notation, so that a stray bar
+/// does not turn a whole paragraph into ruby
+const MAX_RUBY: usize = 20;
+
+/// Parts of image sources that tell tracking pixels apart from chapter images
+const TRACKERS: [&str; 5] = ["/pixel", "/track", "beacon", "analytics", "doubleclick"];
+
+/// A cleanup step run on the chapter content before it is bundled
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum SanitizeStep {
+    /// Script elements and event handler attributes
+    Scripts,
+    /// Images of at most a pixel or from known trackers
+    TrackingPixels,
+    /// Sentences matching the watermark patterns
+    Watermarks,
+    /// Paragraphs without text or images, such as those left by other steps
+    EmptyParagraphs,
+    /// Style attributes and elements that override the theme of the bundle
+    InlineStyles,
+}
+
+impl SanitizeStep {
+    /// Every step in the order it runs
+    pub const ALL: [SanitizeStep; 5] = [
+        SanitizeStep::Scripts,
+        SanitizeStep::TrackingPixels,
+        SanitizeStep::Watermarks,
+        SanitizeStep::EmptyParagraphs,
+        SanitizeStep::InlineStyles,
+    ];
+}
+
+impl FromStr for SanitizeStep {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().replace('_', "-").as_str() {
+            "scripts" => Ok(SanitizeStep::Scripts),
+            "tracking-pixels" => Ok(SanitizeStep::TrackingPixels),
+            "watermarks" => Ok(SanitizeStep::Watermarks),
+            "empty-paragraphs" => Ok(SanitizeStep::EmptyParagraphs),
+            "inline-styles" => Ok(SanitizeStep::InlineStyles),
+            _ => Err(format!("unknown sanitize step '{s}'")),
+        }
+    }
+}
+
+impl Display for SanitizeStep {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            SanitizeStep::Scripts => "scripts",
+            SanitizeStep::TrackingPixels => "tracking-pixels",
+            SanitizeStep::Watermarks => "watermarks",
+            SanitizeStep::EmptyParagraphs => "empty-paragraphs",
+            SanitizeStep::InlineStyles => "inline-styles",
+        };
+        write!(f, "{name}")
+    }
+}
+
+/// How the chapter content is cleaned up before it is bundled
+#[derive(Clone, Debug)]
+pub struct SanitizeOptions {
+    /// The steps that are not run
+    pub disabled: BTreeSet<SanitizeStep>,
+    /// The patterns of the watermark sentences that are removed
+    pub watermarks: Vec<Regex>,
+    /// Turn the reading notation of Japanese web novels, `｜漢字《かんじ》` or
+    /// `漢字《かんじ》` after kanji, into ruby markup
+    pub ruby_notation: bool,
+}
+
+impl Default for SanitizeOptions {
+    fn default() -> Self {
+        Self {
+            disabled: Default::default(),
+            ruby_notation: false,
+            watermarks: WATERMARKS
+                .iter()
+                .map(|pattern| Regex::new(pattern).expect("default watermarks are valid"))
+                .collect(),
+        }
+    }
+}
+
+impl SanitizeOptions {
+    /// Every step but the disabled ones, removing the watermarks of the
+    /// patterns along with the default ones
+    pub fn new<D, W>(disabled: D, watermarks: W) -> Result<Self, String>
+    where
+        D: IntoIterator,
+        D::Item: AsRef<str>,
+        W: IntoIterator,
+        W::Item: AsRef<str>,
+    {
+        let mut options = Self {
+            disabled: disabled
+                .into_iter()
+                .map(|step| step.as_ref().parse())
+                .collect::<Result<_, _>>()?,
+            ..Default::default()
+        };
+
+        for pattern in watermarks {
+            let pattern = pattern.as_ref();
+            let regex = Regex::new(pattern)
+                .map_err(|e| format!("invalid watermark pattern '{pattern}': {e}"))?;
+            options.watermarks.push(regex);
+        }
+        Ok(options)
+    }
+
+    /// Whether the step is run
+    pub fn is_enabled(&self, step: SanitizeStep) -> bool {
+        !self.disabled.contains(&step)
+    }
+}
+
+/// Remove the scripts, tracking pixels, watermarks, empty paragraphs and inline
+/// styles of the html content, as enabled by the options
+pub fn sanitize_html(html: &str, options: &SanitizeOptions) -> String {
+    let mut removed_elements = vec![];
+    if options.is_enabled(SanitizeStep::Scripts) {
+        removed_elements.extend(["script", "noscript"]);
+    }
+    if options.is_enabled(SanitizeStep::InlineStyles) {
+        removed_elements.push("style");
+    }
+    let empty_paragraphs = options.is_enabled(SanitizeStep::EmptyParagraphs);
+
+    let mut sanitized = String::with_capacity(html.len());
+    // The element whose content is being removed
+    let mut skipped: Option<String> = None;
+    // Where the current paragraph starts in the output and whether it has
+    // text or images so far
+    let mut paragraph: Option<(usize, bool)> = None;
+    let mut rest = html;
+
+    loop {
+        let start = rest.find('<').unwrap_or(rest.len());
+        if skipped.is_none() {
+            let mut text = remove_watermarks(&rest[..start], options);
+            if options.ruby_notation {
+                text = ruby_notation(&text);
+            }
+            if let Some((_, content)) = &mut paragraph {
+                *content |= !decode_entities(&text).trim().is_empty();
+            }
+            sanitized.push_str(&text);
+        }
+        if start == rest.len() {
+            break;
+        }
+
+        let inner = &rest[start + 1..];
+        let end = tag_end(inner);
+        let tag = &inner[..end];
+        let closed = end < inner.len();
+        rest = inner.get(end + 1..).unwrap_or_default();
+
+        let name = tag
+            .trim_start_matches('/')
+            .split(|c: char| c.is_whitespace() || c == '/')
+            .next()
+            .unwrap_or_default()
+            .to_ascii_lowercase();
+        let closing = tag.starts_with('/');
+
+        if let Some(element) = &skipped {
+            if closing && name == *element {
+                skipped = None;
+            }
+            continue;
+        }
+        if !closing && removed_elements.contains(&name.as_str()) {
+            if !tag.trim_end().ends_with('/') {
+                skipped = Some(name);
+            }
+            continue;
+        }
+        if options.is_enabled(SanitizeStep::TrackingPixels) && is_tracking_pixel(tag) {
+            continue;
+        }
+
+        if empty_paragraphs && name == "p" {
+            match (closing, paragraph.take()) {
+                (false, _) if tag.trim_end().ends_with('/') => continue,
+                (false, _) => paragraph = Some((sanitized.len(), false)),
+                (true, Some((position, false))) => {
+                    sanitized.truncate(position);
+                    continue;
+                }
+                (true, _) => {}
+            }
+        } else if let Some((_, content)) = &mut paragraph {
+            *content |= is_image(tag);
+        }
+
+        sanitized.push('<');
+        sanitized.push_str(&remove_attributes(tag, options));
+        if closed {
+            sanitized.push('>');
+        }
+    }
+
+    sanitized
+}
+
+fn remove_watermarks(text: &str, options: &SanitizeOptions) -> String {
+    if !options.is_enabled(SanitizeStep::Watermarks) {
+        return text.to_string();
+    }
+
+    let mut text = text.to_string();
+    for watermark in &options.watermarks {
+        text = watermark.replace_all(&text, "").into_owned();
+    }
+    text
+}
+
+/// The text with its ruby notation as ruby elements, the parentheses of `rp`
+/// showing the reading in readers without support for ruby
+fn ruby_notation(text: &str) -> String {
+    if !text.contains('《') {
+        return text.to_string();
+    }
+
+    let mut annotated = String::with_capacity(text.len());
+    // Where the bar that starts the base text is in the annotated text
+    let mut bar: Option<usize> = None;
+    let mut chars = text.char_indices().peekable();
+
+    while let Some((index, c)) = chars.next() {
+        match c {
+            // A bar before the brackets keeps them as text
+            '｜' | '|' if chars.peek().is_some_and(|(_, next)| *next == '《') => {
+                let (_, bracket) = chars.next().expect("peeked");
+                annotated.push(bracket);
+                bar = None;
+            }
+            '｜' | '|' => {
+                bar = Some(annotated.len());
+                annotated.push(c);
+            }
+            '《' => {
+                let reading = text[index + c.len_utf8()..]
+                    .split_once('》')
+                    .map(|(reading, _)| reading)
+                    .filter(|reading| {
+                        !reading.trim().is_empty() && reading.chars().count() <= MAX_RUBY
+                    });
+                let start = match bar.take() {
+                    Some(bar) => Some((
+                        bar,
+                        bar + annotated[bar..].chars().next().map_or(0, char::len_utf8),
+                    )),
+                    None => annotated
+                        .char_indices()
+                        .rev()
+                        .take_while(|(_, c)| is_kanji(*c))
+                        .last()
+                        .map(|(start, _)| (start, start)),
+                };
+                let base = start
+                    .map(|(_, base)| &annotated[base..])
+                    .filter(|base| !base.trim().is_empty() && base.chars().count() <= MAX_RUBY);
+
+                match (start, base, reading) {
+                    (Some((start, _)), Some(base), Some(reading)) => {
+                        let ruby =
+                            format!("<ruby>{base}<rp>(</rp><rt>{reading}</rt><rp>)</rp></ruby>");
+                        annotated.truncate(start);
+                        annotated.push_str(&ruby);
+                        // Skip the reading and the closing bracket
+                        for _ in 0..reading.chars().count() + 1 {
+                            chars.next();
+                        }
+                    }
+                    _ => annotated.push(c),
+                }
+            }
+            _ => annotated.push(c),
+        }
+    }
+
+    annotated
+}
+
+fn is_kanji(c: char) -> bool {
+    matches!(c, '\u{3400}'..='\u{4DBF}' | '\u{4E00}'..='\u{9FFF}' | '\u{F900}'..='\u{FAFF}' | '々' | '〆' | '〇' | 'ヶ')
+}
+
+fn is_tracking_pixel(tag: &str) -> bool {
+    if !is_image(tag) {
+        return false;
+    }
+
+    let tiny = |name| {
+        attribute(tag, name)
+            .and_then(|value| value.trim().trim_end_matches("px").parse::<u32>().ok())
+            .is_some_and(|value| value <= 1)
+    };
+    let source = attribute(tag, "src")
+        .unwrap_or_default()
+        .to_ascii_lowercase();
+    tiny("width") || tiny("height") || TRACKERS.iter().any(|tracker| source.contains(tracker))
+}
+
+/// The inside of the tag without the event handler and style attributes, as
+/// enabled by the options
+fn remove_attributes(tag: &str, options: &SanitizeOptions) -> String {
+    let scripts = options.is_enabled(SanitizeStep::Scripts);
+    let styles = options.is_enabled(SanitizeStep::InlineStyles);
+    if tag.starts_with(['/', '!', '?']) || !(scripts || styles) {
+        return tag.to_string();
+    }
+
+    let (name, attributes, self_closing) = split_tag(tag);
+    let kept = attributes
+        .iter()
+        .filter(|attribute| {
+            let key = attribute
+                .split(|c: char| c == '=' || c.is_whitespace())
+                .next()
+                .unwrap_or_default()
+                .to_ascii_lowercase();
+            !(scripts && key.starts_with("on") || styles && key == "style")
+        })
+        .collect::<Vec<_>>();
+    if kept.len() == attributes.len() {
+        return tag.to_string();
+    }
+
+    let mut cleaned = name.to_string();
+    for attribute in kept {
+        cleaned.push(' ');
+        cleaned.push_str(attribute);
+    }
+    if self_closing {
+        cleaned.push('/');
+    }
+    cleaned
+}
+
+/// The name, attributes as written and whether the element is self closing of
+/// the inside of a start tag
+fn split_tag(tag: &str) -> (&str, Vec<&str>, bool) {
+    let trimmed = tag.trim_end();
+    let (tag, self_closing) = match trimmed.strip_suffix('/') {
+        Some(tag) => (tag, true),
+        None => (trimmed, false),
+    };
+
+    let name_end = tag.find(char::is_whitespace).unwrap_or(tag.len());
+    let name = &tag[..name_end];
+    let bytes = tag.as_bytes();
+    let mut attributes = vec![];
+    let mut index = name_end;
+
+    let skip_whitespace = |mut index: usize| {
+        while index < bytes.len() && bytes[index].is_ascii_whitespace() {
+            index += 1;
+        }
+        index
+    };
+
+    loop {
+        index = skip_whitespace(index);
+        if index >= bytes.len() {
+            break;
+        }
+
+        let start = index;
+        while index < bytes.len() && !bytes[index].is_ascii_whitespace() && bytes[index] != b'=' {
+            index += 1;
+        }
+        let mut end = index;
+
+        let value = skip_whitespace(index);
+        if bytes.get(value) == Some(&b'=') {
+            index = skip_whitespace(value + 1);
+            match bytes.get(index) {
+                Some(&quote @ (b'"' | b'\'')) => {
+                    index = tag[index + 1..]
+                        .find(quote as char)
+                        .map(|close| index + close + 2)
+                        .unwrap_or(bytes.len());
+                }
+                _ => {
+                    while index < bytes.len() && !bytes[index].is_ascii_whitespace() {
+                        index += 1;
+                    }
+                }
+            }
+            end = index;
+        }
+
+        attributes.push(&tag[start..end]);
+    }
+
+    (name, attributes, self_closing)
+}
+
+/// A bundle whose chapter content is sanitized as it is read, so that every
+/// format is written from the cleaned up content
+pub struct SanitizedBundle<'a, B> {
+    inner: &'a B,
+    options: &'a SanitizeOptions,
+}
+
+impl<'a, B: Bundle> SanitizedBundle<'a, B> {
+    pub fn new(inner: &'a B, options: &'a SanitizeOptions) -> Self {
+        Self { inner, options }
+    }
+}
+
+impl<'a, B: Bundle> Bundle for SanitizedBundle<'a, B> {
+    fn meta(&self) -> Option<&Meta> {
+        self.inner.meta()
+    }
+
+    fn novel(&self) -> &Novel {
+        self.inner.novel()
+    }
+
+    fn cover_path(&self) -> Option<&Path> {
+        self.inner.cover_path()
+    }
+
+    fn cover_content_type(&self) -> Option<&str> {
+        self.inner.cover_content_type()
+    }
+
+    fn cover_content(&self) -> Result<Option<Vec<u8>>, Box<dyn std::error::Error>> {
+        self.inner.cover_content()
+    }
+
+    fn chapter_content(&self, url: &str) -> Result<Option<String>, Box<dyn std::error::Error>> {
+        let content = self.inner.chapter_content(url)?;
+        Ok(content.map(|content| sanitize_html(&content, self.options)))
+    }
+
+    fn asset(&self, url: &str) -> Result<Option<Asset>, Box<dyn std::error::Error>> {
+        self.inner.asset(url)
+    }
+
+    fn notes(&self) -> Option<&str> {
+        self.inner.notes()
+    }
+
+    fn rights(&self) -> Option<&str> {
+        self.inner.rights()
+    }
+
+    fn fetched_at(&self) -> Option<chrono::DateTime<chrono::Utc>> {
+        self.inner.fetched_at()
+    }
+
+    fn title_rules(&self) -> Option<&TitleRules> {
+        self.inner.title_rules()
+    }
+
+    fn part(&self) -> Option<&Part> {
+        self.inner.part()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_sanitize_chapter_content() {
+        let html = concat!(
+            r#"<script type="text/javascript">track("a > b");</script>"#,
+            r#"<p style="color: red" onclick="go()" class="text">Tom &amp; Jerry.</p>"#,
+            r#"<p>The end. Read the latest chapters at novelsite.com!</p>"#,
+            r#"<p>&nbsp;<br/></p>"#,
+            r#"<p><img src="https://example.com/a.png" alt="A"/></p>"#,
+            r#"<img src="https://stats.example.com/p.gif" width="1" height="1"/>"#,
+            r#"<style>p { color: red }</style>"#,
+        );
+
+        assert_eq!(
+            sanitize_html(html, &SanitizeOptions::default()),
+            concat!(
+                r#"<p class="text">Tom &amp; Jerry.</p>"#,
+                r#"<p>The end.</p>"#,
+                r#"<p><img src="https://example.com/a.png" alt="A"/></p>"#,
+            )
+        );
+
+        let options =
+            SanitizeOptions::new(["inline-styles", "empty-paragraphs"], [r"Tom &amp; "]).unwrap();
+        let sanitized = sanitize_html(html, &options);
+        assert!(sanitized.contains(r#"<p style="color: red" class="text">Jerry.</p>"#));
+        assert!(sanitized.contains("<p>The end.</p>"));
+        assert!(sanitized.contains("<p>&nbsp;<br/></p>"));
+        assert!(sanitized.ends_with("<style>p { color: red }</style>"));
+
+        let options = SanitizeOptions::new(["watermarks"], [""; 0]).unwrap();
+        assert!(sanitize_html(html, &options).contains("novelsite.com"));
+
+        assert!(SanitizeOptions::new(["links"], [""; 0]).is_err());
+
+        let options = SanitizeOptions {
+            ruby_notation: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            sanitize_html(
+                "<p>｜異世界《いせかい》の魔法《まほう》と|《括弧》</p>",
+                &options
+            ),
+            concat!(
+                "<p><ruby>異世界<rp>(</rp><rt>いせかい</rt><rp>)</rp></ruby>の",
+                "<ruby>魔法<rp>(</rp><rt>まほう</rt><rp>)</rp></ruby>と《括弧》</p>"
+            )
+        );
+        assert!(SanitizeOptions::new([""; 0], ["("]).is_err());
+    }
+}
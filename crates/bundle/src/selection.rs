@@ -0,0 +1,124 @@
+use quelle_common::ValueRange;
+use quelle_core::prelude::*;
+
+/// The chapters of the novel to bundle, every chapter when empty
+///
+/// Chapters are kept when they match every part of the selection.
+#[derive(Clone, Debug, Default)]
+pub struct ChapterSelection {
+    /// Ranges of chapter positions in the novel, starting at 1
+    pub chapters: Vec<ValueRange<usize>>,
+    /// Ranges of volume numbers, starting at 1
+    pub volumes: Vec<ValueRange<usize>>,
+    /// Only the chapters after the chapter with this url, every chapter when
+    /// the novel has no such chapter
+    pub after: Option<String>,
+}
+
+impl ChapterSelection {
+    pub fn is_empty(&self) -> bool {
+        self.chapters.is_empty() && self.volumes.is_empty() && self.after.is_none()
+    }
+
+    /// Remove the chapters that are not selected from the novel, and the volumes
+    /// left empty, returning the number of chapters removed
+    pub fn apply(&self, novel: &mut Novel) -> usize {
+        let matches = |ranges: &[ValueRange<usize>], value: usize| {
+            ranges.is_empty() || ranges.iter().any(|range| range.contains(&value))
+        };
+
+        let after = self.after.as_ref().and_then(|url| {
+            novel
+                .volumes
+                .iter()
+                .flat_map(|volume| &volume.chapters)
+                .position(|chapter| &chapter.url == url)
+        });
+
+        let mut removed = 0;
+        let mut position = 0;
+        for (index, volume) in novel.volumes.iter_mut().enumerate() {
+            let volume_selected = matches(&self.volumes, index + 1);
+            let before = volume.chapters.len();
+            volume.chapters.retain(|_| {
+                position += 1;
+                volume_selected
+                    && matches(&self.chapters, position)
+                    && after.is_none_or(|after| position > after + 1)
+            });
+            removed += before - volume.chapters.len();
+        }
+
+        novel.volumes.retain(|volume| !volume.chapters.is_empty());
+        removed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn novel() -> Novel {
+        let chapter = |index: i32| Chapter {
+            index,
+            title: format!("Chapter {index}"),
+            url: index.to_string(),
+            updated_at: None,
+            number: None,
+            part: None,
+            label: None,
+        };
+
+        Novel {
+            volumes: vec![
+                Volume {
+                    chapters: (1..=3).map(chapter).collect(),
+                    ..Default::default()
+                },
+                Volume {
+                    index: 1,
+                    chapters: (4..=6).map(chapter).collect(),
+                    ..Default::default()
+                },
+            ],
+            ..Default::default()
+        }
+    }
+
+    fn urls(novel: &Novel) -> Vec<&str> {
+        novel
+            .volumes
+            .iter()
+            .flat_map(|volume| &volume.chapters)
+            .map(|chapter| chapter.url.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn should_keep_selected_chapters() {
+        let mut selected = novel();
+        let selection = ChapterSelection {
+            chapters: vec!["2..4".parse().unwrap(), "6".parse().unwrap()],
+            ..Default::default()
+        };
+        assert_eq!(selection.apply(&mut selected), 2);
+        assert_eq!(urls(&selected), ["2", "3", "4", "6"]);
+
+        let mut selected = novel();
+        let selection = ChapterSelection {
+            volumes: vec!["2".parse().unwrap()],
+            after: Some(String::from("4")),
+            ..Default::default()
+        };
+        selection.apply(&mut selected);
+        assert_eq!(urls(&selected), ["5", "6"]);
+        assert_eq!(selected.volumes.len(), 1);
+
+        let mut selected = novel();
+        let selection = ChapterSelection {
+            after: Some(String::from("missing")),
+            ..Default::default()
+        };
+        assert_eq!(selection.apply(&mut selected), 0);
+    }
+}
@@ -0,0 +1,266 @@
+use std::{fs, path::Path};
+
+use log::{info, warn};
+
+use crate::{
+    data::{cover_image, image_extension, volume_title, Bundle, BundleVolume},
+    text::escape,
+};
+
+/// The stylesheet shared by the pages of the site
+const STYLE: &str = r#"body {
+  margin: 0 auto;
+  max-width: 42em;
+  padding: 1em 1.5em 3em;
+  background: #1b1c1f;
+  color: #d8d6d0;
+  font-family: Georgia, "Times New Roman", serif;
+  font-size: 1.1em;
+  line-height: 1.7;
+}
+a { color: #8fb4e8; text-decoration: none; }
+a:hover { text-decoration: underline; }
+h1, h2 { line-height: 1.3; color: #ecebe7; }
+img.cover { display: block; max-width: 16em; margin: 1em auto; }
+ol.chapters { padding-left: 2em; }
+ul.novels { list-style: none; padding: 0; }
+ul.novels li { margin: 0.6em 0; }
+.authors { color: #a09e98; }
+nav {
+  display: flex;
+  justify-content: space-between;
+  margin: 1.5em 0;
+  font-family: sans-serif;
+  font-size: 0.9em;
+}
+nav .disabled { visibility: hidden; }
+"#;
+
+/// A novel listed on the index of a library site, see [`write_library_index`]
+#[derive(Clone, Debug)]
+pub struct SiteEntry {
+    pub title: String,
+    pub authors: Vec<String>,
+    /// The directory of the novel site relative to the library site
+    pub path: String,
+}
+
+/// Render the novel as a static website in the directory, with an index page
+/// listing the chapters and a page per chapter linked to its neighbours
+pub fn bundle_site<B: Bundle>(bundle: &B, dir: &Path) -> Result<(), Box<dyn std::error::Error>> {
+    let novel = bundle.novel();
+    fs::create_dir_all(dir.join("chapters"))?;
+    fs::write(dir.join("style.css"), STYLE)?;
+
+    let cover = write_cover(bundle, dir)?;
+
+    let mut body = format!("<h1>{}</h1>\n", escape(&novel.title));
+    if !novel.authors.is_empty() {
+        body += &format!(
+            "<p class=\"authors\">by {}</p>\n",
+            escape(&novel.authors.join(", "))
+        );
+    }
+    if let Some(cover) = cover {
+        body += &format!("<img class=\"cover\" src=\"{cover}\" alt=\"Cover\">\n");
+    }
+    for paragraph in &novel.description {
+        body += &format!("<p>{}</p>\n", escape(paragraph));
+    }
+    if let Some(rights) = bundle.rights() {
+        body += &format!("<p><small>{}</small></p>\n", escape(rights));
+    }
+    if let Some(notes) = bundle.notes() {
+        body += "<h2>Notes</h2>\n";
+        for paragraph in notes.split("\n\n").map(str::trim) {
+            body += &format!("<p>{}</p>\n", escape(paragraph));
+        }
+    }
+
+    let chapters = bundle.chapters();
+    let titles = chapters
+        .iter()
+        .map(|(position, chapter)| {
+            bundle
+                .chapter_title(chapter, *position)
+                .unwrap_or_else(|| chapter.title.clone())
+        })
+        .collect::<Vec<_>>();
+
+    // Chapters are listed under their volume when the novel has several
+    let structured = novel.volumes.len() > 1;
+    let mut index = 0;
+    for BundleVolume {
+        number,
+        volume,
+        chapters: selected,
+    } in bundle.volumes()
+    {
+        if structured {
+            body += &format!("<h2>{}</h2>\n", escape(&volume_title(volume, number)));
+        }
+        body += "<ol class=\"chapters\">\n";
+        for (position, _) in selected {
+            body += &format!(
+                "<li value=\"{position}\"><a href=\"chapters/{position}.html\">{}</a></li>\n",
+                escape(&titles[index])
+            );
+            index += 1;
+        }
+        body += "</ol>\n";
+    }
+
+    fs::write(
+        dir.join("index.html"),
+        page(&novel.title, "style.css", &body),
+    )?;
+    info!("Written index page");
+
+    for (index, (position, chapter)) in chapters.iter().enumerate() {
+        let title = &titles[index];
+        let nav = navigation(
+            index.checked_sub(1).map(|index| chapters[index].0),
+            chapters.get(index + 1).map(|(position, _)| *position),
+        );
+
+        let content = match bundle.chapter_content(&chapter.url)? {
+            Some(content) => content,
+            None => {
+                warn!("Using placeholder content for '{}'.", chapter.title);
+                String::from("<p>No downloaded content</p>")
+            }
+        };
+
+        let body = format!("{nav}<h1>{}</h1>\n{content}\n{nav}", escape(title));
+        let path = dir.join(format!("chapters/{position}.html"));
+        fs::write(path, page(title, "../style.css", &body))?;
+
+        info!("Written '{}'.", chapter.title);
+    }
+
+    info!("Site writing complete.");
+    Ok(())
+}
+
+/// Write an index page linking to the sites of the novels in the library directory
+pub fn write_library_index(dir: &Path, entries: &[SiteEntry]) -> std::io::Result<()> {
+    fs::create_dir_all(dir)?;
+    fs::write(dir.join("style.css"), STYLE)?;
+
+    let mut body = String::from("<h1>Library</h1>\n<ul class=\"novels\">\n");
+    for entry in entries {
+        body += &format!(
+            "<li><a href=\"{}/index.html\">{}</a>",
+            escape(&entry.path),
+            escape(&entry.title)
+        );
+        if !entry.authors.is_empty() {
+            body += &format!(
+                " <span class=\"authors\">by {}</span>",
+                escape(&entry.authors.join(", "))
+            );
+        }
+        body += "</li>\n";
+    }
+    body += "</ul>\n";
+
+    fs::write(dir.join("index.html"), page("Library", "style.css", &body))
+}
+
+fn page(title: &str, stylesheet: &str, body: &str) -> String {
+    format!(
+        r#"<!DOCTYPE html>
+<html>
+<head>
+<meta charset="utf-8">
+<meta name="viewport" content="width=device-width, initial-scale=1">
+<title>{}</title>
+<link rel="stylesheet" href="{stylesheet}">
+</head>
+<body>
+{body}</body>
+</html>
+"#,
+        escape(title)
+    )
+}
+
+/// Links to the previous and next chapters by position, and back to the index
+fn navigation(previous: Option<usize>, next: Option<usize>) -> String {
+    let link = |position: Option<usize>, text: &str| match position {
+        Some(position) => format!("<a href=\"{position}.html\">{text}</a>"),
+        None => format!("<span class=\"disabled\">{text}</span>"),
+    };
+
+    format!(
+        "<nav>{}<a href=\"../index.html\">Index</a>{}</nav>\n",
+        link(previous, "&larr; Previous"),
+        link(next, "Next &rarr;")
+    )
+}
+
+/// Copy the cover into the site, returning its path relative to the site
+fn write_cover<B: Bundle>(
+    bundle: &B,
+    dir: &Path,
+) -> Result<Option<String>, Box<dyn std::error::Error>> {
+    let Some((content_type, content)) = cover_image(bundle)? else {
+        return Ok(None);
+    };
+
+    let name = format!("cover.{}", image_extension(&content_type));
+    fs::write(dir.join(&name), content)?;
+
+    info!("Written cover");
+    Ok(Some(name))
+}
+
+#[cfg(test)]
+mod tests {
+    use quelle_core::prelude::*;
+
+    use super::*;
+    use crate::test_support::TestBundle;
+
+    #[test]
+    fn should_write_linked_chapter_pages() {
+        let dir = tempfile::tempdir().unwrap();
+        let chapter = |url: &str| Chapter {
+            index: 0,
+            title: format!("Chapter {url}"),
+            url: url.to_string(),
+            updated_at: None,
+            number: None,
+            part: None,
+            label: None,
+        };
+        let bundle = TestBundle::new(Novel {
+            title: String::from("Novel & Co"),
+            volumes: vec![Volume {
+                chapters: vec![chapter("1"), chapter("2")],
+                ..Default::default()
+            }],
+            ..Default::default()
+        })
+        .content(|url| Some(format!("<p>Content of {url}</p>")));
+
+        bundle_site(&bundle, &dir.path().join("novel")).unwrap();
+        let index = fs::read_to_string(dir.path().join("novel/index.html")).unwrap();
+        assert!(index.contains("<h1>Novel &amp; Co</h1>"));
+        assert!(index.contains("<a href=\"chapters/2.html\">Chapter 2</a>"));
+
+        let first = fs::read_to_string(dir.path().join("novel/chapters/1.html")).unwrap();
+        assert!(first.contains("<p>Content of 1</p>"));
+        assert!(first.contains("<a href=\"2.html\">Next &rarr;</a>"));
+        assert!(first.contains("<span class=\"disabled\">&larr; Previous</span>"));
+
+        let entries = [SiteEntry {
+            title: String::from("Novel & Co"),
+            authors: vec![],
+            path: String::from("novel"),
+        }];
+        write_library_index(dir.path(), &entries).unwrap();
+        let library = fs::read_to_string(dir.path().join("index.html")).unwrap();
+        assert!(library.contains("<a href=\"novel/index.html\">Novel &amp; Co</a>"));
+    }
+}
@@ -0,0 +1,347 @@
+use std::{
+    ops::Range,
+    path::{Path, PathBuf},
+};
+
+use quelle_common::TitleRules;
+use quelle_core::prelude::*;
+
+use crate::data::{Asset, Bundle};
+
+/// Where the output is split into multiple parts
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SplitOptions {
+    /// Start a new part at every volume, within the other limits
+    pub per_volume: bool,
+    /// The maximum number of chapters in a part
+    pub max_chapters: Option<usize>,
+    /// The maximum size of the chapter content in a part, in bytes
+    pub max_bytes: Option<u64>,
+}
+
+impl SplitOptions {
+    pub fn is_none(&self) -> bool {
+        !self.per_volume && self.max_chapters.is_none() && self.max_bytes.is_none()
+    }
+}
+
+/// A part of the novel and the other parts it was split with
+#[derive(Clone, Debug)]
+pub struct Part {
+    /// The position of the part, starting at 0
+    pub index: usize,
+    /// Every part of the novel in order
+    pub spans: Vec<PartSpan>,
+}
+
+/// The chapters of a part and the file it is written to
+#[derive(Clone, Debug)]
+pub struct PartSpan {
+    /// The positions of the chapters in the novel, starting at 0
+    pub chapters: Range<usize>,
+    pub file_name: Option<String>,
+}
+
+impl Part {
+    /// The number of the part, starting at 1
+    pub fn number(&self) -> usize {
+        self.index + 1
+    }
+
+    pub fn total(&self) -> usize {
+        self.spans.len()
+    }
+
+    pub fn chapters(&self) -> Range<usize> {
+        self.spans[self.index].chapters.clone()
+    }
+}
+
+/// Split the chapters of the novel into ranges respecting the limits
+///
+/// The size of a part is estimated from the length of the chapter content.
+/// A chapter larger than the limit is given a part of its own.
+pub fn split_chapters<B: Bundle>(
+    bundle: &B,
+    options: &SplitOptions,
+) -> Result<Vec<Range<usize>>, Box<dyn std::error::Error>> {
+    // Each chapter along with whether it starts a volume
+    let chapters = bundle
+        .novel()
+        .volumes
+        .iter()
+        .flat_map(|volume| {
+            volume
+                .chapters
+                .iter()
+                .enumerate()
+                .map(|(index, chapter)| (index == 0, chapter))
+        })
+        .collect::<Vec<_>>();
+
+    let mut ranges = vec![];
+    let mut start = 0;
+    let mut bytes = 0;
+
+    for (position, (first, chapter)) in chapters.iter().enumerate() {
+        let size = match options.max_bytes {
+            Some(_) => bundle
+                .chapter_content(&chapter.url)?
+                .map(|content| content.len() as u64)
+                .unwrap_or_default(),
+            None => 0,
+        };
+
+        let count = position - start;
+        let full = (options.per_volume && *first)
+            || options.max_chapters.is_some_and(|max| count >= max)
+            || options.max_bytes.is_some_and(|max| bytes + size > max);
+
+        if count > 0 && full {
+            ranges.push(start..position);
+            start = position;
+            bytes = 0;
+        }
+
+        bytes += size;
+    }
+
+    if start < chapters.len() || ranges.is_empty() {
+        ranges.push(start..chapters.len());
+    }
+
+    Ok(ranges)
+}
+
+const KEPUB_EXTENSION: &str = ".kepub.epub";
+
+/// The path of the part, numbered so that the parts sort in order
+///
+/// ## Example
+///
+/// `out/novel.epub` becomes `out/novel - Part 02.epub` for the second of twelve parts.
+pub fn part_path(path: &Path, number: usize, total: usize) -> PathBuf {
+    let width = total.to_string().len();
+    let file_name = path
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_default();
+
+    // Kobo readers only recognise kepubs by their double extension
+    let (stem, extension) = match file_name.strip_suffix(KEPUB_EXTENSION) {
+        Some(stem) => (stem.to_string(), Some(String::from(KEPUB_EXTENSION))),
+        None => (
+            path.file_stem()
+                .map(|stem| stem.to_string_lossy().to_string())
+                .unwrap_or_default(),
+            path.extension()
+                .map(|extension| format!(".{}", extension.to_string_lossy())),
+        ),
+    };
+
+    let mut name = format!("{stem} - Part {number:0width$}");
+    if let Some(extension) = extension {
+        name.push_str(&extension);
+    }
+
+    path.with_file_name(name)
+}
+
+/// A bundle of a single part of the novel
+pub struct PartBundle<'a, B> {
+    inner: &'a B,
+    part: Part,
+}
+
+impl<'a, B: Bundle> PartBundle<'a, B> {
+    pub fn new(inner: &'a B, part: Part) -> Self {
+        Self { inner, part }
+    }
+}
+
+impl<'a, B: Bundle> Bundle for PartBundle<'a, B> {
+    fn meta(&self) -> Option<&Meta> {
+        self.inner.meta()
+    }
+
+    fn novel(&self) -> &Novel {
+        self.inner.novel()
+    }
+
+    fn cover_path(&self) -> Option<&Path> {
+        self.inner.cover_path()
+    }
+
+    fn cover_content_type(&self) -> Option<&str> {
+        self.inner.cover_content_type()
+    }
+
+    fn cover_content(&self) -> Result<Option<Vec<u8>>, Box<dyn std::error::Error>> {
+        self.inner.cover_content()
+    }
+
+    fn chapter_content(&self, url: &str) -> Result<Option<String>, Box<dyn std::error::Error>> {
+        self.inner.chapter_content(url)
+    }
+
+    fn asset(&self, url: &str) -> Result<Option<Asset>, Box<dyn std::error::Error>> {
+        self.inner.asset(url)
+    }
+
+    fn notes(&self) -> Option<&str> {
+        self.inner.notes()
+    }
+
+    fn rights(&self) -> Option<&str> {
+        self.inner.rights()
+    }
+
+    fn fetched_at(&self) -> Option<chrono::DateTime<chrono::Utc>> {
+        self.inner.fetched_at()
+    }
+
+    fn title_rules(&self) -> Option<&TitleRules> {
+        self.inner.title_rules()
+    }
+
+    fn part(&self) -> Option<&Part> {
+        Some(&self.part)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::{Path, PathBuf};
+
+    use super::*;
+    use crate::test_support::TestBundle;
+
+    fn bundle(count: usize) -> TestBundle {
+        TestBundle::new(Novel {
+            volumes: vec![Volume {
+                chapters: (0..count)
+                    .map(|index| Chapter {
+                        index: index as i32,
+                        title: format!("Chapter {index}"),
+                        url: String::from("url"),
+                        updated_at: None,
+                        number: None,
+                        part: None,
+                        label: None,
+                    })
+                    .collect(),
+                ..Default::default()
+            }],
+            ..Default::default()
+        })
+        .content(|url| Some("x".repeat(url.len() * 10)))
+    }
+
+    #[test]
+    fn should_split_by_chapters_and_bytes() {
+        let options = SplitOptions {
+            max_chapters: Some(2),
+            ..Default::default()
+        };
+        assert_eq!(
+            split_chapters(&bundle(5), &options).unwrap(),
+            vec![0..2, 2..4, 4..5]
+        );
+
+        let options = SplitOptions {
+            max_bytes: Some(65),
+            ..Default::default()
+        };
+        assert_eq!(
+            split_chapters(&bundle(5), &options).unwrap(),
+            vec![0..2, 2..4, 4..5]
+        );
+
+        assert_eq!(
+            split_chapters(&bundle(0), &SplitOptions::default()).unwrap(),
+            vec![0..0]
+        );
+    }
+
+    #[test]
+    fn should_group_part_chapters_by_volume() {
+        let mut bundle = bundle(4);
+        let second = bundle.novel.volumes[0].chapters.split_off(2);
+        bundle.novel.volumes.push(Volume {
+            index: 1,
+            name: String::from("Volume 2"),
+            chapters: second,
+        });
+
+        let options = SplitOptions {
+            per_volume: true,
+            max_chapters: Some(1),
+            ..Default::default()
+        };
+        assert_eq!(
+            split_chapters(&bundle, &options).unwrap(),
+            vec![0..1, 1..2, 2..3, 3..4]
+        );
+        let options = SplitOptions {
+            per_volume: true,
+            ..Default::default()
+        };
+        assert_eq!(split_chapters(&bundle, &options).unwrap(), vec![0..2, 2..4]);
+
+        let volumes = bundle.volumes();
+        assert_eq!(volumes.len(), 2);
+        assert_eq!(volumes[1].number, 2);
+        assert_eq!(
+            volumes[1]
+                .chapters
+                .iter()
+                .map(|(p, _)| *p)
+                .collect::<Vec<_>>(),
+            [3, 4]
+        );
+
+        let part = PartBundle::new(
+            &bundle,
+            Part {
+                index: 1,
+                spans: vec![
+                    PartSpan {
+                        chapters: 0..3,
+                        file_name: None,
+                    },
+                    PartSpan {
+                        chapters: 3..4,
+                        file_name: None,
+                    },
+                ],
+            },
+        );
+        let volumes = part.volumes();
+        assert_eq!(volumes.len(), 1);
+        assert_eq!(volumes[0].volume.name, "Volume 2");
+        assert_eq!(
+            volumes[0]
+                .chapters
+                .iter()
+                .map(|(p, _)| *p)
+                .collect::<Vec<_>>(),
+            [4]
+        );
+    }
+
+    #[test]
+    fn should_number_part_paths() {
+        assert_eq!(
+            part_path(Path::new("out/novel.epub"), 2, 12),
+            PathBuf::from("out/novel - Part 02.epub")
+        );
+        assert_eq!(
+            part_path(Path::new("novel"), 1, 3),
+            PathBuf::from("novel - Part 1")
+        );
+        assert_eq!(
+            part_path(Path::new("novel.kepub.epub"), 3, 3),
+            PathBuf::from("novel - Part 3.kepub.epub")
+        );
+    }
+}
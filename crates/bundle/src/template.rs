@@ -0,0 +1,195 @@
+use std::path::PathBuf;
+
+use quelle_core::prelude::*;
+
+use crate::Format;
+
+/// The maximum length of a single substituted value
+const MAX_VALUE_LEN: usize = 120;
+
+/// A template used to construct the output path of a bundle
+///
+/// Supported variables are `{title}`, `{author}`, `{authors}`, `{chapters}`,
+/// `{source}`, `{lang}` and `{ext}`. A leading `~` is expanded to the home
+/// directory. When `{ext}` is not used, the extension of the format is
+/// applied to the resulting path.
+///
+/// ## Example
+///
+/// ```text
+/// ~/Books/{author}/{title} - {chapters} ch.epub
+/// ```
+#[derive(Clone, Debug)]
+pub struct OutputTemplate(String);
+
+impl OutputTemplate {
+    pub fn new<S: Into<String>>(template: S) -> Self {
+        OutputTemplate(template.into())
+    }
+
+    /// Render the template into a path for the novel and format
+    pub fn render(
+        &self,
+        novel: &Novel,
+        meta: Option<&Meta>,
+        format: Format,
+    ) -> Result<PathBuf, String> {
+        let mut rendered = String::with_capacity(self.0.len());
+        let mut rest = self.0.as_str();
+        let mut uses_ext = false;
+
+        while let Some(start) = rest.find('{') {
+            rendered.push_str(&rest[..start]);
+
+            let end = rest[start..]
+                .find('}')
+                .ok_or_else(|| String::from("unclosed '{' in output template"))?;
+
+            let name = &rest[start + 1..start + end];
+            let value = match name {
+                "title" => novel.title.clone(),
+                "author" => novel
+                    .authors
+                    .first()
+                    .cloned()
+                    .unwrap_or_else(|| String::from("Unknown")),
+                "authors" => novel.authors.join(", "),
+                "chapters" => novel
+                    .volumes
+                    .iter()
+                    .map(|volume| volume.chapters.len())
+                    .sum::<usize>()
+                    .to_string(),
+                "source" => meta
+                    .map(|meta| meta.name.clone())
+                    .unwrap_or_else(|| String::from("Unknown")),
+                "lang" => novel.langs.first().cloned().unwrap_or_default(),
+                "ext" => {
+                    uses_ext = true;
+                    format.extension().to_string()
+                }
+                _ => return Err(format!("unknown output template variable '{{{name}}}'")),
+            };
+
+            rendered.push_str(&sanitize(&value));
+            rest = &rest[start + end + 1..];
+        }
+
+        rendered.push_str(rest);
+
+        let mut path = expand_home(&rendered);
+        if !uses_ext {
+            let has_format_ext = path
+                .extension()
+                .and_then(|ext| ext.to_str())
+                .map(|ext| ext.parse::<Format>().is_ok())
+                .unwrap_or(false);
+
+            if has_format_ext {
+                path.set_extension(format.extension());
+            } else {
+                let mut name = path.into_os_string();
+                name.push(format!(".{}", format.extension()));
+                path = PathBuf::from(name);
+            }
+        }
+
+        Ok(path)
+    }
+}
+
+/// Make the value safe to use as a single path component
+fn sanitize(value: &str) -> String {
+    let sanitized = value
+        .chars()
+        .map(|c| match c {
+            '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|' => '_',
+            c if c.is_control() => '_',
+            c => c,
+        })
+        .take(MAX_VALUE_LEN)
+        .collect::<String>();
+
+    let sanitized = sanitized.trim().trim_matches('.').trim();
+    if sanitized.is_empty() {
+        String::from("_")
+    } else {
+        sanitized.to_string()
+    }
+}
+
+fn expand_home(path: &str) -> PathBuf {
+    let home = std::env::var_os("HOME").or_else(|| std::env::var_os("USERPROFILE"));
+
+    match (path.strip_prefix('~'), home) {
+        (Some(rest), Some(home)) if rest.is_empty() || rest.starts_with(['/', '\\']) => {
+            PathBuf::from(home).join(rest.trim_start_matches(['/', '\\']))
+        }
+        _ => PathBuf::from(path),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use quelle_core::prelude::*;
+
+    use super::OutputTemplate;
+    use crate::Format;
+
+    fn novel() -> Novel {
+        Novel {
+            title: String::from("Re: Zero / Part 1"),
+            authors: vec![String::from("Tappei")],
+            volumes: vec![Volume {
+                chapters: (0..3)
+                    .map(|index| Chapter {
+                        index,
+                        title: format!("Chapter {index}"),
+                        url: String::new(),
+                        updated_at: None,
+                        number: None,
+                        part: None,
+                        label: None,
+                    })
+                    .collect(),
+                ..Default::default()
+            }],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn should_render_variables() {
+        let template = OutputTemplate::new("Books/{author}/{title} - {chapters} ch.epub");
+        assert_eq!(
+            template.render(&novel(), None, Format::Epub).unwrap(),
+            PathBuf::from("Books/Tappei/Re_ Zero _ Part 1 - 3 ch.epub")
+        );
+    }
+
+    #[test]
+    fn should_apply_format_extension() {
+        let template = OutputTemplate::new("{title}");
+        assert_eq!(
+            template.render(&novel(), None, Format::Epub).unwrap(),
+            PathBuf::from("Re_ Zero _ Part 1.epub")
+        );
+
+        let template = OutputTemplate::new("out/{author}.{ext}");
+        assert_eq!(
+            template.render(&novel(), None, Format::Epub).unwrap(),
+            PathBuf::from("out/Tappei.epub")
+        );
+    }
+
+    #[test]
+    fn should_reject_unknown_variables() {
+        let template = OutputTemplate::new("{unknown}.epub");
+        assert!(template.render(&novel(), None, Format::Epub).is_err());
+
+        let template = OutputTemplate::new("{title.epub");
+        assert!(template.render(&novel(), None, Format::Epub).is_err());
+    }
+}
@@ -0,0 +1,67 @@
+use std::path::Path;
+
+use quelle_core::prelude::*;
+
+use crate::data::{Asset, Bundle};
+
+type ContentFn = Box<dyn Fn(&str) -> Option<String> + Sync>;
+type AssetFn = Box<dyn Fn(&str) -> Option<Asset> + Sync>;
+
+/// A bundle of the novel without metadata or cover, whose chapters and assets
+/// are given by functions of their url
+pub struct TestBundle {
+    pub novel: Novel,
+    content: ContentFn,
+    asset: AssetFn,
+}
+
+impl TestBundle {
+    /// A bundle whose chapters were not downloaded and that has no assets
+    pub fn new(novel: Novel) -> Self {
+        Self {
+            novel,
+            content: Box::new(|_| None),
+            asset: Box::new(|_| None),
+        }
+    }
+
+    pub fn content(mut self, content: impl Fn(&str) -> Option<String> + Sync + 'static) -> Self {
+        self.content = Box::new(content);
+        self
+    }
+
+    #[cfg_attr(
+        not(any(feature = "cbz", feature = "fb2", feature = "pdf")),
+        allow(dead_code)
+    )]
+    pub fn asset(mut self, asset: impl Fn(&str) -> Option<Asset> + Sync + 'static) -> Self {
+        self.asset = Box::new(asset);
+        self
+    }
+}
+
+impl Bundle for TestBundle {
+    fn meta(&self) -> Option<&Meta> {
+        None
+    }
+
+    fn novel(&self) -> &Novel {
+        &self.novel
+    }
+
+    fn cover_path(&self) -> Option<&Path> {
+        None
+    }
+
+    fn cover_content_type(&self) -> Option<&str> {
+        None
+    }
+
+    fn chapter_content(&self, url: &str) -> Result<Option<String>, Box<dyn std::error::Error>> {
+        Ok((self.content)(url))
+    }
+
+    fn asset(&self, url: &str) -> Result<Option<Asset>, Box<dyn std::error::Error>> {
+        Ok((self.asset)(url))
+    }
+}
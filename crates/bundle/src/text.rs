@@ -0,0 +1,298 @@
+/// Elements that end a paragraph of text
+const BLOCK_TAGS: [&str; 16] = [
+    "p",
+    "br",
+    "div",
+    "h1",
+    "h2",
+    "h3",
+    "h4",
+    "h5",
+    "h6",
+    "li",
+    "tr",
+    "hr",
+    "blockquote",
+    "pre",
+    "section",
+    "article",
+];
+
+/// The paragraphs of text of the html content, without markup
+pub fn text_paragraphs(html: &str) -> Vec<String> {
+    let mut paragraphs = vec![];
+    let mut paragraph = String::new();
+    let mut rest = html;
+
+    while let Some(start) = rest.find('<') {
+        paragraph.push_str(&rest[..start]);
+        let Some(end) = rest[start..].find('>') else {
+            rest = &rest[start..];
+            break;
+        };
+
+        let tag = rest[start + 1..start + end]
+            .trim_start_matches('/')
+            .split(|c: char| c.is_whitespace() || c == '/')
+            .next()
+            .unwrap_or_default()
+            .to_ascii_lowercase();
+
+        if BLOCK_TAGS.contains(&tag.as_str()) {
+            paragraphs.push(std::mem::take(&mut paragraph));
+        }
+        rest = &rest[start + end + 1..];
+    }
+
+    paragraph.push_str(rest);
+    paragraphs.push(paragraph);
+
+    paragraphs
+        .into_iter()
+        .map(|paragraph| {
+            decode_entities(&paragraph.split_whitespace().collect::<Vec<_>>().join(" "))
+        })
+        .map(|paragraph| paragraph.trim().to_string())
+        .filter(|paragraph| !paragraph.is_empty())
+        .collect()
+}
+
+/// Remove the first paragraph when it is one of the titles of the chapter,
+/// as chapter content usually repeats the title as its heading
+///
+/// The titles are the title shown for the chapter and the title given by the
+/// source, which differ when the titles are renumbered.
+pub fn strip_title_heading(paragraphs: &mut Vec<String>, titles: &[&str]) {
+    if paragraphs
+        .first()
+        .is_some_and(|first| titles.contains(&first.as_str()))
+    {
+        paragraphs.remove(0);
+    }
+}
+
+/// Replace the character references of html text with their characters
+pub fn decode_entities(text: &str) -> String {
+    let mut decoded = String::with_capacity(text.len());
+    let mut rest = text;
+
+    while let Some(start) = rest.find('&') {
+        decoded.push_str(&rest[..start]);
+        rest = &rest[start..];
+
+        let character = rest.find(';').filter(|end| *end <= 10).and_then(|end| {
+            let name = &rest[1..end];
+            let character = match name {
+                "amp" => Some('&'),
+                "lt" => Some('<'),
+                "gt" => Some('>'),
+                "quot" => Some('"'),
+                "apos" => Some('\''),
+                "nbsp" => Some(' '),
+                _ => name
+                    .strip_prefix("#x")
+                    .or_else(|| name.strip_prefix("#X"))
+                    .and_then(|hex| u32::from_str_radix(hex, 16).ok())
+                    .or_else(|| name.strip_prefix('#')?.parse().ok())
+                    .and_then(char::from_u32),
+            };
+            character.map(|character| (character, end))
+        });
+
+        match character {
+            Some((character, end)) => {
+                decoded.push(character);
+                rest = &rest[end + 1..];
+            }
+            None => {
+                decoded.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+
+    decoded.push_str(rest);
+    decoded
+}
+
+/// Interlinear annotation characters, which mark the base text of ruby, its
+/// annotation and its end in text without markup
+#[cfg(any(feature = "pdf", test))]
+pub(crate) const RUBY_ANCHOR: char = '\u{FFF9}';
+#[cfg(any(feature = "pdf", test))]
+pub(crate) const RUBY_SEPARATOR: char = '\u{FFFA}';
+#[cfg(any(feature = "pdf", test))]
+pub(crate) const RUBY_TERMINATOR: char = '\u{FFFB}';
+
+/// Replace the ruby elements of the html content with interlinear annotation
+/// characters, so that the readings of ruby are kept apart from the text when
+/// the markup is removed
+///
+/// The parentheses of `rp` elements, shown by readers without support for
+/// ruby, are left out.
+#[cfg(any(feature = "pdf", test))]
+pub(crate) fn annotate_ruby(html: &str) -> String {
+    let mut annotated = String::with_capacity(html.len());
+    let mut rest = html;
+
+    while let Some(start) = find_tag(rest, "ruby") {
+        annotated.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let end = find_tag(rest, "/ruby").unwrap_or(rest.len());
+        let ruby = &rest[rest.find('>').map_or(end, |open| open + 1).min(end)..end];
+        rest = rest[end..]
+            .find('>')
+            .map_or("", |close| &rest[end + close + 1..]);
+
+        // The base text and annotation of each pair, as html without tags
+        let mut base = String::new();
+        let mut annotation: Option<String> = None;
+        let mut skipped = false;
+        let mut inner = ruby;
+        loop {
+            let text_end = inner.find('<').unwrap_or(inner.len());
+            let text = &inner[..text_end];
+            match &mut annotation {
+                _ if skipped => {}
+                Some(annotation) => annotation.push_str(text),
+                None => base.push_str(text),
+            }
+            if text_end == inner.len() {
+                break;
+            }
+
+            let tag_end = inner[text_end..]
+                .find('>')
+                .map_or(inner.len(), |end| text_end + end + 1);
+            let tag = inner[text_end + 1..tag_end]
+                .trim_end_matches('>')
+                .to_ascii_lowercase();
+            let name = tag
+                .split(|c: char| c.is_whitespace())
+                .next()
+                .unwrap_or_default();
+            match name {
+                "rp" => skipped = true,
+                "/rp" => skipped = false,
+                "rt" => {
+                    if let Some(annotation) = annotation.take() {
+                        push_ruby(&mut annotated, &std::mem::take(&mut base), &annotation);
+                    }
+                    annotation = Some(String::new());
+                }
+                "/rt" => {
+                    if let Some(annotation) = annotation.take() {
+                        push_ruby(&mut annotated, &std::mem::take(&mut base), &annotation);
+                    }
+                }
+                _ => {}
+            }
+            inner = &inner[tag_end..];
+        }
+
+        match annotation {
+            Some(annotation) => push_ruby(&mut annotated, &base, &annotation),
+            None => annotated.push_str(&base),
+        }
+    }
+
+    annotated.push_str(rest);
+    annotated
+}
+
+#[cfg(any(feature = "pdf", test))]
+fn push_ruby(annotated: &mut String, base: &str, annotation: &str) {
+    if base.trim().is_empty() || annotation.trim().is_empty() {
+        annotated.push_str(base);
+        return;
+    }
+    annotated.push(RUBY_ANCHOR);
+    annotated.push_str(base.trim());
+    annotated.push(RUBY_SEPARATOR);
+    annotated.push_str(annotation.trim());
+    annotated.push(RUBY_TERMINATOR);
+}
+
+/// The position of the start of the first tag of the name, ignoring case
+#[cfg(any(feature = "pdf", test))]
+fn find_tag(html: &str, name: &str) -> Option<usize> {
+    let mut offset = 0;
+    while let Some(start) = html[offset..].find('<') {
+        let start = offset + start;
+        let tag = &html[start + 1..];
+        let matched = tag
+            .get(..name.len())
+            .is_some_and(|found| found.eq_ignore_ascii_case(name))
+            && tag[name.len()..].starts_with(|c: char| c == '>' || c == '/' || c.is_whitespace());
+        if matched {
+            return Some(start);
+        }
+        offset = start + 1;
+    }
+    None
+}
+
+/// Replace the characters with a meaning in markup with their references
+pub fn escape(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Break the text into lines of at most `width` characters between words,
+/// words longer than the width are kept on their own line
+pub fn wrap(text: &str, width: usize) -> Vec<String> {
+    let mut lines = vec![];
+    let mut line = String::new();
+
+    for word in text.split_whitespace() {
+        let length = line.chars().count();
+        if length > 0 && length + 1 + word.chars().count() > width {
+            lines.push(std::mem::take(&mut line));
+        }
+        if !line.is_empty() {
+            line.push(' ');
+        }
+        line.push_str(word);
+    }
+
+    if !line.is_empty() {
+        lines.push(line);
+    }
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_strip_either_title_heading() {
+        let mut paragraphs = vec![String::from("Chapter 1: One"), String::from("Text")];
+        strip_title_heading(&mut paragraphs, &["1. One", "Chapter 1: One"]);
+        assert_eq!(paragraphs, ["Text"]);
+
+        strip_title_heading(&mut paragraphs, &["1. One", "Chapter 1: One"]);
+        assert_eq!(paragraphs, ["Text"]);
+    }
+
+    #[test]
+    fn should_extract_paragraphs_and_decode_entities() {
+        let html = "<h1>Title</h1><p>Tom &amp; Jerry&#39;s   <em>day</em></p><p>&#x2014;&nbsp;end &copy</p>";
+        assert_eq!(
+            text_paragraphs(html),
+            vec!["Title", "Tom & Jerry's day", "— end &copy"]
+        );
+    }
+
+    #[test]
+    fn should_annotate_ruby() {
+        let html = "<p><ruby>漢<rp>(</rp><rt>かん</rt><rp>)</rp>字<rt>じ</rt></ruby>を<RUBY><rb>読</rb><rt>よ</RUBY>む</p>";
+        assert_eq!(
+            text_paragraphs(&annotate_ruby(html)),
+            vec!["\u{FFF9}漢\u{FFFA}かん\u{FFFB}\u{FFF9}字\u{FFFA}じ\u{FFFB}を\u{FFF9}読\u{FFFA}よ\u{FFFB}む"]
+        );
+    }
+}
@@ -0,0 +1,226 @@
+use std::{
+    fmt::Display,
+    fs, io,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+/// The layout shared by the built-in themes
+const BASE: &str = r#"body {
+  margin: 0 5%;
+  line-height: 1.6;
+  text-align: justify;
+  hyphens: auto;
+}
+h1, h2 {
+  line-height: 1.25;
+  text-align: center;
+  page-break-after: avoid;
+  margin: 1.5em 0 1em;
+}
+h1.volume { margin-top: 30%; }
+p { margin: 0 0 0.8em; }
+img { max-width: 100%; }
+a { color: inherit; }
+a.noteref { text-decoration: none; }
+aside.footnote { font-size: 0.85em; margin: 0 0 0.8em; }
+"#;
+
+const SERIF: &str = r#"body { font-family: Georgia, "Times New Roman", serif; }
+"#;
+
+const SANS: &str = r#"body { font-family: "Helvetica Neue", Arial, sans-serif; }
+p { margin: 0 0 1em; }
+"#;
+
+const SEPIA: &str = r#"body {
+  font-family: Georgia, "Times New Roman", serif;
+  background-color: #f4ecd8;
+  color: #5b4636;
+}
+"#;
+
+const HIGH_CONTRAST: &str = r#"body {
+  font-family: Verdana, Arial, sans-serif;
+  font-size: 1.15em;
+  line-height: 1.8;
+  text-align: left;
+  background-color: #000000;
+  color: #ffffff;
+}
+a { color: #ffff00; text-decoration: underline; }
+"#;
+
+/// How bundled books are styled
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub enum Theme {
+    #[default]
+    Serif,
+    Sans,
+    /// Dark text on a warm paper colour
+    Sepia,
+    /// Large text in white on black
+    HighContrast,
+    /// A stylesheet, or a directory of stylesheets with the fonts and images
+    /// they use at the same relative paths
+    Custom(PathBuf),
+}
+
+/// A file used by a custom stylesheet
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ThemeResource {
+    /// The path relative to the stylesheet
+    pub name: String,
+    pub path: PathBuf,
+    pub content_type: &'static str,
+}
+
+impl Theme {
+    /// The stylesheet of the theme
+    pub fn stylesheet(&self) -> io::Result<String> {
+        let extra = match self {
+            Theme::Serif => SERIF,
+            Theme::Sans => SANS,
+            Theme::Sepia => SEPIA,
+            Theme::HighContrast => HIGH_CONTRAST,
+            Theme::Custom(path) if path.is_dir() => {
+                let mut stylesheet = String::new();
+                for file in files(path)? {
+                    if file.extension().is_some_and(|extension| extension == "css") {
+                        stylesheet += &fs::read_to_string(file)?;
+                        stylesheet.push('\n');
+                    }
+                }
+                return Ok(stylesheet);
+            }
+            Theme::Custom(path) => return fs::read_to_string(path),
+        };
+
+        Ok(format!("{BASE}{extra}"))
+    }
+
+    /// The fonts, images and other files of a custom stylesheet directory
+    pub fn resources(&self) -> io::Result<Vec<ThemeResource>> {
+        let Theme::Custom(dir) = self else {
+            return Ok(vec![]);
+        };
+        if !dir.is_dir() {
+            return Ok(vec![]);
+        }
+
+        let mut resources = vec![];
+        for path in files(dir)? {
+            let extension = path
+                .extension()
+                .map(|extension| extension.to_string_lossy().to_ascii_lowercase())
+                .unwrap_or_default();
+            let content_type = match extension.as_str() {
+                "css" => continue,
+                "ttf" => "font/ttf",
+                "otf" => "font/otf",
+                "woff" => "font/woff",
+                "woff2" => "font/woff2",
+                "png" => "image/png",
+                "jpg" | "jpeg" => "image/jpeg",
+                "gif" => "image/gif",
+                "svg" => "image/svg+xml",
+                _ => continue,
+            };
+
+            let name = path
+                .strip_prefix(dir)
+                .unwrap_or(&path)
+                .components()
+                .map(|component| component.as_os_str().to_string_lossy())
+                .collect::<Vec<_>>()
+                .join("/");
+            resources.push(ThemeResource {
+                name,
+                path,
+                content_type,
+            });
+        }
+
+        Ok(resources)
+    }
+}
+
+/// The files in the directory and its subdirectories, sorted by path
+fn files(dir: &Path) -> io::Result<Vec<PathBuf>> {
+    let mut files = vec![];
+    let mut dirs = vec![dir.to_path_buf()];
+    while let Some(dir) = dirs.pop() {
+        for entry in fs::read_dir(dir)? {
+            let path = entry?.path();
+            if path.is_dir() {
+                dirs.push(path);
+            } else {
+                files.push(path);
+            }
+        }
+    }
+
+    files.sort();
+    Ok(files)
+}
+
+impl FromStr for Theme {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "serif" => Ok(Theme::Serif),
+            "sans" => Ok(Theme::Sans),
+            "sepia" => Ok(Theme::Sepia),
+            "high-contrast" => Ok(Theme::HighContrast),
+            _ if Path::new(s).exists() => Ok(Theme::Custom(PathBuf::from(s))),
+            _ => Err(format!(
+                "'{s}' is neither a theme (serif, sans, sepia, high-contrast) nor a stylesheet"
+            )),
+        }
+    }
+}
+
+impl Display for Theme {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Theme::Serif => write!(f, "serif"),
+            Theme::Sans => write!(f, "sans"),
+            Theme::Sepia => write!(f, "sepia"),
+            Theme::HighContrast => write!(f, "high-contrast"),
+            Theme::Custom(path) => write!(f, "{}", path.display()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_read_custom_stylesheet_directory() {
+        assert_eq!("Sepia".parse(), Ok(Theme::Sepia));
+        assert!("unknown-theme".parse::<Theme>().is_err());
+        assert!(Theme::HighContrast
+            .stylesheet()
+            .unwrap()
+            .contains("background-color: #000000"));
+
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir_all(dir.path().join("fonts")).unwrap();
+        fs::write(dir.path().join("a.css"), "p { color: red; }").unwrap();
+        fs::write(dir.path().join("fonts/serif.woff2"), "font").unwrap();
+        fs::write(dir.path().join("notes.txt"), "ignored").unwrap();
+
+        let theme = dir.path().to_string_lossy().parse::<Theme>().unwrap();
+        assert_eq!(theme.stylesheet().unwrap(), "p { color: red; }\n");
+        assert_eq!(
+            theme.resources().unwrap(),
+            vec![ThemeResource {
+                name: String::from("fonts/serif.woff2"),
+                path: dir.path().join("fonts/serif.woff2"),
+                content_type: "font/woff2",
+            }]
+        );
+    }
+}
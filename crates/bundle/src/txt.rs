@@ -0,0 +1,175 @@
+use std::io::Write;
+
+use log::{info, warn};
+
+use crate::{
+    data::Bundle,
+    front::{bundle_title, colophon, FrontMatter},
+    text::{strip_title_heading, text_paragraphs, wrap},
+};
+
+/// How the novel is laid out as plain text
+#[derive(Clone, Debug)]
+pub struct TxtOptions {
+    /// The line written between chapters
+    pub separator: String,
+    /// The number of characters lines are wrapped at, not wrapped when `None`
+    pub width: Option<usize>,
+}
+
+impl Default for TxtOptions {
+    fn default() -> Self {
+        Self {
+            separator: String::from("* * *"),
+            width: None,
+        }
+    }
+}
+
+/// Write the novel as a single plain text file, with paragraphs separated by
+/// a blank line
+pub fn bundle_txt<B: Bundle, W: Write>(
+    bundle: &B,
+    out: &mut W,
+    options: &TxtOptions,
+    front_matter: &FrontMatter,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let novel = bundle.novel();
+    let mut writer = TxtWriter { out, options };
+
+    if front_matter.title_page {
+        writer.paragraph(&bundle_title(bundle))?;
+        if !novel.authors.is_empty() {
+            writer.paragraph(&format!("by {}", novel.authors.join(", ")))?;
+        }
+    }
+    if front_matter.synopsis {
+        for paragraph in &novel.description {
+            writer.paragraph(paragraph)?;
+        }
+    }
+    if front_matter.colophon {
+        for (label, value) in colophon(bundle) {
+            writer.paragraph(&format!("{label}: {value}"))?;
+        }
+    }
+    if let Some(rights) = bundle.rights() {
+        writer.paragraph(rights)?;
+    }
+    if let Some(notes) = bundle.notes() {
+        writer.paragraph("Notes")?;
+        for paragraph in notes.split("\n\n").map(str::trim) {
+            writer.paragraph(paragraph)?;
+        }
+    }
+
+    info!("Written title, authors, and description");
+
+    for (position, chapter) in bundle.chapters() {
+        writer.separator()?;
+
+        let title = bundle
+            .chapter_title(chapter, position)
+            .unwrap_or_else(|| chapter.title.clone());
+        writer.paragraph(&title)?;
+
+        match bundle.chapter_content(&chapter.url)? {
+            Some(content) => {
+                let mut paragraphs = text_paragraphs(&content);
+                strip_title_heading(&mut paragraphs, &[&title, &chapter.title]);
+                for paragraph in paragraphs {
+                    writer.paragraph(&paragraph)?;
+                }
+            }
+            None => {
+                warn!("Using placeholder content for '{}'.", chapter.title);
+                writer.paragraph("No downloaded content")?;
+            }
+        }
+
+        info!("Written '{}'.", chapter.title);
+    }
+
+    writer.out.flush()?;
+    info!("Text writing complete.");
+    Ok(())
+}
+
+struct TxtWriter<'a, W> {
+    out: &'a mut W,
+    options: &'a TxtOptions,
+}
+
+impl<W: Write> TxtWriter<'_, W> {
+    fn paragraph(&mut self, text: &str) -> std::io::Result<()> {
+        if text.trim().is_empty() {
+            return Ok(());
+        }
+
+        match self.options.width {
+            Some(width) => {
+                for line in wrap(text, width) {
+                    writeln!(self.out, "{line}")?;
+                }
+            }
+            None => writeln!(self.out, "{}", text.trim())?,
+        }
+        writeln!(self.out)
+    }
+
+    fn separator(&mut self) -> std::io::Result<()> {
+        writeln!(self.out, "{}", self.options.separator)?;
+        writeln!(self.out)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use quelle_core::prelude::*;
+
+    use super::*;
+    use crate::test_support::TestBundle;
+
+    #[test]
+    fn should_write_wrapped_chapters_between_separators() {
+        let chapter = |url: &str, title: &str| Chapter {
+            index: 0,
+            title: title.to_string(),
+            url: url.to_string(),
+            updated_at: None,
+            number: None,
+            part: None,
+            label: None,
+        };
+        let bundle = TestBundle::new(Novel {
+            title: String::from("Novel"),
+            authors: vec![String::from("Author")],
+            volumes: vec![Volume {
+                chapters: vec![chapter("1", "One"), chapter("2", "Two")],
+                ..Default::default()
+            }],
+            ..Default::default()
+        })
+        .content(|url| {
+            (url == "1").then(|| String::from("<h1>One</h1><p>The first chapter of the novel</p>"))
+        });
+
+        let options = TxtOptions {
+            separator: String::from("~~~"),
+            width: Some(16),
+        };
+        // The colophon has the date of the bundle
+        let front_matter = FrontMatter {
+            colophon: false,
+            ..Default::default()
+        };
+        let mut out = vec![];
+        bundle_txt(&bundle, &mut out, &options, &front_matter).unwrap();
+
+        let text = String::from_utf8(out).unwrap();
+        assert_eq!(
+            text,
+            "Novel\n\nby Author\n\n~~~\n\nOne\n\nThe first\nchapter of the\nnovel\n\n~~~\n\nTwo\n\nNo downloaded\ncontent\n\n"
+        );
+    }
+}
@@ -0,0 +1,11 @@
+use std::io;
+
+use tempfile::TempDir;
+
+/// A temporary directory for the files a format is made from, removed once
+/// the bundle is written
+pub fn work_dir(name: &str) -> io::Result<TempDir> {
+    tempfile::Builder::new()
+        .prefix(&format!("quelle-{name}-"))
+        .tempdir()
+}
@@ -0,0 +1,125 @@
+use crate::text::escape;
+
+/// What a page is in the book, marked up with the matching `epub:type` and
+/// ARIA role so that reading systems and assistive technologies can tell the
+/// parts of the book apart
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(not(feature = "epub"), allow(dead_code))]
+pub enum PageKind {
+    TitlePage,
+    Synopsis,
+    Colophon,
+    /// The list of the parts of a split novel
+    Parts,
+    Copyright,
+    Notes,
+    Volume,
+    Chapter,
+}
+
+impl PageKind {
+    /// The structural semantics of the section of the page
+    pub fn epub_type(&self) -> &'static str {
+        match self {
+            PageKind::TitlePage => "titlepage",
+            PageKind::Synopsis => "preface",
+            PageKind::Colophon => "colophon",
+            PageKind::Parts => "frontmatter",
+            PageKind::Copyright => "copyright-page",
+            PageKind::Notes => "foreword",
+            PageKind::Volume => "part",
+            PageKind::Chapter => "chapter",
+        }
+    }
+
+    /// The DPUB-ARIA role of the section, when there is one for the page
+    pub fn role(&self) -> Option<&'static str> {
+        match self {
+            PageKind::Synopsis => Some("doc-preface"),
+            PageKind::Colophon => Some("doc-colophon"),
+            PageKind::Notes => Some("doc-foreword"),
+            PageKind::Volume => Some("doc-part"),
+            PageKind::Chapter => Some("doc-chapter"),
+            PageKind::TitlePage | PageKind::Parts | PageKind::Copyright => None,
+        }
+    }
+
+    fn matter(&self) -> &'static str {
+        match self {
+            PageKind::Volume | PageKind::Chapter => "bodymatter",
+            _ => "frontmatter",
+        }
+    }
+}
+
+/// Wrap the content into an xhtml page linked to the stylesheet of the theme,
+/// in the language of the novel and marked up as the kind of page
+pub fn page(
+    file_name: &str,
+    title: &str,
+    lang: Option<&str>,
+    kind: PageKind,
+    body: &str,
+) -> String {
+    let stylesheet = format!(
+        "{}stylesheet.css",
+        "../".repeat(file_name.matches('/').count())
+    );
+    let title = escape(title);
+    let lang = lang
+        .map(|lang| {
+            let lang = escape(lang);
+            format!(r#" lang="{lang}" xml:lang="{lang}""#)
+        })
+        .unwrap_or_default();
+    let role = kind
+        .role()
+        .map(|role| format!(r#" role="{role}""#))
+        .unwrap_or_default();
+
+    format!(
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE html>
+<html xmlns="http://www.w3.org/1999/xhtml" xmlns:epub="http://www.idpf.org/2007/ops"{lang}>
+<head>
+<title>{title}</title>
+<link rel="stylesheet" type="text/css" href="{stylesheet}"/>
+</head>
+<body epub:type="{matter}">
+<section epub:type="{epub_type}"{role} aria-label="{title}">
+{body}
+</section>
+</body>
+</html>
+"#,
+        matter = kind.matter(),
+        epub_type = kind.epub_type(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_mark_up_page_semantics() {
+        let chapter = page(
+            "chapters/1.xhtml",
+            "One & Two",
+            Some("en"),
+            PageKind::Chapter,
+            "<h1>One &amp; Two</h1>",
+        );
+        assert!(chapter
+            .contains(r#"xmlns:epub="http://www.idpf.org/2007/ops" lang="en" xml:lang="en">"#));
+        assert!(chapter.contains(r#"href="../stylesheet.css""#));
+        assert!(chapter.contains(r#"<body epub:type="bodymatter">"#));
+        assert!(chapter.contains(
+            r#"<section epub:type="chapter" role="doc-chapter" aria-label="One &amp; Two">"#
+        ));
+
+        let rights = page("rights.xhtml", "Rights", None, PageKind::Copyright, "");
+        assert!(rights.contains(r#"xmlns:epub="http://www.idpf.org/2007/ops">"#));
+        assert!(rights.contains(r#"<section epub:type="copyright-page" aria-label="Rights">"#));
+    }
+}
@@ -1,4 +1,9 @@
-use anyhow::Context;
+use anyhow::{bail, Context};
+use quelle_engine::fixtures::Fixtures;
+use quelle_lock::{
+    package::{write_package, PackageFiles, PACKAGE_EXTENSION},
+    validation::{ValidationContext, ValidationEngine},
+};
 use serde::Deserialize;
 use std::{
     fs,
@@ -24,13 +29,36 @@ struct CrateCargo {
 #[derive(Deserialize, Debug)]
 struct Package {
     name: String,
+    version: String,
+    #[serde(default)]
+    metadata: Option<Metadata>,
 }
 
-pub fn build(extension: Option<PathBuf>, out: PathBuf, release: bool) -> anyhow::Result<()> {
-    match extension {
-        Some(path) => {
-            build_extension(&path.as_os_str().to_string_lossy(), &out, release)?;
-        }
+/// The `[package.metadata]` table of an extension
+#[derive(Deserialize, Debug, Default)]
+struct Metadata {
+    #[serde(default)]
+    quelle: Branding,
+}
+
+/// The `[package.metadata.quelle]` table of an extension
+#[derive(Deserialize, Debug, Default)]
+struct Branding {
+    /// The color used to brand the extension, as `#rrggbb`
+    accent: Option<String>,
+}
+
+/// Names of the icon searched for in the directory of an extension
+const ICONS: [&str; 2] = ["icon.png", "icon.svg"];
+
+pub async fn build(
+    extension: Option<PathBuf>,
+    out: PathBuf,
+    release: bool,
+    package: bool,
+) -> anyhow::Result<()> {
+    let paths = match extension {
+        Some(path) => vec![path.as_os_str().to_string_lossy().into_owned()],
         None => {
             let members = {
                 let content =
@@ -40,24 +68,44 @@ pub fn build(extension: Option<PathBuf>, out: PathBuf, release: bool) -> anyhow:
                 cargo.workspace.members
             };
 
-            let extensions = members.iter().filter(|v| v.starts_with("extensions/"));
+            members
+                .into_iter()
+                .filter(|v| v.starts_with("extensions/"))
+                .collect()
+        }
+    };
 
-            for extension in extensions {
-                build_extension(extension, &out, release)?
-            }
+    for path in paths {
+        build_extension(&path, &out, release)?;
+        if package {
+            package_extension(&path, &out).await?;
         }
     }
 
     Ok(())
 }
 
-fn build_extension(path: &str, out: &Path, release: bool) -> anyhow::Result<()> {
-    let package_name = {
-        let path = Path::new(path).join("Cargo.toml");
-        let content = fs::read_to_string(path)?;
-        let cargo = toml::from_str::<CrateCargo>(&content)?;
-        cargo.package.name
-    };
+/// Run the validation rules on the wasm, failing when any rule fails
+pub async fn validate(wasm: &Path) -> anyhow::Result<()> {
+    let context = ValidationContext::load(wasm).await?;
+    let report = ValidationEngine::default().run(context);
+    print!("{report}");
+
+    if !report.passed() {
+        bail!("'{}' failed validation", wasm.display());
+    }
+    Ok(())
+}
+
+fn read_package(path: &str) -> anyhow::Result<Package> {
+    let path = Path::new(path).join("Cargo.toml");
+    let content = fs::read_to_string(path)?;
+    let cargo = toml::from_str::<CrateCargo>(&content)?;
+    Ok(cargo.package)
+}
+
+pub fn build_extension(path: &str, out: &Path, release: bool) -> anyhow::Result<()> {
+    let package_name = read_package(path)?.name;
 
     let mut args = vec![
         "build",
@@ -88,3 +136,42 @@ fn build_extension(path: &str, out: &Path, release: bool) -> anyhow::Result<()>
 
     Ok(())
 }
+
+/// Validate the built wasm and bundle it with the icon, changelog and fixtures
+/// of the extension into a package, replacing the bare wasm
+async fn package_extension(path: &str, out: &Path) -> anyhow::Result<()> {
+    let package = read_package(path)?;
+    let dir = Path::new(path);
+
+    let wasm = out.join(format!("{}.wasm", package.name));
+    validate(&wasm).await?;
+
+    let fixtures = Fixtures::path_for(&wasm);
+    let files = PackageFiles {
+        wasm: wasm.clone(),
+        icon: ICONS
+            .iter()
+            .map(|name| dir.join(name))
+            .find(|path| path.exists()),
+        changelog: Some(dir.join("CHANGELOG.md")).filter(|path| path.exists()),
+        fixtures: fixtures.exists().then(|| fixtures.clone()),
+    };
+
+    let to = out.join(format!("{}.{PACKAGE_EXTENSION}", package.name));
+    let accent = package.metadata.and_then(|metadata| metadata.quelle.accent);
+    write_package(
+        &to,
+        &package.name,
+        &package.version,
+        accent.as_deref(),
+        &files,
+    )
+    .with_context(|| format!("failed to package '{}'", package.name))?;
+
+    fs::remove_file(wasm)?;
+    if fixtures.exists() {
+        fs::remove_file(fixtures)?;
+    }
+
+    Ok(())
+}
@@ -1,7 +1,7 @@
 use std::{error, fs, future::Future, path::PathBuf};
 
 use quelle_engine::module::{
-    http::{parse_response, read_request, send_request_reqwest},
+    http::{parse_response, read_request, send_request_reqwest, RedirectPolicy},
     utils::write_str,
 };
 use slug::slugify;
@@ -9,6 +9,7 @@ use wasmtime::Caller;
 
 pub struct CachingImpl {
     pub client: reqwest::Client,
+    pub redirect: RedirectPolicy,
     pub cache: Cache,
 }
 
@@ -17,8 +18,10 @@ impl CachingImpl {
         Self {
             client: reqwest::Client::builder()
                 .user_agent("Mozilla/5.0 (X11; Fedora; Linux x86_64; rv:107.0) Gecko/20100101 Firefox/107.0")
+                .redirect(reqwest::redirect::Policy::none())
                 .build()
                 .unwrap(),
+            redirect: RedirectPolicy::default(),
             cache: Cache::default(),
         }
     }
@@ -40,9 +43,11 @@ pub fn send_request<'a>(
             json
         } else {
             let key = request.url.clone();
-            let client = &caller.data().client;
+            let CachingImpl {
+                client, redirect, ..
+            } = caller.data();
 
-            let response = send_request_reqwest::<CachingImpl>(client, request).await;
+            let response = send_request_reqwest::<CachingImpl>(client, request, redirect).await;
             let response = parse_response(response).await;
 
             let json = serde_json::to_string(&response).unwrap();
@@ -1,12 +1,17 @@
 mod build;
 mod cache;
+mod watch;
 
-use std::path::PathBuf;
+use std::{path::PathBuf, time::Duration};
 
 use cache::{Cache, CachingImpl};
 use clap::{Parser, Subcommand};
+use futures_util::{pin_mut, StreamExt};
 use quelle_core::prelude::{ExtensionConfig, Request};
-use quelle_engine::Runtime;
+use quelle_engine::{
+    fixtures::{self, FixtureImpl, Fixtures},
+    Runtime,
+};
 use simplelog::{Config, LevelFilter, TermLogger};
 use url::Url;
 
@@ -17,6 +22,11 @@ struct Cli {
     #[clap(short, long, action = clap::ArgAction::Count)]
     verbose: u8,
 
+    /// Screen reader friendly output without colors or progress bars.
+    /// Can also be enabled by setting QUELLE_ACCESSIBLE.
+    #[clap(long)]
+    accessible: bool,
+
     #[command(subcommand)]
     command: Commands,
 }
@@ -54,6 +64,10 @@ enum Commands {
         /// Page used in search and popular
         #[arg(short, long, default_value = "1")]
         page: i32,
+
+        /// Stream search results across pages up to this many novels
+        #[arg(long)]
+        limit: Option<usize>,
     },
 
     /// Build the extensions into wasm
@@ -69,6 +83,45 @@ enum Commands {
         /// Build the extension(s) with release profile
         #[arg(short, long)]
         release: bool,
+
+        /// Bundle each extension with its icon, changelog and fixtures into a package
+        #[arg(short, long)]
+        package: bool,
+    },
+
+    /// Check a built extension against the rules applied before it is packaged
+    Validate {
+        /// The path to the wasm file
+        path: PathBuf,
+    },
+
+    /// Watch all extensions and their local dependencies, rebuilding on change
+    Watch {
+        /// The output directory for the built extensions
+        #[arg(short, long, default_value = "extensions")]
+        out: PathBuf,
+
+        /// Build the extension(s) with release profile
+        #[arg(short, long)]
+        release: bool,
+
+        /// How often to check for changes in milliseconds
+        #[arg(short, long, default_value = "1000")]
+        interval: u64,
+    },
+
+    /// Record the responses used by the extension into a fixtures file next to it
+    Fixtures {
+        /// The path to the wasm file
+        path: PathBuf,
+
+        /// Novel urls to fetch while recording
+        #[arg(short, long)]
+        novel: Vec<Url>,
+
+        /// Chapter urls to fetch while recording
+        #[arg(short, long)]
+        chapter: Vec<Url>,
     },
 
     /// Read the compiled wasm files and create a record
@@ -102,7 +155,14 @@ enum Commands {
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
-    let cli = Cli::parse();
+    let mut cli = Cli::parse();
+    cli.accessible |= std::env::var_os("QUELLE_ACCESSIBLE").is_some();
+
+    if cli.accessible {
+        // Inherited by the cargo processes spawned to build extensions
+        std::env::set_var("CARGO_TERM_COLOR", "never");
+        std::env::set_var("CARGO_TERM_PROGRESS_WHEN", "never");
+    }
 
     let level = match cli.verbose {
         0 => LevelFilter::Error,
@@ -116,7 +176,11 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         level,
         Config::default(),
         simplelog::TerminalMode::Mixed,
-        simplelog::ColorChoice::Auto,
+        if cli.accessible {
+            simplelog::ColorChoice::Never
+        } else {
+            simplelog::ColorChoice::Auto
+        },
     )
     .unwrap();
 
@@ -130,6 +194,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
             search,
             options,
             page,
+            limit,
         } => {
             let config = ExtensionConfig {
                 level_filter: level,
@@ -158,13 +223,23 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
             }
 
             if let Some(query) = search {
-                if runner.text_search_supported() {
+                if !runner.text_search_supported() {
+                    println!("query search not supported");
+                } else if let Some(limit) = limit {
+                    let results = runner.search_iter(&query, limit);
+                    pin_mut!(results);
+
+                    while let Some(item) = results.next().await {
+                        match item {
+                            Ok(item) => println!("{item:?}"),
+                            Err(e) => println!("page failed: {e}"),
+                        }
+                    }
+                } else {
                     let result = runner.text_search(&query, page).await?;
                     for item in result {
                         println!("{item:?}");
                     }
-                } else {
-                    println!("query search not supported");
                 }
             }
 
@@ -209,8 +284,56 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
             extension,
             out,
             release,
+            package,
         } => {
-            build::build(extension, out, release)?;
+            build::build(extension, out, release, package).await?;
+        }
+        Commands::Validate { path } => {
+            build::validate(&path).await?;
+        }
+        Commands::Watch {
+            out,
+            release,
+            interval,
+        } => {
+            watch::watch_all(out, release, Duration::from_millis(interval)).await?;
+        }
+        Commands::Fixtures {
+            path,
+            novel,
+            chapter,
+        } => {
+            let mut runner = Runtime::builder()
+                .send_request(fixtures::send_request)
+                .build(&path, FixtureImpl::record())
+                .await?;
+
+            runner
+                .setup(&ExtensionConfig {
+                    level_filter: level,
+                })
+                .await?;
+
+            for url in &novel {
+                runner.fetch_novel(url.as_str()).await?;
+            }
+
+            for url in &chapter {
+                runner.fetch_chapter_content(url.as_str()).await?;
+            }
+
+            let mut fixtures = runner.data().fixtures.clone();
+            fixtures.novels = novel.into_iter().map(String::from).collect();
+            fixtures.chapters = chapter.into_iter().map(String::from).collect();
+
+            let fixtures_path = Fixtures::path_for(&path);
+            fixtures.save(&fixtures_path)?;
+
+            println!(
+                "Recorded {} responses to '{}'",
+                fixtures.responses.len(),
+                fixtures_path.display()
+            );
         }
         Commands::Lock { dir } => {
             quelle_lock::Lock::generate(&dir).await?;
@@ -225,7 +348,8 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
                 let request = Request::new(quelle_core::prelude::Method::Get, url);
 
                 use quelle_engine::module::http::{parse_response, send_request_reqwest};
-                let response = send_request_reqwest::<CachingImpl>(client, request).await;
+                let response =
+                    send_request_reqwest::<CachingImpl>(client, request, &data.redirect).await;
                 let response = parse_response(response).await;
 
                 let json = serde_json::to_string(&response).unwrap();
@@ -0,0 +1,223 @@
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    fs,
+    path::{Path, PathBuf},
+    process::Command,
+    time::{Duration, SystemTime},
+};
+
+use anyhow::{bail, Context};
+use log::{debug, error, info};
+use quelle_engine::{data::DefaultImpl, Runtime};
+use serde::Deserialize;
+
+use crate::build::build_extension;
+
+#[derive(Deserialize, Debug)]
+struct Metadata {
+    packages: Vec<Package>,
+    workspace_root: PathBuf,
+}
+
+#[derive(Deserialize, Debug)]
+struct Package {
+    name: String,
+    manifest_path: PathBuf,
+    dependencies: Vec<Dependency>,
+}
+
+#[derive(Deserialize, Debug)]
+struct Dependency {
+    name: String,
+    path: Option<PathBuf>,
+}
+
+/// The local packages of the workspace and how they depend on each other
+struct Workspace {
+    /// Package name to the directory containing its manifest
+    dirs: HashMap<String, PathBuf>,
+    /// Package name to the names of local packages that depend on it
+    dependents: HashMap<String, Vec<String>>,
+    /// Names of the packages that are extensions
+    extensions: HashSet<String>,
+}
+
+impl Workspace {
+    fn load() -> anyhow::Result<Self> {
+        let output = Command::new("cargo")
+            .args(["metadata", "--format-version", "1", "--no-deps"])
+            .output()
+            .context("failed to run 'cargo metadata'")?;
+
+        if !output.status.success() {
+            bail!("'cargo metadata' exited with {}", output.status);
+        }
+
+        let metadata = serde_json::from_slice::<Metadata>(&output.stdout)
+            .context("failed to parse the output of 'cargo metadata'")?;
+
+        let extensions_dir = metadata.workspace_root.join("extensions");
+
+        let mut dirs = HashMap::new();
+        let mut dependents = HashMap::<String, Vec<String>>::new();
+        let mut extensions = HashSet::new();
+
+        for package in metadata.packages {
+            let Some(dir) = package.manifest_path.parent() else {
+                continue;
+            };
+
+            if dir.starts_with(&extensions_dir) {
+                extensions.insert(package.name.clone());
+            }
+
+            for dependency in package.dependencies {
+                if dependency.path.is_some() {
+                    dependents
+                        .entry(dependency.name)
+                        .or_default()
+                        .push(package.name.clone());
+                }
+            }
+
+            dirs.insert(package.name, dir.to_path_buf());
+        }
+
+        Ok(Self {
+            dirs,
+            dependents,
+            extensions,
+        })
+    }
+
+    /// The extensions that need to be rebuilt when the given packages change
+    fn affected_extensions(&self, changed: &[String]) -> Vec<String> {
+        let mut visited = HashSet::new();
+        let mut queue = changed.iter().cloned().collect::<VecDeque<_>>();
+
+        while let Some(name) = queue.pop_front() {
+            if !visited.insert(name.clone()) {
+                continue;
+            }
+
+            if let Some(dependents) = self.dependents.get(&name) {
+                queue.extend(dependents.iter().cloned());
+            }
+        }
+
+        let mut affected = visited
+            .into_iter()
+            .filter(|name| self.extensions.contains(name))
+            .collect::<Vec<_>>();
+
+        affected.sort();
+        affected
+    }
+}
+
+/// Watch every extension and the crates they depend on,
+/// rebuilding and reloading the affected extensions on change.
+pub async fn watch_all(out: PathBuf, release: bool, interval: Duration) -> anyhow::Result<()> {
+    let workspace = Workspace::load()?;
+    let mut runtimes = HashMap::<String, Runtime<DefaultImpl>>::new();
+
+    let mut extensions = workspace.extensions.iter().cloned().collect::<Vec<_>>();
+    extensions.sort();
+    rebuild(&workspace, &extensions, &out, release, &mut runtimes).await;
+
+    let mut modified = modified_times(&workspace);
+    println!("Watching {} extensions for changes...", extensions.len());
+
+    loop {
+        tokio::time::sleep(interval).await;
+
+        let current = modified_times(&workspace);
+        let changed = current
+            .iter()
+            .filter(|(name, time)| modified.get(*name) != Some(time))
+            .map(|(name, _)| name.clone())
+            .collect::<Vec<_>>();
+
+        modified = current;
+        if changed.is_empty() {
+            continue;
+        }
+
+        info!("Detected changes in {}", changed.join(", "));
+
+        let affected = workspace.affected_extensions(&changed);
+        rebuild(&workspace, &affected, &out, release, &mut runtimes).await;
+    }
+}
+
+async fn rebuild(
+    workspace: &Workspace,
+    extensions: &[String],
+    out: &Path,
+    release: bool,
+    runtimes: &mut HashMap<String, Runtime<DefaultImpl>>,
+) {
+    for name in extensions {
+        let Some(dir) = workspace.dirs.get(name) else {
+            continue;
+        };
+
+        if let Err(e) = build_extension(&dir.to_string_lossy(), out, release) {
+            error!("failed to build '{name}': {e}");
+            continue;
+        }
+
+        let path = out.join(format!("{name}.wasm"));
+        match load(&path).await {
+            Ok((runtime, summary)) => {
+                println!("Reloaded {summary}");
+                runtimes.insert(name.clone(), runtime);
+            }
+            Err(e) => error!("failed to load '{}': {e}", path.display()),
+        }
+    }
+}
+
+async fn load(path: &Path) -> anyhow::Result<(Runtime<DefaultImpl>, String)> {
+    let mut runtime = Runtime::new(path).await?;
+    let meta = runtime.meta().await?;
+    let summary = format!("{}=={} ({})", meta.id, meta.version, meta.name);
+    Ok((runtime, summary))
+}
+
+/// The latest modification time of the sources of each local package
+fn modified_times(workspace: &Workspace) -> HashMap<String, SystemTime> {
+    workspace
+        .dirs
+        .iter()
+        .filter_map(|(name, dir)| latest_modified(dir).map(|time| (name.clone(), time)))
+        .collect()
+}
+
+fn latest_modified(dir: &Path) -> Option<SystemTime> {
+    let mut latest = None;
+
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) => {
+            debug!("failed to read '{}': {e}", dir.display());
+            return None;
+        }
+    };
+
+    for entry in entries.flatten() {
+        let path = entry.path();
+        let time = if path.is_dir() {
+            if path.file_name().is_some_and(|name| name == "target") {
+                continue;
+            }
+            latest_modified(&path)
+        } else {
+            entry.metadata().and_then(|m| m.modified()).ok()
+        };
+
+        latest = latest.max(time);
+    }
+
+    latest
+}
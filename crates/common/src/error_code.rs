@@ -0,0 +1,133 @@
+use serde::{Serialize, Serializer};
+
+/// The catalog of errors reported to users
+///
+/// Codes are stable so that scripts can branch on them and users can search for them.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ErrorCode {
+    Unknown,
+    StoreIo,
+    StoreCorrupt,
+    NovelNotFound,
+    SchemaUnsupported,
+    BackupInvalid,
+    BackupConflict,
+    RemoteFailed,
+    NovelExists,
+    LibraryLocked,
+    EpubInvalid,
+    LockUnreadable,
+    SourceNotSupported,
+    ExtensionMissing,
+    ExtensionFailed,
+    ExtensionParseFailed,
+    ExtensionUnsupported,
+    FixturesMissing,
+    ExtensionTestFailed,
+    BudgetExceeded,
+    RequestFailed,
+    HostSuspended,
+    ChaptersFailed,
+    Offline,
+    CredentialMissing,
+    PassphraseRequired,
+    PassphraseWrong,
+    BundleFailed,
+}
+
+impl ErrorCode {
+    pub const ALL: [ErrorCode; 28] = [
+        ErrorCode::Unknown,
+        ErrorCode::StoreIo,
+        ErrorCode::StoreCorrupt,
+        ErrorCode::NovelNotFound,
+        ErrorCode::SchemaUnsupported,
+        ErrorCode::BackupInvalid,
+        ErrorCode::BackupConflict,
+        ErrorCode::RemoteFailed,
+        ErrorCode::NovelExists,
+        ErrorCode::LibraryLocked,
+        ErrorCode::EpubInvalid,
+        ErrorCode::LockUnreadable,
+        ErrorCode::SourceNotSupported,
+        ErrorCode::ExtensionMissing,
+        ErrorCode::ExtensionFailed,
+        ErrorCode::ExtensionParseFailed,
+        ErrorCode::ExtensionUnsupported,
+        ErrorCode::FixturesMissing,
+        ErrorCode::ExtensionTestFailed,
+        ErrorCode::BudgetExceeded,
+        ErrorCode::RequestFailed,
+        ErrorCode::HostSuspended,
+        ErrorCode::ChaptersFailed,
+        ErrorCode::Offline,
+        ErrorCode::CredentialMissing,
+        ErrorCode::PassphraseRequired,
+        ErrorCode::PassphraseWrong,
+        ErrorCode::BundleFailed,
+    ];
+
+    pub fn code(&self) -> &'static str {
+        match self {
+            ErrorCode::Unknown => "E-CLI-001",
+            ErrorCode::StoreIo => "E-STORE-001",
+            ErrorCode::StoreCorrupt => "E-STORE-002",
+            ErrorCode::NovelNotFound => "E-STORE-003",
+            ErrorCode::SchemaUnsupported => "E-STORE-004",
+            ErrorCode::BackupInvalid => "E-STORE-005",
+            ErrorCode::BackupConflict => "E-STORE-006",
+            ErrorCode::RemoteFailed => "E-STORE-007",
+            ErrorCode::NovelExists => "E-STORE-008",
+            ErrorCode::LibraryLocked => "E-STORE-009",
+            ErrorCode::EpubInvalid => "E-STORE-010",
+            ErrorCode::LockUnreadable => "E-LOCK-001",
+            ErrorCode::SourceNotSupported => "E-EXT-001",
+            ErrorCode::ExtensionMissing => "E-EXT-002",
+            ErrorCode::ExtensionFailed => "E-EXT-003",
+            ErrorCode::ExtensionParseFailed => "E-EXT-004",
+            ErrorCode::ExtensionUnsupported => "E-EXT-005",
+            ErrorCode::FixturesMissing => "E-EXT-006",
+            ErrorCode::ExtensionTestFailed => "E-EXT-007",
+            ErrorCode::BudgetExceeded => "E-EXT-008",
+            ErrorCode::RequestFailed => "E-NET-001",
+            ErrorCode::HostSuspended => "E-NET-002",
+            ErrorCode::ChaptersFailed => "E-NET-003",
+            ErrorCode::Offline => "E-NET-004",
+            ErrorCode::CredentialMissing => "E-AUTH-001",
+            ErrorCode::PassphraseRequired => "E-AUTH-002",
+            ErrorCode::PassphraseWrong => "E-AUTH-003",
+            ErrorCode::BundleFailed => "E-BUNDLE-001",
+        }
+    }
+
+    /// The error with the code, such as `E-STORE-003`
+    pub fn from_code(code: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|value| value.code() == code)
+    }
+}
+
+impl Serialize for ErrorCode {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.code())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use super::*;
+
+    #[test]
+    fn should_have_unique_codes() {
+        let codes = ErrorCode::ALL
+            .iter()
+            .map(ErrorCode::code)
+            .collect::<HashSet<_>>();
+
+        assert_eq!(codes.len(), ErrorCode::ALL.len());
+        for code in ErrorCode::ALL {
+            assert_eq!(ErrorCode::from_code(code.code()), Some(code));
+        }
+    }
+}
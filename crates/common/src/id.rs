@@ -0,0 +1,89 @@
+use std::{fmt::Display, str::FromStr};
+
+use serde::{Deserialize, Serialize};
+
+/// Identifies a saved novel as `<source>/<slug>`
+///
+/// The slug is derived from the novel title, matching the layout of the
+/// novel directory in the library.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[serde(try_from = "String", into = "String")]
+pub struct NovelId {
+    pub source: String,
+    pub slug: String,
+}
+
+#[derive(thiserror::Error, Debug, PartialEq, Eq)]
+#[error("invalid novel id '{0}', expected '<source>/<slug>'")]
+pub struct ParseNovelIdError(String);
+
+impl NovelId {
+    /// The id of the novel with the title from the source
+    pub fn new(source: &str, title: &str) -> Self {
+        Self {
+            source: source.to_string(),
+            slug: slug::slugify(title),
+        }
+    }
+}
+
+impl Display for NovelId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}/{}", self.source, self.slug)
+    }
+}
+
+impl FromStr for NovelId {
+    type Err = ParseNovelIdError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.split_once('/') {
+            Some((source, slug))
+                if !source.is_empty() && !slug.is_empty() && !slug.contains('/') =>
+            {
+                Ok(Self {
+                    source: source.to_string(),
+                    slug: slug.to_string(),
+                })
+            }
+            _ => Err(ParseNovelIdError(s.to_string())),
+        }
+    }
+}
+
+impl TryFrom<String> for NovelId {
+    type Error = ParseNovelIdError;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+impl From<NovelId> for String {
+    fn from(value: NovelId) -> Self {
+        value.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_round_trip_ids() {
+        let id = NovelId::new("en.royalroad", "Mother of Learning");
+        assert_eq!(id.to_string(), "en.royalroad/mother-of-learning");
+        assert_eq!("en.royalroad/mother-of-learning".parse(), Ok(id.clone()));
+
+        let json = serde_json::to_string(&id).unwrap();
+        assert_eq!(json, r#""en.royalroad/mother-of-learning""#);
+        assert_eq!(serde_json::from_str::<NovelId>(&json).unwrap(), id);
+    }
+
+    #[test]
+    fn should_reject_malformed_ids() {
+        assert!("royalroad".parse::<NovelId>().is_err());
+        assert!("/slug".parse::<NovelId>().is_err());
+        assert!("source/a/b".parse::<NovelId>().is_err());
+    }
+}
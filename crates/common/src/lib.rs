@@ -0,0 +1,18 @@
+//! User facing types shared by the command line, persistence and bundling
+//!
+//! Types here define wire formats, so clients and scripts that exchange them
+//! agree on a single representation.
+
+mod error_code;
+mod id;
+mod progress;
+mod query;
+mod titles;
+mod url;
+
+pub use error_code::ErrorCode;
+pub use id::{NovelId, ParseNovelIdError};
+pub use progress::ProgressEvent;
+pub use query::{Field, Query, ValueRange};
+pub use titles::TitleRules;
+pub use url::canonical_url;
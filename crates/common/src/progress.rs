@@ -0,0 +1,31 @@
+use serde::{Deserialize, Serialize};
+
+/// The progress of a long running operation such as a download or a bundle
+///
+/// Chapter numbers count from 1 up to `total`, the number of chapters
+/// handled by the operation.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum ProgressEvent {
+    ChapterDownloaded {
+        number: usize,
+        total: usize,
+        title: String,
+    },
+    ChapterSkipped {
+        number: usize,
+        total: usize,
+        title: String,
+    },
+    ChapterFailed {
+        title: String,
+        url: String,
+        reason: String,
+    },
+    /// A chapter was read into the bundle being written
+    ChapterBundled {
+        number: usize,
+        total: usize,
+        title: String,
+    },
+}
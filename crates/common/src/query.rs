@@ -0,0 +1,333 @@
+use std::{
+    fmt::Display,
+    ops::{Bound, RangeBounds},
+    str::FromStr,
+};
+
+use serde::{Deserialize, Serialize};
+
+/// A field of a novel that a query term can match against
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Field {
+    Title,
+    Author,
+    Source,
+    Lang,
+    /// A collection the novel was added to
+    Collection,
+    Status,
+    /// A subject or tag given by the source
+    Tag,
+    /// The date the novel was last saved, as a [`ValueRange`] of `YYYY-MM-DD` dates
+    Updated,
+    /// The number of chapters not downloaded yet, as a [`ValueRange`] of counts
+    Pending,
+}
+
+impl FromStr for Field {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "title" => Ok(Field::Title),
+            "author" => Ok(Field::Author),
+            "source" => Ok(Field::Source),
+            "lang" => Ok(Field::Lang),
+            "collection" => Ok(Field::Collection),
+            "status" => Ok(Field::Status),
+            "tag" => Ok(Field::Tag),
+            "updated" => Ok(Field::Updated),
+            "pending" => Ok(Field::Pending),
+            _ => Err(format!("unknown query field '{s}'")),
+        }
+    }
+}
+
+impl Display for Field {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let value = match self {
+            Field::Title => "title",
+            Field::Author => "author",
+            Field::Source => "source",
+            Field::Lang => "lang",
+            Field::Collection => "collection",
+            Field::Status => "status",
+            Field::Tag => "tag",
+            Field::Updated => "updated",
+            Field::Pending => "pending",
+        };
+
+        write!(f, "{value}")
+    }
+}
+
+/// The syntax tree of a query used to filter novels
+///
+/// The text form is a list of terms that must all match. A term is either
+/// `field:value` or a bare value matched against the title. Alternatives are
+/// separated with `|`, a leading `-` negates the term and double quotes allow
+/// values with spaces.
+///
+/// ## Example
+///
+/// ```text
+/// author:"Tappei Nagatsuki" lang:en|ja -source:novelpub
+/// status:ongoing updated:2023-01-01.. pending:>0
+/// ```
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Query {
+    All(Vec<Query>),
+    Any(Vec<Query>),
+    Not(Box<Query>),
+    Term { field: Field, value: String },
+}
+
+impl Query {
+    /// Evaluate the query, using `term` to decide whether a single term matches
+    pub fn matches<F>(&self, term: &F) -> bool
+    where
+        F: Fn(Field, &str) -> bool,
+    {
+        match self {
+            Query::All(queries) => queries.iter().all(|query| query.matches(term)),
+            Query::Any(queries) => queries.iter().any(|query| query.matches(term)),
+            Query::Not(query) => !query.matches(term),
+            Query::Term { field, value } => term(*field, value),
+        }
+    }
+}
+
+impl FromStr for Query {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let terms = split_terms(s)?
+            .into_iter()
+            .map(|term| parse_term(&term))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(Query::All(terms))
+    }
+}
+
+fn parse_term(term: &str) -> Result<Query, String> {
+    if let Some(term) = term.strip_prefix('-') {
+        return Ok(Query::Not(Box::new(parse_term(term)?)));
+    }
+
+    let (field, values) = match term.split_once(':') {
+        Some((field, values)) => (field.parse()?, values),
+        None => (Field::Title, term),
+    };
+
+    let mut alternatives = values
+        .split('|')
+        .filter(|value| !value.is_empty())
+        .map(|value| {
+            match field {
+                Field::Updated => {
+                    let range = value.parse::<ValueRange<String>>()?;
+                    if !range.bounds().all(is_date) {
+                        return Err(format!("'{value}' is not a range of YYYY-MM-DD dates"));
+                    }
+                }
+                Field::Pending => {
+                    value.parse::<ValueRange<usize>>()?;
+                }
+                _ => {}
+            }
+
+            Ok(Query::Term {
+                field,
+                value: value.to_string(),
+            })
+        })
+        .collect::<Result<Vec<_>, String>>()?;
+
+    match alternatives.len() {
+        0 => Err(format!("missing value for query field '{field}'")),
+        1 => Ok(alternatives.remove(0)),
+        _ => Ok(Query::Any(alternatives)),
+    }
+}
+
+/// A range of values matched by a query term
+///
+/// Written as `a..b`, `a..` or `..b` with inclusive ends, as `>a`, `>=a`, `<b`
+/// or `<=b`, or as a single value that must match exactly.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ValueRange<T> {
+    start: Bound<T>,
+    end: Bound<T>,
+}
+
+impl<T> ValueRange<T> {
+    fn bounds(&self) -> impl Iterator<Item = &T> {
+        [&self.start, &self.end]
+            .into_iter()
+            .filter_map(|bound| match bound {
+                Bound::Included(value) | Bound::Excluded(value) => Some(value),
+                Bound::Unbounded => None,
+            })
+    }
+}
+
+impl<T: PartialOrd> ValueRange<T> {
+    pub fn contains(&self, value: &T) -> bool {
+        RangeBounds::contains(self, value)
+    }
+}
+
+impl<T> RangeBounds<T> for ValueRange<T> {
+    fn start_bound(&self) -> Bound<&T> {
+        self.start.as_ref()
+    }
+
+    fn end_bound(&self) -> Bound<&T> {
+        self.end.as_ref()
+    }
+}
+
+impl<T: FromStr> FromStr for ValueRange<T> {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parse = |value: &str| {
+            value
+                .parse::<T>()
+                .map_err(|_| format!("invalid value '{value}' in range '{s}'"))
+        };
+
+        let (start, end) = if let Some((start, end)) = s.split_once("..") {
+            let start = match start {
+                "" => Bound::Unbounded,
+                start => Bound::Included(parse(start)?),
+            };
+            let end = match end {
+                "" => Bound::Unbounded,
+                end => Bound::Included(parse(end)?),
+            };
+            (start, end)
+        } else if let Some(value) = s.strip_prefix(">=") {
+            (Bound::Included(parse(value)?), Bound::Unbounded)
+        } else if let Some(value) = s.strip_prefix("<=") {
+            (Bound::Unbounded, Bound::Included(parse(value)?))
+        } else if let Some(value) = s.strip_prefix('>') {
+            (Bound::Excluded(parse(value)?), Bound::Unbounded)
+        } else if let Some(value) = s.strip_prefix('<') {
+            (Bound::Unbounded, Bound::Excluded(parse(value)?))
+        } else {
+            (Bound::Included(parse(s)?), Bound::Included(parse(s)?))
+        };
+
+        Ok(ValueRange { start, end })
+    }
+}
+
+/// Whether the value is a date of the form `YYYY-MM-DD`, which sorts like the date
+fn is_date(value: &String) -> bool {
+    let bytes = value.as_bytes();
+    bytes.len() == 10
+        && bytes.iter().enumerate().all(|(i, byte)| match i {
+            4 | 7 => *byte == b'-',
+            _ => byte.is_ascii_digit(),
+        })
+}
+
+/// Split the input on whitespace outside of double quotes, removing the quotes
+fn split_terms(input: &str) -> Result<Vec<String>, String> {
+    let mut terms = vec![];
+    let mut current = String::new();
+    let mut quoted = false;
+
+    for c in input.chars() {
+        match c {
+            '"' => quoted = !quoted,
+            c if c.is_whitespace() && !quoted => {
+                if !current.is_empty() {
+                    terms.push(std::mem::take(&mut current));
+                }
+            }
+            c => current.push(c),
+        }
+    }
+
+    if quoted {
+        return Err(String::from("unclosed '\"' in query"));
+    }
+
+    if !current.is_empty() {
+        terms.push(current);
+    }
+
+    Ok(terms)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn term(field: Field, value: &str) -> Query {
+        Query::Term {
+            field,
+            value: value.to_string(),
+        }
+    }
+
+    #[test]
+    fn should_parse_terms() {
+        let query = r#"author:"Tappei Nagatsuki" lang:en|ja -source:novelpub zero"#
+            .parse::<Query>()
+            .unwrap();
+
+        assert_eq!(
+            query,
+            Query::All(vec![
+                term(Field::Author, "Tappei Nagatsuki"),
+                Query::Any(vec![term(Field::Lang, "en"), term(Field::Lang, "ja")]),
+                Query::Not(Box::new(term(Field::Source, "novelpub"))),
+                term(Field::Title, "zero"),
+            ])
+        );
+
+        assert!("genre:action".parse::<Query>().is_err());
+        assert!("author:".parse::<Query>().is_err());
+        assert!("\"unclosed".parse::<Query>().is_err());
+        assert!("updated:yesterday".parse::<Query>().is_err());
+        assert!("pending:>some".parse::<Query>().is_err());
+    }
+
+    #[test]
+    fn should_parse_ranges() {
+        let range = "2..5".parse::<ValueRange<usize>>().unwrap();
+        assert!(range.contains(&2) && range.contains(&5) && !range.contains(&6));
+
+        let range = ">0".parse::<ValueRange<usize>>().unwrap();
+        assert!(!range.contains(&0) && range.contains(&1));
+
+        let range = "3".parse::<ValueRange<usize>>().unwrap();
+        assert!(range.contains(&3) && !range.contains(&4));
+
+        let range = "2023-01-01..".parse::<ValueRange<String>>().unwrap();
+        assert!(range.contains(&String::from("2023-06-15")));
+        assert!(!range.contains(&String::from("2022-12-31")));
+    }
+
+    #[test]
+    fn should_evaluate_query() {
+        let query = "lang:en|ja -source:novelpub".parse::<Query>().unwrap();
+        let novel = |source: &'static str| {
+            move |field: Field, value: &str| match field {
+                Field::Lang => value == "en",
+                Field::Source => value == source,
+                _ => false,
+            }
+        };
+
+        assert!(query.matches(&novel("royalroad")));
+        assert!(!query.matches(&novel("novelpub")));
+        assert!(Query::All(vec![]).matches(&novel("novelpub")));
+    }
+}
@@ -0,0 +1,164 @@
+use serde::{Deserialize, Serialize};
+
+/// Words that may precede a chapter number at the start of a title
+const NUMBER_WORDS: [&str; 8] = [
+    "chapter", "chap.", "chap", "ch.", "ch", "episode", "ep.", "ep",
+];
+
+/// Rules to normalize chapter titles when exporting
+///
+/// Scraped titles are inconsistent between sources, some repeat the chapter
+/// number the exporter already shows while others have no number at all.
+///
+/// The template supports `{number}`, `{title}` and `{position}`, the place of
+/// the chapter in the novel starting at 1.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, Eq)]
+pub struct TitleRules {
+    /// Remove chapter numbers at the start of titles, ex: `Chapter 123 - `
+    #[serde(default)]
+    pub strip_prefix: bool,
+    /// The template of every title, ex: `Chapter {number}: {title}`
+    #[serde(default)]
+    pub template: Option<String>,
+    /// Number chapters sequentially across volumes instead of using the source numbers
+    #[serde(default)]
+    pub renumber: bool,
+}
+
+impl TitleRules {
+    /// Whether the rules leave titles as they are
+    pub fn is_default(&self) -> bool {
+        self == &Self::default()
+    }
+
+    /// The title of a chapter with its label or number given by the source
+    pub fn apply(&self, title: &str, number: Option<&str>, position: usize) -> String {
+        let title = if self.strip_prefix {
+            strip_number_prefix(title)
+        } else {
+            title.trim()
+        };
+
+        let position = position.to_string();
+        let number = match number {
+            Some(number) if !self.renumber => number,
+            _ => position.as_str(),
+        };
+
+        let Some(template) = &self.template else {
+            return if title.starts_with(number) {
+                title.to_string()
+            } else {
+                format!("{number}: {title}")
+            };
+        };
+
+        let mut rendered = String::with_capacity(template.len() + title.len());
+        let mut rest = template.as_str();
+        while let Some(start) = rest.find('{') {
+            rendered.push_str(&rest[..start]);
+
+            let Some(end) = rest[start..].find('}') else {
+                break;
+            };
+
+            match &rest[start + 1..start + end] {
+                "number" => rendered.push_str(number),
+                "title" => rendered.push_str(title),
+                "position" => rendered.push_str(&position),
+                _ => rendered.push_str(&rest[start..=start + end]),
+            }
+            rest = &rest[start + end + 1..];
+        }
+
+        rendered.push_str(rest);
+        rendered
+    }
+}
+
+/// Remove every chapter number at the start of the title, keeping titles that are only a number
+fn strip_number_prefix(title: &str) -> &str {
+    let mut title = title.trim();
+    while let Some(rest) = strip_number_once(title) {
+        if rest.is_empty() || rest.len() == title.len() {
+            break;
+        }
+        title = rest;
+    }
+
+    title
+}
+
+fn strip_number_once(title: &str) -> Option<&str> {
+    let lower = title.to_ascii_lowercase();
+    let rest = NUMBER_WORDS
+        .iter()
+        .find_map(|word| lower.strip_prefix(word))
+        .map(|rest| &title[title.len() - rest.len()..])
+        .unwrap_or(title)
+        .trim_start();
+
+    if !rest.starts_with(|c: char| c.is_ascii_digit()) {
+        return None;
+    }
+
+    let end = rest
+        .find(|c: char| !c.is_ascii_digit() && c != '.')
+        .unwrap_or(rest.len());
+    let rest = &rest[end..];
+
+    let stripped = rest.trim_start_matches(|c: char| {
+        c.is_whitespace() || matches!(c, '-' | ':' | '.' | ')' | '–' | '—')
+    });
+
+    // A number followed by letters is part of the title, ex: 12th
+    if stripped.len() == rest.len() && !rest.is_empty() {
+        return None;
+    }
+
+    Some(stripped)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_strip_repeated_prefixes() {
+        assert_eq!(
+            strip_number_prefix("Chapter 123 - Chapter 123 - The Fall"),
+            "The Fall"
+        );
+        assert_eq!(strip_number_prefix("Ch. 12: Return"), "Return");
+        assert_eq!(strip_number_prefix("121.5 Interlude"), "Interlude");
+        assert_eq!(strip_number_prefix("Chapter 7"), "Chapter 7");
+        assert_eq!(strip_number_prefix("12th Night"), "12th Night");
+        assert_eq!(strip_number_prefix("Chapel of Rest"), "Chapel of Rest");
+    }
+
+    #[test]
+    fn should_apply_template_and_numbering() {
+        let rules = TitleRules {
+            strip_prefix: true,
+            template: Some(String::from("Chapter {number}: {title}")),
+            renumber: false,
+        };
+        assert_eq!(
+            rules.apply("Chapter 3 - Home", Some("3"), 5),
+            "Chapter 3: Home"
+        );
+
+        let rules = TitleRules {
+            renumber: true,
+            ..rules
+        };
+        assert_eq!(
+            rules.apply("Chapter 3 - Home", Some("3"), 5),
+            "Chapter 5: Home"
+        );
+
+        let rules = TitleRules::default();
+        assert_eq!(rules.apply("Home", Some("3"), 5), "3: Home");
+        assert_eq!(rules.apply("Home", None, 5), "5: Home");
+    }
+}
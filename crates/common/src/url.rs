@@ -0,0 +1,58 @@
+use url::Url;
+
+/// The form of the url used to recognise the same page across sources and novels
+///
+/// The fragment, the `www.` host prefix and a trailing slash are removed and
+/// `http` is upgraded to `https`. Values that are not urls are only trimmed.
+pub fn canonical_url(value: &str) -> String {
+    let value = value.trim();
+    let Ok(mut url) = Url::parse(value) else {
+        return value.to_string();
+    };
+
+    url.set_fragment(None);
+
+    if url.scheme() == "http" {
+        let _ = url.set_scheme("https");
+    }
+
+    if let Some(host) = url.host_str().and_then(|host| host.strip_prefix("www.")) {
+        let host = host.to_string();
+        let _ = url.set_host(Some(&host));
+    }
+
+    if url.path().len() > 1 && url.path().ends_with('/') {
+        let path = url.path().trim_end_matches('/').to_string();
+        url.set_path(&path);
+    }
+
+    let mut value = url.to_string();
+    if url.query().is_none() && url.path() == "/" {
+        value.pop();
+    }
+
+    value
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_canonicalize_equivalent_urls() {
+        let expected = "https://example.com/novel/1/chapter-2";
+        for value in [
+            "https://example.com/novel/1/chapter-2",
+            "http://example.com/novel/1/chapter-2/",
+            "https://www.Example.com/novel/1/chapter-2#comments",
+        ] {
+            assert_eq!(canonical_url(value), expected);
+        }
+
+        assert_eq!(
+            canonical_url("https://example.com/read?id=2"),
+            "https://example.com/read?id=2"
+        );
+        assert_eq!(canonical_url("http://example.com/"), "https://example.com");
+    }
+}
@@ -1,7 +1,7 @@
 use log::LevelFilter;
 use serde::{Deserialize, Serialize};
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct ExtensionConfig {
     pub level_filter: LevelFilter,
 }
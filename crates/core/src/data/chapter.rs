@@ -1,4 +1,4 @@
-use chrono::{DateTime, NaiveDateTime, Utc};
+use chrono::{DateTime, NaiveDate, NaiveDateTime, Utc};
 use serde::{Deserialize, Serialize};
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -7,6 +7,51 @@ pub struct Chapter {
     pub title: String,
     pub url: String,
     pub updated_at: Option<TaggedDateTime>,
+    /// The absolute chapter number given by the source
+    #[serde(default)]
+    pub number: Option<u32>,
+    /// The decimal part of the number, ex: 5 for interlude chapter 121.5
+    #[serde(default)]
+    pub part: Option<u32>,
+    /// A short label to display instead of the number, ex: Prologue
+    #[serde(default)]
+    pub label: Option<String>,
+}
+
+impl Chapter {
+    /// Split a chapter number such as `121.5` into the number and its part
+    pub fn parse_number(value: &str) -> Option<(u32, Option<u32>)> {
+        let value = value
+            .trim()
+            .trim_start_matches(|c: char| !c.is_ascii_digit());
+        let end = value
+            .find(|c: char| !c.is_ascii_digit() && c != '.')
+            .unwrap_or(value.len());
+
+        match value[..end].trim_end_matches('.').split_once('.') {
+            Some((number, part)) => Some((number.parse().ok()?, part.parse().ok())),
+            None => Some((value[..end].trim_end_matches('.').parse().ok()?, None)),
+        }
+    }
+
+    /// The number of the chapter including its part, ex: `121.5`
+    pub fn display_number(&self) -> Option<String> {
+        match (self.number, self.part) {
+            (Some(number), Some(part)) => Some(format!("{number}.{part}")),
+            (Some(number), None) => Some(number.to_string()),
+            _ => None,
+        }
+    }
+
+    /// The title shown in tables of contents
+    ///
+    /// The label or number is prepended unless the title already starts with it.
+    pub fn toc_title(&self) -> String {
+        match self.label.clone().or_else(|| self.display_number()) {
+            Some(label) if !self.title.starts_with(&label) => format!("{label}: {}", self.title),
+            _ => self.title.clone(),
+        }
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -16,6 +61,16 @@ pub enum TaggedDateTime {
     Local(NaiveDateTime),
 }
 
+impl TaggedDateTime {
+    /// The calendar date, as given by the source
+    pub fn date(&self) -> NaiveDate {
+        match self {
+            TaggedDateTime::Utc(value) => value.date_naive(),
+            TaggedDateTime::Local(value) => value.date(),
+        }
+    }
+}
+
 #[derive(Serialize, Deserialize, Default, Debug)]
 pub struct Content {
     pub data: String,
@@ -26,3 +81,16 @@ impl From<String> for Content {
         Content { data: value }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::Chapter;
+
+    #[test]
+    fn should_parse_chapter_numbers() {
+        assert_eq!(Chapter::parse_number("121"), Some((121, None)));
+        assert_eq!(Chapter::parse_number("Chapter 121.5"), Some((121, Some(5))));
+        assert_eq!(Chapter::parse_number("12. The end"), Some((12, None)));
+        assert_eq!(Chapter::parse_number("Prologue"), None);
+    }
+}
@@ -13,6 +13,21 @@ pub struct Meta {
     pub base_urls: Vec<String>,
     pub rds: Vec<ReadingDirection>,
     pub attrs: Vec<Attribute>,
+    /// Patterns of novel urls where `*` matches any text, ex: `https://example.com/novel/*`
+    #[serde(default)]
+    pub novel_url_patterns: Vec<String>,
+    /// What the source needs to show mature content, if it hides it by default
+    #[serde(default)]
+    pub age_gate: Option<AgeGate>,
+}
+
+/// The cookies and headers a source expects once the reader confirmed their age
+#[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq, Eq)]
+pub struct AgeGate {
+    #[serde(default)]
+    pub cookies: Vec<(String, String)>,
+    #[serde(default)]
+    pub headers: Vec<(String, String)>,
 }
 
 impl Meta {
@@ -49,6 +64,42 @@ impl Meta {
     pub fn home_url(&self) -> &str {
         &self.base_urls[0]
     }
+
+    /// Whether the url points to a novel, or none when the source has no patterns
+    pub fn is_novel_url(&self, url: &str) -> Option<bool> {
+        if self.novel_url_patterns.is_empty() {
+            return None;
+        }
+
+        Some(
+            self.novel_url_patterns
+                .iter()
+                .any(|pattern| matches_url_pattern(pattern, url)),
+        )
+    }
+}
+
+/// Whether the url matches the pattern, where `*` matches any text
+pub fn matches_url_pattern(pattern: &str, url: &str) -> bool {
+    let mut parts = pattern.split('*');
+    let first = parts.next().unwrap_or_default();
+    let Some(mut rest) = url.strip_prefix(first) else {
+        return false;
+    };
+
+    let parts = parts.collect::<Vec<_>>();
+    let Some((last, middle)) = parts.split_last() else {
+        return rest.is_empty();
+    };
+
+    for part in middle {
+        match rest.find(part) {
+            Some(index) => rest = &rest[index + part.len()..],
+            None => return false,
+        }
+    }
+
+    rest.ends_with(last)
 }
 
 fn base_url(url: Url) -> String {
@@ -157,6 +208,33 @@ mod tests {
         );
     }
 
+    #[test]
+    fn should_match_url_patterns() {
+        let meta = Meta {
+            novel_url_patterns: vec![String::from("https://example.com/fiction/*")],
+            ..Default::default()
+        };
+
+        assert_eq!(
+            meta.is_novel_url("https://example.com/fiction/12/name"),
+            Some(true)
+        );
+        assert_eq!(
+            meta.is_novel_url("https://example.com/chapter/12"),
+            Some(false)
+        );
+        assert_eq!(Meta::default().is_novel_url("https://example.com"), None);
+
+        assert!(matches_url_pattern(
+            "https://*.example.com/*.html",
+            "https://www.example.com/a.html"
+        ));
+        assert!(!matches_url_pattern(
+            "https://example.com/novel",
+            "https://example.com/novel/1"
+        ));
+    }
+
     #[test]
     fn should_get_base_url() {
         assert_eq!(
@@ -1,6 +1,6 @@
 use serde::{Deserialize, Serialize};
 
-use super::{Metadata, NovelStatus, Volume};
+use super::{Chapter, Metadata, NovelStatus, Volume};
 
 #[derive(Serialize, Deserialize, Debug, Default)]
 pub struct Novel {
@@ -15,6 +15,33 @@ pub struct Novel {
     pub langs: Vec<String>,
 }
 
+impl Novel {
+    /// The license or attribution reported by the source as `rights` metadata
+    pub fn rights(&self) -> Option<&str> {
+        self.metadata
+            .iter()
+            .find(|metadata| metadata.name == "rights")
+            .map(|metadata| metadata.value.as_str())
+    }
+
+    /// Keep only the chapters matching the predicate, removing volumes left empty,
+    /// and return the number of chapters removed
+    pub fn retain_chapters<F>(&mut self, mut predicate: F) -> usize
+    where
+        F: FnMut(&Chapter) -> bool,
+    {
+        let mut removed = 0;
+        for volume in self.volumes.iter_mut() {
+            let before = volume.chapters.len();
+            volume.chapters.retain(|chapter| predicate(chapter));
+            removed += before - volume.chapters.len();
+        }
+
+        self.volumes.retain(|volume| !volume.chapters.is_empty());
+        removed
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug, Default)]
 pub struct BasicNovel {
     pub title: String,
@@ -12,7 +12,7 @@ pub struct Request {
     pub headers: Option<String>,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Clone, Debug)]
 pub enum Body {
     Form(HashMap<String, String>),
 }
@@ -57,7 +57,7 @@ impl Request {
     }
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
 pub enum Method {
     Get,
     Post,
@@ -71,6 +71,12 @@ pub struct Response {
     pub status: usize,
     pub body: Option<Vec<u8>>,
     pub headers: Option<String>,
+    /// The final url of the response after following redirects
+    #[serde(default)]
+    pub url: Option<String>,
+    /// The urls that were redirected from, in the order they were visited
+    #[serde(default)]
+    pub redirects: Vec<String>,
 }
 
 impl Response {
@@ -80,6 +86,17 @@ impl Response {
             .map(|body| std::str::from_utf8(body))
             .transpose()
     }
+
+    /// The url the response was served from, if known
+    #[inline]
+    pub fn final_url(&self) -> Option<&str> {
+        self.url.as_deref()
+    }
+
+    #[inline]
+    pub fn is_redirected(&self) -> bool {
+        !self.redirects.is_empty()
+    }
 }
 
 #[derive(Serialize, Deserialize, thiserror::Error, Debug)]
@@ -92,6 +109,12 @@ pub struct RequestError {
     pub message: String,
 }
 
+impl BoxedRequestError {
+    pub fn kind(&self) -> &RequestErrorKind {
+        &self.0.kind
+    }
+}
+
 impl std::fmt::Display for BoxedRequestError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(f, "{}", self.0)
@@ -109,10 +132,14 @@ impl std::fmt::Display for RequestError {
 pub enum RequestErrorKind {
     Serial,
     Request,
+    /// No connection to the host could be opened
+    Connect,
     Redirect,
     Status(u16),
     Body,
     Timeout,
+    /// The host refused the request as the call exceeded its budget
+    Budget,
     Unknown,
 }
 
@@ -147,6 +174,8 @@ impl From<reqwest::Error> for RequestError {
             RequestErrorKind::Body
         } else if error.is_redirect() {
             RequestErrorKind::Redirect
+        } else if error.is_connect() {
+            RequestErrorKind::Connect
         } else if error.is_request() {
             RequestErrorKind::Request
         } else if error.is_status() {
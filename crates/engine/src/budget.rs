@@ -0,0 +1,161 @@
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+
+/// Limits on the requests an extension may make during a single call
+///
+/// A buggy extension could otherwise keep paginating forever, for example
+/// during a scheduled update. Unset limits are not enforced.
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct Budget {
+    /// The maximum number of http requests
+    pub max_requests: Option<usize>,
+    /// The maximum number of response body bytes
+    pub max_bytes: Option<usize>,
+    /// The maximum time spent from the start of the call
+    pub deadline: Option<Duration>,
+}
+
+impl Budget {
+    pub fn max_requests(mut self, value: usize) -> Self {
+        self.max_requests = Some(value);
+        self
+    }
+
+    pub fn max_bytes(mut self, value: usize) -> Self {
+        self.max_bytes = Some(value);
+        self
+    }
+
+    pub fn deadline(mut self, value: Duration) -> Self {
+        self.deadline = Some(value);
+        self
+    }
+}
+
+/// The limit of a budget that was exceeded
+#[derive(thiserror::Error, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub enum BudgetExceeded {
+    #[error("exceeded the budget of {0} requests")]
+    Requests(usize),
+
+    #[error("exceeded the budget of {0} bytes")]
+    Bytes(usize),
+
+    #[error("exceeded the deadline of {0:?}")]
+    Deadline(Duration),
+}
+
+/// The budget spent by the current extension call
+#[derive(Debug)]
+pub struct BudgetTracker {
+    budget: Budget,
+    requests: usize,
+    bytes: usize,
+    started: Instant,
+    exceeded: Option<BudgetExceeded>,
+}
+
+impl Default for BudgetTracker {
+    fn default() -> Self {
+        Self::new(Budget::default())
+    }
+}
+
+impl BudgetTracker {
+    pub fn new(budget: Budget) -> Self {
+        Self {
+            budget,
+            requests: 0,
+            bytes: 0,
+            started: Instant::now(),
+            exceeded: None,
+        }
+    }
+
+    /// Count a request about to be sent, failing if it is over the budget
+    pub fn start_request(&mut self) -> Result<(), BudgetExceeded> {
+        self.check_deadline()?;
+
+        self.requests += 1;
+        match self.budget.max_requests {
+            Some(max) if self.requests > max => self.exceed(BudgetExceeded::Requests(max)),
+            _ => Ok(()),
+        }
+    }
+
+    /// Count the bytes of a received response body
+    pub fn record_bytes(&mut self, len: usize) -> Result<(), BudgetExceeded> {
+        self.bytes += len;
+        match self.budget.max_bytes {
+            Some(max) if self.bytes > max => self.exceed(BudgetExceeded::Bytes(max)),
+            _ => Ok(()),
+        }
+    }
+
+    /// The bytes left before the response bodies are over the budget
+    pub fn remaining_bytes(&self) -> Option<usize> {
+        self.budget
+            .max_bytes
+            .map(|max| max.saturating_sub(self.bytes))
+    }
+
+    /// The time left before the deadline, used as the timeout of a request
+    pub fn remaining_time(&self) -> Option<Duration> {
+        self.budget
+            .deadline
+            .map(|deadline| deadline.saturating_sub(self.started.elapsed()))
+    }
+
+    /// The exceeded limit since the tracker was created, if any
+    pub fn exceeded(&self) -> Option<&BudgetExceeded> {
+        self.exceeded.as_ref()
+    }
+
+    fn check_deadline(&mut self) -> Result<(), BudgetExceeded> {
+        match (self.budget.deadline, self.remaining_time()) {
+            (Some(deadline), Some(remaining)) if remaining.is_zero() => {
+                self.exceed(BudgetExceeded::Deadline(deadline))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    fn exceed(&mut self, exceeded: BudgetExceeded) -> Result<(), BudgetExceeded> {
+        self.exceeded.get_or_insert(exceeded.clone());
+        Err(exceeded)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_enforce_limits() {
+        let mut tracker = BudgetTracker::new(Budget::default().max_requests(2).max_bytes(10));
+
+        assert!(tracker.start_request().is_ok());
+        assert!(tracker.record_bytes(6).is_ok());
+        assert_eq!(tracker.remaining_bytes(), Some(4));
+        assert!(tracker.start_request().is_ok());
+        assert_eq!(tracker.record_bytes(6), Err(BudgetExceeded::Bytes(10)));
+        assert_eq!(tracker.start_request(), Err(BudgetExceeded::Requests(2)));
+
+        // The first exceeded limit is the one reported for the call
+        assert_eq!(tracker.exceeded(), Some(&BudgetExceeded::Bytes(10)));
+    }
+
+    #[test]
+    fn should_enforce_deadline() {
+        let mut tracker = BudgetTracker::new(Budget::default().deadline(Duration::ZERO));
+        assert_eq!(
+            tracker.start_request(),
+            Err(BudgetExceeded::Deadline(Duration::ZERO))
+        );
+
+        let mut tracker = BudgetTracker::default();
+        assert!(tracker.start_request().is_ok());
+        assert_eq!(tracker.remaining_time(), None);
+    }
+}
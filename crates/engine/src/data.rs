@@ -1,3 +1,34 @@
+use std::sync::Arc;
+
+use crate::{
+    budget::BudgetTracker,
+    hooks::{self, EngineHooks},
+    module::http::{RedirectPolicy, Session},
+};
+
 pub struct DefaultImpl {
     pub client: reqwest::Client,
+    pub redirect: RedirectPolicy,
+    /// The requests spent by the current call, see [`crate::Runtime::within_budget`]
+    pub budget: BudgetTracker,
+    /// Authenticates the requests to the source when set
+    pub session: Option<Session>,
+    /// Notified of every request sent by the extension
+    pub hooks: Arc<dyn EngineHooks>,
+}
+
+impl DefaultImpl {
+    pub fn new(redirect: RedirectPolicy) -> Self {
+        Self {
+            client: reqwest::Client::builder()
+                .user_agent("Mozilla/5.0 (X11; Fedora; Linux x86_64; rv:107.0) Gecko/20100101 Firefox/107.0")
+                .redirect(reqwest::redirect::Policy::none())
+                .build()
+                .unwrap(),
+            redirect,
+            budget: Default::default(),
+            session: None,
+            hooks: hooks::default_hooks(),
+        }
+    }
 }
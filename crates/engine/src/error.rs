@@ -3,6 +3,8 @@ use std::{fmt::Display, string::FromUtf8Error};
 use quelle_core::prelude::QuelleError;
 use wasmtime::Trap;
 
+use crate::budget::BudgetExceeded;
+
 pub type Result<T> = std::result::Result<T, Error>;
 
 #[derive(thiserror::Error, Debug)]
@@ -31,6 +33,12 @@ pub enum Error {
     #[error("wasm memory access error")]
     MemoryAccessError,
 
+    #[error("extension process failed: {0}")]
+    ProcessError(String),
+
+    #[error("{0}")]
+    BudgetExceeded(#[from] BudgetExceeded),
+
     #[error("{0}")]
     Other(#[from] anyhow::Error),
 }
@@ -0,0 +1,191 @@
+//! Recorded HTTP responses that let an extension run without network.
+//!
+//! Fixtures are stored next to the wasm file as `<name>.fixtures.json` and
+//! hold the responses of every request made while fetching the recorded
+//! novels and chapters. Running the same calls against the fixtures
+//! verifies that an installed extension works.
+
+use std::{
+    collections::BTreeMap,
+    fs::{self, File},
+    future::Future,
+    io::BufReader,
+    path::{Path, PathBuf},
+};
+
+use quelle_core::prelude::*;
+use serde::{Deserialize, Serialize};
+use wasmtime::Caller;
+
+use crate::{
+    error::{self, Error},
+    module::{
+        http::{parse_response, read_request, send_request_reqwest, RedirectPolicy},
+        utils::write_str,
+    },
+    Runtime,
+};
+
+/// The maximum size of a fixtures file so that packages stay small
+pub const MAX_FIXTURES_SIZE: usize = 5 * 1024 * 1024;
+
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
+pub struct Fixtures {
+    /// Urls of the novels fetched when verifying
+    #[serde(default)]
+    pub novels: Vec<String>,
+    /// Urls of the chapters fetched when verifying
+    #[serde(default)]
+    pub chapters: Vec<String>,
+    /// Serialized responses passed to the extension keyed by request url
+    #[serde(default)]
+    pub responses: BTreeMap<String, String>,
+}
+
+impl Fixtures {
+    /// The location of the fixtures for the wasm extension
+    pub fn path_for(wasm_path: &Path) -> PathBuf {
+        wasm_path.with_extension("fixtures.json")
+    }
+
+    pub fn open(path: &Path) -> error::Result<Self> {
+        let file = File::open(path).map_err(anyhow::Error::from)?;
+        serde_json::from_reader(BufReader::new(file)).map_err(|_| Error::DeserializeError)
+    }
+
+    /// Write the fixtures, failing when they exceed [`MAX_FIXTURES_SIZE`]
+    pub fn save(&self, path: &Path) -> error::Result<()> {
+        let json = serde_json::to_vec(self).map_err(|_| Error::SerializeError)?;
+        if json.len() > MAX_FIXTURES_SIZE {
+            return Err(anyhow::anyhow!(
+                "fixtures are {} bytes, larger than the limit of {MAX_FIXTURES_SIZE} bytes",
+                json.len()
+            )
+            .into());
+        }
+
+        fs::write(path, json).map_err(anyhow::Error::from)?;
+        Ok(())
+    }
+}
+
+/// Serves requests from fixtures, optionally recording missing responses
+pub struct FixtureImpl {
+    pub client: reqwest::Client,
+    pub redirect: RedirectPolicy,
+    pub fixtures: Fixtures,
+    pub record: bool,
+}
+
+impl FixtureImpl {
+    /// Replay the fixtures, failing requests that were not recorded
+    pub fn replay(fixtures: Fixtures) -> Self {
+        Self {
+            client: reqwest::Client::new(),
+            redirect: RedirectPolicy::default(),
+            fixtures,
+            record: false,
+        }
+    }
+
+    /// Send requests over the network and record their responses
+    pub fn record() -> Self {
+        Self {
+            client: reqwest::Client::builder()
+                .user_agent("Mozilla/5.0 (X11; Fedora; Linux x86_64; rv:107.0) Gecko/20100101 Firefox/107.0")
+                .redirect(reqwest::redirect::Policy::none())
+                .build()
+                .unwrap(),
+            redirect: RedirectPolicy::default(),
+            fixtures: Fixtures::default(),
+            record: true,
+        }
+    }
+}
+
+pub fn send_request<'a>(
+    mut caller: Caller<'a, FixtureImpl>,
+    ptr: i32,
+    len: i32,
+) -> Box<dyn Future<Output = i32> + Send + 'a> {
+    Box::new(async move {
+        let memory = caller.get_export("memory").unwrap().into_memory().unwrap();
+        let request = read_request(&mut caller, ptr, len, &memory);
+
+        let recorded = caller.data().fixtures.responses.get(&request.url).cloned();
+        let json = match recorded {
+            Some(json) => json,
+            None if caller.data().record => {
+                let key = request.url.clone();
+                let FixtureImpl {
+                    client, redirect, ..
+                } = caller.data();
+
+                let response = send_request_reqwest::<FixtureImpl>(client, request, redirect).await;
+                let response = parse_response(response).await;
+
+                let json = serde_json::to_string(&response).unwrap();
+                caller
+                    .data_mut()
+                    .fixtures
+                    .responses
+                    .insert(key, json.clone());
+                json
+            }
+            None => {
+                let response: Result<Response, RequestError> = Err(RequestError {
+                    kind: RequestErrorKind::Unknown,
+                    message: String::from("no fixture recorded for the url"),
+                    url: Some(request.url),
+                });
+                serde_json::to_string(&response).unwrap()
+            }
+        };
+
+        write_str(&mut caller, &memory, json.as_str()).await
+    })
+}
+
+/// The outcome of a single verification call
+#[derive(Debug)]
+pub struct FixtureCheck {
+    pub url: String,
+    pub result: error::Result<()>,
+}
+
+/// Run the recorded calls of the extension against its fixtures
+pub async fn verify(wasm_path: &Path, fixtures: Fixtures) -> error::Result<Vec<FixtureCheck>> {
+    let novels = fixtures.novels.clone();
+    let chapters = fixtures.chapters.clone();
+
+    let mut runtime = Runtime::builder()
+        .send_request(send_request)
+        .build(wasm_path, FixtureImpl::replay(fixtures))
+        .await?;
+
+    let mut checks = vec![];
+
+    for url in novels {
+        let result: error::Result<()> = match runtime.fetch_novel(&url).await {
+            Ok(novel) if novel.title.is_empty() => {
+                Err(anyhow::anyhow!("the novel title is empty").into())
+            }
+            Ok(_) => Ok(()),
+            Err(e) => Err(e),
+        };
+        checks.push(FixtureCheck { url, result });
+    }
+
+    for url in chapters {
+        let result: error::Result<()> = match runtime.fetch_chapter_content(&url).await {
+            Ok(content) if content.data.trim().is_empty() => {
+                Err(anyhow::anyhow!("the chapter content is empty").into())
+            }
+            Ok(_) => Ok(()),
+            Err(e) => Err(e),
+        };
+        checks.push(FixtureCheck { url, result });
+    }
+
+    Ok(checks)
+}
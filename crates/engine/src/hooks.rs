@@ -0,0 +1,118 @@
+use std::{
+    fmt::Display,
+    future::Future,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use log::{debug, trace, warn};
+
+use crate::error;
+
+/// Callbacks for embedders to observe the engine, such as to collect metrics
+/// or report progress, instead of parsing the logs.
+///
+/// Every method does nothing by default. Hooks are called synchronously from
+/// the runtime, so they should return quickly.
+pub trait EngineHooks: Send + Sync {
+    /// An http request of the extension is about to be sent
+    fn on_request_start(&self, _url: &str) {}
+
+    /// An http request of the extension completed or failed
+    fn on_request_end(&self, _url: &str, _outcome: &RequestOutcome) {}
+
+    /// A call into the extension returned
+    fn on_extension_call(&self, _call: ExtensionCall, _elapsed: Duration) {}
+
+    /// A call into the extension returned an error
+    fn on_error(&self, _call: ExtensionCall, _error: &error::Error) {}
+}
+
+/// The result of an http request sent by the extension
+#[derive(Clone, Debug)]
+pub struct RequestOutcome {
+    /// The response status, or none when no response was received
+    pub status: Option<u16>,
+    /// The length of the response body
+    pub bytes: usize,
+    pub elapsed: Duration,
+}
+
+/// The functions of an extension called by the runtime
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ExtensionCall {
+    Setup,
+    Meta,
+    CanonicalizeUrl,
+    FetchNovel,
+    FetchChapterContent,
+    Popular,
+    TextSearch,
+    FilterSearch,
+}
+
+impl Display for ExtensionCall {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let value = match self {
+            ExtensionCall::Setup => "setup",
+            ExtensionCall::Meta => "meta",
+            ExtensionCall::CanonicalizeUrl => "canonicalize_url",
+            ExtensionCall::FetchNovel => "fetch_novel",
+            ExtensionCall::FetchChapterContent => "fetch_chapter_content",
+            ExtensionCall::Popular => "popular",
+            ExtensionCall::TextSearch => "text_search",
+            ExtensionCall::FilterSearch => "filter_search",
+        };
+
+        write!(f, "{value}")
+    }
+}
+
+/// The default hooks, writing every event to the log
+#[derive(Clone, Copy, Debug, Default)]
+pub struct LogHooks;
+
+impl EngineHooks for LogHooks {
+    fn on_request_start(&self, url: &str) {
+        trace!("request to '{url}' started");
+    }
+
+    fn on_request_end(&self, url: &str, outcome: &RequestOutcome) {
+        debug!(
+            "request to '{url}' finished with {:?} ({} bytes) in {:?}",
+            outcome.status, outcome.bytes, outcome.elapsed
+        );
+    }
+
+    fn on_extension_call(&self, call: ExtensionCall, elapsed: Duration) {
+        debug!("extension call '{call}' returned in {elapsed:?}");
+    }
+
+    fn on_error(&self, call: ExtensionCall, error: &error::Error) {
+        warn!("extension call '{call}' failed: {error}");
+    }
+}
+
+pub(crate) fn default_hooks() -> Arc<dyn EngineHooks> {
+    Arc::new(LogHooks)
+}
+
+/// Report the call to the hooks once the future completes
+pub(crate) async fn observe<T, F>(
+    hooks: Arc<dyn EngineHooks>,
+    call: ExtensionCall,
+    future: F,
+) -> error::Result<T>
+where
+    F: Future<Output = error::Result<T>>,
+{
+    let started = Instant::now();
+    let result = future.await;
+
+    hooks.on_extension_call(call, started.elapsed());
+    if let Err(e) = &result {
+        hooks.on_error(call, e);
+    }
+
+    result
+}
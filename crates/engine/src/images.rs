@@ -0,0 +1,255 @@
+//! Fetch remote images on behalf of a reader.
+//!
+//! Chapters reference images on the source, which browsers often fail to
+//! load because of hotlink protection. The proxy fetches them through the
+//! same http layer as extensions, waiting between requests to a host, and
+//! keeps them on disk so every image is only fetched once.
+
+use std::{
+    collections::HashMap,
+    fs,
+    path::PathBuf,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use quelle_core::prelude::{Request, RequestError};
+use reqwest::{header::CONTENT_TYPE, Method, Url};
+use sha2::{Digest, Sha256};
+
+use crate::module::{
+    http::{send_request_with, RedirectPolicy, SendOptions},
+    resume::{read_body, RESUME_ATTEMPTS},
+};
+
+#[derive(thiserror::Error, Debug)]
+pub enum ProxyError {
+    #[error("'{0}' is not an http url")]
+    InvalidUrl(String),
+
+    #[error("{0}")]
+    Request(RequestError),
+
+    #[error("request to '{url}' failed with status {status}")]
+    Status { url: String, status: u16 },
+
+    #[error("'{url}' is not an image ({content_type})")]
+    NotAnImage { url: String, content_type: String },
+
+    #[error("'{url}' is larger than {max_bytes} bytes")]
+    TooLarge { url: String, max_bytes: usize },
+
+    #[error("{0}")]
+    Io(#[from] std::io::Error),
+}
+
+/// An image fetched by the proxy
+#[derive(Clone, Debug)]
+pub struct ProxiedImage {
+    pub content_type: String,
+    pub bytes: Vec<u8>,
+}
+
+pub struct ImageProxy {
+    client: reqwest::Client,
+    redirect: RedirectPolicy,
+    cache_dir: PathBuf,
+    /// The minimum time between two requests to the same host
+    interval: Duration,
+    timeout: Duration,
+    max_bytes: usize,
+    /// The time each host may next be requested
+    next_request: Mutex<HashMap<String, Instant>>,
+}
+
+impl ImageProxy {
+    pub fn new(cache_dir: PathBuf) -> Self {
+        Self {
+            client: reqwest::Client::builder()
+                .user_agent("Mozilla/5.0 (X11; Fedora; Linux x86_64; rv:107.0) Gecko/20100101 Firefox/107.0")
+                .redirect(reqwest::redirect::Policy::none())
+                .build()
+                .unwrap(),
+            redirect: Default::default(),
+            cache_dir,
+            interval: Duration::from_millis(500),
+            timeout: Duration::from_secs(30),
+            max_bytes: 10 * 1024 * 1024,
+            next_request: Default::default(),
+        }
+    }
+
+    /// The minimum time between two requests to the same host
+    pub fn interval(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// The size of the largest image that is fetched
+    pub fn max_bytes(mut self, max_bytes: usize) -> Self {
+        self.max_bytes = max_bytes;
+        self
+    }
+
+    pub fn redirect(mut self, redirect: RedirectPolicy) -> Self {
+        self.redirect = redirect;
+        self
+    }
+
+    /// The image at the url, from the cache when it was fetched before
+    pub async fn fetch(&self, url: &str) -> Result<ProxiedImage, ProxyError> {
+        let parsed = Url::parse(url)
+            .ok()
+            .filter(|url| matches!(url.scheme(), "http" | "https"))
+            .ok_or_else(|| ProxyError::InvalidUrl(url.to_string()))?;
+
+        let key = cache_key(parsed.as_str());
+        let path = self.cache_dir.join(&key);
+        let type_path = self.cache_dir.join(format!("{key}.type"));
+
+        if path.exists() && type_path.exists() {
+            return Ok(ProxiedImage {
+                content_type: fs::read_to_string(type_path)?,
+                bytes: fs::read(path)?,
+            });
+        }
+
+        self.wait_for_host(parsed.host_str().unwrap_or_default())
+            .await;
+
+        let options = SendOptions {
+            timeout: Some(self.timeout),
+            max_len: Some(self.max_bytes),
+            ..Default::default()
+        };
+        let response = send_request_with(
+            &self.client,
+            Request::get(parsed.to_string()),
+            &self.redirect,
+            options,
+        )
+        .await
+        .map_err(ProxyError::Request)?
+        .response;
+
+        let status = response.status();
+        if !status.is_success() {
+            return Err(ProxyError::Status {
+                url: url.to_string(),
+                status: status.as_u16(),
+            });
+        }
+
+        let content_type = response
+            .headers()
+            .get(CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .unwrap_or_default()
+            .to_string();
+
+        if !content_type.starts_with("image/") {
+            return Err(ProxyError::NotAnImage {
+                url: url.to_string(),
+                content_type,
+            });
+        }
+
+        let too_large = || ProxyError::TooLarge {
+            url: url.to_string(),
+            max_bytes: self.max_bytes,
+        };
+
+        if response
+            .content_length()
+            .is_some_and(|len| len as usize > self.max_bytes)
+        {
+            return Err(too_large());
+        }
+
+        let bytes = read_body(
+            &self.client,
+            &Method::GET,
+            response,
+            options,
+            RESUME_ATTEMPTS,
+        )
+        .await
+        .map_err(|e| ProxyError::Request(e.into()))?;
+        if bytes.len() > self.max_bytes {
+            return Err(too_large());
+        }
+
+        fs::create_dir_all(&self.cache_dir)?;
+        fs::write(&path, &bytes)?;
+        fs::write(&type_path, &content_type)?;
+
+        Ok(ProxiedImage {
+            content_type,
+            bytes,
+        })
+    }
+
+    /// Wait until the host may be requested again, reserving the next slot
+    async fn wait_for_host(&self, host: &str) {
+        let now = Instant::now();
+        let start = {
+            let mut next_request = self.next_request.lock().unwrap();
+            let start = next_request
+                .get(host)
+                .copied()
+                .filter(|next| *next > now)
+                .unwrap_or(now);
+
+            next_request.insert(host.to_string(), start + self.interval);
+            start
+        };
+
+        if start > now {
+            tokio::time::sleep(start - now).await;
+        }
+    }
+}
+
+/// A file name for the url that stays the same between runs
+fn cache_key(url: &str) -> String {
+    format!("{:x}", Sha256::digest(url.as_bytes()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_key_cache_by_digest_of_url() {
+        let key = cache_key("https://example.com/cover.png");
+        assert_eq!(
+            key,
+            "0e9f5eb4e5096c2a8d88740e56d932092c5d9efc3103736dfdb8b93ddbdc5d68"
+        );
+        assert_ne!(key, cache_key("https://example.com/cover.jpg"));
+    }
+
+    #[tokio::test]
+    async fn should_reserve_next_slot_of_host() {
+        let proxy = ImageProxy::new(std::env::temp_dir()).interval(Duration::from_millis(100));
+
+        let started = Instant::now();
+        proxy.wait_for_host("example.com").await;
+        proxy.wait_for_host("example.org").await;
+        assert!(started.elapsed() < Duration::from_millis(100));
+
+        // Each request waits for the slot reserved by the one before it
+        proxy.wait_for_host("example.com").await;
+        proxy.wait_for_host("example.com").await;
+        assert!(started.elapsed() >= Duration::from_millis(200));
+
+        let next_request = proxy.next_request.lock().unwrap();
+        assert!(next_request["example.com"] >= started + Duration::from_millis(300));
+        assert!(next_request["example.org"] < started + Duration::from_millis(200));
+    }
+}
@@ -1,12 +1,26 @@
+pub mod budget;
 pub mod data;
 pub mod error;
+pub mod fixtures;
+pub mod hooks;
+pub mod images;
 pub mod module;
+pub mod pool;
+pub mod process;
+mod search;
+#[cfg(test)]
+mod test_support;
 
+use budget::{Budget, BudgetTracker};
 use data::DefaultImpl;
 use error::Error;
+use futures_util::Stream;
+use hooks::{observe, EngineHooks, ExtensionCall};
+use module::http::RedirectPolicy;
+pub use pool::{PooledRuntime, RuntimePool};
 use quelle_core::prelude::*;
 use serde::{de::DeserializeOwned, Serialize};
-use std::{future::Future, path::Path, slice};
+use std::{future::Future, path::Path, slice, sync::Arc};
 use wasmtime::*;
 
 type SendRequestFn<D> =
@@ -14,9 +28,34 @@ type SendRequestFn<D> =
 
 type LogFn<D> = fn(caller: Caller<'_, D>, ptr: i32, len: i32);
 
+/// The maximum linear memory of a pooled instance in wasm pages (256 MiB)
+const MAX_MEMORY_PAGES: u64 = 4096;
+
+/// The functions the host links into extensions, by module and name
+pub const HOST_IMPORTS: [(&str, &str); 5] = [
+    ("env", "http_send_request"),
+    ("env", "log_event"),
+    ("env", "io_print"),
+    ("env", "io_eprint"),
+    ("env", "io_trace"),
+];
+
+/// The module and name of every function the extension imports, without linking it
+pub fn module_imports(path: &Path) -> error::Result<Vec<(String, String)>> {
+    let module = Module::from_file(&Engine::default(), path)?;
+    let imports = module
+        .imports()
+        .map(|import| (import.module().to_string(), import.name().to_string()))
+        .collect();
+
+    Ok(imports)
+}
+
 pub struct RuntimeBuilder<D> {
     send_request: Option<SendRequestFn<D>>,
     log: Option<LogFn<D>>,
+    pooling: Option<u32>,
+    hooks: Option<Arc<dyn EngineHooks>>,
 }
 
 impl<D> Default for RuntimeBuilder<D> {
@@ -24,6 +63,8 @@ impl<D> Default for RuntimeBuilder<D> {
         Self {
             send_request: Default::default(),
             log: Default::default(),
+            pooling: Default::default(),
+            hooks: Default::default(),
         }
     }
 }
@@ -39,11 +80,47 @@ impl<D: Send + 'static> RuntimeBuilder<D> {
         self
     }
 
+    /// Use the pooling instance allocator with room for the number of instances
+    ///
+    /// Instance memory and tables are reserved up front and reused, which makes
+    /// creating many short lived runtimes considerably cheaper.
+    pub fn pooling(mut self, instances: u32) -> Self {
+        self.pooling = Some(instances);
+        self
+    }
+
+    /// Observe the extension calls of the runtimes with the hooks
+    ///
+    /// Defaults to [`hooks::LogHooks`]. The requests of [`DefaultImpl`] runtimes
+    /// created with [`RuntimePre::instantiate_default`] are reported as well.
+    pub fn hooks(mut self, hooks: Arc<dyn EngineHooks>) -> Self {
+        self.hooks = Some(hooks);
+        self
+    }
+
     pub async fn build(self, path: &Path, data: D) -> error::Result<Runtime<D>> {
+        self.prepare(path)?.instantiate(data).await
+    }
+
+    /// Compile and link the extension without instantiating it
+    ///
+    /// The returned [`RuntimePre`] can create any number of runtimes
+    /// without repeating the compilation and import resolution.
+    pub fn prepare(self, path: &Path) -> error::Result<RuntimePre<D>> {
         let mut config = Config::new();
         config.async_support(true);
         // config.consume_fuel(true);
 
+        if let Some(instances) = self.pooling {
+            let mut pooling = PoolingAllocationConfig::default();
+            pooling
+                .total_core_instances(instances)
+                .total_memories(instances)
+                .total_tables(instances)
+                .memory_pages(MAX_MEMORY_PAGES);
+            config.allocation_strategy(InstanceAllocationStrategy::Pooling(pooling));
+        }
+
         let engine = Engine::new(&config)?;
         let mut linker: Linker<D> = Linker::new(&engine);
         let module = Module::from_file(&engine, path)?;
@@ -58,9 +135,45 @@ impl<D: Send + 'static> RuntimeBuilder<D> {
         linker.func_wrap("env", "io_eprint", module::io::eprint)?;
         linker.func_wrap("env", "io_trace", module::io::trace)?;
 
+        let instance_pre = linker.instantiate_pre(&module)?;
+
+        Ok(RuntimePre {
+            engine,
+            module,
+            instance_pre,
+            hooks: self.hooks.unwrap_or_else(hooks::default_hooks),
+        })
+    }
+}
+
+/// A compiled and linked extension ready to be instantiated
+pub struct RuntimePre<D> {
+    engine: Engine,
+    module: Module,
+    instance_pre: InstancePre<D>,
+    hooks: Arc<dyn EngineHooks>,
+}
+
+impl<D> Clone for RuntimePre<D> {
+    fn clone(&self) -> Self {
+        Self {
+            engine: self.engine.clone(),
+            module: self.module.clone(),
+            instance_pre: self.instance_pre.clone(),
+            hooks: self.hooks.clone(),
+        }
+    }
+}
+
+impl<D: Send + 'static> RuntimePre<D> {
+    /// Create a new runtime with its own store holding the data
+    pub async fn instantiate(&self, data: D) -> error::Result<Runtime<D>> {
+        let engine = self.engine.clone();
+        let module = self.module.clone();
+
         let mut store = Store::new(&engine, data);
 
-        let instance = linker.instantiate_async(&mut store, &module).await?;
+        let instance = self.instance_pre.instantiate_async(&mut store).await?;
         let memory = instance
             .get_memory(&mut store, "memory")
             .ok_or(anyhow::format_err!("failed to find `memory` export"))?;
@@ -94,6 +207,7 @@ impl<D: Send + 'static> RuntimeBuilder<D> {
             meta: get_func!("meta"),
             fetch_novel: get_func!("fetch_novel"),
             fetch_chapter_content: get_func!("fetch_chapter_content"),
+            canonicalize_url: get_func_optional!("canonicalize_url"),
             popular_url: get_func_optional!("popular_url"),
             popular: get_func_optional!("popular"),
             text_search_url: get_func_optional!("text_search_url"),
@@ -110,10 +224,50 @@ impl<D: Send + 'static> RuntimeBuilder<D> {
             instance,
             memory,
             functions,
+            hooks: self.hooks.clone(),
         })
     }
 }
 
+impl RuntimePre<DefaultImpl> {
+    /// Compile and link the extension using the default host implementation
+    pub fn new(path: &Path) -> error::Result<Self> {
+        RuntimeBuilder::default()
+            .send_request(module::http::send_request)
+            .prepare(path)
+    }
+
+    /// Compile and link the extension using the pooling instance allocator
+    pub fn pooled(path: &Path, instances: u32) -> error::Result<Self> {
+        RuntimeBuilder::default()
+            .send_request(module::http::send_request)
+            .pooling(instances)
+            .prepare(path)
+    }
+
+    /// Create a runtime that follows redirects using the given policy
+    ///
+    /// The hosts of the source's base urls are always allowed as redirect targets.
+    pub async fn instantiate_default(
+        &self,
+        redirect: RedirectPolicy,
+    ) -> error::Result<Runtime<DefaultImpl>> {
+        let mut data = DefaultImpl::new(redirect);
+        data.hooks = self.hooks.clone();
+
+        let mut runtime = self.instantiate(data).await?;
+
+        let meta = runtime.meta().await?;
+        runtime
+            .store
+            .data_mut()
+            .redirect
+            .allow_base_urls(&meta.base_urls);
+
+        Ok(runtime)
+    }
+}
+
 #[allow(dead_code)]
 pub struct Runtime<D> {
     engine: Engine,
@@ -122,6 +276,7 @@ pub struct Runtime<D> {
     instance: Instance,
     memory: Memory,
     functions: Functions,
+    hooks: Arc<dyn EngineHooks>,
 }
 
 struct Functions {
@@ -144,6 +299,7 @@ struct Functions {
 
     fetch_novel: TypedFunc<i32, i32>,
     fetch_chapter_content: TypedFunc<i32, i32>,
+    canonicalize_url: Option<TypedFunc<i32, i32>>,
 
     popular_url: Option<TypedFunc<i32, i32>>,
     popular: Option<TypedFunc<i32, i32>>,
@@ -157,17 +313,49 @@ struct Functions {
 
 impl Runtime<DefaultImpl> {
     pub async fn new(path: &Path) -> crate::error::Result<Self> {
-        let data = DefaultImpl {
-            client: reqwest::Client::builder()
-                .user_agent("Mozilla/5.0 (X11; Fedora; Linux x86_64; rv:107.0) Gecko/20100101 Firefox/107.0")
-                .build()
-                .unwrap(),
-        };
-
-        RuntimeBuilder::default()
-            .send_request(module::http::send_request)
-            .build(path, data)
-            .await
+        Self::with_redirect_policy(path, Default::default()).await
+    }
+
+    /// Create a runtime that follows redirects using the given policy
+    ///
+    /// The hosts of the source's base urls are always allowed as redirect targets.
+    pub async fn with_redirect_policy(
+        path: &Path,
+        redirect: RedirectPolicy,
+    ) -> crate::error::Result<Self> {
+        RuntimePre::new(path)?.instantiate_default(redirect).await
+    }
+
+    /// Observe the extension calls and requests of the runtime with the hooks
+    pub fn set_hooks(&mut self, hooks: Arc<dyn EngineHooks>) {
+        self.store.data_mut().hooks = hooks.clone();
+        self.hooks = hooks;
+    }
+
+    /// Run an extension call with the requests it makes limited by the budget
+    ///
+    /// Once a limit is reached further requests fail, and the call returns
+    /// [`Error::BudgetExceeded`] however the extension handled the failures.
+    ///
+    /// ```ignore
+    /// let budget = Budget::default().max_requests(100);
+    /// let novel = runtime
+    ///     .within_budget(budget, async |runtime| runtime.fetch_novel(url).await)
+    ///     .await?;
+    /// ```
+    pub async fn within_budget<T>(
+        &mut self,
+        budget: Budget,
+        call: impl AsyncFnOnce(&mut Self) -> error::Result<T>,
+    ) -> error::Result<T> {
+        self.store.data_mut().budget = BudgetTracker::new(budget);
+        let result = call(self).await;
+
+        let tracker = std::mem::take(&mut self.store.data_mut().budget);
+        match tracker.exceeded() {
+            Some(exceeded) => Err(Error::BudgetExceeded(exceeded.clone())),
+            None => result,
+        }
     }
 }
 
@@ -180,26 +368,41 @@ where
         RuntimeBuilder::default()
     }
 
+    /// The data of the host implementation
+    pub fn data(&self) -> &D {
+        self.store.data()
+    }
+
+    pub fn data_mut(&mut self) -> &mut D {
+        self.store.data_mut()
+    }
+
     /// Call the extension's setup function
     pub async fn setup(&mut self, config: &ExtensionConfig) -> crate::error::Result<()> {
-        let config = self.write_serialize(config).await?;
-
-        self.functions
-            .setup
-            .as_ref()
-            .unwrap_or(&self.functions.setup_default)
-            .call_async(&mut self.store, config)
-            .await?;
+        observe(self.hooks.clone(), ExtensionCall::Setup, async {
+            let config = self.write_serialize(config).await?;
+
+            self.functions
+                .setup
+                .as_ref()
+                .unwrap_or(&self.functions.setup_default)
+                .call_async(&mut self.store, config)
+                .await?;
 
-        Ok(())
+            Ok(())
+        })
+        .await
     }
 
     pub async fn meta(&mut self) -> Result<Meta, crate::error::Error> {
-        let memloc = unsafe { self.meta_memloc().await? };
-        let bytes = self.read_bytes_with_len(memloc.offset, memloc.len as usize);
-        let meta = serde_json::from_slice(bytes).map_err(|_| Error::DeserializeError);
-        self.dealloc_memory(memloc.offset, memloc.len).await?;
-        meta
+        observe(self.hooks.clone(), ExtensionCall::Meta, async {
+            let memloc = unsafe { self.meta_memloc().await? };
+            let bytes = self.read_bytes_with_len(memloc.offset, memloc.len as usize);
+            let meta = serde_json::from_slice(bytes).map_err(|_| Error::DeserializeError);
+            self.dealloc_memory(memloc.offset, memloc.len).await?;
+            meta
+        })
+        .await
     }
 
     pub async unsafe fn meta_memloc(&mut self) -> error::Result<MemLoc> {
@@ -209,14 +412,37 @@ where
         Ok(MemLoc { offset, ptr, len })
     }
 
+    pub fn canonicalize_url_supported(&self) -> bool {
+        self.functions.canonicalize_url.is_some()
+    }
+
+    /// The url of the novel page for a url given by the user, such as a chapter url
+    ///
+    /// The url is returned unchanged when the extension does not support it.
+    pub async fn canonicalize_url(&mut self, url: &str) -> error::Result<String> {
+        observe(self.hooks.clone(), ExtensionCall::CanonicalizeUrl, async {
+            let Some(canonicalize_url) = self.functions.canonicalize_url.clone() else {
+                return Ok(url.to_string());
+            };
+
+            let iptr = self.write_string(url).await?;
+            let len = canonicalize_url.call_async(&mut self.store, iptr).await?;
+            self.parse_result::<String, QuelleError>(len).await
+        })
+        .await
+    }
+
     pub async fn fetch_novel(&mut self, url: &str) -> crate::error::Result<Novel> {
-        let iptr = self.write_string(url).await?;
-        let signed_len = self
-            .functions
-            .fetch_novel
-            .call_async(&mut self.store, iptr)
-            .await?;
-        self.parse_result::<Novel, QuelleError>(signed_len).await
+        observe(self.hooks.clone(), ExtensionCall::FetchNovel, async {
+            let iptr = self.write_string(url).await?;
+            let signed_len = self
+                .functions
+                .fetch_novel
+                .call_async(&mut self.store, iptr)
+                .await?;
+            self.parse_result::<Novel, QuelleError>(signed_len).await
+        })
+        .await
     }
 
     pub async unsafe fn fetch_novel_memloc(&mut self, url: &str) -> error::Result<MemLoc> {
@@ -236,14 +462,21 @@ where
     }
 
     pub async fn fetch_chapter_content(&mut self, url: &str) -> error::Result<Content> {
-        let iptr = self.write_string(url).await?;
-        let offset = self
-            .functions
-            .fetch_chapter_content
-            .call_async(&mut self.store, iptr)
-            .await?;
-
-        self.parse_result::<Content, QuelleError>(offset).await
+        observe(
+            self.hooks.clone(),
+            ExtensionCall::FetchChapterContent,
+            async {
+                let iptr = self.write_string(url).await?;
+                let offset = self
+                    .functions
+                    .fetch_chapter_content
+                    .call_async(&mut self.store, iptr)
+                    .await?;
+
+                self.parse_result::<Content, QuelleError>(offset).await
+            },
+        )
+        .await
     }
 
     pub async unsafe fn fetch_chapter_content_memloc(
@@ -307,9 +540,12 @@ where
     }
 
     pub async fn popular(&mut self, page: i32) -> error::Result<Vec<BasicNovel>> {
-        let signed_len = self.call_popular(page).await?;
-        self.parse_result::<Vec<BasicNovel>, QuelleError>(signed_len)
-            .await
+        observe(self.hooks.clone(), ExtensionCall::Popular, async {
+            let signed_len = self.call_popular(page).await?;
+            self.parse_result::<Vec<BasicNovel>, QuelleError>(signed_len)
+                .await
+        })
+        .await
     }
 
     pub async unsafe fn popular_memloc(&mut self, page: i32) -> error::Result<MemLoc> {
@@ -365,9 +601,27 @@ where
         query: &str,
         page: i32,
     ) -> crate::error::Result<Vec<BasicNovel>> {
-        let signed_len = self.call_text_search(query, page).await?;
-        self.parse_result::<Vec<BasicNovel>, QuelleError>(signed_len)
-            .await
+        observe(self.hooks.clone(), ExtensionCall::TextSearch, async {
+            let signed_len = self.call_text_search(query, page).await?;
+            self.parse_result::<Vec<BasicNovel>, QuelleError>(signed_len)
+                .await
+        })
+        .await
+    }
+
+    /// Stream the search results across pages, up to `limit` novels
+    ///
+    /// Pages are requested from the extension as the stream is consumed. A
+    /// failed page is yielded as an error and the next page is tried, until
+    /// several pages in a row fail. The stream ends when a page is empty or
+    /// only repeats earlier results. The returned stream must be pinned
+    /// before polling, such as with [`futures_util::pin_mut`].
+    pub fn search_iter<'a>(
+        &'a mut self,
+        query: &'a str,
+        limit: usize,
+    ) -> impl Stream<Item = error::Result<BasicNovel>> + 'a {
+        search::search_iter(self, query, limit)
     }
 
     pub async unsafe fn text_search_memloc(
@@ -424,16 +678,19 @@ where
         params: &str,
         page: i32,
     ) -> error::Result<Vec<BasicNovel>> {
-        let Some(filter_search) = self.functions.filter_search.clone() else {
-            return Err(error::Error::NotSupported(error::AffectedFunction::Search));
-        };
+        observe(self.hooks.clone(), ExtensionCall::FilterSearch, async {
+            let Some(filter_search) = self.functions.filter_search.clone() else {
+                return Err(error::Error::NotSupported(error::AffectedFunction::Search));
+            };
 
-        let params_ptr = self.write_string(params).await?;
-        let len = filter_search
-            .call_async(&mut self.store, (params_ptr, page))
-            .await?;
+            let params_ptr = self.write_string(params).await?;
+            let len = filter_search
+                .call_async(&mut self.store, (params_ptr, page))
+                .await?;
 
-        self.parse_result::<Vec<BasicNovel>, QuelleError>(len).await
+            self.parse_result::<Vec<BasicNovel>, QuelleError>(len).await
+        })
+        .await
     }
 
     // --------------------------------------------------------------------------------
@@ -603,3 +860,26 @@ pub struct MemLoc {
     pub ptr: *mut u8,
     pub len: i32,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::stub_extension;
+
+    #[tokio::test]
+    async fn should_instantiate_runtimes_with_their_own_store() {
+        let dir = tempfile::tempdir().unwrap();
+        let pre = RuntimeBuilder::<()>::default()
+            .prepare(&stub_extension(dir.path()))
+            .unwrap();
+
+        let mut first = pre.instantiate(()).await.unwrap();
+        assert_eq!(first.alloc_memory(16).await.unwrap(), 1024);
+        assert_eq!(first.alloc_memory(16).await.unwrap(), 1040);
+
+        // The compiled module is shared, the memory of the instances is not
+        let mut second = pre.clone().instantiate(()).await.unwrap();
+        assert_eq!(second.alloc_memory(16).await.unwrap(), 1024);
+        assert_eq!(first.alloc_memory(16).await.unwrap(), 1056);
+    }
+}
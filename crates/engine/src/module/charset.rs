@@ -0,0 +1,161 @@
+use std::borrow::Cow;
+
+use chardetng::EncodingDetector;
+use encoding_rs::{Encoding, UTF_8};
+use log::debug;
+
+/// The number of bytes scanned for a `<meta charset>` declaration.
+///
+/// This mirrors the prescan limit used by browsers.
+const META_PRESCAN_LIMIT: usize = 1024;
+
+/// Decode the response body into utf-8 bytes.
+///
+/// The encoding is resolved in the following order:
+/// byte order mark, `charset` in the content type header,
+/// `<meta charset>` in the document head, and finally statistical detection.
+///
+/// Bodies with a non-textual content type (ex: images) are returned as is.
+pub fn decode_to_utf8(bytes: Vec<u8>, content_type: Option<&str>) -> Vec<u8> {
+    if !content_type.map(is_text_content).unwrap_or(true) {
+        return bytes;
+    }
+
+    let encoding = detect_encoding(&bytes, content_type);
+    let has_bom = Encoding::for_bom(&bytes).is_some();
+    if encoding == UTF_8 && !has_bom && std::str::from_utf8(&bytes).is_ok() {
+        return bytes;
+    }
+
+    debug!("decoding response body as '{}'", encoding.name());
+
+    let (text, _, had_errors) = encoding.decode(&bytes);
+    if had_errors {
+        debug!("malformed sequences replaced while decoding response body");
+    }
+
+    match text {
+        Cow::Borrowed(text) => text.as_bytes().to_vec(),
+        Cow::Owned(text) => text.into_bytes(),
+    }
+}
+
+pub fn detect_encoding(bytes: &[u8], content_type: Option<&str>) -> &'static Encoding {
+    if let Some((encoding, _)) = Encoding::for_bom(bytes) {
+        return encoding;
+    }
+
+    if let Some(encoding) = content_type.and_then(charset_from_content_type) {
+        return encoding;
+    }
+
+    if let Some(encoding) = charset_from_meta(bytes) {
+        return encoding;
+    }
+
+    if std::str::from_utf8(bytes).is_ok() {
+        return UTF_8;
+    }
+
+    let mut detector = EncodingDetector::new();
+    detector.feed(bytes, true);
+    detector.guess(None, true)
+}
+
+fn is_text_content(content_type: &str) -> bool {
+    let mime = content_type
+        .split(';')
+        .next()
+        .unwrap_or_default()
+        .trim()
+        .to_ascii_lowercase();
+
+    mime.is_empty()
+        || mime.starts_with("text/")
+        || ["html", "xml", "json", "javascript"]
+            .iter()
+            .any(|kind| mime.contains(kind))
+}
+
+fn charset_from_content_type(content_type: &str) -> Option<&'static Encoding> {
+    content_type
+        .split(';')
+        .skip(1)
+        .filter_map(|param| param.split_once('='))
+        .find(|(name, _)| name.trim().eq_ignore_ascii_case("charset"))
+        .and_then(|(_, value)| Encoding::for_label(value.trim().trim_matches('"').as_bytes()))
+}
+
+fn charset_from_meta(bytes: &[u8]) -> Option<&'static Encoding> {
+    let head = &bytes[..bytes.len().min(META_PRESCAN_LIMIT)];
+    let head = String::from_utf8_lossy(head).to_ascii_lowercase();
+
+    let mut rest = head.as_str();
+    while let Some(start) = rest.find("<meta") {
+        rest = &rest[start + 5..];
+        let tag = &rest[..rest.find('>').unwrap_or(rest.len())];
+
+        let Some(index) = tag.find("charset=") else {
+            continue;
+        };
+
+        let label = tag[index + 8..]
+            .trim_start_matches(['"', '\''])
+            .split(|c: char| c == '"' || c == '\'' || c == ';' || c == '/' || c.is_whitespace())
+            .next()
+            .unwrap_or_default();
+
+        // A utf-16 declaration in an ascii compatible document cannot be true
+        return Encoding::for_label(label.as_bytes()).map(Encoding::output_encoding);
+    }
+
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_keep_utf8_body() {
+        let body = "<p>héllo</p>".as_bytes().to_vec();
+        assert_eq!(decode_to_utf8(body.clone(), None), body);
+    }
+
+    #[test]
+    fn should_decode_charset_from_content_type() {
+        let (body, _, _) = encoding_rs::GBK.encode("<p>你好</p>");
+        assert_eq!(
+            decode_to_utf8(body.to_vec(), Some("text/html; charset=GBK")),
+            "<p>你好</p>".as_bytes()
+        );
+    }
+
+    #[test]
+    fn should_decode_charset_from_meta() {
+        let (body, _, _) = encoding_rs::WINDOWS_1252
+            .encode(r#"<html><head><meta charset="windows-1252"></head><p>café</p></html>"#);
+        assert_eq!(
+            decode_to_utf8(body.to_vec(), Some("text/html")),
+            r#"<html><head><meta charset="windows-1252"></head><p>café</p></html>"#.as_bytes()
+        );
+
+        let head = br#"<meta http-equiv="Content-Type" content="text/html; charset=shift_jis">"#;
+        assert_eq!(charset_from_meta(head), Some(encoding_rs::SHIFT_JIS));
+    }
+
+    #[test]
+    fn should_keep_binary_body() {
+        let body = vec![0x89, 0x50, 0x4E, 0x47, 0xFF, 0xFE];
+        assert_eq!(decode_to_utf8(body.clone(), Some("image/png")), body);
+    }
+
+    #[test]
+    fn should_prefer_byte_order_mark() {
+        let body = [&[0xEF, 0xBB, 0xBF][..], "<p>ok</p>".as_bytes()].concat();
+        assert_eq!(
+            detect_encoding(&body, Some("text/html; charset=iso-8859-1")),
+            UTF_8
+        );
+    }
+}
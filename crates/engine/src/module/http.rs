@@ -1,12 +1,26 @@
-use std::future::Future;
+use std::{
+    future::Future,
+    time::{Duration, Instant},
+};
 
 use log::{debug, trace};
-use quelle_core::prelude::{Body, Request, RequestError, RequestErrorKind, Response};
+use quelle_core::prelude::{Body, Method, Request, RequestError, RequestErrorKind, Response};
+use reqwest::{
+    header::{CONTENT_TYPE, LOCATION},
+    StatusCode, Url,
+};
+use serde::{Deserialize, Serialize};
 use wasmtime::{Caller, Memory};
 
 use crate::{
+    budget::BudgetExceeded,
     data::DefaultImpl,
-    module::utils::{read_str_with_len, write_str},
+    hooks::RequestOutcome,
+    module::{
+        charset::decode_to_utf8,
+        resume::{read_body, RESUME_ATTEMPTS},
+        utils::{read_str_with_len, write_str},
+    },
 };
 
 pub fn send_request_noop<'a, D>(
@@ -25,14 +39,70 @@ pub fn send_request<'a>(
     Box::new(async move {
         let memory = caller.get_export("memory").unwrap().into_memory().unwrap();
         let request = read_request(&mut caller, ptr, len, &memory);
-        let client = &caller.data().client;
-        let response = send_request_reqwest::<DefaultImpl>(client, request).await;
-        let response = parse_response(response).await;
+        let url = request.url.clone();
+
+        let hooks = caller.data().hooks.clone();
+        let started = Instant::now();
+        hooks.on_request_start(&url);
+
+        let response = match caller.data_mut().budget.start_request() {
+            Ok(()) => {
+                let DefaultImpl {
+                    client,
+                    redirect,
+                    budget,
+                    session,
+                    ..
+                } = caller.data();
+                let options = SendOptions {
+                    timeout: budget.remaining_time(),
+                    session: session.as_ref(),
+                    max_len: budget.remaining_bytes(),
+                };
+                let response = send_request_with(client, request, redirect, options).await;
+                parse_response_with(response, client, options).await
+            }
+            Err(exceeded) => Err(budget_error(&url, exceeded)),
+        };
+
+        let response = response.and_then(|response| {
+            let len = response.body.as_ref().map(Vec::len).unwrap_or_default();
+            match caller.data_mut().budget.record_bytes(len) {
+                Ok(()) => Ok(response),
+                Err(exceeded) => Err(budget_error(&url, exceeded)),
+            }
+        });
+
+        hooks.on_request_end(
+            &url,
+            &RequestOutcome {
+                status: response
+                    .as_ref()
+                    .ok()
+                    .map(|response| response.status as u16),
+                bytes: response
+                    .as_ref()
+                    .ok()
+                    .and_then(|response| response.body.as_ref())
+                    .map(Vec::len)
+                    .unwrap_or_default(),
+                elapsed: started.elapsed(),
+            },
+        );
+
         let json = serde_json::to_string(&response).unwrap();
         write_str(&mut caller, &memory, json.as_str()).await
     })
 }
 
+fn budget_error(url: &str, exceeded: BudgetExceeded) -> RequestError {
+    RequestError {
+        kind: RequestErrorKind::Budget,
+        url: Some(url.to_string()),
+        message: exceeded.to_string(),
+    }
+}
+
 pub fn read_request<D>(caller: &mut Caller<'_, D>, ptr: i32, len: i32, memory: &Memory) -> Request {
     let request_data = read_str_with_len(caller, &memory, ptr, len as usize);
     let request_data = serde_json::from_str::<Request>(request_data).unwrap();
@@ -40,14 +110,197 @@ pub fn read_request<D>(caller: &mut Caller<'_, D>, ptr: i32, len: i32, memory: &
     request_data
 }
 
+/// Controls how redirects are followed when sending requests
+#[derive(Clone, Debug)]
+pub struct RedirectPolicy {
+    /// The maximum number of redirects to follow before failing
+    pub max_redirects: usize,
+    /// Hosts that may be redirected to from a different host
+    pub allowed_hosts: Vec<String>,
+}
+
+impl Default for RedirectPolicy {
+    fn default() -> Self {
+        Self {
+            max_redirects: 10,
+            allowed_hosts: vec![],
+        }
+    }
+}
+
+impl RedirectPolicy {
+    /// Allow cross-domain redirects to the hosts of the given base urls
+    pub fn allow_base_urls(&mut self, base_urls: &[String]) {
+        let hosts = base_urls
+            .iter()
+            .filter_map(|url| Url::parse(url).ok())
+            .filter_map(|url| url.host_str().map(str::to_string));
+
+        for host in hosts {
+            if !self.allowed_hosts.contains(&host) {
+                self.allowed_hosts.push(host);
+            }
+        }
+    }
+
+    pub fn is_allowed(&self, from: &Url, to: &Url) -> bool {
+        match (from.host_str(), to.host_str()) {
+            (Some(from), Some(to)) if from == to => true,
+            (_, Some(to)) => self.allowed_hosts.iter().any(|host| host == to),
+            _ => false,
+        }
+    }
+}
+
+/// Headers that authenticate requests to a source, such as a session cookie
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct Session {
+    /// The hosts the headers are sent to
+    pub hosts: Vec<String>,
+    pub headers: Vec<(String, String)>,
+}
+
+impl Session {
+    /// A session sending the headers to the hosts of the base urls
+    pub fn for_base_urls(base_urls: &[String], headers: Vec<(String, String)>) -> Self {
+        let hosts = base_urls
+            .iter()
+            .filter_map(|url| Url::parse(url).ok())
+            .filter_map(|url| url.host_str().map(str::to_string))
+            .collect();
+
+        Self { hosts, headers }
+    }
+
+    pub fn applies_to(&self, url: &Url) -> bool {
+        url.host_str()
+            .is_some_and(|host| self.hosts.iter().any(|value| value == host))
+    }
+}
+
+/// A response along with the urls that were redirected from
+#[derive(Debug)]
+pub struct RedirectedResponse {
+    pub response: reqwest::Response,
+    pub redirects: Vec<String>,
+    /// The method of the last request, which a redirect may have changed
+    pub method: Method,
+}
+
+/// Send the request, following redirects according to the policy.
+///
+/// The client is expected to be built with redirects disabled.
 pub async fn send_request_reqwest<'a, D>(
     client: &reqwest::Client,
     request_data: Request,
-) -> Result<reqwest::Response, reqwest::Error> {
+    policy: &RedirectPolicy,
+) -> Result<RedirectedResponse, RequestError> {
+    send_request_with(client, request_data, policy, SendOptions::default()).await
+}
+
+/// Options applied to every attempt when sending a request
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SendOptions<'a> {
+    /// Fail an attempt that takes longer than this
+    pub timeout: Option<Duration>,
+    /// Authenticate requests to the hosts of the session
+    pub session: Option<&'a Session>,
+    /// Stop reading a response body once it is longer than this
+    pub max_len: Option<usize>,
+}
+
+/// Send the request like [`send_request_reqwest`] using the options
+pub async fn send_request_with(
+    client: &reqwest::Client,
+    request_data: Request,
+    policy: &RedirectPolicy,
+    options: SendOptions<'_>,
+) -> Result<RedirectedResponse, RequestError> {
     trace!("executing exposed function 'ext_send_request'");
 
-    let mut request = client.request(request_data.method.into(), &request_data.url);
-    if let Some(body) = request_data.data {
+    let mut url = Url::parse(&request_data.url).map_err(|e| RequestError {
+        kind: RequestErrorKind::Request,
+        url: Some(request_data.url.clone()),
+        message: e.to_string(),
+    })?;
+
+    let mut method = request_data.method;
+    let mut body = request_data.data;
+    let mut redirects = vec![];
+
+    loop {
+        let mut request = build_request(client, method, url.clone(), body.clone());
+        if let Some(timeout) = options.timeout {
+            request = request.timeout(timeout);
+        }
+
+        // Checked on every redirect so that credentials never leak to other hosts
+        if let Some(session) = options.session.filter(|session| session.applies_to(&url)) {
+            for (name, value) in &session.headers {
+                request = request.header(name, value);
+            }
+        }
+
+        let response = request.send().await?;
+
+        let status = response.status();
+        let location = response
+            .headers()
+            .get(LOCATION)
+            .and_then(|value| value.to_str().ok())
+            .map(|value| url.join(value));
+
+        let next = match location {
+            Some(Ok(next)) if status.is_redirection() => next,
+            _ => {
+                return Ok(RedirectedResponse {
+                    response,
+                    redirects,
+                    method,
+                })
+            }
+        };
+
+        if redirects.len() >= policy.max_redirects {
+            return Err(RequestError {
+                kind: RequestErrorKind::Redirect,
+                url: Some(url.to_string()),
+                message: format!("exceeded the maximum of {} redirects", policy.max_redirects),
+            });
+        }
+
+        if !policy.is_allowed(&url, &next) {
+            return Err(RequestError {
+                kind: RequestErrorKind::Redirect,
+                url: Some(url.to_string()),
+                message: format!("cross-domain redirect to '{next}' is not allowed"),
+            });
+        }
+
+        debug!("Following redirect from '{url}' to '{next}'.");
+
+        let downgrade = status == StatusCode::SEE_OTHER
+            || (method == Method::Post
+                && matches!(status, StatusCode::MOVED_PERMANENTLY | StatusCode::FOUND));
+
+        if downgrade {
+            method = Method::Get;
+            body = None;
+        }
+
+        redirects.push(url.to_string());
+        url = next;
+    }
+}
+
+fn build_request(
+    client: &reqwest::Client,
+    method: Method,
+    url: Url,
+    body: Option<Body>,
+) -> reqwest::RequestBuilder {
+    let mut request = client.request(method.into(), url);
+    if let Some(body) = body {
         match body {
             Body::Form(data) => {
                 let mut multipart = reqwest::multipart::Form::new();
@@ -59,13 +312,36 @@ pub async fn send_request_reqwest<'a, D>(
         };
     }
 
-    request.send().await
+    request
 }
 
 pub async fn parse_response(
-    response: reqwest::Result<reqwest::Response>,
+    response: Result<RedirectedResponse, RequestError>,
 ) -> Result<Response, RequestError> {
-    let response = response?;
+    parse_response_resuming(response, None).await
+}
+
+/// Parse the response like [`parse_response`], resuming the body with the
+/// client when the connection drops, see [`read_body`]
+pub async fn parse_response_with(
+    response: Result<RedirectedResponse, RequestError>,
+    client: &reqwest::Client,
+    options: SendOptions<'_>,
+) -> Result<Response, RequestError> {
+    parse_response_resuming(response, Some((client, options))).await
+}
+
+async fn parse_response_resuming(
+    response: Result<RedirectedResponse, RequestError>,
+    resume: Option<(&reqwest::Client, SendOptions<'_>)>,
+) -> Result<Response, RequestError> {
+    let RedirectedResponse {
+        response,
+        redirects,
+        method,
+    } = response?;
+    let url = response.url().to_string();
+
     let header_map = response
         .headers()
         .into_iter()
@@ -78,9 +354,23 @@ pub async fn parse_response(
         message: String::from("failed to serialize response"),
     })?;
 
+    let content_type = header_map.get(CONTENT_TYPE.as_str()).cloned();
+    let status = response.status().as_u16() as usize;
+    let body = match resume {
+        Some((client, options)) => {
+            read_body(client, &method.into(), response, options, RESUME_ATTEMPTS).await
+        }
+        None => response.bytes().await.map(|data| data.to_vec()),
+    };
+    let body = body
+        .map(|data| decode_to_utf8(data, content_type.as_deref()))
+        .ok();
+
     Ok(Response {
-        status: response.status().as_u16() as usize,
-        body: response.bytes().await.map(|data| data.to_vec()).ok(),
+        status,
+        body,
         headers: Some(headers),
+        url: Some(url),
+        redirects,
     })
 }
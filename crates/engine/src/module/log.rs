@@ -16,5 +16,6 @@ pub fn event<D>(mut caller: Caller<'_, D>, ptr: i32, len: i32) {
         }
     };
 
-    println!("{} - {}", event.level, event.args);
+    // Kept off stdout, which carries the responses of a worker process
+    eprintln!("{} - {}", event.level, event.args);
 }
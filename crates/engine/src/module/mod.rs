@@ -1,4 +1,6 @@
+pub mod charset;
 pub mod http;
 pub mod io;
+pub mod resume;
 pub mod utils;
 pub mod log;
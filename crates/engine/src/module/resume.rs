@@ -0,0 +1,131 @@
+use log::debug;
+use reqwest::{
+    header::{ACCEPT_ENCODING, ACCEPT_RANGES, CONTENT_RANGE, ETAG, IF_RANGE, LAST_MODIFIED, RANGE},
+    Method, Response, StatusCode,
+};
+
+use super::http::SendOptions;
+
+/// The number of times a body is resumed after the connection dropped
+pub const RESUME_ATTEMPTS: usize = 3;
+
+/// Read the body of the response, resuming it with range requests when the
+/// connection drops before the end
+///
+/// Only responses to `GET` requests that advertise byte ranges and report
+/// their length are resumed, as the resumed request is always a `GET` and the
+/// length is unknown once the client decompressed the body.
+/// The range requests ask for the content unencoded and are sent with the
+/// validator of the response, so that a body that changed in the meantime is
+/// downloaded again from the start instead of being spliced.
+///
+/// Reading stops as soon as the body is longer than the `max_len` of the
+/// options, returning the body read so far for the caller to reject.
+pub async fn read_body(
+    client: &reqwest::Client,
+    method: &Method,
+    mut response: Response,
+    options: SendOptions<'_>,
+    attempts: usize,
+) -> reqwest::Result<Vec<u8>> {
+    let url = response.url().clone();
+    let resumable = *method == Method::GET
+        && response.content_length().is_some()
+        && response
+            .headers()
+            .get(ACCEPT_RANGES)
+            .and_then(|value| value.to_str().ok())
+            .is_some_and(|value| value.eq_ignore_ascii_case("bytes"));
+
+    let validator = response
+        .headers()
+        .get(ETAG)
+        .or_else(|| response.headers().get(LAST_MODIFIED))
+        .cloned();
+
+    let mut body = vec![];
+    let mut attempt = 0;
+    loop {
+        let error = match response.chunk().await {
+            Ok(Some(chunk)) => {
+                body.extend_from_slice(&chunk);
+                if options.max_len.is_some_and(|max_len| body.len() > max_len) {
+                    debug!("Stopped reading '{url}' over {} bytes.", body.len());
+                    return Ok(body);
+                }
+                continue;
+            }
+            Ok(None) => return Ok(body),
+            Err(error) => error,
+        };
+
+        if !resumable || attempt >= attempts {
+            return Err(error);
+        }
+        attempt += 1;
+        debug!("Resuming '{url}' at byte {} after: {error}.", body.len());
+
+        let mut request = client
+            .get(url.clone())
+            .header(RANGE, format!("bytes={}-", body.len()))
+            .header(ACCEPT_ENCODING, "identity");
+        if let Some(validator) = &validator {
+            request = request.header(IF_RANGE, validator);
+        }
+        if let Some(timeout) = options.timeout {
+            request = request.timeout(timeout);
+        }
+        if let Some(session) = options.session.filter(|session| session.applies_to(&url)) {
+            for (name, value) in &session.headers {
+                request = request.header(name, value);
+            }
+        }
+
+        let Ok(next) = request.send().await else {
+            return Err(error);
+        };
+
+        match next.status() {
+            StatusCode::PARTIAL_CONTENT => {
+                let start = next
+                    .headers()
+                    .get(CONTENT_RANGE)
+                    .and_then(|value| value.to_str().ok())
+                    .and_then(range_start);
+
+                if start != Some(body.len() as u64) {
+                    return Err(error);
+                }
+            }
+            // The server ignored the range or the content changed
+            StatusCode::OK => body.clear(),
+            _ => return Err(error),
+        }
+
+        response = next;
+    }
+}
+
+/// The first byte of a `Content-Range` header such as `bytes 100-199/200`
+fn range_start(content_range: &str) -> Option<u64> {
+    content_range
+        .strip_prefix("bytes ")?
+        .split('-')
+        .next()?
+        .trim()
+        .parse()
+        .ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::range_start;
+
+    #[test]
+    fn should_parse_content_range_start() {
+        assert_eq!(range_start("bytes 100-199/200"), Some(100));
+        assert_eq!(range_start("bytes 0-0/*"), Some(0));
+        assert_eq!(range_start("bytes */200"), None);
+        assert_eq!(range_start("items 1-2/3"), None);
+    }
+}
@@ -0,0 +1,257 @@
+use std::{
+    ops::{Deref, DerefMut},
+    path::Path,
+    sync::{Arc, Mutex, MutexGuard},
+};
+
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+use crate::{data::DefaultImpl, error, Runtime, RuntimePre};
+
+/// A pool of runtimes created from the same pre-instantiated extension
+///
+/// Runtimes are returned to the pool when their [`PooledRuntime`] is dropped
+/// and handed out again by later calls to acquire, keeping any state such as
+/// the setup configuration.
+///
+/// A runtime is recycled at most `max_uses` times before its store is dropped,
+/// since the linear memory of an instance only ever grows.
+pub struct RuntimePool<D> {
+    pre: RuntimePre<D>,
+    idle: Mutex<Vec<Idle<D>>>,
+    max_idle: usize,
+    max_uses: Option<usize>,
+    /// The runtimes that may be acquired at the same time
+    permits: Option<Arc<Semaphore>>,
+}
+
+struct Idle<D> {
+    runtime: Runtime<D>,
+    uses: usize,
+}
+
+impl<D: Send + 'static> RuntimePool<D> {
+    /// Create a pool that keeps at most `max_idle` unused runtimes around
+    pub fn new(pre: RuntimePre<D>, max_idle: usize) -> Self {
+        Self {
+            pre,
+            idle: Mutex::new(Vec::with_capacity(max_idle)),
+            max_idle,
+            max_uses: None,
+            permits: None,
+        }
+    }
+
+    /// Wait for a runtime to be returned before handing out more than
+    /// `max_instances` runtimes at the same time
+    pub fn max_instances(mut self, max_instances: usize) -> Self {
+        self.permits = Some(Arc::new(Semaphore::new(max_instances)));
+        self
+    }
+
+    /// Drop runtimes instead of recycling them after they were used this many times
+    pub fn max_uses(mut self, max_uses: usize) -> Self {
+        self.max_uses = Some(max_uses);
+        self
+    }
+
+    pub fn pre(&self) -> &RuntimePre<D> {
+        &self.pre
+    }
+
+    /// The number of runtimes waiting to be reused
+    pub fn idle(&self) -> usize {
+        self.lock_idle().len()
+    }
+
+    /// Reuse an idle runtime or instantiate a new one with the data
+    ///
+    /// The runtime is returned to the pool when the guard is dropped.
+    pub async fn acquire_with<F>(self: &Arc<Self>, data: F) -> error::Result<PooledRuntime<D>>
+    where
+        F: FnOnce() -> D,
+    {
+        let permit = self.permit().await;
+        if let Some(idle) = self.lock_idle().pop() {
+            return Ok(PooledRuntime::new(self.clone(), idle, permit));
+        }
+
+        let runtime = self.pre.instantiate(data()).await?;
+        Ok(PooledRuntime::new(
+            self.clone(),
+            Idle { runtime, uses: 0 },
+            permit,
+        ))
+    }
+
+    /// Wait until another runtime may be handed out
+    ///
+    /// Runtimes are only instantiated when none are idle, so the runtimes
+    /// alive never outnumber the permits.
+    async fn permit(&self) -> Option<OwnedSemaphorePermit> {
+        let permits = self.permits.clone()?;
+        Some(
+            permits
+                .acquire_owned()
+                .await
+                .expect("the semaphore is never closed"),
+        )
+    }
+
+    fn recycle(&self, runtime: Runtime<D>, uses: usize) {
+        if self.max_uses.is_some_and(|max_uses| uses >= max_uses) {
+            log::debug!("dropping runtime after {uses} uses");
+            return;
+        }
+
+        let mut idle = self.lock_idle();
+        if idle.len() < self.max_idle {
+            idle.push(Idle { runtime, uses });
+        }
+    }
+
+    fn lock_idle(&self) -> MutexGuard<'_, Vec<Idle<D>>> {
+        self.idle.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl RuntimePool<DefaultImpl> {
+    /// Create a pool backed by the pooling instance allocator
+    ///
+    /// At most `size` runtimes are alive at the same time, and acquiring
+    /// another waits until one is returned.
+    pub fn open(path: &Path, size: u32) -> error::Result<Self> {
+        let pre = RuntimePre::pooled(path, size)?;
+        Ok(Self::new(pre, size as usize).max_instances(size as usize))
+    }
+
+    /// Reuse an idle runtime or instantiate a new one following the default redirect policy
+    ///
+    /// The runtime is returned to the pool when the guard is dropped.
+    pub async fn acquire(self: &Arc<Self>) -> error::Result<PooledRuntime<DefaultImpl>> {
+        let permit = self.permit().await;
+        if let Some(idle) = self.lock_idle().pop() {
+            return Ok(PooledRuntime::new(self.clone(), idle, permit));
+        }
+
+        let runtime = self.pre.instantiate_default(Default::default()).await?;
+        Ok(PooledRuntime::new(
+            self.clone(),
+            Idle { runtime, uses: 0 },
+            permit,
+        ))
+    }
+}
+
+/// A runtime borrowed from a [`RuntimePool`] that is recycled on drop
+pub struct PooledRuntime<D: Send + 'static> {
+    pool: Arc<RuntimePool<D>>,
+    runtime: Option<Runtime<D>>,
+    uses: usize,
+    /// Released after the runtime is recycled, when the guard is dropped
+    _permit: Option<OwnedSemaphorePermit>,
+}
+
+impl<D: Send + 'static> PooledRuntime<D> {
+    fn new(pool: Arc<RuntimePool<D>>, idle: Idle<D>, permit: Option<OwnedSemaphorePermit>) -> Self {
+        Self {
+            pool,
+            runtime: Some(idle.runtime),
+            uses: idle.uses,
+            _permit: permit,
+        }
+    }
+
+    /// Drop the runtime instead of recycling it, for example after a trap
+    pub fn discard(mut self) {
+        self.runtime.take();
+    }
+
+    /// The number of times the runtime was used before this one
+    pub fn uses(&self) -> usize {
+        self.uses
+    }
+}
+
+impl<D: Send + 'static> Deref for PooledRuntime<D> {
+    type Target = Runtime<D>;
+
+    fn deref(&self) -> &Self::Target {
+        self.runtime
+            .as_ref()
+            .expect("runtime is only taken on drop")
+    }
+}
+
+impl<D: Send + 'static> DerefMut for PooledRuntime<D> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        self.runtime
+            .as_mut()
+            .expect("runtime is only taken on drop")
+    }
+}
+
+impl<D: Send + 'static> Drop for PooledRuntime<D> {
+    fn drop(&mut self) {
+        if let Some(runtime) = self.runtime.take() {
+            self.pool.recycle(runtime, self.uses + 1);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+    use crate::{test_support::stub_extension, RuntimeBuilder};
+
+    #[tokio::test]
+    async fn should_recycle_runtimes_until_max_uses() {
+        let dir = tempfile::tempdir().unwrap();
+        let pre = RuntimeBuilder::<()>::default()
+            .prepare(&stub_extension(dir.path()))
+            .unwrap();
+        let pool = Arc::new(RuntimePool::new(pre, 2).max_uses(2));
+
+        let mut runtime = pool.acquire_with(|| ()).await.unwrap();
+        assert_eq!(runtime.uses(), 0);
+        assert_eq!(runtime.alloc_memory(16).await.unwrap(), 1024);
+        drop(runtime);
+        assert_eq!(pool.idle(), 1);
+
+        // The recycled runtime keeps the memory of its earlier use
+        let mut runtime = pool.acquire_with(|| ()).await.unwrap();
+        assert_eq!(runtime.uses(), 1);
+        assert_eq!(runtime.alloc_memory(16).await.unwrap(), 1040);
+        drop(runtime);
+        assert_eq!(pool.idle(), 0);
+
+        let mut runtime = pool.acquire_with(|| ()).await.unwrap();
+        assert_eq!(runtime.uses(), 0);
+        assert_eq!(runtime.alloc_memory(16).await.unwrap(), 1024);
+
+        // Discarded runtimes are not recycled
+        runtime.discard();
+        assert_eq!(pool.idle(), 0);
+    }
+
+    #[tokio::test]
+    async fn should_wait_for_instances_of_pooling_allocator() {
+        let dir = tempfile::tempdir().unwrap();
+        let pre = RuntimeBuilder::<()>::default()
+            .pooling(1)
+            .prepare(&stub_extension(dir.path()))
+            .unwrap();
+        let pool = Arc::new(RuntimePool::new(pre, 1).max_instances(1));
+
+        let runtime = pool.acquire_with(|| ()).await.unwrap();
+        let waiting = tokio::time::timeout(Duration::from_millis(50), pool.acquire_with(|| ()));
+        assert!(waiting.await.is_err());
+
+        // The returned runtime is handed out again instead of a second instance
+        drop(runtime);
+        let runtime = pool.acquire_with(|| ()).await.unwrap();
+        assert_eq!(runtime.uses(), 1);
+    }
+}
@@ -0,0 +1,505 @@
+//! Run extensions in a separate OS process.
+//!
+//! The parent talks to a worker process over its stdin and stdout using one
+//! JSON encoded [`Message`] per line, carrying a [`WorkerRequest`] or the
+//! [`WorkerResponse`] with the same id. Extensions may print to stdout too, so
+//! lines that are not the awaited response are skipped. A crash or a hang
+//! inside the extension only takes down the worker, which is restarted on the
+//! next call.
+
+use std::{
+    ffi::OsString,
+    path::{Path, PathBuf},
+    process::Stdio,
+    time::Duration,
+};
+
+use quelle_core::prelude::*;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use tokio::{
+    io::{AsyncBufReadExt, AsyncWriteExt, BufReader, Lines},
+    process::{Child, ChildStdin, ChildStdout, Command},
+};
+
+use crate::{
+    budget::{Budget, BudgetExceeded},
+    error,
+    error::Error,
+    module::http::Session,
+    Runtime,
+};
+
+/// A request or response tagged with the id pairing them
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Message<T> {
+    pub id: u64,
+    pub body: T,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(tag = "method", content = "params", rename_all = "snake_case")]
+pub enum WorkerRequest {
+    Setup(ExtensionConfig),
+    Authenticate(Session),
+    Meta,
+    CanonicalizeUrl {
+        url: String,
+    },
+    FetchNovel {
+        url: String,
+        #[serde(default)]
+        budget: Budget,
+    },
+    FetchChapterContent {
+        url: String,
+        #[serde(default)]
+        budget: Budget,
+    },
+    Popular {
+        page: i32,
+    },
+    TextSearch {
+        query: String,
+        page: i32,
+    },
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum WorkerResponse {
+    Value(serde_json::Value),
+    Returned(QuelleError),
+    BudgetExceeded(BudgetExceeded),
+    Failed(String),
+}
+
+/// Serve requests read from stdin using the extension at the path
+///
+/// This is the entry point of the worker process and returns when stdin is closed.
+pub async fn serve(path: &Path) -> error::Result<()> {
+    let mut runtime = Runtime::new(path).await?;
+
+    let mut lines = BufReader::new(tokio::io::stdin()).lines();
+    let mut stdout = tokio::io::stdout();
+
+    while let Some(line) = lines.next_line().await.map_err(anyhow::Error::from)? {
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        // Without an id there is no one to answer, so the parent times out
+        let Message { id, body } = match serde_json::from_str::<Message<serde_json::Value>>(&line) {
+            Ok(message) => message,
+            Err(e) => {
+                log::warn!("invalid message: {e}");
+                continue;
+            }
+        };
+
+        let body = match serde_json::from_value::<WorkerRequest>(body) {
+            Ok(request) => handle(&mut runtime, request).await,
+            Err(e) => WorkerResponse::Failed(format!("invalid request: {e}")),
+        };
+
+        let mut response =
+            serde_json::to_string(&Message { id, body }).map_err(|_| Error::SerializeError)?;
+        response.push('\n');
+        stdout
+            .write_all(response.as_bytes())
+            .await
+            .map_err(anyhow::Error::from)?;
+        stdout.flush().await.map_err(anyhow::Error::from)?;
+    }
+
+    Ok(())
+}
+
+async fn handle(
+    runtime: &mut Runtime<crate::data::DefaultImpl>,
+    request: WorkerRequest,
+) -> WorkerResponse {
+    let result = match request {
+        WorkerRequest::Setup(config) => runtime.setup(&config).await.and_then(to_value),
+        WorkerRequest::Authenticate(session) => {
+            runtime.data_mut().session = Some(session);
+            Ok(serde_json::Value::Null)
+        }
+        WorkerRequest::Meta => runtime.meta().await.and_then(to_value),
+        WorkerRequest::CanonicalizeUrl { url } => {
+            runtime.canonicalize_url(&url).await.and_then(to_value)
+        }
+        WorkerRequest::FetchNovel { url, budget } => runtime
+            .within_budget(budget, async |runtime| runtime.fetch_novel(&url).await)
+            .await
+            .and_then(to_value),
+        WorkerRequest::FetchChapterContent { url, budget } => runtime
+            .within_budget(budget, async |runtime| {
+                runtime.fetch_chapter_content(&url).await
+            })
+            .await
+            .and_then(to_value),
+        WorkerRequest::Popular { page } => runtime.popular(page).await.and_then(to_value),
+        WorkerRequest::TextSearch { query, page } => {
+            runtime.text_search(&query, page).await.and_then(to_value)
+        }
+    };
+
+    match result {
+        Ok(value) => WorkerResponse::Value(value),
+        Err(Error::ReturnedError(e)) => WorkerResponse::Returned(e),
+        Err(Error::BudgetExceeded(exceeded)) => WorkerResponse::BudgetExceeded(exceeded),
+        Err(e) => WorkerResponse::Failed(e.to_string()),
+    }
+}
+
+fn to_value<T: Serialize>(value: T) -> error::Result<serde_json::Value> {
+    serde_json::to_value(value).map_err(|_| Error::SerializeError)
+}
+
+/// An extension running in a child process
+///
+/// The worker is started lazily with the program and arguments, and restarted
+/// after it crashes or fails to answer within the timeout.
+pub struct ProcessRuntime {
+    program: PathBuf,
+    args: Vec<OsString>,
+    timeout: Duration,
+    config: Option<ExtensionConfig>,
+    session: Option<Session>,
+    worker: Option<Worker>,
+}
+
+struct Worker {
+    child: Child,
+    stdin: ChildStdin,
+    stdout: Lines<BufReader<ChildStdout>>,
+    next_id: u64,
+}
+
+impl ProcessRuntime {
+    /// The command must start a process that calls [`serve`]
+    pub fn new<P, I, S>(program: P, args: I) -> Self
+    where
+        P: Into<PathBuf>,
+        I: IntoIterator<Item = S>,
+        S: Into<OsString>,
+    {
+        Self {
+            program: program.into(),
+            args: args.into_iter().map(Into::into).collect(),
+            timeout: Duration::from_secs(120),
+            config: None,
+            session: None,
+            worker: None,
+        }
+    }
+
+    /// The time to wait for a response before the worker is considered hung
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    pub async fn setup(&mut self, config: &ExtensionConfig) -> error::Result<()> {
+        self.config = Some(config.clone());
+
+        // The configuration is sent when the worker starts
+        self.kill();
+        self.worker().await?;
+        Ok(())
+    }
+
+    /// Authenticate requests with the session, kept when the worker restarts
+    pub async fn authenticate(&mut self, session: Session) -> error::Result<()> {
+        self.session = Some(session.clone());
+
+        // A worker started later receives the session along with the configuration
+        if self.worker.is_some() {
+            self.call(&WorkerRequest::Authenticate(session)).await
+        } else {
+            Ok(())
+        }
+    }
+
+    pub async fn meta(&mut self) -> error::Result<Meta> {
+        self.call(&WorkerRequest::Meta).await
+    }
+
+    pub async fn canonicalize_url(&mut self, url: &str) -> error::Result<String> {
+        self.call(&WorkerRequest::CanonicalizeUrl {
+            url: url.to_string(),
+        })
+        .await
+    }
+
+    /// Fetch the novel with the requests of the extension limited by the
+    /// budget, see [`Runtime::within_budget`]
+    pub async fn fetch_novel(&mut self, url: &str, budget: Budget) -> error::Result<Novel> {
+        self.call(&WorkerRequest::FetchNovel {
+            url: url.to_string(),
+            budget,
+        })
+        .await
+    }
+
+    /// Fetch the chapter content with the requests of the extension limited
+    /// by the budget, see [`Runtime::within_budget`]
+    pub async fn fetch_chapter_content(
+        &mut self,
+        url: &str,
+        budget: Budget,
+    ) -> error::Result<Content> {
+        self.call(&WorkerRequest::FetchChapterContent {
+            url: url.to_string(),
+            budget,
+        })
+        .await
+    }
+
+    pub async fn popular(&mut self, page: i32) -> error::Result<Vec<BasicNovel>> {
+        self.call(&WorkerRequest::Popular { page }).await
+    }
+
+    pub async fn text_search(&mut self, query: &str, page: i32) -> error::Result<Vec<BasicNovel>> {
+        self.call(&WorkerRequest::TextSearch {
+            query: query.to_string(),
+            page,
+        })
+        .await
+    }
+
+    async fn call<T: DeserializeOwned>(&mut self, request: &WorkerRequest) -> error::Result<T> {
+        let timeout = self.timeout;
+        let worker = self.worker().await?;
+
+        let response = match worker.send(request, timeout).await {
+            Ok(response) => response,
+            Err(e) => {
+                self.kill();
+                return Err(e);
+            }
+        };
+
+        match response {
+            WorkerResponse::Value(value) => {
+                serde_json::from_value(value).map_err(|_| Error::DeserializeError)
+            }
+            WorkerResponse::Returned(e) => Err(Error::ReturnedError(e)),
+            WorkerResponse::BudgetExceeded(exceeded) => Err(Error::BudgetExceeded(exceeded)),
+            WorkerResponse::Failed(message) => Err(Error::ProcessError(message)),
+        }
+    }
+
+    async fn worker(&mut self) -> error::Result<&mut Worker> {
+        if self.worker.is_none() {
+            let mut worker = Worker::spawn(&self.program, &self.args)?;
+
+            let setup = self.config.clone().map(WorkerRequest::Setup);
+            let authenticate = self.session.clone().map(WorkerRequest::Authenticate);
+            for request in setup.into_iter().chain(authenticate) {
+                if let WorkerResponse::Failed(message) = worker.send(&request, self.timeout).await?
+                {
+                    return Err(Error::ProcessError(message));
+                }
+            }
+
+            self.worker = Some(worker);
+        }
+
+        Ok(self.worker.as_mut().expect("worker was just started"))
+    }
+
+    /// Kill the worker, leaving the exited process to be reaped by tokio
+    fn kill(&mut self) {
+        if let Some(mut worker) = self.worker.take() {
+            let _ = worker.child.start_kill();
+        }
+    }
+}
+
+impl Drop for ProcessRuntime {
+    fn drop(&mut self) {
+        self.kill();
+    }
+}
+
+impl Worker {
+    fn spawn(program: &Path, args: &[OsString]) -> error::Result<Self> {
+        let mut command = Command::new(program);
+        command
+            .args(args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::inherit())
+            .kill_on_drop(true);
+
+        // Only pass through what the worker needs to reach the network
+        command.env_clear();
+        for key in [
+            "PATH",
+            "HTTP_PROXY",
+            "HTTPS_PROXY",
+            "NO_PROXY",
+            "http_proxy",
+            "https_proxy",
+            "no_proxy",
+            "SSL_CERT_FILE",
+            "SSL_CERT_DIR",
+        ] {
+            if let Some(value) = std::env::var_os(key) {
+                command.env(key, value);
+            }
+        }
+
+        let mut child = command
+            .spawn()
+            .map_err(|e| Error::ProcessError(format!("failed to start worker: {e}")))?;
+
+        let stdin = child.stdin.take().expect("stdin is piped");
+        let stdout = child.stdout.take().expect("stdout is piped");
+
+        log::debug!(
+            "started extension worker with pid {}",
+            child.id().unwrap_or_default()
+        );
+
+        Ok(Self {
+            child,
+            stdin,
+            stdout: BufReader::new(stdout).lines(),
+            next_id: 1,
+        })
+    }
+
+    async fn send(
+        &mut self,
+        request: &WorkerRequest,
+        timeout: Duration,
+    ) -> error::Result<WorkerResponse> {
+        let id = self.next_id;
+        self.next_id += 1;
+
+        let request = serde_json::to_string(&Message { id, body: request })
+            .map_err(|_| Error::SerializeError)?;
+        let write = async {
+            self.stdin.write_all(request.as_bytes()).await?;
+            self.stdin.write_all(b"\n").await?;
+            self.stdin.flush().await
+        };
+        write
+            .await
+            .map_err(|e| Error::ProcessError(format!("failed to write to worker: {e}")))?;
+
+        // Extensions may print to stdout, even lines that look like responses
+        let read = async {
+            while let Ok(Some(line)) = self.stdout.next_line().await {
+                match serde_json::from_str::<Message<WorkerResponse>>(&line) {
+                    Ok(message) if message.id == id => return Some(message.body),
+                    _ => log::info!("worker: {line}"),
+                }
+            }
+            None
+        };
+
+        match tokio::time::timeout(timeout, read).await {
+            Ok(Some(response)) => Ok(response),
+            Ok(None) => {
+                let status = self.child.wait().await.map(|status| status.to_string());
+                Err(Error::ProcessError(format!(
+                    "worker exited unexpectedly ({})",
+                    status.unwrap_or_else(|e| e.to_string())
+                )))
+            }
+            Err(_) => Err(Error::ProcessError(format!(
+                "worker did not respond within {}s",
+                timeout.as_secs()
+            ))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A worker that crashes the first time it is started, marking the path,
+    /// and afterwards prints a response with another id before answering
+    const FLAKY_WORKER: &str = r#"
+if [ -e "$1" ]; then
+  while read -r line; do
+    id=$(echo "$line" | sed 's/^{"id":\([0-9]*\).*/\1/')
+    echo '{"id":0,"body":{"value":"printed"}}'
+    echo "{\"id\":$id,\"body\":{\"value\":\"https://example.com\"}}"
+  done
+else
+  touch "$1"
+  read -r line
+  exit 1
+fi
+"#;
+
+    #[test]
+    fn should_round_trip_requests() {
+        let message = Message {
+            id: 7,
+            body: WorkerRequest::FetchNovel {
+                url: String::from("https://example.com/novel"),
+                budget: Budget::default().max_requests(3),
+            },
+        };
+
+        let line = serde_json::to_string(&message).unwrap();
+        let parsed = serde_json::from_str::<Message<WorkerRequest>>(&line).unwrap();
+
+        assert_eq!(parsed.id, 7);
+        match parsed.body {
+            WorkerRequest::FetchNovel { url, budget } => {
+                assert_eq!(url, "https://example.com/novel");
+                assert_eq!(budget.max_requests, Some(3));
+            }
+            request => panic!("unexpected request {request:?}"),
+        }
+    }
+
+    #[test]
+    fn should_round_trip_responses() {
+        let responses = [
+            WorkerResponse::Value(serde_json::json!({ "title": "Novel" })),
+            WorkerResponse::Returned(QuelleError::WasmAbiError(String::from("abi"))),
+            WorkerResponse::BudgetExceeded(BudgetExceeded::Requests(3)),
+            WorkerResponse::Failed(String::from("crashed")),
+        ];
+
+        for body in responses {
+            let expected = serde_json::to_value(&body).unwrap();
+            let line = serde_json::to_string(&Message { id: 1, body }).unwrap();
+            let parsed = serde_json::from_str::<Message<WorkerResponse>>(&line).unwrap();
+
+            assert_eq!(parsed.id, 1);
+            assert_eq!(serde_json::to_value(&parsed.body).unwrap(), expected);
+        }
+    }
+
+    #[tokio::test]
+    async fn should_restart_crashed_worker() {
+        let dir = tempfile::tempdir().unwrap();
+        let marker = dir.path().join("started");
+
+        let args = [
+            OsString::from("-c"),
+            FLAKY_WORKER.into(),
+            "sh".into(),
+            marker.into(),
+        ];
+        let mut runtime = ProcessRuntime::new("sh", args).timeout(Duration::from_secs(10));
+
+        let crashed = runtime.canonicalize_url("https://example.com").await;
+        assert!(matches!(crashed, Err(Error::ProcessError(_))));
+
+        let url = runtime
+            .canonicalize_url("https://example.com")
+            .await
+            .unwrap();
+        assert_eq!(url, "https://example.com");
+    }
+}
@@ -0,0 +1,93 @@
+use std::collections::{HashSet, VecDeque};
+
+use futures_util::{stream, Stream};
+use quelle_core::prelude::BasicNovel;
+
+use crate::{error, Runtime};
+
+/// The number of consecutive failed pages after which a search stops
+const MAX_PAGE_ERRORS: usize = 3;
+
+struct SearchState<'a, D> {
+    runtime: &'a mut Runtime<D>,
+    query: &'a str,
+    page: i32,
+    remaining: usize,
+    buffer: VecDeque<BasicNovel>,
+    seen: HashSet<String>,
+    errors: usize,
+    done: bool,
+}
+
+impl<'a, D: Send> SearchState<'a, D> {
+    /// Fetch the next page into the buffer
+    ///
+    /// Novels that were already returned by a previous page are dropped, and
+    /// the search ends once a page yields nothing new. Some sources keep
+    /// returning their last page for any page past the end.
+    async fn fetch_page(&mut self) -> error::Result<()> {
+        let page = self.page;
+        self.page += 1;
+
+        let novels = self.runtime.text_search(self.query, page).await?;
+        let before = self.buffer.len();
+        for novel in novels {
+            if self.seen.insert(novel.url.clone()) {
+                self.buffer.push_back(novel);
+            }
+        }
+
+        if self.buffer.len() == before {
+            self.done = true;
+        }
+
+        Ok(())
+    }
+}
+
+pub(crate) fn search_iter<'a, D: Send>(
+    runtime: &'a mut Runtime<D>,
+    query: &'a str,
+    limit: usize,
+) -> impl Stream<Item = error::Result<BasicNovel>> + 'a {
+    let state = SearchState {
+        runtime,
+        query,
+        page: 1,
+        remaining: limit,
+        buffer: VecDeque::new(),
+        seen: HashSet::new(),
+        errors: 0,
+        done: false,
+    };
+
+    stream::unfold(state, |mut state| async move {
+        loop {
+            if state.remaining == 0 {
+                return None;
+            }
+
+            if let Some(novel) = state.buffer.pop_front() {
+                state.remaining -= 1;
+                return Some((Ok(novel), state));
+            }
+
+            if state.done {
+                return None;
+            }
+
+            match state.fetch_page().await {
+                Ok(()) => state.errors = 0,
+                Err(e) => {
+                    state.errors += 1;
+                    if matches!(e, error::Error::NotSupported(_)) || state.errors >= MAX_PAGE_ERRORS
+                    {
+                        state.done = true;
+                    }
+
+                    return Some((Err(e), state));
+                }
+            }
+        }
+    })
+}
@@ -0,0 +1,31 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+/// An extension exporting the functions the runtime looks up, each doing
+/// nothing except `alloc`, which hands out memory from a bump allocator
+const STUB_EXTENSION: &str = r#"(module
+  (memory (export "memory") 1)
+  (global $heap (mut i32) (i32.const 1024))
+  (func (export "alloc") (param $len i32) (result i32)
+    (local $ptr i32)
+    (local.set $ptr (global.get $heap))
+    (global.set $heap (i32.add (global.get $heap) (local.get $len)))
+    (local.get $ptr))
+  (func (export "dealloc") (param i32 i32))
+  (func (export "stack_push") (param i32))
+  (func (export "stack_pop") (result i32) (i32.const 0))
+  (func (export "last_result") (result i32) (i32.const 0))
+  (func (export "setup_default") (param i32))
+  (func (export "meta") (result i32) (i32.const 0))
+  (func (export "fetch_novel") (param i32) (result i32) (i32.const 0))
+  (func (export "fetch_chapter_content") (param i32) (result i32) (i32.const 0)))
+"#;
+
+/// Write the stub extension into the directory, returning its path
+pub fn stub_extension(dir: &Path) -> PathBuf {
+    let path = dir.join("stub.wat");
+    fs::write(&path, STUB_EXTENSION).expect("the extension can be written");
+    path
+}
@@ -8,6 +8,8 @@ macro_rules! define_meta {
             base_urls: [$($base_url:literal),+],
             rds: [$($rd:ident),+],
             attrs: [$($attr:ident),*],
+            $(novel_urls: [$($novel_url:literal),*],)?
+            $(age_gate_cookies: [$(($cookie:literal, $value:literal)),*],)?
         };
     ) => {
         static $var: once_cell::sync::Lazy<Meta> = once_cell::sync::Lazy::new(|| Meta {
@@ -18,6 +20,11 @@ macro_rules! define_meta {
             base_urls: vec![$(String::from($base_url)),+],
             rds: vec![$(ReadingDirection::$rd),+],
             attrs: vec![$(Attribute::$attr),*],
+            novel_url_patterns: vec![$($(String::from($novel_url)),*)?],
+            age_gate: None $(.or(Some(AgeGate {
+                cookies: vec![$((String::from($cookie), String::from($value))),*],
+                headers: vec![],
+            })))?,
         });
 
 
@@ -37,6 +37,34 @@ macro_rules! expose_basic {
     };
 }
 
+/// This trait lets an extension correct urls given by users
+///
+/// The trait should be exposed to wasm abi using [`expose_canonicalize`]
+///
+/// ## Example
+///
+/// ```ignore
+/// struct ExtensionName;
+/// expose_canonicalize!(ExtensionName);
+/// ```
+pub trait CanonicalizeUrl {
+    /// The url of the novel page for any url of the novel, such as a chapter url
+    ///
+    /// The url should be returned unchanged when it already points to the novel.
+    fn canonicalize_url(url: String) -> Result<String, QuelleError>;
+}
+
+/// The macro used to export [CanonicalizeUrl] to wasm abi
+#[macro_export]
+macro_rules! expose_canonicalize {
+    ($name:ident) => {
+        #[quelle_glue::prelude::expose]
+        pub fn canonicalize_url(url: String) -> Result<String, QuelleError> {
+            <$name as $crate::traits::CanonicalizeUrl>::canonicalize_url(url)
+        }
+    };
+}
+
 /// This trait adds popular search functionality to an extension/source
 ///
 /// The trait should be exposed to wasm abi using [`expose_popular`]
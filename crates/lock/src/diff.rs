@@ -0,0 +1,238 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    ffi::OsStr,
+    fmt::{Debug, Display},
+    fs,
+    path::Path,
+};
+
+use anyhow::anyhow;
+use quelle_core::prelude::Meta;
+use quelle_engine::Runtime;
+
+use crate::package::{unpack_package, PACKAGE_EXTENSION};
+
+/// What an extension declares and supports, compared by [`diff_extensions`]
+#[derive(Debug)]
+pub struct ExtensionSummary {
+    pub meta: Meta,
+    /// The optional functions the extension exports, such as `popular`
+    pub capabilities: BTreeSet<&'static str>,
+    /// The description of each search filter by name
+    pub filters: BTreeMap<String, String>,
+    /// The size of the wasm or package in bytes
+    pub size: u64,
+}
+
+impl ExtensionSummary {
+    /// Read the summary of a wasm file or a package
+    ///
+    /// Packages are unpacked into a temporary directory removed afterwards.
+    pub async fn load(path: &Path) -> anyhow::Result<Self> {
+        let size = fs::metadata(path)?.len();
+
+        if path.extension() != Some(OsStr::new(PACKAGE_EXTENSION)) {
+            return Self::load_wasm(path, size).await;
+        }
+
+        let dir = std::env::temp_dir().join(format!(
+            "quelle-diff-{}-{}",
+            std::process::id(),
+            path.file_stem().unwrap_or_default().to_string_lossy()
+        ));
+        let summary = match unpack_package(path, &dir) {
+            Ok(unpacked) => Self::load_wasm(&unpacked.files.wasm, size).await,
+            Err(e) => Err(e),
+        };
+        // Packages failing to unpack can leave some of their files behind
+        if dir.exists() {
+            fs::remove_dir_all(&dir)?;
+        }
+        summary
+    }
+
+    async fn load_wasm(path: &Path, size: u64) -> anyhow::Result<Self> {
+        let mut runner = Runtime::new(path)
+            .await
+            .map_err(|e| anyhow!(e.to_string()))?;
+        let meta = runner.meta().await.map_err(|e| anyhow!(e.to_string()))?;
+
+        let supported = [
+            ("canonicalize_url", runner.canonicalize_url_supported()),
+            ("popular", runner.popular_supported()),
+            ("text_search", runner.text_search_supported()),
+            ("filter_search", runner.filter_search_supported()),
+        ];
+        let capabilities = supported
+            .into_iter()
+            .filter(|(_, supported)| *supported)
+            .map(|(name, _)| name)
+            .collect();
+
+        let filters = if runner.filter_search_supported() {
+            runner
+                .filter_options()
+                .await
+                .map_err(|e| anyhow!(e.to_string()))?
+                .into_iter()
+                .map(|(name, field)| (name, format!("{field:?}")))
+                .collect()
+        } else {
+            BTreeMap::new()
+        };
+
+        Ok(Self {
+            meta,
+            capabilities,
+            filters,
+            size,
+        })
+    }
+}
+
+/// A difference between two versions of an extension
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Change {
+    Added {
+        field: String,
+        value: String,
+    },
+    Removed {
+        field: String,
+        value: String,
+    },
+    Changed {
+        field: String,
+        old: String,
+        new: String,
+    },
+}
+
+impl Display for Change {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Change::Added { field, value } => write!(f, "+ {field}: {value}"),
+            Change::Removed { field, value } => write!(f, "- {field}: {value}"),
+            Change::Changed { field, old, new } => write!(f, "~ {field}: {old} -> {new}"),
+        }
+    }
+}
+
+/// The changes from the old version of the extension to the new one
+pub fn diff_extensions(old: &ExtensionSummary, new: &ExtensionSummary) -> Vec<Change> {
+    let mut changes = vec![];
+
+    let values = [
+        ("id", &old.meta.id, &new.meta.id),
+        ("name", &old.meta.name, &new.meta.name),
+        ("version", &old.meta.version, &new.meta.version),
+    ];
+    for (field, old, new) in values {
+        if old != new {
+            changes.push(Change::Changed {
+                field: field.to_string(),
+                old: old.clone(),
+                new: new.clone(),
+            });
+        }
+    }
+
+    let lists = [
+        ("base_urls", &old.meta.base_urls, &new.meta.base_urls),
+        ("langs", &old.meta.langs, &new.meta.langs),
+        (
+            "novel_url_patterns",
+            &old.meta.novel_url_patterns,
+            &new.meta.novel_url_patterns,
+        ),
+    ];
+    for (field, old, new) in lists {
+        diff_sets(&mut changes, field, old.iter(), new.iter());
+    }
+
+    let (old_rds, new_rds) = (debug_strings(&old.meta.rds), debug_strings(&new.meta.rds));
+    diff_sets(&mut changes, "rds", old_rds.iter(), new_rds.iter());
+    let (old_attrs, new_attrs) = (
+        debug_strings(&old.meta.attrs),
+        debug_strings(&new.meta.attrs),
+    );
+    diff_sets(&mut changes, "attrs", old_attrs.iter(), new_attrs.iter());
+
+    if old.meta.age_gate != new.meta.age_gate {
+        changes.push(Change::Changed {
+            field: String::from("age_gate"),
+            old: format!("{:?}", old.meta.age_gate),
+            new: format!("{:?}", new.meta.age_gate),
+        });
+    }
+
+    diff_sets(
+        &mut changes,
+        "capabilities",
+        old.capabilities.iter(),
+        new.capabilities.iter(),
+    );
+
+    for (name, field) in &old.filters {
+        match new.filters.get(name) {
+            None => changes.push(Change::Removed {
+                field: String::from("filters"),
+                value: name.clone(),
+            }),
+            Some(new) if new != field => changes.push(Change::Changed {
+                field: format!("filters.{name}"),
+                old: field.clone(),
+                new: new.clone(),
+            }),
+            Some(_) => {}
+        }
+    }
+    for name in new.filters.keys() {
+        if !old.filters.contains_key(name) {
+            changes.push(Change::Added {
+                field: String::from("filters"),
+                value: name.clone(),
+            });
+        }
+    }
+
+    if old.size != new.size {
+        changes.push(Change::Changed {
+            field: String::from("size"),
+            old: format!("{} bytes", old.size),
+            new: format!("{} bytes", new.size),
+        });
+    }
+
+    changes
+}
+
+/// Report the values only present in one of the lists
+fn diff_sets<'a, T>(
+    changes: &mut Vec<Change>,
+    field: &str,
+    old: impl Iterator<Item = &'a T>,
+    new: impl Iterator<Item = &'a T>,
+) where
+    T: Display + Ord + ?Sized + 'a,
+{
+    let old = old.collect::<BTreeSet<_>>();
+    let new = new.collect::<BTreeSet<_>>();
+
+    for value in old.difference(&new) {
+        changes.push(Change::Removed {
+            field: field.to_string(),
+            value: value.to_string(),
+        });
+    }
+    for value in new.difference(&old) {
+        changes.push(Change::Added {
+            field: field.to_string(),
+            value: value.to_string(),
+        });
+    }
+}
+
+fn debug_strings<T: Debug>(values: &[T]) -> Vec<String> {
+    values.iter().map(|value| format!("{value:?}")).collect()
+}
@@ -8,13 +8,26 @@ use std::{
 
 use anyhow::{anyhow, bail, Context};
 use log::{debug, info};
-use quelle_engine::Runtime;
+use quelle_core::prelude::{matches_url_pattern, Attribute};
+use quelle_engine::{fixtures::Fixtures, Runtime};
 use serde::{Deserialize, Serialize};
 
+pub mod diff;
+pub mod package;
+pub mod validation;
+
+use package::{is_hex_color, unpack_package, PACKAGE_EXTENSION};
+
+/// The directory packages are unpacked into, inside the extensions directory
+const INSTALLED_DIR: &str = ".installed";
+
 #[derive(Serialize, Deserialize, Debug)]
 pub struct Lock {
     pub version: usize,
     pub extensions: HashMap<String, Extension>,
+    /// Curated lists of extension ids, such as starter packs
+    #[serde(default)]
+    pub lists: HashMap<String, Vec<String>>,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -23,7 +36,47 @@ pub struct Extension {
     pub version: String,
     pub base_urls: Vec<String>,
     pub langs: Vec<String>,
+    /// Tags used to group extensions, derived from the languages and
+    /// attributes of the extension (ex: `lang:en`, `fanfiction`)
+    #[serde(default)]
+    pub categories: Vec<String>,
     pub path: PathBuf,
+    /// Recorded responses used to verify the extension without network
+    #[serde(default)]
+    pub fixtures: Option<PathBuf>,
+    /// Patterns of novel urls, see [`quelle_core::prelude::Meta::novel_url_patterns`]
+    #[serde(default)]
+    pub novel_url_patterns: Vec<String>,
+    /// The icon bundled in the package of the extension
+    #[serde(default)]
+    pub icon: Option<PathBuf>,
+    /// The color used to brand the extension, as `#rrggbb`
+    #[serde(default)]
+    pub accent: Option<String>,
+    /// The changelog bundled in the package of the extension
+    #[serde(default)]
+    pub changelog: Option<PathBuf>,
+}
+
+impl Extension {
+    pub fn has_category(&self, category: &str) -> bool {
+        self.categories
+            .iter()
+            .any(|value| value.eq_ignore_ascii_case(category))
+    }
+
+    /// Whether the url points to a novel, or none when the extension has no patterns
+    pub fn is_novel_url(&self, url: &str) -> Option<bool> {
+        if self.novel_url_patterns.is_empty() {
+            return None;
+        }
+
+        Some(
+            self.novel_url_patterns
+                .iter()
+                .any(|pattern| matches_url_pattern(pattern, url)),
+        )
+    }
 }
 
 impl Lock {
@@ -34,25 +87,59 @@ impl Lock {
     }
 
     pub fn detect(&self, url: &str) -> anyhow::Result<Option<&Extension>> {
-        for (_, extension) in &self.extensions {
-            for base_url in &extension.base_urls {
-                if url.starts_with(base_url) {
-                    return Ok(Some(extension));
-                }
-            }
-        }
+        Ok(self.find(url).map(|(_, extension)| extension))
+    }
 
-        Ok(None)
+    /// The id and extension of the source the url belongs to
+    pub fn find(&self, url: &str) -> Option<(&String, &Extension)> {
+        self.extensions.iter().find(|(_, extension)| {
+            extension
+                .base_urls
+                .iter()
+                .any(|base_url| url.starts_with(base_url))
+        })
     }
 
+    /// The extensions in the curated list with the given name
+    ///
+    /// Ids in the list that are not present in the lock are skipped.
+    pub fn curated(&self, name: &str) -> Option<Vec<(&String, &Extension)>> {
+        let ids = self.lists.get(name)?;
+        let extensions = ids
+            .iter()
+            .filter_map(|id| self.extensions.get_key_value(id))
+            .collect();
+
+        Some(extensions)
+    }
+
+    /// Read the extensions of the directory, either bare wasm files or packages
+    ///
+    /// Packages are verified and unpacked into a hidden directory next to them.
     pub async fn generate(extensions_dir: &Path) -> anyhow::Result<Self> {
         let mut extensions = HashMap::new();
 
         for entry in fs::read_dir(extensions_dir)? {
             let entry = entry?;
-            let path = entry.path();
-
-            if path.extension() != Some(OsStr::new("wasm")) {
+            let mut path = entry.path();
+            let mut fixtures = Fixtures::path_for(&path);
+            let (mut icon, mut accent, mut changelog) = (None, None, None);
+
+            if path.extension() == Some(OsStr::new(PACKAGE_EXTENSION)) {
+                let stem = path.file_stem().unwrap_or_default();
+                let dir = extensions_dir.join(INSTALLED_DIR).join(stem);
+
+                info!("Unpacking '{}'...", path.display());
+                let unpacked = unpack_package(&path, &dir)?;
+                path = unpacked.files.wasm;
+                fixtures = unpacked.files.fixtures.unwrap_or_default();
+                icon = unpacked.files.icon;
+                accent = unpacked
+                    .manifest
+                    .accent
+                    .filter(|accent| is_hex_color(accent));
+                changelog = unpacked.files.changelog;
+            } else if path.extension() != Some(OsStr::new("wasm")) {
                 debug!("skipped non-wasm file '{}'", path.display());
                 continue;
             }
@@ -70,12 +157,31 @@ impl Lock {
 
             info!("Found {}=={}", meta.id, meta.version);
 
+            let categories = meta
+                .langs
+                .iter()
+                .map(|lang| format!("lang:{lang}"))
+                .chain(meta.attrs.iter().map(|attr| match attr {
+                    Attribute::Fanfiction => String::from("fanfiction"),
+                }))
+                .collect();
+
+            if fixtures.exists() {
+                info!("Found fixtures at '{}'", fixtures.display());
+            }
+
             let extension = Extension {
                 name: meta.name,
                 version: meta.version,
                 base_urls: meta.base_urls,
                 langs: meta.langs,
-                path: entry.path(),
+                categories,
+                path,
+                fixtures: fixtures.exists().then_some(fixtures),
+                novel_url_patterns: meta.novel_url_patterns,
+                icon,
+                accent,
+                changelog,
             };
 
             extensions.insert(meta.id, extension);
@@ -84,6 +190,7 @@ impl Lock {
         let lock = Lock {
             version: 1,
             extensions,
+            lists: HashMap::new(),
         };
 
         Ok(lock)
@@ -0,0 +1,200 @@
+use std::{
+    collections::BTreeMap,
+    fs::{self, File},
+    io::{BufReader, Read},
+    path::{Path, PathBuf},
+};
+
+use anyhow::{bail, Context};
+use flate2::{read::GzDecoder, write::GzEncoder};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+/// The version of the package layout written by [`write_package`]
+pub const PACKAGE_FORMAT: u32 = 2;
+
+/// The extension of package files, bare `.wasm` files being the first format
+pub const PACKAGE_EXTENSION: &str = "qpkg";
+
+const MANIFEST: &str = "manifest.json";
+const WASM: &str = "extension.wasm";
+const CHANGELOG: &str = "CHANGELOG.md";
+const FIXTURES: &str = "fixtures.json";
+const ICON: &str = "icon";
+
+/// The first entry of a package, listing the checksum of every other file
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct PackageManifest {
+    pub format: u32,
+    /// The name of the crate the extension was built from
+    pub package: String,
+    pub version: String,
+    /// The color used to brand the extension in interfaces, as `#rrggbb`
+    #[serde(default)]
+    pub accent: Option<String>,
+    /// The sha256 of each file by its name in the package
+    pub files: BTreeMap<String, String>,
+}
+
+/// The files bundled into a package along with the wasm
+#[derive(Debug, Default)]
+pub struct PackageFiles {
+    pub wasm: PathBuf,
+    pub icon: Option<PathBuf>,
+    pub changelog: Option<PathBuf>,
+    pub fixtures: Option<PathBuf>,
+}
+
+/// The files of a package after it was unpacked and verified
+#[derive(Debug)]
+pub struct UnpackedPackage {
+    pub manifest: PackageManifest,
+    pub files: PackageFiles,
+}
+
+/// Bundle the files of the extension into a package at the path
+pub fn write_package(
+    path: &Path,
+    package: &str,
+    version: &str,
+    accent: Option<&str>,
+    files: &PackageFiles,
+) -> anyhow::Result<PackageManifest> {
+    if let Some(accent) = accent.filter(|accent| !is_hex_color(accent)) {
+        bail!("the accent color '{accent}' is not of the form '#rrggbb'");
+    }
+
+    let mut entries = vec![(String::from(WASM), fs::read(&files.wasm)?)];
+
+    if let Some(icon) = &files.icon {
+        let name = match icon.extension().and_then(|value| value.to_str()) {
+            Some(extension) => format!("{ICON}.{extension}"),
+            None => String::from(ICON),
+        };
+        entries.push((name, fs::read(icon)?));
+    }
+    if let Some(changelog) = &files.changelog {
+        entries.push((String::from(CHANGELOG), fs::read(changelog)?));
+    }
+    if let Some(fixtures) = &files.fixtures {
+        entries.push((String::from(FIXTURES), fs::read(fixtures)?));
+    }
+
+    let manifest = PackageManifest {
+        format: PACKAGE_FORMAT,
+        package: package.to_string(),
+        version: version.to_string(),
+        accent: accent.map(str::to_lowercase),
+        files: entries
+            .iter()
+            .map(|(name, data)| (name.clone(), hash(data)))
+            .collect(),
+    };
+
+    let file =
+        File::create(path).with_context(|| format!("failed to create '{}'", path.display()))?;
+    let mut builder = tar::Builder::new(GzEncoder::new(file, flate2::Compression::default()));
+
+    let manifest_data = serde_json::to_vec_pretty(&manifest)?;
+    for (name, data) in [(String::from(MANIFEST), manifest_data)]
+        .iter()
+        .chain(&entries)
+    {
+        let mut header = tar::Header::new_gnu();
+        header.set_size(data.len() as u64);
+        header.set_mode(0o644);
+        header.set_cksum();
+        builder.append_data(&mut header, name, data.as_slice())?;
+    }
+
+    builder.into_inner()?.finish()?;
+    Ok(manifest)
+}
+
+/// Unpack the package into the directory, verifying the checksum of every file
+///
+/// The directory is replaced, so that files of a previous version do not remain.
+pub fn unpack_package(path: &Path, dir: &Path) -> anyhow::Result<UnpackedPackage> {
+    let file = File::open(path).with_context(|| format!("failed to open '{}'", path.display()))?;
+    let mut archive = tar::Archive::new(GzDecoder::new(BufReader::new(file)));
+
+    let mut manifest: Option<PackageManifest> = None;
+    let mut contents = BTreeMap::new();
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        let name = entry.path()?.to_string_lossy().into_owned();
+
+        let mut data = vec![];
+        entry.read_to_end(&mut data)?;
+
+        if name == MANIFEST {
+            manifest = Some(serde_json::from_slice(&data).context("invalid package manifest")?);
+        } else {
+            contents.insert(name, data);
+        }
+    }
+
+    let Some(manifest) = manifest else {
+        bail!("'{}' has no manifest", path.display());
+    };
+
+    if manifest.format > PACKAGE_FORMAT {
+        bail!(
+            "'{}' uses package format {}, newer than the supported format {PACKAGE_FORMAT}",
+            path.display(),
+            manifest.format
+        );
+    }
+
+    for (name, expected) in &manifest.files {
+        match contents.get(name) {
+            Some(data) if hash(data) == *expected => {}
+            Some(_) => bail!(
+                "the checksum of '{name}' in '{}' does not match",
+                path.display()
+            ),
+            None => bail!("'{}' is missing '{name}'", path.display()),
+        }
+    }
+
+    if dir.exists() {
+        fs::remove_dir_all(dir)?;
+    }
+    fs::create_dir_all(dir)?;
+
+    let mut files = PackageFiles::default();
+    for (name, data) in &contents {
+        // Files that are not listed in the manifest cannot be trusted
+        if !manifest.files.contains_key(name) || Path::new(name).components().count() != 1 {
+            continue;
+        }
+
+        let target = dir.join(name);
+        fs::write(&target, data)?;
+
+        match name.as_str() {
+            WASM => files.wasm = target,
+            CHANGELOG => files.changelog = Some(target),
+            FIXTURES => files.fixtures = Some(target),
+            _ if name.starts_with(ICON) => files.icon = Some(target),
+            _ => {}
+        }
+    }
+
+    if files.wasm.as_os_str().is_empty() {
+        bail!("'{}' is missing '{WASM}'", path.display());
+    }
+
+    Ok(UnpackedPackage { manifest, files })
+}
+
+/// Whether the value is a color of the form `#rrggbb`
+pub fn is_hex_color(value: &str) -> bool {
+    value
+        .strip_prefix('#')
+        .is_some_and(|hex| hex.len() == 6 && hex.chars().all(|c| c.is_ascii_hexdigit()))
+}
+
+fn hash(data: &[u8]) -> String {
+    format!("{:x}", Sha256::digest(data))
+}
@@ -0,0 +1,370 @@
+use std::{
+    fmt::Display,
+    fs,
+    panic::{self, AssertUnwindSafe},
+    path::Path,
+    sync::{mpsc, Arc},
+    thread,
+    time::{Duration, Instant},
+};
+
+use anyhow::anyhow;
+use quelle_core::prelude::Meta;
+use quelle_engine::{module_imports, Runtime, HOST_IMPORTS};
+use serde::Serialize;
+
+/// How long a rule may run before it is reported as timed out
+pub const DEFAULT_RULE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// The largest wasm accepted by [`WasmSizeBudget::default`] (8 MiB)
+pub const DEFAULT_WASM_BUDGET: u64 = 8 * 1024 * 1024;
+
+/// What the rules check, read once from the built extension
+#[derive(Debug, Clone)]
+pub struct ValidationContext {
+    pub wasm_size: u64,
+    /// The module and name of every function imported by the wasm
+    pub imports: Vec<(String, String)>,
+    pub meta: Meta,
+}
+
+impl ValidationContext {
+    /// Read the size, imports and meta information of the wasm
+    pub async fn load(path: &Path) -> anyhow::Result<Self> {
+        let wasm_size = fs::metadata(path)?.len();
+        let imports = module_imports(path).map_err(|e| anyhow!(e.to_string()))?;
+
+        let mut runner = Runtime::new(path)
+            .await
+            .map_err(|e| anyhow!(e.to_string()))?;
+        let meta = runner.meta().await.map_err(|e| anyhow!(e.to_string()))?;
+
+        Ok(Self {
+            wasm_size,
+            imports,
+            meta,
+        })
+    }
+}
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "snake_case")]
+pub enum Severity {
+    /// Reported without failing the validation
+    Warning,
+    Error,
+}
+
+/// A problem found by a rule
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct Finding {
+    pub severity: Severity,
+    pub message: String,
+}
+
+impl Finding {
+    pub fn warning(message: impl Into<String>) -> Self {
+        Self {
+            severity: Severity::Warning,
+            message: message.into(),
+        }
+    }
+
+    pub fn error(message: impl Into<String>) -> Self {
+        Self {
+            severity: Severity::Error,
+            message: message.into(),
+        }
+    }
+}
+
+/// A check run on an extension before it is published
+pub trait Rule: Send + Sync {
+    /// A short name identifying the rule in reports
+    fn name(&self) -> &'static str;
+
+    fn check(&self, context: &ValidationContext) -> Vec<Finding>;
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+#[serde(tag = "outcome", content = "findings", rename_all = "snake_case")]
+pub enum Outcome {
+    Completed(Vec<Finding>),
+    /// The rule did not finish within its timeout
+    TimedOut,
+    /// The rule panicked
+    Crashed,
+}
+
+/// The outcome of a single rule
+#[derive(Serialize, Debug, Clone)]
+pub struct RuleResult {
+    pub rule: &'static str,
+    #[serde(flatten)]
+    pub outcome: Outcome,
+    pub elapsed: Duration,
+}
+
+impl RuleResult {
+    pub fn passed(&self) -> bool {
+        match &self.outcome {
+            Outcome::Completed(findings) => findings
+                .iter()
+                .all(|finding| finding.severity < Severity::Error),
+            Outcome::TimedOut | Outcome::Crashed => false,
+        }
+    }
+}
+
+/// The results of every rule, in the order the rules were added
+#[derive(Serialize, Debug, Clone, Default)]
+pub struct ValidationReport {
+    pub results: Vec<RuleResult>,
+}
+
+impl ValidationReport {
+    pub fn passed(&self) -> bool {
+        self.results.iter().all(RuleResult::passed)
+    }
+}
+
+impl Display for ValidationReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for result in &self.results {
+            let status = if result.passed() { "PASS" } else { "FAIL" };
+            writeln!(f, "{status} {} ({:?})", result.rule, result.elapsed)?;
+
+            match &result.outcome {
+                Outcome::Completed(findings) => {
+                    for finding in findings {
+                        writeln!(f, "  {:?}: {}", finding.severity, finding.message)?;
+                    }
+                }
+                Outcome::TimedOut => writeln!(f, "  timed out")?,
+                Outcome::Crashed => writeln!(f, "  the rule panicked")?,
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Runs validation rules concurrently, each on its own thread with a timeout
+///
+/// A rule that times out is reported as failed and left to finish in the
+/// background, so a slow rule never holds up the others.
+pub struct ValidationEngine {
+    rules: Vec<(Arc<dyn Rule>, Duration)>,
+}
+
+impl Default for ValidationEngine {
+    /// The engine with every built-in rule
+    fn default() -> Self {
+        Self::new()
+            .rule(WasmSizeBudget::default())
+            .rule(ImportAllowlist::default())
+            .rule(MetadataCompleteness)
+            .rule(UrlPatternSanity)
+    }
+}
+
+impl ValidationEngine {
+    /// An engine without any rule
+    pub fn new() -> Self {
+        Self { rules: vec![] }
+    }
+
+    pub fn rule(self, rule: impl Rule + 'static) -> Self {
+        self.rule_with_timeout(rule, DEFAULT_RULE_TIMEOUT)
+    }
+
+    pub fn rule_with_timeout(mut self, rule: impl Rule + 'static, timeout: Duration) -> Self {
+        self.rules.push((Arc::new(rule), timeout));
+        self
+    }
+
+    pub fn run(&self, context: ValidationContext) -> ValidationReport {
+        let context = Arc::new(context);
+        let started = Instant::now();
+
+        let receivers = self
+            .rules
+            .iter()
+            .map(|(rule, timeout)| {
+                let (sender, receiver) = mpsc::channel();
+                let name = rule.name();
+                let (rule, context) = (rule.clone(), context.clone());
+                thread::spawn(move || {
+                    let started = Instant::now();
+                    let findings = panic::catch_unwind(AssertUnwindSafe(|| rule.check(&context)));
+                    let _ = sender.send((findings, started.elapsed()));
+                });
+                (name, *timeout, receiver)
+            })
+            .collect::<Vec<_>>();
+
+        let results = receivers
+            .into_iter()
+            .map(|(rule, timeout, receiver)| {
+                // The rules started together, so the time already waited counts
+                let remaining = timeout.saturating_sub(started.elapsed());
+                let (outcome, elapsed) = match receiver.recv_timeout(remaining) {
+                    Ok((Ok(findings), elapsed)) => (Outcome::Completed(findings), elapsed),
+                    Ok((Err(_), elapsed)) => (Outcome::Crashed, elapsed),
+                    Err(mpsc::RecvTimeoutError::Timeout) => (Outcome::TimedOut, timeout),
+                    Err(mpsc::RecvTimeoutError::Disconnected) => {
+                        (Outcome::Crashed, started.elapsed())
+                    }
+                };
+
+                RuleResult {
+                    rule,
+                    outcome,
+                    elapsed,
+                }
+            })
+            .collect();
+
+        ValidationReport { results }
+    }
+}
+
+/// Fails extensions whose wasm is larger than the budget
+pub struct WasmSizeBudget {
+    pub max_bytes: u64,
+}
+
+impl Default for WasmSizeBudget {
+    fn default() -> Self {
+        Self {
+            max_bytes: DEFAULT_WASM_BUDGET,
+        }
+    }
+}
+
+impl Rule for WasmSizeBudget {
+    fn name(&self) -> &'static str {
+        "wasm-size-budget"
+    }
+
+    fn check(&self, context: &ValidationContext) -> Vec<Finding> {
+        if context.wasm_size <= self.max_bytes {
+            return vec![];
+        }
+
+        vec![Finding::error(format!(
+            "the wasm is {} bytes, over the budget of {} bytes",
+            context.wasm_size, self.max_bytes
+        ))]
+    }
+}
+
+/// Fails extensions importing functions the host does not provide
+pub struct ImportAllowlist {
+    pub allowed: Vec<(String, String)>,
+}
+
+impl Default for ImportAllowlist {
+    /// Allow the functions linked by the engine
+    fn default() -> Self {
+        Self {
+            allowed: HOST_IMPORTS
+                .iter()
+                .map(|(module, name)| (module.to_string(), name.to_string()))
+                .collect(),
+        }
+    }
+}
+
+impl Rule for ImportAllowlist {
+    fn name(&self) -> &'static str {
+        "import-allowlist"
+    }
+
+    fn check(&self, context: &ValidationContext) -> Vec<Finding> {
+        context
+            .imports
+            .iter()
+            .filter(|import| !self.allowed.contains(import))
+            .map(|(module, name)| {
+                Finding::error(format!("imports '{module}::{name}', which is not allowed"))
+            })
+            .collect()
+    }
+}
+
+/// Checks that the meta information identifies the source
+pub struct MetadataCompleteness;
+
+impl Rule for MetadataCompleteness {
+    fn name(&self) -> &'static str {
+        "metadata-completeness"
+    }
+
+    fn check(&self, context: &ValidationContext) -> Vec<Finding> {
+        let meta = &context.meta;
+        let mut findings = vec![];
+
+        let required = [
+            ("id", meta.id.is_empty()),
+            ("name", meta.name.is_empty()),
+            ("version", meta.version.is_empty()),
+            ("base_urls", meta.base_urls.is_empty()),
+        ];
+        for (field, missing) in required {
+            if missing {
+                findings.push(Finding::error(format!("the meta field '{field}' is empty")));
+            }
+        }
+
+        if meta.langs.is_empty() {
+            findings.push(Finding::warning("no language is declared"));
+        }
+        if meta.novel_url_patterns.is_empty() {
+            findings.push(Finding::warning(
+                "no novel url pattern is declared, urls cannot be checked before fetching",
+            ));
+        }
+
+        findings
+    }
+}
+
+/// Checks that the base urls are absolute and the novel url patterns belong to them
+pub struct UrlPatternSanity;
+
+impl Rule for UrlPatternSanity {
+    fn name(&self) -> &'static str {
+        "url-pattern-sanity"
+    }
+
+    fn check(&self, context: &ValidationContext) -> Vec<Finding> {
+        let meta = &context.meta;
+        let mut findings = vec![];
+
+        for base_url in &meta.base_urls {
+            if !base_url.starts_with("https://") && !base_url.starts_with("http://") {
+                findings.push(Finding::error(format!(
+                    "the base url '{base_url}' is not an http url"
+                )));
+            }
+        }
+
+        // A pattern outside of the base urls would never be detected
+        for pattern in &meta.novel_url_patterns {
+            let prefix = pattern.split('*').next().unwrap_or_default();
+            let covered = meta
+                .base_urls
+                .iter()
+                .any(|base_url| prefix.starts_with(base_url.trim_end_matches('/')));
+
+            if !covered {
+                findings.push(Finding::error(format!(
+                    "the pattern '{pattern}' does not start with a base url"
+                )));
+            }
+        }
+
+        findings
+    }
+}
@@ -0,0 +1,166 @@
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+};
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::{
+    create_parent_all,
+    encryption::{self, Cipher},
+    error::PersistResult,
+};
+
+/// Images and other files of a novel, such as covers and chapter images, keyed by url
+///
+/// Files are stored once under their hash so that exports can use them
+/// instead of downloading them again.
+#[derive(Debug)]
+pub struct AssetStore {
+    dir: PathBuf,
+    index: AssetIndex,
+    cipher: Option<Cipher>,
+    changed: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug, Default)]
+struct AssetIndex {
+    assets: BTreeMap<String, Asset>,
+}
+
+/// A file stored for a novel
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Asset {
+    /// The url the file was downloaded from
+    pub url: String,
+    pub content_type: String,
+    /// A hash of the content, also used as the name of the file
+    pub hash: String,
+    pub size: u64,
+    pub stored_at: DateTime<Utc>,
+}
+
+impl AssetStore {
+    pub fn open(dir: PathBuf, cipher: Option<Cipher>) -> PersistResult<Self> {
+        let path = Self::index_path(&dir);
+        let index = if path.exists() {
+            serde_json::from_slice(&encryption::read_file(&path, cipher.as_ref())?)?
+        } else {
+            Default::default()
+        };
+
+        Ok(Self {
+            dir,
+            index,
+            cipher,
+            changed: false,
+        })
+    }
+
+    pub fn save(&mut self) -> PersistResult<()> {
+        if !self.changed {
+            return Ok(());
+        }
+
+        let path = Self::index_path(&self.dir);
+        create_parent_all(&path)?;
+
+        let data = serde_json::to_vec(&self.index)?;
+        encryption::write_file(&path, &data, self.cipher.as_ref())?;
+
+        self.changed = false;
+        Ok(())
+    }
+
+    /// Store the content downloaded from the url, replacing any previous content
+    pub fn store_asset(
+        &mut self,
+        url: &str,
+        content_type: &str,
+        content: &[u8],
+    ) -> PersistResult<Asset> {
+        let hash = format!("{:x}", Sha256::digest(content));
+
+        let path = self.content_path(&hash);
+        if !path.exists() {
+            create_parent_all(&path)?;
+            encryption::write_file(&path, content, self.cipher.as_ref())?;
+        }
+
+        let asset = Asset {
+            url: url.to_string(),
+            content_type: content_type.to_string(),
+            hash,
+            size: content.len() as u64,
+            stored_at: Utc::now(),
+        };
+
+        self.index.assets.insert(url.to_string(), asset.clone());
+        self.changed = true;
+
+        Ok(asset)
+    }
+
+    /// The stored asset of the url and its content
+    pub fn get_asset(&self, url: &str) -> PersistResult<Option<(&Asset, Vec<u8>)>> {
+        let Some(asset) = self.index.assets.get(url) else {
+            return Ok(None);
+        };
+
+        let path = self.content_path(&asset.hash);
+        if !path.exists() {
+            return Ok(None);
+        }
+
+        let content = encryption::read_file(&path, self.cipher.as_ref())?;
+
+        Ok(Some((asset, content)))
+    }
+
+    /// Every stored asset ordered by url
+    pub fn list_assets(&self) -> impl Iterator<Item = &Asset> {
+        self.index.assets.values()
+    }
+
+    fn index_path(dir: &Path) -> PathBuf {
+        dir.join("index.json")
+    }
+
+    fn content_path(&self, hash: &str) -> PathBuf {
+        self.dir.join(hash)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_store_and_list_assets() {
+        let dir = tempfile::tempdir().unwrap();
+
+        let mut assets = AssetStore::open(dir.path().to_path_buf(), None).unwrap();
+        assets
+            .store_asset("https://example.com/cover.png", "image/png", b"png")
+            .unwrap();
+        assets
+            .store_asset("https://example.com/image.jpg", "image/jpeg", b"jpg")
+            .unwrap();
+        assets.save().unwrap();
+
+        let assets = AssetStore::open(dir.path().to_path_buf(), None).unwrap();
+        let (asset, content) = assets
+            .get_asset("https://example.com/cover.png")
+            .unwrap()
+            .unwrap();
+        assert_eq!(asset.content_type, "image/png");
+        assert_eq!(content, b"png");
+        assert_eq!(assets.list_assets().count(), 2);
+        assert!(assets
+            .get_asset("https://example.com/missing.png")
+            .unwrap()
+            .is_none());
+    }
+}
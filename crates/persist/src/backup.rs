@@ -0,0 +1,361 @@
+use std::{
+    collections::HashSet,
+    fs::{self, File},
+    io::{self, BufReader, BufWriter, Read},
+    path::{Component, Path, PathBuf},
+    str::FromStr,
+};
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    create_parent_all,
+    error::{PersistError, PersistResult},
+    global::Global,
+    migration::{self, SCHEMA_VERSION},
+    Persist,
+};
+
+/// The version of the backup archive written by this release
+pub const BACKUP_VERSION: u32 = 1;
+
+/// The name of the manifest, the first entry of every archive
+const MANIFEST: &str = "backup.json";
+
+/// The directory of the library files inside the archive
+const LIBRARY: &str = "library";
+
+/// Describes the library packed in a backup
+#[derive(Serialize, Deserialize, Debug)]
+pub struct BackupManifest {
+    pub version: u32,
+    /// The schema version of the library when it was packed
+    pub schema: u32,
+    /// The directory of the library on the machine it was packed on
+    pub base_dir: PathBuf,
+    pub novels: usize,
+    pub created_at: DateTime<Utc>,
+}
+
+/// What to do with a file of the backup that already exists in the library
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ConflictStrategy {
+    /// Keep the file of the library
+    #[default]
+    Skip,
+    /// Replace the file of the library with the one from the backup
+    Overwrite,
+    /// Restore nothing if any file exists
+    Fail,
+}
+
+impl FromStr for ConflictStrategy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "skip" => Ok(ConflictStrategy::Skip),
+            "overwrite" => Ok(ConflictStrategy::Overwrite),
+            "fail" => Ok(ConflictStrategy::Fail),
+            _ => Err(format!("unsupported conflict strategy '{s}'")),
+        }
+    }
+}
+
+/// The files restored from a backup
+#[derive(Debug, Default)]
+pub struct RestoreReport {
+    pub restored: usize,
+    /// Files of the backup that were kept as they are in the library
+    pub skipped: Vec<PathBuf>,
+    /// The novels added to the library
+    pub novels: usize,
+}
+
+/// Pack the library into a tar.zst archive at the path
+///
+/// The chapter cache and the stored credentials are left out, as the cache can
+/// be downloaded again and credentials should not leave the machine.
+pub fn export_backup(persist: &Persist, path: &Path) -> PersistResult<BackupManifest> {
+    let options = &persist.options;
+    let manifest = BackupManifest {
+        version: BACKUP_VERSION,
+        schema: migration::read_version(persist)?.unwrap_or(SCHEMA_VERSION),
+        base_dir: options.base_dir.clone(),
+        novels: persist.read_global()?.novels().count(),
+        created_at: Utc::now(),
+    };
+
+    create_parent_all(path)?;
+    let file = BufWriter::new(File::create(path)?);
+    let encoder = zstd::Encoder::new(file, 0)?;
+    let mut builder = tar::Builder::new(encoder);
+
+    let data = serde_json::to_vec_pretty(&manifest)?;
+    let mut header = tar::Header::new_gnu();
+    header.set_size(data.len() as u64);
+    header.set_mode(0o644);
+    header.set_mtime(manifest.created_at.timestamp().max(0) as u64);
+    header.set_cksum();
+    builder.append_data(&mut header, MANIFEST, data.as_slice())?;
+
+    let excluded = [
+        options.cache_dir.as_path(),
+        options.credentials_path.as_path(),
+        options.trash_dir.as_path(),
+        options.lock_path.as_path(),
+        options.backups_dir.as_path(),
+        path,
+    ];
+
+    for file in library_files(&options.base_dir, &excluded)? {
+        let relative = file.strip_prefix(&options.base_dir).unwrap_or(&file);
+        builder.append_path_with_name(&file, Path::new(LIBRARY).join(relative))?;
+    }
+
+    builder.into_inner()?.finish()?;
+
+    Ok(manifest)
+}
+
+/// Restore the library from an archive written by [`export_backup`]
+///
+/// The novels of the backup are added to the ones already in the library.
+pub fn import_backup(
+    persist: &Persist,
+    path: &Path,
+    strategy: ConflictStrategy,
+) -> PersistResult<RestoreReport> {
+    let options = &persist.options;
+
+    if strategy == ConflictStrategy::Fail {
+        let mut archive = open_archive(path)?;
+        let mut entries = archive.entries()?;
+        read_manifest(&mut entries)?;
+
+        for entry in entries {
+            let entry = entry?;
+            let Some(relative) = library_path(&entry.path()?) else {
+                continue;
+            };
+
+            let target = options.base_dir.join(&relative);
+            if target.exists() && !is_merged(persist, &target) {
+                return Err(PersistError::BackupConflict(target));
+            }
+        }
+    }
+
+    let mut archive = open_archive(path)?;
+    let mut entries = archive.entries()?;
+    let manifest = read_manifest(&mut entries)?;
+
+    let mut report = RestoreReport::default();
+    let mut backup_global = Global::default();
+    let mut restored_dirs = HashSet::new();
+
+    for entry in entries {
+        let mut entry = entry?;
+        let Some(relative) = library_path(&entry.path()?) else {
+            continue;
+        };
+
+        let target = options.base_dir.join(&relative);
+        if target == options.global_path {
+            backup_global = serde_json::from_reader(&mut entry)?;
+            continue;
+        }
+
+        let keep = is_merged(persist, &target) || strategy == ConflictStrategy::Skip;
+        if target.exists() && keep {
+            report.skipped.push(target);
+            continue;
+        }
+
+        create_parent_all(&target)?;
+        entry.unpack(&target)?;
+        report.restored += 1;
+
+        if let Some(dir) = target.parent() {
+            restored_dirs.insert(dir.to_path_buf());
+        }
+    }
+
+    let mut global = persist.read_global()?;
+    for (url, dir) in backup_global.novels() {
+        let dir = rebase(dir, &manifest.base_dir, &options.base_dir);
+        let exists = global.novel_path_from_url(url).is_some();
+        if exists && strategy != ConflictStrategy::Overwrite {
+            continue;
+        }
+
+        if manifest.base_dir != options.base_dir && restored_dirs.contains(&dir) {
+            rebase_cover(persist, &dir, &manifest.base_dir)?;
+        }
+
+        global.insert_novel(url.clone(), dir);
+        if !exists {
+            report.novels += 1;
+        }
+    }
+    persist.save_global(&global)?;
+
+    Ok(report)
+}
+
+fn open_archive(
+    path: &Path,
+) -> PersistResult<tar::Archive<zstd::Decoder<'static, BufReader<File>>>> {
+    let decoder = zstd::Decoder::new(File::open(path)?)?;
+    Ok(tar::Archive::new(decoder))
+}
+
+fn read_manifest<R: Read>(entries: &mut tar::Entries<R>) -> PersistResult<BackupManifest> {
+    let mut entry = match entries.next() {
+        Some(entry) => entry?,
+        None => {
+            return Err(PersistError::InvalidBackup(String::from(
+                "the archive is empty",
+            )))
+        }
+    };
+
+    if entry.path()?.as_ref() != Path::new(MANIFEST) {
+        return Err(PersistError::InvalidBackup(format!(
+            "the archive does not start with '{MANIFEST}'"
+        )));
+    }
+
+    let manifest: BackupManifest = serde_json::from_reader(&mut entry)?;
+    if manifest.version > BACKUP_VERSION {
+        return Err(PersistError::InvalidBackup(format!(
+            "the backup version {} is newer than the supported version {BACKUP_VERSION}",
+            manifest.version
+        )));
+    }
+
+    if manifest.schema > SCHEMA_VERSION {
+        return Err(PersistError::UnsupportedSchema {
+            found: manifest.schema,
+            supported: SCHEMA_VERSION,
+        });
+    }
+
+    Ok(manifest)
+}
+
+/// Files that are merged with the library instead of being replaced
+fn is_merged(persist: &Persist, path: &Path) -> bool {
+    path == persist.options.global_path || path == persist.options.schema_path
+}
+
+/// The path of the entry relative to the library, ignoring entries that
+/// would be written outside of it
+fn library_path(path: &Path) -> Option<PathBuf> {
+    let relative = path.strip_prefix(LIBRARY).ok()?;
+    let safe = relative
+        .components()
+        .all(|component| matches!(component, Component::Normal(_)));
+
+    (safe && relative.components().next().is_some()).then(|| relative.to_path_buf())
+}
+
+pub(crate) fn library_files(dir: &Path, excluded: &[&Path]) -> io::Result<Vec<PathBuf>> {
+    let mut files = vec![];
+    if !dir.exists() {
+        return Ok(files);
+    }
+
+    let mut entries = fs::read_dir(dir)?
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<io::Result<Vec<_>>>()?;
+    entries.sort();
+
+    for path in entries {
+        if excluded.contains(&path.as_path()) {
+            continue;
+        }
+
+        if path.is_dir() {
+            files.extend(library_files(&path, excluded)?);
+        } else {
+            files.push(path);
+        }
+    }
+
+    Ok(files)
+}
+
+pub(crate) fn rebase(path: &Path, from: &Path, to: &Path) -> PathBuf {
+    match path.strip_prefix(from) {
+        Ok(relative) => to.join(relative),
+        Err(_) => path.to_path_buf(),
+    }
+}
+
+/// Point the cover of a restored novel to the library it was restored into
+pub(crate) fn rebase_cover(persist: &Persist, dir: &Path, from: &Path) -> PersistResult<()> {
+    let novel = persist.persist_novel(dir.to_path_buf());
+    let Some(mut data) = novel.read_data()? else {
+        return Ok(());
+    };
+
+    for cover in data.cover.iter_mut().chain(data.overrides.cover.as_mut()) {
+        cover.path = rebase(&cover.path, from, &persist.options.base_dir);
+    }
+    for cover in data.cover_history.iter_mut() {
+        cover.path = rebase(&cover.path, from, &persist.options.base_dir);
+    }
+
+    novel.write_data(&data)
+}
+
+#[cfg(test)]
+mod tests {
+    use quelle_core::prelude::Novel;
+
+    use super::*;
+    use crate::{PersistOptions, SavedNovel};
+
+    #[test]
+    fn should_restore_backup_into_another_library() {
+        let root = tempfile::tempdir().unwrap();
+        let source = Persist::new(PersistOptions::with_base_dir(root.path().join("source")));
+
+        let dir = source.options.novel.dir.join("example").join("novel");
+        let novel = source.persist_novel(dir.clone());
+        novel
+            .write_data(&SavedNovel::new(Novel::default()))
+            .unwrap();
+        fs::create_dir_all(novel.chapters_dir()).unwrap();
+        fs::write(novel.chapters_dir().join("1.html"), "<p>1</p>").unwrap();
+
+        let mut global = Global::default();
+        global.insert_novel(String::from("https://example.com/novel"), dir);
+        source.save_global(&global).unwrap();
+        source.initialize().unwrap();
+
+        let archive = root.path().join("backup.tar.zst");
+        let manifest = export_backup(&source, &archive).unwrap();
+        assert_eq!(manifest.novels, 1);
+
+        let target = Persist::new(PersistOptions::with_base_dir(root.path().join("target")));
+        let report = import_backup(&target, &archive, ConflictStrategy::Fail).unwrap();
+        assert_eq!(report.novels, 1);
+
+        let global = target.read_global().unwrap();
+        let dir = global
+            .novel_path_from_url("https://example.com/novel")
+            .unwrap();
+        assert!(dir.starts_with(&target.options.base_dir));
+        assert!(dir.join("chapters").join("1.html").exists());
+
+        assert!(matches!(
+            import_backup(&target, &archive, ConflictStrategy::Fail),
+            Err(PersistError::BackupConflict(_))
+        ));
+        let report = import_backup(&target, &archive, ConflictStrategy::Skip).unwrap();
+        assert_eq!(report.restored, 0);
+    }
+}
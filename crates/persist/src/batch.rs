@@ -0,0 +1,315 @@
+use std::{
+    fs::{self, File, OpenOptions},
+    io::{BufReader, BufWriter},
+    path::{Path, PathBuf},
+};
+
+use quelle_core::prelude::Chapter;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    compression::{self, Compression},
+    create_parent_all,
+    error::PersistResult,
+    hooks::StorageEvent,
+    PersistNovel, SavedNovel,
+};
+
+/// The directory inside the novel where a batch stages its files
+const BATCH_DIR: &str = ".batch";
+
+/// Written while a batch is applied, so that an interrupted batch can be undone
+const JOURNAL: &str = "journal.json";
+
+/// The files replaced while applying a batch
+#[derive(Serialize, Deserialize, Debug, Default)]
+struct Journal {
+    moves: Vec<Move>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+struct Move {
+    target: PathBuf,
+    /// Where the file previously at the target was kept
+    backup: Option<PathBuf>,
+}
+
+struct Staged {
+    url: String,
+    path: PathBuf,
+    target: PathBuf,
+}
+
+/// Chapter writes applied together with the novel data, or not at all
+///
+/// Chapters are written to a staging directory and only moved into the novel
+/// when the batch is committed. The replaced files are kept until the novel
+/// data is written, and an interrupted commit is undone the next time the
+/// novel data is read. A batch dropped without being committed leaves the
+/// novel unchanged.
+pub struct Batch<'n, 'a> {
+    novel: &'n PersistNovel<'a>,
+    staged: Vec<Staged>,
+    removed: Vec<PathBuf>,
+}
+
+impl<'n, 'a> Batch<'n, 'a> {
+    pub(crate) fn new(novel: &'n PersistNovel<'a>) -> PersistResult<Self> {
+        recover(novel.dir())?;
+        clear(novel.dir())?;
+
+        Ok(Self {
+            novel,
+            staged: vec![],
+            removed: vec![],
+        })
+    }
+
+    /// Stage the content of the chapter, returning the path it is saved to on commit
+    pub fn save_chapter(
+        &mut self,
+        chapter: &Chapter,
+        content: &str,
+        compression: Compression,
+    ) -> PersistResult<PathBuf> {
+        let target = self
+            .novel
+            .chapters_dir()
+            .join(format!("{}.html", chapter.index));
+
+        self.write(&chapter.url, target, content, compression)
+    }
+
+    /// Stage content downloaded from the url to be saved at the target
+    pub(crate) fn write(
+        &mut self,
+        url: &str,
+        target: PathBuf,
+        content: &str,
+        compression: Compression,
+    ) -> PersistResult<PathBuf> {
+        let name = format!("{}.html", self.staged.len());
+        let path = batch_dir(self.novel.dir()).join("staged").join(name);
+        create_parent_all(&path)?;
+
+        let path =
+            compression::write_content(path, content, compression, self.novel.persist().cipher())?;
+        let target = compression.apply_to(target);
+
+        self.staged.push(Staged {
+            url: url.to_string(),
+            path,
+            target: target.clone(),
+        });
+
+        Ok(target)
+    }
+
+    /// Remove the file of the novel on commit
+    pub fn remove_file(&mut self, path: PathBuf) {
+        self.removed.push(path);
+    }
+
+    /// Move the staged chapters into the novel and save the novel data
+    ///
+    /// Every file is restored as it was before when any step fails.
+    pub fn commit(self, data: &SavedNovel) -> PersistResult<()> {
+        let dir = self.novel.dir().to_path_buf();
+        if let Err(error) = self.apply(data) {
+            recover(&dir)?;
+            return Err(error);
+        }
+
+        // The batch is complete once the journal is gone
+        fs::remove_file(batch_dir(&dir).join(JOURNAL))?;
+        clear(&dir)?;
+
+        for staged in &self.staged {
+            self.novel.persist().emit(StorageEvent::ChapterStored {
+                dir: dir.clone(),
+                url: staged.url.clone(),
+                path: staged.target.clone(),
+            });
+        }
+
+        Ok(())
+    }
+
+    fn apply(&self, data: &SavedNovel) -> PersistResult<()> {
+        let batch_dir = batch_dir(self.novel.dir());
+        let data_path = self.novel.data_path();
+        let mut journal = Journal::default();
+
+        let targets = self
+            .staged
+            .iter()
+            .map(|staged| (Some(&staged.path), &staged.target))
+            .chain(self.removed.iter().map(|path| (None, path)))
+            .chain([(None, &data_path)]);
+
+        for (index, (staged, target)) in targets.enumerate() {
+            let backup = target
+                .exists()
+                .then(|| batch_dir.join("backup").join(index.to_string()));
+
+            // The move is recorded before it is made so that it can always be undone
+            journal.moves.push(Move {
+                target: target.clone(),
+                backup: backup.clone(),
+            });
+            write_journal(&batch_dir, &journal)?;
+
+            if let Some(backup) = &backup {
+                create_parent_all(backup)?;
+                fs::rename(target, backup)?;
+            }
+
+            if let Some(staged) = staged {
+                create_parent_all(target)?;
+                fs::rename(staged, target)?;
+            }
+        }
+
+        self.novel.write_data(data)
+    }
+}
+
+impl Drop for Batch<'_, '_> {
+    fn drop(&mut self) {
+        // Staged files of a batch that was not committed are discarded
+        let dir = batch_dir(self.novel.dir());
+        if !dir.join(JOURNAL).exists() {
+            let _ = clear(self.novel.dir());
+        }
+    }
+}
+
+/// Undo a batch that was interrupted while being committed, returning whether there was one
+pub(crate) fn recover(dir: &Path) -> PersistResult<bool> {
+    let batch_dir = batch_dir(dir);
+    let path = batch_dir.join(JOURNAL);
+    if !path.exists() {
+        return Ok(false);
+    }
+
+    let journal: Journal = serde_json::from_reader(BufReader::new(File::open(&path)?))?;
+    for Move { target, backup } in journal.moves.into_iter().rev() {
+        match backup {
+            Some(backup) if backup.exists() => fs::rename(backup, target)?,
+            // The file was replaced before it could be backed up, it is still the original
+            Some(_) => {}
+            None if target.exists() => fs::remove_file(target)?,
+            None => {}
+        }
+    }
+
+    fs::remove_file(path)?;
+    clear(dir)?;
+    Ok(true)
+}
+
+fn batch_dir(dir: &Path) -> PathBuf {
+    dir.join(BATCH_DIR)
+}
+
+fn clear(dir: &Path) -> PersistResult<()> {
+    let batch_dir = batch_dir(dir);
+    if batch_dir.exists() {
+        fs::remove_dir_all(batch_dir)?;
+    }
+    Ok(())
+}
+
+fn write_journal(batch_dir: &Path, journal: &Journal) -> PersistResult<()> {
+    let path = batch_dir.join(JOURNAL);
+    create_parent_all(&path)?;
+
+    let file = OpenOptions::new()
+        .create(true)
+        .truncate(true)
+        .write(true)
+        .open(path)?;
+
+    serde_json::to_writer(BufWriter::new(file), journal)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use quelle_core::prelude::Novel;
+
+    use super::*;
+    use crate::{Persist, PersistOptions};
+
+    fn chapter(index: i32) -> Chapter {
+        Chapter {
+            index,
+            title: format!("Chapter {index}"),
+            url: format!("https://example.com/novel/{index}"),
+            updated_at: None,
+            number: None,
+            part: None,
+            label: None,
+        }
+    }
+
+    #[test]
+    fn should_apply_batch_only_on_commit() {
+        let root = tempfile::tempdir().unwrap();
+        let persist = Persist::new(PersistOptions::with_base_dir(root.path().to_path_buf()));
+        let novel = persist.persist_novel(root.path().join("novel"));
+        let mut data = SavedNovel::new(Novel::default());
+        novel.write_data(&data).unwrap();
+
+        let mut batch = novel.batch().unwrap();
+        let path = batch
+            .save_chapter(&chapter(1), "<p>1</p>", Compression::None)
+            .unwrap();
+        drop(batch);
+        assert!(!path.exists());
+
+        let mut batch = novel.batch().unwrap();
+        let path = batch
+            .save_chapter(&chapter(1), "<p>1</p>", Compression::None)
+            .unwrap();
+        data.downloaded
+            .insert(chapter(1).url, novel.relative_path(path.clone()));
+        batch.commit(&data).unwrap();
+
+        assert_eq!(novel.read_chapter(&path).unwrap(), "<p>1</p>");
+        assert_eq!(novel.read_data().unwrap().unwrap().downloaded.len(), 1);
+        assert!(!novel.dir().join(BATCH_DIR).exists());
+    }
+
+    #[test]
+    fn should_undo_interrupted_commit() {
+        let root = tempfile::tempdir().unwrap();
+        let persist = Persist::new(PersistOptions::with_base_dir(root.path().to_path_buf()));
+        let novel = persist.persist_novel(root.path().join("novel"));
+        novel
+            .write_data(&SavedNovel::new(Novel::default()))
+            .unwrap();
+
+        let chapter_path = novel.chapters_dir().join("1.html");
+        fs::create_dir_all(novel.chapters_dir()).unwrap();
+        fs::write(&chapter_path, "old").unwrap();
+
+        // A commit that stopped after replacing the chapter
+        let batch_dir = batch_dir(novel.dir());
+        let backup = batch_dir.join("backup").join("0");
+        create_parent_all(&backup).unwrap();
+        fs::rename(&chapter_path, &backup).unwrap();
+        fs::write(&chapter_path, "new").unwrap();
+        let journal = Journal {
+            moves: vec![Move {
+                target: chapter_path.clone(),
+                backup: Some(backup),
+            }],
+        };
+        write_journal(&batch_dir, &journal).unwrap();
+
+        assert!(novel.read_data().unwrap().is_some());
+        assert_eq!(fs::read_to_string(&chapter_path).unwrap(), "old");
+        assert!(!batch_dir.exists());
+    }
+}
@@ -0,0 +1,189 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fs::{self, File, OpenOptions},
+    io::{BufReader, BufWriter},
+    path::{Path, PathBuf},
+};
+
+use quelle_common::canonical_url;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::{create_parent_all, error::PersistResult};
+
+/// Chapter content shared by every novel, keyed by the canonical chapter url
+///
+/// Content is stored once under its hash, so duplicate novels and mirrors
+/// referencing the same chapters are served from disk instead of being
+/// downloaded again.
+#[derive(Debug)]
+pub struct ChapterCache {
+    dir: PathBuf,
+    index: CacheIndex,
+    changed: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug, Default)]
+struct CacheIndex {
+    /// The content hash of each canonical chapter url
+    chapters: HashMap<String, String>,
+}
+
+impl ChapterCache {
+    pub fn open(dir: PathBuf) -> PersistResult<Self> {
+        let path = Self::index_path(&dir);
+        let index = if path.exists() {
+            let file = File::open(path)?;
+            let reader = BufReader::new(file);
+            serde_json::from_reader(reader)?
+        } else {
+            Default::default()
+        };
+
+        Ok(Self {
+            dir,
+            index,
+            changed: false,
+        })
+    }
+
+    pub fn save(&mut self) -> PersistResult<()> {
+        if !self.changed {
+            return Ok(());
+        }
+
+        let path = Self::index_path(&self.dir);
+        create_parent_all(&path)?;
+
+        let file = OpenOptions::new()
+            .create(true)
+            .truncate(true)
+            .write(true)
+            .open(path)?;
+
+        let writer = BufWriter::new(file);
+        serde_json::to_writer(writer, &self.index)?;
+
+        self.changed = false;
+        Ok(())
+    }
+
+    /// The cached content of the chapter, if the url or an equivalent one was downloaded
+    pub fn get(&self, url: &str) -> PersistResult<Option<String>> {
+        let Some(hash) = self.index.chapters.get(&canonical_url(url)) else {
+            return Ok(None);
+        };
+
+        let path = self.content_path(hash);
+        if !path.exists() {
+            return Ok(None);
+        }
+
+        Ok(Some(fs::read_to_string(path)?))
+    }
+
+    pub fn insert(&mut self, url: &str, content: &str) -> PersistResult<()> {
+        let hash = format!("{:x}", Sha256::digest(content.as_bytes()));
+
+        let path = self.content_path(&hash);
+        if !path.exists() {
+            create_parent_all(&path)?;
+            fs::write(path, content)?;
+        }
+
+        self.index.chapters.insert(canonical_url(url), hash);
+        self.changed = true;
+
+        Ok(())
+    }
+
+    /// Remove the content no url refers to anymore and the urls whose content
+    /// is missing, returning the number of files removed
+    pub fn prune(&mut self) -> PersistResult<usize> {
+        let before = self.index.chapters.len();
+        let dir = &self.dir;
+        self.index
+            .chapters
+            .retain(|_, hash| dir.join(&hash[..2]).join(format!("{hash}.html")).exists());
+        self.changed |= self.index.chapters.len() != before;
+
+        let referenced = self.index.chapters.values().collect::<HashSet<_>>();
+        let mut removed = 0;
+        if self.dir.exists() {
+            for entry in fs::read_dir(&self.dir)? {
+                let shard = entry?.path();
+                if !shard.is_dir() {
+                    continue;
+                }
+
+                for entry in fs::read_dir(&shard)? {
+                    let path = entry?.path();
+                    let hash = path.file_stem().and_then(|stem| stem.to_str());
+                    if hash.is_some_and(|hash| !referenced.contains(&hash.to_string())) {
+                        fs::remove_file(path)?;
+                        removed += 1;
+                    }
+                }
+            }
+        }
+
+        self.save()?;
+        Ok(removed)
+    }
+
+    /// The cached files whose content no longer matches the hash they are named after
+    pub(crate) fn corrupted(&self) -> PersistResult<Vec<PathBuf>> {
+        let mut corrupted = vec![];
+        for hash in self.index.chapters.values().collect::<HashSet<_>>() {
+            let path = self.content_path(hash);
+            if !path.exists() {
+                continue;
+            }
+
+            let content = fs::read(&path)?;
+            if format!("{:x}", Sha256::digest(&content)) != *hash {
+                corrupted.push(path);
+            }
+        }
+
+        corrupted.sort();
+        Ok(corrupted)
+    }
+
+    fn index_path(dir: &Path) -> PathBuf {
+        dir.join("index.json")
+    }
+
+    fn content_path(&self, hash: &str) -> PathBuf {
+        self.dir.join(&hash[..2]).join(format!("{hash}.html"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_share_content_between_equivalent_urls() {
+        let dir = tempfile::tempdir().unwrap();
+
+        let mut cache = ChapterCache::open(dir.path().to_path_buf()).unwrap();
+        cache
+            .insert(
+                "http://www.example.com/novel/1/chapter-1/",
+                "<p>content</p>",
+            )
+            .unwrap();
+        cache.save().unwrap();
+
+        let cache = ChapterCache::open(dir.path().to_path_buf()).unwrap();
+        assert_eq!(
+            cache.get("https://example.com/novel/1/chapter-1").unwrap(),
+            Some(String::from("<p>content</p>"))
+        );
+        assert_eq!(
+            cache.get("https://example.com/novel/1/chapter-2").unwrap(),
+            None
+        );
+    }
+}
@@ -0,0 +1,149 @@
+use serde::{Deserialize, Serialize};
+
+use crate::SavedNovel;
+
+/// Why a chapter of the novel is not downloaded, kept until it is
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(tag = "state", rename_all = "snake_case")]
+pub enum ChapterState {
+    /// A download started that has not reached the chapter yet
+    Queued,
+    /// The chapter could not be downloaded
+    Failed { error: String, attempts: u32 },
+    /// The source refused the chapter, such as a locked or paywalled chapter
+    Skipped { reason: String, attempts: u32 },
+}
+
+impl ChapterState {
+    /// The number of downloads of the chapter that did not succeed
+    pub fn attempts(&self) -> u32 {
+        match self {
+            ChapterState::Queued => 0,
+            ChapterState::Failed { attempts, .. } | ChapterState::Skipped { attempts, .. } => {
+                *attempts
+            }
+        }
+    }
+}
+
+/// Whether a chapter of the novel is downloaded and why not, see [`SavedNovel::chapter_status`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChapterStatus<'a> {
+    Downloaded,
+    /// Not downloaded yet and never attempted
+    Pending,
+    Queued,
+    Failed {
+        error: &'a str,
+        attempts: u32,
+    },
+    Skipped {
+        reason: &'a str,
+        attempts: u32,
+    },
+}
+
+impl SavedNovel {
+    pub fn chapter_status(&self, url: &str) -> ChapterStatus<'_> {
+        if self.downloaded.contains_key(url) {
+            return ChapterStatus::Downloaded;
+        }
+
+        match self.chapter_states.get(url) {
+            None => ChapterStatus::Pending,
+            Some(ChapterState::Queued) => ChapterStatus::Queued,
+            Some(ChapterState::Failed { error, attempts }) => ChapterStatus::Failed {
+                error,
+                attempts: *attempts,
+            },
+            Some(ChapterState::Skipped { reason, attempts }) => ChapterStatus::Skipped {
+                reason,
+                attempts: *attempts,
+            },
+        }
+    }
+
+    /// Mark the chapters as queued, keeping the failures of the previous downloads
+    pub(crate) fn queue_chapters(&mut self, urls: Vec<String>) {
+        for url in urls {
+            self.chapter_states
+                .entry(url)
+                .or_insert(ChapterState::Queued);
+        }
+    }
+
+    /// Record a download of the chapter that did not succeed
+    pub(crate) fn fail_chapter(&mut self, url: String, error: String, skipped: bool) {
+        let attempts = self
+            .chapter_states
+            .get(&url)
+            .map_or(0, ChapterState::attempts)
+            + 1;
+
+        let state = if skipped {
+            ChapterState::Skipped {
+                reason: error,
+                attempts,
+            }
+        } else {
+            ChapterState::Failed { error, attempts }
+        };
+        self.chapter_states.insert(url, state);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Utc;
+    use quelle_core::prelude::Novel;
+
+    use super::*;
+    use crate::{Event, EventKind};
+
+    #[test]
+    fn should_track_attempts_until_downloaded() {
+        let mut data = SavedNovel::new(Novel::default());
+        let url = String::from("https://example.com/1");
+        let event = |kind| Event {
+            kind,
+            added_at: Utc::now(),
+        };
+
+        data.commit_events(vec![
+            event(EventKind::Queued {
+                urls: vec![url.clone()],
+            }),
+            event(EventKind::Failed {
+                url: url.clone(),
+                error: String::from("timed out"),
+                skipped: false,
+            }),
+            event(EventKind::Queued {
+                urls: vec![url.clone()],
+            }),
+            event(EventKind::Failed {
+                url: url.clone(),
+                error: String::from("locked"),
+                skipped: true,
+            }),
+        ]);
+        assert_eq!(
+            data.chapter_status(&url),
+            ChapterStatus::Skipped {
+                reason: "locked",
+                attempts: 2
+            }
+        );
+
+        data.commit_events(vec![event(EventKind::Downloaded {
+            url: url.clone(),
+            path: "chapters/1.html".into(),
+            lang: None,
+            words: None,
+            provenance: None,
+            hash: None,
+        })]);
+        assert_eq!(data.chapter_status(&url), ChapterStatus::Downloaded);
+        assert!(data.chapter_states.is_empty());
+    }
+}
@@ -0,0 +1,222 @@
+use std::{
+    collections::HashSet,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use crate::{error::PersistResult, Persist, PersistNovel, SavedNovel};
+
+/// The inconsistencies between the novel data and the files of the library,
+/// see [`Persist::cleanup`]
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct CleanupReport {
+    /// Chapter files that no downloaded chapter refers to
+    pub orphaned_chapters: Vec<PathBuf>,
+    /// Downloaded chapters whose file is missing, by novel directory and url
+    pub missing_chapters: Vec<(PathBuf, String)>,
+    /// Cover files no cover refers to, and covers whose file is missing
+    pub dangling_covers: Vec<PathBuf>,
+    /// Whether the problems were repaired
+    pub fixed: bool,
+}
+
+impl CleanupReport {
+    pub fn is_empty(&self) -> bool {
+        self.orphaned_chapters.is_empty()
+            && self.missing_chapters.is_empty()
+            && self.dangling_covers.is_empty()
+    }
+}
+
+/// Find the files and chapters out of sync with the novel data, see [`Persist::cleanup`]
+pub(crate) fn cleanup(persist: &Persist, fix: bool) -> PersistResult<CleanupReport> {
+    let mut report = CleanupReport {
+        fixed: fix,
+        ..Default::default()
+    };
+
+    for (_, dir) in persist.read_global()?.novels() {
+        let novel = persist.persist_novel(dir.clone());
+        let Some(mut data) = novel.read_data()? else {
+            continue;
+        };
+
+        let mut changed = check_chapters(&novel, &mut data, fix, &mut report)?;
+        changed |= check_covers(&novel, &mut data, fix, &mut report)?;
+
+        if changed {
+            novel.write_data(&data)?;
+        }
+    }
+
+    Ok(report)
+}
+
+/// Returns whether the novel data was changed
+fn check_chapters(
+    novel: &PersistNovel,
+    data: &mut SavedNovel,
+    fix: bool,
+    report: &mut CleanupReport,
+) -> PersistResult<bool> {
+    let dir = novel.dir();
+    let missing = data
+        .downloaded
+        .iter()
+        .filter(|(_, path)| !dir.join(path).exists())
+        .map(|(url, _)| url.clone())
+        .collect::<Vec<_>>();
+
+    let referenced = data
+        .downloaded
+        .values()
+        .map(|path| dir.join(path))
+        .collect::<HashSet<_>>();
+    for path in files_in(&novel.chapters_dir())? {
+        if !referenced.contains(&path) {
+            if fix {
+                fs::remove_file(&path)?;
+            }
+            report.orphaned_chapters.push(path);
+        }
+    }
+
+    // Forgetting the chapters lets the next update download them again
+    if fix {
+        for url in &missing {
+            data.downloaded.remove(url);
+            data.word_counts.remove(url);
+            data.chapter_langs.remove(url);
+            data.chapter_provenance.remove(url);
+            data.chapter_hashes.remove(url);
+        }
+    }
+
+    let changed = fix && !missing.is_empty();
+    report
+        .missing_chapters
+        .extend(missing.into_iter().map(|url| (dir.to_path_buf(), url)));
+
+    Ok(changed)
+}
+
+/// Returns whether the novel data was changed
+fn check_covers(
+    novel: &PersistNovel,
+    data: &mut SavedNovel,
+    fix: bool,
+    report: &mut CleanupReport,
+) -> PersistResult<bool> {
+    let mut changed = false;
+
+    if let Some(cover) = data.cover.as_ref().filter(|cover| !cover.path.exists()) {
+        report.dangling_covers.push(cover.path.clone());
+        if fix {
+            data.cover = None;
+            changed = true;
+        }
+    }
+
+    let before = data.cover_history.len();
+    for cover in data
+        .cover_history
+        .iter()
+        .filter(|cover| !cover.path.exists())
+    {
+        report.dangling_covers.push(cover.path.clone());
+    }
+    if fix {
+        data.cover_history.retain(|cover| cover.path.exists());
+        changed |= data.cover_history.len() != before;
+    }
+
+    // Covers are compared by name, as their paths may be relative to another directory
+    let referenced = data
+        .cover
+        .iter()
+        .chain(&data.cover_history)
+        .filter_map(|cover| cover.path.file_name().map(ToOwned::to_owned))
+        .collect::<HashSet<_>>();
+
+    let covers = files_in(novel.dir())?
+        .into_iter()
+        .filter(|path| path.file_stem().is_some_and(|stem| stem == "cover"));
+    for path in covers.chain(files_in(&novel.cover_history_dir())?) {
+        let name = path.file_name().map(ToOwned::to_owned);
+        if name.is_some_and(|name| !referenced.contains(&name)) {
+            if fix {
+                fs::remove_file(&path)?;
+            }
+            report.dangling_covers.push(path);
+        }
+    }
+
+    Ok(changed)
+}
+
+/// The files directly inside the directory, if it exists
+fn files_in(dir: &Path) -> PersistResult<Vec<PathBuf>> {
+    if !dir.is_dir() {
+        return Ok(vec![]);
+    }
+
+    let mut files = vec![];
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_file() {
+            files.push(path);
+        }
+    }
+
+    files.sort();
+    Ok(files)
+}
+
+#[cfg(test)]
+mod tests {
+    use quelle_core::prelude::Novel;
+
+    use super::*;
+    use crate::PersistOptions;
+
+    #[test]
+    fn should_find_and_fix_orphans() {
+        let dir = tempfile::tempdir().unwrap();
+        let persist = Persist::new(PersistOptions::with_base_dir(dir.path().to_path_buf()));
+
+        let novel_dir = persist.options.novel.dir.join("source").join("novel");
+        let novel = persist.persist_novel(novel_dir.clone());
+        fs::create_dir_all(novel.chapters_dir()).unwrap();
+        fs::write(novel.chapters_dir().join("0.html"), "<p>kept</p>").unwrap();
+        fs::write(novel.chapters_dir().join("1.html"), "<p>orphan</p>").unwrap();
+        fs::write(novel.cover_path(Some("png")), "cover").unwrap();
+
+        let mut data = SavedNovel::new(Novel::default());
+        let kept = (String::from("https://example.com/0"), "chapters/0.html");
+        let missing = (String::from("https://example.com/2"), "chapters/2.html");
+        for (url, path) in [kept, missing.clone()] {
+            data.downloaded.insert(url, PathBuf::from(path));
+        }
+        novel.write_data(&data).unwrap();
+
+        let mut global = persist.read_global().unwrap();
+        global.insert_novel(String::from("https://example.com/novel"), novel_dir.clone());
+        persist.save_global(&global).unwrap();
+
+        let report = cleanup(&persist, false).unwrap();
+        assert_eq!(
+            report.orphaned_chapters,
+            vec![novel.chapters_dir().join("1.html")]
+        );
+        assert_eq!(
+            report.missing_chapters,
+            vec![(novel_dir, missing.0.clone())]
+        );
+        assert_eq!(report.dangling_covers, vec![novel.cover_path(Some("png"))]);
+
+        cleanup(&persist, true).unwrap();
+        assert!(cleanup(&persist, false).unwrap().is_empty());
+        let data = novel.read_data().unwrap().unwrap();
+        assert!(!data.downloaded.contains_key(&missing.0));
+    }
+}
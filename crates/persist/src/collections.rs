@@ -0,0 +1,133 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fs::{File, OpenOptions},
+    io::{BufReader, BufWriter},
+    path::Path,
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{create_parent_all, error::PersistResult};
+
+/// Shelves the user groups novels into, such as "Reading" or "Finished"
+///
+/// Novels are referenced by their url and may be part of several collections.
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct Collections {
+    collections: BTreeMap<String, BTreeSet<String>>,
+}
+
+impl Collections {
+    pub fn open(path: &Path) -> PersistResult<Self> {
+        let data = if path.exists() {
+            let file = File::open(path)?;
+            let reader = BufReader::new(file);
+            serde_json::from_reader(reader)?
+        } else {
+            Default::default()
+        };
+
+        Ok(data)
+    }
+
+    pub fn save(&self, path: &Path) -> PersistResult<()> {
+        create_parent_all(path)?;
+
+        let file = OpenOptions::new()
+            .create(true)
+            .truncate(true)
+            .write(true)
+            .open(path)?;
+
+        let writer = BufWriter::new(file);
+        serde_json::to_writer_pretty(writer, self)?;
+
+        Ok(())
+    }
+
+    /// Create an empty collection, returning false if it already exists
+    pub fn create_collection(&mut self, name: &str) -> bool {
+        if self.collections.contains_key(name) {
+            return false;
+        }
+
+        self.collections.insert(name.to_string(), BTreeSet::new());
+        true
+    }
+
+    /// Remove the collection, leaving its novels in the library
+    pub fn delete_collection(&mut self, name: &str) -> bool {
+        self.collections.remove(name).is_some()
+    }
+
+    /// Add the novel to the collection, creating the collection if needed
+    pub fn add_to_collection(&mut self, name: &str, url: &str) -> bool {
+        self.collections
+            .entry(name.to_string())
+            .or_default()
+            .insert(url.to_string())
+    }
+
+    pub fn remove_from_collection(&mut self, name: &str, url: &str) -> bool {
+        self.collections
+            .get_mut(name)
+            .is_some_and(|urls| urls.remove(url))
+    }
+
+    /// The name and number of novels of every collection, ordered by name
+    pub fn list_collections(&self) -> impl Iterator<Item = (&String, usize)> {
+        self.collections
+            .iter()
+            .map(|(name, urls)| (name, urls.len()))
+    }
+
+    /// The urls of the novels in the collection
+    pub fn novels(&self, name: &str) -> Option<&BTreeSet<String>> {
+        self.collections.get(name)
+    }
+
+    /// The names of the collections the novel is part of
+    pub fn collections_of<'a>(&'a self, url: &'a str) -> impl Iterator<Item = &'a String> {
+        self.collections
+            .iter()
+            .filter(move |(_, urls)| urls.contains(url))
+            .map(|(name, _)| name)
+    }
+
+    /// Replace the url of a novel that moved in every collection
+    pub fn rename_novel(&mut self, from: &str, to: &str) {
+        for urls in self.collections.values_mut() {
+            if urls.remove(from) {
+                urls.insert(to.to_string());
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Collections;
+
+    #[test]
+    fn should_group_novels_into_collections() {
+        let mut collections = Collections::default();
+        assert!(collections.create_collection("Reading"));
+        assert!(!collections.create_collection("Reading"));
+
+        assert!(collections.add_to_collection("Reading", "https://example.com/a"));
+        assert!(collections.add_to_collection("Xianxia", "https://example.com/a"));
+        assert!(!collections.add_to_collection("Xianxia", "https://example.com/a"));
+
+        let names = collections
+            .collections_of("https://example.com/a")
+            .collect::<Vec<_>>();
+        assert_eq!(names, ["Reading", "Xianxia"]);
+
+        collections.rename_novel("https://example.com/a", "https://example.com/b");
+        assert!(collections.remove_from_collection("Reading", "https://example.com/b"));
+        assert_eq!(
+            collections.list_collections().collect::<Vec<_>>(),
+            [(&String::from("Reading"), 0), (&String::from("Xianxia"), 1)]
+        );
+    }
+}
@@ -0,0 +1,170 @@
+use std::{
+    fmt::Display,
+    fs,
+    io::{self, Read, Write},
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+use flate2::{read::GzDecoder, write::GzEncoder};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    encryption::{self, Cipher},
+    error::{PersistError, PersistResult},
+};
+
+/// How chapter content is compressed on disk
+///
+/// The format of a file is recognised by its extension, so content written
+/// with different settings can be read back at any time.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum Compression {
+    #[default]
+    None,
+    Gzip,
+    Zstd,
+}
+
+impl Compression {
+    pub const ALL: [Compression; 3] = [Compression::None, Compression::Gzip, Compression::Zstd];
+
+    /// The suffix added to the name of compressed files
+    pub fn suffix(&self) -> Option<&'static str> {
+        match self {
+            Compression::None => None,
+            Compression::Gzip => Some("gz"),
+            Compression::Zstd => Some("zst"),
+        }
+    }
+
+    /// The compression of the file, recognised by its extension
+    pub fn of_path(path: &Path) -> Self {
+        let extension = path.extension().and_then(|value| value.to_str());
+        Self::ALL
+            .into_iter()
+            .find(|compression| compression.suffix().is_some() && compression.suffix() == extension)
+            .unwrap_or_default()
+    }
+
+    /// The path with the suffix of this compression
+    pub fn apply_to(&self, path: PathBuf) -> PathBuf {
+        match self.suffix() {
+            Some(suffix) => {
+                let mut name = path.into_os_string();
+                name.push(".");
+                name.push(suffix);
+                PathBuf::from(name)
+            }
+            None => path,
+        }
+    }
+
+    pub fn compress(&self, data: &[u8]) -> io::Result<Vec<u8>> {
+        match self {
+            Compression::None => Ok(data.to_vec()),
+            Compression::Gzip => {
+                let mut encoder = GzEncoder::new(vec![], flate2::Compression::default());
+                encoder.write_all(data)?;
+                encoder.finish()
+            }
+            Compression::Zstd => zstd::encode_all(data, 0),
+        }
+    }
+
+    pub fn decompress(&self, data: &[u8]) -> io::Result<Vec<u8>> {
+        match self {
+            Compression::None => Ok(data.to_vec()),
+            Compression::Gzip => {
+                let mut decoded = vec![];
+                GzDecoder::new(data).read_to_end(&mut decoded)?;
+                Ok(decoded)
+            }
+            Compression::Zstd => zstd::decode_all(data),
+        }
+    }
+}
+
+impl FromStr for Compression {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "none" => Ok(Compression::None),
+            "gzip" | "gz" => Ok(Compression::Gzip),
+            "zstd" | "zst" => Ok(Compression::Zstd),
+            _ => Err(format!("unsupported compression '{s}'")),
+        }
+    }
+}
+
+impl Display for Compression {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let value = match self {
+            Compression::None => "none",
+            Compression::Gzip => "gzip",
+            Compression::Zstd => "zstd",
+        };
+
+        write!(f, "{value}")
+    }
+}
+
+/// Write the content to the path with the suffix of the compression, returning the written path
+///
+/// The content is encrypted after being compressed when a cipher is given.
+pub fn write_content(
+    path: PathBuf,
+    content: &str,
+    compression: Compression,
+    cipher: Option<&Cipher>,
+) -> PersistResult<PathBuf> {
+    let path = compression.apply_to(path);
+    let mut data = compression.compress(content.as_bytes())?;
+    if let Some(cipher) = cipher {
+        data = cipher.encrypt(&data)?;
+    }
+
+    // Written to a new file, as the previous one may be shared with other novels
+    if path.exists() {
+        fs::remove_file(&path)?;
+    }
+    fs::write(&path, data)?;
+    Ok(path)
+}
+
+/// Read the content of the file, decrypting and decompressing it as needed
+pub fn read_content(path: &Path, cipher: Option<&Cipher>) -> PersistResult<String> {
+    let mut data = fs::read(path)?;
+    if encryption::is_encrypted(&data) {
+        let cipher = cipher.ok_or(PersistError::PassphraseRequired)?;
+        data = cipher.decrypt(&data)?;
+    }
+
+    let data = Compression::of_path(path).decompress(&data)?;
+    String::from_utf8(data).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e).into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_round_trip_every_compression() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir_all(&dir).unwrap();
+
+        for compression in Compression::ALL {
+            let path = write_content(
+                dir.path().join("1.html"),
+                "<p>content</p>",
+                compression,
+                None,
+            )
+            .unwrap();
+            assert_eq!(Compression::of_path(&path), compression);
+            assert_eq!(read_content(&path, None).unwrap(), "<p>content</p>");
+        }
+    }
+}
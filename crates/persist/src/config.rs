@@ -0,0 +1,270 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fs::{File, OpenOptions},
+    io::{BufReader, BufWriter},
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    compression::Compression, create_parent_all, encryption::EncryptionConfig,
+    error::PersistResult, maintenance::MaintenanceConfig, s3::RemoteConfig, sources::Executor,
+    webdav::WebDavConfig,
+};
+
+/// Settings that apply to a single library
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
+pub struct LibraryConfig {
+    /// How newly downloaded chapters are compressed
+    #[serde(default)]
+    pub compression: Compression,
+    /// How the key is derived when the library is encrypted
+    #[serde(default)]
+    pub encryption: Option<EncryptionConfig>,
+    /// Which executor runs the extensions
+    #[serde(default)]
+    pub executors: ExecutorConfig,
+    /// The bucket the library is pushed to and pulled from
+    #[serde(default)]
+    pub remote: Option<RemoteConfig>,
+    /// The WebDAV share the library is pushed to and pulled from, used instead of the bucket
+    #[serde(default)]
+    pub webdav: Option<WebDavConfig>,
+    /// Settings by source id
+    #[serde(default)]
+    pub sources: BTreeMap<String, SourceSettings>,
+    /// The number of days deleted novels are kept in the trash, 30 by default
+    #[serde(default)]
+    pub trash_retention_days: Option<u32>,
+    /// When the housekeeping tasks run and which ones
+    #[serde(default)]
+    pub maintenance: MaintenanceConfig,
+    /// The Typst template, or template directory, PDF bundles are laid out with
+    #[serde(default)]
+    pub pdf_template: Option<PathBuf>,
+    /// Bundle settings by profile name
+    #[serde(default)]
+    pub profiles: BTreeMap<String, BundleProfile>,
+    /// The Calibre library bundles are added to
+    #[serde(default)]
+    pub calibre: CalibreConfig,
+    /// Where bundles write the metadata collected by extensions, by metadata
+    /// name (ex: "translator": "calibre:#translator")
+    #[serde(default)]
+    pub metadata: BTreeMap<String, String>,
+    /// How the chapter content is cleaned up before it is bundled
+    #[serde(default)]
+    pub sanitize: SanitizeConfig,
+}
+
+/// The number of days deleted novels are kept when the library does not set it
+const TRASH_RETENTION_DAYS: u32 = 30;
+
+/// Settings of a single source, shared by every novel of the source
+#[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq, Eq)]
+pub struct SourceSettings {
+    /// Send the age gate of the source so that mature content is shown
+    #[serde(default)]
+    pub mature: bool,
+}
+
+/// Bundle settings applied together by naming the profile, such as the formats
+/// and layout suited to a reader
+///
+/// Settings given when bundling take precedence over the ones of the profile.
+#[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq, Eq)]
+pub struct BundleProfile {
+    /// The formats to bundle into, by extension
+    #[serde(default)]
+    pub formats: Vec<String>,
+    /// The output path template
+    #[serde(default)]
+    pub output: Option<String>,
+    /// The name of a built-in epub theme or the path of a stylesheet
+    #[serde(default)]
+    pub epub_theme: Option<String>,
+    #[serde(default)]
+    pub pdf_template: Option<PathBuf>,
+    /// Set Japanese and Chinese novels vertically in pdf bundles
+    #[serde(default)]
+    pub pdf_vertical: bool,
+    /// The fonts embedded into epub and pdf bundles
+    #[serde(default)]
+    pub fonts: Vec<PathBuf>,
+    /// Embed whole fonts instead of the glyphs the novel uses
+    #[serde(default)]
+    pub no_font_subset: bool,
+    #[serde(default)]
+    pub include_images: bool,
+    #[serde(default)]
+    pub image_max_width: Option<u32>,
+    #[serde(default)]
+    pub split_volumes: bool,
+    #[serde(default)]
+    pub split_chapters: Option<usize>,
+    /// The maximum size of a part in megabytes
+    #[serde(default)]
+    pub split_size: Option<u64>,
+}
+
+/// The Calibre library bundles are added to with `calibredb`, the last used
+/// library when none is set
+///
+/// The password of a content server is not saved with the library.
+#[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq, Eq)]
+pub struct CalibreConfig {
+    /// The path of the library or the url of a content server
+    #[serde(default)]
+    pub library: Option<String>,
+    /// The username of the content server
+    #[serde(default)]
+    pub username: Option<String>,
+}
+
+/// The cleanup steps run on chapter content before it is bundled, every step
+/// runs unless disabled
+#[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq, Eq)]
+pub struct SanitizeConfig {
+    /// The steps that are not run, by name (ex: inline-styles)
+    #[serde(default)]
+    pub disabled: BTreeSet<String>,
+    /// Regular expressions of the watermark sentences removed along with the
+    /// built-in ones (ex: "(?i)support us on patreon[.!]?")
+    #[serde(default)]
+    pub watermarks: Vec<String>,
+}
+
+/// A command that runs an extension
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "snake_case")]
+pub enum Task {
+    /// Downloading a novel that is not in the library
+    Add,
+    /// Downloading new chapters of a novel in the library
+    Update,
+}
+
+impl FromStr for Task {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "add" => Ok(Task::Add),
+            "update" => Ok(Task::Update),
+            _ => Err(format!("unsupported command '{s}'")),
+        }
+    }
+}
+
+impl std::fmt::Display for Task {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let value = match self {
+            Task::Add => "add",
+            Task::Update => "update",
+        };
+        write!(f, "{value}")
+    }
+}
+
+/// The executors chosen for sources and commands
+///
+/// A source setting takes precedence over a command setting, which takes
+/// precedence over the default. Without any setting the cheapest executor
+/// known to work for the source is used.
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
+pub struct ExecutorConfig {
+    #[serde(default)]
+    pub default: Option<Executor>,
+    #[serde(default)]
+    pub commands: BTreeMap<Task, Executor>,
+    /// Executors by source id
+    #[serde(default)]
+    pub sources: BTreeMap<String, Executor>,
+}
+
+impl ExecutorConfig {
+    /// The configured executor of the source for the command, if any
+    pub fn resolve(&self, source: &str, task: Task) -> Option<Executor> {
+        self.sources
+            .get(source)
+            .or_else(|| self.commands.get(&task))
+            .or(self.default.as_ref())
+            .copied()
+    }
+}
+
+impl LibraryConfig {
+    /// How long deleted novels are kept in the trash before they are purged
+    pub fn trash_retention(&self) -> chrono::Duration {
+        let days = self.trash_retention_days.unwrap_or(TRASH_RETENTION_DAYS);
+        chrono::Duration::days(days.into())
+    }
+
+    /// The settings of the source, or the defaults when it has none
+    pub fn source(&self, source: &str) -> SourceSettings {
+        self.sources.get(source).cloned().unwrap_or_default()
+    }
+
+    pub fn open(path: &Path) -> PersistResult<Self> {
+        let data = if path.exists() {
+            let file = File::open(path)?;
+            let reader = BufReader::new(file);
+            serde_json::from_reader(reader)?
+        } else {
+            Default::default()
+        };
+
+        Ok(data)
+    }
+
+    pub fn save(&self, path: &Path) -> PersistResult<()> {
+        create_parent_all(path)?;
+
+        let file = OpenOptions::new()
+            .create(true)
+            .truncate(true)
+            .write(true)
+            .open(path)?;
+
+        let writer = BufWriter::new(file);
+        serde_json::to_writer_pretty(writer, self)?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_prefer_source_over_command_executor() {
+        let mut config = ExecutorConfig::default();
+        assert_eq!(config.resolve("novelpub", Task::Add), None);
+
+        config.default = Some(Executor::InProcess);
+        config.commands.insert(Task::Update, Executor::Isolated);
+        assert_eq!(
+            config.resolve("novelpub", Task::Add),
+            Some(Executor::InProcess)
+        );
+        assert_eq!(
+            config.resolve("novelpub", Task::Update),
+            Some(Executor::Isolated)
+        );
+
+        config
+            .sources
+            .insert(String::from("novelpub"), Executor::InProcess);
+        assert_eq!(
+            config.resolve("novelpub", Task::Update),
+            Some(Executor::InProcess)
+        );
+        assert_eq!(
+            config.resolve("other", Task::Update),
+            Some(Executor::Isolated)
+        );
+    }
+}
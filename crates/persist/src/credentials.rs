@@ -0,0 +1,178 @@
+use std::{
+    collections::BTreeMap,
+    fs::{File, OpenOptions},
+    io::{BufReader, BufWriter},
+    path::Path,
+};
+
+use quelle_core::prelude::AgeGate;
+use serde::{Deserialize, Serialize};
+
+use crate::{create_parent_all, error::PersistResult};
+
+/// The stored accounts of every source, keyed by source id and profile name
+///
+/// A source may have several profiles, such as two accounts with different
+/// unlocked chapters. Novels are bound to a profile with [`crate::SavedNovel::credential`].
+/// Credentials are stored unencrypted, so the file should only be readable by the user.
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct CredentialStore {
+    sources: BTreeMap<String, BTreeMap<String, Credential>>,
+}
+
+/// The values sent along with requests to a source to authenticate
+#[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq, Eq)]
+pub struct Credential {
+    #[serde(default)]
+    pub cookies: BTreeMap<String, String>,
+    #[serde(default)]
+    pub headers: BTreeMap<String, String>,
+}
+
+impl Credential {
+    /// The headers to send, with the cookies joined into a `Cookie` header
+    pub fn header_pairs(&self) -> Vec<(String, String)> {
+        let mut headers = self
+            .headers
+            .iter()
+            .map(|(name, value)| (name.clone(), value.clone()))
+            .collect::<Vec<_>>();
+
+        if !self.cookies.is_empty() {
+            let cookie = self
+                .cookies
+                .iter()
+                .map(|(name, value)| format!("{name}={value}"))
+                .collect::<Vec<_>>()
+                .join("; ");
+
+            headers.push((String::from("Cookie"), cookie));
+        }
+
+        headers
+    }
+
+    /// Add the cookies and headers of the age gate, keeping the values already set
+    pub fn add_age_gate(&mut self, gate: &AgeGate) {
+        for (name, value) in &gate.cookies {
+            self.cookies
+                .entry(name.clone())
+                .or_insert_with(|| value.clone());
+        }
+        for (name, value) in &gate.headers {
+            self.headers
+                .entry(name.clone())
+                .or_insert_with(|| value.clone());
+        }
+    }
+}
+
+impl CredentialStore {
+    pub fn open(path: &Path) -> PersistResult<Self> {
+        let data = if path.exists() {
+            let file = File::open(path)?;
+            let reader = BufReader::new(file);
+            serde_json::from_reader(reader)?
+        } else {
+            Default::default()
+        };
+
+        Ok(data)
+    }
+
+    pub fn save(&self, path: &Path) -> PersistResult<()> {
+        create_parent_all(path)?;
+
+        let mut options = OpenOptions::new();
+        options.create(true).truncate(true).write(true);
+
+        #[cfg(unix)]
+        std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+
+        let writer = BufWriter::new(options.open(path)?);
+        serde_json::to_writer_pretty(writer, self)?;
+
+        Ok(())
+    }
+
+    pub fn get(&self, source: &str, profile: &str) -> Option<&Credential> {
+        self.sources.get(source)?.get(profile)
+    }
+
+    /// The profile names stored for the source
+    pub fn profiles(&self, source: &str) -> impl Iterator<Item = &String> {
+        self.sources
+            .get(source)
+            .into_iter()
+            .flat_map(BTreeMap::keys)
+    }
+
+    /// The ids of the sources with stored profiles
+    pub fn sources(&self) -> impl Iterator<Item = &String> {
+        self.sources.keys()
+    }
+
+    pub fn insert(&mut self, source: &str, profile: &str, credential: Credential) {
+        self.sources
+            .entry(source.to_string())
+            .or_default()
+            .insert(profile.to_string(), credential);
+    }
+
+    pub fn remove(&mut self, source: &str, profile: &str) -> Option<Credential> {
+        let profiles = self.sources.get_mut(source)?;
+        let removed = profiles.remove(profile);
+        if profiles.is_empty() {
+            self.sources.remove(source);
+        }
+
+        removed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_keep_profiles_per_source() {
+        let mut store = CredentialStore::default();
+        let mut credential = Credential::default();
+        credential
+            .cookies
+            .insert(String::from("session"), String::from("abc"));
+        credential
+            .cookies
+            .insert(String::from("remember"), String::from("1"));
+
+        store.insert("royalroad", "main", credential.clone());
+        store.insert("royalroad", "alt", Credential::default());
+
+        assert_eq!(store.get("royalroad", "main"), Some(&credential));
+        assert_eq!(
+            store.profiles("royalroad").collect::<Vec<_>>(),
+            vec!["alt", "main"]
+        );
+        assert_eq!(
+            credential.header_pairs(),
+            vec![(
+                String::from("Cookie"),
+                String::from("remember=1; session=abc")
+            )]
+        );
+
+        credential.add_age_gate(&AgeGate {
+            cookies: vec![
+                (String::from("session"), String::from("other")),
+                (String::from("adult"), String::from("true")),
+            ],
+            headers: vec![],
+        });
+        assert_eq!(credential.cookies["session"], "abc");
+        assert_eq!(credential.cookies["adult"], "true");
+
+        store.remove("royalroad", "main");
+        store.remove("royalroad", "alt");
+        assert_eq!(store.sources().count(), 0);
+    }
+}
@@ -0,0 +1,135 @@
+use std::{
+    collections::HashMap,
+    fs::{self, Metadata},
+    path::PathBuf,
+};
+
+use log::warn;
+
+use crate::{compression::Compression, content_hash, error::PersistResult, Persist};
+
+/// The space saved by storing identical chapters once, see [`Persist::deduplicate`]
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct DedupReport {
+    /// The number of downloaded chapters read
+    pub chapters: usize,
+    /// The chapters whose content is stored once for several novels
+    pub shared: usize,
+    /// The chapters linked to an identical one during this run
+    pub deduplicated: usize,
+    /// The bytes not taken by the shared chapters, including earlier runs
+    pub saved_bytes: u64,
+}
+
+/// Link the chapters with the same content to a single file, see [`Persist::deduplicate`]
+pub(crate) fn deduplicate(persist: &Persist, dry_run: bool) -> PersistResult<DedupReport> {
+    let mut report = DedupReport::default();
+
+    // Files can only be shared with the same compression, as it is part of their name
+    let mut stored: HashMap<(Compression, String), (PathBuf, Metadata)> = HashMap::new();
+    for (_, dir) in persist.read_global()?.novels() {
+        let novel = persist.persist_novel(dir.clone());
+        let Some(data) = novel.read_data()? else {
+            continue;
+        };
+
+        for path in data.downloaded.values() {
+            let path = dir.join(path);
+            if !path.is_file() {
+                continue;
+            }
+            report.chapters += 1;
+
+            // The content is hashed once decrypted, as every file is encrypted differently
+            let content = novel.read_chapter(&path)?;
+            let key = (Compression::of_path(&path), content_hash(&content));
+            let metadata = fs::metadata(&path)?;
+
+            let Some((original, original_metadata)) = stored.get(&key) else {
+                stored.insert(key, (path, metadata));
+                continue;
+            };
+
+            if same_file(original_metadata, &metadata) {
+                report.shared += 1;
+                report.saved_bytes += metadata.len();
+                continue;
+            }
+
+            if !dry_run {
+                // Linked under another name first so that the chapter is never missing
+                let linked = path.with_extension("dedup");
+                if let Err(e) = fs::hard_link(original, &linked) {
+                    warn!("Failed to link '{}': {e}.", path.display());
+                    continue;
+                }
+                fs::rename(&linked, &path)?;
+            }
+
+            report.shared += 1;
+            report.deduplicated += 1;
+            report.saved_bytes += metadata.len();
+        }
+    }
+
+    Ok(report)
+}
+
+#[cfg(unix)]
+fn same_file(a: &Metadata, b: &Metadata) -> bool {
+    use std::os::unix::fs::MetadataExt;
+    a.dev() == b.dev() && a.ino() == b.ino()
+}
+
+#[cfg(not(unix))]
+fn same_file(_: &Metadata, _: &Metadata) -> bool {
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use quelle_core::prelude::Novel;
+
+    use super::*;
+    use crate::{write_content, PersistOptions, SavedNovel};
+
+    #[test]
+    fn should_store_identical_chapters_once() {
+        let dir = tempfile::tempdir().unwrap();
+        let persist = Persist::new(PersistOptions::with_base_dir(dir.path().to_path_buf()));
+
+        let mut global = persist.read_global().unwrap();
+        for (source, content) in [("one", "<p>same</p>"), ("two", "<p>same</p>")] {
+            let novel_dir = persist.options.novel.dir.join(source).join("novel");
+            let novel = persist.persist_novel(novel_dir.clone());
+            fs::create_dir_all(novel.chapters_dir()).unwrap();
+
+            let path = write_content(
+                novel.chapters_dir().join("0.html"),
+                content,
+                Compression::None,
+                None,
+            )
+            .unwrap();
+
+            let mut data = SavedNovel::new(Novel::default());
+            let url = format!("https://{source}.com/1");
+            data.downloaded.insert(url, novel.relative_path(path));
+            novel.write_data(&data).unwrap();
+            global.insert_novel(format!("https://{source}.com/novel"), novel_dir);
+        }
+        persist.save_global(&global).unwrap();
+
+        let size = "<p>same</p>".len() as u64;
+        let report = deduplicate(&persist, true).unwrap();
+        assert_eq!((report.chapters, report.deduplicated), (2, 1));
+        assert_eq!(report.saved_bytes, size);
+
+        deduplicate(&persist, false).unwrap();
+        let report = deduplicate(&persist, false).unwrap();
+        if cfg!(unix) {
+            assert_eq!((report.shared, report.deduplicated), (1, 0));
+            assert_eq!(report.saved_bytes, size);
+        }
+    }
+}
@@ -0,0 +1,277 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fs::{self, File},
+    io::{BufReader, BufWriter},
+    path::Path,
+};
+
+use chrono::{DateTime, Utc};
+use quelle_common::NovelId;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    backup::ConflictStrategy,
+    compression::{write_content, Compression},
+    create_parent_all,
+    error::{PersistError, PersistResult},
+    migration::SCHEMA_VERSION,
+    Persist, SavedNovel,
+};
+
+/// The version of the JSON dump written by this release
+pub const DUMP_VERSION: u32 = 1;
+
+/// The source of the novels loaded from a dump that were not saved in a source directory
+const DUMP_SOURCE: &str = "dump";
+
+/// The whole library written as a single JSON document, see [`Persist::dump_json`]
+///
+/// The novels are written as they are saved in the library, with the paths
+/// of their chapters relative to the novel directory. Chapter contents are
+/// only included when requested, as they make up most of the library.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct LibraryDump {
+    pub version: u32,
+    /// The schema version of the library when it was dumped
+    pub schema: u32,
+    pub created_at: DateTime<Utc>,
+    pub novels: Vec<NovelDump>,
+    /// The urls of the novels in each collection keyed by name
+    #[serde(default)]
+    pub collections: BTreeMap<String, BTreeSet<String>>,
+}
+
+/// A novel of the library dump
+#[derive(Serialize, Deserialize, Debug)]
+pub struct NovelDump {
+    /// The url the novel is saved from
+    pub url: String,
+    /// The id of the novel, naming its directory once loaded
+    #[serde(default)]
+    pub id: Option<NovelId>,
+    pub data: SavedNovel,
+    /// The content of the downloaded chapters keyed by url, empty unless included
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub chapters: BTreeMap<String, String>,
+}
+
+/// The novels added to the library from a dump, see [`Persist::load_json`]
+#[derive(Debug, Default)]
+pub struct LoadReport {
+    pub novels: usize,
+    pub chapters: usize,
+    /// The urls of the novels kept as they are in the library
+    pub skipped: Vec<String>,
+}
+
+/// Write the library as JSON, see [`Persist::dump_json`]
+pub(crate) fn dump_json(
+    persist: &Persist,
+    path: &Path,
+    chapters: bool,
+) -> PersistResult<LibraryDump> {
+    let global = persist.read_global()?;
+
+    let mut novels = vec![];
+    for (url, dir) in global.novels() {
+        let novel = persist.persist_novel(dir.clone());
+        let Some(data) = novel.read_data()? else {
+            continue;
+        };
+
+        let mut contents = BTreeMap::new();
+        if chapters {
+            for (url, path) in &data.downloaded {
+                if novel.dir().join(path).exists() {
+                    contents.insert(url.clone(), novel.read_chapter(path)?);
+                }
+            }
+        }
+
+        novels.push(NovelDump {
+            url: url.clone(),
+            id: persist.novel_id(dir),
+            data,
+            chapters: contents,
+        });
+    }
+    novels.sort_by(|a, b| a.url.cmp(&b.url));
+
+    let collections = persist.read_collections()?;
+    let collections = collections
+        .list_collections()
+        .filter_map(|(name, _)| Some((name.clone(), collections.novels(name)?.clone())))
+        .collect();
+
+    let dump = LibraryDump {
+        version: DUMP_VERSION,
+        schema: SCHEMA_VERSION,
+        created_at: Utc::now(),
+        novels,
+        collections,
+    };
+
+    create_parent_all(path)?;
+    let writer = BufWriter::new(File::create(path)?);
+    serde_json::to_writer_pretty(writer, &dump)?;
+    Ok(dump)
+}
+
+/// Add the novels of a JSON dump to the library, see [`Persist::load_json`]
+pub(crate) fn load_json(
+    persist: &Persist,
+    path: &Path,
+    strategy: ConflictStrategy,
+) -> PersistResult<LoadReport> {
+    let reader = BufReader::new(File::open(path)?);
+    let dump: LibraryDump = serde_json::from_reader(reader)?;
+    if dump.version > DUMP_VERSION {
+        return Err(PersistError::InvalidBackup(format!(
+            "the dump version {} is newer than the supported version {DUMP_VERSION}",
+            dump.version
+        )));
+    }
+    if dump.schema > SCHEMA_VERSION {
+        return Err(PersistError::UnsupportedSchema {
+            found: dump.schema,
+            supported: SCHEMA_VERSION,
+        });
+    }
+
+    let mut global = persist.read_global()?;
+    if strategy == ConflictStrategy::Fail {
+        if let Some(novel) = dump
+            .novels
+            .iter()
+            .find(|novel| global.primary_url(&novel.url).is_some())
+        {
+            return Err(PersistError::NovelExists(novel.url.clone()));
+        }
+    }
+
+    let mut report = LoadReport::default();
+    for NovelDump {
+        url,
+        id,
+        mut data,
+        chapters,
+    } in dump.novels
+    {
+        let dir = match global.novel_path_from_url(&url) {
+            Some(_) if strategy == ConflictStrategy::Skip => {
+                report.skipped.push(url);
+                continue;
+            }
+            Some(dir) => dir.to_path_buf(),
+            None => {
+                let id = id.unwrap_or_else(|| NovelId::new(DUMP_SOURCE, &data.novel.title));
+                persist.options.novel.dir.join(&id.source).join(&id.slug)
+            }
+        };
+
+        let novel = persist.persist_novel(dir.clone());
+        fs::create_dir_all(novel.chapters_dir())?;
+
+        // Chapters without their content are downloaded again
+        let mut downloaded = BTreeMap::new();
+        for (url, path) in std::mem::take(&mut data.downloaded) {
+            let Some(content) = chapters.get(&url) else {
+                continue;
+            };
+
+            let plain = match Compression::of_path(&path) {
+                Compression::None => path.clone(),
+                _ => path.with_extension(""),
+            };
+            let compression = Compression::of_path(&path);
+            let written = write_content(dir.join(plain), content, compression, persist.cipher())?;
+            downloaded.insert(url, novel.relative_path(written));
+            report.chapters += 1;
+        }
+        data.chapter_hashes
+            .retain(|url, _| downloaded.contains_key(url));
+        data.downloaded = downloaded.into_iter().collect();
+
+        // The cover files are not part of the dump
+        data.cover = None;
+        data.cover_history.clear();
+        data.overrides.cover = None;
+
+        novel.write_data(&data)?;
+        for mirror in &data.mirrors {
+            global.insert_alias(mirror.clone(), url.clone());
+        }
+        global.insert_novel(url, dir);
+        report.novels += 1;
+    }
+    persist.save_global(&global)?;
+
+    let mut collections = persist.read_collections()?;
+    for (name, urls) in dump.collections {
+        for url in urls {
+            collections.add_to_collection(&name, &url);
+        }
+    }
+    persist.save_collections(&collections)?;
+
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use quelle_core::prelude::Novel;
+
+    use super::*;
+    use crate::PersistOptions;
+
+    #[test]
+    fn should_load_dumped_library() {
+        let root = tempfile::tempdir().unwrap();
+        let source = Persist::new(PersistOptions::with_base_dir(root.path().join("source")));
+
+        let url = String::from("https://example.com/novel");
+        let dir = source.options.novel.dir.join("example").join("novel");
+        let novel = source.persist_novel(dir.clone());
+        fs::create_dir_all(novel.chapters_dir()).unwrap();
+
+        let mut data = SavedNovel::new(Novel {
+            title: String::from("Novel"),
+            ..Default::default()
+        });
+        for index in 0..2 {
+            let path = novel.chapters_dir().join(format!("{index}.html"));
+            let path = write_content(path, "<p>content</p>", Compression::None, None).unwrap();
+            data.downloaded
+                .insert(format!("{url}/{index}"), novel.relative_path(path));
+        }
+        novel.write_data(&data).unwrap();
+
+        let mut global = source.read_global().unwrap();
+        global.insert_novel(url.clone(), dir);
+        source.save_global(&global).unwrap();
+
+        let path = root.path().join("library.json");
+        let dump = dump_json(&source, &path, false).unwrap();
+        assert_eq!(dump.novels.len(), 1);
+        assert!(dump.novels[0].chapters.is_empty());
+
+        // Without the content, the chapters are left to download again
+        let target = Persist::new(PersistOptions::with_base_dir(root.path().join("target")));
+        let report = load_json(&target, &path, ConflictStrategy::Skip).unwrap();
+        assert_eq!((report.novels, report.chapters), (1, 0));
+
+        dump_json(&source, &path, true).unwrap();
+        let report = load_json(&target, &path, ConflictStrategy::Skip).unwrap();
+        assert_eq!(report.skipped, vec![url.clone()]);
+
+        let report = load_json(&target, &path, ConflictStrategy::Overwrite).unwrap();
+        assert_eq!((report.novels, report.chapters), (1, 2));
+
+        let dir = target.read_global().unwrap();
+        let dir = dir.novel_path_from_url(&url).unwrap();
+        let novel = target.persist_novel(dir.to_path_buf());
+        let data = novel.read_data().unwrap().unwrap();
+        let content = novel.read_chapter(&data.downloaded[&format!("{url}/1")]);
+        assert_eq!(content.unwrap(), "<p>content</p>");
+    }
+}
@@ -0,0 +1,187 @@
+use aes_gcm::{
+    aead::{rand_core::RngCore, Aead, AeadCore, KeyInit, OsRng},
+    Aes256Gcm, Key, Nonce,
+};
+use std::{fs, path::Path};
+
+use argon2::Argon2;
+use serde::{Deserialize, Serialize};
+
+use crate::error::{PersistError, PersistResult};
+
+/// Written before the nonce of every encrypted file
+const MAGIC: &[u8] = b"QENC1";
+
+const NONCE_LEN: usize = 12;
+
+/// Encrypted with the key of the library to check the passphrase when opening it
+const CHECK: &[u8] = b"quelle";
+
+/// How the key of an encrypted library is derived, saved in the library config
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct EncryptionConfig {
+    pub salt: Vec<u8>,
+    /// A known value encrypted with the key
+    pub check: Vec<u8>,
+}
+
+/// Encrypts and decrypts files of the library with AES-256-GCM
+///
+/// The key is derived from the passphrase with Argon2.
+#[derive(Clone)]
+pub struct Cipher {
+    cipher: Aes256Gcm,
+}
+
+impl std::fmt::Debug for Cipher {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Cipher").finish_non_exhaustive()
+    }
+}
+
+impl Cipher {
+    fn derive(passphrase: &str, salt: &[u8]) -> PersistResult<Self> {
+        let mut key = [0u8; 32];
+        Argon2::default()
+            .hash_password_into(passphrase.as_bytes(), salt, &mut key)
+            .map_err(|e| PersistError::Encryption(e.to_string()))?;
+
+        Ok(Self {
+            cipher: Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(&key)),
+        })
+    }
+
+    /// Create a key for a new encrypted library
+    pub fn create(passphrase: &str) -> PersistResult<(Self, EncryptionConfig)> {
+        let mut salt = vec![0u8; 16];
+        OsRng.fill_bytes(&mut salt);
+
+        let cipher = Self::derive(passphrase, &salt)?;
+        let check = cipher.encrypt(CHECK)?;
+
+        Ok((cipher, EncryptionConfig { salt, check }))
+    }
+
+    /// Derive the key of an encrypted library, failing when the passphrase is wrong
+    pub fn open(passphrase: &str, config: &EncryptionConfig) -> PersistResult<Self> {
+        let cipher = Self::derive(passphrase, &config.salt)?;
+        match cipher.decrypt(&config.check) {
+            Ok(check) if check == CHECK => Ok(cipher),
+            _ => Err(PersistError::WrongPassphrase),
+        }
+    }
+
+    pub fn encrypt(&self, data: &[u8]) -> PersistResult<Vec<u8>> {
+        let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+        let encrypted = self
+            .cipher
+            .encrypt(&nonce, data)
+            .map_err(|e| PersistError::Encryption(e.to_string()))?;
+
+        let mut output = Vec::with_capacity(MAGIC.len() + NONCE_LEN + encrypted.len());
+        output.extend_from_slice(MAGIC);
+        output.extend_from_slice(&nonce);
+        output.extend_from_slice(&encrypted);
+        Ok(output)
+    }
+
+    /// Decrypt the data, returning data that was never encrypted unchanged
+    pub fn decrypt(&self, data: &[u8]) -> PersistResult<Vec<u8>> {
+        let Some(rest) = data.strip_prefix(MAGIC) else {
+            return Ok(data.to_vec());
+        };
+
+        if rest.len() < NONCE_LEN {
+            return Err(PersistError::Encryption(String::from("truncated file")));
+        }
+
+        let (nonce, encrypted) = rest.split_at(NONCE_LEN);
+        self.cipher
+            .decrypt(Nonce::from_slice(nonce), encrypted)
+            .map_err(|e| PersistError::Encryption(e.to_string()))
+    }
+}
+
+/// Whether the data was written by [`Cipher::encrypt`]
+pub fn is_encrypted(data: &[u8]) -> bool {
+    data.starts_with(MAGIC)
+}
+
+/// Read the file, decrypting it when it was encrypted
+pub fn read_file(path: &Path, cipher: Option<&Cipher>) -> PersistResult<Vec<u8>> {
+    let data = fs::read(path)?;
+    if !is_encrypted(&data) {
+        return Ok(data);
+    }
+
+    cipher
+        .ok_or(PersistError::PassphraseRequired)?
+        .decrypt(&data)
+}
+
+/// Write the data to the file, encrypting it when a cipher is given
+pub fn write_file(path: &Path, data: &[u8], cipher: Option<&Cipher>) -> PersistResult<()> {
+    match cipher {
+        Some(cipher) => fs::write(path, cipher.encrypt(data)?)?,
+        None => fs::write(path, data)?,
+    }
+    Ok(())
+}
+
+/// Encrypt the data into a line of hex digits, for files appended to a line at a time
+pub(crate) fn encrypt_line(cipher: &Cipher, data: &[u8]) -> PersistResult<String> {
+    let encrypted = cipher.encrypt(data)?;
+    Ok(encrypted.iter().map(|byte| format!("{byte:02x}")).collect())
+}
+
+/// Decrypt a line written by [`encrypt_line`], returning lines of json unchanged
+pub(crate) fn decrypt_line(line: &str, cipher: Option<&Cipher>) -> PersistResult<Vec<u8>> {
+    if line.starts_with('{') {
+        return Ok(line.as_bytes().to_vec());
+    }
+
+    let invalid = || PersistError::Encryption(String::from("invalid encrypted line"));
+    let data = (0..line.len())
+        .step_by(2)
+        .map(|at| {
+            line.get(at..at + 2)
+                .and_then(|byte| u8::from_str_radix(byte, 16).ok())
+        })
+        .collect::<Option<Vec<_>>>()
+        .ok_or_else(invalid)?;
+
+    cipher
+        .ok_or(PersistError::PassphraseRequired)?
+        .decrypt(&data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_encrypt_and_check_passphrase() {
+        let (cipher, config) = Cipher::create("secret").unwrap();
+
+        let encrypted = cipher.encrypt(b"<p>content</p>").unwrap();
+        assert!(is_encrypted(&encrypted));
+        assert_eq!(cipher.decrypt(&encrypted).unwrap(), b"<p>content</p>");
+        assert_eq!(cipher.decrypt(b"plain").unwrap(), b"plain");
+
+        let opened = Cipher::open("secret", &config).unwrap();
+        assert_eq!(opened.decrypt(&encrypted).unwrap(), b"<p>content</p>");
+        assert!(matches!(
+            Cipher::open("wrong", &config),
+            Err(PersistError::WrongPassphrase)
+        ));
+
+        let line = encrypt_line(&cipher, b"{\"kind\":1}").unwrap();
+        assert!(!line.contains("kind"));
+        assert_eq!(decrypt_line(&line, Some(&cipher)).unwrap(), b"{\"kind\":1}");
+        assert_eq!(decrypt_line("{}", None).unwrap(), b"{}");
+        assert!(matches!(
+            decrypt_line(&line, None),
+            Err(PersistError::PassphraseRequired)
+        ));
+    }
+}
@@ -1,4 +1,4 @@
-use std::io;
+use std::{io, path::PathBuf};
 
 pub type PersistResult<T> = Result<T, PersistError>;
 
@@ -9,6 +9,44 @@ pub enum PersistError {
 
     #[error("{0}")]
     IO(#[from] io::Error),
+
+    #[error(
+        "the library uses schema version {found}, newer than the supported version {supported}"
+    )]
+    UnsupportedSchema { found: u32, supported: u32 },
+
+    #[error("failed to encrypt or decrypt: {0}")]
+    Encryption(String),
+
+    #[error("the library is encrypted and requires a passphrase")]
+    PassphraseRequired,
+
+    #[error("the passphrase of the library is wrong")]
+    WrongPassphrase,
+
+    #[error("the content of '{}' changed while being copied", .0.display())]
+    TransferMismatch(PathBuf),
+
+    #[error("failed to reach the remote storage: {0}")]
+    Remote(String),
+
+    #[error("invalid backup: {0}")]
+    InvalidBackup(String),
+
+    #[error("invalid epub: {0}")]
+    InvalidEpub(String),
+
+    #[error("'{}' already exists in the library", .0.display())]
+    BackupConflict(PathBuf),
+
+    #[error("a novel from '{0}' is already in the library")]
+    NovelExists(String),
+
+    #[error("cannot merge the novel from '{0}' into itself")]
+    SameNovel(String),
+
+    #[error("the library is in use by another process, locked at '{}'", .0.display())]
+    Locked(PathBuf),
 }
 
 impl From<serde_json::Error> for PersistError {
@@ -1,19 +1,28 @@
 use std::{
-    fs::{File, OpenOptions},
+    fs::{self, File, OpenOptions},
     io::{self, BufRead, BufReader, LineWriter, Seek, Write},
-    path::PathBuf,
+    path::{Path, PathBuf},
 };
 
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 
-use crate::{create_parent_all, error::PersistResult};
+use crate::{
+    create_parent_all,
+    encryption::{self, Cipher},
+    error::PersistResult,
+    Provenance,
+};
 
+/// The events of a novel, appended to the log a line at a time
+///
+/// The events of an encrypted library are encrypted line by line.
 #[derive(Debug)]
 pub struct EventLog {
     events: Option<Vec<Event>>,
     file: LineWriter<File>,
     path: PathBuf,
+    cipher: Option<Cipher>,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -24,11 +33,35 @@ pub struct Event {
 
 #[derive(Serialize, Deserialize, Debug)]
 pub enum EventKind {
-    Downloaded { url: String, path: PathBuf },
+    Downloaded {
+        url: String,
+        path: PathBuf,
+        /// The detected language of the chapter content
+        #[serde(default)]
+        lang: Option<String>,
+        /// The number of words of the chapter content
+        #[serde(default)]
+        words: Option<u64>,
+        /// The extension the content was fetched with
+        #[serde(default)]
+        provenance: Option<Provenance>,
+        /// The checksum of the chapter content
+        #[serde(default)]
+        hash: Option<String>,
+    },
+    /// A download of the chapters started
+    Queued { urls: Vec<String> },
+    /// The chapter could not be downloaded
+    Failed {
+        url: String,
+        error: String,
+        /// The source refused the chapter, such as a locked chapter
+        skipped: bool,
+    },
 }
 
 impl EventLog {
-    pub fn new(path: PathBuf) -> PersistResult<Self> {
+    pub fn new(path: PathBuf, cipher: Option<Cipher>) -> PersistResult<Self> {
         println!("event_log = {}", path.display());
         create_parent_all(&path)?;
 
@@ -39,6 +72,7 @@ impl EventLog {
             events: None,
             file,
             path,
+            cipher,
         })
     }
 
@@ -50,7 +84,8 @@ impl EventLog {
             if line.is_empty() {
                 continue;
             };
-            let event = serde_json::from_str(&line)?;
+            let line = encryption::decrypt_line(&line, self.cipher.as_ref())?;
+            let event = serde_json::from_slice(&line)?;
             events.push(event);
         }
 
@@ -68,8 +103,13 @@ impl EventLog {
         };
 
         let bytes = serde_json::to_vec(&event)?;
-        self.file.write(&bytes)?;
-        self.file.write(b"\n")?;
+        match &self.cipher {
+            Some(cipher) => self
+                .file
+                .write_all(encryption::encrypt_line(cipher, &bytes)?.as_bytes())?,
+            None => self.file.write_all(&bytes)?,
+        }
+        self.file.write_all(b"\n")?;
 
         match self.events.as_mut() {
             Some(events) => {
@@ -93,3 +133,19 @@ impl EventLog {
         Ok(())
     }
 }
+
+/// Encrypt the lines of the log at the path that are not encrypted yet
+pub(crate) fn encrypt_log(path: &Path, cipher: &Cipher) -> PersistResult<()> {
+    let mut content = String::new();
+    for line in BufReader::new(File::open(path)?).lines() {
+        let line = line?;
+        match line.starts_with('{') {
+            true => content += &encryption::encrypt_line(cipher, line.as_bytes())?,
+            false => content += &line,
+        }
+        content.push('\n');
+    }
+
+    fs::write(path, content)?;
+    Ok(())
+}
@@ -0,0 +1,101 @@
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+};
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::{create_parent_all, encryption, error::PersistResult, PersistNovel};
+
+/// The bundles written of a novel keyed by output path, so that bundling
+/// again can skip the files whose content would not change
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct ExportLog {
+    exports: BTreeMap<PathBuf, ExportRecord>,
+    /// The url of the last chapter of the novel when it was last bundled
+    #[serde(default)]
+    pub last_chapter: Option<String>,
+}
+
+/// A bundle written of a novel
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct ExportRecord {
+    pub format: String,
+    /// The number of chapters in the bundle
+    pub chapters: usize,
+    /// Identifies the content of the bundle, changing when chapters are added
+    /// or updated, the metadata is edited or the bundle options change
+    pub fingerprint: String,
+    pub exported_at: DateTime<Utc>,
+}
+
+impl ExportLog {
+    pub fn get(&self, path: &Path) -> Option<&ExportRecord> {
+        self.exports.get(path)
+    }
+
+    /// Whether the file at the path was written with the same content and is still there
+    pub fn is_current(&self, path: &Path, fingerprint: &str) -> bool {
+        self.get(path)
+            .is_some_and(|record| record.fingerprint == fingerprint)
+            && path.exists()
+    }
+
+    pub fn record(&mut self, path: PathBuf, format: &str, chapters: usize, fingerprint: String) {
+        let record = ExportRecord {
+            format: format.to_string(),
+            chapters,
+            fingerprint,
+            exported_at: Utc::now(),
+        };
+        self.exports.insert(path, record);
+    }
+}
+
+impl PersistNovel<'_> {
+    #[inline]
+    pub fn exports_path(&self) -> PathBuf {
+        self.dir().join("exports.json")
+    }
+
+    /// The bundles written of the novel
+    pub fn read_exports(&self) -> PersistResult<ExportLog> {
+        let path = self.exports_path();
+        if !path.exists() {
+            return Ok(Default::default());
+        }
+
+        let data = encryption::read_file(&path, self.persist().cipher())?;
+        Ok(serde_json::from_slice(&data)?)
+    }
+
+    pub fn write_exports(&self, exports: &ExportLog) -> PersistResult<()> {
+        let path = self.exports_path();
+        create_parent_all(&path)?;
+
+        let data = serde_json::to_vec_pretty(exports)?;
+        encryption::write_file(&path, &data, self.persist().cipher())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_skip_only_unchanged_existing_exports() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.path().join("novel.epub");
+
+        let mut exports = ExportLog::default();
+        exports.record(path.clone(), "epub", 3, String::from("abc"));
+        assert!(!exports.is_current(&path, "abc"));
+
+        std::fs::write(&path, "epub").unwrap();
+        assert!(exports.is_current(&path, "abc"));
+        assert!(!exports.is_current(&path, "abd"));
+        assert_eq!(exports.get(&path).map(|record| record.chapters), Some(3));
+    }
+}
@@ -5,6 +5,7 @@ use std::{
     path::{Path, PathBuf},
 };
 
+use quelle_common::NovelId;
 use serde::{Deserialize, Serialize};
 
 use crate::{create_parent_all, error::PersistResult};
@@ -12,6 +13,12 @@ use crate::{create_parent_all, error::PersistResult};
 #[derive(Serialize, Deserialize, Debug, Default)]
 pub struct Global {
     novels: HashMap<String, PathBuf>,
+    /// The other urls of the novels keyed by url, leading to the url the novel is saved from
+    #[serde(default)]
+    aliases: HashMap<String, String>,
+    /// The ids of the novels merged into another, leading to the url of that novel
+    #[serde(default)]
+    merged: HashMap<NovelId, String>,
 }
 
 impl Global {
@@ -42,14 +49,25 @@ impl Global {
         Ok(())
     }
 
+    /// The url and directory of every saved novel
+    pub fn novels(&self) -> impl Iterator<Item = (&String, &PathBuf)> {
+        self.novels.iter()
+    }
+
     pub fn novel_path_from_url(&self, url: &str) -> Option<&Path> {
-        if let Some(value) = self.novels.get(url).map(AsRef::as_ref) {
-            return Some(value);
-        }
+        let url = self.primary_url(url)?;
+        self.novels.get(url).map(AsRef::as_ref)
+    }
 
-        if let Some(url) = url.strip_suffix("/") {
-            if let Some(value) = self.novels.get(url).map(AsRef::as_ref) {
-                return Some(value);
+    /// The url the novel is saved from, given the url or one of its mirrors
+    pub fn primary_url<'a>(&'a self, url: &'a str) -> Option<&'a str> {
+        let urls = [Some(url), url.strip_suffix("/")];
+        for url in urls.into_iter().flatten() {
+            if let Some((key, _)) = self.novels.get_key_value(url) {
+                return Some(key);
+            }
+            if let Some(primary) = self.aliases.get(url) {
+                return Some(primary);
             }
         }
 
@@ -59,6 +77,32 @@ impl Global {
     pub fn insert_novel(&mut self, url: String, path: PathBuf) {
         self.novels.insert(url, path);
     }
+
+    /// Forget the novel saved from the url, returning its directory
+    pub fn remove_novel(&mut self, url: &str) -> Option<PathBuf> {
+        self.aliases.retain(|_, primary| primary != url);
+        self.novels.remove(url)
+    }
+
+    /// Lead another url of a novel to the url it is saved from
+    pub fn insert_alias(&mut self, alias: String, url: String) {
+        self.aliases.insert(alias, url);
+    }
+
+    /// Lead the id of a novel merged into another to the url of that novel
+    pub fn insert_merged(&mut self, id: NovelId, url: String) {
+        self.merged.insert(id, url);
+    }
+
+    /// The url of the novel the novel with the id was merged into
+    pub fn merged_into(&self, id: &NovelId) -> Option<&str> {
+        self.merged.get(id).map(String::as_str)
+    }
+
+    /// Forget another url of a novel, returning the url it led to
+    pub fn remove_alias(&mut self, alias: &str) -> Option<String> {
+        self.aliases.remove(alias)
+    }
 }
 
 #[cfg(test)]
@@ -84,4 +128,20 @@ mod tests {
             Some(Path::new("/novels/123"))
         );
     }
+
+    #[test]
+    fn should_return_path_from_alias() {
+        let mut global = Global::default();
+        let url = String::from("https://example.com/novel/123");
+        global.insert_novel(url.clone(), PathBuf::from("/novels/123"));
+        global.insert_alias(String::from("https://mirror.com/novel/abc"), url.clone());
+
+        assert_eq!(
+            global.novel_path_from_url("https://mirror.com/novel/abc/"),
+            Some(Path::new("/novels/123"))
+        );
+
+        global.remove_novel(&url);
+        assert_eq!(global.primary_url("https://mirror.com/novel/abc"), None);
+    }
 }
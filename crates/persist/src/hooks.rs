@@ -0,0 +1,77 @@
+use std::{
+    path::PathBuf,
+    sync::{
+        mpsc::{self, Receiver, Sender},
+        Mutex,
+    },
+};
+
+/// A change made to the library
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StorageEvent {
+    /// The novel was saved in the library for the first time
+    NovelAdded { url: String, dir: PathBuf },
+    /// The novel is no longer part of the library
+    NovelRemoved { url: String, dir: PathBuf },
+    /// The data of the novel was saved
+    NovelUpdated { dir: PathBuf },
+    /// The content of a chapter was saved to the path
+    ChapterStored {
+        dir: PathBuf,
+        url: String,
+        path: PathBuf,
+    },
+}
+
+/// The receivers of the changes made to a library
+#[derive(Debug, Default)]
+pub(crate) struct Subscribers {
+    senders: Mutex<Vec<Sender<StorageEvent>>>,
+}
+
+impl Subscribers {
+    pub fn subscribe(&self) -> Receiver<StorageEvent> {
+        let (sender, receiver) = mpsc::channel();
+        self.lock().push(sender);
+        receiver
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.lock().is_empty()
+    }
+
+    /// Send the event to every receiver, forgetting the ones that were dropped
+    pub fn emit(&self, event: StorageEvent) {
+        self.lock()
+            .retain(|sender| sender.send(event.clone()).is_ok());
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Vec<Sender<StorageEvent>>> {
+        self.senders
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_send_events_to_live_subscribers() {
+        let subscribers = Subscribers::default();
+        assert!(subscribers.is_empty());
+
+        let first = subscribers.subscribe();
+        let second = subscribers.subscribe();
+        drop(second);
+
+        let event = StorageEvent::NovelUpdated {
+            dir: PathBuf::from("novels/example/novel"),
+        };
+        subscribers.emit(event.clone());
+
+        assert_eq!(first.try_recv(), Ok(event));
+        assert_eq!(subscribers.lock().len(), 1);
+    }
+}
@@ -0,0 +1,155 @@
+use std::{
+    collections::HashMap,
+    fs::{File, OpenOptions},
+    io::{BufReader, BufWriter},
+    path::Path,
+};
+
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::{create_parent_all, error::PersistResult};
+
+/// The number of consecutive failures before a host is suspended
+const FAILURE_THRESHOLD: u32 = 3;
+
+/// The suspension applied when a host first reaches the failure threshold
+const BASE_BACKOFF_MINUTES: i64 = 30;
+
+/// The upper bound on how long a host may be suspended
+const MAX_BACKOFF_MINUTES: i64 = 24 * 60;
+
+/// Tracks failure streaks of source hosts so that hosts which are down
+/// can be skipped until their backoff expires.
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct HostRegistry {
+    hosts: HashMap<String, HostStatus>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
+pub struct HostStatus {
+    pub failure_streak: u32,
+    pub last_failure: Option<DateTime<Utc>>,
+    pub suspended_until: Option<DateTime<Utc>>,
+}
+
+impl HostRegistry {
+    pub fn open(path: &Path) -> PersistResult<Self> {
+        let data = if path.exists() {
+            let file = File::open(path)?;
+            let reader = BufReader::new(file);
+            serde_json::from_reader(reader)?
+        } else {
+            Default::default()
+        };
+
+        Ok(data)
+    }
+
+    pub fn save(&self, path: &Path) -> PersistResult<()> {
+        create_parent_all(path)?;
+
+        let file = OpenOptions::new()
+            .create(true)
+            .truncate(true)
+            .write(true)
+            .open(path)?;
+
+        let writer = BufWriter::new(file);
+        serde_json::to_writer(writer, self)?;
+
+        Ok(())
+    }
+
+    pub fn status(&self, host: &str) -> Option<&HostStatus> {
+        self.hosts.get(host)
+    }
+
+    /// Returns the time until which the host is suspended, if it is currently suspended
+    pub fn suspended_until(&self, host: &str, now: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        self.hosts
+            .get(host)
+            .and_then(|status| status.suspended_until)
+            .filter(|until| *until > now)
+    }
+
+    /// Record a failed request to the host and suspend it if the streak is long enough
+    pub fn record_failure(&mut self, host: &str, now: DateTime<Utc>) -> &HostStatus {
+        let status = self.hosts.entry(host.to_string()).or_default();
+        status.failure_streak += 1;
+        status.last_failure = Some(now);
+
+        if status.failure_streak >= FAILURE_THRESHOLD {
+            let exponent = (status.failure_streak - FAILURE_THRESHOLD).min(16);
+            let minutes = (BASE_BACKOFF_MINUTES << exponent).min(MAX_BACKOFF_MINUTES);
+            status.suspended_until = Some(now + Duration::minutes(minutes));
+        }
+
+        status
+    }
+
+    /// Record a successful request, which resets the failure streak of the host
+    pub fn record_success(&mut self, host: &str) {
+        self.hosts.remove(host);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{Duration, Utc};
+
+    use super::HostRegistry;
+
+    #[test]
+    fn should_suspend_after_failure_threshold() {
+        let now = Utc::now();
+        let mut registry = HostRegistry::default();
+
+        registry.record_failure("example.com", now);
+        registry.record_failure("example.com", now);
+        assert_eq!(registry.suspended_until("example.com", now), None);
+
+        registry.record_failure("example.com", now);
+        assert_eq!(
+            registry.suspended_until("example.com", now),
+            Some(now + Duration::minutes(30))
+        );
+
+        registry.record_failure("example.com", now);
+        assert_eq!(
+            registry.suspended_until("example.com", now),
+            Some(now + Duration::minutes(60))
+        );
+    }
+
+    #[test]
+    fn should_resume_after_backoff_or_success() {
+        let now = Utc::now();
+        let mut registry = HostRegistry::default();
+
+        for _ in 0..3 {
+            registry.record_failure("example.com", now);
+        }
+
+        let later = now + Duration::minutes(31);
+        assert_eq!(registry.suspended_until("example.com", later), None);
+
+        registry.record_success("example.com");
+        assert!(registry.status("example.com").is_none());
+    }
+
+    #[test]
+    fn should_cap_backoff() {
+        let now = Utc::now();
+        let mut registry = HostRegistry::default();
+
+        for _ in 0..40 {
+            registry.record_failure("example.com", now);
+        }
+
+        assert_eq!(
+            registry.suspended_until("example.com", now),
+            Some(now + Duration::hours(24))
+        );
+    }
+}
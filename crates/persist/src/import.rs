@@ -0,0 +1,698 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fs::{self, File},
+    io::{BufReader, Read},
+    path::{Path, PathBuf},
+};
+
+use quelle_common::NovelId;
+use quelle_core::prelude::{Chapter, Metadata, Novel, NovelStatus, Volume};
+use zip::ZipArchive;
+
+use crate::{
+    content_hash,
+    error::{PersistError, PersistResult},
+    stats::count_words,
+    CoverLoc, Persist, SavedNovel,
+};
+
+/// The source of the novels imported from EPUB files
+pub const IMPORT_SOURCE: &str = "epub";
+
+/// Dublin core elements read into the dedicated novel fields
+const NOVEL_ELEMENTS: [&str; 6] = [
+    "title",
+    "creator",
+    "description",
+    "language",
+    "source",
+    "identifier",
+];
+
+/// A novel imported by [`Persist::import_epub`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ImportedNovel {
+    pub url: String,
+    pub dir: PathBuf,
+    pub title: String,
+    pub volumes: usize,
+    pub chapters: usize,
+}
+
+/// Read the EPUB into a new novel of the library, see [`Persist::import_epub`]
+pub(crate) fn import_epub(persist: &Persist, path: &Path) -> PersistResult<ImportedNovel> {
+    let mut epub = Epub::open(path)?;
+    let container = epub.read_string("META-INF/container.xml")?;
+    let opf_path = elements(&container, "rootfile")
+        .into_iter()
+        .find_map(|rootfile| attribute(rootfile.attrs, "full-path"))
+        .ok_or_else(|| invalid("the container does not list a package document"))?;
+
+    let opf = epub.read_string(&opf_path)?;
+    let base = parent(&opf_path);
+    let package = Package::parse(&opf, &base);
+
+    let mut novel = package.novel();
+    if novel.title.is_empty() {
+        return Err(invalid("the package document has no title"));
+    }
+    if novel.url.is_empty() {
+        let path = fs::canonicalize(path)?;
+        novel.url = format!("file://{}", path.display());
+    }
+
+    let mut global = persist.read_global()?;
+    if global.novel_path_from_url(&novel.url).is_some() {
+        return Err(PersistError::NovelExists(novel.url));
+    }
+
+    let toc = match &package.toc {
+        Some(toc) => read_toc(&epub.read_string(toc)?, &parent(toc), toc.ends_with(".ncx")),
+        None => HashMap::new(),
+    };
+
+    let id = NovelId::new(IMPORT_SOURCE, &novel.title);
+    let dir = persist.options.novel.dir.join(&id.source).join(&id.slug);
+    let persist_novel = persist.persist_novel(dir.clone());
+    let compression = persist.read_config()?.compression;
+
+    let mut volumes: Vec<Volume> = vec![];
+    let mut contents = vec![];
+    for href in &package.spine {
+        if package.skipped.contains(href) {
+            continue;
+        }
+
+        let document = epub.read_string(href)?;
+        let body = inner_body(&document);
+
+        // Volumes written by quelle are a title page holding a single heading
+        if let Some(heading) = elements(body, "h1")
+            .into_iter()
+            .find(|heading| attribute(heading.attrs, "class").as_deref() == Some("volume"))
+        {
+            volumes.push(Volume {
+                index: volumes.len() as i32,
+                name: text(heading.text),
+                chapters: vec![],
+            });
+            continue;
+        }
+
+        let (heading, content) = split_heading(body);
+        let title = toc
+            .get(href)
+            .cloned()
+            .or(heading)
+            .or_else(|| elements(&document, "title").first().map(|e| text(e.text)))
+            .filter(|title| !title.is_empty())
+            .unwrap_or_else(|| format!("Chapter {}", contents.len() + 1));
+
+        if volumes.is_empty() {
+            volumes.push(Volume::default());
+        }
+
+        let chapter = Chapter {
+            index: contents.len() as i32,
+            title,
+            url: format!("{}#{href}", novel.url),
+            updated_at: None,
+            number: None,
+            part: None,
+            label: None,
+        };
+        contents.push((chapter.url.clone(), content.trim().to_string()));
+        volumes.last_mut().unwrap().chapters.push(chapter);
+    }
+
+    if contents.is_empty() {
+        return Err(invalid("the spine has no chapters"));
+    }
+
+    novel.volumes = volumes;
+    let mut data = SavedNovel::new(novel);
+    if let Some((href, media_type)) = &package.cover {
+        let extension = Path::new(href).extension().and_then(|value| value.to_str());
+        let path = persist_novel.write_cover(extension, &epub.read(href)?)?;
+
+        data.cover = Some(CoverLoc {
+            path,
+            content_type: media_type.clone(),
+            url: None,
+            hash: None,
+        });
+    }
+
+    let mut batch = persist_novel.batch()?;
+    let chapters = data
+        .novel
+        .volumes
+        .iter()
+        .flat_map(|volume| &volume.chapters);
+    for (chapter, (url, content)) in chapters.zip(&contents) {
+        let path = batch.save_chapter(chapter, content, compression)?;
+        data.downloaded
+            .insert(url.clone(), persist_novel.relative_path(path));
+        data.word_counts.insert(url.clone(), count_words(content));
+        data.chapter_hashes
+            .insert(url.clone(), content_hash(content));
+    }
+    batch.commit(&data)?;
+
+    global.insert_novel(data.novel.url.clone(), dir.clone());
+    persist.save_global(&global)?;
+
+    Ok(ImportedNovel {
+        url: data.novel.url.clone(),
+        dir,
+        title: data.novel.title.clone(),
+        volumes: data.novel.volumes.len(),
+        chapters: contents.len(),
+    })
+}
+
+struct Epub {
+    archive: ZipArchive<BufReader<File>>,
+}
+
+impl Epub {
+    fn open(path: &Path) -> PersistResult<Self> {
+        let file = BufReader::new(File::open(path)?);
+        let archive = ZipArchive::new(file).map_err(|e| invalid(e.to_string()))?;
+        Ok(Self { archive })
+    }
+
+    fn read(&mut self, name: &str) -> PersistResult<Vec<u8>> {
+        let mut file = self
+            .archive
+            .by_name(name)
+            .map_err(|_| invalid(format!("'{name}' is missing")))?;
+
+        let mut data = vec![];
+        file.read_to_end(&mut data)?;
+        Ok(data)
+    }
+
+    fn read_string(&mut self, name: &str) -> PersistResult<String> {
+        let data = self.read(name)?;
+        String::from_utf8(data).map_err(|_| invalid(format!("'{name}' is not utf-8")))
+    }
+}
+
+/// The parts of the package document needed to import the novel
+#[derive(Debug, Default)]
+struct Package {
+    metadata: Vec<(String, String)>,
+    /// The `meta` elements by name
+    meta: Vec<(String, String)>,
+    /// The content documents in reading order, as paths in the archive
+    spine: Vec<String>,
+    /// Content documents that are not chapters, such as the cover or the preface
+    skipped: HashSet<String>,
+    /// The path and media type of the cover image
+    cover: Option<(String, String)>,
+    /// The NCX or navigation document
+    toc: Option<String>,
+}
+
+impl Package {
+    fn parse(opf: &str, base: &str) -> Self {
+        let mut package = Package::default();
+
+        let metadata = elements(opf, "metadata");
+        let metadata = metadata.first().map(|element| element.text).unwrap_or("");
+        for element in children(metadata) {
+            if element.name == "meta" {
+                if let (Some(name), Some(content)) = (
+                    attribute(element.attrs, "name"),
+                    attribute(element.attrs, "content"),
+                ) {
+                    package.meta.push((name, content));
+                }
+            } else if element.prefix == "dc" {
+                package.metadata.push((
+                    element.name.to_string(),
+                    unescape(element.text).trim().into(),
+                ));
+            }
+        }
+
+        let cover_id = package
+            .meta
+            .iter()
+            .find(|(name, _)| name == "cover")
+            .map(|(_, id)| id.clone());
+
+        let mut items = HashMap::new();
+        let mut toc_id = None;
+        for spine in elements(opf, "spine") {
+            toc_id = attribute(spine.attrs, "toc");
+        }
+        for item in elements(opf, "item") {
+            let (Some(id), Some(href)) =
+                (attribute(item.attrs, "id"), attribute(item.attrs, "href"))
+            else {
+                continue;
+            };
+            let href = resolve(base, &href);
+            let media_type = attribute(item.attrs, "media-type").unwrap_or_default();
+            let properties = attribute(item.attrs, "properties").unwrap_or_default();
+            let properties = properties.split_whitespace().collect::<Vec<_>>();
+
+            if properties.contains(&"cover-image") || cover_id.as_ref() == Some(&id) {
+                package.cover = Some((href.clone(), media_type.clone()));
+            }
+            if properties.contains(&"nav") {
+                package.skipped.insert(href.clone());
+                package.toc = Some(href.clone());
+            } else if toc_id.as_ref() == Some(&id) && package.toc.is_none() {
+                package.toc = Some(href.clone());
+            }
+
+            items.insert(id, href);
+        }
+
+        for itemref in elements(opf, "itemref") {
+            let linear = attribute(itemref.attrs, "linear");
+            if let Some(href) = attribute(itemref.attrs, "idref").and_then(|id| items.get(&id)) {
+                if linear.as_deref() == Some("no") {
+                    package.skipped.insert(href.clone());
+                }
+                package.spine.push(href.clone());
+            }
+        }
+
+        // The front matter referenced by the guide is not part of the novel
+        for reference in elements(opf, "reference") {
+            if let Some(href) = attribute(reference.attrs, "href") {
+                package.skipped.insert(resolve(base, &href));
+            }
+        }
+
+        package
+    }
+
+    fn values<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a str> {
+        self.metadata
+            .iter()
+            .filter(move |(element, value)| element == name && !value.is_empty())
+            .map(|(_, value)| value.as_str())
+    }
+
+    fn novel(&self) -> Novel {
+        let url = self
+            .values("source")
+            .chain(self.values("identifier"))
+            .find(|value| value.starts_with("http://") || value.starts_with("https://"))
+            .unwrap_or_default();
+
+        let description = self
+            .values("description")
+            .flat_map(|value| value.split("</p>"))
+            .map(text)
+            .filter(|paragraph| !paragraph.is_empty())
+            .collect();
+
+        let mut metadata = self
+            .metadata
+            .iter()
+            .filter(|(name, value)| !NOVEL_ELEMENTS.contains(&name.as_str()) && !value.is_empty())
+            .map(|(name, value)| Metadata::new(name.clone(), value.clone(), None))
+            .collect::<Vec<_>>();
+
+        let mut status = NovelStatus::Unknown;
+        for (name, value) in &self.meta {
+            match name.as_str() {
+                "quelle:status" => status = NovelStatus::from(value.as_str()),
+                "cover" => {}
+                _ if name.starts_with("calibre:") => {}
+                _ => metadata.push(Metadata::new(name.clone(), value.clone(), None)),
+            }
+        }
+
+        Novel {
+            url: url.to_string(),
+            authors: self.values("creator").map(String::from).collect(),
+            title: self.values("title").next().unwrap_or_default().to_string(),
+            cover: None,
+            description,
+            volumes: vec![],
+            metadata,
+            status,
+            langs: self
+                .values("language")
+                .flat_map(|value| value.split(','))
+                .map(|lang| lang.trim().to_string())
+                .filter(|lang| !lang.is_empty())
+                .collect(),
+        }
+    }
+}
+
+/// The title of each content document in the table of contents
+fn read_toc(document: &str, base: &str, ncx: bool) -> HashMap<String, String> {
+    let entries = if ncx {
+        // Every navigation point has a label followed by its content
+        let labels = elements(document, "navLabel");
+        let contents = elements(document, "content");
+        labels
+            .into_iter()
+            .zip(contents)
+            .filter_map(|(label, content)| {
+                Some((attribute(content.attrs, "src")?, text(label.text)))
+            })
+            .collect::<Vec<_>>()
+    } else {
+        elements(document, "a")
+            .into_iter()
+            .filter_map(|link| Some((attribute(link.attrs, "href")?, text(link.text))))
+            .collect()
+    };
+
+    let mut toc = HashMap::new();
+    for (href, title) in entries {
+        toc.entry(resolve(base, &href)).or_insert(title);
+    }
+    toc
+}
+
+/// The first heading of the chapter and the content after it
+///
+/// Chapters exported by quelle start with their title as a heading, which
+/// would be written twice if kept.
+fn split_heading(body: &str) -> (Option<String>, &str) {
+    let trimmed = body.trim_start();
+    for name in ["h1", "h2"] {
+        if !trimmed.starts_with(&format!("<{name}")) {
+            continue;
+        }
+
+        if let Some(heading) = elements(trimmed, name).first() {
+            let close = format!("</{name}>");
+            if let Some(end) = trimmed.find(&close) {
+                return (Some(text(heading.text)), &trimmed[end + close.len()..]);
+            }
+        }
+    }
+
+    (None, body)
+}
+
+fn inner_body(document: &str) -> &str {
+    elements(document, "body")
+        .first()
+        .map(|body| body.text)
+        .unwrap_or(document)
+}
+
+fn invalid(message: impl Into<String>) -> PersistError {
+    PersistError::InvalidEpub(message.into())
+}
+
+/// The directory of the path in the archive, ending with a slash
+fn parent(path: &str) -> String {
+    match path.rfind('/') {
+        Some(end) => path[..=end].to_string(),
+        None => String::new(),
+    }
+}
+
+/// The path in the archive of a link relative to the directory
+fn resolve(base: &str, href: &str) -> String {
+    let href = href.split('#').next().unwrap_or_default();
+    let href = percent_decode(&unescape(href));
+
+    let mut parts: Vec<&str> = vec![];
+    for part in base.split('/').chain(href.split('/')) {
+        match part {
+            "" | "." => {}
+            ".." => {
+                parts.pop();
+            }
+            _ => parts.push(part),
+        }
+    }
+    parts.join("/")
+}
+
+fn percent_decode(value: &str) -> String {
+    let bytes = value.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = bytes
+            .get(i + 1..i + 3)
+            .and_then(|hex| u8::from_str_radix(std::str::from_utf8(hex).ok()?, 16).ok());
+        match (bytes[i], hex) {
+            (b'%', Some(byte)) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            (byte, _) => {
+                decoded.push(byte);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+/// An element found in an XML document
+#[derive(Debug)]
+struct Element<'a> {
+    prefix: &'a str,
+    name: &'a str,
+    attrs: &'a str,
+    /// The content between the tags, empty for self-closing elements
+    text: &'a str,
+}
+
+/// The elements with the local name anywhere in the document, in document order
+///
+/// The content documents only need to be scanned for a few elements, so a
+/// complete XML parser is not needed. Elements nested in one of the same
+/// name are not found.
+fn elements<'a>(document: &'a str, name: &str) -> Vec<Element<'a>> {
+    let mut found = vec![];
+    let mut rest = document;
+    while let Some((element, after)) = next_element(rest) {
+        rest = if element.name == name {
+            found.push(element);
+            after
+        } else {
+            // Keep looking inside of the element
+            &rest[rest.find('>').map_or(rest.len(), |end| end + 1)..]
+        };
+    }
+    found
+}
+
+/// The elements directly inside the content
+fn children(content: &str) -> Vec<Element<'_>> {
+    let mut found = vec![];
+    let mut rest = content;
+    while let Some((element, after)) = next_element(rest) {
+        found.push(element);
+        rest = after;
+    }
+    found
+}
+
+/// The next element and the document after it
+fn next_element(document: &str) -> Option<(Element<'_>, &str)> {
+    let mut offset = 0;
+    loop {
+        let start = offset + document[offset..].find('<')?;
+        let tag = &document[start + 1..];
+        let end = start + 1 + tag.find('>')?;
+
+        if tag.starts_with(['/', '?', '!']) {
+            offset = end + 1;
+            continue;
+        }
+
+        let head = &document[start + 1..end];
+        let self_closing = head.ends_with('/');
+        let head = head.trim_end_matches('/');
+        let (qualified, attrs) = head
+            .split_once(|c: char| c.is_whitespace())
+            .unwrap_or((head, ""));
+        let (prefix, name) = qualified.split_once(':').unwrap_or(("", qualified));
+
+        let after = &document[end + 1..];
+        if self_closing {
+            let element = Element {
+                prefix,
+                name,
+                attrs,
+                text: "",
+            };
+            return Some((element, after));
+        }
+
+        let close = format!("</{qualified}>");
+        let (text, after) = match after.find(&close) {
+            Some(position) => (&after[..position], &after[position + close.len()..]),
+            None => ("", after),
+        };
+        let element = Element {
+            prefix,
+            name,
+            attrs,
+            text,
+        };
+        return Some((element, after));
+    }
+}
+
+/// The unescaped value of the attribute, ignoring its namespace
+fn attribute(attrs: &str, name: &str) -> Option<String> {
+    let mut rest = attrs;
+    while let Some(position) = rest.find('=') {
+        let key = rest[..position]
+            .split_whitespace()
+            .last()
+            .unwrap_or_default();
+        let key = key.rsplit(':').next().unwrap_or(key);
+        let value = rest[position + 1..].trim_start();
+
+        let quote = value.chars().next()?;
+        let value = &value[1..];
+        let end = value.find(quote)?;
+        if key == name {
+            return Some(unescape(&value[..end]));
+        }
+        rest = &value[end + 1..];
+    }
+    None
+}
+
+/// The text of the content without its tags, with the whitespace collapsed
+fn text(content: &str) -> String {
+    let mut stripped = String::with_capacity(content.len());
+    let mut in_tag = false;
+    for c in content.chars() {
+        match c {
+            '<' => in_tag = true,
+            '>' if in_tag => {
+                in_tag = false;
+                stripped.push(' ');
+            }
+            _ if !in_tag => stripped.push(c),
+            _ => {}
+        }
+    }
+
+    unescape(&stripped)
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn unescape(value: &str) -> String {
+    let mut unescaped = String::with_capacity(value.len());
+    let mut rest = value;
+    while let Some(start) = rest.find('&') {
+        unescaped.push_str(&rest[..start]);
+        rest = &rest[start..];
+
+        let Some(end) = rest.find(';').filter(|end| *end <= 10) else {
+            unescaped.push('&');
+            rest = &rest[1..];
+            continue;
+        };
+
+        let entity = &rest[1..end];
+        let c = match entity {
+            "amp" => Some('&'),
+            "lt" => Some('<'),
+            "gt" => Some('>'),
+            "quot" => Some('"'),
+            "apos" => Some('\''),
+            _ => entity
+                .strip_prefix("#x")
+                .map(|hex| u32::from_str_radix(hex, 16))
+                .or_else(|| entity.strip_prefix('#').map(str::parse))
+                .and_then(Result::ok)
+                .and_then(char::from_u32),
+        };
+
+        match c {
+            Some(c) => {
+                unescaped.push(c);
+                rest = &rest[end + 1..];
+            }
+            None => {
+                unescaped.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    unescaped.push_str(rest);
+    unescaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_read_package_document() {
+        let opf = r#"<?xml version="1.0"?>
+            <package xmlns="http://www.idpf.org/2007/opf" version="2.0">
+              <metadata xmlns:dc="http://purl.org/dc/elements/1.1/">
+                <dc:title>Tom &amp; Jerry</dc:title>
+                <dc:creator opf:role="aut">Author</dc:creator>
+                <dc:description>&lt;p&gt;One&lt;/p&gt;&lt;p&gt;Two&lt;/p&gt;</dc:description>
+                <dc:language>en</dc:language>
+                <dc:source>https://example.com/novel</dc:source>
+                <dc:subject>Fantasy</dc:subject>
+                <meta name="cover" content="cover-image"/>
+                <meta name="quelle:status" content="Completed"/>
+              </metadata>
+              <manifest>
+                <item id="cover-image" href="cover.png" media-type="image/png"/>
+                <item id="ncx" href="toc.ncx" media-type="application/x-dtbncx+xml"/>
+                <item id="preface" href="preface.xhtml" media-type="application/xhtml+xml"/>
+                <item id="c1" href="chapters/My%20Chapter.xhtml" media-type="application/xhtml+xml"/>
+              </manifest>
+              <spine toc="ncx">
+                <itemref idref="preface"/>
+                <itemref idref="c1"/>
+              </spine>
+              <guide>
+                <reference type="preface" title="Preface" href="preface.xhtml"/>
+              </guide>
+            </package>"#;
+
+        let package = Package::parse(opf, "OEBPS/");
+        assert_eq!(
+            package.spine,
+            vec!["OEBPS/preface.xhtml", "OEBPS/chapters/My Chapter.xhtml"]
+        );
+        assert!(package.skipped.contains("OEBPS/preface.xhtml"));
+        assert_eq!(package.toc.as_deref(), Some("OEBPS/toc.ncx"));
+        assert_eq!(
+            package.cover,
+            Some((String::from("OEBPS/cover.png"), String::from("image/png")))
+        );
+
+        let novel = package.novel();
+        assert_eq!(novel.title, "Tom & Jerry");
+        assert_eq!(novel.url, "https://example.com/novel");
+        assert_eq!(novel.authors, vec!["Author"]);
+        assert_eq!(novel.description, vec!["One", "Two"]);
+        assert_eq!(novel.langs, vec!["en"]);
+        assert!(matches!(novel.status, NovelStatus::Completed));
+        assert_eq!(novel.metadata.len(), 1);
+        assert_eq!(novel.metadata[0].value, "Fantasy");
+    }
+
+    #[test]
+    fn should_split_leading_heading() {
+        let (heading, content) = split_heading("\n<h1>Chapter <em>1</em></h1><p>Text</p>");
+        assert_eq!(heading.as_deref(), Some("Chapter 1"));
+        assert_eq!(content, "<p>Text</p>");
+        assert_eq!(split_heading("<p>Text</p>"), (None, "<p>Text</p>"));
+    }
+}
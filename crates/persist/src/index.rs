@@ -0,0 +1,223 @@
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    create_parent_all,
+    encryption::{self, Cipher},
+    error::PersistResult,
+    stats::NovelStats,
+    Persist, PersistNovel, SavedNovel,
+};
+
+/// What a library listing needs to know about a novel, kept in the library
+/// index so that listing does not read every novel
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct NovelSummary {
+    pub dir: PathBuf,
+    pub title: String,
+    pub authors: Vec<String>,
+    pub langs: Vec<String>,
+    pub tags: Vec<String>,
+    /// The status of the novel as written by its debug format, ex: `Ongoing`
+    pub status: String,
+    pub updated_at: DateTime<Utc>,
+    pub pending: usize,
+    pub stats: NovelStats,
+    /// When the novel data was last written, to tell if the summary is outdated
+    modified: Option<SystemTime>,
+}
+
+impl NovelSummary {
+    fn new(novel: &PersistNovel, data: &SavedNovel) -> Self {
+        Self {
+            dir: novel.dir().to_path_buf(),
+            title: data.title().to_string(),
+            authors: data.authors().to_vec(),
+            langs: data.novel.langs.clone(),
+            tags: data.tags().map(ToString::to_string).collect(),
+            status: format!("{:?}", data.novel.status),
+            updated_at: data.updated_at,
+            pending: data.pending_chapters(),
+            stats: data.stats(),
+            modified: modified(&novel.data_path()),
+        }
+    }
+
+    fn is_current(&self, novel: &PersistNovel) -> bool {
+        self.dir == novel.dir()
+            && self.modified.is_some()
+            && self.modified == modified(&novel.data_path())
+    }
+}
+
+/// The progress of rebuilding the library index
+#[derive(Debug)]
+pub struct IndexProgress<'a> {
+    pub url: &'a str,
+    pub number: usize,
+    pub total: usize,
+}
+
+/// The summaries of the novels keyed by url, see [`Persist::rebuild_index`]
+///
+/// The index of an encrypted library is encrypted, as the summaries reveal
+/// the novels.
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct LibraryIndex {
+    pub built_at: Option<DateTime<Utc>>,
+    pub novels: BTreeMap<String, NovelSummary>,
+}
+
+impl LibraryIndex {
+    pub fn open(path: &Path, cipher: Option<&Cipher>) -> PersistResult<Self> {
+        let index = if path.exists() {
+            serde_json::from_slice(&encryption::read_file(path, cipher)?)?
+        } else {
+            Default::default()
+        };
+
+        Ok(index)
+    }
+
+    pub fn save(&self, path: &Path, cipher: Option<&Cipher>) -> PersistResult<()> {
+        create_parent_all(path)?;
+        encryption::write_file(path, &serde_json::to_vec(self)?, cipher)
+    }
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+}
+
+/// Summarize every novel and save the index, see [`Persist::rebuild_index`]
+pub(crate) fn rebuild(
+    persist: &Persist,
+    mut progress: impl FnMut(IndexProgress),
+) -> PersistResult<LibraryIndex> {
+    let global = persist.read_global()?;
+    let total = global.novels().count();
+
+    let mut index = LibraryIndex::default();
+    for (number, (url, dir)) in global.novels().enumerate() {
+        let novel = persist.persist_novel(dir.clone());
+        if let Some(data) = novel.read_data()? {
+            index
+                .novels
+                .insert(url.clone(), NovelSummary::new(&novel, &data));
+        }
+
+        progress(IndexProgress {
+            url,
+            number: number + 1,
+            total,
+        });
+    }
+
+    index.built_at = Some(Utc::now());
+    index.save(&persist.options.index_path, persist.cipher())?;
+    Ok(index)
+}
+
+/// The summary of every novel, only reading the novels changed since the
+/// index was built, see [`Persist::novel_summaries`]
+pub(crate) fn summaries(persist: &Persist) -> PersistResult<Vec<(String, NovelSummary)>> {
+    let mut index = LibraryIndex::open(&persist.options.index_path, persist.cipher())?;
+
+    let mut summaries = vec![];
+    for (url, dir) in persist.read_global()?.novels() {
+        let novel = persist.persist_novel(dir.clone());
+        let summary = match index.novels.remove(url) {
+            Some(summary) if summary.is_current(&novel) => summary,
+            _ => match novel.read_data()? {
+                Some(data) => NovelSummary::new(&novel, &data),
+                None => continue,
+            },
+        };
+        summaries.push((url.clone(), summary));
+    }
+
+    summaries.sort_by(|(a, _), (b, _)| a.cmp(b));
+    Ok(summaries)
+}
+
+#[cfg(test)]
+mod tests {
+    use quelle_core::prelude::Novel;
+
+    use super::*;
+    use crate::PersistOptions;
+
+    #[test]
+    fn should_summarize_changed_novels_again() {
+        let dir = tempfile::tempdir().unwrap();
+        let persist = Persist::new(PersistOptions::with_base_dir(dir.path().to_path_buf()));
+
+        let novel_dir = persist.options.novel.dir.join("source").join("novel");
+        let novel = persist.persist_novel(novel_dir.clone());
+        let mut data = SavedNovel::new(Novel {
+            title: String::from("Before"),
+            ..Default::default()
+        });
+        novel.write_data(&data).unwrap();
+
+        let url = String::from("https://example.com/novel");
+        let mut global = persist.read_global().unwrap();
+        global.insert_novel(url.clone(), novel_dir);
+        persist.save_global(&global).unwrap();
+
+        let mut events = vec![];
+        let index = rebuild(&persist, |progress| {
+            events.push((progress.number, progress.total))
+        })
+        .unwrap();
+        assert_eq!(events, vec![(1, 1)]);
+        assert_eq!(index.novels[&url].title, "Before");
+
+        // Changed without the index, ensuring the modification time differs
+        std::thread::sleep(std::time::Duration::from_millis(20));
+        data.novel.title = String::from("After");
+        novel.write_data(&data).unwrap();
+
+        let summaries = summaries(&persist).unwrap();
+        assert_eq!(summaries.len(), 1);
+        assert_eq!(summaries[0].1.title, "After");
+    }
+
+    #[test]
+    fn should_encrypt_index_of_encrypted_library() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut persist = Persist::new(PersistOptions::with_base_dir(dir.path().to_path_buf()));
+        persist.encrypt("secret").unwrap();
+
+        let novel_dir = persist.options.novel.dir.join("source").join("novel");
+        let data = SavedNovel::new(Novel {
+            title: String::from("Hidden"),
+            ..Default::default()
+        });
+        persist
+            .persist_novel(novel_dir.clone())
+            .write_data(&data)
+            .unwrap();
+
+        let url = String::from("https://example.com/novel");
+        let mut global = persist.read_global().unwrap();
+        global.insert_novel(url.clone(), novel_dir);
+        persist.save_global(&global).unwrap();
+
+        rebuild(&persist, |_| {}).unwrap();
+        let content = fs::read(&persist.options.index_path).unwrap();
+        assert!(encryption::is_encrypted(&content));
+
+        let index = LibraryIndex::open(&persist.options.index_path, persist.cipher()).unwrap();
+        assert_eq!(index.novels[&url].title, "Hidden");
+    }
+}
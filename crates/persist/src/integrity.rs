@@ -0,0 +1,194 @@
+use std::{
+    fmt::Display,
+    path::{Path, PathBuf},
+};
+
+use sha2::{Digest, Sha256};
+
+use crate::{
+    error::PersistResult, Collections, CredentialStore, Global, HostRegistry, LibraryConfig,
+    Persist, SourceStats,
+};
+
+/// The checksum recorded for the content of a chapter
+pub fn content_hash(content: &str) -> String {
+    format!("{:x}", Sha256::digest(content.as_bytes()))
+}
+
+/// The entries of the library found corrupted, see [`Persist::verify`]
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct IntegrityReport {
+    /// The number of downloaded chapters checked
+    pub chapters: usize,
+    /// The chapters compared with a recorded checksum, the ones saved before
+    /// checksums were recorded are only checked to be readable
+    pub checksums: usize,
+    pub corrupted: Vec<CorruptEntry>,
+}
+
+impl IntegrityReport {
+    pub fn is_empty(&self) -> bool {
+        self.corrupted.is_empty()
+    }
+
+    fn push(&mut self, path: PathBuf, kind: Corruption) {
+        self.corrupted.push(CorruptEntry { path, kind });
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CorruptEntry {
+    pub path: PathBuf,
+    pub kind: Corruption,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Corruption {
+    /// A downloaded chapter whose file is missing
+    Missing,
+    /// The file cannot be decompressed or decrypted
+    Unreadable(String),
+    /// The content differs from the checksum recorded when it was saved
+    ChecksumMismatch,
+    /// The metadata file is not valid JSON or does not match its format
+    InvalidMetadata(String),
+}
+
+impl Display for Corruption {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Corruption::Missing => write!(f, "missing"),
+            Corruption::Unreadable(reason) => write!(f, "unreadable: {reason}"),
+            Corruption::ChecksumMismatch => write!(f, "checksum mismatch"),
+            Corruption::InvalidMetadata(reason) => write!(f, "invalid metadata: {reason}"),
+        }
+    }
+}
+
+/// Check the metadata files and the content of every chapter, see [`Persist::verify`]
+pub(crate) fn verify(persist: &Persist) -> PersistResult<IntegrityReport> {
+    let mut report = IntegrityReport::default();
+    let options = &persist.options;
+
+    check_metadata(&mut report, &options.hosts_path, HostRegistry::open);
+    check_metadata(&mut report, &options.sources_path, SourceStats::open);
+    check_metadata(
+        &mut report,
+        &options.credentials_path,
+        CredentialStore::open,
+    );
+    check_metadata(&mut report, &options.collections_path, Collections::open);
+    check_metadata(&mut report, &options.config_path, LibraryConfig::open);
+
+    // Without the global data the novels cannot be found
+    let Some(global) = check_metadata(&mut report, &options.global_path, Global::open) else {
+        return Ok(report);
+    };
+
+    for (_, dir) in global.novels() {
+        let novel = persist.persist_novel(dir.clone());
+        let data = match novel.read_data() {
+            Ok(Some(data)) => data,
+            Ok(None) => continue,
+            Err(e) => {
+                report.push(
+                    novel.data_path(),
+                    Corruption::InvalidMetadata(e.to_string()),
+                );
+                continue;
+            }
+        };
+
+        for (url, path) in &data.downloaded {
+            report.chapters += 1;
+            let path = dir.join(path);
+            if !path.exists() {
+                report.push(path, Corruption::Missing);
+                continue;
+            }
+
+            let content = match novel.read_chapter(&path) {
+                Ok(content) => content,
+                Err(e) => {
+                    report.push(path, Corruption::Unreadable(e.to_string()));
+                    continue;
+                }
+            };
+
+            if let Some(hash) = data.chapter_hashes.get(url) {
+                report.checksums += 1;
+                if *hash != content_hash(&content) {
+                    report.push(path, Corruption::ChecksumMismatch);
+                }
+            }
+        }
+    }
+
+    // The cached content is named after its checksum
+    for path in persist.read_chapter_cache()?.corrupted()? {
+        report.push(path, Corruption::ChecksumMismatch);
+    }
+
+    Ok(report)
+}
+
+/// Open the metadata file, reporting it when it cannot be read
+fn check_metadata<T>(
+    report: &mut IntegrityReport,
+    path: &Path,
+    open: impl Fn(&Path) -> PersistResult<T>,
+) -> Option<T> {
+    match open(path) {
+        Ok(value) => Some(value),
+        Err(e) => {
+            let kind = Corruption::InvalidMetadata(e.to_string());
+            report.push(path.to_path_buf(), kind);
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use quelle_core::prelude::Novel;
+
+    use super::*;
+    use crate::{write_content, Compression, PersistOptions, SavedNovel};
+
+    #[test]
+    fn should_report_corrupted_entries() {
+        let dir = tempfile::tempdir().unwrap();
+        let persist = Persist::new(PersistOptions::with_base_dir(dir.path().to_path_buf()));
+
+        let novel_dir = persist.options.novel.dir.join("source").join("novel");
+        let novel = persist.persist_novel(novel_dir.clone());
+        fs::create_dir_all(novel.chapters_dir()).unwrap();
+
+        let mut data = SavedNovel::new(Novel::default());
+        for (index, content) in ["<p>intact</p>", "<p>changed</p>"].into_iter().enumerate() {
+            let path = novel.chapters_dir().join(format!("{index}.html"));
+            let path = write_content(path, content, Compression::None, None).unwrap();
+
+            let url = format!("https://example.com/{index}");
+            data.chapter_hashes
+                .insert(url.clone(), content_hash("<p>intact</p>"));
+            data.downloaded.insert(url, novel.relative_path(path));
+        }
+        novel.write_data(&data).unwrap();
+
+        let mut global = persist.read_global().unwrap();
+        global.insert_novel(String::from("https://example.com/novel"), novel_dir);
+        persist.save_global(&global).unwrap();
+        fs::write(&persist.options.collections_path, "{ not json").unwrap();
+
+        let report = verify(&persist).unwrap();
+        assert_eq!((report.chapters, report.checksums), (2, 2));
+
+        let mut kinds = report.corrupted.into_iter().map(|entry| entry.kind);
+        assert!(matches!(kinds.next(), Some(Corruption::InvalidMetadata(_))));
+        assert_eq!(kinds.next(), Some(Corruption::ChecksumMismatch));
+        assert_eq!(kinds.next(), None);
+    }
+}
@@ -1,15 +1,89 @@
+mod asset;
+mod backup;
+mod batch;
+mod cache;
+mod chapter_state;
+mod cleanup;
+mod collections;
+mod compression;
+mod config;
+mod credentials;
+mod dedup;
+mod dump;
+mod encryption;
 mod error;
 mod event;
+mod exports;
 mod file;
 mod global;
+mod hooks;
+mod hosts;
+mod import;
+mod index;
+mod integrity;
+mod libraries;
+mod lock;
+mod maintenance;
+mod merge;
+mod migration;
+mod mirrors;
 mod novel;
+mod opf;
 mod options;
+mod overrides;
 mod persist;
+mod provenance;
+mod remote;
+mod s3;
+mod sources;
+mod stats;
+mod transfer;
+mod trash;
+mod versions;
+mod webdav;
 
+pub use asset::{Asset, AssetStore};
+pub use backup::{BackupManifest, ConflictStrategy, RestoreReport, BACKUP_VERSION};
+pub use batch::Batch;
+pub use cache::ChapterCache;
+pub use chapter_state::{ChapterState, ChapterStatus};
+pub use cleanup::CleanupReport;
+pub use collections::Collections;
+pub use compression::{read_content, write_content, Compression};
+pub use config::{
+    BundleProfile, CalibreConfig, ExecutorConfig, LibraryConfig, SanitizeConfig, SourceSettings,
+    Task,
+};
+pub use credentials::{Credential, CredentialStore};
+pub use dedup::DedupReport;
+pub use dump::{LibraryDump, LoadReport, NovelDump, DUMP_VERSION};
+pub use encryption::{read_file, write_file, Cipher, EncryptionConfig};
 pub use error::PersistError;
 pub use event::{Event, EventKind, EventLog};
+pub use exports::{ExportLog, ExportRecord};
 pub use file::create_parent_all;
 pub use global::Global;
+pub use hooks::StorageEvent;
+pub use hosts::{HostRegistry, HostStatus};
+pub use import::{ImportedNovel, IMPORT_SOURCE};
+pub use index::{IndexProgress, LibraryIndex, NovelSummary};
+pub use integrity::{content_hash, CorruptEntry, Corruption, IntegrityReport};
+pub use libraries::{LibraryManager, DEFAULT_LIBRARY};
+pub use lock::LockMode;
+pub use maintenance::{MaintenanceConfig, MaintenanceTask, TaskSummary};
+pub use merge::MergeReport;
+pub use migration::{MigrationReport, SCHEMA_VERSION};
 pub use novel::{CoverLoc, PersistNovel, SavedNovel};
+pub use opf::to_opf;
 pub use options::PersistOptions;
+pub use overrides::NovelOverrides;
 pub use persist::Persist;
+pub use provenance::{Provenance, NOVEL_FIELDS};
+pub use remote::{DirStore, MemoryStore, ObjectStore, ObjectStoreStorage, SyncReport};
+pub use s3::{RemoteConfig, S3Store};
+pub use sources::{Executor, ExecutorStats, SourceStats};
+pub use stats::{count_words, NovelStats, StorageStats, WORDS_PER_MINUTE};
+pub use transfer::{TransferEvent, TransferReport};
+pub use trash::{Trash, TrashedNovel};
+pub use versions::{diff_lines, text_lines, ChapterVersion, DiffLine};
+pub use webdav::{WebDavConfig, WebDavStore};
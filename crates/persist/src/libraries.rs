@@ -0,0 +1,156 @@
+use std::{
+    collections::BTreeMap,
+    fs::{File, OpenOptions},
+    io::{BufReader, BufWriter},
+    path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{create_parent_all, error::PersistResult, PersistOptions};
+
+/// The name of the library stored in the default directory
+pub const DEFAULT_LIBRARY: &str = "main";
+
+/// The named libraries the user switches between, such as "main" or "archive"
+///
+/// The default library always exists and is stored in the directory of
+/// [`PersistOptions::default`] unless it was given another one.
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct LibraryManager {
+    /// The library used when none is given, the default one if unset
+    #[serde(default)]
+    active: Option<String>,
+    #[serde(default)]
+    libraries: BTreeMap<String, PathBuf>,
+}
+
+impl LibraryManager {
+    pub fn open(path: &Path) -> PersistResult<Self> {
+        let data = if path.exists() {
+            let file = File::open(path)?;
+            let reader = BufReader::new(file);
+            serde_json::from_reader(reader)?
+        } else {
+            Default::default()
+        };
+
+        Ok(data)
+    }
+
+    pub fn save(&self, path: &Path) -> PersistResult<()> {
+        create_parent_all(path)?;
+
+        let file = OpenOptions::new()
+            .create(true)
+            .truncate(true)
+            .write(true)
+            .open(path)?;
+
+        let writer = BufWriter::new(file);
+        serde_json::to_writer_pretty(writer, self)?;
+
+        Ok(())
+    }
+
+    /// Store the library with the name in the directory, returning false if
+    /// the name is taken
+    pub fn add(&mut self, name: &str, dir: PathBuf) -> bool {
+        if self.dir(name).is_some() {
+            return false;
+        }
+
+        self.libraries.insert(name.to_string(), dir);
+        true
+    }
+
+    /// Forget the library, leaving its files in place
+    ///
+    /// The default library cannot be removed. The default library becomes
+    /// active again when the active one is removed.
+    pub fn remove(&mut self, name: &str) -> Option<PathBuf> {
+        if name == DEFAULT_LIBRARY {
+            return None;
+        }
+
+        let dir = self.libraries.remove(name)?;
+        if self.active.as_deref() == Some(name) {
+            self.active = None;
+        }
+        Some(dir)
+    }
+
+    /// Use the library when none is given, returning false if it does not exist
+    pub fn switch(&mut self, name: &str) -> bool {
+        if self.dir(name).is_none() {
+            return false;
+        }
+
+        self.active = (name != DEFAULT_LIBRARY).then(|| name.to_string());
+        true
+    }
+
+    /// The name of the library used when none is given
+    pub fn active(&self) -> &str {
+        self.active.as_deref().unwrap_or(DEFAULT_LIBRARY)
+    }
+
+    /// The directory of the library with the name
+    pub fn dir(&self, name: &str) -> Option<PathBuf> {
+        match self.libraries.get(name) {
+            Some(dir) => Some(dir.clone()),
+            None if name == DEFAULT_LIBRARY => Some(PersistOptions::default().base_dir),
+            None => None,
+        }
+    }
+
+    /// The options of the library with the name, or of the active one
+    pub fn options(&self, name: Option<&str>) -> Option<PersistOptions> {
+        let dir = self.dir(name.unwrap_or_else(|| self.active()))?;
+        Some(PersistOptions::with_base_dir(dir))
+    }
+
+    /// The name and directory of every library ordered by name
+    pub fn libraries(&self) -> Vec<(String, PathBuf)> {
+        let mut libraries = self
+            .libraries
+            .iter()
+            .map(|(name, dir)| (name.clone(), dir.clone()))
+            .collect::<BTreeMap<_, _>>();
+
+        libraries
+            .entry(DEFAULT_LIBRARY.to_string())
+            .or_insert_with(|| PersistOptions::default().base_dir);
+
+        libraries.into_iter().collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_switch_between_libraries() {
+        let mut manager = LibraryManager::default();
+        assert_eq!(manager.active(), DEFAULT_LIBRARY);
+        assert!(!manager.add(DEFAULT_LIBRARY, PathBuf::from("other")));
+
+        assert!(manager.add("archive", PathBuf::from("archive")));
+        assert!(!manager.switch("fanfic"));
+        assert!(manager.switch("archive"));
+        assert_eq!(
+            manager.options(None).unwrap().base_dir,
+            PathBuf::from("archive")
+        );
+        assert_eq!(
+            manager.options(Some(DEFAULT_LIBRARY)).unwrap().base_dir,
+            PathBuf::from("data")
+        );
+
+        assert_eq!(manager.remove("archive"), Some(PathBuf::from("archive")));
+        assert_eq!(manager.active(), DEFAULT_LIBRARY);
+        assert!(manager.remove(DEFAULT_LIBRARY).is_none());
+        assert_eq!(manager.libraries().len(), 1);
+    }
+}
@@ -0,0 +1,98 @@
+use std::{
+    fs::{File, OpenOptions},
+    io::ErrorKind,
+    path::Path,
+};
+
+use fs2::FileExt;
+
+use crate::{
+    create_parent_all,
+    error::{PersistError, PersistResult},
+};
+
+/// How the library is locked against other processes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LockMode {
+    /// Other processes may read the library but not write to it
+    Shared,
+    /// No other process may use the library
+    Exclusive,
+}
+
+/// An advisory lock on the library, released when dropped
+///
+/// Processes that read the library share the lock, while a process that
+/// writes to it holds it alone, so that a background update and a command
+/// run by the user never write to the same files.
+#[derive(Debug)]
+pub(crate) struct LibraryLock {
+    file: File,
+}
+
+impl LibraryLock {
+    /// Lock the file, waiting for other processes to release it or failing
+    /// with [`PersistError::Locked`] right away
+    pub fn acquire(path: &Path, mode: LockMode, wait: bool) -> PersistResult<Self> {
+        create_parent_all(path)?;
+        let file = OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(path)?;
+
+        // Called through the trait, as the standard library has methods of the same name
+        let result = match (mode, wait) {
+            (LockMode::Shared, true) => FileExt::lock_shared(&file),
+            (LockMode::Shared, false) => FileExt::try_lock_shared(&file),
+            (LockMode::Exclusive, true) => FileExt::lock_exclusive(&file),
+            (LockMode::Exclusive, false) => FileExt::try_lock_exclusive(&file),
+        };
+
+        match result {
+            Ok(()) => Ok(Self { file }),
+            Err(e) if e.kind() == ErrorKind::WouldBlock || is_contended(&e) => {
+                Err(PersistError::Locked(path.to_path_buf()))
+            }
+            Err(e) => Err(e.into()),
+        }
+    }
+}
+
+impl Drop for LibraryLock {
+    fn drop(&mut self) {
+        let _ = FileExt::unlock(&self.file);
+    }
+}
+
+/// Whether the error is the one returned by a lock held by another process
+fn is_contended(error: &std::io::Error) -> bool {
+    error.raw_os_error() == fs2::lock_contended_error().raw_os_error()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_fail_fast_when_locked_by_writer() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("library.lock");
+
+        let first = LibraryLock::acquire(&path, LockMode::Shared, false).unwrap();
+        let second = LibraryLock::acquire(&path, LockMode::Shared, false).unwrap();
+        assert!(matches!(
+            LibraryLock::acquire(&path, LockMode::Exclusive, false),
+            Err(PersistError::Locked(_))
+        ));
+
+        drop((first, second));
+        let writer = LibraryLock::acquire(&path, LockMode::Exclusive, false).unwrap();
+        assert!(matches!(
+            LibraryLock::acquire(&path, LockMode::Shared, false),
+            Err(PersistError::Locked(_))
+        ));
+
+        drop(writer);
+    }
+}
@@ -0,0 +1,242 @@
+use std::{collections::BTreeSet, fs, path::PathBuf, str::FromStr};
+
+use chrono::{DateTime, Duration, Local, Timelike, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::{dedup::DedupReport, error::PersistResult, IntegrityReport, Persist};
+
+/// The shortest time between two maintenance runs, so that it runs once a night
+const MIN_INTERVAL_HOURS: i64 = 12;
+
+/// A housekeeping task run during the maintenance of the library
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "snake_case")]
+pub enum MaintenanceTask {
+    /// Remove the cached chapter content no url refers to
+    PruneCache,
+    /// Purge the trash, recompress the chapters and store identical chapters once
+    Compact,
+    /// Check the metadata files and the content of every downloaded chapter
+    Verify,
+    /// Regenerate the extension lock, run by the client which knows the extensions
+    RefreshManifest,
+    /// Summarize the novels into the index used when listing the library
+    RebuildIndex,
+    /// Pack the library into a backup, keeping the most recent ones
+    Backup,
+}
+
+impl MaintenanceTask {
+    /// Every task, in the order they are run
+    pub const ALL: [MaintenanceTask; 6] = [
+        MaintenanceTask::PruneCache,
+        MaintenanceTask::Compact,
+        MaintenanceTask::Verify,
+        MaintenanceTask::RefreshManifest,
+        MaintenanceTask::RebuildIndex,
+        MaintenanceTask::Backup,
+    ];
+}
+
+impl FromStr for MaintenanceTask {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().replace('-', "_").as_str() {
+            "prune_cache" => Ok(MaintenanceTask::PruneCache),
+            "compact" => Ok(MaintenanceTask::Compact),
+            "verify" => Ok(MaintenanceTask::Verify),
+            "refresh_manifest" => Ok(MaintenanceTask::RefreshManifest),
+            "rebuild_index" => Ok(MaintenanceTask::RebuildIndex),
+            "backup" => Ok(MaintenanceTask::Backup),
+            _ => Err(format!("unsupported maintenance task '{s}'")),
+        }
+    }
+}
+
+impl std::fmt::Display for MaintenanceTask {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let value = match self {
+            MaintenanceTask::PruneCache => "prune-cache",
+            MaintenanceTask::Compact => "compact",
+            MaintenanceTask::Verify => "verify",
+            MaintenanceTask::RefreshManifest => "refresh-manifest",
+            MaintenanceTask::RebuildIndex => "rebuild-index",
+            MaintenanceTask::Backup => "backup",
+        };
+        write!(f, "{value}")
+    }
+}
+
+/// When the maintenance runs and which tasks it runs
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct MaintenanceConfig {
+    /// The local hour from which the maintenance may run, 2 by default
+    #[serde(default = "default_start_hour")]
+    pub start_hour: u32,
+    /// The local hour the maintenance must not start after, 6 by default
+    #[serde(default = "default_end_hour")]
+    pub end_hour: u32,
+    /// The tasks that are not run
+    #[serde(default)]
+    pub disabled: BTreeSet<MaintenanceTask>,
+    /// The number of backups kept by the backup task, 3 by default
+    #[serde(default = "default_backups_kept")]
+    pub backups_kept: usize,
+    /// When the maintenance last ran
+    #[serde(default)]
+    pub last_run: Option<DateTime<Utc>>,
+}
+
+fn default_start_hour() -> u32 {
+    2
+}
+
+fn default_end_hour() -> u32 {
+    6
+}
+
+fn default_backups_kept() -> usize {
+    3
+}
+
+impl Default for MaintenanceConfig {
+    fn default() -> Self {
+        Self {
+            start_hour: default_start_hour(),
+            end_hour: default_end_hour(),
+            disabled: BTreeSet::new(),
+            backups_kept: default_backups_kept(),
+            last_run: None,
+        }
+    }
+}
+
+impl MaintenanceConfig {
+    pub fn is_enabled(&self, task: MaintenanceTask) -> bool {
+        !self.disabled.contains(&task)
+    }
+
+    /// Whether the hour is within the maintenance hours, which may span midnight
+    pub fn in_window(&self, hour: u32) -> bool {
+        if self.start_hour <= self.end_hour {
+            (self.start_hour..self.end_hour).contains(&hour)
+        } else {
+            hour >= self.start_hour || hour < self.end_hour
+        }
+    }
+
+    /// Whether the maintenance should run now, as it is within the maintenance
+    /// hours and did not already run tonight
+    pub fn is_due(&self, now: DateTime<Local>) -> bool {
+        let ran_recently = self.last_run.is_some_and(|last_run| {
+            now.with_timezone(&Utc) - last_run < Duration::hours(MIN_INTERVAL_HOURS)
+        });
+
+        self.in_window(now.hour()) && !ran_recently
+    }
+}
+
+/// What a maintenance task did, see [`Persist::maintain`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TaskSummary {
+    CachePruned {
+        removed: usize,
+    },
+    Compacted {
+        purged: usize,
+        recompressed: usize,
+        dedup: DedupReport,
+    },
+    Verified(IntegrityReport),
+    IndexRebuilt {
+        novels: usize,
+    },
+    BackedUp {
+        path: PathBuf,
+        novels: usize,
+        /// The older backups removed
+        removed: usize,
+    },
+}
+
+/// Run the task, returning nothing for the tasks run by the client
+pub(crate) fn run(persist: &Persist, task: MaintenanceTask) -> PersistResult<Option<TaskSummary>> {
+    let config = persist.read_config()?;
+
+    let summary = match task {
+        MaintenanceTask::PruneCache => TaskSummary::CachePruned {
+            removed: persist.read_chapter_cache()?.prune()?,
+        },
+        MaintenanceTask::Compact => TaskSummary::Compacted {
+            purged: persist.purge_trash(config.trash_retention())?.len(),
+            recompressed: persist.recompress(config.compression)?,
+            dedup: persist.deduplicate(false)?,
+        },
+        MaintenanceTask::Verify => TaskSummary::Verified(persist.verify()?),
+        MaintenanceTask::RefreshManifest => return Ok(None),
+        MaintenanceTask::RebuildIndex => TaskSummary::IndexRebuilt {
+            novels: persist.rebuild_index(|_| {})?.novels.len(),
+        },
+        MaintenanceTask::Backup => backup(persist, config.maintenance.backups_kept)?,
+    };
+
+    Ok(Some(summary))
+}
+
+fn backup(persist: &Persist, kept: usize) -> PersistResult<TaskSummary> {
+    let dir = &persist.options.backups_dir;
+    let path = dir.join(format!(
+        "library-{}.tar.zst",
+        Utc::now().format("%Y%m%d-%H%M%S")
+    ));
+    let manifest = persist.export_backup(&path)?;
+
+    // The names sort by date, oldest first
+    let mut backups = fs::read_dir(dir)?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| {
+            path.file_name()
+                .and_then(|name| name.to_str())
+                .is_some_and(|name| name.starts_with("library-") && name.ends_with(".tar.zst"))
+        })
+        .collect::<Vec<_>>();
+    backups.sort();
+
+    let removed = backups.len().saturating_sub(kept.max(1));
+    for old in &backups[..removed] {
+        fs::remove_file(old)?;
+    }
+
+    Ok(TaskSummary::BackedUp {
+        path,
+        novels: manifest.novels,
+        removed,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+
+    use super::*;
+
+    #[test]
+    fn should_run_within_hours_once_a_night() {
+        let mut config = MaintenanceConfig {
+            start_hour: 23,
+            end_hour: 4,
+            ..Default::default()
+        };
+        assert!(config.in_window(23) && config.in_window(0) && config.in_window(3));
+        assert!(!config.in_window(4) && !config.in_window(12));
+
+        let night = Local.with_ymd_and_hms(2024, 5, 1, 1, 0, 0).unwrap();
+        assert!(config.is_due(night));
+
+        config.last_run = Some((night - Duration::hours(1)).with_timezone(&Utc));
+        assert!(!config.is_due(night));
+        assert!(!config.is_due(night + Duration::hours(12)));
+        assert!(config.is_due(night + Duration::days(1)));
+    }
+}
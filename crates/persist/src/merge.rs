@@ -0,0 +1,326 @@
+use std::fs;
+
+use quelle_core::prelude::Novel;
+
+use crate::{
+    compression::Compression,
+    error::{PersistError, PersistResult},
+    mirrors::match_chapters,
+    trash::{self, TrashedNovel},
+    CoverLoc, Persist, PersistNovel, SavedNovel,
+};
+
+/// What the duplicate added to the novel it was merged into, see [`Persist::merge_novels`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MergeReport {
+    /// The chapters only downloaded in the duplicate
+    pub chapters: usize,
+    pub assets: usize,
+    /// The duplicate, moved into the trash
+    pub duplicate: TrashedNovel,
+}
+
+/// Combine the duplicate into the primary novel, see [`Persist::merge_novels`]
+pub(crate) fn merge_novels(
+    persist: &Persist,
+    primary: &str,
+    duplicate: &str,
+) -> PersistResult<Option<MergeReport>> {
+    let global = persist.read_global()?;
+    let (Some(primary), Some(duplicate)) =
+        (global.primary_url(primary), global.primary_url(duplicate))
+    else {
+        return Ok(None);
+    };
+    if primary == duplicate {
+        return Err(PersistError::SameNovel(primary.to_string()));
+    }
+
+    let (primary, duplicate) = (primary.to_string(), duplicate.to_string());
+    let primary_dir = global.novel_path_from_url(&primary).unwrap().to_path_buf();
+    let duplicate_dir = global
+        .novel_path_from_url(&duplicate)
+        .unwrap()
+        .to_path_buf();
+
+    let primary_novel = persist.persist_novel(primary_dir);
+    let duplicate_novel = persist.persist_novel(duplicate_dir.clone());
+    let (Some(mut data), Some(other)) = (primary_novel.read_data()?, duplicate_novel.read_data()?)
+    else {
+        return Ok(None);
+    };
+
+    let chapters = merge_chapters(&primary_novel, &mut data, &duplicate_novel, &other)?;
+
+    let source = duplicate_novel.read_assets()?;
+    let mut assets = primary_novel.read_assets()?;
+    let mut copied = 0;
+    for asset in source.list_assets() {
+        if assets.get_asset(&asset.url)?.is_some() {
+            continue;
+        }
+        if let Some((_, content)) = source.get_asset(&asset.url)? {
+            assets.store_asset(&asset.url, &asset.content_type, &content)?;
+            copied += 1;
+        }
+    }
+    assets.save()?;
+
+    merge_metadata(&primary_novel, &mut data, other)?;
+    primary_novel.write_data(&data)?;
+
+    let collections = persist
+        .read_collections()?
+        .collections_of(&duplicate)
+        .cloned()
+        .collect::<Vec<_>>();
+
+    // Kept in the trash in case the novels were not the same work
+    let trashed =
+        trash::delete_novel(persist, &duplicate)?.expect("the duplicate is in the library");
+
+    let mut collections_data = persist.read_collections()?;
+    for name in collections {
+        collections_data.add_to_collection(&name, &primary);
+    }
+    persist.save_collections(&collections_data)?;
+
+    let mut global = persist.read_global()?;
+    for mirror in &data.mirrors {
+        global.insert_alias(mirror.clone(), primary.clone());
+    }
+    if let Some(id) = persist.novel_id(&duplicate_dir) {
+        global.insert_merged(id, primary.clone());
+    }
+    persist.save_global(&global)?;
+
+    Ok(Some(MergeReport {
+        chapters,
+        assets: copied,
+        duplicate: trashed,
+    }))
+}
+
+/// Save the chapters only downloaded in the duplicate into the novel,
+/// returning the number of chapters saved
+fn merge_chapters(
+    novel: &PersistNovel,
+    data: &mut SavedNovel,
+    duplicate: &PersistNovel,
+    other: &SavedNovel,
+) -> PersistResult<usize> {
+    let mut count = 0;
+    for (chapter, matched) in match_chapters(&data.novel, &other.novel) {
+        if data.downloaded.contains_key(&chapter.url) {
+            continue;
+        }
+        let Some(path) = other.downloaded.get(&matched.url) else {
+            continue;
+        };
+
+        let content = duplicate.read_chapter(path)?;
+        fs::create_dir_all(novel.chapters_dir())?;
+        let path = novel.save_chapter(chapter, content, Compression::of_path(path))?;
+
+        let url = chapter.url.clone();
+        data.downloaded
+            .insert(url.clone(), novel.relative_path(path));
+        data.chapter_states.remove(&url);
+        if let Some(value) = other.word_counts.get(&matched.url) {
+            data.word_counts.insert(url.clone(), *value);
+        }
+        if let Some(value) = other.chapter_langs.get(&matched.url) {
+            data.chapter_langs.insert(url.clone(), value.clone());
+        }
+        if let Some(value) = other.chapter_provenance.get(&matched.url) {
+            data.chapter_provenance.insert(url.clone(), value.clone());
+        }
+        if let Some(value) = other.chapter_hashes.get(&matched.url) {
+            data.chapter_hashes.insert(url, value.clone());
+        }
+        count += 1;
+    }
+
+    Ok(count)
+}
+
+/// Fill what the novel is missing with the metadata of the duplicate,
+/// keeping the duplicate urls as mirrors of the novel
+fn merge_metadata(
+    novel: &PersistNovel,
+    data: &mut SavedNovel,
+    other: SavedNovel,
+) -> PersistResult<()> {
+    let Novel {
+        url,
+        authors,
+        cover,
+        description,
+        metadata,
+        langs,
+        ..
+    } = other.novel;
+
+    if data.novel.authors.is_empty() {
+        data.novel.authors = authors;
+    }
+    if data.novel.description.is_empty() {
+        data.novel.description = description;
+    }
+    if data.novel.cover.is_none() {
+        data.novel.cover = cover;
+    }
+    for lang in langs {
+        if !data.novel.langs.contains(&lang) {
+            data.novel.langs.push(lang);
+        }
+    }
+    for entry in metadata {
+        let exists = data
+            .novel
+            .metadata
+            .iter()
+            .any(|value| value.name == entry.name && value.value == entry.value);
+        if !exists {
+            data.novel.metadata.push(entry);
+        }
+    }
+
+    if data.cover.is_none() {
+        data.cover = other
+            .cover
+            .map(|cover| copy_cover(novel, cover))
+            .transpose()?;
+    }
+
+    let overrides = &mut data.overrides;
+    overrides.title = overrides.title.take().or(other.overrides.title);
+    overrides.authors = overrides.authors.take().or(other.overrides.authors);
+    overrides.tags = overrides.tags.take().or(other.overrides.tags);
+    if overrides.cover.is_none() {
+        overrides.cover = other
+            .overrides
+            .cover
+            .map(|cover| copy_cover(novel, cover))
+            .transpose()?;
+    }
+
+    data.notes = match (data.notes.take(), other.notes) {
+        (Some(notes), Some(other)) if notes != other => Some(format!("{notes}\n\n{other}")),
+        (notes, other) => notes.or(other),
+    };
+    data.rights = data.rights.take().or(other.rights);
+    data.credential = data.credential.take().or(other.credential);
+    data.title_rules = data.title_rules.take().or(other.title_rules);
+
+    for mirror in other.mirrors.into_iter().chain([url]) {
+        if mirror != data.novel.url && !data.mirrors.contains(&mirror) {
+            data.mirrors.push(mirror);
+        }
+    }
+
+    Ok(())
+}
+
+/// Copy a cover of the duplicate into the novel directory, as the duplicate is trashed
+fn copy_cover(novel: &PersistNovel, cover: CoverLoc) -> PersistResult<CoverLoc> {
+    let Some(name) = cover.path.file_name() else {
+        return Ok(cover);
+    };
+    if !cover.path.exists() {
+        return Ok(cover);
+    }
+
+    let path = novel
+        .dir()
+        .join(format!("merged-{}", name.to_string_lossy()));
+    fs::copy(&cover.path, &path)?;
+    Ok(CoverLoc { path, ..cover })
+}
+
+#[cfg(test)]
+mod tests {
+    use quelle_core::prelude::{Chapter, Volume};
+
+    use super::*;
+    use crate::PersistOptions;
+
+    fn save_novel<'a>(persist: &'a Persist, url: &str, downloaded: &[u32]) -> PersistNovel<'a> {
+        let chapters = (1..=3)
+            .map(|number| Chapter {
+                index: number as i32 - 1,
+                title: format!("Chapter {number}"),
+                url: format!("{url}/{number}"),
+                updated_at: None,
+                number: Some(number),
+                part: None,
+                label: None,
+            })
+            .collect();
+        let mut data = SavedNovel::new(Novel {
+            url: url.to_string(),
+            volumes: vec![Volume {
+                index: 0,
+                name: String::from("Volume"),
+                chapters,
+            }],
+            ..Default::default()
+        });
+
+        let host = url.trim_start_matches("https://");
+        let dir = persist.options.novel.dir.join(host).join("novel");
+        let novel = persist.persist_novel(dir.clone());
+        fs::create_dir_all(novel.chapters_dir()).unwrap();
+        for number in downloaded {
+            let chapter = &data.novel.volumes[0].chapters[*number as usize - 1];
+            let content = format!("<p>{host} {number}</p>");
+            let path = novel
+                .save_chapter(chapter, content, Compression::None)
+                .unwrap();
+            data.downloaded
+                .insert(chapter.url.clone(), novel.relative_path(path));
+        }
+        novel.write_data(&data).unwrap();
+
+        let mut global = persist.read_global().unwrap();
+        global.insert_novel(url.to_string(), dir);
+        persist.save_global(&global).unwrap();
+        novel
+    }
+
+    #[test]
+    fn should_merge_duplicate_into_primary() {
+        let dir = tempfile::tempdir().unwrap();
+        let persist = Persist::new(PersistOptions::with_base_dir(dir.path().to_path_buf()));
+
+        let primary = save_novel(&persist, "https://primary.com", &[1]);
+        let duplicate = save_novel(&persist, "https://duplicate.com", &[1, 2]);
+        let mut other = duplicate.read_data().unwrap().unwrap();
+        other.novel.description = vec![String::from("A description")];
+        duplicate.write_data(&other).unwrap();
+        let duplicate_id = persist.novel_id(duplicate.dir()).unwrap();
+
+        let report = merge_novels(&persist, "https://primary.com", "https://duplicate.com")
+            .unwrap()
+            .unwrap();
+        assert_eq!(report.chapters, 1);
+        assert!(!duplicate.dir().exists());
+
+        let data = primary.read_data().unwrap().unwrap();
+        let content = |url: &str| primary.read_chapter(&data.downloaded[url]).unwrap();
+        assert_eq!(content("https://primary.com/1"), "<p>primary.com 1</p>");
+        assert_eq!(content("https://primary.com/2"), "<p>duplicate.com 2</p>");
+        assert_eq!(data.novel.description, vec![String::from("A description")]);
+        assert_eq!(data.mirrors, vec![String::from("https://duplicate.com")]);
+
+        let global = persist.read_global().unwrap();
+        assert_eq!(
+            global.primary_url("https://duplicate.com"),
+            Some("https://primary.com")
+        );
+        assert_eq!(
+            persist.novel_dir(&duplicate_id).unwrap().as_deref(),
+            Some(primary.dir())
+        );
+    }
+}
@@ -0,0 +1,219 @@
+use std::{
+    fs::{File, OpenOptions},
+    io::{BufReader, BufWriter},
+};
+
+use log::info;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    create_parent_all,
+    error::{PersistError, PersistResult},
+    stats, Persist,
+};
+
+/// The version of the library layout written by this release
+pub const SCHEMA_VERSION: u32 = 3;
+
+/// A change to the layout of a library, bringing it to the version
+struct Migration {
+    version: u32,
+    description: &'static str,
+    run: fn(&Persist) -> PersistResult<()>,
+}
+
+/// Migrations in the order they are applied, one for every version
+const MIGRATIONS: [Migration; SCHEMA_VERSION as usize] = [
+    Migration {
+        version: 1,
+        description: "record the schema version of the library",
+        run: |_| Ok(()),
+    },
+    Migration {
+        version: 2,
+        description: "write the metadata file of novels saved before it existed",
+        run: write_missing_metadata,
+    },
+    Migration {
+        version: 3,
+        description: "count the words of chapters downloaded before word counts were kept",
+        run: count_missing_words,
+    },
+];
+
+#[derive(Serialize, Deserialize, Debug, Default)]
+struct Schema {
+    version: u32,
+}
+
+/// The migrations run, or that would be run, to bring a library up to date
+#[derive(Debug)]
+pub struct MigrationReport {
+    /// The version of the library before migrating
+    pub from: u32,
+    pub to: u32,
+    /// The version and description of every migration
+    pub migrations: Vec<(u32, &'static str)>,
+    /// Whether the migrations were only reported and not run
+    pub dry_run: bool,
+}
+
+impl MigrationReport {
+    pub fn is_empty(&self) -> bool {
+        self.migrations.is_empty()
+    }
+}
+
+/// Run the pending migrations of the library in order, or only report them
+///
+/// The version is saved after every migration so that an interrupted run
+/// continues where it stopped.
+pub fn migrate(persist: &Persist, dry_run: bool) -> PersistResult<MigrationReport> {
+    let from = if let Some(version) = read_version(persist)? {
+        version
+    } else if persist.options.global_path.exists() {
+        // Libraries created before versioning
+        0
+    } else {
+        // A new library already has the current layout
+        if !dry_run {
+            save_version(persist, SCHEMA_VERSION)?;
+        }
+        SCHEMA_VERSION
+    };
+
+    if from > SCHEMA_VERSION {
+        return Err(PersistError::UnsupportedSchema {
+            found: from,
+            supported: SCHEMA_VERSION,
+        });
+    }
+
+    let pending = MIGRATIONS
+        .iter()
+        .filter(|migration| migration.version > from)
+        .collect::<Vec<_>>();
+
+    if !dry_run {
+        for migration in &pending {
+            info!(
+                "Migrating library to version {}: {}.",
+                migration.version, migration.description
+            );
+            (migration.run)(persist)?;
+            save_version(persist, migration.version)?;
+        }
+    }
+
+    Ok(MigrationReport {
+        from,
+        to: SCHEMA_VERSION,
+        migrations: pending
+            .into_iter()
+            .map(|migration| (migration.version, migration.description))
+            .collect(),
+        dry_run,
+    })
+}
+
+/// The version the library was last migrated to, if it was ever versioned
+pub(crate) fn read_version(persist: &Persist) -> PersistResult<Option<u32>> {
+    let path = &persist.options.schema_path;
+    if !path.exists() {
+        return Ok(None);
+    }
+
+    let file = File::open(path)?;
+    let schema: Schema = serde_json::from_reader(BufReader::new(file))?;
+    Ok(Some(schema.version))
+}
+
+fn save_version(persist: &Persist, version: u32) -> PersistResult<()> {
+    let path = &persist.options.schema_path;
+    create_parent_all(path)?;
+
+    let file = OpenOptions::new()
+        .create(true)
+        .truncate(true)
+        .write(true)
+        .open(path)?;
+
+    let writer = BufWriter::new(file);
+    serde_json::to_writer(writer, &Schema { version })?;
+
+    Ok(())
+}
+
+fn write_missing_metadata(persist: &Persist) -> PersistResult<()> {
+    let global = persist.read_global()?;
+    for (_, dir) in global.novels() {
+        let novel = persist.persist_novel(dir.clone());
+        if novel.metadata_path().exists() {
+            continue;
+        }
+
+        if let Some(data) = novel.read_data()? {
+            novel.write_metadata(&data)?;
+        }
+    }
+
+    Ok(())
+}
+
+fn count_missing_words(persist: &Persist) -> PersistResult<()> {
+    let global = persist.read_global()?;
+    for (_, dir) in global.novels() {
+        let novel = persist.persist_novel(dir.clone());
+        let Some(mut data) = novel.read_data()? else {
+            continue;
+        };
+
+        if stats::count_missing_words(&novel, &mut data)? {
+            novel.write_data(&data)?;
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use quelle_core::prelude::Novel;
+
+    use super::*;
+    use crate::{PersistOptions, SavedNovel};
+
+    #[test]
+    fn should_migrate_unversioned_library() {
+        let dir = tempfile::tempdir().unwrap();
+        let persist = Persist::new(PersistOptions::with_base_dir(dir.path().to_path_buf()));
+
+        let novel_dir = persist.options.novel.dir.join("source").join("novel");
+        let novel = persist.persist_novel(novel_dir.clone());
+        novel
+            .write_data(&SavedNovel::new(Novel {
+                title: String::from("Novel"),
+                ..Default::default()
+            }))
+            .unwrap();
+        fs::remove_file(novel.metadata_path()).unwrap();
+
+        let mut global = persist.read_global().unwrap();
+        global.insert_novel(String::from("https://example.com/novel"), novel_dir);
+        persist.save_global(&global).unwrap();
+
+        let report = migrate(&persist, true).unwrap();
+        assert_eq!((report.from, report.migrations.len()), (0, 3));
+        assert!(!novel.metadata_path().exists());
+
+        let report = migrate(&persist, false).unwrap();
+        assert_eq!(report.migrations.len(), 3);
+        assert!(novel.metadata_path().exists());
+
+        let report = migrate(&persist, false).unwrap();
+        assert!(report.is_empty());
+        assert_eq!(report.from, SCHEMA_VERSION);
+    }
+}
@@ -0,0 +1,307 @@
+use std::{
+    collections::{HashMap, HashSet},
+    ffi::OsString,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use log::debug;
+use quelle_core::prelude::{Chapter, Novel};
+
+use crate::{compression::Compression, error::PersistResult, Persist, PersistNovel, SavedNovel};
+
+/// Record another url the novel can be downloaded from, see [`Persist::add_mirror`]
+pub(crate) fn add_mirror(persist: &Persist, url: &str, mirror: &str) -> PersistResult<bool> {
+    let mut global = persist.read_global()?;
+    let Some(primary) = global.primary_url(url).map(ToString::to_string) else {
+        return Ok(false);
+    };
+
+    let novel = persist.persist_novel(global.novel_path_from_url(&primary).unwrap().into());
+    let Some(mut data) = novel.read_data()? else {
+        return Ok(false);
+    };
+
+    if mirror != primary && !data.mirrors.iter().any(|value| value == mirror) {
+        data.mirrors.push(mirror.to_string());
+        novel.write_data(&data)?;
+    }
+
+    global.insert_alias(mirror.to_string(), primary);
+    persist.save_global(&global)?;
+    Ok(true)
+}
+
+/// Forget a mirror of the novel, see [`Persist::remove_mirror`]
+pub(crate) fn remove_mirror(persist: &Persist, mirror: &str) -> PersistResult<bool> {
+    let mut global = persist.read_global()?;
+    let Some(primary) = global.remove_alias(mirror) else {
+        return Ok(false);
+    };
+
+    if let Some(dir) = global.novel_path_from_url(&primary) {
+        let novel = persist.persist_novel(dir.into());
+        if let Some(mut data) = novel.read_data()? {
+            data.mirrors.retain(|value| value != mirror);
+            novel.write_data(&data)?;
+        }
+    }
+
+    persist.save_global(&global)?;
+    Ok(true)
+}
+
+/// Download the novel from another source from now on, see [`Persist::switch_source`]
+pub(crate) fn switch_source(
+    persist: &Persist,
+    url: &str,
+    novel: Novel,
+) -> PersistResult<Option<usize>> {
+    let mut global = persist.read_global()?;
+    let Some(primary) = global.primary_url(url).map(ToString::to_string) else {
+        return Ok(None);
+    };
+    let dir = global.novel_path_from_url(&primary).unwrap().to_path_buf();
+
+    let persist_novel = persist.persist_novel(dir.clone());
+    let Some(mut data) = persist_novel.read_data()? else {
+        return Ok(None);
+    };
+
+    let active = novel.url.clone();
+    let moves = data.switch_source(&persist_novel, novel);
+    let carried = moves.len();
+    move_all(&persist_novel, &mut data, moves)?;
+    persist_novel.write_data(&data)?;
+
+    // The previous url keeps leading to the novel as a mirror
+    global.remove_novel(&primary);
+    global.insert_novel(active.clone(), dir);
+    for mirror in &data.mirrors {
+        global.insert_alias(mirror.clone(), active.clone());
+    }
+    persist.save_global(&global)?;
+
+    let mut collections = persist.read_collections()?;
+    collections.rename_novel(&primary, &active);
+    persist.save_collections(&collections)?;
+
+    Ok(Some(carried))
+}
+
+/// A chapter file renamed after the chapter it matches in the new source
+struct Move {
+    url: String,
+    from: PathBuf,
+    to: PathBuf,
+    /// The previous versions of the chapter, when any were kept
+    versions: Option<(PathBuf, PathBuf)>,
+}
+
+impl SavedNovel {
+    /// Replace the novel with the one fetched from another source, carrying the
+    /// downloaded chapters over to the chapters of the same number, or at the same
+    /// position when the source does not number them
+    ///
+    /// The chapters without a match are kept downloaded under their previous url.
+    fn switch_source(&mut self, persist_novel: &PersistNovel, novel: Novel) -> Vec<Move> {
+        let previous = std::mem::replace(&mut self.novel, novel);
+
+        let mut moves = vec![];
+        for (chapter, matched) in match_chapters(&self.novel, &previous) {
+            let Some(path) = self.downloaded.remove(&matched.url) else {
+                continue;
+            };
+
+            let to = Compression::of_path(&path)
+                .apply_to(PathBuf::from("chapters").join(format!("{}.html", chapter.index)));
+            let versions = (
+                persist_novel.versions_dir(matched),
+                persist_novel.versions_dir(chapter),
+            );
+            moves.push(Move {
+                url: chapter.url.clone(),
+                from: path,
+                to: to.clone(),
+                versions: Some(versions).filter(|(from, _)| from.exists()),
+            });
+
+            let url = &chapter.url;
+            self.downloaded.insert(url.clone(), to);
+            rekey(&mut self.word_counts, &matched.url, url);
+            rekey(&mut self.chapter_langs, &matched.url, url);
+            rekey(&mut self.chapter_provenance, &matched.url, url);
+            rekey(&mut self.chapter_hashes, &matched.url, url);
+        }
+
+        // The failures were of the previous source
+        self.chapter_states.clear();
+
+        self.mirrors.retain(|mirror| *mirror != self.novel.url);
+        if !self.mirrors.contains(&previous.url) {
+            self.mirrors.push(previous.url);
+        }
+
+        moves
+    }
+}
+
+/// The chapters of the novel paired with the chapter of the same number in the
+/// other novel, or at the same position when the source does not number them
+pub(crate) fn match_chapters<'a>(
+    novel: &'a Novel,
+    other: &'a Novel,
+) -> Vec<(&'a Chapter, &'a Chapter)> {
+    let chapters = |novel: &'a Novel| novel.volumes.iter().flat_map(|volume| &volume.chapters);
+    let others = chapters(other).collect::<Vec<_>>();
+    let numbered = others
+        .iter()
+        .filter_map(|chapter| Some(((chapter.number?, chapter.part), *chapter)))
+        .collect::<HashMap<_, _>>();
+
+    chapters(novel)
+        .enumerate()
+        .filter_map(|(position, chapter)| {
+            let matched = match chapter.number {
+                Some(number) => numbered.get(&(number, chapter.part)).copied(),
+                None => others.get(position).copied(),
+            };
+            matched.map(|matched| (chapter, matched))
+        })
+        .collect()
+}
+
+fn previous(path: &Path) -> PathBuf {
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    path.with_file_name(format!("previous-{name}"))
+}
+
+fn rekey<T>(values: &mut HashMap<String, T>, from: &str, to: &str) {
+    if let Some(value) = values.remove(from) {
+        values.insert(to.to_string(), value);
+    }
+}
+
+/// Rename the files in two steps, as a file may take the name of another one,
+/// renaming the chapters without a match that are in the way
+fn move_all(novel: &PersistNovel, data: &mut SavedNovel, moves: Vec<Move>) -> PersistResult<()> {
+    let dir = novel.dir();
+    let staged = |path: &Path| {
+        let mut name = OsString::from(path.as_os_str());
+        name.push(".switch");
+        PathBuf::from(name)
+    };
+
+    for item in &moves {
+        fs::rename(dir.join(&item.from), staged(&dir.join(&item.from)))?;
+        if let Some((from, _)) = &item.versions {
+            fs::rename(from, staged(from))?;
+        }
+    }
+
+    let targets = moves.iter().map(|item| &item.to).collect::<HashSet<_>>();
+    for (url, path) in data.downloaded.iter_mut() {
+        if moves.iter().any(|item| item.url == *url) || !targets.contains(path) {
+            continue;
+        }
+
+        let renamed = previous(path);
+        fs::rename(dir.join(&*path), dir.join(&renamed))?;
+        debug!("Renamed the chapter of {url} to {}", renamed.display());
+        *path = renamed;
+    }
+
+    for item in &moves {
+        fs::rename(staged(&dir.join(&item.from)), dir.join(&item.to))?;
+        if let Some((from, to)) = &item.versions {
+            if to.exists() {
+                fs::rename(to, previous(to))?;
+            }
+            fs::rename(staged(from), to)?;
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use quelle_core::prelude::Volume;
+
+    use super::*;
+    use crate::PersistOptions;
+
+    fn novel(url: &str, numbers: &[u32]) -> Novel {
+        let chapters = numbers
+            .iter()
+            .enumerate()
+            .map(|(index, number)| Chapter {
+                index: index as i32,
+                title: format!("Chapter {number}"),
+                url: format!("{url}/{number}"),
+                updated_at: None,
+                number: Some(*number),
+                part: None,
+                label: None,
+            })
+            .collect();
+
+        Novel {
+            url: url.to_string(),
+            volumes: vec![Volume {
+                index: 0,
+                name: String::from("Volume"),
+                chapters,
+            }],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn should_carry_chapters_over_to_new_source() {
+        let dir = tempfile::tempdir().unwrap();
+        let persist = Persist::new(PersistOptions::with_base_dir(dir.path().to_path_buf()));
+
+        let old_url = "https://dead.com/novel";
+        let novel_dir = persist.options.novel.dir.join("dead").join("novel");
+        let persist_novel = persist.persist_novel(novel_dir.clone());
+        fs::create_dir_all(persist_novel.chapters_dir()).unwrap();
+        let mut data = SavedNovel::new(novel(old_url, &[1, 2, 3, 5]));
+        for index in [0, 2, 3] {
+            let chapter = &data.novel.volumes[0].chapters[index];
+            let content = format!("<p>{}</p>", chapter.title);
+            let path = persist_novel
+                .save_chapter(chapter, content, Compression::None)
+                .unwrap();
+            data.downloaded
+                .insert(chapter.url.clone(), persist_novel.relative_path(path));
+        }
+        persist_novel.write_data(&data).unwrap();
+
+        let mut global = persist.read_global().unwrap();
+        global.insert_novel(old_url.to_string(), novel_dir.clone());
+        persist.save_global(&global).unwrap();
+
+        // The new source has a prologue, shifting the chapters
+        let new_url = "https://mirror.com/novel";
+        let carried = switch_source(&persist, old_url, novel(new_url, &[0, 1, 2, 3]))
+            .unwrap()
+            .unwrap();
+        assert_eq!(carried, 2);
+
+        let data = persist_novel.read_data().unwrap().unwrap();
+        assert_eq!(data.mirrors, vec![old_url.to_string()]);
+        let content = |url: &str| persist_novel.read_chapter(&data.downloaded[url]).unwrap();
+        assert_eq!(content("https://mirror.com/novel/1"), "<p>Chapter 1</p>");
+        assert_eq!(content("https://mirror.com/novel/3"), "<p>Chapter 3</p>");
+        // Kept under its previous url, out of the way of the new chapter 3
+        assert_eq!(content("https://dead.com/novel/5"), "<p>Chapter 5</p>");
+
+        let global = persist.read_global().unwrap();
+        assert_eq!(global.primary_url(old_url), Some(new_url));
+        assert_eq!(
+            global.novel_path_from_url(old_url),
+            Some(novel_dir.as_path())
+        );
+    }
+}
@@ -1,15 +1,30 @@
 use std::{
-    collections::HashMap,
+    collections::{BTreeMap, HashMap},
     fs::{self, File, OpenOptions},
     io::{BufReader, BufWriter},
     path::{Path, PathBuf},
 };
 
 use chrono::{DateTime, Utc};
+use quelle_common::TitleRules;
 use quelle_core::prelude::{Chapter, Novel};
 use serde::{Deserialize, Serialize};
 
-use crate::{create_parent_all, error::PersistResult, event::EventLog, Event, EventKind, Persist};
+use crate::{
+    asset::AssetStore,
+    batch::{self, Batch},
+    chapter_state::ChapterState,
+    compression::{self, Compression},
+    create_parent_all, encryption,
+    error::PersistResult,
+    event::EventLog,
+    hooks::StorageEvent,
+    opf::to_opf,
+    overrides::{is_tag, NovelOverrides},
+    stats::NovelStats,
+    versions::{self, ChapterVersion},
+    Event, EventKind, Persist, Provenance,
+};
 
 #[derive(Debug)]
 pub struct PersistNovel<'a> {
@@ -22,13 +37,62 @@ pub struct SavedNovel {
     pub novel: Novel,
     pub cover: Option<CoverLoc>,
     pub downloaded: HashMap<String, PathBuf>,
+    /// The detected language of each downloaded chapter keyed by url
+    #[serde(default)]
+    pub chapter_langs: HashMap<String, String>,
+    /// The number of words of each downloaded chapter keyed by url
+    #[serde(default)]
+    pub word_counts: HashMap<String, u64>,
+    /// Previously downloaded covers, oldest first
+    #[serde(default)]
+    pub cover_history: Vec<CoverLoc>,
+    /// Free-form notes or review of the novel in plain text, with paragraphs
+    /// separated by blank lines
+    #[serde(default)]
+    pub notes: Option<String>,
+    /// License or attribution set by the user, replacing the one from the source
+    #[serde(default)]
+    pub rights: Option<String>,
+    /// The profile of the stored credentials used to fetch the novel
+    #[serde(default)]
+    pub credential: Option<String>,
+    /// Chapter title rules of the novel, replacing the ones given when exporting
+    #[serde(default)]
+    pub title_rules: Option<TitleRules>,
+    /// The extension that produced each field of the novel, see [`NOVEL_FIELDS`](crate::NOVEL_FIELDS)
+    #[serde(default)]
+    pub provenance: BTreeMap<String, Provenance>,
+    /// The extension that produced each downloaded chapter keyed by url
+    #[serde(default)]
+    pub chapter_provenance: HashMap<String, Provenance>,
+    /// The checksum of each downloaded chapter keyed by url, see [`content_hash`]
+    ///
+    /// [`content_hash`]: crate::content_hash
+    #[serde(default)]
+    pub chapter_hashes: HashMap<String, String>,
+    /// Why the chapters that are not downloaded are missing keyed by url,
+    /// see [`SavedNovel::chapter_status`]
+    #[serde(default)]
+    pub chapter_states: HashMap<String, ChapterState>,
+    /// Metadata edited by the user, see [`SavedNovel::merged`]
+    #[serde(default)]
+    pub overrides: NovelOverrides,
+    /// The other urls the novel can be downloaded from, see [`Persist::switch_source`]
+    #[serde(default)]
+    pub mirrors: Vec<String>,
     pub updated_at: DateTime<Utc>,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct CoverLoc {
     pub path: PathBuf,
     pub content_type: String,
+    /// The url the cover was downloaded from
+    #[serde(default)]
+    pub url: Option<String>,
+    /// A hash of the cover content used to detect changes
+    #[serde(default)]
+    pub hash: Option<String>,
 }
 
 impl<'a> PersistNovel<'a> {
@@ -38,26 +102,39 @@ impl<'a> PersistNovel<'a> {
 
     pub fn event_log(&self) -> PersistResult<EventLog> {
         let path = self.dir.join(&self.persist.options.novel.events);
-        EventLog::new(path)
+        EventLog::new(path, self.persist.cipher().cloned())
     }
 
     pub fn dir(&self) -> &Path {
         &self.dir
     }
 
+    pub(crate) fn persist(&self) -> &'a Persist {
+        self.persist
+    }
+
+    /// Start a group of chapter writes saved together with the novel data
+    pub fn batch(&self) -> PersistResult<Batch<'_, 'a>> {
+        Batch::new(self)
+    }
+
     pub fn data_path(&self) -> PathBuf {
         self.dir.join(&self.persist.options.novel.filename)
     }
 
+    /// Read the novel data, first undoing a batch that was interrupted while saved
     pub fn read_data(&self) -> PersistResult<Option<SavedNovel>> {
+        batch::recover(&self.dir)?;
         let path = self.data_path();
 
-        let data = if path.exists() {
+        let data = if !path.exists() {
+            None
+        } else if let Some(cipher) = self.persist.cipher() {
+            Some(serde_json::from_slice(&cipher.decrypt(&fs::read(&path)?)?)?)
+        } else {
             let file = File::open(&path)?;
             let file = BufReader::new(file);
             Some(serde_json::from_reader(file)?)
-        } else {
-            None
         };
 
         Ok(data)
@@ -67,15 +144,40 @@ impl<'a> PersistNovel<'a> {
         let path = self.data_path();
         create_parent_all(&path)?;
 
-        let file = OpenOptions::new()
-            .create(true)
-            .truncate(true)
-            .write(true)
-            .open(path)?;
+        if let Some(cipher) = self.persist.cipher() {
+            fs::write(path, cipher.encrypt(&serde_json::to_vec(data)?)?)?;
+        } else {
+            let file = OpenOptions::new()
+                .create(true)
+                .truncate(true)
+                .write(true)
+                .open(path)?;
 
-        let writer = BufWriter::new(file);
-        serde_json::to_writer(writer, data)?;
+            let writer = BufWriter::new(file);
+            serde_json::to_writer(writer, data)?;
 
+            self.write_metadata(data)?;
+        }
+
+        self.persist.emit(StorageEvent::NovelUpdated {
+            dir: self.dir.clone(),
+        });
+        Ok(())
+    }
+
+    pub fn metadata_path(&self) -> PathBuf {
+        self.dir.join(&self.persist.options.novel.metadata)
+    }
+
+    /// Write the OPF metadata file so that external tools can read the novel
+    ///
+    /// Nothing is written for encrypted libraries, as the file would reveal the novel.
+    pub fn write_metadata(&self, data: &SavedNovel) -> PersistResult<()> {
+        if self.persist.cipher().is_some() {
+            return Ok(());
+        }
+
+        fs::write(self.metadata_path(), to_opf(data))?;
         Ok(())
     }
 
@@ -85,14 +187,101 @@ impl<'a> PersistNovel<'a> {
     }
 
     /// Directory should exist
-    pub fn save_chapter(&self, chapter: &Chapter, content: String) -> PersistResult<PathBuf> {
+    ///
+    /// Content previously saved for the chapter is kept as a version when it differs.
+    pub fn save_chapter(
+        &self,
+        chapter: &Chapter,
+        content: String,
+        compression: Compression,
+    ) -> PersistResult<PathBuf> {
         let name = format!("{}.html", chapter.index);
         let path = self.chapters_dir().join(name);
 
-        fs::write(&path, content)?;
+        for previous in Compression::ALL.map(|compression| compression.apply_to(path.clone())) {
+            if previous.exists() {
+                let versions_dir = self.versions_dir(chapter);
+                versions::archive(&versions_dir, &previous, &content, self.persist.cipher())?;
+            }
+        }
+
+        let path = compression::write_content(path, &content, compression, self.persist.cipher())?;
+        self.persist.emit(StorageEvent::ChapterStored {
+            dir: self.dir.clone(),
+            url: chapter.url.clone(),
+            path: path.clone(),
+        });
+
         Ok(path)
     }
 
+    /// The directory the previous contents of the chapter are kept in
+    pub fn versions_dir(&self, chapter: &Chapter) -> PathBuf {
+        self.dir.join("versions").join(chapter.index.to_string())
+    }
+
+    /// The previous contents of the chapter, oldest first
+    pub fn chapter_versions(&self, chapter: &Chapter) -> PersistResult<Vec<ChapterVersion>> {
+        versions::list(&self.versions_dir(chapter))
+    }
+
+    /// Forget the previous contents of the chapter, keeping the current one
+    pub fn clear_chapter_versions(&self, chapter: &Chapter) -> PersistResult<()> {
+        let dir = self.versions_dir(chapter);
+        if dir.exists() {
+            fs::remove_dir_all(dir)?;
+        }
+        Ok(())
+    }
+
+    /// Read the content of a downloaded chapter, decompressing it if needed
+    pub fn read_chapter(&self, path: &Path) -> PersistResult<String> {
+        compression::read_content(&self.dir.join(path), self.persist.cipher())
+    }
+
+    /// Rewrite the downloaded chapters with the compression and save the new paths,
+    /// returning the number of chapters rewritten
+    pub fn recompress(
+        &self,
+        data: &mut SavedNovel,
+        compression: Compression,
+    ) -> PersistResult<usize> {
+        let mut batch = self.batch()?;
+        let mut count = 0;
+        for (url, path) in data.downloaded.iter_mut() {
+            let current = self.dir.join(&*path);
+            if Compression::of_path(&current) == compression || !current.exists() {
+                continue;
+            }
+
+            let content = compression::read_content(&current, self.persist.cipher())?;
+            let target = current.with_extension("").with_extension("html");
+            let written = batch.write(url, target, &content, compression)?;
+            if written != current {
+                batch.remove_file(current);
+            }
+
+            *path = self.relative_path(written);
+            count += 1;
+        }
+
+        if count > 0 {
+            batch.commit(data)?;
+        }
+
+        Ok(count)
+    }
+
+    #[inline]
+    pub fn assets_dir(&self) -> PathBuf {
+        self.dir.join("assets")
+    }
+
+    /// The images and other files stored for the novel
+    pub fn read_assets(&self) -> PersistResult<AssetStore> {
+        AssetStore::open(self.assets_dir(), self.persist.cipher().cloned())
+    }
+
     pub fn relative_path(&self, path: PathBuf) -> PathBuf {
         pathdiff::diff_paths(&path, &self.dir).unwrap_or(path)
     }
@@ -105,6 +294,38 @@ impl<'a> PersistNovel<'a> {
 
         self.dir.join(name)
     }
+
+    /// Write the cover of the file type, encrypted in an encrypted library,
+    /// and return its path
+    pub fn write_cover(&self, file_type: Option<&str>, content: &[u8]) -> PersistResult<PathBuf> {
+        let path = self.cover_path(file_type);
+        create_parent_all(&path)?;
+        encryption::write_file(&path, content, self.persist.cipher())?;
+        Ok(path)
+    }
+
+    #[inline]
+    pub fn cover_history_dir(&self) -> PathBuf {
+        self.dir.join("covers")
+    }
+
+    /// Move the cover into the history directory and return its new location
+    pub fn archive_cover(&self, cover: CoverLoc) -> PersistResult<CoverLoc> {
+        let name = cover
+            .path
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_else(|| String::from("cover"));
+
+        let path = self
+            .cover_history_dir()
+            .join(format!("{}-{name}", Utc::now().timestamp()));
+
+        create_parent_all(&path)?;
+        fs::rename(&cover.path, &path)?;
+
+        Ok(CoverLoc { path, ..cover })
+    }
 }
 
 impl SavedNovel {
@@ -113,10 +334,107 @@ impl SavedNovel {
             novel,
             cover: None,
             downloaded: Default::default(),
+            chapter_langs: Default::default(),
+            word_counts: Default::default(),
+            cover_history: Default::default(),
+            notes: None,
+            rights: None,
+            credential: None,
+            title_rules: None,
+            provenance: Default::default(),
+            chapter_provenance: Default::default(),
+            chapter_hashes: Default::default(),
+            chapter_states: Default::default(),
+            overrides: Default::default(),
+            mirrors: vec![],
             updated_at: Utc::now(),
         }
     }
 
+    /// The title of the novel, preferring the one set by the user
+    pub fn title(&self) -> &str {
+        self.overrides.title.as_deref().unwrap_or(&self.novel.title)
+    }
+
+    /// The authors of the novel, preferring the ones set by the user
+    pub fn authors(&self) -> &[String] {
+        self.overrides
+            .authors
+            .as_deref()
+            .unwrap_or(&self.novel.authors)
+    }
+
+    /// The cover of the novel, preferring the one chosen by the user
+    pub fn cover(&self) -> Option<&CoverLoc> {
+        self.overrides.cover.as_ref().or(self.cover.as_ref())
+    }
+
+    /// The novel data with the edits of the user merged over the fetched
+    /// metadata, to be read or exported
+    ///
+    /// The edits are kept, so that writing the merged data back changes nothing.
+    pub fn merged(mut self) -> Self {
+        self.overrides.apply(&mut self.novel);
+        if let Some(cover) = &self.overrides.cover {
+            self.cover = Some(cover.clone());
+        }
+        self
+    }
+
+    /// The license or attribution of the novel, preferring the one set by the user
+    pub fn rights(&self) -> Option<&str> {
+        self.rights.as_deref().or_else(|| self.novel.rights())
+    }
+
+    /// The number of chapters of the novel that were not downloaded yet
+    pub fn pending_chapters(&self) -> usize {
+        self.novel
+            .volumes
+            .iter()
+            .flat_map(|volume| &volume.chapters)
+            .filter(|chapter| !self.downloaded.contains_key(&chapter.url))
+            .count()
+    }
+
+    /// Record that the fields of the novel were fetched with the extension
+    pub fn set_provenance<'f>(
+        &mut self,
+        fields: impl IntoIterator<Item = &'f str>,
+        provenance: &Provenance,
+    ) {
+        for field in fields {
+            self.provenance
+                .insert(field.to_string(), provenance.clone());
+        }
+    }
+
+    /// The statistics of the novel, from the word counts kept as chapters are saved
+    pub fn stats(&self) -> NovelStats {
+        NovelStats {
+            chapters: self.downloaded.len() + self.pending_chapters(),
+            downloaded: self.downloaded.len(),
+            words: self
+                .downloaded
+                .keys()
+                .filter_map(|url| self.word_counts.get(url))
+                .sum(),
+        }
+    }
+
+    /// The subjects and tags given by the source, or the ones set by the user
+    pub fn tags(&self) -> Box<dyn Iterator<Item = &str> + '_> {
+        match &self.overrides.tags {
+            Some(tags) => Box::new(tags.iter().map(String::as_str)),
+            None => Box::new(
+                self.novel
+                    .metadata
+                    .iter()
+                    .filter(|metadata| is_tag(metadata))
+                    .map(|metadata| metadata.value.as_str()),
+            ),
+        }
+    }
+
     pub fn is_cover_downloaded(&self) -> bool {
         match &self.cover {
             Some(cover) => cover.path.exists() && cover.path.is_file(),
@@ -127,9 +445,39 @@ impl SavedNovel {
     pub fn commit_events(&mut self, events: Vec<Event>) {
         for event in events {
             match event.kind {
-                EventKind::Downloaded { url, path } => {
+                EventKind::Downloaded {
+                    url,
+                    path,
+                    lang,
+                    words,
+                    provenance,
+                    hash,
+                } => {
+                    if let Some(words) = words {
+                        self.word_counts.insert(url.clone(), words);
+                    }
+                    match hash {
+                        Some(hash) => self.chapter_hashes.insert(url.clone(), hash),
+                        None => self.chapter_hashes.remove(&url),
+                    };
+                    if let Some(provenance) = provenance {
+                        self.chapter_provenance.insert(url.clone(), provenance);
+                    }
+                    if let Some(lang) = lang {
+                        if self.novel.langs.is_empty() {
+                            self.novel.langs.push(lang.clone());
+                        }
+                        self.chapter_langs.insert(url.clone(), lang);
+                    }
+                    self.chapter_states.remove(&url);
                     self.downloaded.insert(url, path);
                 }
+                EventKind::Queued { urls } => self.queue_chapters(urls),
+                EventKind::Failed {
+                    url,
+                    error,
+                    skipped,
+                } => self.fail_chapter(url, error, skipped),
             }
         }
     }
@@ -0,0 +1,206 @@
+use std::fmt::Write;
+
+use quelle_core::prelude::{Metadata, Namespace, NovelStatus, DUBLIN_CORE};
+
+use crate::{overrides::is_tag, SavedNovel};
+
+/// Render the novel information as an OPF package document.
+///
+/// The document follows the layout of the `metadata.opf` files
+/// written by Calibre so that the library can be read by external tools.
+pub fn to_opf(data: &SavedNovel) -> String {
+    let novel = &data.novel;
+    let mut out = String::new();
+
+    // Writing to a string never fails
+    let _ = writeln!(out, r#"<?xml version="1.0" encoding="utf-8"?>"#);
+    let _ = writeln!(
+        out,
+        r#"<package xmlns="http://www.idpf.org/2007/opf" unique-identifier="uuid_id" version="2.0">"#
+    );
+    let _ = writeln!(
+        out,
+        r#"  <metadata xmlns:dc="http://purl.org/dc/elements/1.1/" xmlns:opf="http://www.idpf.org/2007/opf">"#
+    );
+
+    let _ = writeln!(
+        out,
+        r#"    <dc:identifier opf:scheme="URL" id="uuid_id">{}</dc:identifier>"#,
+        escape(&novel.url)
+    );
+    let _ = writeln!(out, "    <dc:title>{}</dc:title>", escape(data.title()));
+
+    for author in data.authors() {
+        let _ = writeln!(
+            out,
+            r#"    <dc:creator opf:role="aut">{}</dc:creator>"#,
+            escape(author)
+        );
+    }
+
+    if !novel.description.is_empty() {
+        let description = format!("<p>{}</p>", novel.description.join("</p><p>"));
+        let _ = writeln!(
+            out,
+            "    <dc:description>{}</dc:description>",
+            escape(&description)
+        );
+    }
+
+    for lang in &novel.langs {
+        let _ = writeln!(out, "    <dc:language>{}</dc:language>", escape(lang));
+    }
+
+    let _ = writeln!(out, "    <dc:source>{}</dc:source>", escape(&novel.url));
+
+    if let Some(rights) = data.rights() {
+        let _ = writeln!(out, "    <dc:rights>{}</dc:rights>", escape(rights));
+    }
+
+    // The tags edited by the user replace the ones given by the source
+    let edited_tags = data.overrides.tags.as_ref();
+    let metadata = novel
+        .metadata
+        .iter()
+        .filter(|metadata| edited_tags.is_none() || !is_tag(metadata));
+    for metadata in metadata {
+        write_metadata(&mut out, metadata);
+    }
+    for tag in edited_tags.into_iter().flatten() {
+        let metadata = Metadata::new(String::from("subject"), tag.clone(), None);
+        write_metadata(&mut out, &metadata);
+    }
+
+    if !matches!(novel.status, NovelStatus::Unknown) {
+        let _ = writeln!(
+            out,
+            r#"    <meta name="quelle:status" content="{:?}"/>"#,
+            novel.status
+        );
+    }
+
+    let _ = writeln!(
+        out,
+        r#"    <meta name="calibre:timestamp" content="{}"/>"#,
+        data.updated_at.to_rfc3339()
+    );
+
+    let _ = writeln!(out, "  </metadata>");
+
+    let cover = data
+        .cover()
+        .and_then(|cover| cover.path.file_name())
+        .map(|name| name.to_string_lossy());
+
+    if let Some(cover) = cover {
+        let _ = writeln!(out, "  <guide>");
+        let _ = writeln!(
+            out,
+            r#"    <reference type="cover" title="Cover" href="{}"/>"#,
+            escape(&cover)
+        );
+        let _ = writeln!(out, "  </guide>");
+    }
+
+    let _ = writeln!(out, "</package>");
+    out
+}
+
+/// Dublin core elements that are written from the dedicated novel fields
+const NOVEL_FIELDS: [&str; 7] = [
+    "title",
+    "creator",
+    "description",
+    "language",
+    "source",
+    "identifier",
+    "rights",
+];
+
+fn write_metadata(out: &mut String, metadata: &Metadata) {
+    if NOVEL_FIELDS.contains(&metadata.name.as_str()) {
+        return;
+    }
+
+    // The namespace comes from the extension, so the name is checked before it
+    // becomes an element
+    let _ = match metadata.ns {
+        Namespace::DC if DUBLIN_CORE.contains(&metadata.name.as_str()) => writeln!(
+            out,
+            "    <dc:{name}>{}</dc:{name}>",
+            escape(&metadata.value),
+            name = metadata.name
+        ),
+        Namespace::DC | Namespace::OPF => writeln!(
+            out,
+            r#"    <meta name="{}" content="{}"/>"#,
+            escape(&metadata.name),
+            escape(&metadata.value)
+        ),
+    };
+}
+
+fn escape(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use quelle_core::prelude::{Metadata, Novel};
+
+    use super::to_opf;
+    use crate::SavedNovel;
+
+    #[test]
+    fn should_write_dublin_core_fields() {
+        let novel = Novel {
+            url: String::from("https://example.com/novel/1?a=1&b=2"),
+            title: String::from("Tom & Jerry"),
+            authors: vec![String::from("Author")],
+            langs: vec![String::from("en")],
+            metadata: vec![Metadata::new(
+                String::from("subject"),
+                String::from("Fantasy"),
+                None,
+            )],
+            ..Default::default()
+        };
+
+        let opf = to_opf(&SavedNovel::new(novel));
+
+        assert!(opf.contains("<dc:title>Tom &amp; Jerry</dc:title>"));
+        assert!(opf.contains(r#"<dc:creator opf:role="aut">Author</dc:creator>"#));
+        assert!(opf.contains("<dc:language>en</dc:language>"));
+        assert!(opf.contains("<dc:subject>Fantasy</dc:subject>"));
+        assert!(opf.contains("https://example.com/novel/1?a=1&amp;b=2"));
+        assert!(!opf.contains("<guide>"));
+    }
+
+    #[test]
+    fn should_write_unknown_dublin_core_names_as_meta() {
+        let mut novel = Novel {
+            url: String::from("https://example.com/novel/1"),
+            title: String::from("Title"),
+            ..Default::default()
+        };
+        let mut metadata = Metadata::new(String::from("subject"), String::from("Fantasy"), None);
+        metadata.name = String::from("bad name><x");
+        novel.metadata.push(metadata);
+
+        let opf = to_opf(&SavedNovel::new(novel));
+
+        assert!(!opf.contains("<dc:bad"));
+        assert!(opf.contains(r#"<meta name="bad name&gt;&lt;x" content="Fantasy"/>"#));
+    }
+}
@@ -4,6 +4,25 @@ use std::path::PathBuf;
 pub struct PersistOptions {
     pub base_dir: PathBuf,
     pub global_path: PathBuf,
+    pub hosts_path: PathBuf,
+    pub sources_path: PathBuf,
+    pub credentials_path: PathBuf,
+    /// The collections the novels are grouped into
+    pub collections_path: PathBuf,
+    /// The directory of the chapter content shared between novels
+    pub cache_dir: PathBuf,
+    /// The version of the layout the library was last migrated to
+    pub schema_path: PathBuf,
+    /// The settings of the library, such as the chapter compression
+    pub config_path: PathBuf,
+    /// The directory deleted novels are kept in until they are purged
+    pub trash_dir: PathBuf,
+    /// The file locked while a process uses the library
+    pub lock_path: PathBuf,
+    /// The directory the backups of the maintenance are written to
+    pub backups_dir: PathBuf,
+    /// The summaries of the novels used when listing the library
+    pub index_path: PathBuf,
     pub novel: NovelOptions,
 }
 
@@ -12,6 +31,8 @@ pub struct NovelOptions {
     pub dir: PathBuf,
     pub filename: PathBuf,
     pub events: PathBuf,
+    /// The OPF metadata file written next to the novel data
+    pub metadata: PathBuf,
 }
 
 impl PersistOptions {
@@ -19,19 +40,35 @@ impl PersistOptions {
     pub fn new() -> Self {
         Default::default()
     }
-}
 
-impl Default for PersistOptions {
-    fn default() -> Self {
-        let base_dir = PathBuf::from("data");
+    /// The default layout of a library stored in the directory
+    pub fn with_base_dir(base_dir: PathBuf) -> Self {
         Self {
             global_path: base_dir.join("global.json"),
+            hosts_path: base_dir.join("hosts.json"),
+            sources_path: base_dir.join("sources.json"),
+            credentials_path: base_dir.join("credentials.json"),
+            collections_path: base_dir.join("collections.json"),
+            cache_dir: base_dir.join("cache").join("chapters"),
+            schema_path: base_dir.join("schema.json"),
+            config_path: base_dir.join("library.json"),
+            trash_dir: base_dir.join("trash"),
+            lock_path: base_dir.join("library.lock"),
+            backups_dir: base_dir.join("backups"),
+            index_path: base_dir.join("index.json"),
             novel: NovelOptions {
                 dir: base_dir.join("novels"),
                 filename: PathBuf::from("novel.json"),
                 events: PathBuf::from("log.jsonl"),
+                metadata: PathBuf::from("metadata.opf"),
             },
             base_dir,
         }
     }
 }
+
+impl Default for PersistOptions {
+    fn default() -> Self {
+        Self::with_base_dir(PathBuf::from("data"))
+    }
+}
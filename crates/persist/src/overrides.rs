@@ -0,0 +1,130 @@
+use std::{fs, path::Path};
+
+use quelle_core::prelude::{Metadata, Novel};
+use serde::{Deserialize, Serialize};
+
+use crate::{encryption, error::PersistResult, CoverLoc, PersistNovel, SavedNovel};
+
+/// The name of the cover chosen by the user, kept apart from the downloaded one
+const COVER_OVERRIDE: &str = "cover-override";
+
+/// Metadata edited by the user, merged over the one fetched from the source
+///
+/// Kept apart from the novel so that updating the novel from the source never
+/// replaces the edits.
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
+pub struct NovelOverrides {
+    #[serde(default)]
+    pub title: Option<String>,
+    #[serde(default)]
+    pub authors: Option<Vec<String>>,
+    /// Replaces the subjects and tags given by the source
+    #[serde(default)]
+    pub tags: Option<Vec<String>>,
+    #[serde(default)]
+    pub cover: Option<CoverLoc>,
+}
+
+impl NovelOverrides {
+    pub fn is_empty(&self) -> bool {
+        self.title.is_none()
+            && self.authors.is_none()
+            && self.tags.is_none()
+            && self.cover.is_none()
+    }
+
+    /// Replace the fields of the novel with the edited ones
+    pub fn apply(&self, novel: &mut Novel) {
+        if let Some(title) = &self.title {
+            novel.title = title.clone();
+        }
+        if let Some(authors) = &self.authors {
+            novel.authors = authors.clone();
+        }
+        if let Some(tags) = &self.tags {
+            novel.metadata.retain(|metadata| !is_tag(metadata));
+            for tag in tags {
+                let metadata = Metadata::new(String::from("subject"), tag.clone(), None);
+                novel.metadata.push(metadata);
+            }
+        }
+    }
+}
+
+/// Whether the metadata is a subject or tag of the novel
+pub(crate) fn is_tag(metadata: &Metadata) -> bool {
+    metadata.name == "subject" || metadata.name == "tag"
+}
+
+impl PersistNovel<'_> {
+    /// Copy the image into the novel and use it as the cover instead of the downloaded one
+    pub fn set_cover_override(
+        &self,
+        data: &mut SavedNovel,
+        image: &Path,
+        content_type: String,
+    ) -> PersistResult<()> {
+        let name = match image.extension().and_then(|value| value.to_str()) {
+            Some(extension) => format!("{COVER_OVERRIDE}.{extension}"),
+            None => String::from(COVER_OVERRIDE),
+        };
+        let path = self.dir().join(name);
+
+        self.remove_cover_override(data)?;
+        encryption::write_file(&path, &fs::read(image)?, self.persist().cipher())?;
+
+        data.overrides.cover = Some(CoverLoc {
+            path,
+            content_type,
+            url: None,
+            hash: None,
+        });
+        Ok(())
+    }
+
+    /// Go back to the downloaded cover
+    pub fn remove_cover_override(&self, data: &mut SavedNovel) -> PersistResult<()> {
+        if let Some(cover) = data.overrides.cover.take() {
+            if cover.path.exists() {
+                fs::remove_file(cover.path)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::to_opf;
+
+    #[test]
+    fn should_merge_overrides_over_fetched_metadata() {
+        let novel = Novel {
+            title: String::from("Fetched"),
+            authors: vec![String::from("Author")],
+            metadata: vec![
+                Metadata::new(String::from("subject"), String::from("Action"), None),
+                Metadata::new(String::from("publisher"), String::from("Site"), None),
+            ],
+            ..Default::default()
+        };
+        let mut data = SavedNovel::new(novel);
+        data.overrides.title = Some(String::from("Edited"));
+        data.overrides.tags = Some(vec![String::from("Drama")]);
+
+        assert_eq!(data.title(), "Edited");
+        assert_eq!(data.authors(), ["Author"]);
+        assert_eq!(data.tags().collect::<Vec<_>>(), ["Drama"]);
+
+        let opf = to_opf(&data);
+        assert!(opf.contains("<dc:title>Edited</dc:title>"));
+        assert!(opf.contains("<dc:subject>Drama</dc:subject>"));
+        assert!(!opf.contains("Action"));
+
+        let merged = data.merged();
+        assert_eq!(merged.novel.title, "Edited");
+        let values = merged.novel.metadata.iter().map(|m| m.value.as_str());
+        assert_eq!(values.collect::<Vec<_>>(), ["Site", "Drama"]);
+    }
+}
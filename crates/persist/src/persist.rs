@@ -1,15 +1,160 @@
-use crate::{error::PersistResult, global::Global, novel::PersistNovel, PersistOptions};
-use quelle_core::prelude::Meta;
-use std::path::PathBuf;
+use crate::{
+    backup::{self, BackupManifest, ConflictStrategy, RestoreReport},
+    cache::ChapterCache,
+    cleanup::{self, CleanupReport},
+    collections::Collections,
+    compression::Compression,
+    config::LibraryConfig,
+    credentials::CredentialStore,
+    dedup::{self, DedupReport},
+    dump::{self, LibraryDump, LoadReport},
+    encryption::{self, Cipher},
+    error::{PersistError, PersistResult},
+    event,
+    global::Global,
+    hooks::{StorageEvent, Subscribers},
+    hosts::HostRegistry,
+    import::{self, ImportedNovel},
+    index::{self, IndexProgress, LibraryIndex, NovelSummary},
+    integrity::{self, IntegrityReport},
+    lock::{LibraryLock, LockMode},
+    maintenance::{self, MaintenanceTask, TaskSummary},
+    merge::{self, MergeReport},
+    migration::{self, MigrationReport},
+    mirrors,
+    novel::PersistNovel,
+    sources::SourceStats,
+    stats::{self, StorageStats},
+    transfer::{self, TransferEvent, TransferReport},
+    trash::{self, Trash, TrashedNovel},
+    PersistOptions,
+};
+use chrono::Duration;
+use quelle_common::NovelId;
+use quelle_core::prelude::{Meta, Novel};
+use std::{
+    fs,
+    path::{Component, Path, PathBuf},
+    sync::mpsc::Receiver,
+};
 
 #[derive(Debug)]
 pub struct Persist {
     pub options: PersistOptions,
+    /// Encrypts the novels once an encrypted library is unlocked
+    cipher: Option<Cipher>,
+    subscribers: Subscribers,
+    /// Held until the instance is dropped
+    lock: Option<LibraryLock>,
 }
 
 impl Persist {
     pub fn new(options: PersistOptions) -> Self {
-        Persist { options }
+        Persist {
+            options,
+            cipher: None,
+            subscribers: Subscribers::default(),
+            lock: None,
+        }
+    }
+
+    /// Lock the library against other processes until this instance is dropped
+    ///
+    /// Fails with [`PersistError::Locked`] when another process holds a
+    /// conflicting lock, unless asked to wait for it. Locking again replaces
+    /// the previous lock.
+    pub fn lock(&mut self, mode: LockMode, wait: bool) -> PersistResult<()> {
+        self.lock = None;
+        self.lock = Some(LibraryLock::acquire(&self.options.lock_path, mode, wait)?);
+        Ok(())
+    }
+
+    /// Receive the changes made to the library through this instance from now on
+    pub fn subscribe(&self) -> Receiver<StorageEvent> {
+        self.subscribers.subscribe()
+    }
+
+    pub(crate) fn emit(&self, event: StorageEvent) {
+        self.subscribers.emit(event);
+    }
+
+    /// Derive the key of an encrypted library from the passphrase
+    ///
+    /// Libraries that are not encrypted ignore the passphrase.
+    pub fn unlock(&mut self, passphrase: Option<&str>) -> PersistResult<()> {
+        let Some(config) = self.read_config()?.encryption else {
+            return Ok(());
+        };
+
+        let passphrase = passphrase.ok_or(PersistError::PassphraseRequired)?;
+        self.cipher = Some(Cipher::open(passphrase, &config)?);
+        Ok(())
+    }
+
+    /// The cipher of the library, when it is encrypted and unlocked
+    pub fn cipher(&self) -> Option<&Cipher> {
+        self.cipher.as_ref()
+    }
+
+    /// Encrypt the library with the passphrase, rewriting the data, chapters,
+    /// covers, assets, versions and logs of every novel, and return the number
+    /// of novels encrypted
+    ///
+    /// Running it again with the same passphrase continues an interrupted run.
+    pub fn encrypt(&mut self, passphrase: &str) -> PersistResult<usize> {
+        let mut config = self.read_config()?;
+        let cipher = match &config.encryption {
+            Some(encryption) => Cipher::open(passphrase, encryption)?,
+            None => {
+                let (cipher, encryption) = Cipher::create(passphrase)?;
+                config.encryption = Some(encryption);
+                self.save_config(&config)?;
+                cipher
+            }
+        };
+        self.cipher = Some(cipher.clone());
+
+        let global = self.read_global()?;
+        let mut count = 0;
+        for (_, dir) in global.novels() {
+            let novel = self.persist_novel(dir.clone());
+            let Some(data) = novel.read_data()? else {
+                continue;
+            };
+
+            novel.write_data(&data)?;
+            if novel.metadata_path().exists() {
+                fs::remove_file(novel.metadata_path())?;
+            }
+
+            let events = dir.join(&self.options.novel.events);
+            if events.exists() {
+                event::encrypt_log(&events, &cipher)?;
+            }
+            encrypt_files(dir, &events, &cipher)?;
+            count += 1;
+        }
+
+        // The summaries of the index would reveal the novels
+        let index = &self.options.index_path;
+        if index.exists() {
+            let content = fs::read(index)?;
+            if !encryption::is_encrypted(&content) {
+                fs::write(index, cipher.encrypt(&content)?)?;
+            }
+        }
+
+        Ok(count)
+    }
+
+    /// Bring the library up to date, running the pending migrations
+    pub fn initialize(&self) -> PersistResult<MigrationReport> {
+        migration::migrate(self, false)
+    }
+
+    /// The migrations that [`Persist::initialize`] would run, without changing the library
+    pub fn pending_migrations(&self) -> PersistResult<MigrationReport> {
+        migration::migrate(self, true)
     }
 
     pub fn persist_novel<'a>(&'a self, dir: PathBuf) -> PersistNovel<'a> {
@@ -17,16 +162,442 @@ impl Persist {
     }
 
     pub fn novel_path(&self, meta: &Meta, title: &str) -> PathBuf {
-        let mut path = self.options.novel.dir.join(&meta.id);
-        path.push(slug::slugify(title));
+        let id = NovelId::new(&meta.id, title);
+        let mut path = self.options.novel.dir.join(&id.source);
+        path.push(&id.slug);
         path
     }
 
+    /// The id of the novel saved in the directory
+    pub fn novel_id(&self, dir: &Path) -> Option<NovelId> {
+        let relative = dir.strip_prefix(&self.options.novel.dir).ok()?;
+        let mut components = relative.components().map(|component| match component {
+            Component::Normal(value) => value.to_str(),
+            _ => None,
+        });
+
+        match (components.next(), components.next(), components.next()) {
+            (Some(Some(source)), Some(Some(slug)), None) => Some(NovelId {
+                source: source.to_string(),
+                slug: slug.to_string(),
+            }),
+            _ => None,
+        }
+    }
+
+    /// The directory of the novel with the id, following the novels it was merged into
+    pub fn novel_dir(&self, id: &NovelId) -> PersistResult<Option<PathBuf>> {
+        let global = self.read_global()?;
+        let dir = self.options.novel.dir.join(&id.source).join(&id.slug);
+        if global.novels().any(|(_, path)| *path == dir) {
+            return Ok(Some(dir));
+        }
+
+        let dir = global
+            .merged_into(id)
+            .and_then(|url| global.novel_path_from_url(url))
+            .map(Path::to_path_buf);
+        Ok(dir)
+    }
+
     pub fn read_global(&self) -> PersistResult<Global> {
         Global::open(&self.options.global_path)
     }
 
     pub fn save_global(&self, global: &Global) -> PersistResult<()> {
-        global.save(&self.options.global_path)
+        if self.subscribers.is_empty() {
+            return global.save(&self.options.global_path);
+        }
+
+        let previous = self.read_global()?;
+        global.save(&self.options.global_path)?;
+
+        for (url, dir) in global.novels() {
+            if previous.novel_path_from_url(url).is_none() {
+                self.emit(StorageEvent::NovelAdded {
+                    url: url.clone(),
+                    dir: dir.clone(),
+                });
+            }
+        }
+        for (url, dir) in previous.novels() {
+            if global.novel_path_from_url(url).is_none() {
+                self.emit(StorageEvent::NovelRemoved {
+                    url: url.clone(),
+                    dir: dir.clone(),
+                });
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn read_hosts(&self) -> PersistResult<HostRegistry> {
+        HostRegistry::open(&self.options.hosts_path)
+    }
+
+    pub fn save_hosts(&self, hosts: &HostRegistry) -> PersistResult<()> {
+        hosts.save(&self.options.hosts_path)
+    }
+
+    pub fn read_sources(&self) -> PersistResult<SourceStats> {
+        SourceStats::open(&self.options.sources_path)
+    }
+
+    pub fn save_sources(&self, sources: &SourceStats) -> PersistResult<()> {
+        sources.save(&self.options.sources_path)
+    }
+
+    /// The chapter and word counts of every novel in the library
+    pub fn library_stats(&self) -> PersistResult<StorageStats> {
+        stats::library_stats(self)
+    }
+
+    /// Summarize every novel into the library index, reporting the progress
+    /// after each novel
+    ///
+    /// Listing the library reads the summaries instead of every novel, only
+    /// reading the novels changed since the index was rebuilt.
+    pub fn rebuild_index(
+        &self,
+        progress: impl FnMut(IndexProgress),
+    ) -> PersistResult<LibraryIndex> {
+        index::rebuild(self, progress)
+    }
+
+    /// The summary of every novel in the library ordered by url, see [`Persist::rebuild_index`]
+    pub fn novel_summaries(&self) -> PersistResult<Vec<(String, NovelSummary)>> {
+        index::summaries(self)
+    }
+
+    /// Find chapter files without novel data, downloaded chapters whose file is
+    /// missing and covers out of sync with the novel data
+    ///
+    /// When fixing, the files are removed and the missing chapters and covers
+    /// are forgotten, so that they are downloaded again.
+    pub fn cleanup(&self, fix: bool) -> PersistResult<CleanupReport> {
+        cleanup::cleanup(self, fix)
+    }
+
+    /// Check the chapters against the checksums recorded when they were saved
+    /// and that the metadata files can be read, reporting the corrupted entries
+    ///
+    /// Useful after disk issues or interrupted syncs, nothing is repaired.
+    pub fn verify(&self) -> PersistResult<IntegrityReport> {
+        integrity::verify(self)
+    }
+
+    /// Store the downloaded chapters with identical content once, such as the
+    /// same novel saved from two sources, and report the space saved
+    ///
+    /// The chapters share a single file through hard links, so nothing changes
+    /// for the novels reading them.
+    pub fn deduplicate(&self, dry_run: bool) -> PersistResult<DedupReport> {
+        dedup::deduplicate(self, dry_run)
+    }
+
+    pub fn read_collections(&self) -> PersistResult<Collections> {
+        Collections::open(&self.options.collections_path)
+    }
+
+    pub fn save_collections(&self, collections: &Collections) -> PersistResult<()> {
+        collections.save(&self.options.collections_path)
+    }
+
+    pub fn read_credentials(&self) -> PersistResult<CredentialStore> {
+        CredentialStore::open(&self.options.credentials_path)
+    }
+
+    pub fn save_credentials(&self, credentials: &CredentialStore) -> PersistResult<()> {
+        credentials.save(&self.options.credentials_path)
+    }
+
+    pub fn read_chapter_cache(&self) -> PersistResult<ChapterCache> {
+        ChapterCache::open(self.options.cache_dir.clone())
+    }
+
+    pub fn read_config(&self) -> PersistResult<LibraryConfig> {
+        LibraryConfig::open(&self.options.config_path)
+    }
+
+    pub fn save_config(&self, config: &LibraryConfig) -> PersistResult<()> {
+        config.save(&self.options.config_path)
+    }
+
+    pub fn read_trash(&self) -> PersistResult<Trash> {
+        Trash::open(&trash::manifest_path(&self.options.trash_dir))
+    }
+
+    /// Record another url the novel saved from the url can be downloaded from,
+    /// such as the same novel on a mirror, returning whether the novel is saved
+    ///
+    /// The novel is then also found from the mirror.
+    pub fn add_mirror(&self, url: &str, mirror: &str) -> PersistResult<bool> {
+        mirrors::add_mirror(self, url, mirror)
+    }
+
+    /// Forget a mirror of a novel, returning whether it was recorded
+    pub fn remove_mirror(&self, mirror: &str) -> PersistResult<bool> {
+        mirrors::remove_mirror(self, mirror)
+    }
+
+    /// Download the novel saved from the url from another source from now on,
+    /// given the novel as fetched from that source, returning the number of
+    /// downloaded chapters carried over or `None` if the novel is not saved
+    ///
+    /// Chapters are matched by number, or by position when the source does not
+    /// number them. The previous url is kept as a mirror.
+    pub fn switch_source(&self, url: &str, novel: Novel) -> PersistResult<Option<usize>> {
+        mirrors::switch_source(self, url, novel)
+    }
+
+    /// Combine two saved novels of the same work, such as the novel saved from
+    /// two sources, into the primary novel, returning `None` if either is not saved
+    ///
+    /// The chapters only downloaded in the duplicate are saved into the primary
+    /// novel and the metadata it is missing is taken from the duplicate. The
+    /// duplicate is moved into the trash, its urls and id leading to the
+    /// primary novel from then on.
+    pub fn merge_novels(
+        &self,
+        primary: &str,
+        duplicate: &str,
+    ) -> PersistResult<Option<MergeReport>> {
+        merge::merge_novels(self, primary, duplicate)
+    }
+
+    /// Move the novel saved from the url into the trash, returning it if it was saved
+    ///
+    /// Novels kept in the trash for longer than the retention of the library
+    /// are purged first.
+    pub fn delete_novel(&self, url: &str) -> PersistResult<Option<TrashedNovel>> {
+        trash::purge_trash(self, self.read_config()?.trash_retention())?;
+        trash::delete_novel(self, url)
+    }
+
+    /// Move the novel most recently deleted from the url back into the library
+    pub fn restore_novel(&self, url: &str) -> PersistResult<Option<TrashedNovel>> {
+        trash::restore_novel(self, url)
+    }
+
+    /// Permanently delete the novels kept in the trash for at least the retention
+    pub fn purge_trash(&self, retention: Duration) -> PersistResult<Vec<TrashedNovel>> {
+        trash::purge_trash(self, retention)
+    }
+
+    /// Pack the library into a single archive to move it to another machine
+    pub fn export_backup(&self, path: &Path) -> PersistResult<BackupManifest> {
+        backup::export_backup(self, path)
+    }
+
+    /// Write the whole library to the path as a single JSON document, see
+    /// [`LibraryDump`] for the format
+    ///
+    /// The content of the downloaded chapters is only included when `chapters`
+    /// is set. Cover files are never included.
+    pub fn dump_json(&self, path: &Path, chapters: bool) -> PersistResult<LibraryDump> {
+        dump::dump_json(self, path, chapters)
+    }
+
+    /// Add the novels of a JSON document written by [`Persist::dump_json`]
+    ///
+    /// Chapters whose content is not in the dump and the covers are left to
+    /// be downloaded again.
+    pub fn load_json(&self, path: &Path, strategy: ConflictStrategy) -> PersistResult<LoadReport> {
+        dump::load_json(self, path, strategy)
+    }
+
+    /// Restore the novels of an archive written by [`Persist::export_backup`]
+    pub fn import_backup(
+        &self,
+        path: &Path,
+        strategy: ConflictStrategy,
+    ) -> PersistResult<RestoreReport> {
+        backup::import_backup(self, path, strategy)
+    }
+
+    /// Add the novel of an EPUB file to the library, with its metadata, volumes,
+    /// chapters and cover, so that it can be managed and bundled like the others
+    ///
+    /// The novel keeps the url of its source when the EPUB gives one.
+    pub fn import_epub(&self, path: &Path) -> PersistResult<ImportedNovel> {
+        import::import_epub(self, path)
+    }
+
+    /// Copy every novel of the library into another one, reporting the progress
+    pub fn transfer_to<F>(&self, to: &Persist, progress: F) -> PersistResult<TransferReport>
+    where
+        F: FnMut(TransferEvent),
+    {
+        transfer::transfer(self, to, progress)
+    }
+
+    /// Run the housekeeping task, returning nothing for the tasks run by the client
+    ///
+    /// See [`MaintenanceConfig`](crate::MaintenanceConfig) for when the tasks should run.
+    pub fn maintain(&self, task: MaintenanceTask) -> PersistResult<Option<TaskSummary>> {
+        maintenance::run(self, task)
+    }
+
+    /// Rewrite the downloaded chapters of every novel with the compression,
+    /// returning the number of chapters rewritten
+    pub fn recompress(&self, compression: Compression) -> PersistResult<usize> {
+        let global = self.read_global()?;
+
+        let mut count = 0;
+        for (_, dir) in global.novels() {
+            let novel = self.persist_novel(dir.clone());
+            if let Some(mut data) = novel.read_data()? {
+                count += novel.recompress(&mut data, compression)?;
+            }
+        }
+
+        Ok(count)
+    }
+}
+
+/// Encrypt every file in the directory and its subdirectories that is not
+/// encrypted yet, except the log encrypted a line at a time
+fn encrypt_files(dir: &Path, log: &Path, cipher: &Cipher) -> PersistResult<()> {
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            encrypt_files(&path, log, cipher)?;
+            continue;
+        }
+
+        let content = fs::read(&path)?;
+        if path != log && !encryption::is_encrypted(&content) {
+            fs::write(&path, cipher.encrypt(&content)?)?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use quelle_core::prelude::Chapter;
+
+    use super::*;
+    use crate::{read_file, CoverLoc, EventKind, SavedNovel};
+
+    /// The files in the directory and its subdirectories
+    fn files(dir: &Path) -> Vec<PathBuf> {
+        let mut files = vec![];
+        for entry in fs::read_dir(dir).unwrap() {
+            let path = entry.unwrap().path();
+            match path.is_dir() {
+                true => files.extend(self::files(&path)),
+                false => files.push(path),
+            }
+        }
+        files
+    }
+
+    #[test]
+    fn should_leave_no_plaintext_novel_file_after_encrypting() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut persist = Persist::new(PersistOptions::with_base_dir(dir.path().to_path_buf()));
+        let novel_dir = persist.options.novel.dir.join("example").join("novel");
+        let export = dir.path().join("novel.epub");
+
+        let chapter = Chapter {
+            index: 1,
+            title: String::from("One"),
+            url: String::from("https://example.com/1"),
+            updated_at: None,
+            number: None,
+            part: None,
+            label: None,
+        };
+        {
+            let novel = persist.persist_novel(novel_dir.clone());
+            fs::create_dir_all(novel.chapters_dir()).unwrap();
+
+            let mut data = SavedNovel::new(Novel {
+                title: String::from("Example"),
+                url: String::from("https://example.com/novel"),
+                ..Default::default()
+            });
+            // Saving different content keeps the first as a version
+            novel
+                .save_chapter(&chapter, String::from("<p>first</p>"), Compression::None)
+                .unwrap();
+            let path = novel
+                .save_chapter(&chapter, String::from("<p>second</p>"), Compression::None)
+                .unwrap();
+            data.downloaded
+                .insert(chapter.url.clone(), novel.relative_path(path));
+
+            let cover = novel.cover_path(Some("png"));
+            fs::write(&cover, "https://example.com/cover.png").unwrap();
+            data.cover = Some(CoverLoc {
+                path: cover,
+                content_type: String::from("image/png"),
+                url: None,
+                hash: None,
+            });
+
+            let mut assets = novel.read_assets().unwrap();
+            assets
+                .store_asset("https://example.com/image.png", "image/png", b"image")
+                .unwrap();
+            assets.save().unwrap();
+
+            let mut exports = novel.read_exports().unwrap();
+            exports.record(export.clone(), "epub", 1, String::from("abc"));
+            novel.write_exports(&exports).unwrap();
+
+            let queued = EventKind::Queued {
+                urls: vec![chapter.url.clone()],
+            };
+            novel.event_log().unwrap().push_event(queued).unwrap();
+            novel.write_data(&data).unwrap();
+        }
+
+        let mut global = persist.read_global().unwrap();
+        global.insert_novel(String::from("https://example.com/novel"), novel_dir.clone());
+        persist.save_global(&global).unwrap();
+
+        assert_eq!(persist.encrypt("secret").unwrap(), 1);
+
+        let events = novel_dir.join(&persist.options.novel.events);
+        for path in files(&novel_dir) {
+            let content = fs::read(&path).unwrap();
+            assert!(
+                !String::from_utf8_lossy(&content).contains("example.com"),
+                "'{}' is not encrypted",
+                path.display()
+            );
+            assert!(path == events || encryption::is_encrypted(&content));
+        }
+
+        let novel = persist.persist_novel(novel_dir.clone());
+        let data = novel.read_data().unwrap().unwrap();
+        let path = &data.downloaded[&chapter.url];
+        assert_eq!(novel.read_chapter(path).unwrap(), "<p>second</p>");
+        let versions = novel.chapter_versions(&chapter).unwrap();
+        assert_eq!(
+            novel.read_chapter(&versions[0].path).unwrap(),
+            "<p>first</p>"
+        );
+
+        let cover = &data.cover.unwrap().path;
+        assert_eq!(
+            read_file(cover, persist.cipher()).unwrap(),
+            b"https://example.com/cover.png"
+        );
+        let assets = novel.read_assets().unwrap();
+        let (_, content) = assets
+            .get_asset("https://example.com/image.png")
+            .unwrap()
+            .unwrap();
+        assert_eq!(content, b"image");
+        let exports = novel.read_exports().unwrap();
+        assert_eq!(exports.get(&export).map(|record| record.chapters), Some(1));
+
+        let mut log = novel.event_log().unwrap();
+        log.read_events().unwrap();
+        assert_eq!(log.take_events().map(|events| events.len()), Some(1));
     }
 }
@@ -0,0 +1,39 @@
+use chrono::{DateTime, Utc};
+use quelle_core::prelude::Meta;
+use serde::{Deserialize, Serialize};
+
+/// Fields of the novel whose provenance is recorded when it is fetched
+pub const NOVEL_FIELDS: [&str; 8] = [
+    "title",
+    "authors",
+    "cover",
+    "description",
+    "volumes",
+    "metadata",
+    "status",
+    "langs",
+];
+
+/// The extension that produced a part of a novel and when it was fetched
+///
+/// Kept so that data produced by a faulty version of an extension can be
+/// found and fetched again once the extension is fixed.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Provenance {
+    /// The id of the extension
+    pub source: String,
+    /// The version of the extension
+    pub version: String,
+    pub fetched_at: DateTime<Utc>,
+}
+
+impl Provenance {
+    /// The provenance of data fetched now by the extension
+    pub fn now(meta: &Meta) -> Self {
+        Self {
+            source: meta.id.clone(),
+            version: meta.version.clone(),
+            fetched_at: Utc::now(),
+        }
+    }
+}
@@ -0,0 +1,352 @@
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{Component, Path, PathBuf},
+    sync::Mutex,
+};
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::{
+    backup::{library_files, rebase, rebase_cover},
+    create_parent_all,
+    error::PersistResult,
+    Persist,
+};
+
+/// The key of the manifest listing the library files in the bucket
+const MANIFEST: &str = "manifest.json";
+
+/// A bucket of objects the library can be stored in
+pub trait ObjectStore: std::fmt::Debug {
+    /// The content of the object, or `None` if it does not exist
+    fn get(&self, key: &str) -> PersistResult<Option<Vec<u8>>>;
+
+    fn put(&self, key: &str, data: &[u8]) -> PersistResult<()>;
+}
+
+/// Objects stored as files in a directory, such as a mounted bucket
+#[derive(Debug)]
+pub struct DirStore {
+    dir: PathBuf,
+}
+
+impl DirStore {
+    pub fn new(dir: PathBuf) -> Self {
+        Self { dir }
+    }
+}
+
+impl ObjectStore for DirStore {
+    fn get(&self, key: &str) -> PersistResult<Option<Vec<u8>>> {
+        let path = self.dir.join(key);
+        if !path.exists() {
+            return Ok(None);
+        }
+
+        Ok(Some(fs::read(path)?))
+    }
+
+    fn put(&self, key: &str, data: &[u8]) -> PersistResult<()> {
+        let path = self.dir.join(key);
+        create_parent_all(&path)?;
+        fs::write(path, data)?;
+        Ok(())
+    }
+}
+
+/// Objects kept in memory without touching the filesystem, for tests and
+/// sessions that do not outlive the process
+#[derive(Debug, Default)]
+pub struct MemoryStore {
+    objects: Mutex<BTreeMap<String, Vec<u8>>>,
+}
+
+impl MemoryStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The keys of the stored objects in order
+    pub fn keys(&self) -> Vec<String> {
+        let objects = self.objects.lock().unwrap_or_else(|e| e.into_inner());
+        objects.keys().cloned().collect()
+    }
+}
+
+impl ObjectStore for MemoryStore {
+    fn get(&self, key: &str) -> PersistResult<Option<Vec<u8>>> {
+        let objects = self.objects.lock().unwrap_or_else(|e| e.into_inner());
+        Ok(objects.get(key).cloned())
+    }
+
+    fn put(&self, key: &str, data: &[u8]) -> PersistResult<()> {
+        let mut objects = self.objects.lock().unwrap_or_else(|e| e.into_inner());
+        objects.insert(key.to_string(), data.to_vec());
+        Ok(())
+    }
+}
+
+/// The files of the library in the bucket
+#[derive(Serialize, Deserialize, Debug, Default)]
+struct RemoteManifest {
+    /// The directory of the library that last pushed to the bucket
+    base_dir: PathBuf,
+    files: BTreeMap<String, RemoteFile>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+struct RemoteFile {
+    hash: String,
+    /// Chapters are only downloaded when they are read
+    #[serde(default)]
+    chapter: bool,
+}
+
+/// The files transferred between the library and the bucket
+#[derive(Debug, Default)]
+pub struct SyncReport {
+    pub uploaded: usize,
+    pub downloaded: usize,
+    /// Files that were the same on both sides
+    pub unchanged: usize,
+}
+
+/// Stores the library in a bucket, keeping the local library as a cache
+///
+/// Pushing uploads the files that changed since the last push. Pulling
+/// downloads the novel data but leaves the chapters in the bucket until
+/// [`ObjectStoreStorage::fetch_chapters`] is called for a novel, so that only
+/// the chapters being read are downloaded, and only once.
+#[derive(Debug)]
+pub struct ObjectStoreStorage {
+    store: Box<dyn ObjectStore>,
+}
+
+impl ObjectStoreStorage {
+    pub fn new(store: Box<dyn ObjectStore>) -> Self {
+        Self { store }
+    }
+
+    /// Upload the files of the library that are not in the bucket yet
+    ///
+    /// Files removed from the library are kept in the bucket.
+    pub fn push(&self, persist: &Persist) -> PersistResult<SyncReport> {
+        let options = &persist.options;
+        let mut manifest = self.read_manifest()?;
+        manifest.base_dir = options.base_dir.clone();
+
+        let chapter_dirs = persist
+            .read_global()?
+            .novels()
+            .map(|(_, dir)| persist.persist_novel(dir.clone()).chapters_dir())
+            .collect::<Vec<_>>();
+
+        let mut report = SyncReport::default();
+        for path in library_files(&options.base_dir, &excluded(persist))? {
+            let Some(key) = key_of(&options.base_dir, &path) else {
+                continue;
+            };
+
+            let content = fs::read(&path)?;
+            let file = RemoteFile {
+                hash: hash(&content),
+                chapter: path
+                    .parent()
+                    .is_some_and(|parent| chapter_dirs.iter().any(|dir| dir == parent)),
+            };
+
+            if manifest.files.get(&key) == Some(&file) {
+                report.unchanged += 1;
+                continue;
+            }
+
+            self.store.put(&key, &content)?;
+            manifest.files.insert(key, file);
+            report.uploaded += 1;
+        }
+
+        self.store.put(MANIFEST, &serde_json::to_vec(&manifest)?)?;
+        Ok(report)
+    }
+
+    /// Download the files of the library from the bucket, except the chapters
+    ///
+    /// The remote settings of the library are kept.
+    pub fn pull(&self, persist: &Persist) -> PersistResult<SyncReport> {
+        let options = &persist.options;
+        let manifest = self.read_manifest()?;
+        let remote = persist.read_config()?.remote;
+
+        let mut report = SyncReport::default();
+        for (key, file) in manifest.files.iter().filter(|(_, file)| !file.chapter) {
+            if self.download(persist, key, file)? {
+                report.downloaded += 1;
+            } else {
+                report.unchanged += 1;
+            }
+        }
+
+        let mut config = persist.read_config()?;
+        config.remote = remote;
+        persist.save_config(&config)?;
+
+        if manifest.base_dir != options.base_dir {
+            let mut global = persist.read_global()?;
+            let novels = global
+                .novels()
+                .map(|(url, dir)| {
+                    (
+                        url.clone(),
+                        rebase(dir, &manifest.base_dir, &options.base_dir),
+                    )
+                })
+                .collect::<Vec<_>>();
+
+            for (url, dir) in novels {
+                rebase_cover(persist, &dir, &manifest.base_dir)?;
+                global.insert_novel(url, dir);
+            }
+            persist.save_global(&global)?;
+        }
+
+        Ok(report)
+    }
+
+    /// Download the chapters of the novel that are not in the library yet
+    /// and return the number downloaded
+    pub fn fetch_chapters(&self, persist: &Persist, dir: &Path) -> PersistResult<usize> {
+        let chapters_dir = persist.persist_novel(dir.to_path_buf()).chapters_dir();
+        let Some(prefix) = key_of(&persist.options.base_dir, &chapters_dir) else {
+            return Ok(0);
+        };
+
+        let manifest = self.read_manifest()?;
+        let mut count = 0;
+        for (key, file) in &manifest.files {
+            let in_novel = Path::new(key).parent() == Some(Path::new(&prefix));
+            if file.chapter && in_novel && self.download(persist, key, file)? {
+                count += 1;
+            }
+        }
+
+        Ok(count)
+    }
+
+    /// Download the file unless the library has the same content
+    fn download(&self, persist: &Persist, key: &str, file: &RemoteFile) -> PersistResult<bool> {
+        let path = persist.options.base_dir.join(key);
+        if path.exists() && hash(&fs::read(&path)?) == file.hash {
+            return Ok(false);
+        }
+
+        let Some(content) = self.store.get(key)? else {
+            return Ok(false);
+        };
+
+        // Written aside first, so that an interrupted pull never leaves part of a file
+        let mut partial = path.clone().into_os_string();
+        partial.push(".partial");
+        create_parent_all(&path)?;
+        fs::write(&partial, content)?;
+        fs::rename(partial, path)?;
+        Ok(true)
+    }
+
+    fn read_manifest(&self) -> PersistResult<RemoteManifest> {
+        match self.store.get(MANIFEST)? {
+            Some(data) => Ok(serde_json::from_slice(&data)?),
+            None => Ok(Default::default()),
+        }
+    }
+}
+
+/// Files that stay on the machine
+fn excluded(persist: &Persist) -> [&Path; 4] {
+    [
+        persist.options.cache_dir.as_path(),
+        persist.options.credentials_path.as_path(),
+        persist.options.trash_dir.as_path(),
+        persist.options.lock_path.as_path(),
+    ]
+}
+
+fn hash(content: &[u8]) -> String {
+    format!("{:x}", Sha256::digest(content))
+}
+
+/// The key of the file in the bucket, its path relative to the library joined with slashes
+fn key_of(base_dir: &Path, path: &Path) -> Option<String> {
+    let components = path
+        .strip_prefix(base_dir)
+        .ok()?
+        .components()
+        .map(|component| match component {
+            Component::Normal(value) => value.to_str(),
+            _ => None,
+        })
+        .collect::<Option<Vec<_>>>()?;
+
+    Some(components.join("/"))
+}
+
+#[cfg(test)]
+mod tests {
+    use quelle_core::prelude::Novel;
+
+    use super::*;
+    use crate::{Global, PersistOptions, SavedNovel};
+
+    #[test]
+    fn should_pull_chapters_only_when_fetched() {
+        let root = tempfile::tempdir().unwrap();
+        let storage = ObjectStoreStorage::new(Box::new(DirStore::new(root.path().join("bucket"))));
+
+        let source = Persist::new(PersistOptions::with_base_dir(root.path().join("source")));
+        let dir = source.options.novel.dir.join("example").join("novel");
+        let novel = source.persist_novel(dir.clone());
+        novel
+            .write_data(&SavedNovel::new(Novel::default()))
+            .unwrap();
+        fs::create_dir_all(novel.chapters_dir()).unwrap();
+        fs::write(novel.chapters_dir().join("1.html"), "<p>1</p>").unwrap();
+
+        let mut global = Global::default();
+        global.insert_novel(String::from("https://example.com/novel"), dir);
+        source.save_global(&global).unwrap();
+
+        let report = storage.push(&source).unwrap();
+        assert_eq!(report.uploaded, 4);
+        assert_eq!(storage.push(&source).unwrap().uploaded, 0);
+
+        let target = Persist::new(PersistOptions::with_base_dir(root.path().join("target")));
+        storage.pull(&target).unwrap();
+
+        let global = target.read_global().unwrap();
+        let dir = global
+            .novel_path_from_url("https://example.com/novel")
+            .unwrap();
+        assert!(dir.starts_with(&target.options.base_dir));
+
+        let chapter = dir.join("chapters").join("1.html");
+        assert!(!chapter.exists());
+        assert_eq!(storage.fetch_chapters(&target, dir).unwrap(), 1);
+        assert!(chapter.exists());
+        assert_eq!(storage.fetch_chapters(&target, dir).unwrap(), 0);
+    }
+
+    #[test]
+    fn should_keep_objects_in_memory() {
+        let store = MemoryStore::new();
+        assert_eq!(store.get("novels/novel.json").unwrap(), None);
+
+        store.put("novels/novel.json", b"{}").unwrap();
+        store.put("global.json", b"[]").unwrap();
+        assert_eq!(
+            store.get("novels/novel.json").unwrap(),
+            Some(b"{}".to_vec())
+        );
+        assert_eq!(store.keys(), vec!["global.json", "novels/novel.json"]);
+    }
+}
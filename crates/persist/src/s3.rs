@@ -0,0 +1,192 @@
+use chrono::Utc;
+use hmac::{Hmac, Mac};
+use reqwest::{blocking::Client, Method, StatusCode, Url};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::{
+    error::{PersistError, PersistResult},
+    remote::ObjectStore,
+};
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// Where the library is stored remotely, saved in the library config
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct RemoteConfig {
+    /// The url of an S3 compatible service (ex: https://s3.us-east-1.amazonaws.com)
+    pub endpoint: String,
+    pub bucket: String,
+    #[serde(default = "default_region")]
+    pub region: String,
+    /// Prepended to the keys of the library files in the bucket
+    #[serde(default)]
+    pub prefix: String,
+}
+
+fn default_region() -> String {
+    String::from("us-east-1")
+}
+
+/// Objects in a bucket of an S3 compatible service, such as AWS S3,
+/// Google Cloud Storage with HMAC keys, MinIO or Cloudflare R2
+///
+/// Requests are signed with AWS Signature Version 4 and use path-style urls.
+pub struct S3Store {
+    config: RemoteConfig,
+    access_key: String,
+    secret_key: String,
+    client: Client,
+}
+
+impl std::fmt::Debug for S3Store {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("S3Store")
+            .field("config", &self.config)
+            .finish_non_exhaustive()
+    }
+}
+
+impl S3Store {
+    pub fn new(config: RemoteConfig, access_key: String, secret_key: String) -> Self {
+        Self {
+            config,
+            access_key,
+            secret_key,
+            client: Client::new(),
+        }
+    }
+
+    fn send(
+        &self,
+        method: Method,
+        key: &str,
+        body: Vec<u8>,
+    ) -> PersistResult<reqwest::blocking::Response> {
+        let key = match self.config.prefix.trim_matches('/') {
+            "" => key.to_string(),
+            prefix => format!("{prefix}/{key}"),
+        };
+        let path = format!("/{}/{}", self.config.bucket, encode_path(&key));
+        let url = Url::parse(&format!(
+            "{}{path}",
+            self.config.endpoint.trim_end_matches('/')
+        ))
+        .map_err(|e| PersistError::Remote(e.to_string()))?;
+
+        let host = match (url.host_str(), url.port()) {
+            (Some(host), Some(port)) => format!("{host}:{port}"),
+            (Some(host), None) => host.to_string(),
+            (None, _) => return Err(PersistError::Remote(format!("invalid endpoint '{url}'"))),
+        };
+
+        let now = Utc::now();
+        let amz_date = now.format("%Y%m%dT%H%M%SZ").to_string();
+        let date = now.format("%Y%m%d").to_string();
+        let payload_hash = format!("{:x}", Sha256::digest(&body));
+
+        let signed_headers = "host;x-amz-content-sha256;x-amz-date";
+        let canonical_request = format!(
+            "{method}\n{path}\n\nhost:{host}\nx-amz-content-sha256:{payload_hash}\nx-amz-date:{amz_date}\n\n{signed_headers}\n{payload_hash}"
+        );
+
+        let scope = format!("{date}/{}/s3/aws4_request", self.config.region);
+        let string_to_sign = format!(
+            "AWS4-HMAC-SHA256\n{amz_date}\n{scope}\n{:x}",
+            Sha256::digest(canonical_request.as_bytes())
+        );
+
+        let signing_key = [
+            date.as_bytes(),
+            self.config.region.as_bytes(),
+            b"s3",
+            b"aws4_request",
+        ]
+        .into_iter()
+        .fold(
+            format!("AWS4{}", self.secret_key).into_bytes(),
+            |key, data| hmac(&key, data),
+        );
+        let signature = hex(&hmac(&signing_key, string_to_sign.as_bytes()));
+
+        let authorization = format!(
+            "AWS4-HMAC-SHA256 Credential={}/{scope}, SignedHeaders={signed_headers}, Signature={signature}",
+            self.access_key
+        );
+
+        self.client
+            .request(method, url)
+            .header("x-amz-date", amz_date)
+            .header("x-amz-content-sha256", payload_hash)
+            .header("authorization", authorization)
+            .body(body)
+            .send()
+            .map_err(|e| PersistError::Remote(e.to_string()))
+    }
+}
+
+impl ObjectStore for S3Store {
+    fn get(&self, key: &str) -> PersistResult<Option<Vec<u8>>> {
+        let response = self.send(Method::GET, key, vec![])?;
+        match response.status() {
+            StatusCode::NOT_FOUND => Ok(None),
+            status if status.is_success() => {
+                let body = response
+                    .bytes()
+                    .map_err(|e| PersistError::Remote(e.to_string()))?;
+                Ok(Some(body.to_vec()))
+            }
+            status => Err(PersistError::Remote(format!(
+                "GET '{key}' returned {status}"
+            ))),
+        }
+    }
+
+    fn put(&self, key: &str, data: &[u8]) -> PersistResult<()> {
+        let response = self.send(Method::PUT, key, data.to_vec())?;
+        match response.status() {
+            status if status.is_success() => Ok(()),
+            status => Err(PersistError::Remote(format!(
+                "PUT '{key}' returned {status}"
+            ))),
+        }
+    }
+}
+
+fn hmac(key: &[u8], data: &[u8]) -> Vec<u8> {
+    let mut mac = HmacSha256::new_from_slice(key).expect("HMAC accepts keys of any length");
+    mac.update(data);
+    mac.finalize().into_bytes().to_vec()
+}
+
+fn hex(data: &[u8]) -> String {
+    data.iter().map(|byte| format!("{byte:02x}")).collect()
+}
+
+/// Percent-encode the key as required by the signature, keeping the slashes
+pub(crate) fn encode_path(key: &str) -> String {
+    let mut encoded = String::with_capacity(key.len());
+    for byte in key.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' | b'/' => {
+                encoded.push(byte as char)
+            }
+            _ => encoded.push_str(&format!("%{byte:02X}")),
+        }
+    }
+    encoded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_encode_keys_for_signature() {
+        assert_eq!(
+            encode_path("novels/royalroad/the novel/chapters/1.html"),
+            "novels/royalroad/the%20novel/chapters/1.html"
+        );
+        assert_eq!(encode_path("a+b=c"), "a%2Bb%3Dc");
+    }
+}
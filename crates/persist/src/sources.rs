@@ -0,0 +1,202 @@
+use std::{
+    collections::HashMap,
+    fs::{File, OpenOptions},
+    io::{BufReader, BufWriter},
+    path::Path,
+    str::FromStr,
+};
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::{create_parent_all, error::PersistResult};
+
+/// The number of attempts before the success rate of an executor is trusted
+const MIN_ATTEMPTS: u32 = 3;
+
+/// The success rate below which an executor is considered not working
+const MIN_SUCCESS_RATE: f64 = 0.5;
+
+/// How an extension is executed, from cheapest to most expensive
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[serde(rename_all = "snake_case")]
+pub enum Executor {
+    /// The extension runs inside the calling process
+    InProcess,
+    /// The extension runs in a separate worker process
+    Isolated,
+}
+
+impl Executor {
+    pub const ALL: [Executor; 2] = [Executor::InProcess, Executor::Isolated];
+}
+
+impl FromStr for Executor {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().replace('-', "_").as_str() {
+            "in_process" => Ok(Executor::InProcess),
+            "isolated" => Ok(Executor::Isolated),
+            _ => Err(format!("unsupported executor '{s}'")),
+        }
+    }
+}
+
+impl std::fmt::Display for Executor {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let value = match self {
+            Executor::InProcess => "in_process",
+            Executor::Isolated => "isolated",
+        };
+        write!(f, "{value}")
+    }
+}
+
+/// Tracks which executors succeed for each source so that
+/// the cheapest working executor can be chosen by default.
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct SourceStats {
+    sources: HashMap<String, HashMap<Executor, ExecutorStats>>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
+pub struct ExecutorStats {
+    pub successes: u32,
+    pub failures: u32,
+    pub last_success: Option<DateTime<Utc>>,
+    pub last_failure: Option<DateTime<Utc>>,
+}
+
+impl ExecutorStats {
+    pub fn attempts(&self) -> u32 {
+        self.successes + self.failures
+    }
+
+    pub fn success_rate(&self) -> Option<f64> {
+        match self.attempts() {
+            0 => None,
+            attempts => Some(self.successes as f64 / attempts as f64),
+        }
+    }
+
+    /// Whether the executor has failed often enough to be avoided
+    pub fn is_failing(&self) -> bool {
+        self.attempts() >= MIN_ATTEMPTS
+            && self
+                .success_rate()
+                .is_some_and(|rate| rate < MIN_SUCCESS_RATE)
+    }
+}
+
+impl SourceStats {
+    pub fn open(path: &Path) -> PersistResult<Self> {
+        let data = if path.exists() {
+            let file = File::open(path)?;
+            let reader = BufReader::new(file);
+            serde_json::from_reader(reader)?
+        } else {
+            Default::default()
+        };
+
+        Ok(data)
+    }
+
+    pub fn save(&self, path: &Path) -> PersistResult<()> {
+        create_parent_all(path)?;
+
+        let file = OpenOptions::new()
+            .create(true)
+            .truncate(true)
+            .write(true)
+            .open(path)?;
+
+        let writer = BufWriter::new(file);
+        serde_json::to_writer(writer, self)?;
+
+        Ok(())
+    }
+
+    /// The ids of the sources with recorded statistics
+    pub fn sources(&self) -> impl Iterator<Item = &String> {
+        self.sources.keys()
+    }
+
+    pub fn stats(&self, source: &str, executor: Executor) -> Option<&ExecutorStats> {
+        self.sources.get(source)?.get(&executor)
+    }
+
+    pub fn record_success(&mut self, source: &str, executor: Executor, now: DateTime<Utc>) {
+        let stats = self.entry(source, executor);
+        stats.successes += 1;
+        stats.last_success = Some(now);
+    }
+
+    pub fn record_failure(&mut self, source: &str, executor: Executor, now: DateTime<Utc>) {
+        let stats = self.entry(source, executor);
+        stats.failures += 1;
+        stats.last_failure = Some(now);
+    }
+
+    /// The cheapest executor that is not known to fail for the source
+    pub fn preferred(&self, source: &str) -> Executor {
+        Executor::ALL
+            .into_iter()
+            .find(|executor| {
+                !self
+                    .stats(source, *executor)
+                    .is_some_and(ExecutorStats::is_failing)
+            })
+            .unwrap_or(Executor::InProcess)
+    }
+
+    fn entry(&mut self, source: &str, executor: Executor) -> &mut ExecutorStats {
+        self.sources
+            .entry(source.to_string())
+            .or_default()
+            .entry(executor)
+            .or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Utc;
+
+    use super::{Executor, SourceStats};
+
+    #[test]
+    fn should_prefer_cheapest_working_executor() {
+        let mut stats = SourceStats::default();
+        let now = Utc::now();
+        assert_eq!(stats.preferred("novelpub"), Executor::InProcess);
+
+        for _ in 0..3 {
+            stats.record_failure("novelpub", Executor::InProcess, now);
+        }
+        assert_eq!(stats.preferred("novelpub"), Executor::Isolated);
+
+        for _ in 0..4 {
+            stats.record_success("novelpub", Executor::InProcess, now);
+        }
+        assert_eq!(stats.preferred("novelpub"), Executor::InProcess);
+    }
+
+    #[test]
+    fn should_roundtrip_executor_keys() {
+        let mut stats = SourceStats::default();
+        stats.record_success("novelpub", Executor::Isolated, Utc::now());
+
+        let json = serde_json::to_string(&stats).unwrap();
+        assert!(json.contains(r#""isolated""#));
+
+        let stats = serde_json::from_str::<SourceStats>(&json).unwrap();
+        assert_eq!(
+            stats
+                .stats("novelpub", Executor::Isolated)
+                .unwrap()
+                .successes,
+            1
+        );
+    }
+}
@@ -0,0 +1,102 @@
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{error::PersistResult, versions::text_lines, Persist, PersistNovel, SavedNovel};
+
+/// The reading speed used to estimate reading times
+pub const WORDS_PER_MINUTE: u64 = 250;
+
+/// The number of words in the text of the chapter content
+pub fn count_words(html: &str) -> u64 {
+    text_lines(html)
+        .iter()
+        .map(|line| line.split_whitespace().count() as u64)
+        .sum()
+}
+
+/// Statistics of a single novel
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct NovelStats {
+    pub chapters: usize,
+    pub downloaded: usize,
+    /// The words of the downloaded chapters
+    pub words: u64,
+}
+
+/// Statistics of every novel in the library
+#[derive(Serialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct StorageStats {
+    pub novels: usize,
+    pub chapters: usize,
+    pub downloaded: usize,
+    pub words: u64,
+}
+
+impl NovelStats {
+    pub fn reading_time(&self) -> Duration {
+        reading_time(self.words)
+    }
+}
+
+impl StorageStats {
+    pub fn reading_time(&self) -> Duration {
+        reading_time(self.words)
+    }
+
+    fn add(&mut self, stats: NovelStats) {
+        self.novels += 1;
+        self.chapters += stats.chapters;
+        self.downloaded += stats.downloaded;
+        self.words += stats.words;
+    }
+}
+
+fn reading_time(words: u64) -> Duration {
+    Duration::from_secs(words * 60 / WORDS_PER_MINUTE)
+}
+
+/// Sum the statistics of every novel, see [`Persist::library_stats`]
+pub(crate) fn library_stats(persist: &Persist) -> PersistResult<StorageStats> {
+    let mut stats = StorageStats::default();
+    for (_, dir) in persist.read_global()?.novels() {
+        if let Some(data) = persist.persist_novel(dir.clone()).read_data()? {
+            stats.add(data.stats());
+        }
+    }
+
+    Ok(stats)
+}
+
+/// Count the words of the downloaded chapters that have no word count
+///
+/// Returns whether any count was added.
+pub(crate) fn count_missing_words(
+    novel: &PersistNovel,
+    data: &mut SavedNovel,
+) -> PersistResult<bool> {
+    let mut changed = false;
+    for (url, path) in &data.downloaded {
+        if data.word_counts.contains_key(url) || !novel.dir().join(path).exists() {
+            continue;
+        }
+
+        let words = count_words(&novel.read_chapter(path)?);
+        data.word_counts.insert(url.clone(), words);
+        changed = true;
+    }
+
+    Ok(changed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_count_words_of_text() {
+        assert_eq!(count_words("<p>One two</p><p>three <em>four</em></p>"), 4);
+        assert_eq!(count_words(""), 0);
+        assert_eq!(reading_time(500), Duration::from_secs(120));
+    }
+}
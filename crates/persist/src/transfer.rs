@@ -0,0 +1,275 @@
+use std::{
+    collections::BTreeSet,
+    fs::{self, File, OpenOptions},
+    io::{BufReader, BufWriter},
+    path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    compression::{read_content, write_content, Compression},
+    create_parent_all,
+    error::{PersistError, PersistResult},
+    CoverLoc, Persist,
+};
+
+/// The progress of a transfer between libraries
+#[derive(Debug)]
+pub enum TransferEvent<'a> {
+    /// The novel was copied with its chapters and assets
+    Copied {
+        title: &'a str,
+        number: usize,
+        total: usize,
+        chapters: usize,
+    },
+    /// The novel was copied by a previous run that was interrupted
+    Skipped {
+        url: &'a str,
+        number: usize,
+        total: usize,
+    },
+}
+
+/// What was copied to the other library
+#[derive(Debug, Default)]
+pub struct TransferReport {
+    pub novels: usize,
+    /// Novels copied by a previous run
+    pub skipped: usize,
+    pub chapters: usize,
+    pub assets: usize,
+}
+
+/// The novels already copied, so that an interrupted transfer can continue
+#[derive(Serialize, Deserialize, Debug, Default)]
+struct TransferJournal {
+    completed: BTreeSet<String>,
+}
+
+impl TransferJournal {
+    fn path(persist: &Persist) -> PathBuf {
+        persist.options.base_dir.join("transfer.json")
+    }
+
+    fn open(persist: &Persist) -> PersistResult<Self> {
+        let path = Self::path(persist);
+        let journal = if path.exists() {
+            let file = File::open(path)?;
+            serde_json::from_reader(BufReader::new(file))?
+        } else {
+            Default::default()
+        };
+
+        Ok(journal)
+    }
+
+    fn save(&self, persist: &Persist) -> PersistResult<()> {
+        let path = Self::path(persist);
+        create_parent_all(&path)?;
+
+        let file = OpenOptions::new()
+            .create(true)
+            .truncate(true)
+            .write(true)
+            .open(path)?;
+
+        serde_json::to_writer(BufWriter::new(file), self)?;
+        Ok(())
+    }
+}
+
+/// Copy every novel with its chapters, covers and assets into another library
+///
+/// Content is written with the compression and encryption of the other library
+/// and read back to verify it. Novels are registered in the other library as
+/// soon as they are copied, and running the transfer again after it was
+/// interrupted continues with the remaining novels.
+pub fn transfer<F>(from: &Persist, to: &Persist, mut progress: F) -> PersistResult<TransferReport>
+where
+    F: FnMut(TransferEvent),
+{
+    let compression = to.read_config()?.compression;
+    let mut journal = TransferJournal::open(to)?;
+    let mut global = to.read_global()?;
+    let mut report = TransferReport::default();
+
+    let source = from.read_global()?;
+    let novels = source.novels().collect::<Vec<_>>();
+    let total = novels.len();
+
+    for (index, (url, dir)) in novels.into_iter().enumerate() {
+        let number = index + 1;
+        if journal.completed.contains(url) {
+            report.skipped += 1;
+            progress(TransferEvent::Skipped { url, number, total });
+            continue;
+        }
+
+        let novel = from.persist_novel(dir.clone());
+        let Some(mut data) = novel.read_data()? else {
+            continue;
+        };
+
+        let target = match from.novel_id(dir) {
+            Some(id) => to.options.novel.dir.join(&id.source).join(&id.slug),
+            None => to
+                .options
+                .novel
+                .dir
+                .join(dir.file_name().unwrap_or_default()),
+        };
+        let target = to.persist_novel(target);
+
+        let mut chapters = 0;
+        for path in data.downloaded.values_mut() {
+            let current = dir.join(&*path);
+            if !current.exists() {
+                continue;
+            }
+
+            let content = read_content(&current, from.cipher())?;
+            let name = match Compression::of_path(path) {
+                Compression::None => path.clone(),
+                _ => path.with_extension(""),
+            };
+
+            let destination = target.dir().join(name);
+            create_parent_all(&destination)?;
+            let written = write_content(destination, &content, compression, to.cipher())?;
+            if read_content(&written, to.cipher())? != content {
+                return Err(PersistError::TransferMismatch(written));
+            }
+
+            *path = target.relative_path(written);
+            chapters += 1;
+        }
+
+        let source_assets = novel.read_assets()?;
+        let mut assets = target.read_assets()?;
+        for asset in source_assets.list_assets() {
+            let Some((_, content)) = source_assets.get_asset(&asset.url)? else {
+                continue;
+            };
+
+            let stored = assets.store_asset(&asset.url, &asset.content_type, &content)?;
+            if stored.hash != asset.hash {
+                return Err(PersistError::TransferMismatch(target.assets_dir()));
+            }
+            report.assets += 1;
+        }
+        assets.save()?;
+
+        data.cover = data
+            .cover
+            .take()
+            .map(|cover| copy_cover(cover, target.dir()))
+            .transpose()?;
+        data.overrides.cover = data
+            .overrides
+            .cover
+            .take()
+            .map(|cover| copy_cover(cover, target.dir()))
+            .transpose()?;
+        data.cover_history = data
+            .cover_history
+            .into_iter()
+            .map(|cover| copy_cover(cover, &target.cover_history_dir()))
+            .collect::<PersistResult<_>>()?;
+
+        target.write_data(&data)?;
+
+        global.insert_novel(url.clone(), target.dir().to_path_buf());
+        to.save_global(&global)?;
+
+        journal.completed.insert(url.clone());
+        journal.save(to)?;
+
+        report.novels += 1;
+        report.chapters += chapters;
+        progress(TransferEvent::Copied {
+            title: &data.novel.title,
+            number,
+            total,
+            chapters,
+        });
+    }
+
+    let path = TransferJournal::path(to);
+    if path.exists() {
+        fs::remove_file(path)?;
+    }
+
+    Ok(report)
+}
+
+fn copy_cover(cover: CoverLoc, dir: &Path) -> PersistResult<CoverLoc> {
+    if !cover.path.exists() {
+        return Ok(cover);
+    }
+
+    let Some(name) = cover.path.file_name() else {
+        return Ok(cover);
+    };
+
+    let path = dir.join(name);
+    create_parent_all(&path)?;
+    fs::copy(&cover.path, &path)?;
+
+    if fs::read(&cover.path)? != fs::read(&path)? {
+        return Err(PersistError::TransferMismatch(path));
+    }
+
+    Ok(CoverLoc { path, ..cover })
+}
+
+#[cfg(test)]
+mod tests {
+    use quelle_core::prelude::Novel;
+
+    use super::*;
+    use crate::{Global, LibraryConfig, PersistOptions, SavedNovel};
+
+    #[test]
+    fn should_transfer_novels_with_target_compression() {
+        let root = tempfile::tempdir().unwrap();
+        let from = Persist::new(PersistOptions::with_base_dir(root.path().join("from")));
+        let to = Persist::new(PersistOptions::with_base_dir(root.path().join("to")));
+
+        let dir = from.options.novel.dir.join("example").join("novel");
+        let novel = from.persist_novel(dir.clone());
+        let mut data = SavedNovel::new(Novel::default());
+        fs::create_dir_all(novel.chapters_dir()).unwrap();
+        fs::write(novel.chapters_dir().join("1.html"), "<p>1</p>").unwrap();
+        data.downloaded.insert(
+            String::from("https://example.com/novel/1"),
+            PathBuf::from("chapters/1.html"),
+        );
+        novel.write_data(&data).unwrap();
+
+        let mut global = Global::default();
+        global.insert_novel(String::from("https://example.com/novel"), dir);
+        from.save_global(&global).unwrap();
+
+        to.save_config(&LibraryConfig {
+            compression: Compression::Zstd,
+            ..Default::default()
+        })
+        .unwrap();
+
+        let report = transfer(&from, &to, |_| {}).unwrap();
+        assert_eq!(report.novels, 1);
+        assert_eq!(report.chapters, 1);
+
+        let global = to.read_global().unwrap();
+        let dir = global
+            .novel_path_from_url("https://example.com/novel")
+            .unwrap();
+        let novel = to.persist_novel(dir.to_path_buf());
+        let data = novel.read_data().unwrap().unwrap();
+        let path = &data.downloaded["https://example.com/novel/1"];
+        assert_eq!(path, Path::new("chapters/1.html.zst"));
+        assert_eq!(novel.read_chapter(path).unwrap(), "<p>1</p>");
+    }
+}
@@ -0,0 +1,269 @@
+use std::{
+    fs::{self, File, OpenOptions},
+    io::{BufReader, BufWriter},
+    path::{Path, PathBuf},
+};
+
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    create_parent_all,
+    error::{PersistError, PersistResult},
+    Persist,
+};
+
+/// The file listing the novels in the trash directory
+const MANIFEST: &str = "trash.json";
+
+/// A novel removed from the library that can still be restored
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct TrashedNovel {
+    pub url: String,
+    pub title: String,
+    /// The directory the novel was saved in before it was deleted
+    pub dir: PathBuf,
+    /// The directory of the novel inside the trash
+    pub trashed_dir: PathBuf,
+    /// The collections the novel was part of
+    #[serde(default)]
+    pub collections: Vec<String>,
+    pub deleted_at: DateTime<Utc>,
+}
+
+/// The novels in the trash, the most recently deleted last
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct Trash {
+    novels: Vec<TrashedNovel>,
+}
+
+impl Trash {
+    pub fn open(path: &Path) -> PersistResult<Self> {
+        let data = if path.exists() {
+            let file = File::open(path)?;
+            let reader = BufReader::new(file);
+            serde_json::from_reader(reader)?
+        } else {
+            Default::default()
+        };
+
+        Ok(data)
+    }
+
+    pub fn save(&self, path: &Path) -> PersistResult<()> {
+        create_parent_all(path)?;
+
+        let file = OpenOptions::new()
+            .create(true)
+            .truncate(true)
+            .write(true)
+            .open(path)?;
+
+        let writer = BufWriter::new(file);
+        serde_json::to_writer_pretty(writer, self)?;
+
+        Ok(())
+    }
+
+    pub fn novels(&self) -> impl Iterator<Item = &TrashedNovel> {
+        self.novels.iter()
+    }
+
+    /// The most recently deleted novel saved from the url
+    fn position(&self, url: &str) -> Option<usize> {
+        self.novels.iter().rposition(|novel| novel.url == url)
+    }
+}
+
+pub(crate) fn manifest_path(trash_dir: &Path) -> PathBuf {
+    trash_dir.join(MANIFEST)
+}
+
+/// Move the novel saved from the url into the trash, see [`Persist::delete_novel`]
+pub(crate) fn delete_novel(persist: &Persist, url: &str) -> PersistResult<Option<TrashedNovel>> {
+    let mut global = persist.read_global()?;
+    let Some(dir) = global.novel_path_from_url(url).map(Path::to_path_buf) else {
+        return Ok(None);
+    };
+
+    // The url may have been given with a trailing slash the library does not use
+    let url = global
+        .novels()
+        .find(|(_, path)| **path == dir)
+        .map(|(url, _)| url.clone())
+        .unwrap_or_else(|| url.to_string());
+
+    let title = persist
+        .persist_novel(dir.clone())
+        .read_data()
+        .ok()
+        .flatten()
+        .map(|data| data.novel.title)
+        .unwrap_or_default();
+
+    let deleted_at = Utc::now();
+    let name = dir
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let trashed_dir = persist
+        .options
+        .trash_dir
+        .join(format!("{}-{name}", deleted_at.timestamp_millis()));
+
+    if dir.exists() {
+        create_parent_all(&trashed_dir)?;
+        fs::rename(&dir, &trashed_dir)?;
+    }
+
+    let mut collections = persist.read_collections()?;
+    let names = collections
+        .collections_of(&url)
+        .cloned()
+        .collect::<Vec<_>>();
+    for name in &names {
+        collections.remove_from_collection(name, &url);
+    }
+
+    let novel = TrashedNovel {
+        url: url.clone(),
+        title,
+        dir,
+        trashed_dir,
+        collections: names,
+        deleted_at,
+    };
+
+    let path = manifest_path(&persist.options.trash_dir);
+    let mut trash = Trash::open(&path)?;
+    trash.novels.push(novel.clone());
+    trash.save(&path)?;
+
+    global.remove_novel(&url);
+    persist.save_global(&global)?;
+    persist.save_collections(&collections)?;
+
+    Ok(Some(novel))
+}
+
+/// Move the novel back into the library, see [`Persist::restore_novel`]
+pub(crate) fn restore_novel(persist: &Persist, url: &str) -> PersistResult<Option<TrashedNovel>> {
+    let path = manifest_path(&persist.options.trash_dir);
+    let mut trash = Trash::open(&path)?;
+    let Some(position) = trash.position(url) else {
+        return Ok(None);
+    };
+
+    let mut global = persist.read_global()?;
+    let novel = &trash.novels[position];
+    if global.novel_path_from_url(&novel.url).is_some() || novel.dir.exists() {
+        return Err(PersistError::NovelExists(novel.url.clone()));
+    }
+
+    if novel.trashed_dir.exists() {
+        create_parent_all(&novel.dir)?;
+        fs::rename(&novel.trashed_dir, &novel.dir)?;
+    }
+
+    let novel = trash.novels.remove(position);
+    trash.save(&path)?;
+
+    let mut collections = persist.read_collections()?;
+    for name in &novel.collections {
+        collections.add_to_collection(name, &novel.url);
+    }
+    persist.save_collections(&collections)?;
+
+    global.insert_novel(novel.url.clone(), novel.dir.clone());
+    if let Ok(Some(data)) = persist.persist_novel(novel.dir.clone()).read_data() {
+        for mirror in data.mirrors {
+            global.insert_alias(mirror, novel.url.clone());
+        }
+    }
+    persist.save_global(&global)?;
+
+    Ok(Some(novel))
+}
+
+/// Delete the novels kept in the trash for longer than the retention, see [`Persist::purge_trash`]
+pub(crate) fn purge_trash(
+    persist: &Persist,
+    retention: Duration,
+) -> PersistResult<Vec<TrashedNovel>> {
+    let path = manifest_path(&persist.options.trash_dir);
+    let mut trash = Trash::open(&path)?;
+    if trash.novels.is_empty() {
+        return Ok(vec![]);
+    }
+
+    let now = Utc::now();
+    let (purged, kept) = trash
+        .novels
+        .into_iter()
+        .partition::<Vec<_>, _>(|novel| now - novel.deleted_at >= retention);
+
+    for novel in &purged {
+        if novel.trashed_dir.exists() {
+            fs::remove_dir_all(&novel.trashed_dir)?;
+        }
+    }
+
+    trash.novels = kept;
+    trash.save(&path)?;
+
+    Ok(purged)
+}
+
+#[cfg(test)]
+mod tests {
+    use quelle_core::prelude::Novel;
+
+    use super::*;
+    use crate::{Global, PersistOptions, SavedNovel};
+
+    #[test]
+    fn should_restore_deleted_novel_until_purged() {
+        let root = tempfile::tempdir().unwrap();
+        let persist = Persist::new(PersistOptions::with_base_dir(root.path().to_path_buf()));
+        let url = "https://example.com/novel";
+
+        let dir = persist.options.novel.dir.join("example").join("novel");
+        let novel = Novel {
+            title: String::from("Novel"),
+            ..Default::default()
+        };
+        persist
+            .persist_novel(dir.clone())
+            .write_data(&SavedNovel::new(novel))
+            .unwrap();
+
+        let mut global = Global::default();
+        global.insert_novel(String::from(url), dir.clone());
+        persist.save_global(&global).unwrap();
+
+        let mut collections = persist.read_collections().unwrap();
+        collections.add_to_collection("Reading", url);
+        persist.save_collections(&collections).unwrap();
+
+        let trashed = persist.delete_novel(url).unwrap().unwrap();
+        assert_eq!(trashed.title, "Novel");
+        assert!(!dir.exists());
+        assert!(trashed.trashed_dir.exists());
+        assert!(persist.read_global().unwrap().novels().next().is_none());
+
+        let restored = persist.restore_novel(url).unwrap().unwrap();
+        assert_eq!(restored.collections, ["Reading"]);
+        assert!(dir.exists());
+        assert!(persist
+            .read_global()
+            .unwrap()
+            .novel_path_from_url(url)
+            .is_some());
+
+        let trashed = persist.delete_novel(url).unwrap().unwrap();
+        assert!(persist.purge_trash(Duration::days(30)).unwrap().is_empty());
+        assert_eq!(persist.purge_trash(Duration::zero()).unwrap().len(), 1);
+        assert!(persist.restore_novel(url).unwrap().is_none());
+        assert!(!trashed.trashed_dir.exists());
+    }
+}
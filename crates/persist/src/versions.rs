@@ -0,0 +1,197 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use chrono::{DateTime, TimeZone, Utc};
+
+use crate::{
+    compression::{self, Compression},
+    create_parent_all,
+    encryption::Cipher,
+    error::PersistResult,
+};
+
+/// Content a chapter had before it was fetched again and found to differ
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChapterVersion {
+    pub path: PathBuf,
+    /// When the content was replaced
+    pub replaced_at: DateTime<Utc>,
+}
+
+/// A line of the difference between two versions of a chapter
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DiffLine {
+    Same(String),
+    Removed(String),
+    Added(String),
+}
+
+/// Elements that start a new line of text
+const BLOCK_TAGS: [&str; 16] = [
+    "p",
+    "br",
+    "div",
+    "h1",
+    "h2",
+    "h3",
+    "h4",
+    "h5",
+    "h6",
+    "li",
+    "tr",
+    "hr",
+    "blockquote",
+    "pre",
+    "section",
+    "article",
+];
+
+/// Keep the file saved at the path as a version of the chapter when the new content differs
+///
+/// The file is removed instead when its content is the same, so that a file of
+/// another compression is not left behind.
+pub(crate) fn archive(
+    versions_dir: &Path,
+    path: &Path,
+    content: &str,
+    cipher: Option<&Cipher>,
+) -> PersistResult<Option<ChapterVersion>> {
+    if compression::read_content(path, cipher)? == content {
+        fs::remove_file(path)?;
+        return Ok(None);
+    }
+
+    let replaced_at = Utc::now();
+    let name = format!("{}.html", replaced_at.timestamp_millis());
+    let version = Compression::of_path(path).apply_to(versions_dir.join(name));
+
+    create_parent_all(&version)?;
+    fs::rename(path, &version)?;
+
+    Ok(Some(ChapterVersion {
+        path: version,
+        replaced_at,
+    }))
+}
+
+/// The versions kept in the directory, oldest first
+pub(crate) fn list(versions_dir: &Path) -> PersistResult<Vec<ChapterVersion>> {
+    if !versions_dir.exists() {
+        return Ok(vec![]);
+    }
+
+    let mut versions = vec![];
+    for entry in fs::read_dir(versions_dir)? {
+        let path = entry?.path();
+        let millis = path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .and_then(|name| name.split('.').next())
+            .and_then(|stem| stem.parse::<i64>().ok());
+
+        if let Some(replaced_at) =
+            millis.and_then(|millis| Utc.timestamp_millis_opt(millis).single())
+        {
+            versions.push(ChapterVersion { path, replaced_at });
+        }
+    }
+
+    versions.sort_by_key(|version| version.replaced_at);
+    Ok(versions)
+}
+
+/// The text of the chapter content, one line per paragraph
+pub fn text_lines(html: &str) -> Vec<String> {
+    let mut lines = vec![];
+    let mut line = String::new();
+    let mut rest = html;
+
+    while let Some(start) = rest.find('<') {
+        line.push_str(&rest[..start]);
+        let Some(end) = rest[start..].find('>') else {
+            rest = &rest[start..];
+            break;
+        };
+
+        let tag = rest[start + 1..start + end]
+            .trim_start_matches('/')
+            .split(|c: char| c.is_whitespace() || c == '/')
+            .next()
+            .unwrap_or_default()
+            .to_ascii_lowercase();
+
+        if BLOCK_TAGS.contains(&tag.as_str()) {
+            lines.push(std::mem::take(&mut line));
+        }
+        rest = &rest[start + end + 1..];
+    }
+
+    line.push_str(rest);
+    lines.push(line);
+
+    lines
+        .into_iter()
+        .map(|line| line.split_whitespace().collect::<Vec<_>>().join(" "))
+        .filter(|line| !line.is_empty())
+        .collect()
+}
+
+/// The lines removed from the old text and added in the new text
+pub fn diff_lines(old: &[String], new: &[String]) -> Vec<DiffLine> {
+    // Length of the longest common subsequence of the remaining lines
+    let mut lengths = vec![vec![0usize; new.len() + 1]; old.len() + 1];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            lengths[i][j] = if old[i] == new[j] {
+                lengths[i + 1][j + 1] + 1
+            } else {
+                lengths[i + 1][j].max(lengths[i][j + 1])
+            };
+        }
+    }
+
+    let (mut i, mut j) = (0, 0);
+    let mut diff = vec![];
+    while i < old.len() && j < new.len() {
+        if old[i] == new[j] {
+            diff.push(DiffLine::Same(old[i].clone()));
+            i += 1;
+            j += 1;
+        } else if lengths[i + 1][j] >= lengths[i][j + 1] {
+            diff.push(DiffLine::Removed(old[i].clone()));
+            i += 1;
+        } else {
+            diff.push(DiffLine::Added(new[j].clone()));
+            j += 1;
+        }
+    }
+
+    diff.extend(old[i..].iter().cloned().map(DiffLine::Removed));
+    diff.extend(new[j..].iter().cloned().map(DiffLine::Added));
+    diff
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_diff_paragraphs() {
+        let old = text_lines("<p>First</p><p>Second  line</p>\n<p>Third</p>");
+        let new = text_lines("<p>First</p><p>Second <em>edited</em></p><p>Third</p><br/>Fourth");
+        assert_eq!(old, ["First", "Second line", "Third"]);
+
+        assert_eq!(
+            diff_lines(&old, &new),
+            [
+                DiffLine::Same(String::from("First")),
+                DiffLine::Removed(String::from("Second line")),
+                DiffLine::Added(String::from("Second edited")),
+                DiffLine::Same(String::from("Third")),
+                DiffLine::Added(String::from("Fourth")),
+            ]
+        );
+    }
+}
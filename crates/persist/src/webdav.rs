@@ -0,0 +1,283 @@
+use std::{collections::HashSet, sync::Mutex};
+
+use reqwest::{
+    blocking::{Client, RequestBuilder},
+    Method, StatusCode, Url,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    error::{PersistError, PersistResult},
+    remote::ObjectStore,
+    s3::encode_path,
+};
+
+/// The extension of the files uploaded before they are moved to their key
+const PARTIAL: &str = ".partial";
+
+/// A WebDAV share the library is stored in, saved in the library config
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct WebDavConfig {
+    /// The url of the directory holding the library
+    /// (ex: https://cloud.example.com/remote.php/dav/files/user/quelle)
+    pub url: String,
+    #[serde(default)]
+    pub username: Option<String>,
+}
+
+/// Objects stored as files of a WebDAV share, such as Nextcloud or ownCloud
+///
+/// Files are uploaded under a temporary name and moved over the previous
+/// file once complete, so that an interrupted upload never replaces a file
+/// with part of its content.
+pub struct WebDavStore {
+    base: Url,
+    username: Option<String>,
+    password: Option<String>,
+    client: Client,
+    /// The directories known to exist on the share
+    created: Mutex<HashSet<String>>,
+}
+
+impl std::fmt::Debug for WebDavStore {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("WebDavStore")
+            .field("base", &self.base)
+            .field("username", &self.username)
+            .finish_non_exhaustive()
+    }
+}
+
+impl WebDavStore {
+    pub fn new(config: WebDavConfig, password: Option<String>) -> PersistResult<Self> {
+        // Keys are joined to the url, which must then end with a slash
+        let url = format!("{}/", config.url.trim_end_matches('/'));
+        let base = Url::parse(&url).map_err(|e| PersistError::Remote(e.to_string()))?;
+
+        Ok(Self {
+            base,
+            username: config.username,
+            password,
+            client: Client::new(),
+            created: Mutex::new(HashSet::new()),
+        })
+    }
+
+    fn url(&self, key: &str) -> PersistResult<Url> {
+        self.base
+            .join(&encode_path(key))
+            .map_err(|e| PersistError::Remote(e.to_string()))
+    }
+
+    fn request(&self, method: Method, url: Url) -> RequestBuilder {
+        let request = self.client.request(method, url);
+        match &self.username {
+            Some(username) => request.basic_auth(username, self.password.as_ref()),
+            None => request,
+        }
+    }
+
+    fn send(&self, request: RequestBuilder) -> PersistResult<reqwest::blocking::Response> {
+        request
+            .send()
+            .map_err(|e| PersistError::Remote(e.to_string()))
+    }
+
+    /// Create the directories the key is in, as WebDAV does not create them on upload
+    fn create_parents(&self, key: &str) -> PersistResult<()> {
+        let mut created = self.created.lock().unwrap_or_else(|e| e.into_inner());
+
+        for (end, _) in key.match_indices('/') {
+            let parent = &key[..=end];
+            if created.contains(parent) {
+                continue;
+            }
+
+            let mkcol = Method::from_bytes(b"MKCOL").expect("MKCOL is a valid method");
+            let response = self.send(self.request(mkcol, self.url(parent)?))?;
+            match response.status() {
+                // An existing directory is reported as not allowed
+                status if status.is_success() || status == StatusCode::METHOD_NOT_ALLOWED => {
+                    created.insert(parent.to_string());
+                }
+                status => {
+                    return Err(PersistError::Remote(format!(
+                        "MKCOL '{parent}' returned {status}"
+                    )))
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl ObjectStore for WebDavStore {
+    fn get(&self, key: &str) -> PersistResult<Option<Vec<u8>>> {
+        let response = self.send(self.request(Method::GET, self.url(key)?))?;
+        match response.status() {
+            StatusCode::NOT_FOUND => Ok(None),
+            status if status.is_success() => {
+                let body = response
+                    .bytes()
+                    .map_err(|e| PersistError::Remote(e.to_string()))?;
+                Ok(Some(body.to_vec()))
+            }
+            status => Err(PersistError::Remote(format!(
+                "GET '{key}' returned {status}"
+            ))),
+        }
+    }
+
+    fn put(&self, key: &str, data: &[u8]) -> PersistResult<()> {
+        self.create_parents(key)?;
+
+        let partial = self.url(&format!("{key}{PARTIAL}"))?;
+        let request = self
+            .request(Method::PUT, partial.clone())
+            .body(data.to_vec());
+        let response = self.send(request)?;
+        if !response.status().is_success() {
+            return Err(PersistError::Remote(format!(
+                "PUT '{key}' returned {}",
+                response.status()
+            )));
+        }
+
+        let r#move = Method::from_bytes(b"MOVE").expect("MOVE is a valid method");
+        let request = self
+            .request(r#move, partial)
+            .header("Destination", self.url(key)?.as_str())
+            .header("Overwrite", "T");
+        let response = self.send(request)?;
+        match response.status() {
+            status if status.is_success() => Ok(()),
+            status => Err(PersistError::Remote(format!(
+                "MOVE '{key}' returned {status}"
+            ))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::HashMap,
+        io::{BufRead, BufReader, Read, Write},
+        net::{TcpListener, TcpStream},
+        sync::Arc,
+        thread,
+    };
+
+    use super::*;
+
+    type Files = Arc<Mutex<HashMap<String, Vec<u8>>>>;
+
+    /// Serve a WebDAV share keeping its files in memory on a local port,
+    /// returning the url of the share
+    fn serve_share() -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/quelle", listener.local_addr().unwrap());
+        let files = Files::default();
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                let files = files.clone();
+                thread::spawn(move || serve(stream.unwrap(), files));
+            }
+        });
+        url
+    }
+
+    /// Answer the requests of the connection until it is closed
+    fn serve(mut stream: TcpStream, files: Files) {
+        let mut reader = BufReader::new(stream.try_clone().unwrap());
+        loop {
+            let mut line = String::new();
+            if reader.read_line(&mut line).unwrap_or(0) == 0 {
+                return;
+            }
+            let mut parts = line.split_whitespace();
+            let method = parts.next().unwrap().to_string();
+            let path = parts.next().unwrap().to_string();
+
+            let (mut length, mut destination) = (0, None);
+            loop {
+                let mut header = String::new();
+                reader.read_line(&mut header).unwrap();
+                let Some((name, value)) = header.trim_end().split_once(": ") else {
+                    break;
+                };
+                match name.to_ascii_lowercase().as_str() {
+                    "content-length" => length = value.parse().unwrap(),
+                    "destination" => destination = Some(value.to_string()),
+                    _ => {}
+                }
+            }
+            let mut body = vec![0; length];
+            reader.read_exact(&mut body).unwrap();
+
+            let mut files = files.lock().unwrap();
+            let (status, content) = match method.as_str() {
+                "MKCOL" => ("201 Created", vec![]),
+                "PUT" => {
+                    files.insert(path, body);
+                    ("201 Created", vec![])
+                }
+                "MOVE" => {
+                    let destination = Url::parse(&destination.unwrap()).unwrap();
+                    let content = files.remove(&path).unwrap();
+                    files.insert(destination.path().to_string(), content);
+                    ("201 Created", vec![])
+                }
+                _ => match files.get(&path) {
+                    Some(content) => ("200 OK", content.clone()),
+                    None => ("404 Not Found", vec![]),
+                },
+            };
+            drop(files);
+
+            let head = format!(
+                "HTTP/1.1 {status}\r\nContent-Length: {}\r\n\r\n",
+                content.len()
+            );
+            stream.write_all(head.as_bytes()).unwrap();
+            stream.write_all(&content).unwrap();
+        }
+    }
+
+    #[test]
+    fn should_store_objects_on_share() {
+        let config = WebDavConfig {
+            url: serve_share(),
+            username: Some(String::from("user")),
+        };
+        let store = WebDavStore::new(config, Some(String::from("password"))).unwrap();
+
+        assert_eq!(store.get("novels/the novel/novel.json").unwrap(), None);
+        store.put("novels/the novel/novel.json", b"{}").unwrap();
+        store.put("novels/the novel/novel.json", b"[]").unwrap();
+        assert_eq!(
+            store.get("novels/the novel/novel.json").unwrap(),
+            Some(b"[]".to_vec())
+        );
+        assert_eq!(
+            store.get("novels/the novel/novel.json.partial").unwrap(),
+            None
+        );
+    }
+
+    #[test]
+    fn should_join_keys_to_share_url() {
+        let config = WebDavConfig {
+            url: String::from("https://cloud.example.com/dav/files/user/quelle"),
+            username: None,
+        };
+        let store = WebDavStore::new(config, None).unwrap();
+
+        let url = store.url("novels/the novel/novel.json").unwrap();
+        assert_eq!(
+            url.as_str(),
+            "https://cloud.example.com/dav/files/user/quelle/novels/the%20novel/novel.json"
+        );
+    }
+}
@@ -140,6 +140,9 @@ fn collect_volumes(doc: &NodeRef) -> Result<Vec<Volume>, QuelleError> {
                 updated_at: NaiveDate::parse_from_str(parts[2].trim(), "%B %-d, %Y")
                     .map(|d| TaggedDateTime::Local(d.and_time(NaiveTime::default())))
                     .ok(),
+                number: None,
+                part: None,
+                label: None,
             };
 
             volume.chapters.push(chapter);
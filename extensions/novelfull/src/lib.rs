@@ -133,6 +133,9 @@ fn volumes(
                 title: element.get_text(),
                 url: META.convert_into_absolute_url(url, Some(novel_url))?,
                 updated_at: None,
+                number: None,
+                part: None,
+                label: None,
             };
 
             volume.chapters.push(chapter);
@@ -189,11 +189,16 @@ fn extract_toc(doc: &NodeRef, volume: &mut Volume) -> Result<(), QuelleError> {
         let chapter_no = a.as_node().select_first(".chapter-no").get_text()?;
         let chapter_title = a.as_node().select_first(".chapter-title").get_text()?;
 
+        let number = Chapter::parse_number(&chapter_no);
+
         let chapter = Chapter {
             index,
             title: format!("{} {}", chapter_no, chapter_title),
             url: META.convert_into_absolute_url(url, None)?,
             updated_at,
+            number: number.map(|(number, _)| number),
+            part: number.and_then(|(_, part)| part),
+            label: None,
         };
 
         volume.chapters.push(chapter);
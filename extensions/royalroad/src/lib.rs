@@ -22,9 +22,21 @@ define_meta! {
         base_urls: ["https://www.royalroad.com"],
         rds: [Ltr],
         attrs: [],
+        novel_urls: ["https://www.royalroad.com/fiction/*"],
     };
 }
 
+expose_canonicalize!(RoyalRoad);
+impl CanonicalizeUrl for RoyalRoad {
+    fn canonicalize_url(url: String) -> Result<String, QuelleError> {
+        // Chapter urls extend the novel url, ex: /fiction/21220/mother-of-learning/chapter/301778/1-good-morning-brother
+        match url.find("/chapter/") {
+            Some(index) => Ok(url[..index].to_string()),
+            None => Ok(url),
+        }
+    }
+}
+
 expose_basic!(RoyalRoad);
 impl FetchBasic for RoyalRoad {
     fn fetch_novel(url: String) -> Result<Novel, QuelleError> {
@@ -121,6 +133,9 @@ fn parse_chapter_list(nodes: Select<Elements<Descendants>>) -> Result<Vec<Chapte
             title: link.text_contents().clean_text(),
             url: META.convert_into_absolute_url(url, None)?,
             updated_at,
+            number: None,
+            part: None,
+            label: None,
         };
 
         chapters.push(chapter);
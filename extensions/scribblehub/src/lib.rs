@@ -19,6 +19,7 @@ define_meta! {
         base_urls: ["https://www.scribblehub.com"],
         rds: [Ltr],
         attrs: [],
+        novel_urls: ["https://www.scribblehub.com/series/*"],
     };
 }
 
@@ -146,6 +147,9 @@ fn volumes(id: &str) -> Result<Vec<Volume>, QuelleError> {
                 title: a.get_text(),
                 url: href,
                 updated_at,
+                number: None,
+                part: None,
+                label: None,
             };
 
             volume.chapters.push(chapter);